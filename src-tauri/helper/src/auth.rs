@@ -0,0 +1,60 @@
+use std::{fs, path::PathBuf};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::security;
+
+/// Shared secret between an elevated GUI and the helper. Lives under
+/// ProgramData, readable by SYSTEM and Administrators only.
+pub fn secret_path() -> PathBuf {
+    let program_data = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".to_string());
+    PathBuf::from(program_data).join("USB-Shield").join("helper.key")
+}
+
+pub fn load_or_create_secret() -> Result<Vec<u8>, String> {
+    let path = secret_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        // Every start, so files left by an older version lose the inherited
+        // ProgramData ACL that lets all users read them
+        security::restrict(dir, security::DIRECTORY_SDDL)?;
+    }
+    if let Ok(secret) = fs::read(&path) {
+        if secret.len() == 32 {
+            security::restrict(&path, security::SECRET_SDDL)?;
+            return Ok(secret);
+        }
+    }
+    let mut secret = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    fs::write(&path, &secret).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    security::restrict(&path, security::SECRET_SDDL)?;
+    Ok(secret)
+}
+
+pub fn load_secret() -> Result<Vec<u8>, String> {
+    fs::read(secret_path()).map_err(|e| format!("Helper secret unavailable: {}", e))
+}
+
+pub fn new_nonce() -> String {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+pub fn mac(secret: &[u8], nonce: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(nonce.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn verify(secret: &[u8], nonce: &str, response: &str) -> bool {
+    let expected = match hex::decode(response) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(nonce.as_bytes());
+    mac.verify_slice(&expected).is_ok()
+}
@@ -0,0 +1,691 @@
+use std::{collections::HashMap, thread, time::Duration};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::DeviceAndDriverInstallation::{
+            CM_Get_Child, CM_Get_DevNode_Status, CM_Get_Device_IDW, CM_Get_Parent, CM_Get_Sibling, CM_Locate_DevNodeW, CM_Request_Device_EjectW,
+            SetupDiCallClassInstaller, SetupDiCreateDeviceInfoList, SetupDiDestroyDeviceInfoList,
+            SetupDiEnumDeviceInfo, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW, SetupDiGetDeviceInstanceIdW,
+            SetupDiGetDeviceInterfaceDetailW, SetupDiOpenDeviceInfoW, SetupDiSetClassInstallParamsW,
+            CM_LOCATE_DEVNODE_NORMAL, CM_PROB_DISABLED, CR_SUCCESS, DICS_DISABLE,
+            DICS_ENABLE, DICS_FLAG_GLOBAL, DIF_PROPERTYCHANGE, DIGCF_ALLCLASSES, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT,
+            PNP_VETO_TYPE, PNP_VetoTypeUnknown, SP_CLASSINSTALL_HEADER, SP_DEVICE_INTERFACE_DATA,
+            SP_DEVICE_INTERFACE_DETAIL_DATA_W, SP_DEVINFO_DATA, SP_PROPCHANGE_PARAMS,
+        },
+        Foundation::{CloseHandle, GetLastError, HANDLE, HWND},
+        Storage::FileSystem::{
+            CreateFileW, GetLogicalDrives, FILE_FLAGS_AND_ATTRIBUTES, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
+            FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+        },
+        System::{
+            Ioctl::{
+                DISK_ATTRIBUTE_READ_ONLY, FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME, GUID_DEVINTERFACE_DISK,
+                IOCTL_DISK_SET_DISK_ATTRIBUTES, IOCTL_STORAGE_GET_DEVICE_NUMBER, SET_DISK_ATTRIBUTES,
+                STORAGE_DEVICE_NUMBER,
+            },
+            Power::{DevicePowerClose, DevicePowerEnumDevices, DevicePowerOpen, DevicePowerSetDeviceState},
+            IO::DeviceIoControl,
+        },
+    },
+};
+use winreg::{
+    enums::{HKEY_LOCAL_MACHINE, KEY_READ},
+    RegKey,
+};
+
+use crate::protocol::{DevnodeState, Failure, HelperState, RemovableStoragePolicy, WakeState};
+use crate::registry::{self, Root};
+
+const USBSTOR_KEY: &str = r"SYSTEM\CurrentControlSet\Services\USBSTOR";
+const ENUM_KEY: &str = r"SYSTEM\CurrentControlSet\Enum";
+const STORAGE_POLICIES_KEY: &str = r"SYSTEM\CurrentControlSet\Control\StorageDevicePolicies";
+const REMOVABLE_STORAGE_POLICIES_KEY: &str = r"SOFTWARE\Policies\Microsoft\Windows\RemovableStorageDevices";
+
+// powrprof.h query flags, as used by `powercfg -devicequery`
+const DEVICEPOWER_FILTER_DEVICES_PRESENT: u32 = 0x2000_0000;
+const DEVICEPOWER_FILTER_HARDWARE: u32 = 0x1000_0000;
+const DEVICEPOWER_FILTER_WAKEENABLED: u32 = 0x0800_0000;
+const DEVICEPOWER_FILTER_WAKEPROGRAMMABLE: u32 = 0x0400_0000;
+const DEVICEPOWER_SETWAKEENABLED: u32 = 0x1;
+const DEVICEPOWER_CLEARWAKEENABLED: u32 = 0x2;
+// PDCAP_WAKE_FROM_S0_SUPPORTED through PDCAP_WAKE_FROM_S3_SUPPORTED
+const PDCAP_WAKE_FROM_SLEEP: u32 = 0x00F0_0000;
+
+const EJECT_LOCK_ATTEMPTS: u32 = 5;
+const EJECT_LOCK_RETRY: Duration = Duration::from_millis(200);
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Write the USBSTOR service `Start` value. Only 3 (demand start) and
+/// 4 (disabled) are accepted; anything else would be a misuse of the helper.
+pub fn apply_policy(usbstor_start: u32) -> Result<(), Failure> {
+    if usbstor_start != 3 && usbstor_start != 4 {
+        return Err(format!("Refusing USBSTOR Start value {}", usbstor_start).into());
+    }
+    // Open, not create: a missing USBSTOR service is not ours to invent
+    let key = registry::open(Root::LocalMachine, USBSTOR_KEY)?
+        .ok_or_else(|| format!(r"HKLM\{} does not exist", USBSTOR_KEY))?;
+    Ok(key.set_dword("Start", usbstor_start)?)
+}
+
+/// Set the machine-wide storage write protection. It applies to volumes as
+/// they mount; disks already mounted keep their current state.
+pub fn set_storage_write_protect(enabled: bool) -> Result<(), Failure> {
+    Ok(registry::create(Root::LocalMachine, STORAGE_POLICIES_KEY)?.set_dword("WriteProtect", enabled as u32)?)
+}
+
+/// Whether the machine-wide storage write protection is set. Reading HKLM
+/// needs no elevation.
+pub fn storage_write_protect() -> Result<bool, String> {
+    let protect = match registry::open(Root::LocalMachine, STORAGE_POLICIES_KEY)? {
+        Some(key) => key.dword("WriteProtect")?,
+        None => None,
+    };
+    Ok(protect.is_some_and(|value| value != 0))
+}
+
+/// Turn selective suspend on or off for one present USB device. Anything
+/// that is not a plain `USB\<ids>\<instance>` path is refused, so the
+/// request cannot be pointed at other parts of the Enum tree.
+pub fn set_selective_suspend(instance_id: &str, enabled: bool) -> Result<(), String> {
+    let parts: Vec<&str> = instance_id.split('\\').collect();
+    if parts.len() != 3 || !parts[0].eq_ignore_ascii_case("USB") || parts.iter().any(|p| p.is_empty() || *p == "..") {
+        return Err(format!("Refusing to change power settings of {}", instance_id));
+    }
+    if !devnode_status(instance_id).0 {
+        return Err(format!("Device not present: {}", instance_id));
+    }
+    let key = registry::create(Root::LocalMachine, &format!(r"{}\{}\Device Parameters", ENUM_KEY, instance_id))?;
+    key.set_dword("SelectiveSuspendEnabled", enabled as u32)?;
+    Ok(key.set_dword("EnhancedPowerManagementEnabled", enabled as u32)?)
+}
+
+/// Names of present devices matching `filter`, one DevicePowerEnumDevices
+/// pass. The caller holds the DevicePowerOpen session.
+unsafe fn device_power_names(filter: u32) -> Vec<String> {
+    let mut names = Vec::new();
+    for index in 0.. {
+        let mut buffer = [0u16; 512];
+        let mut size = (buffer.len() * 2) as u32;
+        if !DevicePowerEnumDevices(
+            index,
+            DEVICEPOWER_FILTER_DEVICES_PRESENT | DEVICEPOWER_FILTER_HARDWARE | filter,
+            PDCAP_WAKE_FROM_SLEEP,
+            Some(buffer.as_mut_ptr() as *mut u8),
+            &mut size,
+        )
+        .as_bool()
+        {
+            break;
+        }
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        names.push(String::from_utf16_lossy(&buffer[..len]));
+    }
+    names
+}
+
+/// Every present device that can be programmed to wake the machine, and
+/// whether it currently is. Needs no elevation; the UI calls it directly.
+pub fn wake_devices() -> Result<Vec<WakeState>, String> {
+    unsafe {
+        if !DevicePowerOpen(0).as_bool() {
+            return Err("Failed to open the device power database".to_string());
+        }
+        let programmable = device_power_names(DEVICEPOWER_FILTER_WAKEPROGRAMMABLE);
+        let armed = device_power_names(DEVICEPOWER_FILTER_WAKEENABLED);
+        DevicePowerClose();
+        Ok(programmable
+            .into_iter()
+            .map(|name| WakeState {
+                armed: armed.contains(&name),
+                name,
+            })
+            .collect())
+    }
+}
+
+/// Arm or disarm wake for a device named by `wake_devices`. Other names are
+/// refused rather than passed on.
+pub fn set_wake_enabled(name: &str, enabled: bool) -> Result<(), String> {
+    if !wake_devices()?.iter().any(|device| device.name == name) {
+        return Err(format!("{} is not a wake-programmable device", name));
+    }
+    let name_wide = wide(name);
+    let flag = if enabled {
+        DEVICEPOWER_SETWAKEENABLED
+    } else {
+        DEVICEPOWER_CLEARWAKEENABLED
+    };
+    let status = unsafe {
+        if !DevicePowerOpen(0).as_bool() {
+            return Err("Failed to open the device power database".to_string());
+        }
+        let status = DevicePowerSetDeviceState(PCWSTR(name_wide.as_ptr()), flag, None);
+        DevicePowerClose();
+        status
+    };
+    if status != 0 {
+        return Err(format!("Failed to change wake setting of {} (error {})", name, status));
+    }
+    Ok(())
+}
+
+// `{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}`, nothing that could name another key
+fn is_class_guid(guid: &str) -> bool {
+    let inner = match guid.strip_prefix('{').and_then(|g| g.strip_suffix('}')) {
+        Some(inner) => inner,
+        None => return false,
+    };
+    let groups: Vec<&str> = inner.split('-').collect();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+        && groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Write the machine-wide removable storage policy of one device class.
+/// Denials that are lifted are deleted rather than set to 0, so the key
+/// looks as if Group Policy never touched them; a class with nothing
+/// denied loses its key.
+pub fn set_removable_storage_policy(policy: &RemovableStoragePolicy) -> Result<(), Failure> {
+    if !is_class_guid(&policy.class_guid) {
+        return Err(format!("Refusing removable storage policy for {}", policy.class_guid).into());
+    }
+    let path = format!(r"{}\{}", REMOVABLE_STORAGE_POLICIES_KEY, policy.class_guid);
+    if !policy.deny_read && !policy.deny_write && !policy.deny_execute {
+        return Ok(registry::delete_tree(Root::LocalMachine, &path)?);
+    }
+    let key = registry::create(Root::LocalMachine, &path)?;
+    for (name, deny) in [
+        ("Deny_Read", policy.deny_read),
+        ("Deny_Write", policy.deny_write),
+        ("Deny_Execute", policy.deny_execute),
+    ] {
+        if deny {
+            key.set_dword(name, 1)?;
+        } else {
+            key.delete_value(name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every device class with a machine-wide removable storage policy, however
+/// it was written. Needs no elevation; the UI calls it directly.
+pub fn removable_storage_policies() -> Result<Vec<RemovableStoragePolicy>, String> {
+    let root = match RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(REMOVABLE_STORAGE_POLICIES_KEY, KEY_READ) {
+        Ok(root) => root,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Registry access failed: {}", e)),
+    };
+    let mut policies = Vec::new();
+    for class_guid in root.enum_keys().filter_map(Result::ok) {
+        let key = match root.open_subkey_with_flags(&class_guid, KEY_READ) {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        let denied = |name: &str| key.get_value::<u32, _>(name).is_ok_and(|value| value != 0);
+        policies.push(RemovableStoragePolicy {
+            deny_read: denied("Deny_Read"),
+            deny_write: denied("Deny_Write"),
+            deny_execute: denied("Deny_Execute"),
+            class_guid,
+        });
+    }
+    Ok(policies)
+}
+
+pub fn read_state(instance_ids: &[String]) -> HelperState {
+    let usbstor_start = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(USBSTOR_KEY, KEY_READ)
+        .and_then(|key| key.get_value::<u32, _>("Start"))
+        .ok();
+
+    let devices = instance_ids
+        .iter()
+        .map(|instance_id| {
+            let (present, disabled) = devnode_status(instance_id);
+            DevnodeState {
+                instance_id: instance_id.clone(),
+                present,
+                disabled,
+            }
+        })
+        .collect();
+
+    HelperState { usbstor_start, devices }
+}
+
+fn devnode_status(instance_id: &str) -> (bool, bool) {
+    let instance_id_wide = wide(instance_id);
+    unsafe {
+        let mut dev_inst = 0u32;
+        if CM_Locate_DevNodeW(&mut dev_inst, PCWSTR(instance_id_wide.as_ptr()), CM_LOCATE_DEVNODE_NORMAL) != CR_SUCCESS {
+            return (false, false);
+        }
+        let mut status = 0u32;
+        let mut problem = 0u32;
+        if CM_Get_DevNode_Status(&mut status, &mut problem, dev_inst, 0) != CR_SUCCESS {
+            return (true, false);
+        }
+        (true, problem == CM_PROB_DISABLED)
+    }
+}
+
+/// Instance IDs of every present devnode under the USB enumerator, root
+/// hubs and interface children included.
+pub fn present_usb_devices() -> Result<Vec<String>, String> {
+    let enumerator = wide("USB");
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            None,
+            PCWSTR(enumerator.as_ptr()),
+            HWND(0),
+            DIGCF_PRESENT | DIGCF_ALLCLASSES,
+        )
+        .map_err(|e| format!("Failed to get device information set: {}", e))?;
+
+        let mut instance_ids = Vec::new();
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        for index in 0.. {
+            if !SetupDiEnumDeviceInfo(device_info_set, index, &mut device_info_data).as_bool() {
+                break;
+            }
+            let mut buffer = [0u16; 512];
+            if SetupDiGetDeviceInstanceIdW(device_info_set, &device_info_data, Some(&mut buffer), None).as_bool() {
+                let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+                instance_ids.push(String::from_utf16_lossy(&buffer[..len]));
+            }
+        }
+
+        SetupDiDestroyDeviceInfoList(device_info_set);
+        Ok(instance_ids)
+    }
+}
+
+// `USB\Class_03&SubClass_01&Prot_01` and `USB\DevClass_00&...` -> the class
+fn compatible_classes(instance_id: &str) -> Vec<u8> {
+    let ids: Vec<String> = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(format!(r"{}\{}", ENUM_KEY, instance_id), KEY_READ)
+        .and_then(|key| key.get_value("CompatibleIDs"))
+        .unwrap_or_default();
+    ids.iter()
+        .filter_map(|id| {
+            let upper = id.to_ascii_uppercase();
+            let rest = upper.strip_prefix("USB\\CLASS_").or_else(|| upper.strip_prefix("USB\\DEVCLASS_"))?;
+            u8::from_str_radix(rest.get(..2)?, 16).ok()
+        })
+        .collect()
+}
+
+/// Base classes a USB device declares: its own device class unless that is
+/// 0 (defined per interface), plus the class of every interface child, the
+/// way the GUI's class policy counts them. Empty while the children have not
+/// enumerated yet.
+pub fn usb_classes(instance_id: &str) -> Vec<u8> {
+    let mut classes: Vec<u8> = compatible_classes(instance_id).into_iter().filter(|class| *class != 0).collect();
+    let instance_id_wide = wide(instance_id);
+    unsafe {
+        let mut dev_inst = 0u32;
+        if CM_Locate_DevNodeW(&mut dev_inst, PCWSTR(instance_id_wide.as_ptr()), CM_LOCATE_DEVNODE_NORMAL) != CR_SUCCESS {
+            return classes;
+        }
+        let mut child = 0u32;
+        if CM_Get_Child(&mut child, dev_inst, 0) != CR_SUCCESS {
+            return classes;
+        }
+        loop {
+            let mut buffer = [0u16; 512];
+            if CM_Get_Device_IDW(child, &mut buffer, 0) == CR_SUCCESS {
+                let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+                let child_id = String::from_utf16_lossy(&buffer[..len]);
+                if child_id.to_ascii_uppercase().contains("&MI_") {
+                    classes.extend(compatible_classes(&child_id));
+                }
+            }
+            let mut sibling = 0u32;
+            if CM_Get_Sibling(&mut sibling, child, 0) != CR_SUCCESS {
+                break;
+            }
+            child = sibling;
+        }
+    }
+    classes.sort_unstable();
+    classes.dedup();
+    classes
+}
+
+/// Enable or disable exactly one devnode, addressed by its device instance ID.
+pub fn set_device_state(instance_id: &str, enable: bool) -> Result<(), Failure> {
+    unsafe {
+        let instance_id_wide = wide(instance_id);
+
+        let device_info_set = match SetupDiCreateDeviceInfoList(None, HWND(0)) {
+            Ok(set) => set,
+            Err(e) => return Err(format!("Failed to create device information set: {}", e).into()),
+        };
+
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+
+        // Open the devnode directly by instance ID - no substring matching
+        let result = if !SetupDiOpenDeviceInfoW(
+            device_info_set,
+            PCWSTR(instance_id_wide.as_ptr()),
+            HWND(0),
+            0,
+            Some(&mut device_info_data),
+        )
+        .as_bool()
+        {
+            Err(Failure::device_not_found(instance_id))
+        } else {
+            let propchange_params = SP_PROPCHANGE_PARAMS {
+                ClassInstallHeader: SP_CLASSINSTALL_HEADER {
+                    cbSize: std::mem::size_of::<SP_CLASSINSTALL_HEADER>() as u32,
+                    InstallFunction: DIF_PROPERTYCHANGE,
+                },
+                StateChange: if enable { DICS_ENABLE } else { DICS_DISABLE },
+                Scope: DICS_FLAG_GLOBAL,
+                HwProfile: 0,
+            };
+
+            // Set class installer parameters
+            if !SetupDiSetClassInstallParamsW(
+                device_info_set,
+                Some(&device_info_data),
+                Some(&propchange_params.ClassInstallHeader),
+                std::mem::size_of::<SP_PROPCHANGE_PARAMS>() as u32,
+            )
+            .as_bool()
+            {
+                Err(Failure::win32("Failed to set class install params", GetLastError().0))
+            } else if !SetupDiCallClassInstaller(
+                DIF_PROPERTYCHANGE,
+                device_info_set,
+                Some(&device_info_data),
+            )
+            .as_bool()
+            {
+                Err(Failure::win32("Failed to call class installer", GetLastError().0))
+            } else {
+                Ok(())
+            }
+        };
+
+        // Cleanup
+        SetupDiDestroyDeviceInfoList(device_info_set);
+        result
+    }
+}
+
+// `E:` -> `E`; never an arbitrary device path
+fn drive_letter(mount_point: &str) -> Result<char, String> {
+    match mount_point.as_bytes() {
+        [letter, b':'] if letter.is_ascii_alphabetic() => Ok((*letter as char).to_ascii_uppercase()),
+        _ => Err(format!("Refusing mount point {}", mount_point)),
+    }
+}
+
+// IOCTL_STORAGE_GET_DEVICE_NUMBER on a disk or volume path
+unsafe fn device_number(path: &str) -> Option<u32> {
+    let handle = open(path, 0).ok()?;
+    let mut number = STORAGE_DEVICE_NUMBER::default();
+    let ok = ioctl(handle, IOCTL_STORAGE_GET_DEVICE_NUMBER, None::<&()>, Some(&mut number));
+    CloseHandle(handle);
+    ok.then_some(number.DeviceNumber)
+}
+
+// The USB device (not one of its interfaces) a function devnode sits on
+unsafe fn usb_device_ancestor(dev_inst: u32) -> Option<String> {
+    let mut current = dev_inst;
+    for _ in 0..16 {
+        let mut parent = 0u32;
+        if CM_Get_Parent(&mut parent, current, 0) != CR_SUCCESS {
+            return None;
+        }
+        let mut buffer = [0u16; 512];
+        if CM_Get_Device_IDW(parent, &mut buffer, 0) != CR_SUCCESS {
+            return None;
+        }
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        let id = String::from_utf16_lossy(&buffer[..len]);
+        let upper = id.to_ascii_uppercase();
+        if upper.starts_with("USB\\") && !upper.contains("&MI_") {
+            return Some(id);
+        }
+        current = parent;
+    }
+    None
+}
+
+/// Disk device number -> instance ID of the USB device the disk sits on.
+/// Internal disks are left out.
+fn usb_disks() -> Result<HashMap<u32, String>, String> {
+    let mut disks = HashMap::new();
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            Some(&GUID_DEVINTERFACE_DISK),
+            None,
+            HWND(0),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )
+        .map_err(|e| format!("Failed to enumerate disks: {}", e))?;
+
+        let mut interface = SP_DEVICE_INTERFACE_DATA {
+            cbSize: std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32,
+            ..Default::default()
+        };
+        for index in 0.. {
+            if !SetupDiEnumDeviceInterfaces(device_info_set, None, &GUID_DEVINTERFACE_DISK, index, &mut interface)
+                .as_bool()
+            {
+                break;
+            }
+            let mut required = 0u32;
+            SetupDiGetDeviceInterfaceDetailW(device_info_set, &interface, None, 0, Some(&mut required), None);
+            if required == 0 {
+                continue;
+            }
+            // u32 elements keep the detail struct aligned
+            let mut buffer = vec![0u32; (required as usize).div_ceil(4)];
+            let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+            (*detail).cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+            let mut device_info_data = SP_DEVINFO_DATA {
+                cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+                ..Default::default()
+            };
+            if !SetupDiGetDeviceInterfaceDetailW(
+                device_info_set,
+                &interface,
+                Some(detail),
+                required,
+                None,
+                Some(&mut device_info_data),
+            )
+            .as_bool()
+            {
+                continue;
+            }
+            let path = PCWSTR((*detail).DevicePath.as_ptr()).to_string().unwrap_or_default();
+            if let (Some(usb_device), Some(number)) =
+                (usb_device_ancestor(device_info_data.DevInst), device_number(&path))
+            {
+                disks.insert(number, usb_device);
+            }
+        }
+        SetupDiDestroyDeviceInfoList(device_info_set);
+    }
+    Ok(disks)
+}
+
+// The USB device behind a drive letter; `None` for internal disks, network
+// shares and volumes spanning several disks
+fn usb_device_of(letter: char, disks: &HashMap<u32, String>) -> Option<String> {
+    unsafe { device_number(&format!("\\\\.\\{}:", letter)) }.and_then(|number| disks.get(&number).cloned())
+}
+
+/// Drive letters of the volumes on one USB device, worked out here rather
+/// than taken from the client.
+pub fn usb_volumes(instance_id: &str) -> Result<Vec<String>, String> {
+    let disks = usb_disks()?;
+    let mask = unsafe { GetLogicalDrives() };
+    Ok((0..26u8)
+        .filter(|index| mask & (1 << index) != 0)
+        .map(|index| (b'A' + index) as char)
+        .filter(|letter| usb_device_of(*letter, &disks).is_some_and(|id| id.eq_ignore_ascii_case(instance_id)))
+        .map(|letter| format!("{}:", letter))
+        .collect())
+}
+
+/// Write-protect (or release) the disk behind a drive letter. The attribute
+/// is not persisted, so it is gone once the stick is unplugged. Only disks
+/// on USB are touched; fixed internal volumes are refused.
+pub fn set_volume_read_only(mount_point: &str, read_only: bool) -> Result<(), String> {
+    let letter = drive_letter(mount_point)?;
+    if usb_device_of(letter, &usb_disks()?).is_none() {
+        return Err(format!("Refusing {}: it is not on a USB device", mount_point));
+    }
+
+    unsafe {
+        let number = device_number(&format!("\\\\.\\{}:", letter))
+            .ok_or_else(|| format!("Failed to resolve the disk behind {}", mount_point))?;
+        let disk = open(
+            &format!("\\\\.\\PhysicalDrive{}", number),
+            (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+        )?;
+        let attributes = SET_DISK_ATTRIBUTES {
+            Version: std::mem::size_of::<SET_DISK_ATTRIBUTES>() as u32,
+            Persist: false.into(),
+            Attributes: if read_only { DISK_ATTRIBUTE_READ_ONLY } else { 0 },
+            AttributesMask: DISK_ATTRIBUTE_READ_ONLY,
+            ..Default::default()
+        };
+        let ok = ioctl(disk, IOCTL_DISK_SET_DISK_ATTRIBUTES, Some(&attributes), None::<&mut ()>);
+        CloseHandle(disk);
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Failed to change the read-only attribute of {}", mount_point))
+        }
+    }
+}
+
+/// Safely remove one present USB device, as "Safely Remove Hardware" does:
+/// lock and dismount its volumes, then have PnP eject it. The volumes are
+/// looked up here; `mount_points` from the client must all be among them.
+/// A volume with open files fails the removal unless `force` is set, in
+/// which case it is dismounted from under them.
+pub fn eject_device(instance_id: &str, mount_points: &[String], force: bool) -> Result<(), Failure> {
+    // Same shape check as for selective suspend: a USB device, nothing else
+    let parts: Vec<&str> = instance_id.split('\\').collect();
+    if parts.len() != 3 || !parts[0].eq_ignore_ascii_case("USB") || parts.iter().any(|p| p.is_empty() || *p == "..") {
+        return Err(format!("Refusing to eject {}", instance_id).into());
+    }
+    let owned = usb_volumes(instance_id)?;
+    for mount_point in mount_points {
+        let letter = drive_letter(mount_point)?;
+        if !owned.iter().any(|volume| volume.starts_with(letter)) {
+            return Err(format!("Refusing mount point {}: it is not on {}", mount_point, instance_id).into());
+        }
+    }
+
+    // Held, locked, until the device is gone, so nothing remounts meanwhile
+    let mut volumes = Vec::new();
+    let result = unsafe { dismount_and_eject(instance_id, &owned, force, &mut volumes) };
+    for volume in volumes {
+        unsafe {
+            CloseHandle(volume);
+        }
+    }
+    result
+}
+
+unsafe fn dismount_and_eject(
+    instance_id: &str,
+    mount_points: &[String],
+    force: bool,
+    volumes: &mut Vec<HANDLE>,
+) -> Result<(), Failure> {
+    for mount_point in mount_points {
+        let letter = drive_letter(mount_point)?;
+        let volume = open(&format!("\\\\.\\{}:", letter), (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0)?;
+        volumes.push(volume);
+
+        // Explorer retries too: the lock fails while a write is being flushed
+        let mut locked = false;
+        for attempt in 0..EJECT_LOCK_ATTEMPTS {
+            if attempt > 0 {
+                thread::sleep(EJECT_LOCK_RETRY);
+            }
+            if ioctl(volume, FSCTL_LOCK_VOLUME, None::<&()>, None::<&mut ()>) {
+                locked = true;
+                break;
+            }
+        }
+        if !locked && !force {
+            return Err(Failure::win32(&format!("{} is in use", mount_point), GetLastError().0));
+        }
+        if !ioctl(volume, FSCTL_DISMOUNT_VOLUME, None::<&()>, None::<&mut ()>) {
+            return Err(Failure::win32(&format!("Failed to dismount {}", mount_point), GetLastError().0));
+        }
+    }
+
+    let instance_id_wide = wide(instance_id);
+    let mut dev_inst = 0u32;
+    if CM_Locate_DevNodeW(&mut dev_inst, PCWSTR(instance_id_wide.as_ptr()), CM_LOCATE_DEVNODE_NORMAL) != CR_SUCCESS {
+        return Err(Failure::device_not_found(instance_id));
+    }
+    // With a buffer for the veto, Windows reports it instead of showing a dialog
+    let mut veto_type = PNP_VETO_TYPE::default();
+    let mut veto_name = [0u16; 512];
+    let status = CM_Request_Device_EjectW(dev_inst, Some(&mut veto_type as *mut _), Some(&mut veto_name), 0);
+    if status != CR_SUCCESS || veto_type != PNP_VetoTypeUnknown {
+        let len = veto_name.iter().position(|&c| c == 0).unwrap_or(veto_name.len());
+        return Err(format!(
+            "Ejecting {} was vetoed by {} (veto type {}, CONFIGRET {})",
+            instance_id,
+            String::from_utf16_lossy(&veto_name[..len]),
+            veto_type.0,
+            status.0
+        )
+        .into());
+    }
+    Ok(())
+}
+
+unsafe fn open(path: &str, access: u32) -> Result<HANDLE, String> {
+    let path_wide = wide(path);
+    CreateFileW(
+        PCWSTR(path_wide.as_ptr()),
+        access,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        None,
+        OPEN_EXISTING,
+        FILE_FLAGS_AND_ATTRIBUTES(0),
+        None,
+    )
+    .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+unsafe fn ioctl<I, O>(handle: HANDLE, code: u32, input: Option<&I>, output: Option<&mut O>) -> bool {
+    let input_size = input.map_or(0, |_| std::mem::size_of::<I>() as u32);
+    let output_size = output.as_ref().map_or(0, |_| std::mem::size_of::<O>() as u32);
+    let mut returned = 0u32;
+    DeviceIoControl(
+        handle,
+        code,
+        input.map(|i| i as *const I as *const std::ffi::c_void),
+        input_size,
+        output.map(|o| o as *mut O as *mut std::ffi::c_void),
+        output_size,
+        Some(&mut returned),
+        None,
+    )
+    .as_bool()
+}
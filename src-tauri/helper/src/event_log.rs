@@ -0,0 +1,52 @@
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::PSID,
+        System::EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_WARNING_TYPE,
+            REPORT_EVENT_TYPE,
+        },
+    },
+};
+
+// The GUI's source, which it registers with a message table that formats
+// IDs 1 to 1000 as the insertion string. Nothing is printed when the helper
+// runs as a service, so this is where its failures go.
+const SOURCE: &str = "USB-Shield";
+
+/// Event IDs the helper writes, alongside the GUI's 1xx range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelperEvent {
+    /// The guard disabled an arrival while no GUI was running
+    GuardBlocked = 200,
+    GuardFailed = 201,
+    /// The service or its pipe server stopped working
+    ServiceFailed = 202,
+    /// One client connection failed, e.g. it did not authenticate
+    ClientFailed = 203,
+}
+
+impl HelperEvent {
+    fn event_type(self) -> REPORT_EVENT_TYPE {
+        match self {
+            HelperEvent::GuardBlocked | HelperEvent::ClientFailed => EVENTLOG_WARNING_TYPE,
+            HelperEvent::GuardFailed | HelperEvent::ServiceFailed => EVENTLOG_ERROR_TYPE,
+        }
+    }
+}
+
+/// Write `message` to the Application log. Failing to log is not reported
+/// anywhere; there is nowhere left to report it.
+pub fn write(event: HelperEvent, message: &str) {
+    let source: Vec<u16> = SOURCE.encode_utf16().chain(Some(0)).collect();
+    let text: Vec<u16> = message.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        let handle = match RegisterEventSourceW(PCWSTR::null(), PCWSTR(source.as_ptr())) {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+        let strings = [PCWSTR(text.as_ptr())];
+        ReportEventW(handle, event.event_type(), 0, event as u32, PSID::default(), 0, Some(&strings), None);
+        DeregisterEventSource(handle);
+    }
+}
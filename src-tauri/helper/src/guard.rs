@@ -0,0 +1,185 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::auth;
+use crate::enforcement;
+use crate::event_log::{self, HelperEvent};
+use crate::protocol::{GuardBlock, GuardPolicy, GuardStatus};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// How long after its last sync the GUI counts as gone
+const GUI_GRACE: Duration = Duration::from_secs(30);
+
+struct GuardState {
+    policy: GuardPolicy,
+    last_sync: Option<Instant>,
+    blocked: Vec<GuardBlock>,
+}
+
+static STATE: Mutex<GuardState> = Mutex::new(GuardState {
+    policy: GuardPolicy {
+        autoblock: false,
+        trusted: Vec::new(),
+        bindings: Vec::new(),
+        allowed_classes: Vec::new(),
+    },
+    last_sync: None,
+    blocked: Vec::new(),
+});
+
+/// Next to the pipe secret, so it is readable before anyone logs in. It
+/// inherits the directory's SYSTEM and Administrators only ACL.
+fn policy_path() -> PathBuf {
+    auth::secret_path().with_file_name("guard-policy.json")
+}
+
+fn save_policy(policy: &GuardPolicy) -> Result<(), String> {
+    let path = policy_path();
+    let data = serde_json::to_vec_pretty(policy).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// `USB\VID_046D&PID_C31C\...`; interface children and root hubs yield None
+fn parse_ids(instance_id: &str) -> Option<(u16, u16)> {
+    let upper = instance_id.to_ascii_uppercase();
+    if upper.contains("&MI_") {
+        return None;
+    }
+    let vid = upper.split("VID_").nth(1)?.get(..4)?;
+    let pid = upper.split("PID_").nth(1)?.get(..4)?;
+    Some((u16::from_str_radix(vid, 16).ok()?, u16::from_str_radix(pid, 16).ok()?))
+}
+
+// The serial is the last part of the instance ID, unless Windows made one up
+fn serial_of(instance_id: &str) -> Option<&str> {
+    instance_id.rsplit('\\').next().filter(|serial| !serial.contains('&'))
+}
+
+/// Whether policy lets the device through: a trusted model (the right unit,
+/// if the model is pinned) or a device made only of allowed classes.
+fn admitted(policy: &GuardPolicy, instance_id: &str, ids: (u16, u16)) -> bool {
+    let mut bindings = policy
+        .bindings
+        .iter()
+        .filter(|binding| (binding.vendor_id, binding.product_id) == ids)
+        .peekable();
+    let unit_ok = bindings.peek().is_none()
+        || bindings.any(|binding| match (&binding.serial, serial_of(instance_id)) {
+            (None, _) => true,
+            (Some(bound), Some(actual)) => bound.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+        });
+    if policy.trusted.contains(&ids) && unit_ok {
+        return true;
+    }
+    let classes = enforcement::usb_classes(instance_id);
+    !classes.is_empty() && classes.iter().all(|class| policy.allowed_classes.contains(class))
+}
+
+fn active(state: &GuardState) -> bool {
+    state.policy.autoblock && state.last_sync.is_none_or(|at| at.elapsed() > GUI_GRACE)
+}
+
+/// Whether `policy` lets through something the current one blocks.
+pub fn relaxes(policy: &GuardPolicy) -> bool {
+    let current = &STATE.lock().unwrap().policy;
+    (current.autoblock && !policy.autoblock)
+        || policy.trusted.iter().any(|model| !current.trusted.contains(model))
+        || policy.allowed_classes.iter().any(|class| !current.allowed_classes.contains(class))
+        // A model that loses its pins is trusted in every unit, and one more
+        // pin admits one more unit
+        || current.bindings.iter().any(|binding| !policy.bindings.contains(binding))
+        || policy.bindings.iter().any(|binding| {
+            !current.bindings.contains(binding)
+                && current
+                    .bindings
+                    .iter()
+                    .any(|had| had.vendor_id == binding.vendor_id && had.product_id == binding.product_id)
+        })
+}
+
+/// Take the GUI's current policy and hand back what was blocked since the
+/// previous sync.
+pub fn sync(policy: GuardPolicy) -> GuardStatus {
+    let mut state = STATE.lock().unwrap();
+    if state.policy != policy {
+        if let Err(e) = save_policy(&policy) {
+            event_log::write(HelperEvent::GuardFailed, &format!("Failed to save the guard policy: {}", e));
+        }
+        state.policy = policy;
+    }
+    state.last_sync = Some(Instant::now());
+    GuardStatus {
+        policy: state.policy.clone(),
+        active: active(&state),
+        blocked: std::mem::take(&mut state.blocked),
+    }
+}
+
+/// Watch for arrivals and block untrusted ones whenever the GUI is not
+/// there to do it. Devices already present when the service starts are left
+/// alone, so a keyboard needed to log in is never cut off at boot.
+pub fn start() {
+    if let Some(policy) = fs::read(policy_path())
+        .ok()
+        .and_then(|data| serde_json::from_slice::<GuardPolicy>(&data).ok())
+    {
+        STATE.lock().unwrap().policy = policy;
+    }
+
+    thread::spawn(|| {
+        let mut known: HashSet<String> = enforcement::present_usb_devices()
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let present: HashSet<String> = match enforcement::present_usb_devices() {
+                Ok(present) => present.into_iter().collect(),
+                Err(e) => {
+                    event_log::write(HelperEvent::GuardFailed, &format!("Failed to list USB devices: {}", e));
+                    continue;
+                }
+            };
+            let (enforcing, policy) = {
+                let state = STATE.lock().unwrap();
+                (active(&state), state.policy.clone())
+            };
+            if enforcing {
+                for instance_id in present.difference(&known) {
+                    let (vendor_id, product_id) = match parse_ids(instance_id) {
+                        Some(ids) if !admitted(&policy, instance_id, ids) => ids,
+                        _ => continue,
+                    };
+                    match enforcement::set_device_state(instance_id, false) {
+                        Ok(()) => {
+                            event_log::write(
+                                HelperEvent::GuardBlocked,
+                                &format!("Blocked {} while USB-Shield was not running", instance_id),
+                            );
+                            STATE.lock().unwrap().blocked.push(GuardBlock {
+                                instance_id: instance_id.clone(),
+                                vendor_id,
+                                product_id,
+                                blocked_at: SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .map_or(0, |since| since.as_secs()),
+                            })
+                        }
+                        Err(e) => event_log::write(
+                            HelperEvent::GuardFailed,
+                            &format!("Failed to block {}: {}", instance_id, e),
+                        ),
+                    }
+                }
+            }
+            known = present;
+        }
+    });
+}
@@ -0,0 +1,16 @@
+//! The only code that needs to run elevated: changing devnode state, writing
+//! the enforced registry values and reading them back. The GUI talks to it
+//! over an authenticated named pipe (see `protocol` and `security`); anything else lives in
+//! the UI crate. Run as a service, `guard` keeps autoblocking while no GUI
+//! is running and reports to the event log (`event_log`). `registry` is
+//! shared: the UI crate's own writes go through it too.
+
+pub mod auth;
+pub mod enforcement;
+pub mod event_log;
+pub mod guard;
+pub mod protocol;
+pub mod registry;
+pub mod security;
+pub mod server;
+pub mod service;
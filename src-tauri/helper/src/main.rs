@@ -0,0 +1,20 @@
+// Elevated enforcement helper. Installed as a Windows service it keeps
+// enforcing while the GUI is closed and before login; it can also be run
+// from an elevated prompt. An elevated GUI falls back to in-process
+// enforcement when it is not running.
+
+fn main() {
+    let result = match std::env::args().nth(1).as_deref() {
+        Some("--service") => uport_shield_helper::service::run(),
+        Some("install") => uport_shield_helper::service::install(),
+        Some("uninstall") => uport_shield_helper::service::uninstall(),
+        _ => {
+            uport_shield_helper::guard::start();
+            uport_shield_helper::server::serve()
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("uport-shield-helper: {}", e);
+        std::process::exit(1);
+    }
+}
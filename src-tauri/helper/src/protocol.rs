@@ -0,0 +1,246 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\uport-shield-helper";
+
+// Anything larger is a protocol violation, not a real request
+const MAX_FRAME: usize = 1024 * 1024;
+
+/// The complete helper API. Keep it this small.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    /// Write the USBSTOR `Start` value (3 = enabled, 4 = disabled)
+    ApplyPolicy { usbstor_start: u32 },
+    SetDeviceState { instance_id: String, enable: bool },
+    ReadState { instance_ids: Vec<String> },
+    /// Set or clear the (non-persistent) read-only attribute of the disk
+    /// behind a drive letter such as `"E:"`; refused unless the disk is on USB
+    SetVolumeReadOnly { mount_point: String, read_only: bool },
+    /// Write `StorageDevicePolicies\WriteProtect`, which makes every storage
+    /// volume mounted from then on read-only
+    SetStorageWriteProtect { enabled: bool },
+    /// Write `SelectiveSuspendEnabled` and `EnhancedPowerManagementEnabled`
+    /// under a USB device's `Device Parameters` key
+    SetSelectiveSuspend { instance_id: String, enabled: bool },
+    /// Arm or disarm wake for a device, addressed by its power manager name
+    /// as `powercfg -deviceenablewake` does
+    SetWakeEnabled { name: String, enabled: bool },
+    /// Write the machine-wide `Deny_Read`, `Deny_Write` and `Deny_Execute`
+    /// values under `Policies\Microsoft\Windows\RemovableStorageDevices`
+    /// for one device class; nothing denied removes the class key
+    SetRemovableStoragePolicy { policy: RemovableStoragePolicy },
+    /// Dismount the USB device's volumes, then eject it. The helper finds
+    /// the volumes itself; `mount_points` naming any other volume is refused.
+    /// `force` dismounts volumes that still have files open
+    EjectDevice { instance_id: String, mount_points: Vec<String>, force: bool },
+    /// Hand the service the policy to enforce while the GUI is away. Sent
+    /// periodically; each one also tells the service the GUI is still up.
+    /// Answered with the guard status, whose blocks are then handed over.
+    SyncGuard { policy: GuardPolicy },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    State(HelperState),
+    Guard(GuardStatus),
+    Error {
+        message: String,
+        /// Absent from helpers that predate it, and for failures with no code
+        #[serde(default)]
+        code: Option<ErrorCode>,
+    },
+}
+
+/// What a failed request ran into, for the client to act on without
+/// parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// No devnode has the instance ID, or it is not present
+    DeviceNotFound,
+    /// A Win32, SetupAPI or registry call failed with this error
+    Win32 { code: u32 },
+    /// The request relaxes protection and the client did not prove it is
+    /// elevated
+    ElevationRequired,
+}
+
+/// A failed request: the message shown to people and, where known, the code
+/// behind it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub message: String,
+    pub code: Option<ErrorCode>,
+}
+
+impl Failure {
+    pub fn device_not_found(instance_id: &str) -> Self {
+        Failure {
+            message: format!("Device not found: {}", instance_id),
+            code: Some(ErrorCode::DeviceNotFound),
+        }
+    }
+
+    pub fn elevation_required() -> Self {
+        Failure {
+            message: "Only an elevated client may relax protection".to_string(),
+            code: Some(ErrorCode::ElevationRequired),
+        }
+    }
+
+    /// `message (error N)`, the way every Win32 failure is worded
+    pub fn win32(message: &str, code: u32) -> Self {
+        Failure {
+            message: format!("{} (error {})", message, code),
+            code: Some(ErrorCode::Win32 { code }),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure { message, code: None }
+    }
+}
+
+impl From<Failure> for String {
+    fn from(failure: Failure) -> Self {
+        failure.message
+    }
+}
+
+impl From<Failure> for Response {
+    fn from(failure: Failure) -> Self {
+        Response::Error {
+            message: failure.message,
+            code: failure.code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HelperState {
+    pub usbstor_start: Option<u32>,
+    pub devices: Vec<DevnodeState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevnodeState {
+    pub instance_id: String,
+    pub present: bool,
+    pub disabled: bool,
+}
+
+/// What the service enforces on its own when no GUI is running: a
+/// deliberately small subset of the GUI's rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuardPolicy {
+    /// Disable untrusted arrivals
+    pub autoblock: bool,
+    /// VID/PID pairs let through
+    pub trusted: Vec<(u16, u16)>,
+    /// Trusted models pinned to particular units; other units of the model
+    /// are blocked
+    #[serde(default)]
+    pub bindings: Vec<GuardBinding>,
+    /// Base classes allowed by class policy: a device made only of these
+    /// is let through
+    #[serde(default)]
+    pub allowed_classes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuardBinding {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// `None` matches any unit of the model
+    pub serial: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuardStatus {
+    pub policy: GuardPolicy,
+    /// Enforcing right now, because no GUI has synced recently
+    pub active: bool,
+    /// Devices blocked since the last sync
+    pub blocked: Vec<GuardBlock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardBlock {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Seconds since the Unix epoch
+    pub blocked_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WakeState {
+    pub name: String,
+    pub armed: bool,
+}
+
+/// The removable storage access policy of one device class, the registry
+/// form of the "Removable Storage Access" Group Policy settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemovableStoragePolicy {
+    /// Device class GUID in braces, e.g. `{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}`
+    pub class_guid: String,
+    pub deny_read: bool,
+    pub deny_write: bool,
+    pub deny_execute: bool,
+}
+
+/// First message from the server: a fresh nonce the client must MAC.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    pub nonce: String,
+}
+
+/// The nonce's MAC under the pipe secret, which only elevated processes can
+/// read. An unelevated client sends none and is held to requests that do
+/// not relax protection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    #[serde(default)]
+    pub mac: Option<String>,
+}
+
+/// Frames are a little-endian u32 length followed by that many bytes of JSON.
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> Result<(), String> {
+    let body = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    writer
+        .write_all(&(body.len() as u32).to_le_bytes())
+        .and_then(|_| writer.write_all(&body))
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Helper pipe write failed: {}", e))
+}
+
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T, String> {
+    let mut length = [0u8; 4];
+    reader
+        .read_exact(&mut length)
+        .map_err(|e| format!("Helper pipe read failed: {}", e))?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME {
+        return Err(format!("Helper frame too large ({} bytes)", length));
+    }
+    let mut body = vec![0u8; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("Helper pipe read failed: {}", e))?;
+    serde_json::from_slice(&body).map_err(|e| format!("Malformed helper message: {}", e))
+}
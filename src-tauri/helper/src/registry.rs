@@ -0,0 +1,226 @@
+//! Registry writes through the wide-character API. Every path and value
+//! name is converted to a NUL-terminated UTF-16 buffer that outlives the
+//! call, and every opened key is closed when its `Key` is dropped. Both
+//! crates write the registry only through here; reads may use `winreg`.
+
+use std::fmt;
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_SUCCESS, WIN32_ERROR},
+        System::Registry::{
+            RegCloseKey, RegCreateKeyExW, RegDeleteTreeW, RegDeleteValueW, RegOpenKeyExW, RegQueryValueExW,
+            RegSetValueExW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_QUERY_VALUE, KEY_SET_VALUE,
+            REG_DWORD, REG_OPTION_NON_VOLATILE, REG_SZ, REG_VALUE_TYPE,
+        },
+    },
+};
+
+use crate::protocol::{ErrorCode, Failure};
+
+/// The hives we write to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    LocalMachine,
+    CurrentUser,
+}
+
+impl Root {
+    fn hkey(self) -> HKEY {
+        match self {
+            Root::LocalMachine => HKEY_LOCAL_MACHINE,
+            Root::CurrentUser => HKEY_CURRENT_USER,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Root::LocalMachine => "HKLM",
+            Root::CurrentUser => "HKCU",
+        }
+    }
+}
+
+/// A failed registry call. Displayed as `... (error N)`, like every other
+/// Win32 failure the helper reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryError {
+    /// Key, or `key\value`, the call was about
+    pub path: String,
+    pub code: u32,
+}
+
+impl RegistryError {
+    pub fn is_access_denied(&self) -> bool {
+        self.code == ERROR_ACCESS_DENIED.0
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.code == ERROR_FILE_NOT_FOUND.0
+    }
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Registry access to {} failed (error {})", self.path, self.code)
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<RegistryError> for String {
+    fn from(error: RegistryError) -> Self {
+        error.to_string()
+    }
+}
+
+impl From<RegistryError> for Failure {
+    fn from(error: RegistryError) -> Self {
+        Failure {
+            message: error.to_string(),
+            code: Some(ErrorCode::Win32 { code: error.code }),
+        }
+    }
+}
+
+// NUL-terminated UTF-16, boxed so the buffer cannot grow or move while a
+// pointer to it is in use
+fn wide(s: &str) -> Box<[u16]> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+fn check(status: WIN32_ERROR, path: impl FnOnce() -> String) -> Result<(), RegistryError> {
+    if status == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(RegistryError { path: path(), code: status.0 })
+    }
+}
+
+/// An open key, closed on drop.
+pub struct Key {
+    handle: HKEY,
+    path: String,
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.handle);
+        }
+    }
+}
+
+/// Open `path` under `root` for reading and writing values, creating it
+/// and any missing parents.
+pub fn create(root: Root, path: &str) -> Result<Key, RegistryError> {
+    let subkey = wide(path);
+    let mut handle = HKEY::default();
+    let status = unsafe {
+        RegCreateKeyExW(
+            root.hkey(),
+            PCWSTR(subkey.as_ptr()),
+            0,
+            PCWSTR::null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_SET_VALUE | KEY_QUERY_VALUE,
+            None,
+            &mut handle,
+            None,
+        )
+    };
+    let path = format!(r"{}\{}", root.name(), path);
+    check(status, || path.clone())?;
+    Ok(Key { handle, path })
+}
+
+/// Open an existing key for reading and writing values; `None` when it
+/// does not exist.
+pub fn open(root: Root, path: &str) -> Result<Option<Key>, RegistryError> {
+    let subkey = wide(path);
+    let mut handle = HKEY::default();
+    let status = unsafe {
+        RegOpenKeyExW(
+            root.hkey(),
+            PCWSTR(subkey.as_ptr()),
+            0,
+            KEY_SET_VALUE | KEY_QUERY_VALUE,
+            &mut handle,
+        )
+    };
+    let path = format!(r"{}\{}", root.name(), path);
+    match check(status, || path.clone()) {
+        Ok(()) => Ok(Some(Key { handle, path })),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Delete `path` under `root` with everything below it. A key that is
+/// already gone is not an error.
+pub fn delete_tree(root: Root, path: &str) -> Result<(), RegistryError> {
+    let subkey = wide(path);
+    let status = unsafe { RegDeleteTreeW(root.hkey(), PCWSTR(subkey.as_ptr())) };
+    match check(status, || format!(r"{}\{}", root.name(), path)) {
+        Err(e) if e.is_not_found() => Ok(()),
+        other => other,
+    }
+}
+
+impl Key {
+    fn value_path(&self, name: &str) -> String {
+        format!(r"{}\{}", self.path, name)
+    }
+
+    fn set(&self, name: &str, kind: REG_VALUE_TYPE, data: &[u8]) -> Result<(), RegistryError> {
+        let value_name = wide(name);
+        let status = unsafe { RegSetValueExW(self.handle, PCWSTR(value_name.as_ptr()), 0, kind, Some(data)) };
+        check(status, || self.value_path(name))
+    }
+
+    pub fn set_dword(&self, name: &str, value: u32) -> Result<(), RegistryError> {
+        self.set(name, REG_DWORD, &value.to_le_bytes())
+    }
+
+    pub fn set_string(&self, name: &str, value: &str) -> Result<(), RegistryError> {
+        // REG_SZ data is the UTF-16 string including its terminating NUL
+        let data: Vec<u8> = wide(value).iter().flat_map(|unit| unit.to_le_bytes()).collect();
+        self.set(name, REG_SZ, &data)
+    }
+
+    /// Remove a value. One that is already gone is not an error.
+    pub fn delete_value(&self, name: &str) -> Result<(), RegistryError> {
+        let value_name = wide(name);
+        let status = unsafe { RegDeleteValueW(self.handle, PCWSTR(value_name.as_ptr())) };
+        match check(status, || self.value_path(name)) {
+            Err(e) if e.is_not_found() => Ok(()),
+            other => other,
+        }
+    }
+
+    /// A DWORD value, or `None` when it is missing or of another type.
+    pub fn dword(&self, name: &str) -> Result<Option<u32>, RegistryError> {
+        let value_name = wide(name);
+        let mut kind = REG_VALUE_TYPE::default();
+        let mut data = [0u8; 4];
+        let mut size = data.len() as u32;
+        let status = unsafe {
+            RegQueryValueExW(
+                self.handle,
+                PCWSTR(value_name.as_ptr()),
+                None,
+                Some(&mut kind),
+                Some(data.as_mut_ptr()),
+                Some(&mut size),
+            )
+        };
+        match check(status, || self.value_path(name)) {
+            Ok(()) if kind == REG_DWORD && size == 4 => Ok(Some(u32::from_le_bytes(data))),
+            Ok(()) => Ok(None),
+            // Longer than a DWORD, so not one
+            Err(e) if e.is_not_found() || e.code == ERROR_MORE_DATA.0 => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{ffi::c_void, os::windows::ffi::OsStrExt, path::Path};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, BOOL, ERROR_SUCCESS, HANDLE, HLOCAL, PSID},
+        Security::{
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SetNamedSecurityInfoW, SDDL_REVISION_1,
+                SE_FILE_OBJECT,
+            },
+            GetSecurityDescriptorDacl, GetTokenInformation, RevertToSelf, TokenElevation, ACL,
+            DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+            SECURITY_ATTRIBUTES, TOKEN_ELEVATION, TOKEN_QUERY,
+        },
+        System::{
+            Memory::LocalFree,
+            Pipes::{GetNamedPipeClientSessionId, ImpersonateNamedPipeClient},
+            RemoteDesktop::WTSGetActiveConsoleSessionId,
+            Threading::{GetCurrentThread, OpenThreadToken},
+        },
+    },
+};
+
+/// The pipe: full control for SYSTEM and Administrators, read and write for
+/// whoever is logged on interactively, nothing for services or network
+/// logons.
+pub const PIPE_SDDL: &str = "D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;GRGW;;;IU)";
+/// The ProgramData directory and what is created in it: SYSTEM and
+/// Administrators only.
+pub const DIRECTORY_SDDL: &str = "D:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)";
+/// The pipe secret: SYSTEM and Administrators only, so knowing it proves
+/// the client is elevated.
+pub const SECRET_SDDL: &str = "D:P(A;;FA;;;SY)(A;;FA;;;BA)";
+
+/// A self-relative security descriptor built from SDDL, freed on drop.
+pub struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+impl SecurityDescriptor {
+    pub fn from_sddl(sddl: &str) -> Result<Self, String> {
+        let text: Vec<u16> = sddl.encode_utf16().chain(Some(0)).collect();
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        let ok = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                PCWSTR(text.as_ptr()),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                None,
+            )
+            .as_bool()
+        };
+        if !ok {
+            return Err(format!("Invalid security descriptor {}: {}", sddl, std::io::Error::last_os_error()));
+        }
+        Ok(SecurityDescriptor(descriptor))
+    }
+
+    /// For `CreateNamedPipeW` and friends; borrows the descriptor.
+    pub fn attributes(&self) -> SECURITY_ATTRIBUTES {
+        SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.0 .0,
+            bInheritHandle: BOOL(0),
+        }
+    }
+
+    fn dacl(&self) -> Result<*const ACL, String> {
+        let mut present = 0i32;
+        let mut defaulted = 0i32;
+        let mut dacl: *mut ACL = std::ptr::null_mut();
+        let ok = unsafe { GetSecurityDescriptorDacl(self.0, &mut present, &mut dacl, &mut defaulted).as_bool() };
+        if !ok || present == 0 {
+            return Err("Security descriptor has no DACL".to_string());
+        }
+        Ok(dacl)
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = LocalFree(HLOCAL(self.0 .0 as isize));
+        }
+    }
+}
+
+/// Replace the DACL of `path` with the one in `sddl`, dropping whatever it
+/// inherited from its parent.
+pub fn restrict(path: &Path, sddl: &str) -> Result<(), String> {
+    let descriptor = SecurityDescriptor::from_sddl(sddl)?;
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let error = unsafe {
+        SetNamedSecurityInfoW(
+            PCWSTR(name.as_ptr()),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+            PSID::default(),
+            PSID::default(),
+            Some(descriptor.dacl()?),
+            None,
+        )
+    };
+    if error != ERROR_SUCCESS {
+        return Err(format!("Failed to restrict access to {} (Error {})", path.display(), error.0));
+    }
+    Ok(())
+}
+
+fn client_elevated(pipe: HANDLE) -> bool {
+    unsafe {
+        if !ImpersonateNamedPipeClient(pipe).as_bool() {
+            return false;
+        }
+        let mut token = HANDLE::default();
+        let opened = OpenThreadToken(GetCurrentThread(), TOKEN_QUERY, true, &mut token).as_bool();
+        RevertToSelf();
+        if !opened {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut returned = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut c_void),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        )
+        .as_bool();
+        CloseHandle(token);
+        ok && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Whether the process on the other end of `pipe` may drive the helper: an
+/// elevated process (which covers SYSTEM), or one in the console session,
+/// where the GUI of the logged-on user runs. Only one that also knows the
+/// secret may relax protection.
+pub fn client_allowed(pipe: HANDLE) -> bool {
+    let mut session = 0u32;
+    let in_console = unsafe {
+        GetNamedPipeClientSessionId(pipe, &mut session).as_bool() && session == WTSGetActiveConsoleSessionId()
+    };
+    in_console || client_elevated(pipe)
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs::File,
+    os::windows::io::{FromRawHandle, RawHandle},
+    sync::Arc,
+    thread,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, ERROR_PIPE_CONNECTED, GetLastError},
+        Storage::FileSystem::PIPE_ACCESS_DUPLEX,
+        System::Pipes::{
+            ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    },
+};
+
+use crate::auth;
+use crate::enforcement;
+use crate::event_log::{self, HelperEvent};
+use crate::guard;
+use crate::protocol::{read_frame, write_frame, Challenge, ChallengeResponse, Failure, Request, Response, PIPE_NAME};
+use crate::security::{self, SecurityDescriptor};
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Accept clients forever, one thread per connection.
+pub fn serve() -> Result<(), String> {
+    let secret = Arc::new(auth::load_or_create_secret()?);
+    let name: Vec<u16> = PIPE_NAME.encode_utf16().chain(Some(0)).collect();
+    // Without one the pipe would get the default DACL, open to every user
+    let descriptor = SecurityDescriptor::from_sddl(security::PIPE_SDDL)?;
+    let attributes = descriptor.attributes();
+
+    loop {
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                PCWSTR(name.as_ptr()),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                Some(&attributes),
+            )
+        };
+        if pipe.is_invalid() {
+            return Err(format!("CreateNamedPipe failed (Error {})", unsafe { GetLastError().0 }));
+        }
+
+        let connected = unsafe { ConnectNamedPipe(pipe, None).as_bool() || GetLastError() == ERROR_PIPE_CONNECTED };
+        if !connected {
+            unsafe {
+                CloseHandle(pipe);
+            }
+            continue;
+        }
+        if !security::client_allowed(pipe) {
+            unsafe {
+                DisconnectNamedPipe(pipe);
+                CloseHandle(pipe);
+            }
+            continue;
+        }
+
+        let secret = secret.clone();
+        // The File takes ownership of the handle and closes it on drop
+        let stream = unsafe { File::from_raw_handle(pipe.0 as RawHandle) };
+        thread::spawn(move || {
+            if let Err(e) = handle_client(stream, &secret) {
+                event_log::write(HelperEvent::ClientFailed, &format!("Helper client: {}", e));
+            }
+        });
+    }
+}
+
+fn handle_client(mut stream: File, secret: &[u8]) -> Result<(), String> {
+    let nonce = auth::new_nonce();
+    write_frame(&mut stream, &Challenge { nonce: nonce.clone() })?;
+    let response: ChallengeResponse = read_frame(&mut stream)?;
+    let elevated = match response.mac {
+        Some(mac) if auth::verify(secret, &nonce, &mac) => true,
+        Some(_) => {
+            write_frame(&mut stream, &Response::from(Failure::from("Authentication failed".to_string())))?;
+            return Err("client failed authentication".to_string());
+        }
+        None => false,
+    };
+    write_frame(&mut stream, &Response::Ok)?;
+
+    // Serve requests until the client hangs up
+    while let Ok(request) = read_frame::<_, Request>(&mut stream) {
+        let response = if !elevated && relaxes(&request) {
+            Response::from(Failure::elevation_required())
+        } else {
+            dispatch(request)
+        };
+        write_frame(&mut stream, &response)?;
+    }
+    Ok(())
+}
+
+/// Whether `request` lets more through than before, which an unelevated
+/// client may not ask for: everything else it could do by itself anyway.
+fn relaxes(request: &Request) -> bool {
+    match request {
+        Request::ApplyPolicy { usbstor_start } => *usbstor_start != 4,
+        Request::SetDeviceState { enable, .. } => *enable,
+        Request::SetVolumeReadOnly { read_only, .. } => !read_only,
+        Request::SetStorageWriteProtect { enabled } => !enabled,
+        // Written whole, so anything short of denying all access may lift a denial
+        Request::SetRemovableStoragePolicy { policy } => {
+            !(policy.deny_read && policy.deny_write && policy.deny_execute)
+        }
+        Request::SyncGuard { policy } => guard::relaxes(policy),
+        Request::ReadState { .. }
+        | Request::SetSelectiveSuspend { .. }
+        | Request::SetWakeEnabled { .. }
+        | Request::EjectDevice { .. } => false,
+    }
+}
+
+pub fn dispatch(request: Request) -> Response {
+    let result: Result<Response, Failure> = match request {
+        Request::ApplyPolicy { usbstor_start } => enforcement::apply_policy(usbstor_start).map(|()| Response::Ok),
+        Request::SetDeviceState { instance_id, enable } => {
+            enforcement::set_device_state(&instance_id, enable).map(|()| Response::Ok)
+        }
+        Request::ReadState { instance_ids } => Ok(Response::State(enforcement::read_state(&instance_ids))),
+        Request::SetVolumeReadOnly { mount_point, read_only } => {
+            enforcement::set_volume_read_only(&mount_point, read_only).map(|()| Response::Ok).map_err(Failure::from)
+        }
+        Request::SetStorageWriteProtect { enabled } => {
+            enforcement::set_storage_write_protect(enabled).map(|()| Response::Ok)
+        }
+        Request::SetSelectiveSuspend { instance_id, enabled } => {
+            enforcement::set_selective_suspend(&instance_id, enabled).map(|()| Response::Ok).map_err(Failure::from)
+        }
+        Request::SetWakeEnabled { name, enabled } => {
+            enforcement::set_wake_enabled(&name, enabled).map(|()| Response::Ok).map_err(Failure::from)
+        }
+        Request::SetRemovableStoragePolicy { policy } => {
+            enforcement::set_removable_storage_policy(&policy).map(|()| Response::Ok)
+        }
+        Request::EjectDevice { instance_id, mount_points, force } => {
+            enforcement::eject_device(&instance_id, &mount_points, force).map(|()| Response::Ok)
+        }
+        Request::SyncGuard { policy } => Ok(Response::Guard(guard::sync(policy))),
+    };
+    result.unwrap_or_else(Response::from)
+}
@@ -0,0 +1,126 @@
+use std::{ffi::OsString, sync::mpsc, thread, time::Duration};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+use crate::event_log::{self, HelperEvent};
+use crate::guard;
+use crate::server;
+
+pub const SERVICE_NAME: &str = "UsbShieldHelper";
+const DISPLAY_NAME: &str = "USB-Shield enforcement";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hand the process to the service control manager. Only returns once the
+/// service has stopped; fails when not started by the SCM.
+pub fn run() -> Result<(), String> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(|e| format!("Service dispatcher failed: {}", e))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        event_log::write(HelperEvent::ServiceFailed, &format!("Helper service: {}", e));
+    }
+}
+
+fn status(state: ServiceState, controls: ServiceControlAccept) -> ServiceStatus {
+    ServiceStatus {
+        service_type: SERVICE_TYPE,
+        current_state: state,
+        controls_accepted: controls,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+fn run_service() -> Result<(), String> {
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop_tx.send(());
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })
+    .map_err(|e| format!("Failed to register the service control handler: {}", e))?;
+
+    handle
+        .set_service_status(status(
+            ServiceState::Running,
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ))
+        .map_err(|e| e.to_string())?;
+
+    guard::start();
+    // The pipe server blocks forever; the process ends with the service
+    thread::spawn(|| {
+        if let Err(e) = server::serve() {
+            event_log::write(HelperEvent::ServiceFailed, &format!("Helper pipe server: {}", e));
+        }
+    });
+    let _ = stop_rx.recv();
+
+    handle
+        .set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty()))
+        .map_err(|e| e.to_string())
+}
+
+/// Register the helper as an auto-start LocalSystem service, so protection
+/// starts at boot, before anyone logs in.
+pub fn install() -> Result<(), String> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .map_err(|e| format!("Failed to open the service manager: {}", e))?;
+    let executable_path = std::env::current_exe().map_err(|e| e.to_string())?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path,
+        launch_arguments: vec![OsString::from("--service")],
+        dependencies: vec![],
+        // LocalSystem
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::START)
+        .map_err(|e| format!("Failed to create the service: {}", e))?;
+    service
+        .start::<OsString>(&[])
+        .map_err(|e| format!("Service installed but failed to start: {}", e))
+}
+
+/// Whether the service is registered, running or not.
+pub fn is_installed() -> bool {
+    ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .and_then(|manager| manager.open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS))
+        .is_ok()
+}
+
+pub fn uninstall() -> Result<(), String> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|e| format!("Failed to open the service manager: {}", e))?;
+    let service = manager
+        .open_service(SERVICE_NAME, ServiceAccess::STOP | ServiceAccess::DELETE)
+        .map_err(|e| format!("Failed to open the service: {}", e))?;
+    // Already stopped is fine
+    let _ = service.stop();
+    service.delete().map_err(|e| format!("Failed to delete the service: {}", e))
+}
@@ -0,0 +1,366 @@
+pub mod usb;
+
+use usb::admin_pin::*;
+use usb::audit::*;
+use usb::background::*;
+use usb::class_policy::*;
+use usb::commands::*;
+use usb::config_export::*;
+use usb::device_interfaces::*;
+use usb::device_labels::*;
+use usb::docks::*;
+use usb::dry_run::*;
+use usb::eject::*;
+use usb::device_power::*;
+use usb::email_alerts::*;
+use usb::emergency::*;
+use usb::exfiltration::*;
+use usb::forensics::*;
+use usb::gpo_policy::*;
+use usb::guest::*;
+use usb::hello::*;
+use usb::hid_quarantine::*;
+use usb::hub_policy::*;
+use usb::idle::*;
+use usb::inventory::*;
+use usb::keyboard_lockdown::*;
+use usb::keystrokes::*;
+use usb::logging::*;
+use usb::network::*;
+use usb::network_adapters::*;
+use usb::notifications::*;
+use usb::pause::*;
+use usb::policy_diff::*;
+use usb::port_locks::*;
+use usb::port_power::*;
+use usb::power::*;
+use usb::privilege::*;
+use usb::profiles::*;
+use usb::protection_schedule::*;
+use usb::quarantine::*;
+use usb::quota::*;
+use usb::reblock::*;
+use usb::remote::*;
+use usb::rollback::*;
+use usb::scheduler::*;
+use usb::security_key::*;
+use usb::self_test::*;
+use usb::siem::*;
+use usb::simulation::*;
+use usb::smartcard::*;
+use usb::status::*;
+use usb::storage_readonly::*;
+use usb::suggestions::*;
+use usb::support_bundle::*;
+use usb::tamper::*;
+use usb::threats::*;
+use usb::transfers::*;
+use usb::type_c::*;
+use usb::usb_names::*;
+use usb::trust_rules::*;
+use usb::trust_share::*;
+use usb::uninstall::*;
+use usb::verification::*;
+use usb::vpn::*;
+use usb::webhooks::*;
+use usb::wireless::*;
+
+use tauri::Manager;
+
+pub fn run() {
+    // Before single-instance, which would hand the flag to a running UI
+    if std::env::args().any(|arg| arg == usb::uninstall::CLEANUP_ARG) {
+        std::process::exit(usb::uninstall::run_from_uninstaller());
+    }
+    usb::privilege::wait_for_predecessor();
+    tauri::Builder::default()
+        // Must come first: a second launch (e.g. from a deep link) hands its
+        // URL to this instance and exits
+        .plugin(tauri_plugin_single_instance::init(|_app, _argv, _cwd| {}))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            usb::etw::register();
+            usb::event_log::register();
+            usb::usb_config::init(app.path().app_data_dir()?)?;
+            if let Err(e) = usb::logging::init() {
+                eprintln!("{}", e);
+            }
+            if let Err(e) = usb::siem::load() {
+                log::error!("Failed to load SIEM forwarding settings: {}", e);
+            }
+            if let Err(e) = usb::notifications::load() {
+                log::error!("Failed to load notification settings: {}", e);
+            }
+            app.manage(usb::commands::app_state().clone());
+            // A corrupt whitelist must not keep the app from starting
+            if let Err(e) = app.state::<AppState>().load_trusted_devices() {
+                log::error!("Failed to load trusted devices: {}", e);
+            }
+            usb::events::init(app.handle().clone());
+            usb::backend::init()?;
+            if let Err(e) = usb::usb_control::load() {
+                log::error!("Failed to restore block records: {}", e);
+            }
+            if let Err(e) = usb::port_locks::load() {
+                log::error!("Failed to load port locks: {}", e);
+            }
+            if let Err(e) = usb::keyboard_lockdown::load() {
+                log::error!("Failed to load keyboard lockdown: {}", e);
+            }
+            if let Err(e) = usb::network_adapters::load() {
+                log::error!("Failed to load the network adapter policy: {}", e);
+            }
+            if let Err(e) = usb::quarantine::load() {
+                log::error!("Failed to load the quarantine: {}", e);
+            }
+            if let Err(e) = usb::device_interfaces::load() {
+                log::error!("Failed to load interface rules: {}", e);
+            }
+            if let Err(e) = usb::protection_schedule::load() {
+                log::error!("Failed to load the protection schedule: {}", e);
+            }
+            if let Err(e) = usb::email_alerts::load() {
+                log::error!("Failed to load email alert settings: {}", e);
+            }
+            if let Err(e) = usb::webhooks::load() {
+                log::error!("Failed to load webhooks: {}", e);
+            }
+            if let Err(e) = usb::threats::load() {
+                log::error!("Failed to load threat signatures: {}", e);
+            }
+            if let Err(e) = usb::tamper::load() {
+                log::error!("Failed to load guarded settings: {}", e);
+            }
+            if let Err(e) = usb::storage_readonly::load() {
+                log::error!("Failed to restore read-only storage: {}", e);
+            }
+            usb::self_test::run_at_startup();
+            usb::idle::start();
+            usb::protection_schedule::start();
+            usb::network::start();
+            usb::vpn::start();
+            usb::power::start();
+            usb::docks::start();
+            usb::smartcard::start();
+            usb::remote::start();
+            usb::transfers::start();
+            usb::keystrokes::start();
+            usb::hotplug::start();
+            usb::background::start();
+            usb::verification::start();
+            usb::tamper::start();
+            usb::forensics::start();
+            usb::deep_link::init(app.handle())?;
+            usb::tray::init(app.handle())?;
+
+            #[cfg(debug_assertions)]
+            {
+                let window = app.get_webview_window("main").unwrap();
+                window.open_devtools();
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_usb_devices,
+            block_all_usb_ports,
+            unblock_usb_port,
+            get_storage_readonly,
+            set_storage_readonly,
+            get_gpo_storage_policies,
+            set_gpo_storage_policy,
+            restart_usb_service,
+            add_trusted_device,
+            add_trusted_device_temporary,
+            revoke_temporary_trust,
+            remove_trusted_device,
+            get_trusted_devices,
+            get_usb_ids_status,
+            update_usb_ids,
+            get_device_labels,
+            set_device_label,
+            set_device_note,
+            reload_trusted_devices,
+            get_autoblock_mode, 
+            set_autoblock_mode,
+            get_autoblock_schedule,
+            set_autoblock_schedule,
+            get_autoblock_prompts,
+            answer_autoblock_prompt,
+            get_quarantine_mode,
+            set_quarantine_mode,
+            get_quarantined_devices,
+            resolve_quarantine,
+            get_class_policies,
+            set_class_policy,
+            get_hub_policy,
+            set_hub_policy,
+            set_device_operation_timeout,
+            get_device_operation_timeout,
+            block_device,
+            unblock_device,
+            block_devices,
+            unblock_devices,
+            get_device_interfaces,
+            block_device_interface,
+            unblock_device_interface,
+            get_interface_rules,
+            block_all_of_class,
+            unblock_all_of_class,
+            request_emergency_token,
+            unblock_everything,
+            get_pending_reblocks,
+            cancel_reblock,
+            unblock_device_for,
+            extend_timed_unblock,
+            revoke_timed_unblock,
+            start_guest_mode,
+            end_guest_mode,
+            get_guest_mode,
+            get_protection_pause,
+            pause_protection,
+            resume_protection,
+            get_active_profile,
+            set_active_profile,
+            get_schedule,
+            set_schedule,
+            get_schedule_state,
+            get_idle_lockdown,
+            set_idle_lockdown,
+            get_network_policy,
+            set_network_policy,
+            get_network_location,
+            get_notification_settings,
+            set_notification_route,
+            get_notification_preferences,
+            set_notification_preferences,
+            get_recent_logs,
+            get_log_level,
+            set_log_level,
+            get_vpn_status,
+            get_vpn_storage_rule,
+            set_vpn_storage_rule,
+            get_wireless_policy,
+            set_wireless_policy,
+            get_network_adapter_policy,
+            set_block_usb_network_adapters,
+            set_network_adapter_dock_allowed,
+            get_power_source,
+            get_power_policy,
+            set_power_policy,
+            get_docks,
+            get_dock_candidates,
+            register_dock,
+            remove_dock,
+            get_security_key_gate,
+            register_security_key,
+            remove_security_key,
+            set_security_key_gate,
+            get_smartcard_policy,
+            set_smartcard_policy,
+            get_admin_pin_status,
+            set_admin_pin,
+            verify_admin_pin,
+            get_windows_hello_requirement,
+            set_windows_hello_requirement,
+            export_audit_range,
+            get_audit_log,
+            export_audit_log,
+            get_remote_usb_policy,
+            set_remote_usb_policy,
+            get_redirection_clients,
+            get_transfer_stats,
+            get_exfiltration_policy,
+            set_exfiltration_policy,
+            get_write_blocked_volumes,
+            release_write_block,
+            start_forensic_mode,
+            stop_forensic_mode,
+            get_forensic_status,
+            hash_evidence,
+            acquire_evidence,
+            get_write_quota_policy,
+            set_write_quota_policy,
+            get_write_quota_usage,
+            get_selective_suspend,
+            set_selective_suspend,
+            get_usb_suspend_setting,
+            set_usb_suspend_setting,
+            get_wake_devices,
+            set_wake_enabled,
+            get_type_c_ports,
+            power_cycle_port,
+            cut_port_power,
+            restore_port_power,
+            get_powered_off_ports,
+            get_threat_signatures,
+            update_threat_database,
+            get_port_locks,
+            block_port,
+            unblock_port,
+            get_status_summary,
+            get_effective_protection_status,
+            get_tamper_response,
+            set_tamper_response,
+            get_last_tamper,
+            get_startup_health,
+            get_background_protection,
+            run_self_test,
+            get_siem_forwarding,
+            configure_siem_forwarding,
+            test_siem_connection,
+            get_webhooks,
+            set_webhooks,
+            send_test_webhook,
+            get_email_alerts,
+            configure_email_alerts,
+            send_test_email,
+            get_privilege_status,
+            relaunch_elevated,
+            toggle_status_widget,
+            query_inventory,
+            get_device_history,
+            set_device_preblocked,
+            get_policy_suggestions,
+            accept_policy_suggestion,
+            query_audit_log,
+            get_keystroke_baseline,
+            set_keystroke_baseline,
+            reset_keystroke_baseline,
+            get_hid_quarantine,
+            approve_hid_device,
+            get_keyboard_lockdown,
+            set_keyboard_lockdown,
+            approve_keyboard,
+            forget_keyboard,
+            generate_support_bundle,
+            diff_policies,
+            export_config,
+            import_config,
+            get_verification_schedule,
+            set_verification_schedule,
+            run_verification_now,
+            get_last_verification,
+            get_trust_bindings,
+            bind_trusted_device,
+            remove_trust_binding,
+            export_trusted_device,
+            import_trusted_device,
+            get_volume_label_rules,
+            set_volume_label_rules,
+            get_simulation_state,
+            simulate_attach,
+            simulate_detach,
+            simulate_failure,
+            simulate_transfer,
+            get_dry_run_mode,
+            set_dry_run_mode,
+            get_rollback_snapshot,
+            restore_previous_state,
+            cleanup_all_enforcements,
+            eject_device,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
\ No newline at end of file
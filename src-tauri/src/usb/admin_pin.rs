@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::error::UsbShieldError;
+use super::hello;
+use super::privilege;
+use super::usb_config;
+
+const ADMIN_PIN_FILE: &str = "admin-pin.json";
+const MIN_PIN_LENGTH: usize = 4;
+const SESSION_LIFETIME: Duration = Duration::from_secs(5 * 60);
+// Wrong PINs in a row before verification is refused for a while
+const MAX_FAILURES: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize)]
+struct StoredPin {
+    /// Argon2id PHC string; the PIN itself is never written
+    hash: String,
+    set_at: DateTime<Utc>,
+}
+
+/// Proof of a recent PIN verification, passed to the guarded commands.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminSession {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminPinStatus {
+    pub configured: bool,
+    pub set_at: Option<DateTime<Utc>>,
+    /// The PIN file is unreadable, so no PIN is accepted until an
+    /// administrator sets a new one
+    pub locked: bool,
+}
+
+// What admin-pin.json says. A file that cannot be read, or that is gone
+// while the audit log says a PIN was set, counts as a PIN nobody can enter:
+// removing or corrupting it must not turn the PIN off.
+enum PinRecord {
+    Unset,
+    Set(StoredPin),
+    Unreadable(String),
+}
+
+struct PinState {
+    // Read from disk on first use
+    stored: Option<PinRecord>,
+    sessions: HashMap<String, Instant>,
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<PinState> = Mutex::new(PinState {
+        stored: None,
+        sessions: HashMap::new(),
+        failures: 0,
+        locked_until: None,
+    });
+}
+
+// The last change on record, for telling "never set" from "deleted"
+fn pin_was_configured() -> bool {
+    audit::read_all().is_ok_and(|entries| {
+        entries
+            .iter()
+            .rev()
+            .find(|entry| entry.action == "admin_pin_changed")
+            .is_some_and(|entry| entry.details["configured"].as_bool().unwrap_or(false))
+    })
+}
+
+fn read_stored() -> PinRecord {
+    let path = match usb_config::data_file(ADMIN_PIN_FILE) {
+        Ok(path) => path,
+        Err(e) => return PinRecord::Unreadable(e),
+    };
+    match fs::read(&path) {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(stored) => PinRecord::Set(stored),
+            Err(e) => PinRecord::Unreadable(format!("Failed to parse {}: {}", path.display(), e)),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if pin_was_configured() {
+                PinRecord::Unreadable(format!("{} was removed", path.display()))
+            } else {
+                PinRecord::Unset
+            }
+        }
+        Err(e) => PinRecord::Unreadable(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn stored(state: &mut PinState) -> &PinRecord {
+    state.stored.get_or_insert_with(read_stored)
+}
+
+fn write_stored(stored: Option<&StoredPin>) -> Result<(), String> {
+    let path = usb_config::data_file(ADMIN_PIN_FILE)?;
+    match stored {
+        Some(stored) => {
+            let data = serde_json::to_vec_pretty(stored).map_err(|e| e.to_string())?;
+            fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        }
+        None => match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pin.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash the PIN: {}", e))
+}
+
+fn pin_matches(pin: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(pin.as_bytes(), &hash).is_ok())
+}
+
+/// Refuse `action` unless `token` is a live admin session. A no-op while no
+/// PIN is set; always refused while the PIN file is unreadable.
+pub fn require_admin(token: Option<&str>, action: &str) -> Result<(), UsbShieldError> {
+    let mut state = STATE.lock().unwrap();
+    match stored(&mut state) {
+        PinRecord::Unset => return Ok(()),
+        PinRecord::Unreadable(e) => log::error!("Admin PIN unreadable, refusing to {}: {}", action, e),
+        PinRecord::Set(_) => {}
+    }
+    let now = Instant::now();
+    state.sessions.retain(|_, expires| *expires > now);
+    if token.is_some_and(|token| state.sessions.contains_key(token)) {
+        return Ok(());
+    }
+    drop(state);
+    audit::record("admin_session_rejected", json!({ "action": action, "token_given": token.is_some() }));
+    Err(UsbShieldError::AdminPinRequired {
+        action: action.to_string(),
+    })
+}
+
+#[command]
+pub fn get_admin_pin_status() -> Result<AdminPinStatus, String> {
+    let mut state = STATE.lock().unwrap();
+    Ok(match stored(&mut state) {
+        PinRecord::Unset => AdminPinStatus {
+            configured: false,
+            set_at: None,
+            locked: false,
+        },
+        PinRecord::Set(stored) => AdminPinStatus {
+            configured: true,
+            set_at: Some(stored.set_at),
+            locked: false,
+        },
+        PinRecord::Unreadable(_) => AdminPinStatus {
+            configured: true,
+            set_at: None,
+            locked: true,
+        },
+    })
+}
+
+/// Set the admin PIN, or `None` to remove it. Changing or removing an
+/// existing PIN needs an admin session; open sessions end either way. A
+/// locked PIN can only be replaced from an elevated process, with consent.
+#[command]
+pub fn set_admin_pin(pin: Option<String>, admin_token: Option<String>) -> Result<(), String> {
+    let locked = matches!(stored(&mut STATE.lock().unwrap()), PinRecord::Unreadable(_));
+    if locked {
+        if !privilege::is_elevated() {
+            return Err(UsbShieldError::ElevationRequired {
+                action: "reset the unreadable admin PIN".to_string(),
+            }
+            .into());
+        }
+        hello::require_consent("reset the admin PIN")?;
+    } else {
+        require_admin(admin_token.as_deref(), "change the admin PIN")?;
+    }
+    let stored = match &pin {
+        Some(pin) if pin.chars().count() < MIN_PIN_LENGTH => {
+            return Err(format!("The PIN needs at least {} characters", MIN_PIN_LENGTH));
+        }
+        Some(pin) => Some(StoredPin {
+            hash: hash_pin(pin)?,
+            set_at: Utc::now(),
+        }),
+        None => None,
+    };
+    write_stored(stored.as_ref())?;
+
+    let mut state = STATE.lock().unwrap();
+    state.stored = Some(match stored {
+        Some(stored) => PinRecord::Set(stored),
+        None => PinRecord::Unset,
+    });
+    state.sessions.clear();
+    drop(state);
+    audit::record("admin_pin_changed", json!({ "configured": pin.is_some() }));
+    Ok(())
+}
+
+/// Check the PIN and open an admin session for the guarded commands.
+#[command]
+pub fn verify_admin_pin(pin: String) -> Result<AdminSession, String> {
+    let mut state = STATE.lock().unwrap();
+    let now = Instant::now();
+    if state.locked_until.is_some_and(|until| until > now) {
+        return Err("Too many wrong PINs; try again later".to_string());
+    }
+    let hash = match stored(&mut state) {
+        PinRecord::Set(stored) => stored.hash.clone(),
+        PinRecord::Unset => return Err("No admin PIN is set".to_string()),
+        PinRecord::Unreadable(_) => {
+            return Err("The admin PIN file is unreadable; an administrator must set a new PIN".to_string())
+        }
+    };
+    if !pin_matches(&pin, &hash) {
+        state.failures += 1;
+        let failures = state.failures;
+        if failures >= MAX_FAILURES {
+            state.failures = 0;
+            state.locked_until = Some(now + LOCKOUT);
+        }
+        drop(state);
+        audit::record("admin_pin_rejected", json!({ "failures": failures }));
+        return Err("Wrong PIN".to_string());
+    }
+
+    state.failures = 0;
+    state.locked_until = None;
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    state.sessions.insert(token.clone(), now + SESSION_LIFETIME);
+    drop(state);
+    audit::record("admin_session_opened", json!({ "valid_secs": SESSION_LIFETIME.as_secs() }));
+    Ok(AdminSession {
+        token,
+        expires_at: Utc::now() + chrono::Duration::from_std(SESSION_LIFETIME).unwrap(),
+    })
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::command;
+
+use super::logging::{self, LogLevel};
+use super::paging::{Page, PageRequest};
+use super::siem;
+use super::signing;
+use super::usb_config;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+lazy_static! {
+    // Serialises appends so concurrent commands never interleave lines
+    static ref AUDIT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub action: String,
+    pub details: Value,
+}
+
+/// Append one entry to the audit log (JSON lines in the app data dir), and
+/// to the operations log and SIEM forwarding. Failures are only logged; auditing must never
+/// block enforcement.
+pub fn record(action: &str, details: Value) {
+    logging::write(LogLevel::Info, "audit", action, details.clone());
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        action: action.to_string(),
+        details,
+    };
+    if let Err(e) = append(&entry) {
+        log::error!("Failed to write audit entry '{}': {}", action, e);
+    }
+    siem::forward(&entry);
+}
+
+fn append(entry: &AuditEntry) -> Result<(), String> {
+    let path = usb_config::data_file(AUDIT_FILE)?;
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+
+    let _guard = AUDIT_LOCK.lock().unwrap();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Every entry in the log, oldest first. Unparseable lines are skipped.
+pub fn read_all() -> Result<Vec<AuditEntry>, String> {
+    let path = usb_config::data_file(AUDIT_FILE)?;
+    let _guard = AUDIT_LOCK.lock().unwrap();
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Every field is optional; set fields must all match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    /// Exact action name, e.g. `"remote_usb_blocked"`
+    pub action: Option<String>,
+    /// Case-insensitive substring of the serialized details
+    pub text: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// One page of the audit log, newest first unless `oldest_first` is set.
+#[command]
+pub fn query_audit_log(
+    filter: AuditFilter,
+    oldest_first: Option<bool>,
+    page: Option<PageRequest>,
+) -> Result<Page<AuditEntry>, String> {
+    let needle = filter.text.as_deref().map(str::to_lowercase).filter(|t| !t.is_empty());
+    let mut entries: Vec<AuditEntry> = read_all()?
+        .into_iter()
+        .filter(|entry| {
+            filter.action.as_deref().is_none_or(|action| entry.action == action)
+                && filter.since.is_none_or(|since| entry.timestamp >= since)
+                && filter.until.is_none_or(|until| entry.timestamp <= until)
+                && needle
+                    .as_deref()
+                    .is_none_or(|needle| entry.details.to_string().to_lowercase().contains(needle))
+        })
+        .collect();
+    // The log is appended in order, so reversing is the sort
+    if !oldest_first.unwrap_or(false) {
+        entries.reverse();
+    }
+    Ok(page.unwrap_or_default().apply(entries))
+}
+
+/// `query_audit_log`, newest first.
+#[command]
+pub fn get_audit_log(filter: AuditFilter, page: Option<PageRequest>) -> Result<Page<AuditEntry>, String> {
+    query_audit_log(filter, None, page)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditExport {
+    pub path: String,
+    /// Detached signature file next to the export
+    pub signature_path: String,
+    pub signature: String,
+    pub entries: usize,
+}
+
+/// Export the entries between `start` and `end` (inclusive) for a review
+/// period. The file records who exported it and is HMAC signed with the
+/// machine key; the signature is written to `<path>.sig`.
+#[command]
+pub fn export_audit_range(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    format: ExportFormat,
+    path: Option<String>,
+) -> Result<AuditExport, String> {
+    if end < start {
+        return Err("End of range is before its start".to_string());
+    }
+
+    let entries: Vec<AuditEntry> = read_all()?
+        .into_iter()
+        .filter(|entry| entry.timestamp >= start && entry.timestamp <= end)
+        .collect();
+
+    let exported_by = format!(
+        "{}\\{}",
+        std::env::var("USERDOMAIN").unwrap_or_default(),
+        std::env::var("USERNAME").unwrap_or_default()
+    );
+    let exported_at = Utc::now();
+
+    let body = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&json!({
+            "exported_by": exported_by,
+            "exported_at": exported_at,
+            "computer": std::env::var("COMPUTERNAME").ok(),
+            "range": { "start": start, "end": end },
+            "entries": entries,
+        }))
+        .map_err(|e| e.to_string())?,
+        ExportFormat::Csv => {
+            let mut csv = format!(
+                "# exported_by={} exported_at={} range={}..{}\ntimestamp,action,details\n",
+                exported_by,
+                exported_at.to_rfc3339(),
+                start.to_rfc3339(),
+                end.to_rfc3339()
+            );
+            for entry in &entries {
+                csv.push_str(&format!(
+                    "{},{},{}\n",
+                    entry.timestamp.to_rfc3339(),
+                    csv_field(&entry.action),
+                    csv_field(&entry.details.to_string())
+                ));
+            }
+            csv
+        }
+    };
+
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let extension = if format == ExportFormat::Json { "json" } else { "csv" };
+            usb_config::data_file(&format!("audit-export-{}.{}", exported_at.format("%Y%m%dT%H%M%SZ"), extension))?
+        }
+    };
+    let signature = signing::sign(body.as_bytes())?;
+    let signature_path = PathBuf::from(format!("{}.sig", path.display()));
+    fs::write(&path, &body).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    fs::write(&signature_path, &signature).map_err(|e| format!("Failed to write {}: {}", signature_path.display(), e))?;
+
+    record(
+        "audit_exported",
+        json!({
+            "exported_by": exported_by,
+            "path": path.display().to_string(),
+            "range": { "start": start, "end": end },
+            "entries": entries.len(),
+        }),
+    );
+
+    Ok(AuditExport {
+        path: path.display().to_string(),
+        signature_path: signature_path.display().to_string(),
+        signature,
+        entries: entries.len(),
+    })
+}
+
+/// Export the whole log to `path`, signed like `export_audit_range`.
+#[command]
+pub fn export_audit_log(path: String, format: ExportFormat) -> Result<AuditExport, String> {
+    let entries = read_all()?;
+    let now = Utc::now();
+    let start = entries.first().map_or(now, |entry| entry.timestamp);
+    let end = entries.last().map_or(now, |entry| entry.timestamp);
+    export_audit_range(start, end, format, Some(path))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,283 @@
+use std::time::Duration;
+use once_cell::sync::OnceCell;
+use rusb::{DeviceHandle, DeviceList, GlobalContext};
+use uport_shield_helper::{
+    enforcement,
+    protocol::{ErrorCode, Failure, RemovableStoragePolicy, WakeState},
+    registry::{self, Root},
+};
+
+use super::category::{self, InterfaceClass};
+use super::correlation::{self, DevNode, InterfaceNode};
+use super::device_power::{self, SelectiveSuspend};
+use super::helper_client;
+use super::operations;
+use super::port_power;
+use super::serial_ports;
+use super::service_control;
+use super::simulation;
+use super::type_c::{self, TypeCPort};
+use super::volumes::{self, IoCounters, Volume};
+
+static CONTROLLER: OnceCell<Box<dyn UsbController>> = OnceCell::new();
+
+// Removable Disks device class
+const REMOVABLE_DISKS_POLICY_KEY: &str =
+    r"Software\Policies\Microsoft\Windows\RemovableStorageDevices\{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}";
+const SERVICE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A device as reported by the USB stack, before it is matched to a devnode.
+#[derive(Debug, Clone)]
+pub struct RawDevice {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Device descriptor class code (0x09 for hubs, 0x00 for per-interface)
+    pub device_class: u8,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub bus_number: u8,
+    /// Hub port numbers from the root hub down
+    pub ports: Vec<u8>,
+    pub interfaces: Vec<InterfaceClass>,
+}
+
+/// Everything the enforcement code needs from the machine. The hardware
+/// implementation talks to libusb, SetupAPI and the privileged helper; the
+/// simulated one serves scripted devices for development and demos.
+pub trait UsbController: Send + Sync {
+    fn list_devices(&self) -> Result<Vec<RawDevice>, String>;
+    fn devnodes(&self) -> Result<Vec<DevNode>, String>;
+    /// The `&MI_xx` functions of a composite device, addressed by the
+    /// device's instance ID
+    fn interface_nodes(&self, instance_id: &str) -> Result<Vec<InterfaceNode>, String>;
+    fn com_port(&self, node: &DevNode) -> Option<String>;
+    fn volumes(&self) -> Result<Vec<Volume>, String>;
+    fn io_counters(&self, mount_point: &str) -> Result<IoCounters, String>;
+    fn set_device_state(&self, instance_id: &str, enable: bool) -> Result<(), Failure>;
+    fn set_volume_read_only(&self, mount_point: &str, read_only: bool) -> Result<(), String>;
+    /// Safe removal: dismount the device's volumes, then eject it. The
+    /// `mount_points` we expect it to have must all belong to the device.
+    /// `force` dismounts volumes with files still open.
+    fn eject_device(&self, instance_id: &str, mount_points: &[String], force: bool) -> Result<(), Failure>;
+    /// Machine-wide write protection for every storage volume mounted from now on
+    fn set_storage_write_protect(&self, enabled: bool) -> Result<(), Failure>;
+    /// The machine-wide write protection in force, which outlives a restart
+    fn storage_write_protect(&self) -> Result<bool, String>;
+    /// Machine-wide removable storage policies per device class, as Group
+    /// Policy writes them
+    fn removable_storage_policies(&self) -> Result<Vec<RemovableStoragePolicy>, String>;
+    fn set_removable_storage_policy(&self, policy: &RemovableStoragePolicy) -> Result<(), Failure>;
+    fn selective_suspend(&self, instance_id: &str) -> Result<SelectiveSuspend, String>;
+    fn set_selective_suspend(&self, instance_id: &str, enabled: bool) -> Result<(), String>;
+    /// Wake-programmable devices by the name the power manager knows them under
+    fn wake_devices(&self) -> Result<Vec<WakeState>, String>;
+    fn set_wake_enabled(&self, name: &str, enabled: bool) -> Result<(), String>;
+    fn type_c_ports(&self) -> Result<Vec<TypeCPort>, String>;
+    /// Switch power to one downstream port of a hub, by the hub's instance ID
+    fn set_port_power(&self, hub_instance_id: &str, port: u8, on: bool) -> Result<(), String>;
+    fn apply_policy(&self, usbstor_start: u32) -> Result<(), Failure>;
+    /// The USBSTOR `Start` value in force, `None` if it cannot be read
+    fn usbstor_start(&self) -> Option<u32>;
+    fn restart_storage_service(&self) -> Result<(), String>;
+}
+
+/// Pick the backend. Called once from setup; the simulated backend is used
+/// when the `simulation` feature is enabled or `USB_SHIELD_SIMULATION` is set.
+pub fn init() -> Result<(), String> {
+    let controller: Box<dyn UsbController> = match simulation::requested() {
+        Some(script) => {
+            simulation::load_script_file(script.as_deref())?;
+            Box::new(simulation::SimulatedController)
+        }
+        None => Box::new(HardwareController),
+    };
+    // A second init keeps the first backend
+    let _ = install(controller);
+    Ok(())
+}
+
+/// Use `controller` for the rest of the process. Fails once a backend is in
+/// use, since enforcement state would otherwise refer to the wrong machine.
+pub fn install(controller: Box<dyn UsbController>) -> Result<(), String> {
+    CONTROLLER
+        .set(controller)
+        .map_err(|_| "A USB backend is already installed".to_string())
+}
+
+pub fn controller() -> &'static dyn UsbController {
+    CONTROLLER.get_or_init(|| Box::new(HardwareController)).as_ref()
+}
+
+pub struct HardwareController;
+
+impl UsbController for HardwareController {
+    fn list_devices(&self) -> Result<Vec<RawDevice>, String> {
+        let devices = DeviceList::new().map_err(|e| e.to_string())?;
+        let mut result = Vec::new();
+
+        for device in devices.iter() {
+            let descriptor = device.device_descriptor().map_err(|e| e.to_string())?;
+
+            let (manufacturer, product, serial_number) = match device.open() {
+                Ok(handle) => (
+                    read_usb_string(&handle, descriptor.manufacturer_string_index()),
+                    read_usb_string(&handle, descriptor.product_string_index()),
+                    read_usb_string(&handle, descriptor.serial_number_string_index())
+                ),
+                Err(_) => (None, None, None),
+            };
+
+            result.push(RawDevice {
+                vendor_id: descriptor.vendor_id(),
+                product_id: descriptor.product_id(),
+                device_class: descriptor.class_code(),
+                manufacturer,
+                product,
+                serial_number,
+                bus_number: device.bus_number(),
+                ports: device.port_numbers().unwrap_or_default(),
+                interfaces: category::read_interface_classes(&device),
+            });
+        }
+
+        Ok(result)
+    }
+
+    fn devnodes(&self) -> Result<Vec<DevNode>, String> {
+        correlation::enumerate_usb_devnodes()
+    }
+
+    fn interface_nodes(&self, instance_id: &str) -> Result<Vec<InterfaceNode>, String> {
+        correlation::enumerate_interface_devnodes(instance_id)
+    }
+
+    fn com_port(&self, node: &DevNode) -> Option<String> {
+        serial_ports::com_port(node.dev_inst)
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>, String> {
+        volumes::enumerate_volumes()
+    }
+
+    fn io_counters(&self, mount_point: &str) -> Result<IoCounters, String> {
+        volumes::io_counters(mount_point)
+    }
+
+    fn set_device_state(&self, instance_id: &str, enable: bool) -> Result<(), Failure> {
+        helper_client::set_device_state(instance_id, enable)
+    }
+
+    fn set_volume_read_only(&self, mount_point: &str, read_only: bool) -> Result<(), String> {
+        helper_client::set_volume_read_only(mount_point, read_only)
+    }
+
+    fn eject_device(&self, instance_id: &str, mount_points: &[String], force: bool) -> Result<(), Failure> {
+        helper_client::eject_device(instance_id, mount_points, force)
+    }
+
+    fn set_storage_write_protect(&self, enabled: bool) -> Result<(), Failure> {
+        helper_client::set_storage_write_protect(enabled)?;
+        // The per-user "Removable Disks: Deny write access" policy, which
+        // Explorer and the shell honour without a remount
+        let result = if enabled {
+            registry::create(Root::CurrentUser, REMOVABLE_DISKS_POLICY_KEY)
+                .and_then(|policy| policy.set_dword("Deny_Write", 1))
+        } else {
+            registry::open(Root::CurrentUser, REMOVABLE_DISKS_POLICY_KEY)
+                .and_then(|policy| policy.map_or(Ok(()), |policy| policy.delete_value("Deny_Write")))
+        };
+        result.map_err(|e| Failure {
+            message: format!("Failed to update the removable disk policy: {}", e),
+            code: Some(ErrorCode::Win32 { code: e.code }),
+        })
+    }
+
+    fn storage_write_protect(&self) -> Result<bool, String> {
+        enforcement::storage_write_protect()
+    }
+
+    fn removable_storage_policies(&self) -> Result<Vec<RemovableStoragePolicy>, String> {
+        // Reading HKLM needs no elevation, so no round trip to the helper
+        enforcement::removable_storage_policies()
+    }
+
+    fn set_removable_storage_policy(&self, policy: &RemovableStoragePolicy) -> Result<(), Failure> {
+        helper_client::set_removable_storage_policy(policy)
+    }
+
+    fn selective_suspend(&self, instance_id: &str) -> Result<SelectiveSuspend, String> {
+        device_power::read_selective_suspend(instance_id)
+    }
+
+    fn set_selective_suspend(&self, instance_id: &str, enabled: bool) -> Result<(), String> {
+        helper_client::set_selective_suspend(instance_id, enabled)
+    }
+
+    fn wake_devices(&self) -> Result<Vec<WakeState>, String> {
+        // Querying needs no elevation; the helper's code is reused as is
+        enforcement::wake_devices()
+    }
+
+    fn set_wake_enabled(&self, name: &str, enabled: bool) -> Result<(), String> {
+        helper_client::set_wake_enabled(name, enabled)
+    }
+
+    fn type_c_ports(&self) -> Result<Vec<TypeCPort>, String> {
+        type_c::enumerate_type_c_ports()
+    }
+
+    fn set_port_power(&self, hub_instance_id: &str, port: u8, on: bool) -> Result<(), String> {
+        let hub = self
+            .devnodes()?
+            .into_iter()
+            .find(|node| node.instance_id.eq_ignore_ascii_case(hub_instance_id))
+            .ok_or_else(|| format!("Hub not found: {}", hub_instance_id))?;
+        let devices = DeviceList::new().map_err(|e| e.to_string())?;
+        let device = devices
+            .iter()
+            .find(|device| {
+                device
+                    .device_descriptor()
+                    .is_ok_and(|d| d.vendor_id() == hub.vendor_id && d.product_id() == hub.product_id)
+                    && device.port_numbers().is_ok_and(|ports| ports == hub.port_chain)
+            })
+            .ok_or_else(|| format!("Hub not found on the bus: {}", hub_instance_id))?;
+        // The inbox hub driver keeps class requests to itself; only hubs
+        // bound to WinUSB (or libusbK) can be opened from user mode
+        let handle = device
+            .open()
+            .map_err(|e| format!("Cannot send hub requests to {}: {}", hub_instance_id, e))?;
+        port_power::switch_port_power(&handle, port, on)
+    }
+
+    fn apply_policy(&self, usbstor_start: u32) -> Result<(), Failure> {
+        helper_client::apply_policy(usbstor_start)
+    }
+
+    fn usbstor_start(&self) -> Option<u32> {
+        enforcement::read_state(&[]).usbstor_start
+    }
+
+    fn restart_storage_service(&self) -> Result<(), String> {
+        operations::stage("Stopping the USB storage service");
+        service_control::stop("USBSTOR", SERVICE_TIMEOUT)?;
+
+        operations::stage("Starting the USB storage service");
+        service_control::start("USBSTOR", SERVICE_TIMEOUT)?;
+
+        operations::stage("Refreshing Group Policy");
+        service_control::refresh_policy()?;
+
+        Ok(())
+    }
+}
+
+fn read_usb_string(handle: &DeviceHandle<GlobalContext>, index: Option<u8>) -> Option<String> {
+    match index {
+        Some(idx) if idx != 0 => {
+            handle.read_string_descriptor_ascii(idx).ok()
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+use uport_shield_helper::protocol::{GuardBinding, GuardPolicy, GuardStatus};
+
+use super::audit;
+use super::class_policy;
+use super::commands;
+use super::helper_client;
+use super::simulation;
+use super::trust_rules;
+use super::usb_control::{self, BlockReason};
+
+// Well inside the service's grace period, so it never takes over while we run
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether protection carries on once this window is closed.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackgroundProtection {
+    pub helper_running: bool,
+    /// Last status from the helper service, if it is installed
+    pub guard: Option<GuardStatus>,
+}
+
+lazy_static! {
+    static ref LAST: Mutex<Option<GuardStatus>> = Mutex::new(None);
+}
+
+pub fn start() {
+    // Simulated devices mean nothing to the real service
+    if simulation::is_active() {
+        return;
+    }
+    thread::spawn(|| loop {
+        if let Err(e) = sync() {
+            log::error!("Background protection sync failed: {}", e);
+        }
+        thread::sleep(SYNC_INTERVAL);
+    });
+}
+
+/// Hand the helper service the rules to enforce while we are gone, and take
+/// over the devices it blocked while we were.
+fn sync() -> Result<(), String> {
+    if !helper_client::is_running() {
+        *LAST.lock().unwrap() = None;
+        return Ok(());
+    }
+    let state = commands::app_state();
+    let policy = GuardPolicy {
+        autoblock: state.autoblock_enabled(),
+        trusted: state.trusted_devices(),
+        bindings: trust_rules::get_trust_bindings()?
+            .into_iter()
+            .map(|binding| GuardBinding {
+                vendor_id: binding.vendor_id,
+                product_id: binding.product_id,
+                serial: binding.serial,
+            })
+            .collect(),
+        allowed_classes: class_policy::allowed_classes(),
+    };
+    let status = helper_client::sync_guard(policy)?;
+    if !status.blocked.is_empty() {
+        for block in &status.blocked {
+            // Still disabled; this only records it as ours
+            if let Err(e) = usb_control::block(&block.instance_id, BlockReason::Autoblock) {
+                log::error!("Failed to adopt {} from the helper service: {}", block.instance_id, e);
+            }
+        }
+        audit::record("background_blocks_adopted", json!({ "blocked": status.blocked }));
+    }
+    *LAST.lock().unwrap() = Some(status);
+    Ok(())
+}
+
+#[command]
+pub fn get_background_protection() -> Result<BackgroundProtection, String> {
+    Ok(BackgroundProtection {
+        helper_running: helper_client::is_running(),
+        guard: LAST.lock().unwrap().clone(),
+    })
+}
@@ -0,0 +1,184 @@
+use rusb::{Device, UsbContext};
+use serde::{Deserialize, Serialize};
+
+// USB-IF base class codes we care about
+pub const CLASS_PER_INTERFACE: u8 = 0x00;
+pub const CLASS_AUDIO: u8 = 0x01;
+pub const CLASS_CDC: u8 = 0x02;
+pub const CLASS_HID: u8 = 0x03;
+pub const CLASS_IMAGE: u8 = 0x06;
+pub const CLASS_PRINTER: u8 = 0x07;
+pub const CLASS_MASS_STORAGE: u8 = 0x08;
+pub const CLASS_HUB: u8 = 0x09;
+pub const CLASS_SMART_CARD: u8 = 0x0B;
+pub const CLASS_VIDEO: u8 = 0x0E;
+pub const CLASS_WIRELESS: u8 = 0xE0;
+pub const CLASS_MISC: u8 = 0xEF;
+pub const CLASS_VENDOR_SPECIFIC: u8 = 0xFF;
+
+const HID_SUBCLASS_BOOT: u8 = 0x01;
+const HID_PROTOCOL_KEYBOARD: u8 = 0x01;
+const HID_PROTOCOL_MOUSE: u8 = 0x02;
+
+// Vendors that only ship FIDO/U2F tokens on plain (non-boot) HID interfaces
+const SECURITY_KEY_VENDORS: &[u16] = &[
+    0x1050, // Yubico
+    0x096E, // Feitian
+    0x20A0, // Nitrokey
+    0x1209, // pid.codes (SoloKeys)
+    0x311F, // Token2
+];
+
+// Vendors whose vendor-specific and CDC functions are cellular modems
+const MODEM_VENDORS: &[u16] = &[
+    0x1199, // Sierra Wireless
+    0x12D1, // Huawei
+    0x19D2, // ZTE
+    0x2C7C, // Quectel
+    0x1BC7, // Telit
+    0x2CB7, // Fibocom
+    0x1410, // Novatel
+];
+
+// Wi-Fi chipset and dongle vendors whose vendor-specific interfaces are WLAN
+// radios. Realtek is left out: its vendor-specific functions are as often
+// the RTL815x Ethernet adapters.
+const WIFI_VENDORS: &[u16] = &[
+    0x148F, // Ralink / MediaTek
+    0x0CF3, // Qualcomm Atheros
+    0x2357, // TP-Link
+    0x0846, // Netgear
+    0x7392, // Edimax
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeviceCategory {
+    Storage,
+    Keyboard,
+    Mouse,
+    Audio,
+    Camera,
+    Network,
+    /// 3G/4G/5G modem: a network path that bypasses the corporate proxy
+    CellularModem,
+    /// USB Wi-Fi adapter, which can join any nearby network
+    WifiAdapter,
+    Hub,
+    SecurityKey,
+    Other,
+}
+
+/// Class triple of a single interface (or of the device descriptor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceClass {
+    pub class_code: u8,
+    pub sub_class_code: u8,
+    pub protocol_code: u8,
+}
+
+/// Read the class triples of every interface of the active configuration.
+/// Works without opening the device, so it succeeds even without a driver.
+pub fn read_interface_classes<T: UsbContext>(device: &Device<T>) -> Vec<InterfaceClass> {
+    let config = match device.active_config_descriptor() {
+        Ok(config) => config,
+        Err(_) => match device.config_descriptor(0) {
+            Ok(config) => config,
+            Err(_) => return Vec::new(),
+        },
+    };
+
+    config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        // Alternate settings repeat the class triple; keep the first of each
+        .filter(|descriptor| descriptor.setting_number() == 0)
+        .map(|descriptor| InterfaceClass {
+            class_code: descriptor.class_code(),
+            sub_class_code: descriptor.sub_class_code(),
+            protocol_code: descriptor.protocol_code(),
+        })
+        .collect()
+}
+
+/// CDC-ECM/EEM/NCM/MBIM and RNDIS (wireless-controller or misc flavour).
+/// CDC-ACM is deliberately excluded: that is a serial port, not a NIC.
+pub fn is_network_interface(interface: &InterfaceClass) -> bool {
+    matches!(
+        (interface.class_code, interface.sub_class_code, interface.protocol_code),
+        (CLASS_CDC, 0x06, _)
+            | (CLASS_CDC, 0x0C, _)
+            | (CLASS_CDC, 0x0D, _)
+            | (CLASS_CDC, 0x0E, _)
+            | (CLASS_WIRELESS, 0x01, 0x03)
+            | (CLASS_MISC, 0x04, 0x01)
+    )
+}
+
+/// MBIM from any vendor, or a CDC/vendor-specific function of a modem vendor.
+fn is_cellular_modem(vendor_id: u16, interfaces: &[InterfaceClass]) -> bool {
+    interfaces.iter().any(|i| i.class_code == CLASS_CDC && i.sub_class_code == 0x0E)
+        || (MODEM_VENDORS.contains(&vendor_id)
+            && interfaces
+                .iter()
+                .any(|i| is_network_interface(i) || matches!(i.class_code, CLASS_CDC | CLASS_VENDOR_SPECIFIC)))
+}
+
+/// Wi-Fi dongles have no class of their own; only the vendor gives them away.
+fn is_wifi_adapter(vendor_id: u16, interfaces: &[InterfaceClass]) -> bool {
+    WIFI_VENDORS.contains(&vendor_id) && interfaces.iter().any(|i| i.class_code == CLASS_VENDOR_SPECIFIC)
+}
+
+/// Whether any interface is a boot keyboard, which is what a keystroke
+/// injector presents whatever else it carries.
+pub fn has_keyboard(interfaces: &[InterfaceClass]) -> bool {
+    interfaces.iter().any(|i| {
+        i.class_code == CLASS_HID && i.sub_class_code == HID_SUBCLASS_BOOT && i.protocol_code == HID_PROTOCOL_KEYBOARD
+    })
+}
+
+/// Pick one coarse category for a device. Composite devices are classified
+/// by their most security-relevant function: a "keyboard" that also exposes
+/// storage is reported as Storage, a webcam with a microphone as Camera.
+pub fn classify(device_class: u8, vendor_id: u16, interfaces: &[InterfaceClass]) -> DeviceCategory {
+    if device_class == CLASS_HUB {
+        return DeviceCategory::Hub;
+    }
+
+    let has = |class: u8| interfaces.iter().any(|i| i.class_code == class);
+
+    if has(CLASS_MASS_STORAGE) {
+        return DeviceCategory::Storage;
+    }
+    if has(CLASS_SMART_CARD) || (has(CLASS_HID) && SECURITY_KEY_VENDORS.contains(&vendor_id)) {
+        return DeviceCategory::SecurityKey;
+    }
+    if has(CLASS_VIDEO) || has(CLASS_IMAGE) {
+        return DeviceCategory::Camera;
+    }
+    if is_cellular_modem(vendor_id, interfaces) {
+        return DeviceCategory::CellularModem;
+    }
+    if is_wifi_adapter(vendor_id, interfaces) {
+        return DeviceCategory::WifiAdapter;
+    }
+    if interfaces.iter().any(is_network_interface) {
+        return DeviceCategory::Network;
+    }
+
+    let boot_hid = |protocol: u8| {
+        interfaces.iter().any(|i| {
+            i.class_code == CLASS_HID && i.sub_class_code == HID_SUBCLASS_BOOT && i.protocol_code == protocol
+        })
+    };
+    if boot_hid(HID_PROTOCOL_KEYBOARD) {
+        return DeviceCategory::Keyboard;
+    }
+    if boot_hid(HID_PROTOCOL_MOUSE) {
+        return DeviceCategory::Mouse;
+    }
+    if has(CLASS_AUDIO) {
+        return DeviceCategory::Audio;
+    }
+
+    DeviceCategory::Other
+}
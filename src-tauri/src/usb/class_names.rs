@@ -0,0 +1,130 @@
+use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+use super::category::{InterfaceClass, CLASS_PER_INTERFACE};
+
+/// Languages the class table is translated into. Anything else falls back
+/// to English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English = 0,
+    German = 1,
+    French = 2,
+}
+
+// Primary language IDs, the low ten bits of a LANGID
+const LANG_GERMAN: u16 = 0x07;
+const LANG_FRENCH: u16 = 0x0C;
+
+impl Language {
+    /// The Windows display language of the current user.
+    pub fn current() -> Language {
+        match unsafe { GetUserDefaultUILanguage() } & 0x3FF {
+            LANG_GERMAN => Language::German,
+            LANG_FRENCH => Language::French,
+            _ => Language::English,
+        }
+    }
+}
+
+type Names = [&'static str; 3];
+// (class, subclass, protocol)
+type FunctionKey = (u8, Option<u8>, Option<u8>);
+
+// USB-IF base classes
+const CLASSES: &[(u8, Names)] = &[
+    (0x01, ["Audio", "Audio", "Audio"]),
+    (0x02, ["Communications (CDC)", "Kommunikation (CDC)", "Communications (CDC)"]),
+    (0x03, ["HID", "HID", "HID"]),
+    (0x05, ["Physical", "Physisch", "Physique"]),
+    (0x06, ["Image", "Bild", "Image"]),
+    (0x07, ["Printer", "Drucker", "Imprimante"]),
+    (0x08, ["Mass Storage", "Massenspeicher", "Stockage de masse"]),
+    (0x09, ["Hub", "Hub", "Concentrateur"]),
+    (0x0A, ["CDC Data", "CDC-Daten", "Données CDC"]),
+    (0x0B, ["Smart Card", "Smartcard", "Carte à puce"]),
+    (0x0D, ["Content Security", "Inhaltsschutz", "Sécurité du contenu"]),
+    (0x0E, ["Video", "Video", "Vidéo"]),
+    (0x0F, ["Personal Healthcare", "Gesundheitsgerät", "Santé personnelle"]),
+    (0x10, ["Audio/Video", "Audio/Video", "Audio/Vidéo"]),
+    (0x11, ["Billboard", "Billboard", "Billboard"]),
+    (0x12, ["Type-C Bridge", "Type-C-Brücke", "Pont Type-C"]),
+    (0xDC, ["Diagnostic", "Diagnose", "Diagnostic"]),
+    (0xE0, ["Wireless Controller", "Drahtlos-Controller", "Contrôleur sans fil"]),
+    (0xEF, ["Miscellaneous", "Sonstiges", "Divers"]),
+    (0xFE, ["Application Specific", "Anwendungsspezifisch", "Spécifique à l'application"]),
+    (0xFF, ["Vendor Specific", "Herstellerspezifisch", "Spécifique au fabricant"]),
+];
+
+// `None` matches any. Specific entries first.
+const FUNCTIONS: &[(FunctionKey, Names)] = &[
+    ((0x01, Some(0x01), None), ["Control", "Steuerung", "Contrôle"]),
+    ((0x01, Some(0x02), None), ["Streaming", "Streaming", "Streaming"]),
+    ((0x01, Some(0x03), None), ["MIDI", "MIDI", "MIDI"]),
+    ((0x02, Some(0x02), None), ["Serial (ACM)", "Seriell (ACM)", "Série (ACM)"]),
+    ((0x02, Some(0x06), None), ["Ethernet (ECM)", "Ethernet (ECM)", "Ethernet (ECM)"]),
+    ((0x02, Some(0x0C), None), ["Ethernet (EEM)", "Ethernet (EEM)", "Ethernet (EEM)"]),
+    ((0x02, Some(0x0D), None), ["Network (NCM)", "Netzwerk (NCM)", "Réseau (NCM)"]),
+    ((0x02, Some(0x0E), None), ["Mobile Broadband (MBIM)", "Mobilfunk (MBIM)", "Haut débit mobile (MBIM)"]),
+    ((0x03, Some(0x01), Some(0x01)), ["Keyboard", "Tastatur", "Clavier"]),
+    ((0x03, Some(0x01), Some(0x02)), ["Mouse", "Maus", "Souris"]),
+    ((0x06, Some(0x01), Some(0x01)), ["Still Camera (PTP)", "Kamera (PTP)", "Appareil photo (PTP)"]),
+    ((0x08, Some(0x04), None), ["Floppy (UFI)", "Diskette (UFI)", "Disquette (UFI)"]),
+    ((0x08, Some(0x06), Some(0x62)), ["SCSI (UAS)", "SCSI (UAS)", "SCSI (UAS)"]),
+    ((0x08, Some(0x06), None), ["SCSI transparent", "SCSI transparent", "SCSI transparent"]),
+    ((0x09, None, Some(0x00)), ["Full Speed", "Full Speed", "Full Speed"]),
+    ((0x09, None, Some(0x01)), ["High Speed", "High Speed", "High Speed"]),
+    ((0x09, None, Some(0x02)), ["High Speed, Multi-TT", "High Speed, Multi-TT", "High Speed, multi-TT"]),
+    ((0x09, None, Some(0x03)), ["SuperSpeed", "SuperSpeed", "SuperSpeed"]),
+    ((0x0E, Some(0x01), None), ["Control", "Steuerung", "Contrôle"]),
+    ((0x0E, Some(0x02), None), ["Streaming", "Streaming", "Streaming"]),
+    ((0xE0, Some(0x01), Some(0x01)), ["Bluetooth", "Bluetooth", "Bluetooth"]),
+    ((0xE0, Some(0x01), Some(0x03)), ["RNDIS", "RNDIS", "RNDIS"]),
+    ((0xEF, Some(0x02), Some(0x01)), ["Composite", "Verbundgerät", "Composite"]),
+    ((0xEF, Some(0x04), Some(0x01)), ["RNDIS", "RNDIS", "RNDIS"]),
+    (
+        (0xFE, Some(0x01), Some(0x01)),
+        ["Firmware Update (DFU)", "Firmware-Update (DFU)", "Mise à jour du firmware (DFU)"],
+    ),
+    ((0xFE, Some(0x03), None), ["Test & Measurement", "Messgerät", "Test et mesure"]),
+];
+
+const UNKNOWN_CLASS: Names = ["Class", "Klasse", "Classe"];
+
+fn base_name(language: Language, class_code: u8) -> String {
+    match CLASSES.iter().find(|(code, _)| *code == class_code) {
+        Some((_, names)) => names[language as usize].to_string(),
+        None => format!("{} 0x{:02X}", UNKNOWN_CLASS[language as usize], class_code),
+    }
+}
+
+/// Display name of one class triple, e.g. "HID – Keyboard" or
+/// "Mass Storage – SCSI transparent".
+pub fn display_name(language: Language, class: &InterfaceClass) -> String {
+    let base = base_name(language, class.class_code);
+    let function = FUNCTIONS.iter().find(|((code, sub_class, protocol), _)| {
+        *code == class.class_code
+            && sub_class.is_none_or(|s| s == class.sub_class_code)
+            && protocol.is_none_or(|p| p == class.protocol_code)
+    });
+    match function {
+        Some((_, names)) => format!("{} – {}", base, names[language as usize]),
+        None => base,
+    }
+}
+
+/// Names for each distinct interface function, preceded by the device
+/// descriptor class when no interface already names it.
+pub fn device_class_names(language: Language, device_class: u8, interfaces: &[InterfaceClass]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    if device_class != CLASS_PER_INTERFACE && !interfaces.iter().any(|i| i.class_code == device_class) {
+        // Only the base class is known for the device descriptor
+        names.push(base_name(language, device_class));
+    }
+    for class in interfaces {
+        let name = display_name(language, class);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::category::CLASS_PER_INTERFACE;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::dry_run;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_control::BlockReason;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClassAction {
+    /// Untrusted devices made only of allowed classes skip autoblock
+    Allow,
+    /// Untrusted devices with any interface of the class are disabled
+    Block,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassPolicy {
+    /// USB-IF base class code, e.g. 0x08 for mass storage
+    pub class_code: u8,
+    pub action: ClassAction,
+}
+
+lazy_static! {
+    static ref POLICIES: Mutex<BTreeMap<u8, ClassAction>> = Mutex::new(BTreeMap::new());
+}
+
+/// Base classes a device declares: bDeviceClass if set, plus every interface.
+fn classes_of(device: &UsbDeviceInfo) -> Vec<u8> {
+    let mut classes: Vec<u8> = device.interfaces().iter().map(|i| i.class_code).collect();
+    if device.device_class() != CLASS_PER_INTERFACE {
+        classes.push(device.device_class());
+    }
+    classes
+}
+
+/// The class a Block policy matches on the device, if any. Block wins over
+/// Allow, so a keyboard that also exposes storage is blocked with storage.
+pub fn blocked_class(device: &UsbDeviceInfo) -> Option<u8> {
+    let policies = POLICIES.lock().unwrap();
+    classes_of(device)
+        .into_iter()
+        .find(|class| policies.get(class) == Some(&ClassAction::Block))
+}
+
+/// Whether every class of the device is explicitly allowed.
+pub fn allowed(device: &UsbDeviceInfo) -> bool {
+    let policies = POLICIES.lock().unwrap();
+    let classes = classes_of(device);
+    !classes.is_empty() && classes.iter().all(|class| policies.get(class) == Some(&ClassAction::Allow))
+}
+
+/// Classes with an Allow policy, for the helper service's guard.
+pub fn allowed_classes() -> Vec<u8> {
+    POLICIES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, action)| **action == ClassAction::Allow)
+        .map(|(class, _)| *class)
+        .collect()
+}
+
+/// Disable an untrusted, running device that has a blocked class. Returns
+/// whether it was disabled.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    if device.trusted() || device.state() != DeviceState::Connected {
+        return false;
+    }
+    let class = match blocked_class(device) {
+        Some(class) => class,
+        None => return false,
+    };
+    let result = commands::block_device_for(
+        device.vendor_id(),
+        device.product_id(),
+        None,
+        device.instance_id().map(str::to_string),
+        BlockReason::ClassPolicy,
+    );
+    audit::record(
+        "class_policy_blocked",
+        json!({
+            "instance_id": device.instance_id(),
+            "class_code": class,
+            "error": result.as_ref().err(),
+        }),
+    );
+    if result.is_ok() {
+        notifications::notify(
+            Severity::Warning,
+            "class_policy_blocked",
+            "USB device blocked by class policy",
+            device.product().or(device.instance_id()).unwrap_or_default(),
+        );
+    }
+    result.is_ok()
+}
+
+#[command]
+pub fn get_class_policies() -> Result<Vec<ClassPolicy>, String> {
+    Ok(POLICIES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(class_code, action)| ClassPolicy {
+            class_code: *class_code,
+            action: *action,
+        })
+        .collect())
+}
+
+/// Set the action for one class, or clear it with `None`. A new Block
+/// applies to attached devices straight away. Lifting a Block or adding an
+/// Allow needs the admin PIN.
+#[command]
+pub fn set_class_policy(
+    class_code: u8,
+    action: Option<ClassAction>,
+    admin_token: Option<String>,
+) -> Result<(), String> {
+    dry_run::refuse("set_class_policy")?;
+    let previous = POLICIES.lock().unwrap().get(&class_code).copied();
+    let relaxed = (previous == Some(ClassAction::Block) && action != Some(ClassAction::Block))
+        || (action == Some(ClassAction::Allow) && previous != Some(ClassAction::Allow));
+    if relaxed {
+        admin_pin::require_admin(admin_token.as_deref(), "relax a USB class policy")?;
+        hello::require_consent("relax a USB class policy")?;
+    }
+    {
+        let mut policies = POLICIES.lock().unwrap();
+        match action {
+            Some(action) => policies.insert(class_code, action),
+            None => policies.remove(&class_code),
+        };
+    }
+    audit::record(
+        "class_policy_changed",
+        json!({ "class_code": class_code, "previous": previous, "current": action }),
+    );
+
+    if action == Some(ClassAction::Block) {
+        for device in commands::get_usb_devices()? {
+            enforce(&device);
+        }
+    }
+    Ok(())
+}
@@ -1,361 +1,1296 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fs,
     sync::{Arc, Mutex},
-    ffi::CStr
+    time::Duration,
 };
 use lazy_static::lazy_static;
-use rusb::{Context, Device, DeviceDescriptor, DeviceHandle, DeviceList, GlobalContext};
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use tauri::command;
-use windows::{
-    core::{PCSTR, PCWSTR},
-    Win32::{
-        Devices::DeviceAndDriverInstallation::{
-            CM_Locate_DevNodeW, CM_Reenumerate_DevNode, CR_SUCCESS, DIGCF_ALLCLASSES, 
-            DIGCF_PRESENT, SetupDiClassGuidsFromNameW, SetupDiCreateDeviceInfoList, 
-            SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW, 
-            SetupDiGetDeviceInstanceIdW, SetupDiOpenDevRegKey, SetupDiSetDeviceRegistryPropertyW,
-            SP_DEVINFO_DATA, SPDRP_CONFIGFLAGS, DIREG_DEV, DICS_DISABLE, DICS_ENABLE,
-            SetupDiCallClassInstaller, DIF_PROPERTYCHANGE, SP_CLASSINSTALL_HEADER,
-            SP_PROPCHANGE_PARAMS, CM_GETIDLIST_FILTER_PRESENT, CM_Get_Device_ID_List_SizeW,
-            CM_Get_Device_ID_ListW, CM_Get_Device_Interface_List_SizeW, CM_Get_Device_Interface_ListW,
-            CM_Get_Device_Interface_PropertyW, CM_Get_DevNode_PropertyW, CM_Get_DevNode_Status,
-            CM_Locate_DevNodeW, CM_Reenumerate_DevNode, CR_SUCCESS, DIGCF_ALLCLASSES, 
-            DIGCF_DEVICEINTERFACE, DIGCF_PRESENT, SetupDiClassGuidsFromNameW, 
-            SetupDiCreateDeviceInfoList, SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, 
-            SetupDiGetClassDevsW, SetupDiGetDeviceInstanceIdW, SetupDiOpenDevRegKey, 
-            SetupDiSetDeviceRegistryPropertyW, SP_DEVINFO_DATA, SPDRP_CONFIGFLAGS, DIREG_DEV,
-        },
-        Foundation::{HANDLE, HWND, ERROR_SUCCESS},
-        Storage::FileSystem::INVALID_HANDLE_VALUE,
-        System::{
-            Registry::{RegCloseKey, RegSetValueExA, HKEY, KEY_SET_VALUE, REG_DWORD},
-            Threading::{GetCurrentProcess, GetCurrentThreadId},
-        },
-    },
-};
+use serde_json::json;
+use tauri::{command, State};
+use uport_shield_helper::registry::{self, RegistryError, Root};
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::category::{self, DeviceCategory, InterfaceClass};
+use super::class_names::{self, Language};
+use super::class_policy;
+use super::correlation;
+use super::device_labels;
+use super::docks;
+use super::dry_run::{self, Planned, PlannedStep, PolicyPlan};
+use super::drivers::{self, DriverInfo, DriverSignature, FunctionDriver};
+use super::email_alerts;
+use super::error::UsbShieldError;
+use super::etw::{self, TraceEvent};
+use super::events;
+use super::forensics;
+use super::guest;
+use super::hello;
+use super::hotplug;
+use super::inventory::{self, Sighting};
+use super::notifications::{self, Severity};
+use super::operations;
+use super::pause;
+use super::quarantine;
+use super::reblock::{self, ReblockStatus, ReblockTarget};
+use super::remote::{self, Attachment};
+use super::rollback;
+use super::scheduler::{self, AutoblockSensitivity};
+use super::security_key;
+use super::storage_readonly;
+use super::tamper;
+use super::threats;
+use super::transfers;
+use super::trust_rules::{self, BindingVerdict};
+use super::usb_config;
+use super::usb_control::{self, BlockReason, StateChange};
+use super::usb_names;
+use super::volumes::{IoCounters, Volume};
+
+const TRUSTED_DEVICES_FILE: &str = "trusted-devices.json";
+pub const REMOVABLE_STORAGE_POLICY_KEY: &str = r"Software\Policies\Microsoft\Windows\RemovableStorageDevices";
+// Removable Disks, below the key above
+const REMOVABLE_DISKS_CLASS: &str = "{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}";
+const USBSTOR_KEY: &str = r"SYSTEM\CurrentControlSet\Services\USBSTOR";
+pub const EVENT_DEVICE_AUTOBLOCKED: &str = "usb://device-autoblocked";
+pub const EVENT_TEMPORARY_TRUST_EXPIRED: &str = "usb://temporary-trust-expired";
+
+/// The whitelist and the autoblock switch. A cheap handle: clones share the
+/// same lists. There is one per process, behind `app_state()`, which is where
+/// hotplug, timers, tests and most commands read it. Tauri manages a clone of
+/// it only so the trust and autoblock commands can take `State<'_, AppState>`;
+/// that is not a seam for giving them different state.
+#[derive(Clone)]
+pub struct AppState {
+    trusted_devices: Arc<Mutex<HashSet<(u16, u16)>>>,
+    autoblock_enabled: Arc<Mutex<bool>>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState {
+            trusted_devices: Arc::new(Mutex::new(HashSet::new())),
+            autoblock_enabled: Arc::new(Mutex::new(true)),
+        }
+    }
+}
 
+static APP_STATE: OnceCell<AppState> = OnceCell::new();
 
+/// The state the app runs on. `app.manage()` registers a clone of this one.
+pub fn app_state() -> &'static AppState {
+    APP_STATE.get_or_init(AppState::default)
+}
 
-// Shared state for trusted devices
 lazy_static! {
-    static ref TRUSTED_DEVICES: Arc<Mutex<HashSet<(u16, u16)>>> = Arc::new(Mutex::new(HashSet::new()));
-    static ref AUTOBLOCK_ENABLED: Arc<Mutex<bool>> = Arc::new(Mutex::new(true));
+    // Time-limited grants; never written to the whitelist file, so a restart ends them
+    // Model -> id of the re-block schedule of the grant currently in force
+    static ref TEMPORARY_TRUST: Mutex<HashMap<(u16, u16), u64>> = Mutex::new(HashMap::new());
+    // Last category seen per instance ID, so disabled devices keep theirs
+    static ref KNOWN_CATEGORIES: Mutex<HashMap<String, DeviceCategory>> = Mutex::new(HashMap::new());
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceState {
+    /// Present and its driver is running
+    Connected,
+    /// Present but disabled
+    Blocked,
+    /// Present and held disabled until the user decides what it is
+    Quarantined,
+    /// Blocked by us earlier and no longer attached
+    Disconnected,
+    /// Seen by libusb but could not be matched to a devnode
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsbDeviceInfo {
     vendor_id: u16,
     product_id: u16,
     manufacturer: Option<String>,
     product: Option<String>,
+    /// Names from the USB ID database, for when the device's own strings
+    /// cannot be read
+    vendor_name: Option<String>,
+    product_name: Option<String>,
     serial_number: Option<String>,
     port_number: Option<u8>,
-    connected: bool,
+    /// libusb-style location, bus then hub ports: `"1-3.2"`
+    port_chain: Option<String>,
+    /// Windows location path of the port it is plugged into; what port locks match on
+    location_path: Option<String>,
+    instance_id: Option<String>,
+    parent_instance_id: Option<String>,
+    container_id: Option<String>,
+    /// Assigned COM port for USB-serial adapters, e.g. `"COM7"`
+    com_port: Option<String>,
+    category: DeviceCategory,
+    /// bDeviceClass; 0 when each interface declares its own
+    device_class: u8,
+    /// Class triple of every interface of the active configuration
+    interfaces: Vec<InterfaceClass>,
+    /// Class names in the user's display language, e.g. `"HID – Keyboard"`
+    class_names: Vec<String>,
+    state: DeviceState,
+    /// Which decision blocked it, when we did
+    block_reason: Option<BlockReason>,
+    /// USB/IP and other network-attached devices skip physical-port reasoning
+    attachment: Attachment,
+    /// Sharing client a `Redirected` device arrives through, e.g. `"VirtualHere"`
+    redirection_client: Option<String>,
+    /// Driver bound to it, so an unusual or unsigned one stands out
+    driver: Option<DriverInfo>,
+    /// Drivers of its interfaces and of the disks, HID collections and
+    /// ports below them
+    function_drivers: Vec<FunctionDriver>,
+    /// Worst signature among `driver` and `function_drivers`
+    driver_signature: Option<DriverSignature>,
+    /// Mounted volumes, for storage devices
+    volumes: Vec<Volume>,
+    /// Bytes read/written on its volumes during this app session
+    transfer: Option<IoCounters>,
+    /// Storage that mounts but is kept write-protected by read-only mode
+    read_only: bool,
     trusted: bool,
+    /// VID/PID is trusted but this unit or its volume serial no longer
+    /// matches the trust binding (e.g. the stick was reformatted)
+    approval_required: bool,
+    /// Name of the known attack device it matches, e.g. `"Bash Bunny"`
+    threat_match: Option<String>,
+    /// The user's own name for it, e.g. `"Anna's backup drive"`
+    label: Option<String>,
+    note: Option<String>,
+}
+
+impl UsbDeviceInfo {
+    pub fn vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
+    pub fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    pub fn instance_id(&self) -> Option<&str> {
+        self.instance_id.as_deref()
+    }
+
+    pub fn product(&self) -> Option<&str> {
+        self.product.as_deref()
+    }
+
+    pub fn serial_number(&self) -> Option<&str> {
+        self.serial_number.as_deref()
+    }
+
+    pub fn port_chain(&self) -> Option<&str> {
+        self.port_chain.as_deref()
+    }
+
+    pub fn location_path(&self) -> Option<&str> {
+        self.location_path.as_deref()
+    }
+
+    pub fn container_id(&self) -> Option<&str> {
+        self.container_id.as_deref()
+    }
+
+    pub fn category(&self) -> DeviceCategory {
+        self.category
+    }
+
+    pub fn device_class(&self) -> u8 {
+        self.device_class
+    }
+
+    pub fn interfaces(&self) -> &[InterfaceClass] {
+        &self.interfaces
+    }
+
+    pub fn block_reason(&self) -> Option<BlockReason> {
+        self.block_reason
+    }
+
+    pub fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    pub fn trusted(&self) -> bool {
+        self.trusted
+    }
+
+    pub fn threat_match(&self) -> Option<&str> {
+        self.threat_match.as_deref()
+    }
+
+    pub fn approval_required(&self) -> bool {
+        self.approval_required
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Setup classes of its own driver and of its functions' drivers
+    pub fn setup_classes(&self) -> impl Iterator<Item = &str> {
+        self.driver
+            .iter()
+            .chain(self.function_drivers.iter().map(|function| &function.driver))
+            .filter_map(|driver| driver.class_guid.as_deref())
+    }
 }
 
 #[command]
 pub fn get_usb_devices() -> Result<Vec<UsbDeviceInfo>, String> {
-    let devices = DeviceList::new().map_err(|e| e.to_string())?;
+    let mut trace = etw::activity(TraceEvent::Enumerate, "get_usb_devices");
+    let controller = backend::controller();
+    let devices = trace.track(controller.list_devices())?;
     
-    let trusted_devices = TRUSTED_DEVICES.lock().unwrap();
-    let mut result = Vec::new();
+    // Devnode lookup failing (e.g. SetupAPI unavailable) only loses instance IDs
+    let devnodes = controller.devnodes().unwrap_or_default();
+    let volumes = controller.volumes().unwrap_or_default();
+    let volumes_of = |instance_id: &str| -> Vec<Volume> {
+        volumes
+            .iter()
+            .filter(|volume| volume.device_instance_id.eq_ignore_ascii_case(instance_id))
+            .cloned()
+            .collect()
+    };
 
-    for device in devices.iter() {
-        let descriptor = device.device_descriptor().map_err(|e| e.to_string())?;
-        
-        let (manufacturer, product, serial_number) = match device.open() {
-            Ok(handle) => (
-                read_usb_string(&handle, descriptor.manufacturer_string_index()),
-                read_usb_string(&handle, descriptor.product_string_index()),
-                read_usb_string(&handle, descriptor.serial_number_string_index())
-            ),
-            Err(_) => (None, None, None),
-        };
+    let mut trusted_devices = app_state().trusted_devices.lock().unwrap().clone();
+    trusted_devices.extend(TEMPORARY_TRUST.lock().unwrap().keys().copied());
+    let language = Language::current();
+    let mut result = Vec::new();
 
-        let trusted = trusted_devices.contains(&(descriptor.vendor_id(), descriptor.product_id()));
+    for device in devices {
+        let devnode = correlation::correlate(
+            &device.ports,
+            device.vendor_id,
+            device.product_id,
+            device.serial_number.as_deref(),
+            &devnodes,
+        );
+        let category = category::classify(device.device_class, device.vendor_id, &device.interfaces);
+        let device_volumes = devnode.map(|node| volumes_of(&node.instance_id)).unwrap_or_default();
+        let verdict = trust_rules::verdict(
+            device.vendor_id,
+            device.product_id,
+            device.serial_number.as_deref(),
+            &device_volumes,
+        );
+        let threat_match = threats::lookup(
+            device.vendor_id,
+            device.product_id,
+            device.manufacturer.as_deref(),
+            device.product.as_deref(),
+        );
+        let label = device_labels::lookup(device.vendor_id, device.product_id, device.serial_number.as_deref());
+        let (vendor_name, product_name) = usb_names::names(device.vendor_id, device.product_id);
+        let listed = trusted_devices.contains(&(device.vendor_id, device.product_id));
+        // Attack hardware often spoofs a trusted VID/PID; it is never trusted
+        let trusted = threat_match.is_none()
+            && ((listed && verdict != BindingVerdict::Mismatched)
+                || trust_rules::label_allowed(&device_volumes, &device.interfaces)
+                || devnode.is_some_and(|node| docks::trusted_by_dock(node, category, &devnodes))
+                || devnode.is_some_and(|node| guest::admitted(&node.instance_id)));
+        if let Some(node) = devnode {
+            KNOWN_CATEGORIES
+                .lock()
+                .unwrap()
+                .insert(node.instance_id.to_ascii_uppercase(), category);
+        }
 
         result.push(UsbDeviceInfo {
-            vendor_id: descriptor.vendor_id(),
-            product_id: descriptor.product_id(),
-            manufacturer,
-            product,
-            serial_number,
-            port_number: None,
-            connected: true,
+            vendor_id: device.vendor_id,
+            product_id: device.product_id,
+            manufacturer: device.manufacturer,
+            product: device.product,
+            vendor_name,
+            product_name,
+            serial_number: device.serial_number,
+            port_number: device.ports.last().copied(),
+            port_chain: correlation::format_port_chain(device.bus_number, &device.ports),
+            location_path: devnode.and_then(|node| node.location_path.clone()),
+            instance_id: devnode.map(|node| node.instance_id.clone()),
+            parent_instance_id: devnode.and_then(|node| node.parent_instance_id.clone()),
+            container_id: devnode.and_then(|node| node.container_id.clone()),
+            com_port: devnode.and_then(|node| controller.com_port(node)),
+            category,
+            device_class: device.device_class,
+            interfaces: device.interfaces.clone(),
+            class_names: class_names::device_class_names(language, device.device_class, &device.interfaces),
+            state: devnode.map_or(DeviceState::Unknown, devnode_state),
+            block_reason: devnode.and_then(|node| usb_control::block_reason(&node.instance_id)),
+            attachment: devnode.map_or(Attachment::Physical, remote::attachment),
+            redirection_client: devnode.and_then(remote::redirection_client).map(str::to_string),
+            driver: devnode.and_then(|node| node.driver.clone()),
+            function_drivers: devnode.map(|node| node.function_drivers.clone()).unwrap_or_default(),
+            driver_signature: devnode
+                .and_then(|node| drivers::worst_signature(node.driver.as_ref(), &node.function_drivers)),
+            volumes: device_volumes,
+            transfer: devnode.and_then(|node| transfers::device_totals(&node.instance_id)),
+            read_only: category == DeviceCategory::Storage && storage_readonly::enabled(),
             trusted,
+            approval_required: listed && verdict == BindingVerdict::Mismatched,
+            threat_match,
+            label: label.as_ref().and_then(|label| label.label.clone()),
+            note: label.and_then(|label| label.note),
+        });
+    }
+
+
+    // Devices we disabled that libusb no longer lists. Blocked interfaces
+    // are shown with their device.
+    for record in usb_control::block_records() {
+        if correlation::parse_interface_number(&record.instance_id).is_some() {
+            continue;
+        }
+        if result
+            .iter()
+            .any(|d| d.instance_id.as_deref().is_some_and(|id| id.eq_ignore_ascii_case(&record.instance_id)))
+        {
+            continue;
+        }
+        let devnode = devnodes.iter().find(|node| node.instance_id.eq_ignore_ascii_case(&record.instance_id));
+        let threat_match = threats::lookup(record.vendor_id, record.product_id, None, None);
+        let label = device_labels::lookup(
+            record.vendor_id,
+            record.product_id,
+            devnode.and_then(|node| node.serial.as_deref()),
+        );
+        let (vendor_name, product_name) = usb_names::names(record.vendor_id, record.product_id);
+        result.push(UsbDeviceInfo {
+            vendor_id: record.vendor_id,
+            product_id: record.product_id,
+            manufacturer: None,
+            product: None,
+            vendor_name,
+            product_name,
+            serial_number: devnode.and_then(|node| node.serial.clone()),
+            port_number: devnode.and_then(|node| node.port_chain.last().copied()),
+            port_chain: None,
+            location_path: devnode.and_then(|node| node.location_path.clone()),
+            instance_id: Some(record.instance_id.clone()),
+            parent_instance_id: devnode.and_then(|node| node.parent_instance_id.clone()),
+            container_id: devnode.and_then(|node| node.container_id.clone()),
+            com_port: None,
+            category: KNOWN_CATEGORIES
+                .lock()
+                .unwrap()
+                .get(&record.instance_id.to_ascii_uppercase())
+                .copied()
+                .unwrap_or(DeviceCategory::Other),
+            // Descriptors are only readable through libusb
+            device_class: 0,
+            interfaces: Vec::new(),
+            class_names: Vec::new(),
+            state: devnode.map_or(DeviceState::Disconnected, devnode_state),
+            block_reason: Some(record.reason),
+            attachment: devnode.map_or(Attachment::Physical, remote::attachment),
+            redirection_client: devnode.and_then(remote::redirection_client).map(str::to_string),
+            driver: devnode.and_then(|node| node.driver.clone()),
+            function_drivers: devnode.map(|node| node.function_drivers.clone()).unwrap_or_default(),
+            driver_signature: devnode
+                .and_then(|node| drivers::worst_signature(node.driver.as_ref(), &node.function_drivers)),
+            // Disabled disks are not mounted
+            volumes: Vec::new(),
+            transfer: transfers::device_totals(&record.instance_id),
+            read_only: false,
+            trusted: threat_match.is_none()
+                && trusted_devices.contains(&(record.vendor_id, record.product_id))
+                && trust_rules::verdict(
+                    record.vendor_id,
+                    record.product_id,
+                    devnode.and_then(|node| node.serial.as_deref()),
+                    &[],
+                ) != BindingVerdict::Mismatched,
+            approval_required: false,
+            threat_match,
+            label: label.as_ref().and_then(|label| label.label.clone()),
+            note: label.and_then(|label| label.note),
         });
     }
 
+    let sightings: Vec<Sighting> = result
+        .iter()
+        // Disabled devices are still plugged in
+        .filter(|d| d.state != DeviceState::Disconnected)
+        .filter_map(|d| {
+            Some(Sighting {
+                instance_id: d.instance_id.as_deref()?,
+                vendor_id: d.vendor_id,
+                product_id: d.product_id,
+                manufacturer: d.manufacturer.as_deref(),
+                product: d.product.as_deref(),
+                serial: d.serial_number.as_deref(),
+                category: d.category,
+                trusted: d.trusted,
+            })
+        })
+        .collect();
+    inventory::observe(&sightings);
+
     Ok(result)
 }
 
-fn read_usb_string(handle: &DeviceHandle<GlobalContext>, index: Option<u8>) -> Option<String> {
-    match index {
-        Some(idx) if idx != 0 => {
-            match handle.read_string_descriptor_ascii(idx) {
-                Ok(s) => Some(s),
-                Err(_) => None,
+fn devnode_state(node: &correlation::DevNode) -> DeviceState {
+    if node.disabled || (usb_control::is_blocked_by_us(&node.instance_id) && !node.started) {
+        match usb_control::block_reason(&node.instance_id) {
+            Some(BlockReason::Quarantine) => DeviceState::Quarantined,
+            _ => DeviceState::Blocked,
+        }
+    } else {
+        DeviceState::Connected
+    }
+}
+
+fn read_trusted_devices() -> Result<HashSet<(u16, u16)>, String> {
+    let path = usb_config::data_file(TRUSTED_DEVICES_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => {
+            let devices: Vec<(u16, u16)> = serde_json::from_slice(&data)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            Ok(devices.into_iter().collect())
+        }
+        // First run
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_trusted_devices(trusted_devices: &HashSet<(u16, u16)>) -> Result<(), String> {
+    let mut devices: Vec<(u16, u16)> = trusted_devices.iter().copied().collect();
+    devices.sort_unstable();
+    let path = usb_config::data_file(TRUSTED_DEVICES_FILE)?;
+    let data = serde_json::to_vec_pretty(&devices).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+impl AppState {
+    /// Permanently trusted models, sorted. Temporary grants are not included.
+    pub fn trusted_devices(&self) -> Vec<(u16, u16)> {
+        let mut devices: Vec<(u16, u16)> = self.trusted_devices.lock().unwrap().iter().copied().collect();
+        devices.sort_unstable();
+        devices
+    }
+
+    pub fn is_trusted(&self, vendor_id: u16, product_id: u16) -> bool {
+        self.trusted_devices.lock().unwrap().contains(&(vendor_id, product_id))
+    }
+
+    /// Replace the in-memory whitelist, and the bindings and label rules
+    /// that narrow it, with the ones on disk. Called from setup; a file that
+    /// fails to parse leaves the current list alone.
+    pub fn load_trusted_devices(&self) -> Result<(), String> {
+        let devices = read_trusted_devices()?;
+        *self.trusted_devices.lock().unwrap() = devices;
+        trust_rules::load()
+    }
+
+    /// Add to the whitelist. The change is only kept if it could be saved.
+    pub fn add_trusted_device(&self, vendor_id: u16, product_id: u16) -> Result<(), UsbShieldError> {
+        let mut trusted_devices = self.trusted_devices.lock().unwrap();
+        if trusted_devices.insert((vendor_id, product_id)) {
+            if let Err(e) = save_trusted_devices(&trusted_devices) {
+                trusted_devices.remove(&(vendor_id, product_id));
+                return Err(e.into());
+            }
+            audit::record("trusted_device_added", json!({ "vendor_id": vendor_id, "product_id": product_id }));
+        }
+        Ok(())
+    }
+
+    pub fn remove_trusted_device(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        admin_token: Option<&str>,
+    ) -> Result<(), UsbShieldError> {
+        admin_pin::require_admin(admin_token, "remove a trusted device")?;
+        let mut trusted_devices = self.trusted_devices.lock().unwrap();
+        if trusted_devices.remove(&(vendor_id, product_id)) {
+            if let Err(e) = save_trusted_devices(&trusted_devices) {
+                trusted_devices.insert((vendor_id, product_id));
+                return Err(e.into());
             }
+            audit::record("trusted_device_removed", json!({ "vendor_id": vendor_id, "product_id": product_id }));
+        }
+        Ok(())
+    }
+
+    /// Replace the whole whitelist, e.g. from an imported configuration.
+    /// Dropping entries needs an admin session, as removing one does.
+    pub fn set_trusted_devices(&self, devices: Vec<(u16, u16)>, admin_token: Option<&str>) -> Result<(), UsbShieldError> {
+        let devices: HashSet<(u16, u16)> = devices.into_iter().collect();
+        let mut trusted_devices = self.trusted_devices.lock().unwrap();
+        let removed: Vec<(u16, u16)> = trusted_devices.difference(&devices).copied().collect();
+        if !removed.is_empty() {
+            admin_pin::require_admin(admin_token, "remove a trusted device")?;
         }
-        _ => None,
+        if *trusted_devices == devices {
+            return Ok(());
+        }
+        save_trusted_devices(&devices)?;
+        let added: Vec<(u16, u16)> = devices.difference(&trusted_devices).copied().collect();
+        *trusted_devices = devices;
+        audit::record("trusted_devices_replaced", json!({ "added": added, "removed": removed }));
+        Ok(())
+    }
+
+    pub fn autoblock_enabled(&self) -> bool {
+        *self.autoblock_enabled.lock().unwrap()
+    }
+
+    pub fn set_autoblock_mode(&self, enabled: bool, admin_token: Option<&str>) -> Result<(), UsbShieldError> {
+        if !enabled {
+            admin_pin::require_admin(admin_token, "disable autoblock")?;
+            security_key::require_presence("disable autoblock")?;
+            hello::require_consent("disable autoblock")?;
+        }
+        *self.autoblock_enabled.lock().unwrap() = enabled;
+        audit::record("autoblock_mode_changed", json!({ "enabled": enabled }));
+        if !enabled {
+            email_alerts::protection_disabled("autoblock was turned off");
+        }
+        Ok(())
     }
 }
 
+/// Re-read the whitelist, e.g. after the file was edited or deployed by hand.
 #[command]
-pub fn add_trusted_device(vendor_id: u16, product_id: u16) -> Result<(), String> {
-    let mut trusted_devices = TRUSTED_DEVICES.lock().unwrap();
-    trusted_devices.insert((vendor_id, product_id));
-    Ok(())
+pub fn reload_trusted_devices(state: State<'_, AppState>) -> Result<Vec<(u16, u16)>, String> {
+    state.load_trusted_devices()?;
+    Ok(state.trusted_devices())
 }
 
 #[command]
-pub fn remove_trusted_device(vendor_id: u16, product_id: u16) -> Result<(), String> {
-    let mut trusted_devices = TRUSTED_DEVICES.lock().unwrap();
-    trusted_devices.remove(&(vendor_id, product_id));
-    Ok(())
+pub fn add_trusted_device(state: State<'_, AppState>, vendor_id: u16, product_id: u16) -> Result<(), UsbShieldError> {
+    state.add_trusted_device(vendor_id, product_id)
+}
+
+#[command]
+pub fn remove_trusted_device(
+    state: State<'_, AppState>,
+    vendor_id: u16,
+    product_id: u16,
+    admin_token: Option<String>,
+) -> Result<(), UsbShieldError> {
+    state.remove_trusted_device(vendor_id, product_id, admin_token.as_deref())
+}
+
+/// Trust a model for `duration_secs` and enable the units we had blocked.
+/// When the grant lapses trust is revoked and attached units are blocked
+/// again; `extend_timed_unblock` / `revoke_temporary_trust` take the returned id.
+#[command]
+pub fn add_trusted_device_temporary(
+    vendor_id: u16,
+    product_id: u16,
+    duration_secs: u64,
+    admin_token: Option<String>,
+) -> Result<ReblockStatus, String> {
+    if duration_secs == 0 {
+        return Err("Duration must be greater than zero".to_string());
+    }
+    if app_state().is_trusted(vendor_id, product_id) {
+        return Err(format!("VID_{:04X}&PID_{:04X} is already trusted", vendor_id, product_id));
+    }
+    dry_run::refuse("add_trusted_device_temporary")?;
+    admin_pin::require_admin(admin_token.as_deref(), "temporarily trust a device")?;
+    hello::require_consent("temporarily trust a device")?;
+
+    // A second grant replaces the first one's schedule, so only its own
+    // expiry can end it
+    let id = {
+        let mut grants = TEMPORARY_TRUST.lock().unwrap();
+        let id = reblock::schedule(
+            ReblockTarget::Trust { vendor_id, product_id },
+            Duration::from_secs(duration_secs),
+        );
+        grants.insert((vendor_id, product_id), id);
+        id
+    };
+    let blocked: Vec<String> = usb_control::block_records()
+        .into_iter()
+        .filter(|record| record.vendor_id == vendor_id && record.product_id == product_id)
+        .map(|record| record.instance_id)
+        .collect();
+    for instance_id in &blocked {
+        if let Err(e) = usb_control::unblock(instance_id) {
+            log::error!("Failed to enable temporarily trusted {}: {}", instance_id, e);
+        }
+    }
+    audit::record(
+        "temporary_trust_granted",
+        json!({ "id": id, "vendor_id": vendor_id, "product_id": product_id, "duration_secs": duration_secs, "enabled": blocked }),
+    );
+    reblock::pending()
+        .into_iter()
+        .find(|status| status.id == id)
+        .ok_or_else(|| "The grant expired before it was scheduled".to_string())
+}
+
+/// End a temporary trust grant early: the trust is revoked and attached
+/// units are blocked again immediately.
+#[command]
+pub fn revoke_temporary_trust(id: u64) -> Result<(), String> {
+    match reblock::pending().into_iter().find(|status| status.id == id).map(|status| status.target) {
+        Some(ReblockTarget::Trust { .. }) => reblock::fire_now(id),
+        _ => Err(format!("No temporary trust grant with id {}", id)),
+    }
+}
+
+/// End the temporary grant scheduled as `grant`: revoke the trust and block
+/// the attached units. A grant since replaced by a newer one is left alone.
+pub fn expire_temporary_trust(vendor_id: u16, product_id: u16, grant: u64) -> Result<(), String> {
+    {
+        let mut grants = TEMPORARY_TRUST.lock().unwrap();
+        if grants.get(&(vendor_id, product_id)) != Some(&grant) {
+            return Ok(());
+        }
+        grants.remove(&(vendor_id, product_id));
+    }
+    let mut errors = Vec::new();
+    let mut blocked = Vec::new();
+    for device in get_usb_devices()? {
+        if device.vendor_id != vendor_id || device.product_id != product_id || device.trusted {
+            continue;
+        }
+        if let (Some(instance_id), DeviceState::Connected) = (&device.instance_id, device.state) {
+            match usb_control::block(instance_id, BlockReason::TimedReblock) {
+                Ok(_) => blocked.push(instance_id.clone()),
+                Err(e) => errors.push(format!("{}: {}", instance_id, e)),
+            }
+        }
+    }
+    audit::record(
+        "temporary_trust_expired",
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "blocked": blocked, "errors": errors }),
+    );
+    events::emit(
+        EVENT_TEMPORARY_TRUST_EXPIRED,
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "blocked": blocked }),
+    );
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+#[command]
+pub fn get_trusted_devices(state: State<'_, AppState>) -> Result<Vec<(u16, u16)>, String> {
+    Ok(state.trusted_devices())
+}
+
+#[command]
+pub fn set_autoblock_mode(
+    state: State<'_, AppState>,
+    enabled: bool,
+    admin_token: Option<String>,
+) -> Result<(), UsbShieldError> {
+    state.set_autoblock_mode(enabled, admin_token.as_deref())
 }
 
 #[command]
-pub fn get_trusted_devices() -> Result<Vec<(u16, u16)>, String> {
-    let trusted_devices = TRUSTED_DEVICES.lock().unwrap();
-    Ok(trusted_devices.iter().cloned().collect())
+pub fn get_autoblock_mode(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.autoblock_enabled())
+}
+
+/// Disable a device that just arrived if autoblock is on and it is neither
+/// trusted nor made only of allowed classes. Returns whether it was disabled.
+pub fn autoblock_arrival(device: &UsbDeviceInfo) -> bool {
+    if !app_state().autoblock_enabled() || device.trusted || device.state != DeviceState::Connected {
+        return false;
+    }
+    if class_policy::allowed(device) || guest::admit(device) || pause::admit(device) {
+        return false;
+    }
+    let instance_id = match &device.instance_id {
+        Some(instance_id) => instance_id,
+        // Nothing to disable without a devnode
+        None => return false,
+    };
+    if quarantine::enabled() {
+        return quarantine::hold(device);
+    }
+    // Either way the device is disabled first; a Prompt band only adds the question
+    let (sensitivity, band) = scheduler::current_sensitivity();
+    let result = usb_control::block(instance_id, BlockReason::Autoblock);
+    audit::record(
+        "device_autoblocked",
+        json!({
+            "instance_id": instance_id,
+            "vendor_id": device.vendor_id,
+            "product_id": device.product_id,
+            "sensitivity": sensitivity,
+            "band": band,
+            "error": result.as_ref().err().map(ToString::to_string),
+        }),
+    );
+    match result {
+        Ok(_) => {
+            events::emit(EVENT_DEVICE_AUTOBLOCKED, device.clone());
+            if sensitivity == AutoblockSensitivity::Prompt {
+                scheduler::prompt(device, band);
+            }
+            notifications::notify(
+                Severity::Warning,
+                "device_autoblocked",
+                if sensitivity == AutoblockSensitivity::Prompt {
+                    "Untrusted USB device is waiting for approval"
+                } else {
+                    "Untrusted USB device blocked"
+                },
+                device.product.as_deref().unwrap_or(instance_id),
+            );
+            true
+        }
+        Err(e) => {
+            log::error!("Failed to autoblock {}: {}", instance_id, e);
+            false
+        }
+    }
 }
 
+/// Watchdog timeout for a single SetupAPI enable/disable call.
 #[command]
-pub fn set_autoblock_mode(enabled: bool) -> Result<(), String> {
-    let mut autoblock = AUTOBLOCK_ENABLED.lock().unwrap();
-    *autoblock = enabled;
+pub fn set_device_operation_timeout(timeout_ms: u64) -> Result<(), String> {
+    if timeout_ms < 1_000 {
+        return Err("Timeout must be at least 1000 ms".to_string());
+    }
+    usb_control::set_operation_timeout(timeout_ms);
+    audit::record("device_operation_timeout_changed", json!({ "timeout_ms": timeout_ms }));
     Ok(())
 }
 
 #[command]
-pub fn get_autoblock_mode() -> Result<bool, String> {
-    let autoblock = AUTOBLOCK_ENABLED.lock().unwrap();
-    Ok(*autoblock)
+pub fn get_device_operation_timeout() -> Result<u64, String> {
+    Ok(usb_control::operation_timeout().as_millis() as u64)
 }
 
+/// Block USB storage at the driver and policy level. Restarting the storage
+/// service takes seconds, so this runs off the IPC thread.
 #[command]
-pub fn block_all_usb_ports() -> Result<(), String> {
+pub async fn block_all_usb_ports() -> Result<Planned<()>, UsbShieldError> {
+    if dry_run::active() {
+        return Ok(Planned::Simulated(plan_port_block()));
+    }
+    operations::run("block_all_usb_ports", || {
+        rollback::take_before("block_all_usb_ports");
+        apply_port_block()
+    })
+    .await
+    .map(Planned::Applied)
+}
+
+fn plan_port_block() -> PolicyPlan {
+    let mut plan = PolicyPlan::new("block_all_usb_ports");
+    plan.steps = vec![
+        PlannedStep::SetRegistryValue {
+            key: format!(r"HKLM\{}", USBSTOR_KEY),
+            name: "Start".to_string(),
+            value: 4,
+        },
+        PlannedStep::SetRegistryValue {
+            key: format!(r"HKCU\{}", REMOVABLE_STORAGE_POLICY_KEY),
+            name: "Deny_All".to_string(),
+            value: 1,
+        },
+        PlannedStep::RestartStorageService,
+    ];
+    plan
+}
+
+/// `block_all_usb_ports` for callers already off the IPC thread.
+pub fn apply_port_block() -> Result<(), UsbShieldError> {
+    let mut trace = etw::activity(TraceEvent::BlockAllPorts, "USBSTOR Start=4, Deny_All=1");
+
     // Block at system level
-    set_registry_value(
-        HKEY_LOCAL_MACHINE,
-        "SYSTEM\\CurrentControlSet\\Services\\USBSTOR",
-        "Start",
-        4
-    )?;
+    operations::stage("Disabling the USB storage driver");
+    trace.track(tamper::write_usbstor_start(Some(4), || {
+        backend::controller()
+            .apply_policy(4)
+            .map_err(|e| UsbShieldError::policy("block USB storage ports", e))
+    }))?;
 
     // Block at user level
-    set_registry_value(
-        HKEY_CURRENT_USER,
-        "Software\\Policies\\Microsoft\\Windows\\RemovableStorageDevices",
-        "Deny_All",
-        1
-    )?;
+    operations::stage("Applying the removable storage policy");
+    trace.track(set_registry_value(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY, "Deny_All", 1))?;
 
-    restart_usb_service()?;
+    trace.track(restart_storage())?;
     Ok(())
 }
 
+/// Lift the port-level storage block. With `reblock_after_minutes` the block
+/// is re-applied automatically once the window has elapsed.
 #[command]
-pub fn unblock_usb_port() -> Result<(), String> {
+pub async fn unblock_usb_port(
+    reblock_after_minutes: Option<u32>,
+    admin_token: Option<String>,
+) -> Result<Planned<()>, UsbShieldError> {
+    // Nothing is relaxed, so no PIN or consent either
+    if dry_run::active() {
+        return Ok(Planned::Simulated(plan_port_unblock(reblock_after_minutes)));
+    }
+    operations::run("unblock_usb_port", move || {
+        admin_pin::require_admin(admin_token.as_deref(), "unblock USB storage ports")?;
+        hello::require_consent("unblock USB storage ports")?;
+        lift_port_block(reblock_after_minutes)
+    })
+    .await
+    .map(Planned::Applied)
+}
+
+fn plan_port_unblock(reblock_after_minutes: Option<u32>) -> PolicyPlan {
+    let mut plan = PolicyPlan::new("unblock_usb_port");
+    plan.steps.push(PlannedStep::SetRegistryValue {
+        key: format!(r"HKLM\{}", USBSTOR_KEY),
+        name: "Start".to_string(),
+        value: 3,
+    });
+    plan.steps.push(PlannedStep::DeleteRegistryKey {
+        key: format!(r"HKCU\{}", REMOVABLE_STORAGE_POLICY_KEY),
+    });
+    if storage_readonly::enabled() {
+        plan.steps.push(PlannedStep::SetRegistryValue {
+            key: format!(r"HKCU\{}\{}", REMOVABLE_STORAGE_POLICY_KEY, REMOVABLE_DISKS_CLASS),
+            name: "Deny_Write".to_string(),
+            value: 1,
+        });
+    }
+    plan.steps.push(PlannedStep::RestartStorageService);
+    if let Some(minutes) = reblock_after_minutes {
+        plan.steps.push(PlannedStep::ScheduleReblock {
+            target: "USB storage ports".to_string(),
+            after_secs: minutes as u64 * 60,
+        });
+    }
+    plan
+}
+
+/// `unblock_usb_port` without the interactive confirmation.
+pub fn lift_port_block(reblock_after_minutes: Option<u32>) -> Result<(), UsbShieldError> {
+    let mut trace = etw::activity(TraceEvent::UnblockPorts, "USBSTOR Start=3, remove RemovableStorageDevices");
+
     // Unblock at system level
-    set_registry_value(
-        HKEY_LOCAL_MACHINE,
-        "SYSTEM\\CurrentControlSet\\Services\\USBSTOR",
-        "Start",
-        3
-    )?;
+    operations::stage("Enabling the USB storage driver");
+    trace.track(tamper::write_usbstor_start(None, || {
+        backend::controller()
+            .apply_policy(3)
+            .map_err(|e| UsbShieldError::policy("unblock USB storage ports", e))
+    }))?;
 
     // Remove user-level restrictions
-    unsafe {
-        RegDeleteKeyA(
-            HKEY_CURRENT_USER,
-            PCSTR(b"Software\\Policies\\Microsoft\\Windows\\RemovableStorageDevices\0".as_ptr())
-        );
+    operations::stage("Removing the removable storage policy");
+    // The whole tree: per-class policies below it would keep the key alive
+    trace.track(
+        registry::delete_tree(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY)
+            .map_err(|e| registry_error(REMOVABLE_STORAGE_POLICY_KEY, e)),
+    )?;
+
+    // Deleting the key took read-only mode's and forensic mode's removable
+    // disk policy with it
+    if storage_readonly::enabled() || forensics::active() {
+        trace.track(
+            backend::controller()
+                .set_storage_write_protect(true)
+                .map_err(|e| UsbShieldError::policy("write-protect removable storage", e)),
+        )?;
     }
 
-    restart_usb_service()?;
+    trace.track(restart_storage())?;
+
+    if let Some(minutes) = reblock_after_minutes {
+        reblock::schedule(ReblockTarget::Ports, Duration::from_secs(minutes as u64 * 60));
+    }
     Ok(())
 }
 
 #[command]
-pub fn restart_usb_service() -> Result<(), String> {
-    // Stop service
-    let _ = std::process::Command::new("net")
-        .args(&["stop", "USBSTOR"])
-        .status()
-        .map_err(|e| e.to_string())?;
-    
-    // Start service
-    std::process::Command::new("net")
-        .args(&["start", "USBSTOR"])
-        .status()
-        .map_err(|e| e.to_string())?;
-
-    // Update group policy
-    std::process::Command::new("gpupdate")
-        .args(&["/force"])
-        .status()
-        .map_err(|e| e.to_string())?;
+pub async fn restart_usb_service() -> Result<(), String> {
+    operations::run("restart_usb_service", restart_storage).await
+}
+
+/// Restart USBSTOR and refresh Group Policy so a changed policy takes
+/// effect, for callers already off the IPC thread.
+pub fn restart_storage() -> Result<(), String> {
+    let _trace = etw::activity(TraceEvent::ApplyPolicy, "restart USBSTOR, refresh policy");
 
+    backend::controller().restart_storage_service()?;
+    // Devices drop off and come back during the restart
+    hotplug::redetect("service_restart");
     Ok(())
 }
 
-fn set_registry_value(hkey: HKEY, path: &str, value_name: &str, value: u32) -> Result<(), String> {
-    unsafe {
-        let mut key_handle: HKEY = HKEY::default();
-        let path_pcstr = PCSTR(path.as_ptr());
-        let value_name_pcstr = PCSTR(value_name.as_ptr());
-
-        // Open or create key
-        let status = if hkey == HKEY_LOCAL_MACHINE {
-            RegOpenKeyExA(hkey, path_pcstr, 0, KEY_SET_VALUE, &mut key_handle)
-        } else {
-            // Simplified key creation for user-level
-            RegCreateKeyA(
-                hkey,
-                path_pcstr,
-                &mut key_handle
-            )
-        };
+fn registry_error(path: &str, error: RegistryError) -> UsbShieldError {
+    if error.is_access_denied() {
+        UsbShieldError::RegistryAccessDenied { key: path.to_string() }
+    } else {
+        error.to_string().into()
+    }
+}
 
-        if status != windows::Win32::Foundation::WIN32_ERROR(0) {
-            return Err(format!("Registry access failed (Error {})", status.0));
+fn set_registry_value(root: Root, path: &str, value_name: &str, value: u32) -> Result<(), UsbShieldError> {
+    registry::create(root, path)
+        .and_then(|key| key.set_dword(value_name, value))
+        .map_err(|e| registry_error(path, e))
+}
+
+/// Disable every present devnode matching the identity. `serial` narrows the
+/// match to one physical unit; `instance_id` targets exactly one devnode.
+/// Returns one entry per changed devnode, including how many attempts it took.
+#[command]
+pub async fn block_device(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    instance_id: Option<String>,
+) -> Result<Planned<Vec<StateChange>>, UsbShieldError> {
+    operations::run("block_device", move || {
+        if dry_run::active() {
+            let identity = DeviceIdentity { vendor_id, product_id, serial, instance_id };
+            return Ok(Planned::Simulated(plan_batch("block_device", vec![identity], Some(BlockReason::Manual))?));
         }
+        block_device_for(vendor_id, product_id, serial, instance_id, BlockReason::Manual).map(Planned::Applied)
+    })
+    .await
+}
 
-        // Set value
-        let value_bytes = value.to_le_bytes();
-        let status = RegSetValueExA(
-            key_handle,
-            value_name_pcstr,
-            0,
-            REG_DWORD,
-            Some(&value_bytes)
-        );
+/// `block_device` on behalf of a policy, recording `reason` with each block.
+pub fn block_device_for(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    instance_id: Option<String>,
+    reason: BlockReason,
+) -> Result<Vec<StateChange>, UsbShieldError> {
+    let devnodes = backend::controller().devnodes()?;
+    let targets = resolve_targets(&devnodes, vendor_id, product_id, serial.as_deref(), instance_id.as_deref())?;
+    apply_device_state(&targets, Some(reason))
+}
+
+/// Enable matching devnodes. With `reblock_after_minutes` each one is
+/// disabled again once the window has elapsed.
+#[command]
+pub async fn unblock_device(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    instance_id: Option<String>,
+    reblock_after_minutes: Option<u32>,
+    admin_token: Option<String>,
+) -> Result<Planned<Vec<StateChange>>, UsbShieldError> {
+    operations::run("unblock_device", move || {
+        if dry_run::active() {
+            let identity = DeviceIdentity { vendor_id, product_id, serial, instance_id };
+            return plan_unblock_device(identity, reblock_after_minutes).map(Planned::Simulated);
+        }
+        admin_pin::require_admin(admin_token.as_deref(), "unblock a device")?;
+        hello::require_consent("unblock a device")?;
+        enable_device(vendor_id, product_id, serial, instance_id, reblock_after_minutes).map(Planned::Applied)
+    })
+    .await
+}
+
+fn plan_unblock_device(
+    identity: DeviceIdentity,
+    reblock_after_minutes: Option<u32>,
+) -> Result<PolicyPlan, UsbShieldError> {
+    let mut plan = plan_batch("unblock_device", vec![identity], None)?;
+    if let Some(minutes) = reblock_after_minutes {
+        let reblocks: Vec<PlannedStep> = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                PlannedStep::EnableDevice { instance_id, .. } => Some(PlannedStep::ScheduleReblock {
+                    target: instance_id.clone(),
+                    after_secs: minutes as u64 * 60,
+                }),
+                _ => None,
+            })
+            .collect();
+        plan.steps.extend(reblocks);
+    }
+    Ok(plan)
+}
 
-        RegCloseKey(key_handle);
+/// `unblock_device` without the interactive confirmation, for callers that
+/// already verified the user or act on their own authority.
+pub fn enable_device(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    instance_id: Option<String>,
+    reblock_after_minutes: Option<u32>,
+) -> Result<Vec<StateChange>, UsbShieldError> {
+    let devnodes = backend::controller().devnodes()?;
+    let targets = resolve_targets(&devnodes, vendor_id, product_id, serial.as_deref(), instance_id.as_deref())?;
+    let changes = apply_device_state(&targets, None)?;
 
-        if status != windows::Win32::Foundation::WIN32_ERROR(0) {
-            return Err(format!("Failed to set value (Error {})", status.0));
+    if let Some(minutes) = reblock_after_minutes {
+        for change in &changes {
+            reblock::schedule(
+                ReblockTarget::Device { instance_id: change.instance_id.clone() },
+                Duration::from_secs(minutes as u64 * 60),
+            );
         }
     }
-    Ok(())
+    Ok(changes)
+}
+
+fn resolve_targets(
+    devnodes: &[correlation::DevNode],
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<&str>,
+    instance_id: Option<&str>,
+) -> Result<Vec<String>, UsbShieldError> {
+    let targets: Vec<String> = devnodes
+        .iter()
+        .filter(|node| node.vendor_id == vendor_id && node.product_id == product_id)
+        .filter(|node| match instance_id {
+            Some(id) => node.instance_id.eq_ignore_ascii_case(id),
+            None => true,
+        })
+        .filter(|node| match serial {
+            Some(serial) => node.serial.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(serial)),
+            None => true,
+        })
+        .map(|node| node.instance_id.clone())
+        .collect();
+
+    if targets.is_empty() {
+        return Err(UsbShieldError::DeviceNotFound {
+            device: format!("VID_{:04X}&PID_{:04X}", vendor_id, product_id),
+        });
+    }
+    Ok(targets)
+}
+
+// `None` enables, `Some(reason)` blocks for that reason
+fn apply_device_state(targets: &[String], reason: Option<BlockReason>) -> Result<Vec<StateChange>, UsbShieldError> {
+    let mut changed = Vec::new();
+    let mut errors = Vec::new();
+    for instance_id in targets {
+        let result = match reason {
+            Some(reason) => usb_control::block(instance_id, reason),
+            None => usb_control::unblock(instance_id),
+        };
+        match result {
+            Ok(change) => changed.push(change),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    // A single failure keeps its kind; several are only worth reading
+    match errors.len() {
+        0 => Ok(changed),
+        1 => Err(errors.remove(0)),
+        _ => Err(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; ").into()),
+    }
 }
 
+/// One entry of a batch block/unblock request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceIdentity {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub instance_id: Option<String>,
+}
 
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub identity: DeviceIdentity,
+    pub changes: Vec<StateChange>,
+    pub error: Option<UsbShieldError>,
+}
 
+/// Block many identities in one call. Devnodes are enumerated once for the
+/// whole batch; a failing item does not stop the rest.
 #[command]
-pub fn block_device(vendor_id: u16, product_id: u16) -> Result<(), String> {
-    let hwid = format!("USB\\VID_{:04X}&PID_{:04X}", vendor_id, product_id);
-    set_device_state(&hwid, false)
+pub async fn block_devices(devices: Vec<DeviceIdentity>) -> Result<Planned<Vec<BatchItemResult>>, String> {
+    operations::run("block_devices", move || {
+        if dry_run::active() {
+            return plan_batch("block_devices", devices, Some(BlockReason::Manual)).map(Planned::Simulated);
+        }
+        apply_batch(devices, Some(BlockReason::Manual)).map(Planned::Applied)
+    })
+    .await
 }
 
 #[command]
-pub fn unblock_device(vendor_id: u16, product_id: u16) -> Result<(), String> {
-    let hwid = format!("USB\\VID_{:04X}&PID_{:04X}", vendor_id, product_id);
-    set_device_state(&hwid, true)
-}
-
-fn set_device_state(hardware_id: &str, enable: bool) -> Result<(), String> {
-    unsafe {
-        // Convert to UTF-16 for Windows API
-        let hwid_wide: Vec<u16> = hardware_id.encode_utf16().chain(Some(0)).collect();
-        
-        // Get device information set
-        let device_info_set = SetupDiGetClassDevsW(
-            None,
-            Some(PCWSTR(hwid_wide.as_ptr())),
-            HWND(0),
-            DIGCF_PRESENT | DIGCF_ALLCLASSES,
-        );
-        
-        if device_info_set == INVALID_HANDLE_VALUE {
-            return Err("Failed to get device information set".to_string());
+pub async fn unblock_devices(
+    devices: Vec<DeviceIdentity>,
+    admin_token: Option<String>,
+) -> Result<Planned<Vec<BatchItemResult>>, String> {
+    operations::run("unblock_devices", move || {
+        if dry_run::active() {
+            return plan_batch("unblock_devices", devices, None).map(Planned::Simulated);
         }
-        
-        // Create device info data structure
-        let mut device_info_data = SP_DEVINFO_DATA {
-            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
-            ..Default::default()
-        };
-        
-        // Enumerate devices
-        let mut result = Err("Device not found".to_string());
-        for index in 0.. {
-            if !SetupDiEnumDeviceInfo(device_info_set, index, &mut device_info_data).as_bool() {
-                break;
-            }
-            
-            // Get device instance ID
-            let mut instance_id_buffer = [0u16; 256];
-            if SetupDiGetDeviceInstanceIdW(
-                device_info_set,
-                &device_info_data,
-                &mut instance_id_buffer,
-                instance_id_buffer.len() as u32,
-                None,
-            ).as_bool()
-            {
-                let instance_id = String::from_utf16_lossy(
-                    &instance_id_buffer[..instance_id_buffer.iter().position(|&x| x == 0).unwrap_or(0)]
-                );
-                
-                if instance_id.contains(hardware_id) {
-                    // Found our device - change state
-                    let mut propchange_params = SP_PROPCHANGE_PARAMS {
-                        ClassInstallHeader: SP_CLASSINSTALL_HEADER {
-                            cbSize: std::mem::size_of::<SP_PROPCHANGE_PARAMS>() as u32,
-                            InstallFunction: DIF_PROPERTYCHANGE,
-                        },
-                        StateChange: if enable { DICS_ENABLE } else { DICS_DISABLE },
-                        Scope: 0, // Global
-                        HwProfile: 0,
-                    };
-                    
-                    // Set class installer parameters
-                    if SetupDiSetClassInstallParamsW(
-                        device_info_set,
-                        &device_info_data,
-                        &propchange_params.ClassInstallHeader,
-                        std::mem::size_of::<SP_PROPCHANGE_PARAMS>() as u32,
-                    ).as_bool()
-                    {
-                        // Call class installer
-                        if SetupDiCallClassInstaller(
-                            DIF_PROPERTYCHANGE,
-                            device_info_set,
-                            &device_info_data,
-                        ).as_bool()
-                        {
-                            result = Ok(());
-                        } else {
-                            result = Err("Failed to call class installer".to_string());
-                        }
-                    } else {
-                        result = Err("Failed to set class install params".to_string());
+        admin_pin::require_admin(admin_token.as_deref(), "unblock devices")?;
+        hello::require_consent("unblock devices")?;
+        apply_batch(devices, None).map(Planned::Applied)
+    })
+    .await
+}
+
+/// `unblock_devices` for policies lifting their own blocks, never simulated.
+pub fn enable_devices(devices: Vec<DeviceIdentity>) -> Result<Vec<BatchItemResult>, String> {
+    apply_batch(devices, None)
+}
+
+/// What `apply_batch` would change, resolving targets the same way.
+fn plan_batch(command: &str, devices: Vec<DeviceIdentity>, reason: Option<BlockReason>) -> Result<PolicyPlan, String> {
+    let devnodes = backend::controller().devnodes()?;
+    let mut plan = PolicyPlan::new(command);
+    for identity in devices {
+        match resolve_targets(
+            &devnodes,
+            identity.vendor_id,
+            identity.product_id,
+            identity.serial.as_deref(),
+            identity.instance_id.as_deref(),
+        ) {
+            Ok(targets) => {
+                let steps = devnodes
+                    .iter()
+                    .filter(|node| targets.contains(&node.instance_id))
+                    .filter_map(|node| dry_run::device_step(node, reason));
+                for step in steps {
+                    // An identity may overlap one listed before it
+                    if !plan.steps.contains(&step) {
+                        plan.steps.push(step);
                     }
-                    break;
                 }
             }
+            Err(e) => plan.unmatched.push(e.to_string()),
         }
-        
-        // Cleanup
-        SetupDiDestroyDeviceInfoList(device_info_set);
-        result
     }
+    Ok(plan)
+}
+
+fn apply_batch(devices: Vec<DeviceIdentity>, reason: Option<BlockReason>) -> Result<Vec<BatchItemResult>, String> {
+    let devnodes = backend::controller().devnodes()?;
+
+    Ok(devices
+        .into_iter()
+        .map(|identity| {
+            let outcome = resolve_targets(
+                &devnodes,
+                identity.vendor_id,
+                identity.product_id,
+                identity.serial.as_deref(),
+                identity.instance_id.as_deref(),
+            )
+            .and_then(|targets| apply_device_state(&targets, reason));
+            match outcome {
+                Ok(changes) => BatchItemResult { identity, changes, error: None },
+                Err(e) => BatchItemResult { identity, changes: Vec::new(), error: Some(e) },
+            }
+        })
+        .collect())
 }
 
+/// Block every attached device of a category right now, trusted or not.
 #[command]
-pub fn block_all_untrusted() -> Result<(), String> {
+pub async fn block_all_of_class(class: DeviceCategory) -> Result<Planned<Vec<BatchItemResult>>, String> {
+    operations::run("block_all_of_class", move || {
+        if dry_run::active() {
+            let identities = class_identities(class, Some(BlockReason::Manual))?;
+            return plan_batch("block_all_of_class", identities, Some(BlockReason::Manual)).map(Planned::Simulated);
+        }
+        block_class_for(class, BlockReason::Manual).map(Planned::Applied)
+    })
+    .await
+}
+
+/// `block_all_of_class` on behalf of a policy.
+pub fn block_class_for(class: DeviceCategory, reason: BlockReason) -> Result<Vec<BatchItemResult>, String> {
+    apply_to_class(class, Some(reason))
+}
+
+#[command]
+pub async fn unblock_all_of_class(
+    class: DeviceCategory,
+    admin_token: Option<String>,
+) -> Result<Planned<Vec<BatchItemResult>>, String> {
+    operations::run("unblock_all_of_class", move || {
+        if dry_run::active() {
+            let identities = class_identities(class, None)?;
+            return plan_batch("unblock_all_of_class", identities, None).map(Planned::Simulated);
+        }
+        admin_pin::require_admin(admin_token.as_deref(), "unblock a device class")?;
+        hello::require_consent("unblock a device class")?;
+        apply_to_class(class, None).map(Planned::Applied)
+    })
+    .await
+}
+
+fn apply_to_class(class: DeviceCategory, reason: Option<BlockReason>) -> Result<Vec<BatchItemResult>, String> {
+    apply_batch(class_identities(class, reason)?, reason)
+}
+
+// Attached devices of `class` that blocking (`Some`) or unblocking would change
+fn class_identities(class: DeviceCategory, reason: Option<BlockReason>) -> Result<Vec<DeviceIdentity>, String> {
+    let wanted = if reason.is_none() { DeviceState::Blocked } else { DeviceState::Connected };
+    Ok(get_usb_devices()?
+        .into_iter()
+        .filter(|device| device.category == class && device.state == wanted)
+        // Without a devnode we could only guess which unit to touch
+        .filter_map(|device| {
+            Some(DeviceIdentity {
+                vendor_id: device.vendor_id,
+                product_id: device.product_id,
+                serial: device.serial_number,
+                instance_id: Some(device.instance_id?),
+            })
+        })
+        .collect())
+}
+
+#[command]
+pub async fn block_all_untrusted() -> Result<Planned<()>, UsbShieldError> {
+    operations::run("block_all_untrusted", || {
+        if dry_run::active() {
+            let identities = get_usb_devices()?
+                .into_iter()
+                .filter(|device| !device.trusted)
+                .map(|device| DeviceIdentity {
+                    vendor_id: device.vendor_id,
+                    product_id: device.product_id,
+                    serial: device.serial_number,
+                    instance_id: device.instance_id,
+                })
+                .collect();
+            return Ok(Planned::Simulated(plan_batch("block_all_untrusted", identities, Some(BlockReason::Manual))?));
+        }
+        block_untrusted_for(BlockReason::Manual).map(Planned::Applied)
+    })
+    .await
+}
+
+/// `block_all_untrusted` on behalf of a policy.
+pub fn block_untrusted_for(reason: BlockReason) -> Result<(), UsbShieldError> {
     let devices = get_usb_devices()?;
-    let trusted_devices = TRUSTED_DEVICES.lock().unwrap();
     
+    // `trusted` already folds in dock-level trust
     for device in devices {
-        if !trusted_devices.contains(&(device.vendor_id, device.product_id)) {
-            if let Err(e) = block_device(device.vendor_id, device.product_id) {
-                eprintln!("Failed to block device: {}", e);
+        if !device.trusted {
+            if let Err(e) = block_device_for(
+                device.vendor_id,
+                device.product_id,
+                device.serial_number.clone(),
+                device.instance_id.clone(),
+                reason,
+            ) {
+                log::error!("Failed to block device: {}", e);
             }
         }
     }
@@ -364,15 +1299,34 @@ pub fn block_all_untrusted() -> Result<(), String> {
 }
 
 #[command]
-pub fn unblock_all_trusted() -> Result<(), String> {
-    let trusted_devices = TRUSTED_DEVICES.lock().unwrap();
-    
-    for (vendor_id, product_id) in trusted_devices.iter() {
-        if let Err(e) = unblock_device(*vendor_id, *product_id) {
-            eprintln!("Failed to unblock device: {}", e);
+pub async fn unblock_all_trusted(admin_token: Option<String>) -> Result<Planned<()>, UsbShieldError> {
+    operations::run("unblock_all_trusted", move || {
+        if dry_run::active() {
+            let identities = app_state()
+                .trusted_devices()
+                .into_iter()
+                .map(|(vendor_id, product_id)| DeviceIdentity {
+                    vendor_id,
+                    product_id,
+                    serial: None,
+                    instance_id: None,
+                })
+                .collect();
+            let mut plan = plan_batch("unblock_all_trusted", identities, None)?;
+            // Trusted devices that are not plugged in are simply skipped
+            plan.unmatched.clear();
+            return Ok(Planned::Simulated(plan));
         }
-    }
-    
-    Ok(())
+        admin_pin::require_admin(admin_token.as_deref(), "unblock all trusted devices")?;
+        security_key::require_presence("unblock all trusted devices")?;
+        hello::require_consent("unblock all trusted devices")?;
+        for (vendor_id, product_id) in app_state().trusted_devices() {
+            if let Err(e) = enable_device(vendor_id, product_id, None, None, None) {
+                log::error!("Failed to unblock device: {}", e);
+            }
+        }
+        Ok(Planned::Applied(()))
+    })
+    .await
 }
 
@@ -0,0 +1,219 @@
+use std::fs;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::class_policy::{self, ClassAction, ClassPolicy};
+use super::commands;
+use super::dry_run;
+use super::error::UsbShieldError;
+use super::operations;
+use super::policy_diff::{self, PolicyChange};
+use super::port_locks;
+use super::trust_rules::{self, TrustBinding};
+
+/// Bumped whenever a section changes shape; older files still import.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// The portable part of one machine's setup, in one file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub exported_from: Option<String>,
+    pub autoblock: bool,
+    pub trusted_devices: Vec<(u16, u16)>,
+    pub class_policies: Vec<ClassPolicy>,
+    /// Locked location paths; the hub behind each is looked up again on
+    /// the importing machine
+    pub port_locks: Vec<String>,
+    /// Serial and volume pins on trusted models; missing before version 2
+    #[serde(default)]
+    pub trust_bindings: Vec<TrustBinding>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigImport {
+    pub version: u32,
+    pub exported_from: Option<String>,
+    pub changes: Vec<PolicyChange>,
+    /// Settings that could not be applied; the rest were
+    pub errors: Vec<String>,
+}
+
+fn current() -> Result<ConfigFile, String> {
+    let state = commands::app_state();
+    Ok(ConfigFile {
+        version: CONFIG_VERSION,
+        exported_at: Utc::now(),
+        exported_from: std::env::var("COMPUTERNAME").ok(),
+        autoblock: state.autoblock_enabled(),
+        trusted_devices: state.trusted_devices(),
+        class_policies: class_policy::get_class_policies()?,
+        port_locks: port_locks::get_port_locks()?
+            .into_iter()
+            .map(|lock| lock.location_path)
+            .collect(),
+        trust_bindings: trust_rules::get_trust_bindings()?,
+    })
+}
+
+// The settings alone, for diffing one configuration against another
+fn settings(config: &ConfigFile) -> serde_json::Value {
+    json!({
+        "autoblock": config.autoblock,
+        "trusted_devices": config.trusted_devices,
+        "class_policies": config.class_policies,
+        "port_locks": config.port_locks,
+        "trust_bindings": config.trust_bindings,
+    })
+}
+
+/// The settings in `value` if it is a configuration export. Exports carry
+/// fewer sections than the support-bundle policy, and port locks as bare
+/// paths, so they are only ever diffed against another export or
+/// `live_settings`.
+pub fn settings_of(value: &serde_json::Value) -> Option<serde_json::Value> {
+    serde_json::from_value::<ConfigFile>(value.clone()).ok().map(|config| settings(&config))
+}
+
+/// This machine's settings in the shape of an export.
+pub fn live_settings() -> Result<serde_json::Value, String> {
+    Ok(settings(&current()?))
+}
+
+fn same_unit(a: &TrustBinding, b: &TrustBinding) -> bool {
+    a.vendor_id == b.vendor_id && a.product_id == b.product_id && a.serial == b.serial
+}
+
+/// Write trusted devices and their bindings, class policies, autoblock mode
+/// and port locks to `path`, for `import_config` on another machine.
+#[command]
+pub fn export_config(path: String) -> Result<ConfigFile, String> {
+    let config = current()?;
+    let data = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    audit::record("config_exported", json!({ "path": path, "version": config.version }));
+    Ok(config)
+}
+
+/// Make this machine's configuration match the file at `path`: whatever it
+/// does not list is removed. Each setting is guarded as it is when changed
+/// by hand, so dropping trust, unlocking a port or turning autoblock off
+/// needs an admin session; a setting that fails is reported and the rest
+/// still apply.
+#[command]
+pub async fn import_config(path: String, admin_token: Option<String>) -> Result<ConfigImport, UsbShieldError> {
+    // Its parts would be refused one by one, leaving a partial import
+    dry_run::refuse("import_config")?;
+    operations::run("import_config", move || apply_import(path, admin_token)).await
+}
+
+fn apply_import(path: String, admin_token: Option<String>) -> Result<ConfigImport, UsbShieldError> {
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let config: ConfigFile =
+        serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    if config.version > CONFIG_VERSION {
+        return Err(format!(
+            "{} is configuration version {}; this build reads up to {}",
+            path, config.version, CONFIG_VERSION
+        )
+        .into());
+    }
+    let before = current()?;
+    // Asked once up front, so a missing admin session fails the whole import
+    // with its own kind instead of showing up as text in `errors`
+    let relaxes = before.trusted_devices.iter().any(|model| !config.trusted_devices.contains(model))
+        || before.port_locks.iter().any(|locked| {
+            !config.port_locks.iter().any(|wanted| wanted.eq_ignore_ascii_case(locked))
+        })
+        || (before.autoblock && !config.autoblock)
+        || before.class_policies.iter().any(|policy| {
+            policy.action == ClassAction::Block
+                && !config
+                    .class_policies
+                    .iter()
+                    .any(|wanted| wanted.class_code == policy.class_code && wanted.action == ClassAction::Block)
+        })
+        || config.class_policies.iter().any(|policy| {
+            policy.action == ClassAction::Allow
+                && !before
+                    .class_policies
+                    .iter()
+                    .any(|had| had.class_code == policy.class_code && had.action == ClassAction::Allow)
+        })
+        // A unit that loses its pin falls back to plain VID/PID trust
+        || before.trust_bindings.iter().any(|bound| !config.trust_bindings.contains(bound));
+    if relaxes {
+        admin_pin::require_admin(admin_token.as_deref(), "import a configuration that relaxes protection")?;
+    }
+    let changes = policy_diff::diff(&settings(&before), &settings(&config));
+    let state = commands::app_state();
+    let admin_token = admin_token.as_deref();
+    let mut errors = Vec::new();
+
+    if let Err(e) = state.set_trusted_devices(config.trusted_devices.clone(), admin_token) {
+        errors.push(format!("trusted_devices: {}", e));
+    }
+    // Pins go on before any come off, as with port locks
+    for binding in &config.trust_bindings {
+        if !before.trust_bindings.contains(binding) {
+            if let Err(e) = trust_rules::install_binding(binding.clone()) {
+                errors.push(format!("trust_bindings[{:04x}:{:04x}]: {}", binding.vendor_id, binding.product_id, e));
+            }
+        }
+    }
+    for binding in &before.trust_bindings {
+        if !config.trust_bindings.iter().any(|wanted| same_unit(wanted, binding)) {
+            let (vendor_id, product_id) = (binding.vendor_id, binding.product_id);
+            if let Err(e) = trust_rules::remove_trust_binding(vendor_id, product_id, binding.serial.clone()) {
+                errors.push(format!("trust_bindings[{:04x}:{:04x}]: {}", vendor_id, product_id, e));
+            }
+        }
+    }
+    for policy in &before.class_policies {
+        if !config.class_policies.iter().any(|p| p.class_code == policy.class_code) {
+            if let Err(e) = class_policy::set_class_policy(policy.class_code, None, admin_token.map(String::from)) {
+                errors.push(format!("class_policies[{}]: {}", policy.class_code, e));
+            }
+        }
+    }
+    for policy in &config.class_policies {
+        let action = Some(policy.action);
+        if let Err(e) = class_policy::set_class_policy(policy.class_code, action, admin_token.map(String::from)) {
+            errors.push(format!("class_policies[{}]: {}", policy.class_code, e));
+        }
+    }
+    // Locks go on before any come off, so no port is open in between
+    for location_path in &config.port_locks {
+        if !before.port_locks.iter().any(|locked| locked.eq_ignore_ascii_case(location_path)) {
+            if let Err(e) = port_locks::block_port(location_path.clone()) {
+                errors.push(format!("port_locks[{}]: {}", location_path, e));
+            }
+        }
+    }
+    for location_path in &before.port_locks {
+        if !config.port_locks.iter().any(|wanted| wanted.eq_ignore_ascii_case(location_path)) {
+            if let Err(e) = port_locks::unblock_port(location_path.clone(), admin_token.map(String::from)) {
+                errors.push(format!("port_locks[{}]: {}", location_path, e));
+            }
+        }
+    }
+    if config.autoblock != before.autoblock {
+        if let Err(e) = state.set_autoblock_mode(config.autoblock, admin_token) {
+            errors.push(format!("autoblock: {}", e));
+        }
+    }
+
+    let import = ConfigImport {
+        version: config.version,
+        exported_from: config.exported_from,
+        changes,
+        errors,
+    };
+    audit::record("config_imported", json!({ "path": path, "import": import }));
+    Ok(import)
+}
@@ -0,0 +1,428 @@
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::DeviceAndDriverInstallation::{
+            CM_Get_DevNode_Status, CM_Get_Device_IDW, CM_Get_Parent, SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo,
+            SetupDiGetClassDevsW, SetupDiGetDeviceInstanceIdW, SetupDiGetDeviceRegistryPropertyW,
+            CM_PROB_DISABLED, CR_SUCCESS, DIGCF_ALLCLASSES, DIGCF_PRESENT, DN_STARTED, HDEVINFO,
+            SPDRP_BASE_CONTAINERID,
+            SPDRP_COMPATIBLEIDS, SPDRP_DEVICEDESC, SPDRP_FRIENDLYNAME, SPDRP_LOCATION_PATHS, SP_DEVINFO_DATA,
+        },
+        Foundation::HWND,
+    },
+};
+
+use super::category::InterfaceClass;
+use super::drivers::{self, DriverInfo, FunctionDriver};
+
+/// A present USB devnode as seen by SetupAPI.
+#[derive(Debug, Clone)]
+pub struct DevNode {
+    pub instance_id: String,
+    pub parent_instance_id: Option<String>,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Friendly name, else the driver's device description: what Device
+    /// Manager shows.
+    pub description: Option<String>,
+    /// Last instance ID segment when it is a real serial rather than a
+    /// Windows-generated `&`-separated location key.
+    pub serial: Option<String>,
+    /// Hub port numbers from the root hub down, parsed from the
+    /// `USB(n)` components of the devnode's location path.
+    pub port_chain: Vec<u8>,
+    /// The location path `port_chain` came from, e.g.
+    /// `PCIROOT(0)#PCI(1400)#USBROOT(0)#USB(3)#USB(2)`. Names the physical
+    /// port no matter what is plugged into it.
+    pub location_path: Option<String>,
+    /// Physical-device container GUID; shared by every function of one
+    /// piece of hardware (a dock's hub, NIC and audio all carry the dock's).
+    pub container_id: Option<String>,
+    /// First ancestor outside the USB enumerator: the host controller the
+    /// device hangs off (`PCI\VEN_8086&...`, or a virtual one for remote USB).
+    pub controller_instance_id: Option<String>,
+    pub dev_inst: u32,
+    /// Driver loaded and running.
+    pub started: bool,
+    /// Disabled through DICS_DISABLE (problem code CM_PROB_DISABLED).
+    pub disabled: bool,
+    /// `None` when no driver is installed for it.
+    pub driver: Option<DriverInfo>,
+    /// Drivers of its interfaces and the functions below them
+    pub function_drivers: Vec<FunctionDriver>,
+}
+
+/// Enumerate every present devnode under the USB enumerator that carries a VID/PID.
+/// Interface children (`USB\VID_xxxx&PID_xxxx&MI_xx`) are skipped; they are
+/// addressed through their parent device.
+pub fn enumerate_usb_devnodes() -> Result<Vec<DevNode>, String> {
+    let enumerator = wide("USB");
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            None,
+            PCWSTR(enumerator.as_ptr()),
+            HWND(0),
+            DIGCF_PRESENT | DIGCF_ALLCLASSES,
+        )
+        .map_err(|e| format!("Failed to get device information set: {}", e))?;
+
+        let mut nodes = Vec::new();
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+
+        for index in 0.. {
+            if !SetupDiEnumDeviceInfo(device_info_set, index, &mut device_info_data).as_bool() {
+                break;
+            }
+
+            let instance_id = match instance_id(device_info_set, &device_info_data) {
+                Some(id) => id,
+                None => continue,
+            };
+            let (vendor_id, product_id) = match parse_vid_pid(&instance_id) {
+                Some(ids) => ids,
+                None => continue,
+            };
+            if instance_id.to_ascii_uppercase().contains("&MI_") {
+                continue;
+            }
+
+            let (started, disabled) = devnode_status(device_info_data.DevInst);
+            let location_path = registry_strings(device_info_set, &device_info_data, SPDRP_LOCATION_PATHS)
+                .into_iter()
+                .find(|path| parse_port_chain(path).is_some());
+            nodes.push(DevNode {
+                serial: parse_serial(&instance_id),
+                description: registry_strings(device_info_set, &device_info_data, SPDRP_FRIENDLYNAME)
+                    .into_iter()
+                    .chain(registry_strings(device_info_set, &device_info_data, SPDRP_DEVICEDESC))
+                    .next(),
+                parent_instance_id: parent_instance_id(device_info_data.DevInst),
+                controller_instance_id: controller_instance_id(device_info_data.DevInst),
+                container_id: registry_strings(device_info_set, &device_info_data, SPDRP_BASE_CONTAINERID)
+                    .into_iter()
+                    .next(),
+                port_chain: location_path.as_deref().and_then(parse_port_chain).unwrap_or_default(),
+                location_path,
+                instance_id,
+                vendor_id,
+                product_id,
+                driver: drivers::read(device_info_set, &device_info_data),
+                function_drivers: drivers::function_drivers(device_info_data.DevInst),
+                dev_inst: device_info_data.DevInst,
+                started,
+                disabled,
+            });
+        }
+
+        SetupDiDestroyDeviceInfoList(device_info_set);
+        Ok(nodes)
+    }
+}
+
+/// One function of a composite device: an `&MI_xx` child devnode.
+#[derive(Debug, Clone)]
+pub struct InterfaceNode {
+    pub instance_id: String,
+    pub interface_number: u8,
+    /// From the `USB\Class_xx&SubClass_xx&Prot_xx` compatible ID
+    pub class: Option<InterfaceClass>,
+    pub description: Option<String>,
+    pub disabled: bool,
+}
+
+/// Enumerate the interface children of the USB device `device_instance_id`.
+/// Empty for a device that is not composite.
+pub fn enumerate_interface_devnodes(device_instance_id: &str) -> Result<Vec<InterfaceNode>, String> {
+    let enumerator = wide("USB");
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            None,
+            PCWSTR(enumerator.as_ptr()),
+            HWND(0),
+            DIGCF_PRESENT | DIGCF_ALLCLASSES,
+        )
+        .map_err(|e| format!("Failed to get device information set: {}", e))?;
+
+        let mut nodes = Vec::new();
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+
+        for index in 0.. {
+            if !SetupDiEnumDeviceInfo(device_info_set, index, &mut device_info_data).as_bool() {
+                break;
+            }
+            let instance_id = match instance_id(device_info_set, &device_info_data) {
+                Some(id) => id,
+                None => continue,
+            };
+            let interface_number = match parse_interface_number(&instance_id) {
+                Some(number) => number,
+                None => continue,
+            };
+            let is_child = parent_instance_id(device_info_data.DevInst)
+                .is_some_and(|parent| parent.eq_ignore_ascii_case(device_instance_id));
+            if !is_child {
+                continue;
+            }
+            let (_, disabled) = devnode_status(device_info_data.DevInst);
+            nodes.push(InterfaceNode {
+                class: registry_strings(device_info_set, &device_info_data, SPDRP_COMPATIBLEIDS)
+                    .iter()
+                    .find_map(|id| parse_class_id(id)),
+                description: registry_strings(device_info_set, &device_info_data, SPDRP_FRIENDLYNAME)
+                    .into_iter()
+                    .chain(registry_strings(device_info_set, &device_info_data, SPDRP_DEVICEDESC))
+                    .next(),
+                instance_id,
+                interface_number,
+                disabled,
+            });
+        }
+
+        SetupDiDestroyDeviceInfoList(device_info_set);
+        nodes.sort_by_key(|node| node.interface_number);
+        Ok(nodes)
+    }
+}
+
+/// `USB\VID_22B8&PID_2E82&MI_01\7&1A2B3C&0&0001` -> `1`
+pub fn parse_interface_number(instance_id: &str) -> Option<u8> {
+    let upper = instance_id.to_ascii_uppercase();
+    let at = upper.find("&MI_")? + 4;
+    u8::from_str_radix(upper.get(at..at + 2)?, 16).ok()
+}
+
+/// `USB\Class_08&SubClass_06&Prot_50` -> the class triple
+pub fn parse_class_id(compatible_id: &str) -> Option<InterfaceClass> {
+    let upper = compatible_id.to_ascii_uppercase();
+    let field = |name: &str| {
+        let at = upper.find(name)? + name.len();
+        u8::from_str_radix(upper.get(at..at + 2)?, 16).ok()
+    };
+    Some(InterfaceClass {
+        class_code: field("CLASS_")?,
+        sub_class_code: field("SUBCLASS_")?,
+        protocol_code: field("PROT_")?,
+    })
+}
+
+/// Map a libusb device (by its hub port chain) to its Windows devnode.
+///
+/// The port chain is the primary key: it is unique for every physically
+/// attached device, so identical devices on different ports never collide.
+/// A serial match is accepted as a fallback when the location path is
+/// unavailable. A bare VID/PID match is only used when it is unambiguous;
+/// otherwise `None` is returned rather than guessing.
+pub fn correlate<'a>(
+    ports: &[u8],
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<&str>,
+    devnodes: &'a [DevNode],
+) -> Option<&'a DevNode> {
+    let candidates: Vec<&DevNode> = devnodes
+        .iter()
+        .filter(|node| node.vendor_id == vendor_id && node.product_id == product_id)
+        .collect();
+
+    if !ports.is_empty() {
+        let by_port: Vec<&DevNode> = candidates
+            .iter()
+            .copied()
+            .filter(|node| node.port_chain == ports)
+            .collect();
+        match by_port.len() {
+            1 => return Some(by_port[0]),
+            // Same chain under two root hubs; fall through to the serial.
+            n if n > 1 => return pick_by_serial(&by_port, serial),
+            _ => {}
+        }
+    }
+
+    if let Some(node) = pick_by_serial(&candidates, serial) {
+        return Some(node);
+    }
+
+    match candidates.as_slice() {
+        [only] => Some(only),
+        _ => None,
+    }
+}
+
+fn pick_by_serial<'a>(candidates: &[&'a DevNode], serial: Option<&str>) -> Option<&'a DevNode> {
+    let serial = serial?;
+    let mut matches = candidates
+        .iter()
+        .filter(|node| node.serial.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(serial)));
+    match (matches.next(), matches.next()) {
+        (Some(node), None) => Some(node),
+        _ => None,
+    }
+}
+
+/// Parse `VID_xxxx&PID_xxxx` out of a hardware or instance ID.
+pub fn parse_vid_pid(id: &str) -> Option<(u16, u16)> {
+    let upper = id.to_ascii_uppercase();
+    let vid_at = upper.find("VID_")? + 4;
+    let pid_at = upper.find("PID_")? + 4;
+    let vendor_id = u16::from_str_radix(upper.get(vid_at..vid_at + 4)?, 16).ok()?;
+    let product_id = u16::from_str_radix(upper.get(pid_at..pid_at + 4)?, 16).ok()?;
+    Some((vendor_id, product_id))
+}
+
+pub fn parse_serial(instance_id: &str) -> Option<String> {
+    let last = instance_id.rsplit('\\').next()?;
+    // Devices without a serial get a generated key such as `5&2b3c8f1&0&3`.
+    if last.is_empty() || last.contains('&') {
+        None
+    } else {
+        Some(last.to_string())
+    }
+}
+
+/// `PCIROOT(0)#PCI(1400)#USBROOT(0)#USB(3)#USB(2)` -> `[3, 2]`
+pub fn parse_port_chain(location_path: &str) -> Option<Vec<u8>> {
+    let chain: Vec<u8> = location_path
+        .split('#')
+        .filter_map(|part| part.strip_prefix("USB(")?.strip_suffix(')')?.parse().ok())
+        .collect();
+    if chain.is_empty() {
+        None
+    } else {
+        Some(chain)
+    }
+}
+
+/// Format a bus number and hub port chain the way libusb/`lsusb -t` does:
+/// bus 1, ports [3, 2] -> `"1-3.2"`. Root hubs (empty chain) have no location.
+pub fn format_port_chain(bus: u8, ports: &[u8]) -> Option<String> {
+    if ports.is_empty() {
+        return None;
+    }
+    let ports: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+    Some(format!("{}-{}", bus, ports.join(".")))
+}
+
+pub unsafe fn instance_id(device_info_set: HDEVINFO, device_info_data: &SP_DEVINFO_DATA) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    if SetupDiGetDeviceInstanceIdW(device_info_set, device_info_data, Some(&mut buffer), None).as_bool() {
+        Some(from_wide(&buffer))
+    } else {
+        None
+    }
+}
+
+/// Read a REG_SZ or REG_MULTI_SZ device registry property as a list of strings.
+pub unsafe fn registry_strings(
+    device_info_set: HDEVINFO,
+    device_info_data: &SP_DEVINFO_DATA,
+    property: u32,
+) -> Vec<String> {
+    let mut buffer = [0u8; 1024];
+    if !SetupDiGetDeviceRegistryPropertyW(
+        device_info_set,
+        device_info_data,
+        property,
+        None,
+        Some(&mut buffer),
+        None,
+    )
+    .as_bool()
+    {
+        return Vec::new();
+    }
+
+    // REG_MULTI_SZ: NUL separated, double NUL terminated. REG_SZ is the one-element case.
+    let wide: Vec<u16> = buffer
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    wide.split(|&c| c == 0)
+        .take_while(|s| !s.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+pub unsafe fn devnode_status(dev_inst: u32) -> (bool, bool) {
+    let mut status = 0u32;
+    let mut problem = 0u32;
+    if CM_Get_DevNode_Status(&mut status, &mut problem, dev_inst, 0) != CR_SUCCESS {
+        return (false, false);
+    }
+    (status & DN_STARTED != 0, problem == CM_PROB_DISABLED)
+}
+
+unsafe fn parent_instance_id(dev_inst: u32) -> Option<String> {
+    parent(dev_inst).map(|(_, id)| id)
+}
+
+unsafe fn parent(dev_inst: u32) -> Option<(u32, String)> {
+    let mut parent = 0u32;
+    if CM_Get_Parent(&mut parent, dev_inst, 0) != CR_SUCCESS {
+        return None;
+    }
+    let mut buffer = [0u16; 512];
+    if CM_Get_Device_IDW(parent, &mut buffer, 0) != CR_SUCCESS {
+        return None;
+    }
+    Some((parent, from_wide(&buffer)))
+}
+
+/// Walk up through hubs (`USB\...`) to the host controller.
+unsafe fn controller_instance_id(dev_inst: u32) -> Option<String> {
+    let mut current = dev_inst;
+    for _ in 0..16 {
+        let (parent_inst, id) = parent(current)?;
+        if !id.to_ascii_uppercase().starts_with("USB\\") {
+            return Some(id);
+        }
+        current = parent_inst;
+    }
+    None
+}
+
+/// Instance ID of the USB device a function devnode (a disk, a COM port,
+/// an `&MI_xx` interface) ultimately belongs to.
+pub fn usb_device_ancestor(dev_inst: u32) -> Option<String> {
+    let mut current = dev_inst;
+    for _ in 0..16 {
+        let (parent_inst, id) = unsafe { parent(current)? };
+        let upper = id.to_ascii_uppercase();
+        if upper.starts_with("USB\\") && !upper.contains("&MI_") {
+            return Some(id);
+        }
+        current = parent_inst;
+    }
+    None
+}
+
+/// Walk up the parent chain (self included) within the enumerated devnodes.
+pub fn ancestors<'a>(node: &'a DevNode, devnodes: &'a [DevNode]) -> Vec<&'a DevNode> {
+    let mut chain = vec![node];
+    let mut current = node;
+    while let Some(parent_id) = current.parent_instance_id.as_deref() {
+        match devnodes.iter().find(|n| n.instance_id.eq_ignore_ascii_case(parent_id)) {
+            // Guard against a malformed cycle
+            Some(parent) if chain.len() < 16 => {
+                chain.push(parent);
+                current = parent;
+            }
+            _ => break,
+        }
+    }
+    chain
+}
+
+pub(crate) fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+pub(crate) fn from_wide(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
@@ -0,0 +1,153 @@
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+use super::audit;
+use super::backend;
+use super::status;
+use super::trust_rules::bind_trusted_device;
+use super::usb_control::{self, BlockReason};
+
+pub const SCHEME: &str = "usb-shield";
+
+const CANCELLED: &str = "Cancelled";
+
+/// What a `usb-shield://` link asks for. Anything that changes enforcement
+/// is confirmed in a native dialog first, since any web page can open a link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum DeepLinkAction {
+    /// `usb-shield://status`: bring the main window forward
+    Status,
+    /// `usb-shield://widget`: open or close the status widget
+    Widget,
+    /// `usb-shield://approve?serial=XYZ`: trust the present device with that serial
+    Approve { serial: String },
+    /// `usb-shield://block?serial=XYZ`
+    Block { serial: String },
+}
+
+impl DeepLinkAction {
+    fn mutating(&self) -> bool {
+        matches!(self, DeepLinkAction::Approve { .. } | DeepLinkAction::Block { .. })
+    }
+}
+
+pub fn parse(url: &str) -> Result<DeepLinkAction, String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid link {}: {}", url, e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("Not a {}:// link", SCHEME));
+    }
+    let serial = || {
+        url.query_pairs()
+            .find(|(key, _)| key == "serial")
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| "The link needs a serial parameter".to_string())
+    };
+    match url.host_str().unwrap_or_default().to_ascii_lowercase().as_str() {
+        "status" => Ok(DeepLinkAction::Status),
+        "widget" => Ok(DeepLinkAction::Widget),
+        "approve" => Ok(DeepLinkAction::Approve { serial: serial()? }),
+        "block" => Ok(DeepLinkAction::Block { serial: serial()? }),
+        other => Err(format!("Unknown link action: {}", other)),
+    }
+}
+
+/// Hook up link handling: links opened while running, and the one the app
+/// was launched with. Called once from setup.
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    // Installers register the scheme; a dev build has to do it itself
+    #[cfg(debug_assertions)]
+    app.deep_link().register_all().map_err(|e| e.to_string())?;
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            dispatch(&handle, url.to_string());
+        }
+    });
+    if let Some(urls) = app.deep_link().get_current().map_err(|e| e.to_string())? {
+        for url in urls {
+            dispatch(app, url.to_string());
+        }
+    }
+    Ok(())
+}
+
+// Off the main thread: the confirmation dialog blocks until answered
+fn dispatch(app: &AppHandle, url: String) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let result = parse(&url).and_then(|action| run(&app, &action).map(|()| action));
+        audit::record(
+            "deep_link",
+            json!({ "url": url, "action": result.as_ref().ok(), "error": result.as_ref().err() }),
+        );
+        match result {
+            Err(e) if e != CANCELLED => {
+                app.dialog()
+                    .message(e)
+                    .title("USB-Shield")
+                    .kind(MessageDialogKind::Error)
+                    .blocking_show();
+            }
+            _ => {}
+        }
+    });
+}
+
+fn run(app: &AppHandle, action: &DeepLinkAction) -> Result<(), String> {
+    if action.mutating() && !confirm(app, action)? {
+        return Err(CANCELLED.to_string());
+    }
+    match action {
+        DeepLinkAction::Status => {
+            let window = app
+                .get_webview_window("main")
+                .ok_or_else(|| "The main window is not open".to_string())?;
+            window
+                .show()
+                .and_then(|()| window.unminimize())
+                .and_then(|()| window.set_focus())
+                .map_err(|e| e.to_string())
+        }
+        DeepLinkAction::Widget => status::toggle_widget(app).map(|_| ()),
+        DeepLinkAction::Approve { serial } => bind_trusted_device(instance_for_serial(serial)?).map(|_| ()),
+        DeepLinkAction::Block { serial } => {
+            usb_control::block(&instance_for_serial(serial)?, BlockReason::DeepLink)
+                .map(|_| ())
+                .map_err(String::from)
+        }
+    }
+}
+
+fn confirm(app: &AppHandle, action: &DeepLinkAction) -> Result<bool, String> {
+    let message = match action {
+        DeepLinkAction::Approve { serial } => format!("A link asks to trust the USB device with serial {}.", serial),
+        DeepLinkAction::Block { serial } => format!("A link asks to block the USB device with serial {}.", serial),
+        _ => return Ok(true),
+    };
+    Ok(app
+        .dialog()
+        .message(format!("{} Only continue if you opened this link yourself.", message))
+        .title("USB-Shield")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("Continue".to_string(), "Cancel".to_string()))
+        .blocking_show())
+}
+
+/// The present device carrying `serial`. Ambiguous serials are refused; some
+/// vendors ship every unit with the same one.
+fn instance_for_serial(serial: &str) -> Result<String, String> {
+    let devnodes = backend::controller().devnodes()?;
+    let mut matches = devnodes
+        .iter()
+        .filter(|node| node.serial.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(serial)));
+    match (matches.next(), matches.next()) {
+        (Some(node), None) => Ok(node.instance_id.clone()),
+        (None, _) => Err(format!("No connected device has serial {}", serial)),
+        (Some(_), Some(_)) => Err(format!("More than one connected device has serial {}", serial)),
+    }
+}
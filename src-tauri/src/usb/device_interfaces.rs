@@ -0,0 +1,191 @@
+use std::{fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::category::{self, DeviceCategory, InterfaceClass};
+use super::commands::{DeviceState, UsbDeviceInfo};
+use super::correlation::{parse_vid_pid, InterfaceNode};
+use super::dry_run;
+use super::error::UsbShieldError;
+use super::hello;
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const INTERFACE_RULES_FILE: &str = "interface-rules.json";
+
+/// One function of a composite device, e.g. the MTP, ADB or mass storage
+/// side of a phone, which can be blocked while the rest keeps working.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInterface {
+    pub instance_id: String,
+    pub interface_number: u8,
+    pub class: Option<InterfaceClass>,
+    pub category: Option<DeviceCategory>,
+    pub description: Option<String>,
+    pub blocked: bool,
+}
+
+/// One interface kept blocked, on this device only, whenever it is plugged in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceRule {
+    /// The composite device, not its `&MI_xx` child
+    pub instance_id: String,
+    pub interface_number: u8,
+}
+
+lazy_static! {
+    static ref RULES: Mutex<Vec<InterfaceRule>> = Mutex::new(Vec::new());
+}
+
+fn read_rules() -> Result<Vec<InterfaceRule>, String> {
+    let path = usb_config::data_file(INTERFACE_RULES_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_rules(rules: &[InterfaceRule]) -> Result<(), String> {
+    let path = usb_config::data_file(INTERFACE_RULES_FILE)?;
+    let data = serde_json::to_vec_pretty(rules).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Restore the saved rules at startup.
+pub fn load() -> Result<(), String> {
+    *RULES.lock().unwrap() = read_rules()?;
+    Ok(())
+}
+
+fn describe(device_instance_id: &str, node: InterfaceNode) -> DeviceInterface {
+    let vendor_id = parse_vid_pid(device_instance_id).map_or(0, |(vendor_id, _)| vendor_id);
+    DeviceInterface {
+        category: node.class.map(|class| category::classify(0, vendor_id, &[class])),
+        instance_id: node.instance_id,
+        interface_number: node.interface_number,
+        class: node.class,
+        description: node.description,
+        blocked: node.disabled,
+    }
+}
+
+// The named interface of the device, as it is now
+fn find(instance_id: &str, interface: u8) -> Result<DeviceInterface, UsbShieldError> {
+    get_device_interfaces(instance_id.to_string())?
+        .into_iter()
+        .find(|i| i.interface_number == interface)
+        .ok_or_else(|| UsbShieldError::DeviceNotFound {
+            device: format!("{} interface {}", instance_id, interface),
+        })
+}
+
+fn set_interface_state(instance_id: &str, interface: u8, enable: bool) -> Result<DeviceInterface, UsbShieldError> {
+    let target = find(instance_id, interface)?;
+    if enable {
+        usb_control::unblock(&target.instance_id)
+    } else {
+        usb_control::block(&target.instance_id, BlockReason::Manual)
+    }?;
+    {
+        let mut rules = RULES.lock().unwrap();
+        let mut updated = rules.clone();
+        updated.retain(|rule| {
+            !(rule.instance_id.eq_ignore_ascii_case(instance_id) && rule.interface_number == interface)
+        });
+        if !enable {
+            updated.push(InterfaceRule {
+                instance_id: instance_id.to_string(),
+                interface_number: interface,
+            });
+        }
+        save_rules(&updated)?;
+        *rules = updated;
+    }
+    audit::record(
+        if enable { "device_interface_unblocked" } else { "device_interface_blocked" },
+        json!({ "instance_id": instance_id, "interface": target }),
+    );
+    find(instance_id, interface)
+}
+
+/// Block the interfaces a rule keeps off on a device that just arrived. The
+/// rest of the device is left to the other policies.
+pub fn enforce(device: &UsbDeviceInfo) {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) if device.state() == DeviceState::Connected => instance_id,
+        _ => return,
+    };
+    let blocked: Vec<u8> = RULES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|rule| rule.instance_id.eq_ignore_ascii_case(instance_id))
+        .map(|rule| rule.interface_number)
+        .collect();
+    if blocked.is_empty() {
+        return;
+    }
+    let nodes = match backend::controller().interface_nodes(instance_id) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            log::error!("Failed to list the interfaces of {}: {}", instance_id, e);
+            return;
+        }
+    };
+    for node in nodes.iter().filter(|node| blocked.contains(&node.interface_number) && !node.disabled) {
+        let result = usb_control::block(&node.instance_id, BlockReason::Manual);
+        audit::record(
+            "device_interface_blocked",
+            json!({
+                "instance_id": instance_id,
+                "interface": node.interface_number,
+                "on_arrival": true,
+                "error": result.as_ref().err().map(ToString::to_string),
+            }),
+        );
+    }
+}
+
+#[command]
+pub fn get_interface_rules() -> Result<Vec<InterfaceRule>, String> {
+    Ok(RULES.lock().unwrap().clone())
+}
+
+/// The functions of a composite device. Empty for a single-function device,
+/// which is blocked as a whole.
+#[command]
+pub fn get_device_interfaces(instance_id: String) -> Result<Vec<DeviceInterface>, String> {
+    Ok(backend::controller()
+        .interface_nodes(&instance_id)?
+        .into_iter()
+        .map(|node| describe(&instance_id, node))
+        .collect())
+}
+
+/// Disable one function of a composite device and leave the others, and
+/// the device's power, alone: e.g. keep a phone charging and its keyboard
+/// working while its storage is off. It stays off whenever the device is
+/// plugged in again, until unblocked.
+#[command]
+pub fn block_device_interface(instance_id: String, interface: u8) -> Result<DeviceInterface, UsbShieldError> {
+    dry_run::refuse("block_device_interface")?;
+    set_interface_state(&instance_id, interface, false)
+}
+
+#[command]
+pub fn unblock_device_interface(
+    instance_id: String,
+    interface: u8,
+    admin_token: Option<String>,
+) -> Result<DeviceInterface, UsbShieldError> {
+    dry_run::refuse("unblock_device_interface")?;
+    admin_pin::require_admin(admin_token.as_deref(), "unblock a device function")?;
+    hello::require_consent("unblock a device function")?;
+    set_interface_state(&instance_id, interface, true)
+}
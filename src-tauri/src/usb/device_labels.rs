@@ -0,0 +1,155 @@
+use std::{fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::usb_config;
+
+const LABELS_FILE: &str = "device-labels.json";
+
+/// A name and note the user gave a device, e.g. "Anna's backup drive" and
+/// "encrypted, kept in the safe". With a serial it belongs to one unit;
+/// without, to every device of the VID/PID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceLabel {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+lazy_static! {
+    // Loaded on first use
+    static ref LABELS: Mutex<Option<Vec<DeviceLabel>>> = Mutex::new(None);
+}
+
+fn load() -> Vec<DeviceLabel> {
+    usb_config::data_file(LABELS_FILE)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save(labels: &[DeviceLabel]) -> Result<(), String> {
+    let path = usb_config::data_file(LABELS_FILE)?;
+    let data = serde_json::to_vec_pretty(labels).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn same_serial(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// The label for a device: its own unit's if it has one, else its VID/PID's.
+pub fn lookup(vendor_id: u16, product_id: u16, serial: Option<&str>) -> Option<DeviceLabel> {
+    let mut guard = LABELS.lock().unwrap();
+    let labels = guard.get_or_insert_with(load);
+    let of_model = labels
+        .iter()
+        .filter(|entry| entry.vendor_id == vendor_id && entry.product_id == product_id);
+    of_model
+        .clone()
+        .find(|entry| entry.serial.is_some() && same_serial(entry.serial.as_deref(), serial))
+        .or_else(|| of_model.clone().find(|entry| entry.serial.is_none()))
+        .cloned()
+}
+
+fn update(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    change: impl FnOnce(&mut DeviceLabel),
+) -> Result<DeviceLabel, String> {
+    let mut guard = LABELS.lock().unwrap();
+    let labels = guard.get_or_insert_with(load);
+    let mut entry = labels
+        .iter()
+        .find(|entry| {
+            entry.vendor_id == vendor_id
+                && entry.product_id == product_id
+                && same_serial(entry.serial.as_deref(), serial.as_deref())
+        })
+        .cloned()
+        .unwrap_or(DeviceLabel {
+            vendor_id,
+            product_id,
+            serial: serial.clone(),
+            label: None,
+            note: None,
+        });
+    change(&mut entry);
+
+    let mut updated: Vec<DeviceLabel> = labels
+        .iter()
+        .filter(|other| {
+            !(other.vendor_id == vendor_id
+                && other.product_id == product_id
+                && same_serial(other.serial.as_deref(), serial.as_deref()))
+        })
+        .cloned()
+        .collect();
+    // Nothing left to show: drop the entry instead of keeping a blank one
+    if entry.label.is_some() || entry.note.is_some() {
+        updated.push(entry.clone());
+    }
+    save(&updated)?;
+    *labels = updated;
+    Ok(entry)
+}
+
+// Blank text clears the field
+fn text(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+#[command]
+pub fn get_device_labels() -> Result<Vec<DeviceLabel>, String> {
+    Ok(LABELS.lock().unwrap().get_or_insert_with(load).clone())
+}
+
+/// Name a device. Pass `serial` to name one unit, or leave it out to name
+/// every device of the VID/PID; `None` or blank text removes the name.
+#[command]
+pub fn set_device_label(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    label: Option<String>,
+) -> Result<DeviceLabel, String> {
+    let label = text(label);
+    let entry = update(vendor_id, product_id, serial, |entry| entry.label = label)?;
+    audit::record(
+        "device_label_set",
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "serial": entry.serial, "label": entry.label }),
+    );
+    Ok(entry)
+}
+
+/// Attach a free-text note, addressed like `set_device_label`.
+#[command]
+pub fn set_device_note(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    note: Option<String>,
+) -> Result<DeviceLabel, String> {
+    let note = text(note);
+    let entry = update(vendor_id, product_id, serial, |entry| entry.note = note)?;
+    // The note itself stays out of the audit log; it is the user's own text
+    audit::record(
+        "device_note_set",
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "serial": entry.serial }),
+    );
+    Ok(entry)
+}
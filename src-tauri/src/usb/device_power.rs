@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::{
+    core::GUID,
+    Win32::{
+        Foundation::{ERROR_SUCCESS, HLOCAL},
+        System::{
+            Memory::LocalFree,
+            Power::{
+                PowerGetActiveScheme, PowerReadACValueIndex, PowerReadDCValueIndex, PowerSetActiveScheme,
+                PowerWriteACValueIndex, PowerWriteDCValueIndex,
+            },
+            Registry::HKEY,
+        },
+    },
+};
+use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+use super::audit;
+use super::backend;
+use super::hello;
+
+const ENUM_KEY: &str = r"SYSTEM\CurrentControlSet\Enum";
+
+// Power plan subgroup "USB settings" and its "USB selective suspend setting"
+const GUID_USB_SUBGROUP: GUID = GUID::from_u128(0x2a737441_1930_4402_8d77_b2bebba308a3);
+const GUID_USB_SELECTIVE_SUSPEND: GUID = GUID::from_u128(0x48e6b7a6_50f5_4782_a5d4_53bb8f07e226);
+
+/// The per-device power values under `Enum\<instance>\Device Parameters`.
+/// `None` means the value is absent and the driver default applies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectiveSuspend {
+    pub instance_id: String,
+    /// `SelectiveSuspendEnabled`
+    pub enabled: Option<bool>,
+    /// `EnhancedPowerManagementEnabled`, the "allow the computer to turn off
+    /// this device" box in Device Manager
+    pub enhanced_power_management: Option<bool>,
+}
+
+/// The machine-wide switch in the active power plan. Per-device settings
+/// only matter while this is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbSuspendSetting {
+    pub on_ac: bool,
+    pub on_battery: bool,
+}
+
+/// A USB device the power manager can arm to wake the machine.
+#[derive(Debug, Clone, Serialize)]
+pub struct WakeDevice {
+    pub instance_id: String,
+    /// Name the power manager uses, i.e. the Device Manager name
+    pub name: String,
+    pub armed: bool,
+}
+
+fn flag(key: &RegKey, name: &str) -> Option<bool> {
+    // Drivers write these as REG_DWORD or as a one-byte REG_BINARY
+    key.get_raw_value(name).ok().map(|value| value.bytes.iter().any(|b| *b != 0))
+}
+
+/// Read a device's settings from the registry. Readable without elevation;
+/// writes go through the helper.
+pub fn read_selective_suspend(instance_id: &str) -> Result<SelectiveSuspend, String> {
+    let root = RegKey::predef(HKEY_LOCAL_MACHINE);
+    root.open_subkey(format!(r"{}\{}", ENUM_KEY, instance_id))
+        .map_err(|_| format!("Device not found: {}", instance_id))?;
+    let parameters = root.open_subkey(format!(r"{}\{}\Device Parameters", ENUM_KEY, instance_id)).ok();
+    Ok(SelectiveSuspend {
+        instance_id: instance_id.to_string(),
+        enabled: parameters.as_ref().and_then(|key| flag(key, "SelectiveSuspendEnabled")),
+        enhanced_power_management: parameters
+            .as_ref()
+            .and_then(|key| flag(key, "EnhancedPowerManagementEnabled")),
+    })
+}
+
+/// Run `f` with the active scheme GUID, which PowerGetActiveScheme allocates.
+fn with_active_scheme<T>(f: impl FnOnce(&GUID) -> Result<T, String>) -> Result<T, String> {
+    unsafe {
+        let mut scheme: *mut GUID = std::ptr::null_mut();
+        if PowerGetActiveScheme(None, &mut scheme) != ERROR_SUCCESS || scheme.is_null() {
+            return Err("Failed to read the active power plan".to_string());
+        }
+        let result = f(&*scheme);
+        let _ = LocalFree(HLOCAL(scheme as isize));
+        result
+    }
+}
+
+fn read_usb_suspend_setting() -> Result<UsbSuspendSetting, String> {
+    with_active_scheme(|scheme| unsafe {
+        let (mut ac, mut dc) = (0u32, 0u32);
+        let ac_status = PowerReadACValueIndex(
+            HKEY(0),
+            Some(scheme),
+            Some(&GUID_USB_SUBGROUP),
+            Some(&GUID_USB_SELECTIVE_SUSPEND),
+            &mut ac,
+        );
+        let dc_status = PowerReadDCValueIndex(
+            HKEY(0),
+            Some(scheme),
+            Some(&GUID_USB_SUBGROUP),
+            Some(&GUID_USB_SELECTIVE_SUSPEND),
+            &mut dc,
+        );
+        if ac_status != ERROR_SUCCESS.0 || dc_status != ERROR_SUCCESS.0 {
+            return Err("The power plan has no USB selective suspend setting".to_string());
+        }
+        Ok(UsbSuspendSetting {
+            on_ac: ac != 0,
+            on_battery: dc != 0,
+        })
+    })
+}
+
+#[command]
+pub fn get_selective_suspend(instance_id: String) -> Result<SelectiveSuspend, String> {
+    backend::controller().selective_suspend(&instance_id)
+}
+
+/// Takes effect the next time the device starts, e.g. after a replug.
+#[command]
+pub fn set_selective_suspend(instance_id: String, enabled: bool) -> Result<SelectiveSuspend, String> {
+    let controller = backend::controller();
+    let previous = controller.selective_suspend(&instance_id)?;
+    controller.set_selective_suspend(&instance_id, enabled)?;
+    audit::record(
+        "selective_suspend_changed",
+        json!({ "instance_id": instance_id, "previous": previous.enabled, "enabled": enabled }),
+    );
+    controller.selective_suspend(&instance_id)
+}
+
+#[command]
+pub fn get_usb_suspend_setting() -> Result<UsbSuspendSetting, String> {
+    read_usb_suspend_setting()
+}
+
+/// Change the setting in the active power plan for the current user.
+#[command]
+pub fn set_usb_suspend_setting(setting: UsbSuspendSetting) -> Result<(), String> {
+    let previous = read_usb_suspend_setting().ok();
+    with_active_scheme(|scheme| unsafe {
+        let ac_status = PowerWriteACValueIndex(
+            HKEY(0),
+            scheme,
+            Some(&GUID_USB_SUBGROUP),
+            Some(&GUID_USB_SELECTIVE_SUSPEND),
+            setting.on_ac as u32,
+        );
+        let dc_status = PowerWriteDCValueIndex(
+            HKEY(0),
+            scheme,
+            Some(&GUID_USB_SUBGROUP),
+            Some(&GUID_USB_SELECTIVE_SUSPEND),
+            setting.on_battery as u32,
+        );
+        if ac_status != ERROR_SUCCESS.0 || dc_status != ERROR_SUCCESS.0 {
+            return Err("Failed to write the USB selective suspend setting".to_string());
+        }
+        // Re-applying the scheme makes the change live
+        if PowerSetActiveScheme(HKEY(0), Some(scheme)) != ERROR_SUCCESS {
+            return Err("Failed to apply the power plan".to_string());
+        }
+        Ok(())
+    })?;
+    audit::record("usb_suspend_setting_changed", json!({ "previous": previous, "current": setting }));
+    Ok(())
+}
+
+/// USB devices that can wake the machine. The power manager only knows
+/// devices by name, so they are matched to devnodes on their description.
+#[command]
+pub fn get_wake_devices() -> Result<Vec<WakeDevice>, String> {
+    let controller = backend::controller();
+    let wake = controller.wake_devices()?;
+    Ok(controller
+        .devnodes()?
+        .into_iter()
+        .filter_map(|node| {
+            let name = node.description?;
+            let state = wake.iter().find(|w| w.name == name)?;
+            Some(WakeDevice {
+                instance_id: node.instance_id,
+                name,
+                armed: state.armed,
+            })
+        })
+        .collect())
+}
+
+/// Arming wake lets a device power the machine up unattended, so it asks
+/// for confirmation; disarming does not.
+#[command]
+pub fn set_wake_enabled(instance_id: String, enabled: bool) -> Result<(), String> {
+    let devices = get_wake_devices()?;
+    let device = devices
+        .iter()
+        .find(|d| d.instance_id.eq_ignore_ascii_case(&instance_id))
+        .ok_or_else(|| format!("{} cannot wake the computer", instance_id))?;
+    // Two identical keyboards share a name; changing one would change both
+    if devices.iter().filter(|d| d.name == device.name).count() > 1 {
+        return Err(format!(
+            "{} shares its name with another device; change it in Device Manager instead",
+            device.name
+        ));
+    }
+    if enabled && !device.armed {
+        hello::require_consent("allow a USB device to wake the computer")?;
+    }
+    backend::controller().set_wake_enabled(&device.name, enabled)?;
+    audit::record(
+        "wake_enabled_changed",
+        json!({ "instance_id": instance_id, "name": device.name, "previous": device.armed, "armed": enabled }),
+    );
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::{sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::category::DeviceCategory;
+use super::correlation::{self, DevNode};
+use super::events;
+use super::profiles::{self, Profile};
+
+const SOURCE: &str = "dock";
+pub const EVENT_DOCK_CHANGED: &str = "dock://changed";
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A known docking station, identified by the container ID of its hub.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockPolicy {
+    pub container_id: String,
+    pub name: String,
+    /// Categories trusted automatically when attached behind this dock
+    pub trusted_categories: Vec<DeviceCategory>,
+    /// Profile requested while docked here, if any
+    pub profile: Option<Profile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DockStatus {
+    pub policy: DockPolicy,
+    pub docked: bool,
+}
+
+/// A hub that could be registered as a dock.
+#[derive(Debug, Clone, Serialize)]
+pub struct DockCandidate {
+    pub container_id: String,
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+lazy_static! {
+    static ref DOCKS: Mutex<Vec<DockPolicy>> = Mutex::new(Vec::new());
+    static ref DOCKED: Mutex<Option<String>> = Mutex::new(None);
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        if let Ok(devnodes) = backend::controller().devnodes() {
+            refresh(&devnodes);
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+fn present_dock(devnodes: &[DevNode]) -> Option<DockPolicy> {
+    let docks = DOCKS.lock().unwrap();
+    docks
+        .iter()
+        .find(|dock| {
+            devnodes
+                .iter()
+                .any(|node| node.container_id.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(&dock.container_id)))
+        })
+        .cloned()
+}
+
+/// Re-evaluate which dock (if any) is attached and update the profile request.
+pub fn refresh(devnodes: &[DevNode]) {
+    let dock = present_dock(devnodes);
+    let container = dock.as_ref().map(|d| d.container_id.clone());
+
+    let previous = std::mem::replace(&mut *DOCKED.lock().unwrap(), container.clone());
+    if previous != container {
+        audit::record("dock_changed", json!({ "previous": previous, "current": container }));
+        events::emit(EVENT_DOCK_CHANGED, dock.clone());
+    }
+    profiles::request(SOURCE, dock.and_then(|d| d.profile));
+}
+
+/// True when `container_id` belongs to a registered dock.
+pub fn is_dock(container_id: &str) -> bool {
+    DOCKS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|dock| dock.container_id.eq_ignore_ascii_case(container_id))
+}
+
+/// True when `node` sits behind (or is part of) a registered dock whose
+/// policy trusts `category`. Devices on the laptop's own ports never match.
+pub fn trusted_by_dock(node: &DevNode, category: DeviceCategory, devnodes: &[DevNode]) -> bool {
+    let docks = DOCKS.lock().unwrap();
+    correlation::ancestors(node, devnodes).iter().any(|ancestor| {
+        ancestor.container_id.as_deref().is_some_and(|container| {
+            docks
+                .iter()
+                .any(|dock| dock.container_id.eq_ignore_ascii_case(container) && dock.trusted_categories.contains(&category))
+        })
+    })
+}
+
+#[command]
+pub fn get_docks() -> Result<Vec<DockStatus>, String> {
+    let docked = DOCKED.lock().unwrap().clone();
+    Ok(DOCKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|policy| DockStatus {
+            docked: docked.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(&policy.container_id)),
+            policy: policy.clone(),
+        })
+        .collect())
+}
+
+/// Attached hubs, grouped by container, offered for registration as a dock.
+#[command]
+pub fn get_dock_candidates() -> Result<Vec<DockCandidate>, String> {
+    let devnodes = backend::controller().devnodes()?;
+    let mut candidates: Vec<DockCandidate> = Vec::new();
+    for node in &devnodes {
+        let container_id = match &node.container_id {
+            Some(c) => c.clone(),
+            None => continue,
+        };
+        // Hubs have child devnodes; leaf devices are not docks
+        let has_children = devnodes
+            .iter()
+            .any(|child| {
+                child.parent_instance_id.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(&node.instance_id))
+            });
+        if has_children && !candidates.iter().any(|c| c.container_id.eq_ignore_ascii_case(&container_id)) {
+            candidates.push(DockCandidate {
+                container_id,
+                instance_id: node.instance_id.clone(),
+                vendor_id: node.vendor_id,
+                product_id: node.product_id,
+            });
+        }
+    }
+    Ok(candidates)
+}
+
+#[command]
+pub fn register_dock(policy: DockPolicy) -> Result<(), String> {
+    let mut docks = DOCKS.lock().unwrap();
+    docks.retain(|d| !d.container_id.eq_ignore_ascii_case(&policy.container_id));
+    audit::record("dock_registered", json!(policy));
+    docks.push(policy);
+    drop(docks);
+
+    if let Ok(devnodes) = backend::controller().devnodes() {
+        refresh(&devnodes);
+    }
+    Ok(())
+}
+
+#[command]
+pub fn remove_dock(container_id: String) -> Result<(), String> {
+    DOCKS
+        .lock()
+        .unwrap()
+        .retain(|d| !d.container_id.eq_ignore_ascii_case(&container_id));
+    audit::record("dock_removed", json!({ "container_id": container_id }));
+
+    if let Ok(devnodes) = backend::controller().devnodes() {
+        refresh(&devnodes);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::HLOCAL,
+        Security::Cryptography::{
+            CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+        },
+        System::Memory::LocalFree,
+    },
+};
+
+/// Encrypt `secret` for the current user with DPAPI, as base64 for a JSON
+/// file. Only this user on this machine can decrypt it again.
+pub fn protect(secret: &str) -> Result<String, String> {
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: secret.len() as u32,
+        pbData: secret.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptProtectData(&input, PCWSTR::null(), None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+            .ok()
+            .map_err(|e| e.to_string())?;
+        let protected = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+        let _ = LocalFree(HLOCAL(output.pbData as isize));
+        Ok(STANDARD.encode(protected))
+    }
+}
+
+pub fn unprotect(protected: &str) -> Result<String, String> {
+    let mut data = STANDARD.decode(protected).map_err(|e| e.to_string())?;
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_mut_ptr(),
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptUnprotectData(&input, None, None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+            .ok()
+            .map_err(|e| e.to_string())?;
+        let secret = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+        let _ = LocalFree(HLOCAL(output.pbData as isize));
+        String::from_utf8(secret).map_err(|e| e.to_string())
+    }
+}
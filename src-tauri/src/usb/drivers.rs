@@ -0,0 +1,279 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex, time::SystemTime};
+use chrono::{DateTime, NaiveDate};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::{
+            DeviceAndDriverInstallation::{
+                CM_Get_Child, CM_Get_Device_IDW, CM_Get_Sibling, SetupDiCreateDeviceInfoList,
+                SetupDiDestroyDeviceInfoList, SetupDiGetDevicePropertyW, SetupDiOpenDeviceInfoW, SetupVerifyInfFileW,
+                CR_SUCCESS, HDEVINFO, SPDRP_CLASSGUID, SPDRP_SERVICE, SP_DEVINFO_DATA, SP_INF_SIGNER_INFO_V2_W,
+            },
+            Properties::{
+                DEVPKEY_Device_DriverDate, DEVPKEY_Device_DriverInfPath, DEVPKEY_Device_DriverProvider,
+                DEVPKEY_Device_DriverVersion, DEVPROPKEY, DEVPROPTYPE, DEVPROP_TYPE_FILETIME, DEVPROP_TYPE_STRING,
+            },
+        },
+        Foundation::{ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND, HWND},
+        System::SystemInformation::GetSystemWindowsDirectoryW,
+    },
+};
+
+use super::correlation::{from_wide, registry_strings, wide};
+
+// 100 ns FILETIME ticks between 1601-01-01 and the Unix epoch
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+// Device -> &MI_xx interface -> HID or USBSTOR function is as deep as
+// composite devices go
+const MAX_DEPTH: u32 = 3;
+
+/// Whether the driver package's catalog signature checks out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriverSignature {
+    Signed,
+    Unsigned,
+    /// The INF could not be found or read
+    Unknown,
+}
+
+impl DriverSignature {
+    // Higher is worse: we would rather show "unsigned" than "unknown"
+    fn severity(self) -> u8 {
+        match self {
+            DriverSignature::Signed => 0,
+            DriverSignature::Unknown => 1,
+            DriverSignature::Unsigned => 2,
+        }
+    }
+}
+
+/// The driver bound to a devnode, as Device Manager's Driver tab shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriverInfo {
+    /// Kernel service the driver runs as, e.g. `USBSTOR` or `HidUsb`
+    #[serde(default)]
+    pub service: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// Name under `%SystemRoot%\INF`; third-party packages are `oemNN.inf`
+    #[serde(default)]
+    pub inf_name: Option<String>,
+    pub signature: DriverSignature,
+    /// Who signed the catalog, e.g. `"Microsoft Windows"`
+    #[serde(default)]
+    pub signer: Option<String>,
+    /// Setup class the driver installed the devnode into, e.g. Net's
+    /// `{4d36e972-e325-11ce-bfc1-08002be10318}`
+    #[serde(default)]
+    pub class_guid: Option<String>,
+}
+
+/// The driver of a function below a USB device: an `&MI_xx` interface, or
+/// the `USBSTOR` disk or `HID` collection hanging off one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionDriver {
+    pub instance_id: String,
+    pub driver: DriverInfo,
+}
+
+// An INF name plus the file's size and modification time: `oemNN.inf`
+// names are reused once a package is removed, and a new package under the
+// same name is a new file
+type InfVersion = (String, u64, Option<SystemTime>);
+
+lazy_static! {
+    // Checking a catalog is slow, so each INF file is checked once
+    static ref SIGNATURES: Mutex<HashMap<InfVersion, (DriverSignature, Option<String>)>> =
+        Mutex::new(HashMap::new());
+}
+
+unsafe fn property(
+    device_info_set: HDEVINFO,
+    device_info_data: &SP_DEVINFO_DATA,
+    key: &DEVPROPKEY,
+    expected: DEVPROPTYPE,
+) -> Option<Vec<u8>> {
+    let mut buffer = [0u8; 1024];
+    let mut property_type = DEVPROPTYPE::default();
+    let mut size = 0u32;
+    if !SetupDiGetDevicePropertyW(
+        device_info_set,
+        device_info_data,
+        key,
+        &mut property_type,
+        Some(&mut buffer),
+        Some(&mut size),
+        0,
+    )
+    .as_bool()
+        || property_type != expected
+    {
+        return None;
+    }
+    Some(buffer[..(size as usize).min(buffer.len())].to_vec())
+}
+
+unsafe fn string_property(
+    device_info_set: HDEVINFO,
+    device_info_data: &SP_DEVINFO_DATA,
+    key: &DEVPROPKEY,
+) -> Option<String> {
+    let bytes = property(device_info_set, device_info_data, key, DEVPROP_TYPE_STRING)?;
+    let wide: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    Some(from_wide(&wide)).filter(|s| !s.is_empty())
+}
+
+unsafe fn date_property(
+    device_info_set: HDEVINFO,
+    device_info_data: &SP_DEVINFO_DATA,
+    key: &DEVPROPKEY,
+) -> Option<NaiveDate> {
+    let bytes = property(device_info_set, device_info_data, key, DEVPROP_TYPE_FILETIME)?;
+    let ticks = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+    let seconds = ticks.checked_sub(FILETIME_UNIX_EPOCH)? / 10_000_000;
+    DateTime::from_timestamp(seconds as i64, 0).map(|at| at.date_naive())
+}
+
+fn inf_directory() -> Option<PathBuf> {
+    let mut buffer = [0u16; 260];
+    let len = unsafe { GetSystemWindowsDirectoryW(Some(&mut buffer)) } as usize;
+    if len == 0 || len > buffer.len() {
+        return None;
+    }
+    Some(PathBuf::from(String::from_utf16_lossy(&buffer[..len])).join("INF"))
+}
+
+fn verify(inf_name: &str) -> (DriverSignature, Option<String>) {
+    let file = match inf_directory() {
+        Some(dir) => dir.join(inf_name),
+        None => return (DriverSignature::Unknown, None),
+    };
+    let metadata = match fs::metadata(&file) {
+        Ok(metadata) => metadata,
+        Err(_) => return (DriverSignature::Unknown, None),
+    };
+    let key = (inf_name.to_ascii_lowercase(), metadata.len(), metadata.modified().ok());
+    if let Some(verdict) = SIGNATURES.lock().unwrap().get(&key) {
+        return verdict.clone();
+    }
+    let path = wide(&file.to_string_lossy());
+    let mut signer = SP_INF_SIGNER_INFO_V2_W {
+        cbSize: std::mem::size_of::<SP_INF_SIGNER_INFO_V2_W>() as u32,
+        ..Default::default()
+    };
+    let verdict = match unsafe { SetupVerifyInfFileW(PCWSTR(path.as_ptr()), None, &mut signer) }.ok() {
+        Ok(()) => (
+            DriverSignature::Signed,
+            Some(from_wide(&signer.DigitalSigner)).filter(|s| !s.is_empty()),
+        ),
+        Err(e) if e.code() == ERROR_FILE_NOT_FOUND.to_hresult() || e.code() == ERROR_PATH_NOT_FOUND.to_hresult() => {
+            return (DriverSignature::Unknown, None)
+        }
+        // No catalog, a bad hash or an untrusted root all leave it unsigned
+        Err(_) => (DriverSignature::Unsigned, None),
+    };
+    SIGNATURES.lock().unwrap().insert(key, verdict.clone());
+    verdict
+}
+
+/// The driver of a devnode, or `None` when no driver is installed for it.
+///
+/// # Safety
+///
+/// `device_info_data` must come from `device_info_set`, which must still be
+/// open.
+pub unsafe fn read(device_info_set: HDEVINFO, device_info_data: &SP_DEVINFO_DATA) -> Option<DriverInfo> {
+    let service = registry_strings(device_info_set, device_info_data, SPDRP_SERVICE).into_iter().next();
+    let inf_name = string_property(device_info_set, device_info_data, &DEVPKEY_Device_DriverInfPath);
+    if service.is_none() && inf_name.is_none() {
+        return None;
+    }
+    let (signature, signer) = inf_name.as_deref().map_or((DriverSignature::Unknown, None), verify);
+    Some(DriverInfo {
+        service,
+        provider: string_property(device_info_set, device_info_data, &DEVPKEY_Device_DriverProvider),
+        version: string_property(device_info_set, device_info_data, &DEVPKEY_Device_DriverVersion),
+        date: date_property(device_info_set, device_info_data, &DEVPKEY_Device_DriverDate),
+        inf_name,
+        signature,
+        signer,
+        class_guid: registry_strings(device_info_set, device_info_data, SPDRP_CLASSGUID).into_iter().next(),
+    })
+}
+
+/// The driver of the devnode with this instance ID, for functions that are
+/// not in the USB enumerator's device information set.
+fn read_instance(instance_id: &str) -> Option<DriverInfo> {
+    unsafe {
+        let device_info_set = SetupDiCreateDeviceInfoList(None, HWND(0)).ok()?;
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        let id = wide(instance_id);
+        let opened =
+            SetupDiOpenDeviceInfoW(device_info_set, PCWSTR(id.as_ptr()), HWND(0), 0, Some(&mut device_info_data))
+                .as_bool();
+        let driver = if opened { read(device_info_set, &device_info_data) } else { None };
+        SetupDiDestroyDeviceInfoList(device_info_set);
+        driver
+    }
+}
+
+/// Drivers of every function below the USB device `dev_inst`. A hub's
+/// downstream devices are devices of their own and are not descended into.
+pub fn function_drivers(dev_inst: u32) -> Vec<FunctionDriver> {
+    let mut drivers = Vec::new();
+    collect_functions(dev_inst, 1, &mut drivers);
+    drivers
+}
+
+fn collect_functions(dev_inst: u32, depth: u32, drivers: &mut Vec<FunctionDriver>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    unsafe {
+        let mut child = 0u32;
+        if CM_Get_Child(&mut child, dev_inst, 0) != CR_SUCCESS {
+            return;
+        }
+        loop {
+            let mut buffer = [0u16; 512];
+            if CM_Get_Device_IDW(child, &mut buffer, 0) == CR_SUCCESS {
+                let instance_id = from_wide(&buffer);
+                let upper = instance_id.to_ascii_uppercase();
+                if !upper.starts_with("USB\\") || upper.contains("&MI_") {
+                    if let Some(driver) = read_instance(&instance_id) {
+                        drivers.push(FunctionDriver { instance_id, driver });
+                    }
+                    collect_functions(child, depth + 1, drivers);
+                }
+            }
+            let mut sibling = 0u32;
+            if CM_Get_Sibling(&mut sibling, child, 0) != CR_SUCCESS {
+                return;
+            }
+            child = sibling;
+        }
+    }
+}
+
+/// The weakest signature among a device's own driver and those of its
+/// functions; `None` when nothing has a driver.
+pub fn worst_signature(driver: Option<&DriverInfo>, functions: &[FunctionDriver]) -> Option<DriverSignature> {
+    driver
+        .into_iter()
+        .chain(functions.iter().map(|function| &function.driver))
+        .map(|driver| driver.signature)
+        .max_by_key(|signature| signature.severity())
+}
@@ -0,0 +1,144 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::correlation::DevNode;
+use super::usb_control::BlockReason;
+
+// Never persisted: a machine must not come back from a reboot still only
+// pretending to enforce
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// One change a policy command would make.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedStep {
+    DisableDevice {
+        instance_id: String,
+        vendor_id: u16,
+        product_id: u16,
+        description: Option<String>,
+        reason: BlockReason,
+    },
+    EnableDevice {
+        instance_id: String,
+        vendor_id: u16,
+        product_id: u16,
+        description: Option<String>,
+    },
+    /// A timed unblock: `target` is blocked again after `after_secs`
+    ScheduleReblock { target: String, after_secs: u64 },
+    /// `key` includes the hive, e.g. `HKLM\SYSTEM\...\USBSTOR`
+    SetRegistryValue { key: String, name: String, value: u32 },
+    DeleteRegistryValue { key: String, name: String },
+    /// The key and everything below it
+    DeleteRegistryKey { key: String },
+    /// Write-protect (or release) the disk behind a mounted volume
+    SetVolumeReadOnly { mount_point: String, read_only: bool },
+    RestartStorageService,
+}
+
+/// What a policy command would have done, had dry-run mode been off.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PolicyPlan {
+    /// The simulated command, e.g. `block_all_untrusted`
+    pub command: String,
+    /// In the order they would run
+    pub steps: Vec<PlannedStep>,
+    /// Requested devices that match nothing attached; the real command
+    /// would fail for these
+    pub unmatched: Vec<String>,
+}
+
+impl PolicyPlan {
+    pub fn new(command: &str) -> Self {
+        PolicyPlan {
+            command: command.to_string(),
+            ..PolicyPlan::default()
+        }
+    }
+}
+
+/// The result of a policy command: `{"applied": ...}` with the usual
+/// result, or `{"simulated": {...}}` with the plan in dry-run mode.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Planned<T> {
+    Applied(T),
+    Simulated(PolicyPlan),
+}
+
+impl<T> Planned<T> {
+    pub fn applied(self) -> Option<T> {
+        match self {
+            Planned::Applied(value) => Some(value),
+            Planned::Simulated(_) => None,
+        }
+    }
+
+    pub fn simulated(self) -> Option<PolicyPlan> {
+        match self {
+            Planned::Applied(_) => None,
+            Planned::Simulated(plan) => Some(plan),
+        }
+    }
+}
+
+/// Whether policy commands plan instead of acting. Only the commands the
+/// user calls are simulated; autoblock, rules and timers keep enforcing.
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// For user commands that have no plan to show, e.g. ones that change a
+/// standing rule: refused rather than run while dry-run mode is on.
+pub fn refuse(command: &str) -> Result<(), String> {
+    if active() {
+        return Err(format!("{} cannot be simulated; turn off dry-run mode to run it", command));
+    }
+    Ok(())
+}
+
+/// Disabling (`Some(reason)`) or enabling `node`, unless it is already in
+/// that state.
+pub fn device_step(node: &DevNode, reason: Option<BlockReason>) -> Option<PlannedStep> {
+    match reason {
+        Some(reason) if !node.disabled => Some(PlannedStep::DisableDevice {
+            instance_id: node.instance_id.clone(),
+            vendor_id: node.vendor_id,
+            product_id: node.product_id,
+            description: node.description.clone(),
+            reason,
+        }),
+        None if node.disabled => Some(PlannedStep::EnableDevice {
+            instance_id: node.instance_id.clone(),
+            vendor_id: node.vendor_id,
+            product_id: node.product_id,
+            description: node.description.clone(),
+        }),
+        _ => None,
+    }
+}
+
+#[command]
+pub fn get_dry_run_mode() -> Result<bool, String> {
+    Ok(active())
+}
+
+/// Turn dry-run mode on or off for this session. While it is on, block and
+/// unblock commands only report their plan, so a new policy can be tried
+/// on a production machine without touching it. Since nothing is blocked
+/// meanwhile, turning it on needs the admin PIN.
+#[command]
+pub fn set_dry_run_mode(enabled: bool, admin_token: Option<String>) -> Result<bool, String> {
+    if enabled {
+        admin_pin::require_admin(admin_token.as_deref(), "turn on dry-run mode")?;
+    }
+    if ACTIVE.swap(enabled, Ordering::SeqCst) != enabled {
+        audit::record("dry_run_mode_changed", json!({ "enabled": enabled }));
+    }
+    Ok(enabled)
+}
@@ -0,0 +1,43 @@
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::dry_run;
+use super::error::UsbShieldError;
+use super::operations;
+
+/// Safely remove a storage device, e.g. from the dashboard on a machine
+/// nobody is sitting at: its volumes are flushed and dismounted, then
+/// Windows ejects it as "Safely Remove Hardware" would. Unlike a block, the
+/// device stays gone until it is plugged in again. With `force`, volumes
+/// with files still open are dismounted anyway, losing unsaved writes.
+#[command]
+pub async fn eject_device(instance_id: String, force: Option<bool>) -> Result<(), UsbShieldError> {
+    dry_run::refuse("eject_device")?;
+    operations::run("eject_device", move || {
+        let controller = backend::controller();
+        let force = force.unwrap_or(false);
+        let mount_points: Vec<String> = controller
+            .volumes()?
+            .into_iter()
+            .filter(|volume| volume.device_instance_id.eq_ignore_ascii_case(&instance_id))
+            .map(|volume| volume.mount_point)
+            .collect();
+
+        operations::stage("Dismounting volumes and ejecting the device");
+        if let Err(e) = controller.eject_device(&instance_id, &mount_points, force) {
+            audit::record(
+                "device_eject_failed",
+                json!({ "instance_id": instance_id, "volumes": mount_points, "forced": force, "error": e.message }),
+            );
+            return Err(UsbShieldError::device(&instance_id, e));
+        }
+        audit::record(
+            "device_ejected",
+            json!({ "instance_id": instance_id, "volumes": mount_points, "forced": force }),
+        );
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use lazy_static::lazy_static;
+use lettre::{
+    message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::commands::UsbDeviceInfo;
+use super::dpapi;
+use super::hello;
+use super::operations;
+use super::usb_config;
+
+const EMAIL_FILE: &str = "email-alerts.json";
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Plain SMTP, for a relay on a trusted network only
+    None,
+    /// Upgrade with STARTTLS, usually on port 587
+    StartTls,
+    /// TLS from the first byte, usually on port 465
+    Tls,
+}
+
+/// SMTP relay and admin address for policy-violation alerts. The password
+/// is accepted but never returned; it is kept encrypted with DPAPI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailAlertSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// `None` keeps the saved password, `""` removes it
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub has_password: bool,
+    pub from: String,
+    /// The admin address alerts go to
+    pub to: String,
+}
+
+impl Default for EmailAlertSettings {
+    fn default() -> Self {
+        EmailAlertSettings {
+            enabled: false,
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            has_password: false,
+            from: String::new(),
+            to: String::new(),
+        }
+    }
+}
+
+// What is written to disk: the settings and the DPAPI-protected password
+#[derive(Default, Serialize, Deserialize)]
+struct Stored {
+    #[serde(flatten)]
+    settings: EmailAlertSettings,
+    protected_password: Option<String>,
+}
+
+lazy_static! {
+    // The password in the clear stays in memory only
+    static ref CONFIG: Mutex<(EmailAlertSettings, Option<String>)> =
+        Mutex::new((EmailAlertSettings::default(), None));
+    static ref QUEUE: Mutex<Option<Sender<(String, String)>>> = Mutex::new(None);
+}
+
+fn protect(password: &str) -> Result<String, String> {
+    dpapi::protect(password).map_err(|e| format!("Failed to encrypt the SMTP password: {}", e))
+}
+
+fn unprotect(protected: &str) -> Result<String, String> {
+    dpapi::unprotect(protected).map_err(|e| format!("Failed to decrypt the SMTP password: {}", e))
+}
+
+fn validate(settings: &EmailAlertSettings) -> Result<(), String> {
+    if settings.host.trim().is_empty() {
+        return Err("An SMTP host is required".to_string());
+    }
+    for address in [&settings.from, &settings.to] {
+        address
+            .parse::<lettre::Address>()
+            .map_err(|e| format!("'{}' is not an email address: {}", address, e))?;
+    }
+    Ok(())
+}
+
+fn send(settings: &EmailAlertSettings, password: Option<&str>, subject: &str, body: &str) -> Result<(), String> {
+    let message = Message::builder()
+        .from(settings.from.parse().map_err(|e| format!("Bad sender address: {}", e))?)
+        .to(settings.to.parse().map_err(|e| format!("Bad admin address: {}", e))?)
+        .subject(format!("[USB-Shield] {}", subject))
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .map_err(|e| e.to_string())?;
+    let builder = match settings.security {
+        SmtpSecurity::None => Ok(SmtpTransport::builder_dangerous(&settings.host)),
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&settings.host),
+        SmtpSecurity::Tls => SmtpTransport::relay(&settings.host),
+    }
+    .map_err(|e| format!("Cannot set up TLS for {}: {}", settings.host, e))?;
+    let mut builder = builder.port(settings.port).timeout(Some(TIMEOUT));
+    if let Some(username) = settings.username.as_deref().filter(|u| !u.is_empty()) {
+        let password = password.unwrap_or_default().to_string();
+        builder = builder.credentials(Credentials::new(username.to_string(), password));
+    }
+    builder
+        .build()
+        .send(&message)
+        .map(|_| ())
+        .map_err(|e| format!("Sending mail through {}:{} failed: {}", settings.host, settings.port, e))
+}
+
+fn run(queue: Receiver<(String, String)>) {
+    for (subject, body) in queue {
+        let (settings, password) = CONFIG.lock().unwrap().clone();
+        if let Err(e) = send(&settings, password.as_deref(), &subject, &body) {
+            audit::record("email_alert_failed", json!({ "subject": subject, "error": e }));
+        }
+    }
+}
+
+/// Whether alerts would be sent now.
+pub fn configured() -> bool {
+    CONFIG.lock().unwrap().0.enabled
+}
+
+/// Mail `subject` and `body` to the admin address in the background, if
+/// email alerts are on. Failures are audited.
+pub fn queue(subject: &str, body: &str) -> Result<(), String> {
+    if !configured() {
+        return Err("Email alerts are not configured".to_string());
+    }
+    let mut queue = QUEUE.lock().unwrap();
+    let sender = queue.get_or_insert_with(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run(receiver));
+        sender
+    });
+    sender
+        .send((subject.to_string(), body.to_string()))
+        .map_err(|_| "The email alert worker has stopped".to_string())
+}
+
+/// Alert the admin to an untrusted device being plugged in, blocked or not.
+pub fn untrusted_device(device: &UsbDeviceInfo) {
+    if !configured() {
+        return;
+    }
+    let _ = queue(
+        "Untrusted USB device inserted",
+        &format!(
+            "An untrusted USB device was plugged into {}.\n\n\
+             Product: {}\nVID:PID: {:04X}:{:04X}\nInstance ID: {}\nState: {:?}",
+            std::env::var("COMPUTERNAME").unwrap_or_default(),
+            device.product().unwrap_or("unknown"),
+            device.vendor_id(),
+            device.product_id(),
+            device.instance_id().unwrap_or("unknown"),
+            device.state(),
+        ),
+    );
+}
+
+/// Alert the admin that USB protection was turned off or suspended.
+pub fn protection_disabled(how: &str) {
+    if !configured() {
+        return;
+    }
+    let _ = queue(
+        "USB protection disabled",
+        &format!(
+            "USB protection on {} was disabled: {}.",
+            std::env::var("COMPUTERNAME").unwrap_or_default(),
+            how
+        ),
+    );
+}
+
+fn save(settings: &EmailAlertSettings, password: Option<&str>) -> Result<(), String> {
+    let stored = Stored {
+        settings: settings.clone(),
+        protected_password: password.map(protect).transpose()?,
+    };
+    let path = usb_config::data_file(EMAIL_FILE)?;
+    let data = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Load the saved settings and decrypt the password. Called from setup.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(EMAIL_FILE)?;
+    let stored: Stored = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Stored::default(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let password = stored.protected_password.as_deref().map(unprotect).transpose()?;
+    let mut settings = stored.settings;
+    settings.has_password = password.is_some();
+    *CONFIG.lock().unwrap() = (settings, password);
+    Ok(())
+}
+
+#[command]
+pub fn get_email_alerts() -> Result<EmailAlertSettings, String> {
+    Ok(CONFIG.lock().unwrap().0.clone())
+}
+
+/// Change the SMTP relay or admin address. Changing or turning off alerts
+/// that are on needs consent, like other ways of going unwatched.
+#[command]
+pub fn configure_email_alerts(settings: EmailAlertSettings) -> Result<EmailAlertSettings, String> {
+    if settings.enabled {
+        validate(&settings)?;
+    }
+    let (previous, previous_password) = CONFIG.lock().unwrap().clone();
+    let password_changed = settings.password.is_some();
+    let password = match settings.password.as_deref() {
+        None => previous_password,
+        Some("") => None,
+        Some(password) => Some(password.to_string()),
+    };
+    let settings = EmailAlertSettings {
+        password: None,
+        has_password: password.is_some(),
+        ..settings
+    };
+    if previous.enabled && (previous != settings || password_changed) {
+        hello::require_consent("change email alerts")?;
+    }
+    save(&settings, password.as_deref())?;
+    audit::record(
+        "email_alerts_changed",
+        json!({
+            "enabled": settings.enabled,
+            "host": settings.host,
+            "port": settings.port,
+            "security": settings.security,
+            "to": settings.to,
+        }),
+    );
+    *CONFIG.lock().unwrap() = (settings.clone(), password);
+    Ok(settings)
+}
+
+/// Send a test message with the saved settings, whether or not alerts are
+/// on, and report the SMTP server's verdict.
+#[command]
+pub async fn send_test_email() -> Result<(), String> {
+    operations::run("send_test_email", || {
+        let (settings, password) = CONFIG.lock().unwrap().clone();
+        validate(&settings)?;
+        send(
+            &settings,
+            password.as_deref(),
+            "Test alert",
+            "Email alerts from USB-Shield reach this address.",
+        )
+    })
+    .await
+}
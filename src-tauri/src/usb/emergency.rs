@@ -0,0 +1,121 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::lift_port_block;
+use super::dry_run;
+use super::email_alerts;
+use super::hello;
+use super::reblock::{self, ReblockTarget};
+use super::security_key;
+use super::usb_control;
+
+const TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref PENDING_TOKEN: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmergencyReport {
+    pub devices_enabled: Vec<String>,
+    pub device_errors: Vec<String>,
+    pub port_policy_error: Option<String>,
+}
+
+/// First half of the break-glass flow: returns a one-time token that must be
+/// passed to `unblock_everything` within 30 seconds.
+#[command]
+pub fn request_emergency_token() -> Result<String, String> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    *PENDING_TOKEN.lock().unwrap() = Some((token.clone(), Instant::now()));
+    audit::record("emergency_token_issued", json!({ "valid_secs": TOKEN_LIFETIME.as_secs() }));
+    Ok(token)
+}
+
+/// Revert every block this app has applied: re-enable all devnodes we
+/// disabled and lift the USBSTOR/RemovableStorageDevices port policy.
+#[command]
+pub fn unblock_everything(token: String, admin_token: Option<String>) -> Result<EmergencyReport, String> {
+    dry_run::refuse("unblock_everything")?;
+    admin_pin::require_admin(admin_token.as_deref(), "unblock everything")?;
+    security_key::require_presence("unblock everything")?;
+    hello::require_consent("unblock everything")?;
+    consume_token(&token)?;
+
+    let mut report = EmergencyReport {
+        devices_enabled: Vec::new(),
+        device_errors: Vec::new(),
+        port_policy_error: None,
+    };
+
+    // Nothing should silently re-block behind the operator's back. Trust
+    // grants, guest sessions and pauses still lapse on time, or they would
+    // stay open for good
+    for pending in reblock::pending() {
+        if matches!(pending.target, ReblockTarget::Device { .. } | ReblockTarget::Ports) {
+            reblock::cancel(pending.id);
+        }
+    }
+
+    let records = usb_control::block_records();
+    for record in &records {
+        match usb_control::unblock(&record.instance_id) {
+            Ok(_) => report.devices_enabled.push(record.instance_id.clone()),
+            Err(e) => report.device_errors.push(format!("{}: {}", record.instance_id, e)),
+        }
+    }
+    report.port_policy_error = lift_port_block(None).err().map(String::from);
+
+    audit::record(
+        "emergency_unblock_everything",
+        json!({
+            "blocked_before": records
+                .iter()
+                .map(|r| json!({
+                    "instance_id": r.instance_id,
+                    "vendor_id": format!("{:04X}", r.vendor_id),
+                    "product_id": format!("{:04X}", r.product_id),
+                }))
+                .collect::<Vec<_>>(),
+            "devices_enabled": report.devices_enabled,
+            "device_errors": report.device_errors,
+            "port_policy_error": report.port_policy_error,
+            "user": std::env::var("USERNAME").ok(),
+            "computer": std::env::var("COMPUTERNAME").ok(),
+        }),
+    );
+    email_alerts::protection_disabled(&format!(
+        "an emergency unblock re-enabled {} device(s) and lifted the port policy",
+        report.devices_enabled.len()
+    ));
+
+    Ok(report)
+}
+
+fn consume_token(token: &str) -> Result<(), String> {
+    let pending = PENDING_TOKEN.lock().unwrap().take();
+    match pending {
+        Some((expected, issued)) if expected == token && issued.elapsed() <= TOKEN_LIFETIME => Ok(()),
+        Some((expected, _)) if expected == token => {
+            audit::record("emergency_token_rejected", json!({ "reason": "expired" }));
+            Err("Confirmation token expired; request a new one".to_string())
+        }
+        _ => {
+            audit::record("emergency_token_rejected", json!({ "reason": "mismatch" }));
+            Err("Invalid confirmation token".to_string())
+        }
+    }
+}
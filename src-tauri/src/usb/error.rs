@@ -0,0 +1,109 @@
+use std::fmt;
+use serde::Serialize;
+use uport_shield_helper::protocol::{ErrorCode, Failure};
+
+const ERROR_ACCESS_DENIED: u32 = 5;
+
+/// What went wrong, for the commands the frontend most needs to react to.
+/// Serialized with a `kind` tag, e.g.
+/// `{"kind":"device_not_found","device":"VID_0781&PID_5581"}`, so the UI
+/// can pick a localized message and an action (elevate, enter the admin
+/// PIN, re-plug) instead of showing raw text. Everything else still
+/// travels as `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UsbShieldError {
+    /// A registry key we manage exists but may not be written
+    RegistryAccessDenied { key: String },
+    /// The operation needs an elevated process or the helper service
+    ElevationRequired { action: String },
+    /// Nothing attached matches, e.g. the device was unplugged meanwhile
+    DeviceNotFound { device: String },
+    /// SetupAPI/CfgMgr rejected a state change; `code` is the Win32 error
+    SetupApiError { instance_id: String, code: u32, message: String },
+    /// The devnode did not respond in time and is held until it does
+    DeviceTimeout { instance_id: String, message: String },
+    /// An admin PIN is set and no live admin session was passed
+    AdminPinRequired { action: String },
+    /// Windows Hello was cancelled or failed
+    ConsentDenied { action: String, message: String },
+    Other { message: String },
+}
+
+impl UsbShieldError {
+    /// A failed request about `instance_id`, by the code the helper (or the
+    /// simulated backend) attached to it.
+    pub fn device(instance_id: &str, failure: Failure) -> Self {
+        match failure.code {
+            Some(ErrorCode::DeviceNotFound) => UsbShieldError::DeviceNotFound {
+                device: instance_id.to_string(),
+            },
+            Some(ErrorCode::Win32 { code }) => UsbShieldError::SetupApiError {
+                instance_id: instance_id.to_string(),
+                code,
+                message: failure.message,
+            },
+            Some(ErrorCode::ElevationRequired) => UsbShieldError::ElevationRequired {
+                action: format!("enable {}", instance_id),
+            },
+            None => UsbShieldError::Other {
+                message: format!("{}: {}", instance_id, failure.message),
+            },
+        }
+    }
+
+    /// A failed change to a machine-wide policy, which lives under HKLM and
+    /// so is refused when neither we nor the helper are elevated.
+    pub fn policy(action: &str, failure: Failure) -> Self {
+        match failure.code {
+            Some(ErrorCode::Win32 { code: ERROR_ACCESS_DENIED } | ErrorCode::ElevationRequired) => {
+                UsbShieldError::ElevationRequired {
+                    action: action.to_string(),
+                }
+            }
+            _ => UsbShieldError::Other {
+                message: failure.message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for UsbShieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsbShieldError::RegistryAccessDenied { key } => write!(f, "Access to registry key {} was denied", key),
+            UsbShieldError::ElevationRequired { action } => {
+                write!(f, "Administrator rights or the helper service are required to {}", action)
+            }
+            UsbShieldError::DeviceNotFound { device } => write!(f, "Device not found: {}", device),
+            UsbShieldError::SetupApiError { instance_id, message, .. } => write!(f, "{}: {}", instance_id, message),
+            UsbShieldError::DeviceTimeout { message, .. } => f.write_str(message),
+            UsbShieldError::AdminPinRequired { action } => write!(f, "Admin PIN required to {}", action),
+            UsbShieldError::ConsentDenied { message, .. } => f.write_str(message),
+            UsbShieldError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for UsbShieldError {}
+
+impl From<String> for UsbShieldError {
+    fn from(message: String) -> Self {
+        UsbShieldError::Other { message }
+    }
+}
+
+impl From<Failure> for UsbShieldError {
+    fn from(failure: Failure) -> Self {
+        UsbShieldError::Other {
+            message: failure.message,
+        }
+    }
+}
+
+// Lets `?` carry a typed error out of the many commands that still return text
+impl From<UsbShieldError> for String {
+    fn from(error: UsbShieldError) -> Self {
+        error.to_string()
+    }
+}
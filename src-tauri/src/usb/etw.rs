@@ -0,0 +1,152 @@
+use std::{sync::Mutex, time::Instant};
+use lazy_static::lazy_static;
+use windows::{
+    core::GUID,
+    Win32::System::Diagnostics::Etw::{
+        EventRegister, EventUnregister, EventWrite, EVENT_DATA_DESCRIPTOR, EVENT_DESCRIPTOR,
+    },
+};
+
+// Custom provider "USB-Shield-Enforcement". Enable it with e.g.
+//   wpr -start usb-shield.wprp   or   logman start usbshield -p {8f1f4c1e-6b0a-4c8e-9a37-2d6c1b5e7a10} -ets
+pub const PROVIDER_GUID: GUID = GUID::from_u128(0x8f1f4c1e_6b0a_4c8e_9a37_2d6c1b5e7a10);
+
+const LEVEL_INFO: u8 = 4;
+const LEVEL_ERROR: u8 = 2;
+const OPCODE_START: u8 = 1;
+const OPCODE_STOP: u8 = 2;
+
+lazy_static! {
+    static ref PROVIDER_HANDLE: Mutex<u64> = Mutex::new(0);
+}
+
+/// Operations traced by the provider. The discriminant is the ETW event id.
+#[derive(Debug, Clone, Copy)]
+pub enum TraceEvent {
+    Enumerate = 1,
+    SetDeviceState = 2,
+    BlockAllPorts = 3,
+    UnblockPorts = 4,
+    ApplyPolicy = 5,
+}
+
+impl TraceEvent {
+    // Keywords let a trace session filter on a single area.
+    fn keyword(self) -> u64 {
+        match self {
+            TraceEvent::Enumerate => 0x1,
+            TraceEvent::SetDeviceState => 0x2,
+            TraceEvent::BlockAllPorts | TraceEvent::UnblockPorts => 0x4,
+            TraceEvent::ApplyPolicy => 0x8,
+        }
+    }
+}
+
+pub fn register() {
+    let mut handle = PROVIDER_HANDLE.lock().unwrap();
+    if *handle != 0 {
+        return;
+    }
+    let mut reg_handle: u64 = 0;
+    let status = unsafe { EventRegister(&PROVIDER_GUID, None, None, &mut reg_handle) };
+    if status == 0 {
+        *handle = reg_handle;
+    } else {
+        log::error!("ETW provider registration failed (Error {})", status);
+    }
+}
+
+pub fn unregister() {
+    let mut handle = PROVIDER_HANDLE.lock().unwrap();
+    if *handle != 0 {
+        unsafe {
+            EventUnregister(*handle);
+        }
+        *handle = 0;
+    }
+}
+
+/// A traced operation. Writes a Start event on creation and a Stop event
+/// (with elapsed microseconds and outcome) when dropped.
+pub struct Activity {
+    event: TraceEvent,
+    detail: String,
+    started: Instant,
+    error: Option<String>,
+}
+
+pub fn activity(event: TraceEvent, detail: impl Into<String>) -> Activity {
+    let detail = detail.into();
+    write(event, OPCODE_START, LEVEL_INFO, &detail, 0, 0);
+    Activity {
+        event,
+        detail,
+        started: Instant::now(),
+        error: None,
+    }
+}
+
+impl Activity {
+    /// Mark the activity as failed; the Stop event is then written at error level.
+    pub fn fail(&mut self, error: impl Into<String>) {
+        self.error = Some(error.into());
+    }
+
+    /// Convenience for `activity.track(result)?` at the end of an operation.
+    pub fn track<T, E: ToString>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if let Err(e) = &result {
+            self.fail(e.to_string());
+        }
+        result
+    }
+}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        let elapsed_us = self.started.elapsed().as_micros() as u64;
+        match &self.error {
+            None => write(self.event, OPCODE_STOP, LEVEL_INFO, &self.detail, elapsed_us, 0),
+            Some(e) => {
+                let detail = format!("{}: {}", self.detail, e);
+                write(self.event, OPCODE_STOP, LEVEL_ERROR, &detail, elapsed_us, 1)
+            }
+        }
+    }
+}
+
+// Payload layout: detail (UTF-16, NUL terminated), elapsed_us (u64), status (u32).
+fn write(event: TraceEvent, opcode: u8, level: u8, detail: &str, elapsed_us: u64, status: u32) {
+    let handle = *PROVIDER_HANDLE.lock().unwrap();
+    if handle == 0 {
+        return;
+    }
+
+    let descriptor = EVENT_DESCRIPTOR {
+        Id: event as u16,
+        Version: 0,
+        Channel: 0,
+        Level: level,
+        Opcode: opcode,
+        Task: event as u16,
+        Keyword: event.keyword(),
+    };
+
+    let detail_wide: Vec<u16> = detail.encode_utf16().chain(Some(0)).collect();
+    let data = [
+        data_descriptor(detail_wide.as_ptr() as *const u8, detail_wide.len() * 2),
+        data_descriptor(&elapsed_us as *const u64 as *const u8, std::mem::size_of::<u64>()),
+        data_descriptor(&status as *const u32 as *const u8, std::mem::size_of::<u32>()),
+    ];
+
+    unsafe {
+        EventWrite(handle, &descriptor, Some(&data));
+    }
+}
+
+fn data_descriptor(ptr: *const u8, size: usize) -> EVENT_DATA_DESCRIPTOR {
+    EVENT_DATA_DESCRIPTOR {
+        Ptr: ptr as u64,
+        Size: size as u32,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::PSID,
+        System::EventLog::{
+            DeregisterEventSource, EventSourceHandle, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+        },
+    },
+};
+use uport_shield_helper::registry::{self, Root};
+
+use super::correlation::wide;
+
+// Events go to the Application log under this source, so a collector only
+// needs e.g. `Application: Source="USB-Shield"` to pick them up
+pub const SOURCE: &str = "USB-Shield";
+const SOURCE_KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog\Application\USB-Shield";
+// Its message table maps every ID from 1 to 1000 to "%1", which is all we
+// need: the whole description is our one insertion string
+const MESSAGE_FILE: &str = r"System32\EventCreate.exe";
+// EVENTLOG_ERROR_TYPE | EVENTLOG_WARNING_TYPE | EVENTLOG_INFORMATION_TYPE
+const TYPES_SUPPORTED: u32 = 0x7;
+
+lazy_static! {
+    static ref SOURCE_HANDLE: Mutex<Option<EventSourceHandle>> = Mutex::new(None);
+}
+
+/// Events written to the Windows Event Log. The discriminant is the event
+/// ID, stable across releases so SIEM rules can match on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogEvent {
+    DeviceBlocked = 100,
+    DeviceUnblocked = 101,
+    /// Blocking or unblocking a device failed
+    DeviceStateFailed = 102,
+    DeviceQuarantined = 110,
+    TamperDetected = 120,
+}
+
+impl LogEvent {
+    fn event_type(self) -> REPORT_EVENT_TYPE {
+        match self {
+            LogEvent::DeviceUnblocked => EVENTLOG_INFORMATION_TYPE,
+            LogEvent::DeviceBlocked | LogEvent::DeviceQuarantined => EVENTLOG_WARNING_TYPE,
+            LogEvent::DeviceStateFailed | LogEvent::TamperDetected => EVENTLOG_ERROR_TYPE,
+        }
+    }
+}
+
+// Needs administrator rights, like the rest of enforcement; without it the
+// events are still written, Event Viewer just cannot format them
+fn install_source() -> Result<(), String> {
+    let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+    registry::create(Root::LocalMachine, SOURCE_KEY)
+        .and_then(|key| {
+            key.set_string("EventMessageFile", &format!(r"{}\{}", system_root, MESSAGE_FILE))?;
+            key.set_dword("TypesSupported", TYPES_SUPPORTED)
+        })
+        .map_err(|e| format!("Cannot register the {} event source: {}", SOURCE, e))
+}
+
+pub fn register() {
+    let mut handle = SOURCE_HANDLE.lock().unwrap();
+    if handle.is_some() {
+        return;
+    }
+    if let Err(e) = install_source() {
+        log::warn!("{}", e);
+    }
+    let source = wide(SOURCE);
+    match unsafe { RegisterEventSourceW(PCWSTR::null(), PCWSTR(source.as_ptr())) } {
+        Ok(source) => *handle = Some(source),
+        Err(e) => log::error!("Event log source registration failed: {}", e),
+    }
+}
+
+pub fn unregister() {
+    if let Some(handle) = SOURCE_HANDLE.lock().unwrap().take() {
+        unsafe {
+            DeregisterEventSource(handle);
+        }
+    }
+}
+
+/// Deregister and remove the event source, for uninstalling. Events
+/// already written stay in the log.
+pub fn remove_source() -> Result<(), String> {
+    unregister();
+    registry::delete_tree(Root::LocalMachine, SOURCE_KEY)
+        .map_err(|e| format!("Cannot remove the {} event source: {}", SOURCE, e))
+}
+
+/// Write `message` and the structured `details` (as JSON, on the lines
+/// after it) to the Application log. A no-op until `register` has run.
+pub fn write(event: LogEvent, message: &str, details: &Value) {
+    let handle = match *SOURCE_HANDLE.lock().unwrap() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let text = wide(&format!("{}\r\n\r\n{}", message, details));
+    let strings = [PCWSTR(text.as_ptr())];
+    let written = unsafe {
+        ReportEventW(
+            handle,
+            event.event_type(),
+            0,
+            event as u32,
+            PSID::default(),
+            0,
+            Some(&strings),
+            None,
+        )
+    };
+    if !written.as_bool() {
+        log::error!("Failed to write event {} to the event log", event as u32);
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+static LAST_EVENT: Mutex<Option<LastEvent>> = Mutex::new(None);
+
+// Emitted on a timer; they would always be the "last event"
+const PERIODIC_EVENTS: &[&str] = &["usb://transfer-stats", "usb://reblock-countdown"];
+
+/// Follows every non-periodic event, carrying its `LastEvent`. The status
+/// widget listens to this one event instead of all of them.
+pub const EVENT_STATUS_CHANGED: &str = "status://changed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastEvent {
+    pub name: String,
+    pub at: DateTime<Utc>,
+}
+
+pub fn init(app: AppHandle) {
+    let _ = APP_HANDLE.set(app);
+}
+
+pub fn app_handle() -> Option<&'static AppHandle> {
+    APP_HANDLE.get()
+}
+
+/// The most recent event that was not a periodic sample.
+pub fn last() -> Option<LastEvent> {
+    LAST_EVENT.lock().unwrap().clone()
+}
+
+/// Emit an event to every window. A no-op before setup has run.
+pub fn emit<S: Serialize + Clone>(event: &str, payload: S) {
+    let last = (!PERIODIC_EVENTS.contains(&event)).then(|| LastEvent {
+        name: event.to_string(),
+        at: Utc::now(),
+    });
+    if let Some(last) = &last {
+        *LAST_EVENT.lock().unwrap() = Some(last.clone());
+    }
+    if let Some(app) = APP_HANDLE.get() {
+        if let Err(e) = app.emit(event, payload) {
+            log::error!("Failed to emit {}: {}", event, e);
+        }
+        if let Some(last) = last {
+            let _ = app.emit(EVENT_STATUS_CHANGED, last);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::quota;
+use super::storage_readonly;
+use super::transfers::TransferStats;
+
+pub const EVENT_EXFILTRATION_ALERT: &str = "usb://exfiltration-alert";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExfiltrationPolicy {
+    pub enabled: bool,
+    /// Bytes written to one volume within `window_secs` that raise the alert
+    pub threshold_bytes: u64,
+    pub window_secs: u64,
+    /// Also write-protect the volume for the rest of the session
+    pub write_block: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExfiltrationAlert {
+    pub device_instance_id: String,
+    pub mount_point: String,
+    pub volume_serial: String,
+    pub bytes_written: u64,
+    pub window_secs: u64,
+    pub threshold_bytes: u64,
+    pub write_blocked: bool,
+    pub error: Option<String>,
+}
+
+struct WriteHistory {
+    // (sampled at, cumulative bytes written)
+    samples: VecDeque<(Instant, u64)>,
+    // Set while over the threshold so a burst alerts once, not every sample
+    alerted: bool,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<ExfiltrationPolicy> = Mutex::new(ExfiltrationPolicy {
+        enabled: false,
+        threshold_bytes: 500 * 1024 * 1024,
+        window_secs: 300,
+        write_block: false,
+    });
+    static ref HISTORY: Mutex<HashMap<String, WriteHistory>> = Mutex::new(HashMap::new());
+    // Mount points this module write-protected
+    static ref WRITE_BLOCKED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Called with every transfer sample. Compares the bytes written within the
+/// window against the threshold, per volume.
+pub fn check(stats: &[TransferStats]) {
+    let policy = POLICY.lock().unwrap().clone();
+    if !policy.enabled {
+        return;
+    }
+    let window = Duration::from_secs(policy.window_secs);
+    let now = Instant::now();
+
+    let mut alerts = Vec::new();
+    {
+        let mut history = HISTORY.lock().unwrap();
+        for stat in stats.iter().filter(|s| s.mounted) {
+            let entry = history
+                .entry(format!("{}|{}", stat.device_instance_id.to_ascii_uppercase(), stat.volume_serial))
+                .or_insert_with(|| WriteHistory {
+                    samples: VecDeque::new(),
+                    alerted: false,
+                });
+            entry.samples.push_back((now, stat.totals.bytes_written));
+            // Keep the newest sample at or before the window start as the baseline
+            while entry.samples.len() > 1 && now.duration_since(entry.samples[1].0) >= window {
+                entry.samples.pop_front();
+            }
+
+            let burst = stat.totals.bytes_written - entry.samples[0].1;
+            if burst > policy.threshold_bytes {
+                if !entry.alerted {
+                    entry.alerted = true;
+                    alerts.push((stat.clone(), burst));
+                }
+            } else {
+                entry.alerted = false;
+            }
+        }
+    }
+
+    for (stat, burst) in alerts {
+        raise(&policy, &stat, burst);
+    }
+}
+
+fn raise(policy: &ExfiltrationPolicy, stat: &TransferStats, burst: u64) {
+    let already_blocked = WRITE_BLOCKED.lock().unwrap().contains(&stat.mount_point.to_ascii_uppercase());
+    let error = if policy.write_block && !already_blocked {
+        match backend::controller().set_volume_read_only(&stat.mount_point, true) {
+            Ok(()) => {
+                WRITE_BLOCKED.lock().unwrap().insert(stat.mount_point.to_ascii_uppercase());
+                None
+            }
+            Err(e) => Some(e),
+        }
+    } else {
+        None
+    };
+
+    let alert = ExfiltrationAlert {
+        device_instance_id: stat.device_instance_id.clone(),
+        mount_point: stat.mount_point.clone(),
+        volume_serial: stat.volume_serial.clone(),
+        bytes_written: burst,
+        window_secs: policy.window_secs,
+        threshold_bytes: policy.threshold_bytes,
+        write_blocked: policy.write_block && error.is_none(),
+        error,
+    };
+    audit::record("exfiltration_alert", json!(alert));
+    notifications::notify(
+        Severity::Critical,
+        "exfiltration_alert",
+        "Large copy to USB storage",
+        &format!(
+            "{} MB written to {} within {} s",
+            alert.bytes_written / (1024 * 1024),
+            alert.mount_point,
+            alert.window_secs
+        ),
+    );
+    events::emit(EVENT_EXFILTRATION_ALERT, alert);
+}
+
+/// Whether an alert left `mount_point` write-protected.
+pub fn write_blocked(mount_point: &str) -> bool {
+    WRITE_BLOCKED.lock().unwrap().contains(&mount_point.to_ascii_uppercase())
+}
+
+#[command]
+pub fn get_exfiltration_policy() -> Result<ExfiltrationPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_exfiltration_policy(policy: ExfiltrationPolicy) -> Result<(), String> {
+    if policy.threshold_bytes == 0 || policy.window_secs == 0 {
+        return Err("Threshold and window must be greater than zero".to_string());
+    }
+    let previous = POLICY.lock().unwrap().clone();
+    if previous.enabled && !policy.enabled {
+        hello::require_consent("disable exfiltration alerts")?;
+    }
+    audit::record("exfiltration_policy_changed", json!({ "previous": previous, "current": policy }));
+    *POLICY.lock().unwrap() = policy;
+    HISTORY.lock().unwrap().clear();
+    Ok(())
+}
+
+#[command]
+pub fn get_write_blocked_volumes() -> Result<Vec<String>, String> {
+    Ok(WRITE_BLOCKED.lock().unwrap().iter().cloned().collect())
+}
+
+/// Lift a write block applied after an alert.
+#[command]
+pub fn release_write_block(mount_point: String, admin_token: Option<String>) -> Result<(), String> {
+    let key = mount_point.to_ascii_uppercase();
+    if !WRITE_BLOCKED.lock().unwrap().contains(&key) {
+        return Err(format!("{} is not write-blocked", mount_point));
+    }
+    admin_pin::require_admin(admin_token.as_deref(), "lift a write block")?;
+    hello::require_consent("lift a write block")?;
+    // The daily quota keeps its own block until the day ends, read-only mode until it is turned off
+    if !quota::blocks(&mount_point) && !storage_readonly::holds(&mount_point) {
+        backend::controller().set_volume_read_only(&mount_point, false)?;
+    }
+    WRITE_BLOCKED.lock().unwrap().remove(&key);
+    audit::record("write_block_released", json!({ "mount_point": mount_point }));
+    Ok(())
+}
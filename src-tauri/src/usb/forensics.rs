@@ -0,0 +1,466 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::events;
+use super::hello;
+use super::operations;
+use super::signing;
+use super::storage_readonly;
+use super::usb_config;
+
+pub const EVENT_EVIDENCE_ATTACHED: &str = "forensics://evidence-attached";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForensicSession {
+    pub id: String,
+    pub operator: String,
+    pub case_reference: Option<String>,
+    pub computer: Option<String>,
+    pub started: DateTime<Utc>,
+}
+
+/// A storage volume attached during the session, and the device it is on.
+#[derive(Debug, Clone, Serialize)]
+pub struct EvidenceItem {
+    pub device_instance_id: String,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub serial: Option<String>,
+    pub description: Option<String>,
+    pub mount_point: String,
+    pub volume_serial: String,
+    pub label: Option<String>,
+    pub file_system: Option<String>,
+    pub attached_at: DateTime<Utc>,
+    pub write_blocked: bool,
+    pub error: Option<String>,
+}
+
+/// One read of a file on an evidence volume.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHash {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub hashed_at: DateTime<Utc>,
+    /// Where the file was acquired to, for reads that copied it
+    pub copied_to: Option<String>,
+}
+
+/// A file that could not be read, recorded in place of its hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub path: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvidenceReads {
+    pub files: Vec<FileHash>,
+    pub errors: Vec<FileError>,
+}
+
+/// Chain-of-custody record for one session.
+#[derive(Debug, Clone, Serialize)]
+pub struct ForensicReport {
+    pub session: ForensicSession,
+    pub ended: DateTime<Utc>,
+    pub evidence: Vec<EvidenceItem>,
+    pub files: Vec<FileHash>,
+    pub errors: Vec<FileError>,
+    /// HMAC over the report with this field empty
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForensicExport {
+    /// The report as written to the data directory
+    pub path: String,
+    pub report: ForensicReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForensicStatus {
+    pub session: Option<ForensicSession>,
+    pub evidence: Vec<EvidenceItem>,
+    pub files_hashed: usize,
+    pub files_failed: usize,
+}
+
+struct SessionState {
+    session: ForensicSession,
+    // Mount points present when the session began; not evidence
+    baseline: HashSet<String>,
+    evidence: Vec<EvidenceItem>,
+    files: Vec<FileHash>,
+    errors: Vec<FileError>,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<SessionState>> = Mutex::new(None);
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        thread::sleep(POLL_INTERVAL);
+        if SESSION.lock().unwrap().is_some() {
+            scan();
+        }
+    });
+}
+
+pub fn active() -> bool {
+    SESSION.lock().unwrap().is_some()
+}
+
+/// Whether the session holds `mount_point` write-protected as evidence.
+pub fn holds(mount_point: &str) -> bool {
+    SESSION.lock().unwrap().as_ref().is_some_and(|state| {
+        state
+            .evidence
+            .iter()
+            .any(|item| item.write_blocked && item.mount_point.eq_ignore_ascii_case(mount_point))
+    })
+}
+
+/// Write-protect and record every volume that appeared since the session
+/// began. Volumes mount a moment after their device arrives, so this polls
+/// instead of hanging off hotplug events.
+pub fn scan() -> Vec<EvidenceItem> {
+    let controller = backend::controller();
+    let volumes = match controller.volumes() {
+        Ok(volumes) => volumes,
+        Err(e) => {
+            log::error!("Forensic volume scan failed: {}", e);
+            return Vec::new();
+        }
+    };
+    let fresh: Vec<_> = {
+        let session = SESSION.lock().unwrap();
+        let state = match session.as_ref() {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+        volumes
+            .into_iter()
+            .filter(|volume| {
+                let key = volume.mount_point.to_ascii_uppercase();
+                !state.baseline.contains(&key)
+                    && !state.evidence.iter().any(|item| {
+                        item.mount_point.eq_ignore_ascii_case(&volume.mount_point)
+                            && item.volume_serial == volume.volume_serial
+                    })
+            })
+            .collect()
+    };
+    if fresh.is_empty() {
+        return Vec::new();
+    }
+
+    let devnodes = controller.devnodes().unwrap_or_default();
+    let items: Vec<EvidenceItem> = fresh
+        .into_iter()
+        .map(|volume| {
+            let error = controller.set_volume_read_only(&volume.mount_point, true).err();
+            let node = devnodes
+                .iter()
+                .find(|node| node.instance_id.eq_ignore_ascii_case(&volume.device_instance_id));
+            EvidenceItem {
+                vendor_id: node.map(|node| node.vendor_id),
+                product_id: node.map(|node| node.product_id),
+                serial: node.and_then(|node| node.serial.clone()),
+                description: node.and_then(|node| node.description.clone()),
+                device_instance_id: volume.device_instance_id,
+                mount_point: volume.mount_point,
+                volume_serial: volume.volume_serial,
+                label: volume.label,
+                file_system: volume.file_system,
+                attached_at: Utc::now(),
+                write_blocked: error.is_none(),
+                error,
+            }
+        })
+        .collect();
+
+    let mut session = SESSION.lock().unwrap();
+    let state = match session.as_mut() {
+        Some(state) => state,
+        // Stopped while we were scanning
+        None => return Vec::new(),
+    };
+    for item in &items {
+        audit::record("forensic_evidence_attached", json!({ "session": state.session.id, "evidence": item }));
+        events::emit(EVENT_EVIDENCE_ATTACHED, item.clone());
+    }
+    state.evidence.extend(items.iter().cloned());
+    items
+}
+
+// Hash `path`, copying it to `copy_to` on the way when given
+fn hash_file(path: &Path, copy_to: Option<&Path>) -> Result<FileHash, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut copy = match copy_to {
+        Some(target) => {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            Some(File::create(target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?)
+        }
+        None => None,
+    };
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        if let (Some(copy), Some(target)) = (copy.as_mut(), copy_to) {
+            copy.write_all(&buffer[..read])
+                .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        }
+        size += read as u64;
+    }
+    Ok(FileHash {
+        path: path.display().to_string(),
+        size,
+        sha256: hex::encode(hasher.finalize()),
+        hashed_at: Utc::now(),
+        copied_to: copy_to.map(|target| target.display().to_string()),
+    })
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+    if metadata.is_dir() {
+        let entries = fs::read_dir(path).map_err(|e| format!("Failed to list {}: {}", path.display(), e))?;
+        for entry in entries.flatten() {
+            collect_files(&entry.path(), files)?;
+        }
+    } else if metadata.is_file() {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn operator_name() -> String {
+    format!(
+        "{}\\{}",
+        std::env::var("USERDOMAIN").unwrap_or_default(),
+        std::env::var("USERNAME").unwrap_or_default()
+    )
+}
+
+/// Enter forensic mode. Storage attached from now on mounts under the
+/// machine-wide write protection, is write-protected at the volume level
+/// once it appears and is recorded for the chain of custody.
+#[command]
+pub fn start_forensic_mode(operator: Option<String>, case_reference: Option<String>) -> Result<ForensicSession, String> {
+    let controller = backend::controller();
+    let baseline = controller
+        .volumes()?
+        .into_iter()
+        .map(|volume| volume.mount_point.to_ascii_uppercase())
+        .collect();
+    let mut session = SESSION.lock().unwrap();
+    if session.is_some() {
+        return Err("A forensic session is already running".to_string());
+    }
+    // In place before anything is plugged in: the volume poll only catches
+    // evidence after Windows has mounted it and had its chance to write
+    controller.set_storage_write_protect(true)?;
+    let started = Utc::now();
+    let new_session = ForensicSession {
+        id: started.format("%Y%m%dT%H%M%SZ").to_string(),
+        operator: operator.filter(|name| !name.trim().is_empty()).unwrap_or_else(operator_name),
+        case_reference,
+        computer: std::env::var("COMPUTERNAME").ok(),
+        started,
+    };
+    audit::record("forensic_mode_started", json!(new_session));
+    *session = Some(SessionState {
+        session: new_session.clone(),
+        baseline,
+        evidence: Vec::new(),
+        files: Vec::new(),
+        errors: Vec::new(),
+    });
+    Ok(new_session)
+}
+
+/// Hash every file under `path`, which must be on an evidence volume, and
+/// log each read. A file that cannot be read is logged with its error and
+/// the rest are still hashed.
+#[command]
+pub async fn hash_evidence(path: String) -> Result<EvidenceReads, String> {
+    operations::run("hash_evidence", move || read_files_under(&path, None)).await
+}
+
+/// Copy every file under `path`, which must be on an evidence volume, into
+/// `destination`, hashing and logging each file as it is read. Reads by
+/// other programs are not seen: acquire through this command for them to
+/// be in the report.
+#[command]
+pub async fn acquire_evidence(path: String, destination: String) -> Result<EvidenceReads, String> {
+    operations::run("acquire_evidence", move || read_files_under(&path, Some(Path::new(&destination)))).await
+}
+
+fn read_files_under(path: &str, destination: Option<&Path>) -> Result<EvidenceReads, String> {
+    {
+        let session = SESSION.lock().unwrap();
+        let state = session.as_ref().ok_or_else(|| "Forensic mode is not active".to_string())?;
+        let upper = path.to_ascii_uppercase();
+        if !state
+            .evidence
+            .iter()
+            .any(|item| upper.starts_with(&item.mount_point.to_ascii_uppercase()))
+        {
+            return Err(format!("{} is not on an evidence volume", path));
+        }
+        if let Some(destination) = destination {
+            let upper = destination.display().to_string().to_ascii_uppercase();
+            if state
+                .evidence
+                .iter()
+                .any(|item| upper.starts_with(&item.mount_point.to_ascii_uppercase()))
+            {
+                return Err(format!("{} is on an evidence volume", destination.display()));
+            }
+        }
+    }
+
+    let root = Path::new(path);
+    let mut paths = Vec::new();
+    collect_files(root, &mut paths)?;
+    let mut reads = EvidenceReads {
+        files: Vec::new(),
+        errors: Vec::new(),
+    };
+    for file in &paths {
+        operations::stage(&format!("Hashing {}", file.display()));
+        // A single file named as `path` lands directly in the destination
+        let copy_to = destination.map(|destination| match file.strip_prefix(root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => destination.join(relative),
+            _ => destination.join(file.file_name().unwrap_or_default()),
+        });
+        match hash_file(file, copy_to.as_deref()) {
+            Ok(hash) => reads.files.push(hash),
+            Err(error) => reads.errors.push(FileError {
+                path: file.display().to_string(),
+                error,
+                failed_at: Utc::now(),
+            }),
+        }
+    }
+
+    let mut session = SESSION.lock().unwrap();
+    let state = session.as_mut().ok_or_else(|| "Forensic mode ended during hashing".to_string())?;
+    for hash in &reads.files {
+        audit::record("forensic_file_read", json!({ "session": state.session.id, "file": hash }));
+    }
+    for error in &reads.errors {
+        audit::record("forensic_file_read_failed", json!({ "session": state.session.id, "file": error }));
+    }
+    state.files.extend(reads.files.iter().cloned());
+    state.errors.extend(reads.errors.iter().cloned());
+    Ok(reads)
+}
+
+#[command]
+pub fn get_forensic_status() -> Result<ForensicStatus, String> {
+    let session = SESSION.lock().unwrap();
+    Ok(match session.as_ref() {
+        Some(state) => ForensicStatus {
+            session: Some(state.session.clone()),
+            evidence: state.evidence.clone(),
+            files_hashed: state.files.len(),
+            files_failed: state.errors.len(),
+        },
+        None => ForensicStatus {
+            session: None,
+            evidence: Vec::new(),
+            files_hashed: 0,
+            files_failed: 0,
+        },
+    })
+}
+
+/// End the session, write the signed chain-of-custody report to the data
+/// directory and lift the write protection of evidence still mounted, and
+/// the machine-wide one unless read-only mode still wants it.
+#[command]
+pub fn stop_forensic_mode(admin_token: Option<String>) -> Result<ForensicExport, String> {
+    if !active() {
+        return Err("Forensic mode is not active".to_string());
+    }
+    admin_pin::require_admin(admin_token.as_deref(), "end forensic mode and lift its write protection")?;
+    hello::require_consent("end forensic mode and lift its write protection")?;
+    let state = SESSION
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Forensic mode is not active".to_string())?;
+
+    let mut report = ForensicReport {
+        session: state.session,
+        ended: Utc::now(),
+        evidence: state.evidence,
+        files: state.files,
+        errors: state.errors,
+        signature: String::new(),
+    };
+    report.signature = signing::sign(&serde_json::to_vec(&report).map_err(|e| e.to_string())?)?;
+    let path = usb_config::data_file(&format!("forensic-{}.json", report.session.id))?;
+    fs::write(&path, serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let controller = backend::controller();
+    if !storage_readonly::enabled() {
+        if let Err(e) = controller.set_storage_write_protect(false) {
+            log::error!("Failed to lift the forensic storage write protection: {}", e);
+        }
+    }
+    let mounted: HashSet<String> = controller
+        .volumes()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|volume| volume.mount_point.to_ascii_uppercase())
+        .collect();
+    for item in report.evidence.iter().filter(|item| item.write_blocked) {
+        if mounted.contains(&item.mount_point.to_ascii_uppercase()) && !storage_readonly::holds(&item.mount_point) {
+            if let Err(e) = controller.set_volume_read_only(&item.mount_point, false) {
+                log::error!("Failed to lift forensic write block on {}: {}", item.mount_point, e);
+            }
+        }
+    }
+
+    let path = path.display().to_string();
+    audit::record(
+        "forensic_mode_stopped",
+        json!({ "session": report.session.id, "report": path, "signature": report.signature }),
+    );
+    Ok(ForensicExport { path, report })
+}
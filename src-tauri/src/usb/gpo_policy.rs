@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use uport_shield_helper::protocol::RemovableStoragePolicy;
+
+use super::audit;
+use super::backend;
+use super::dry_run::{self, Planned, PlannedStep, PolicyPlan};
+use super::error::UsbShieldError;
+use super::hello;
+use super::operations;
+use super::rollback;
+use super::tamper;
+
+const POLICIES_KEY: &str = r"HKLM\SOFTWARE\Policies\Microsoft\Windows\RemovableStorageDevices";
+
+/// Device classes covered by the "Removable Storage Access" Group Policy
+/// settings, each under its own policy key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageClass {
+    RemovableDisks,
+    CdDvd,
+    Floppy,
+    Tape,
+    /// Phones, cameras and media players; Windows has no execute policy
+    /// for these
+    Wpd,
+}
+
+impl StorageClass {
+    // WPD devices come in two classes, which the policy lists together
+    pub fn class_guids(self) -> &'static [&'static str] {
+        match self {
+            StorageClass::RemovableDisks => &["{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}"],
+            StorageClass::CdDvd => &["{53f56308-b6bf-11d0-94f2-00a0c91efb8b}"],
+            StorageClass::Floppy => &["{53f56311-b6bf-11d0-94f2-00a0c91efb8b}"],
+            StorageClass::Tape => &["{53f5630b-b6bf-11d0-94f2-00a0c91efb8b}"],
+            StorageClass::Wpd => &["{6AC27878-A6FA-4155-BA85-F98F491D4F33}", "{F33FDC04-D1AC-4E8E-9A30-19BBD4B108AE}"],
+        }
+    }
+
+    fn from_guid(guid: &str) -> Option<Self> {
+        [
+            StorageClass::RemovableDisks,
+            StorageClass::CdDvd,
+            StorageClass::Floppy,
+            StorageClass::Tape,
+            StorageClass::Wpd,
+        ]
+        .into_iter()
+        .find(|class| class.class_guids().iter().any(|g| g.eq_ignore_ascii_case(guid)))
+    }
+}
+
+/// One machine-wide policy in effect, whether we or a domain GPO wrote it.
+#[derive(Debug, Clone, Serialize)]
+pub struct GpoStoragePolicy {
+    /// `None` for a class GUID we do not manage
+    pub class: Option<StorageClass>,
+    pub class_guid: String,
+    pub deny_read: bool,
+    pub deny_write: bool,
+    pub deny_execute: bool,
+}
+
+impl From<RemovableStoragePolicy> for GpoStoragePolicy {
+    fn from(policy: RemovableStoragePolicy) -> Self {
+        GpoStoragePolicy {
+            class: StorageClass::from_guid(&policy.class_guid),
+            class_guid: policy.class_guid,
+            deny_read: policy.deny_read,
+            deny_write: policy.deny_write,
+            deny_execute: policy.deny_execute,
+        }
+    }
+}
+
+/// The removable storage policies currently in effect for every user of
+/// this machine, including ones pushed by a domain controller.
+#[command]
+pub fn get_gpo_storage_policies() -> Result<Vec<GpoStoragePolicy>, String> {
+    let mut policies: Vec<GpoStoragePolicy> = backend::controller()
+        .removable_storage_policies()?
+        .into_iter()
+        .map(GpoStoragePolicy::from)
+        .collect();
+    policies.sort_by_key(|a| a.class_guid.to_ascii_lowercase());
+    Ok(policies)
+}
+
+// The helper deletes the class key when nothing is denied, and otherwise
+// sets or deletes each value
+fn plan(class: StorageClass, read: bool, write: bool, execute: bool) -> PolicyPlan {
+    let mut plan = PolicyPlan::new("set_gpo_storage_policy");
+    for guid in class.class_guids() {
+        let key = format!(r"{}\{}", POLICIES_KEY, guid);
+        if !read && !write && !execute {
+            plan.steps.push(PlannedStep::DeleteRegistryKey { key });
+            continue;
+        }
+        for (name, deny) in [("Deny_Read", read), ("Deny_Write", write), ("Deny_Execute", execute)] {
+            plan.steps.push(if deny {
+                PlannedStep::SetRegistryValue { key: key.clone(), name: name.to_string(), value: 1 }
+            } else {
+                PlannedStep::DeleteRegistryValue { key: key.clone(), name: name.to_string() }
+            });
+        }
+    }
+    plan
+}
+
+/// Deny reading, writing and running programs from one class of removable
+/// storage for every user, the way the Group Policy editor does. Lifting a
+/// denial needs consent. A domain GPO for the same class wins at its next
+/// refresh.
+#[command]
+pub async fn set_gpo_storage_policy(
+    class: StorageClass,
+    read: bool,
+    write: bool,
+    execute: bool,
+) -> Result<Planned<Vec<GpoStoragePolicy>>, UsbShieldError> {
+    if execute && class == StorageClass::Wpd {
+        return Err("Windows has no execute policy for portable devices".to_string().into());
+    }
+    if dry_run::active() {
+        return Ok(Planned::Simulated(plan(class, read, write, execute)));
+    }
+    operations::run("set_gpo_storage_policy", move || apply(class, read, write, execute))
+        .await
+        .map(Planned::Applied)
+}
+
+fn apply(class: StorageClass, read: bool, write: bool, execute: bool) -> Result<Vec<GpoStoragePolicy>, UsbShieldError> {
+    let controller = backend::controller();
+    let current = controller.removable_storage_policies()?;
+    let relaxed = class.class_guids().iter().any(|guid| {
+        current.iter().any(|policy| {
+            policy.class_guid.eq_ignore_ascii_case(guid)
+                && ((policy.deny_read && !read) || (policy.deny_write && !write) || (policy.deny_execute && !execute))
+        })
+    });
+    if relaxed {
+        hello::require_consent("relax the removable storage policy")?;
+    }
+    rollback::take_before("set_gpo_storage_policy");
+    for guid in class.class_guids() {
+        let policy = RemovableStoragePolicy {
+            class_guid: guid.to_string(),
+            deny_read: read,
+            deny_write: write,
+            deny_execute: execute,
+        };
+        tamper::write_removable_storage(&policy, || {
+            controller
+                .set_removable_storage_policy(&policy)
+                .map_err(|e| UsbShieldError::policy("change the removable storage policy", e))
+        })?;
+    }
+    audit::record(
+        "gpo_storage_policy_changed",
+        json!({ "class": class, "deny_read": read, "deny_write": write, "deny_execute": execute }),
+    );
+    Ok(get_gpo_storage_policies()?)
+}
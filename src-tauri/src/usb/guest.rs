@@ -0,0 +1,213 @@
+use std::{sync::Mutex, time::Duration};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::category::DeviceCategory;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::profiles::{self, Profile};
+use super::reblock::{self, ReblockTarget};
+use super::transfers::{self, TransferStats};
+use super::usb_control::{self, BlockReason};
+
+pub const EVENT_GUEST_MODE_CHANGED: &str = "usb://guest-mode-changed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuestDevice {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product: Option<String>,
+    pub admitted_at: DateTime<Utc>,
+}
+
+/// A bounded relaxation for a visitor: the first `max_devices` arrivals in
+/// `categories` get in without being trusted, until `expires_at`.
+#[derive(Debug, Clone, Serialize)]
+pub struct GuestSession {
+    pub categories: Vec<DeviceCategory>,
+    pub max_devices: usize,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The manual profile set aside for the session and put back after it
+    pub previous_profile: Option<Profile>,
+    pub devices: Vec<GuestDevice>,
+    /// Pending re-block that ends the session
+    pub reblock_id: u64,
+}
+
+/// What a finished session let in, with what it did while it was in.
+#[derive(Debug, Clone, Serialize)]
+pub struct GuestReport {
+    pub session: GuestSession,
+    pub ended_at: DateTime<Utc>,
+    /// Per-volume traffic of the guest devices
+    pub transfers: Vec<TransferStats>,
+    pub errors: Vec<String>,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<GuestSession>> = Mutex::new(None);
+}
+
+/// Whether `instance_id` was let in by the running guest session.
+pub fn admitted(instance_id: &str) -> bool {
+    SESSION.lock().unwrap().as_ref().is_some_and(|session| {
+        session
+            .devices
+            .iter()
+            .any(|device| device.instance_id.eq_ignore_ascii_case(instance_id))
+    })
+}
+
+/// Take a guest slot for an untrusted arrival, if the session has one left
+/// for its category. Returns whether the device was let in.
+pub fn admit(device: &UsbDeviceInfo) -> bool {
+    if device.state() != DeviceState::Connected {
+        return false;
+    }
+    let instance_id = match device.instance_id() {
+        Some(instance_id) => instance_id,
+        None => return false,
+    };
+    let mut guard = SESSION.lock().unwrap();
+    let session = match guard.as_mut() {
+        Some(session) => session,
+        None => return false,
+    };
+    if !session.categories.contains(&device.category()) || session.devices.len() >= session.max_devices {
+        return false;
+    }
+    let guest = GuestDevice {
+        instance_id: instance_id.to_string(),
+        vendor_id: device.vendor_id(),
+        product_id: device.product_id(),
+        product: device.product().map(str::to_string),
+        admitted_at: Utc::now(),
+    };
+    audit::record("guest_device_admitted", json!(guest));
+    session.devices.push(guest);
+    let session = session.clone();
+    drop(guard);
+    events::emit(EVENT_GUEST_MODE_CHANGED, Some(session));
+    true
+}
+
+/// Close the session: block the guest devices still attached, put the
+/// previous profile back and log what they did. Run by the re-block ticker
+/// when time runs out.
+pub fn end() -> Result<GuestReport, String> {
+    let session = SESSION
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Guest mode is not active".to_string())?;
+
+    let mut errors = Vec::new();
+    // Guests that were already unplugged have nothing left to block
+    let attached = commands::get_usb_devices().unwrap_or_else(|e| {
+        errors.push(e);
+        Vec::new()
+    });
+    for device in attached.iter().filter(|device| device.state() == DeviceState::Connected) {
+        let instance_id = match device.instance_id() {
+            Some(instance_id) if session.devices.iter().any(|d| d.instance_id.eq_ignore_ascii_case(instance_id)) => {
+                instance_id
+            }
+            _ => continue,
+        };
+        if let Err(e) = usb_control::block(instance_id, BlockReason::TimedReblock) {
+            errors.push(format!("{}: {}", instance_id, e));
+        }
+    }
+    if session.previous_profile.is_some() {
+        profiles::request("manual", session.previous_profile);
+    }
+
+    let report = GuestReport {
+        transfers: transfers::sample()
+            .into_iter()
+            .filter(|stats| {
+                session
+                    .devices
+                    .iter()
+                    .any(|device| device.instance_id.eq_ignore_ascii_case(&stats.device_instance_id))
+            })
+            .collect(),
+        session,
+        ended_at: Utc::now(),
+        errors,
+    };
+    audit::record("guest_mode_ended", json!(report));
+    events::emit(EVENT_GUEST_MODE_CHANGED, None::<GuestSession>);
+    notifications::notify(
+        Severity::Info,
+        "guest_mode_ended",
+        "Guest mode ended",
+        &format!("{} guest device(s) used the session; any still attached are blocked again.", report.session.devices.len()),
+    );
+    Ok(report)
+}
+
+#[command]
+pub fn get_guest_mode() -> Result<Option<GuestSession>, String> {
+    Ok(SESSION.lock().unwrap().clone())
+}
+
+/// Let up to `max_devices` untrusted devices in `categories` in for
+/// `duration_secs`, e.g. one storage device for a visiting auditor. A manual
+/// profile is set aside for the session; automatic conditions still apply.
+#[command]
+pub fn start_guest_mode(
+    categories: Vec<DeviceCategory>,
+    max_devices: usize,
+    duration_secs: u64,
+    admin_token: Option<String>,
+) -> Result<GuestSession, String> {
+    if categories.is_empty() || max_devices == 0 {
+        return Err("Guest mode needs at least one category and one device".to_string());
+    }
+    if duration_secs == 0 {
+        return Err("Duration must be greater than zero".to_string());
+    }
+    if SESSION.lock().unwrap().is_some() {
+        return Err("Guest mode is already active".to_string());
+    }
+    admin_pin::require_admin(admin_token.as_deref(), "start guest mode")?;
+    hello::require_consent("start guest mode")?;
+
+    let previous_profile = profiles::get_active_profile()?.requests.get("manual").copied();
+    if previous_profile.is_some() {
+        profiles::request("manual", None);
+    }
+    let started_at = Utc::now();
+    let session = GuestSession {
+        categories,
+        max_devices,
+        started_at,
+        expires_at: started_at + chrono::Duration::seconds(duration_secs as i64),
+        previous_profile,
+        devices: Vec::new(),
+        reblock_id: reblock::schedule(ReblockTarget::Guest, Duration::from_secs(duration_secs)),
+    };
+    *SESSION.lock().unwrap() = Some(session.clone());
+    audit::record("guest_mode_started", json!(session));
+    events::emit(EVENT_GUEST_MODE_CHANGED, Some(session.clone()));
+    Ok(session)
+}
+
+/// End guest mode before its time is up.
+#[command]
+pub fn end_guest_mode() -> Result<GuestReport, String> {
+    if let Some(session) = SESSION.lock().unwrap().as_ref() {
+        reblock::cancel(session.reblock_id);
+    }
+    end()
+}
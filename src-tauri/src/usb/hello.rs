@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::{
+    core::HSTRING,
+    Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    },
+};
+
+use super::admin_pin;
+use super::audit;
+use super::error::UsbShieldError;
+
+lazy_static! {
+    static ref REQUIRE_HELLO: Mutex<bool> = Mutex::new(false);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloStatus {
+    pub required: bool,
+    /// Whether a fingerprint/face/PIN verifier is configured on this machine
+    pub available: bool,
+}
+
+/// Ask the OS to confirm the user (fingerprint, face or PIN) before a
+/// sensitive operation. A no-op unless the requirement is switched on.
+pub fn require_consent(action: &str) -> Result<(), UsbShieldError> {
+    if !*REQUIRE_HELLO.lock().unwrap() {
+        return Ok(());
+    }
+
+    let denied = |message: String| UsbShieldError::ConsentDenied {
+        action: action.to_string(),
+        message,
+    };
+    let message = HSTRING::from(format!("USB-Shield: confirm to {}", action));
+    let result = UserConsentVerifier::RequestVerificationAsync(&message)
+        .and_then(|operation| operation.get())
+        .map_err(|e| denied(format!("Windows Hello verification failed: {}", e)))?;
+
+    audit::record(
+        "windows_hello_verification",
+        json!({ "action": action, "result": format!("{:?}", result) }),
+    );
+    match result {
+        UserConsentVerificationResult::Verified => Ok(()),
+        UserConsentVerificationResult::Canceled => Err(denied("Windows Hello verification was cancelled".to_string())),
+        UserConsentVerificationResult::DeviceNotPresent | UserConsentVerificationResult::NotConfiguredForUser => {
+            Err(denied("Windows Hello is not set up for this user".to_string()))
+        }
+        _ => Err(denied(format!("Windows Hello verification did not succeed ({:?})", result))),
+    }
+}
+
+fn available() -> bool {
+    UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|operation| operation.get())
+        .is_ok_and(|availability| availability == UserConsentVerifierAvailability::Available)
+}
+
+#[command]
+pub fn get_windows_hello_requirement() -> Result<HelloStatus, String> {
+    Ok(HelloStatus {
+        required: *REQUIRE_HELLO.lock().unwrap(),
+        available: available(),
+    })
+}
+
+/// Turning the requirement off needs the admin PIN and a successful
+/// verification itself.
+#[command]
+pub fn set_windows_hello_requirement(required: bool, admin_token: Option<String>) -> Result<(), String> {
+    if required && !available() {
+        return Err("Windows Hello is not available on this machine".to_string());
+    }
+    if !required {
+        admin_pin::require_admin(admin_token.as_deref(), "turn off the Windows Hello requirement")?;
+    }
+    require_consent("change the Windows Hello requirement")?;
+    *REQUIRE_HELLO.lock().unwrap() = required;
+    audit::record("windows_hello_requirement_changed", json!({ "required": required }));
+    Ok(())
+}
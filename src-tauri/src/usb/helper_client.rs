@@ -0,0 +1,124 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::ErrorKind,
+};
+use uport_shield_helper::{
+    auth,
+    protocol::{
+        read_frame, write_frame, Challenge, ChallengeResponse, Failure, GuardPolicy, GuardStatus,
+        RemovableStoragePolicy, Request, Response, PIPE_NAME,
+    },
+};
+
+use super::privilege;
+
+enum Connection {
+    Helper(File),
+    /// No helper is listening, but this process is elevated and can run
+    /// the enforcement code itself
+    InProcess,
+}
+
+/// Answer the challenge. Unelevated, the secret cannot be read and the
+/// helper only takes requests that do not relax protection.
+fn authenticate(mut stream: File) -> Result<File, String> {
+    let secret = auth::load_secret().ok();
+    let challenge: Challenge = read_frame(&mut stream)?;
+    let mac = secret.map(|secret| auth::mac(&secret, &challenge.nonce));
+    write_frame(&mut stream, &ChallengeResponse { mac })?;
+    match read_frame(&mut stream)? {
+        Response::Ok => Ok(stream),
+        Response::Error { message, .. } => Err(message),
+        other => Err(format!("Unexpected helper response: {:?}", other)),
+    }
+}
+
+/// Connect and authenticate. Only a missing pipe in an elevated process
+/// falls back to in-process enforcement; a helper that refuses us, or no
+/// helper for an unelevated process, is an error.
+fn connect() -> Result<Connection, String> {
+    match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+        Ok(stream) => authenticate(stream).map(Connection::Helper),
+        Err(e) if e.kind() == ErrorKind::NotFound && privilege::is_elevated() => Ok(Connection::InProcess),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(
+            "The USB-Shield helper service is not running; without it enforcing needs administrator rights"
+                .to_string(),
+        ),
+        Err(e) => Err(format!("Cannot connect to the USB-Shield helper service: {}", e)),
+    }
+}
+
+fn call(request: Request) -> Response {
+    match connect() {
+        Ok(Connection::Helper(mut stream)) => write_frame(&mut stream, &request)
+            .and_then(|()| read_frame(&mut stream))
+            .unwrap_or_else(|message| Response::from(Failure::from(message))),
+        Ok(Connection::InProcess) => {
+            log::debug!("No helper service; enforcing in-process");
+            uport_shield_helper::server::dispatch(request)
+        }
+        Err(message) => {
+            log::error!("{}", message);
+            Response::from(Failure::from(message))
+        }
+    }
+}
+
+// Keeps the code the helper sent, for the requests whose failures the app acts on
+fn expect_ok(response: Response) -> Result<(), Failure> {
+    match response {
+        Response::Ok => Ok(()),
+        Response::Error { message, code } => Err(Failure { message, code }),
+        other => Err(Failure::from(format!("Unexpected helper response: {:?}", other))),
+    }
+}
+
+pub fn is_running() -> bool {
+    matches!(connect(), Ok(Connection::Helper(_)))
+}
+
+pub fn set_device_state(instance_id: &str, enable: bool) -> Result<(), Failure> {
+    expect_ok(call(Request::SetDeviceState { instance_id: instance_id.to_string(), enable }))
+}
+
+pub fn apply_policy(usbstor_start: u32) -> Result<(), Failure> {
+    expect_ok(call(Request::ApplyPolicy { usbstor_start }))
+}
+
+pub fn set_volume_read_only(mount_point: &str, read_only: bool) -> Result<(), String> {
+    expect_ok(call(Request::SetVolumeReadOnly { mount_point: mount_point.to_string(), read_only }))
+        .map_err(String::from)
+}
+
+pub fn set_storage_write_protect(enabled: bool) -> Result<(), Failure> {
+    expect_ok(call(Request::SetStorageWriteProtect { enabled }))
+}
+
+pub fn set_selective_suspend(instance_id: &str, enabled: bool) -> Result<(), String> {
+    expect_ok(call(Request::SetSelectiveSuspend { instance_id: instance_id.to_string(), enabled }))
+        .map_err(String::from)
+}
+
+pub fn set_wake_enabled(name: &str, enabled: bool) -> Result<(), String> {
+    expect_ok(call(Request::SetWakeEnabled { name: name.to_string(), enabled })).map_err(String::from)
+}
+
+pub fn set_removable_storage_policy(policy: &RemovableStoragePolicy) -> Result<(), Failure> {
+    expect_ok(call(Request::SetRemovableStoragePolicy { policy: policy.clone() }))
+}
+
+pub fn eject_device(instance_id: &str, mount_points: &[String], force: bool) -> Result<(), Failure> {
+    expect_ok(call(Request::EjectDevice {
+        instance_id: instance_id.to_string(),
+        mount_points: mount_points.to_vec(),
+        force,
+    }))
+}
+
+pub fn sync_guard(policy: GuardPolicy) -> Result<GuardStatus, String> {
+    match call(Request::SyncGuard { policy }) {
+        Response::Guard(status) => Ok(status),
+        Response::Error { message, .. } => Err(message),
+        other => Err(format!("Unexpected helper response: {:?}", other)),
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::category::CLASS_HID;
+use super::commands::{DeviceState, UsbDeviceInfo};
+use super::event_log::{self, LogEvent};
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_control::{self, BlockReason};
+use super::webhooks::{self, WebhookEvent};
+
+pub const EVENT_HID_QUARANTINE: &str = "usb://hid-quarantine";
+
+// Injectors start typing as soon as they enumerate; later bursts are left
+// to the cadence baseline
+const WATCH_FOR: Duration = Duration::from_secs(120);
+// Nobody types this fast for long: gaps under it are replayed keys
+const SUPERHUMAN_INTERVAL_MS: f64 = 20.0;
+// Consecutive superhuman gaps; chords and rollover produce a few at most
+const BURST: usize = 12;
+
+/// Counts the run of superhuman gaps between key presses of one device.
+#[derive(Debug, Clone, Default)]
+pub struct InjectionDetector {
+    run: usize,
+    total_ms: f64,
+}
+
+impl InjectionDetector {
+    /// Feed the gap before one key press; true once the run is long enough.
+    pub fn record(&mut self, interval_ms: f64) -> bool {
+        if interval_ms >= SUPERHUMAN_INTERVAL_MS {
+            self.run = 0;
+            self.total_ms = 0.0;
+            return false;
+        }
+        self.run += 1;
+        self.total_ms += interval_ms;
+        self.run >= BURST
+    }
+
+    pub fn mean_interval_ms(&self) -> f64 {
+        if self.run == 0 {
+            0.0
+        } else {
+            self.total_ms / self.run as f64
+        }
+    }
+}
+
+/// A HID device disabled for typing like a script, until the user approves it.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedHid {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product: Option<String>,
+    pub detected_at: DateTime<Utc>,
+    /// Mean gap between key presses over the burst that tripped detection
+    pub mean_interval_ms: f64,
+    /// Set when disabling it failed; it is still listed so the user knows
+    pub error: Option<String>,
+}
+
+struct Watch {
+    instance_id: String,
+    vendor_id: u16,
+    product_id: u16,
+    product: Option<String>,
+    connected_at: Instant,
+    last_press: Option<Instant>,
+    detector: InjectionDetector,
+}
+
+lazy_static! {
+    static ref WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+    static ref QUARANTINE: Mutex<Vec<QuarantinedHid>> = Mutex::new(Vec::new());
+    // Approved this session; not watched again until restart
+    static ref APPROVED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Start timing the keys of a HID device that was just attached. Composite
+/// devices count too: attack tools often pair a keyboard with storage.
+pub fn on_connected(device: &UsbDeviceInfo) {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) if device.state() == DeviceState::Connected => instance_id,
+        _ => return,
+    };
+    if !device.interfaces().iter().any(|interface| interface.class_code == CLASS_HID)
+        || APPROVED.lock().unwrap().contains(&instance_id.to_ascii_uppercase())
+    {
+        return;
+    }
+    let mut watches = WATCHES.lock().unwrap();
+    watches.retain(|watch| !watch.instance_id.eq_ignore_ascii_case(instance_id));
+    watches.push(Watch {
+        instance_id: instance_id.to_string(),
+        vendor_id: device.vendor_id(),
+        product_id: device.product_id(),
+        product: device.product().map(str::to_string),
+        connected_at: Instant::now(),
+        last_press: None,
+        detector: InjectionDetector::default(),
+    });
+}
+
+/// One key press from the device with this VID/PID, as seen by raw input.
+/// Disables the device when it types faster than a person can; blocks the
+/// caller while that happens.
+pub fn on_key_press(source: (u16, u16), now: Instant) {
+    let detected = {
+        let mut watches = WATCHES.lock().unwrap();
+        watches.retain(|watch| now.saturating_duration_since(watch.connected_at) < WATCH_FOR);
+        let index = match watches
+            .iter()
+            .position(|watch| (watch.vendor_id, watch.product_id) == source)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let watch = &mut watches[index];
+        let interval = watch
+            .last_press
+            .replace(now)
+            .map(|last| now.saturating_duration_since(last).as_secs_f64() * 1000.0);
+        match interval {
+            Some(interval) if watch.detector.record(interval) => watches.remove(index),
+            _ => return,
+        }
+    };
+
+    let result = usb_control::block(&detected.instance_id, BlockReason::HidQuarantine);
+    let quarantined = QuarantinedHid {
+        instance_id: detected.instance_id,
+        vendor_id: detected.vendor_id,
+        product_id: detected.product_id,
+        product: detected.product,
+        detected_at: Utc::now(),
+        mean_interval_ms: detected.detector.mean_interval_ms(),
+        error: result.err().map(String::from),
+    };
+    let message = format!(
+        "Keyboard {} was quarantined for typing faster than a person can.",
+        quarantined.instance_id
+    );
+    event_log::write(LogEvent::DeviceQuarantined, &message, &json!(quarantined));
+    webhooks::dispatch(WebhookEvent::DeviceQuarantined, &message, &json!(quarantined));
+    audit::record("hid_quarantined", json!(quarantined));
+    notifications::notify(
+        Severity::Critical,
+        "hid_quarantined",
+        "Keyboard quarantined",
+        &format!(
+            "{} typed faster than a person can and was blocked until you approve it.",
+            quarantined.product.as_deref().unwrap_or(&quarantined.instance_id)
+        ),
+    );
+    let mut quarantine = QUARANTINE.lock().unwrap();
+    quarantine.retain(|entry| !entry.instance_id.eq_ignore_ascii_case(&quarantined.instance_id));
+    quarantine.push(quarantined);
+    events::emit(EVENT_HID_QUARANTINE, quarantine.clone());
+}
+
+#[command]
+pub fn get_hid_quarantine() -> Result<Vec<QuarantinedHid>, String> {
+    Ok(QUARANTINE.lock().unwrap().clone())
+}
+
+/// Release a quarantined device: the user confirms it is a real keyboard.
+/// It is not watched again until the app restarts.
+#[command]
+pub fn approve_hid_device(id: String, admin_token: Option<String>) -> Result<(), String> {
+    if !QUARANTINE
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|entry| entry.instance_id.eq_ignore_ascii_case(&id))
+    {
+        return Err(format!("{} is not quarantined", id));
+    }
+    admin_pin::require_admin(admin_token.as_deref(), "approve a quarantined keyboard")?;
+    hello::require_consent("approve a quarantined keyboard")?;
+    usb_control::unblock(&id)?;
+
+    APPROVED.lock().unwrap().insert(id.to_ascii_uppercase());
+    let mut quarantine = QUARANTINE.lock().unwrap();
+    quarantine.retain(|entry| !entry.instance_id.eq_ignore_ascii_case(&id));
+    audit::record("hid_approved", json!({ "instance_id": id }));
+    events::emit(EVENT_HID_QUARANTINE, quarantine.clone());
+    Ok(())
+}
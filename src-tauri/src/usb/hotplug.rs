@@ -0,0 +1,378 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::c_void,
+    sync::{
+        mpsc::{self, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::json;
+use windows::{
+    core::GUID,
+    Win32::{
+        Devices::{
+            DeviceAndDriverInstallation::{
+                CM_Register_Notification, CM_NOTIFY_ACTION, CM_NOTIFY_ACTION_DEVICEINTERFACEARRIVAL,
+                CM_NOTIFY_ACTION_DEVICEINTERFACEREMOVAL, CM_NOTIFY_EVENT_DATA, CM_NOTIFY_FILTER,
+                CM_NOTIFY_FILTER_TYPE_DEVICEINTERFACE, CONFIGRET, CR_SUCCESS, HCMNOTIFICATION, PCM_NOTIFY_CALLBACK,
+            },
+            Usb::GUID_DEVINTERFACE_USB_DEVICE,
+        },
+        System::Ioctl::GUID_DEVINTERFACE_VOLUME,
+    },
+};
+
+use super::audit;
+use super::class_policy;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::device_interfaces;
+use super::email_alerts;
+use super::events;
+use super::hid_quarantine;
+use super::hub_policy;
+use super::inventory;
+use super::keyboard_lockdown;
+use super::network_adapters;
+use super::notifications::{self, Severity};
+use super::port_locks;
+use super::simulation;
+use super::threats;
+use super::verification::{self, Drift};
+use super::wireless;
+
+pub const EVENT_DEVICE_CONNECTED: &str = "usb://device-connected";
+pub const EVENT_DEVICE_REMOVED: &str = "usb://device-removed";
+pub const EVENT_POST_RESTART: &str = "usb://post-restart-state";
+// Kept for listeners that just refresh the whole list
+const EVENT_DEVICE_CHANGED: &str = "usb-device-changed";
+
+// A composite device raises one notification per interface; wait for the burst to end
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+// After a service restart or controller reset, Windows re-detects everything;
+// wait until two polls in a row see no change
+const REDETECT_POLL: Duration = Duration::from_millis(500);
+const REDETECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+static WORKER: OnceCell<Mutex<Sender<()>>> = OnceCell::new();
+static SNAPSHOT: Mutex<Option<HashMap<String, UsbDeviceInfo>>> = Mutex::new(None);
+// Keys present before a restart; their comings and goings are not reported one by one
+static REDETECTING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+static NOTIFICATIONS: Mutex<Vec<isize>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize)]
+pub enum HotplugKind {
+    Connected,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotplugEvent {
+    pub kind: HotplugKind,
+    pub device: UsbDeviceInfo,
+}
+
+/// The device list once Windows has finished re-detecting after a restart,
+/// emitted in place of the per-device events for what came back.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostRestartState {
+    /// `"service_restart"` or `"controller_reset"`
+    pub reason: String,
+    pub devices: Vec<UsbDeviceInfo>,
+    /// Instance IDs present before that did not come back
+    pub missing: Vec<String>,
+    /// Devices that came back running when policy says blocked, now re-blocked
+    pub drift: Vec<Drift>,
+    /// Re-detection was still going when the wait timed out
+    pub timed_out: bool,
+}
+
+/// Start the watcher: a worker that re-enumerates after each burst of
+/// notifications, and, on hardware, CM notifications for USB device
+/// interfaces and for volumes, which mount after the device has arrived.
+/// The callback form needs no window.
+pub fn start() {
+    let (sender, receiver) = mpsc::channel::<()>();
+    if WORKER.set(Mutex::new(sender)).is_err() {
+        return;
+    }
+    thread::spawn(move || {
+        rescan();
+        while receiver.recv().is_ok() {
+            thread::sleep(SETTLE_TIME);
+            while receiver.try_recv().is_ok() {}
+            rescan();
+        }
+    });
+
+    if simulation::is_active() {
+        // The simulated backend calls `notify` itself
+        return;
+    }
+    for (name, class) in [("USB device", GUID_DEVINTERFACE_USB_DEVICE), ("volume", GUID_DEVINTERFACE_VOLUME)] {
+        if let Err(status) = register(class) {
+            log::error!("Failed to register for {} notifications: {:?}", name, status);
+        }
+    }
+}
+
+fn register(class: GUID) -> Result<(), CONFIGRET> {
+    let mut filter = CM_NOTIFY_FILTER {
+        cbSize: std::mem::size_of::<CM_NOTIFY_FILTER>() as u32,
+        FilterType: CM_NOTIFY_FILTER_TYPE_DEVICEINTERFACE,
+        ..Default::default()
+    };
+    let callback: PCM_NOTIFY_CALLBACK = Some(on_notification);
+    // HCMNOTIFICATION's raw value, which is what the binding writes
+    let mut handle = 0isize;
+    let status = unsafe {
+        filter.u.DeviceInterface.ClassGuid = class;
+        CM_Register_Notification(&filter as *const CM_NOTIFY_FILTER, None, callback, &mut handle)
+    };
+    if status != CR_SUCCESS {
+        return Err(status);
+    }
+    // Lives for the rest of the process
+    NOTIFICATIONS.lock().unwrap().push(handle);
+    Ok(())
+}
+
+unsafe extern "system" fn on_notification(
+    _notification: HCMNOTIFICATION,
+    _context: *const c_void,
+    action: CM_NOTIFY_ACTION,
+    _data: *const CM_NOTIFY_EVENT_DATA,
+    _size: u32,
+) -> u32 {
+    if action == CM_NOTIFY_ACTION_DEVICEINTERFACEARRIVAL || action == CM_NOTIFY_ACTION_DEVICEINTERFACEREMOVAL {
+        notify();
+    }
+    0
+}
+
+/// Ask the worker to re-enumerate. A no-op until `start` has run.
+pub fn notify() {
+    if let Some(worker) = WORKER.get() {
+        let _ = worker.lock().unwrap().send(());
+    }
+}
+
+fn key(device: &UsbDeviceInfo) -> String {
+    match device.instance_id() {
+        Some(instance_id) => instance_id.to_ascii_uppercase(),
+        None => format!(
+            "{:04X}:{:04X}@{}",
+            device.vendor_id(),
+            device.product_id(),
+            device.port_chain().unwrap_or_default()
+        ),
+    }
+}
+
+fn present(device: &UsbDeviceInfo) -> bool {
+    // Devices we blocked stay listed after they are pulled
+    device.state() != DeviceState::Disconnected
+}
+
+/// Enumerate and emit one event per device that appeared or went away since
+/// the previous scan. The first scan only records what is there, apart from
+/// blocking known attack devices.
+pub fn rescan() -> Vec<HotplugEvent> {
+    let devices = match commands::get_usb_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            log::error!("Hotplug rescan failed: {}", e);
+            return Vec::new();
+        }
+    };
+    let current: HashMap<String, UsbDeviceInfo> = devices
+        .into_iter()
+        .filter(present)
+        .map(|device| (key(&device), device))
+        .collect();
+
+    let previous = SNAPSHOT.lock().unwrap().replace(current.clone());
+    let previous = match previous {
+        Some(previous) => previous,
+        None => {
+            // One plugged in before the app started is no less dangerous
+            for device in current.values() {
+                threats::enforce(device);
+            }
+            return Vec::new();
+        }
+    };
+
+    // Every device dropping off in one scan is a controller reset, not a mass
+    // unplug. Scripted machines are swapped wholesale, so the simulation is left out.
+    let reset = !simulation::is_active()
+        && previous.len() >= 2
+        && previous.keys().all(|key| !current.contains_key(key))
+        && begin_redetect(previous.keys().cloned().collect());
+    if reset {
+        thread::spawn(|| finish_redetect("controller_reset"));
+    }
+    let redetecting = REDETECTING.lock().unwrap().clone().unwrap_or_default();
+
+    // Trust is first decided before the stick's volume has mounted; a bound
+    // device whose volume turns out not to match is handled like an arrival
+    let mismatched: Vec<UsbDeviceInfo> = current
+        .iter()
+        .filter(|(key, device)| {
+            device.approval_required() && previous.get(*key).is_some_and(|before| !before.approval_required())
+        })
+        .map(|(_, device)| device.clone())
+        .collect();
+
+    let mut changes: Vec<HotplugEvent> = current
+        .iter()
+        .filter(|(key, _)| !previous.contains_key(*key))
+        .map(|(_, device)| HotplugEvent {
+            kind: HotplugKind::Connected,
+            device: device.clone(),
+        })
+        .collect();
+    changes.extend(
+        previous
+            .into_iter()
+            .filter(|(key, _)| !current.contains_key(key))
+            .map(|(_, device)| HotplugEvent {
+                kind: HotplugKind::Removed,
+                device,
+            }),
+    );
+
+    for change in changes.iter().filter(|change| !redetecting.contains(&key(&change.device))) {
+        let event = match change.kind {
+            HotplugKind::Connected => EVENT_DEVICE_CONNECTED,
+            HotplugKind::Removed => EVENT_DEVICE_REMOVED,
+        };
+        events::emit(event, change.device.clone());
+        audit::record(
+            match change.kind {
+                HotplugKind::Connected => "device_connected",
+                HotplugKind::Removed => "device_removed",
+            },
+            json!({
+                "instance_id": change.device.instance_id(),
+                "vendor_id": change.device.vendor_id(),
+                "product_id": change.device.product_id(),
+                "port_chain": change.device.port_chain(),
+            }),
+        );
+        // Known attack devices, pre-blocked devices, port locks, hub and
+        // class blocks, network adapters, keyboard lockdown, then autoblock;
+        // interface rules and the wireless policy only see what they let through
+        if let HotplugKind::Connected = change.kind {
+            if !change.device.trusted() {
+                email_alerts::untrusted_device(&change.device);
+            }
+            if !threats::enforce(&change.device)
+                && !inventory::enforce(&change.device)
+                && !port_locks::enforce(&change.device)
+                && !hub_policy::enforce(&change.device)
+                && !class_policy::enforce(&change.device)
+                && !network_adapters::enforce(&change.device)
+                && !keyboard_lockdown::enforce(&change.device)
+                && !commands::autoblock_arrival(&change.device)
+            {
+                device_interfaces::enforce(&change.device);
+                notifications::notify(
+                    Severity::Info,
+                    "device_connected",
+                    "USB device connected",
+                    change.device.product().unwrap_or(&key(&change.device)),
+                );
+                wireless::on_connected(&change.device);
+                hid_quarantine::on_connected(&change.device);
+            }
+        }
+    }
+    for device in &mismatched {
+        audit::record(
+            "trust_binding_mismatched",
+            json!({
+                "instance_id": device.instance_id(),
+                "vendor_id": device.vendor_id(),
+                "product_id": device.product_id(),
+            }),
+        );
+        commands::autoblock_arrival(device);
+    }
+    if !changes.is_empty() || !mismatched.is_empty() {
+        events::emit(EVENT_DEVICE_CHANGED, ());
+    }
+    changes
+}
+
+// Returns false when a re-detection is already under way
+fn begin_redetect(keys: HashSet<String>) -> bool {
+    let mut redetecting = REDETECTING.lock().unwrap();
+    if redetecting.is_some() {
+        return false;
+    }
+    *redetecting = Some(keys);
+    true
+}
+
+fn finish_redetect(reason: &str) -> PostRestartState {
+    let started = Instant::now();
+    let mut quiet_polls = 0;
+    while quiet_polls < 2 && started.elapsed() < REDETECT_TIMEOUT {
+        thread::sleep(REDETECT_POLL);
+        if rescan().is_empty() {
+            quiet_polls += 1;
+        } else {
+            quiet_polls = 0;
+        }
+    }
+    let before = REDETECTING.lock().unwrap().take().unwrap_or_default();
+
+    let devices = commands::get_usb_devices().unwrap_or_else(|e| {
+        log::error!("Post-restart enumeration failed: {}", e);
+        Vec::new()
+    });
+    let present: HashSet<String> = devices.iter().filter(|d| present(d)).map(key).collect();
+    let mut missing: Vec<String> = before.difference(&present).cloned().collect();
+    missing.sort();
+    let state = PostRestartState {
+        reason: reason.to_string(),
+        drift: verification::reconcile(&devices),
+        devices,
+        missing,
+        timed_out: quiet_polls < 2,
+    };
+    audit::record(
+        "post_restart_reconciled",
+        json!({
+            "reason": reason,
+            "devices": state.devices.len(),
+            "missing": state.missing,
+            "drift": state.drift,
+            "timed_out": state.timed_out,
+        }),
+    );
+    events::emit(EVENT_POST_RESTART, state.clone());
+    events::emit(EVENT_DEVICE_CHANGED, ());
+    state
+}
+
+/// Wait for Windows to re-detect every device after something reset the USB
+/// stack, re-apply policy to what came back, and emit one `PostRestartState`.
+/// Returns `None` if a re-detection is already running.
+pub fn redetect(reason: &str) -> Option<PostRestartState> {
+    let keys = SNAPSHOT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|snapshot| snapshot.keys().cloned().collect())
+        .unwrap_or_default();
+    if !begin_redetect(keys) {
+        return None;
+    }
+    Some(finish_redetect(reason))
+}
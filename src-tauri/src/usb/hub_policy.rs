@@ -0,0 +1,184 @@
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::category::DeviceCategory;
+use super::commands::{DeviceState, UsbDeviceInfo};
+use super::correlation::{self, DevNode};
+use super::docks;
+use super::dry_run;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_control::{self, BlockReason};
+
+// Container ID Windows gives devices built into the machine itself
+const LOCAL_MACHINE_CONTAINER: &str = "{00000000-0000-0000-FFFF-FFFFFFFFFFFF}";
+
+/// An approved external hub. Unset fields match anything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HubRule {
+    pub vendor_id: u16,
+    #[serde(default)]
+    pub product_id: Option<u16>,
+    /// Pins the rule to one physical hub
+    #[serde(default)]
+    pub container_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubPolicy {
+    pub enabled: bool,
+    pub allowed: Vec<HubRule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockedHub {
+    pub instance_id: String,
+    /// Devices behind the hub that were disabled with it
+    pub downstream: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<HubPolicy> = Mutex::new(HubPolicy {
+        enabled: false,
+        allowed: Vec::new(),
+    });
+}
+
+fn matches(rule: &HubRule, node: &DevNode) -> bool {
+    rule.vendor_id == node.vendor_id
+        && rule.product_id.is_none_or(|product_id| product_id == node.product_id)
+        && rule.container_id.as_deref().is_none_or(|container| {
+            node.container_id.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(container))
+        })
+}
+
+/// Built-in hubs and registered docks are always approved; anything else
+/// needs a rule.
+pub fn approved(policy: &HubPolicy, node: &DevNode) -> bool {
+    if let Some(container) = node.container_id.as_deref() {
+        if container.eq_ignore_ascii_case(LOCAL_MACHINE_CONTAINER) || docks::is_dock(container) {
+            return true;
+        }
+    }
+    policy.allowed.iter().any(|rule| matches(rule, node))
+}
+
+/// Disable `hub` and every device behind it, deepest first so nothing
+/// re-enumerates on a hub that is about to go.
+fn block_tree(hub: &DevNode, devnodes: &[DevNode]) -> BlockedHub {
+    let mut downstream: Vec<(usize, &DevNode)> = devnodes
+        .iter()
+        .filter(|node| !node.instance_id.eq_ignore_ascii_case(&hub.instance_id))
+        .filter_map(|node| {
+            let chain = correlation::ancestors(node, devnodes);
+            chain
+                .iter()
+                .any(|ancestor| ancestor.instance_id.eq_ignore_ascii_case(&hub.instance_id))
+                .then_some((chain.len(), node))
+        })
+        .collect();
+    downstream.sort_by_key(|(depth, _)| std::cmp::Reverse(*depth));
+
+    let mut errors = Vec::new();
+    for node in downstream.iter().map(|(_, node)| *node).chain(Some(hub)) {
+        if node.disabled {
+            continue;
+        }
+        if let Err(e) = usb_control::block(&node.instance_id, BlockReason::HubPolicy) {
+            errors.push(format!("{}: {}", node.instance_id, e));
+        }
+    }
+    let blocked = BlockedHub {
+        instance_id: hub.instance_id.clone(),
+        downstream: downstream.iter().map(|(_, node)| node.instance_id.clone()).collect(),
+        errors,
+    };
+    audit::record("hub_blocked", json!(blocked));
+    notifications::notify(
+        Severity::Critical,
+        "hub_blocked",
+        "Unapproved USB hub blocked",
+        &format!("{} device(s) behind it were disabled with it.", blocked.downstream.len()),
+    );
+    blocked
+}
+
+/// Block a hub that just arrived if the policy is on and it is not approved.
+/// Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    let policy = POLICY.lock().unwrap().clone();
+    if !policy.enabled || device.category() != DeviceCategory::Hub || device.state() != DeviceState::Connected {
+        return false;
+    }
+    let instance_id = match device.instance_id() {
+        Some(instance_id) => instance_id,
+        None => return false,
+    };
+    let devnodes = match backend::controller().devnodes() {
+        Ok(devnodes) => devnodes,
+        Err(e) => {
+            log::error!("Hub policy could not list devnodes: {}", e);
+            return false;
+        }
+    };
+    match devnodes.iter().find(|node| node.instance_id.eq_ignore_ascii_case(instance_id)) {
+        Some(hub) if !approved(&policy, hub) => {
+            block_tree(hub, &devnodes);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Block every attached hub the policy does not approve.
+fn enforce_all(policy: &HubPolicy) -> Result<Vec<BlockedHub>, String> {
+    let devnodes = backend::controller().devnodes()?;
+    let hubs: Vec<&DevNode> = devnodes
+        .iter()
+        // Hubs are the devnodes with children
+        .filter(|node| {
+            devnodes.iter().any(|child| {
+                child
+                    .parent_instance_id
+                    .as_deref()
+                    .is_some_and(|parent| parent.eq_ignore_ascii_case(&node.instance_id))
+            })
+        })
+        .filter(|node| !node.disabled && !approved(policy, node))
+        .collect();
+    Ok(hubs.into_iter().map(|hub| block_tree(hub, &devnodes)).collect())
+}
+
+#[command]
+pub fn get_hub_policy() -> Result<HubPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+/// Replace the hub policy. Attached hubs it does not approve are blocked
+/// with everything behind them. Approving more hubs, or turning the policy
+/// off, needs the admin PIN.
+#[command]
+pub fn set_hub_policy(policy: HubPolicy, admin_token: Option<String>) -> Result<Vec<BlockedHub>, String> {
+    dry_run::refuse("set_hub_policy")?;
+    let previous = POLICY.lock().unwrap().clone();
+    let relaxed = previous.enabled
+        && (!policy.enabled || policy.allowed.iter().any(|rule| !previous.allowed.contains(rule)));
+    if relaxed {
+        admin_pin::require_admin(admin_token.as_deref(), "approve more USB hubs")?;
+        hello::require_consent("approve more USB hubs")?;
+    }
+    audit::record("hub_policy_changed", json!({ "previous": previous, "current": policy }));
+    *POLICY.lock().unwrap() = policy.clone();
+    if policy.enabled {
+        enforce_all(&policy)
+    } else {
+        Ok(Vec::new())
+    }
+}
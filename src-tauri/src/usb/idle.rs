@@ -0,0 +1,80 @@
+use std::{sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::Win32::{
+    System::SystemInformation::GetTickCount,
+    UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO},
+};
+
+use super::audit;
+use super::profiles::{self, Profile};
+
+const SOURCE: &str = "idle";
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleLockdownSettings {
+    pub enabled: bool,
+    pub idle_minutes: u32,
+    pub profile: Profile,
+}
+
+lazy_static! {
+    static ref SETTINGS: Mutex<IdleLockdownSettings> = Mutex::new(IdleLockdownSettings {
+        enabled: false,
+        idle_minutes: 10,
+        profile: Profile::Strict,
+    });
+}
+
+/// Start the idle watcher. It polls GetLastInputInfo, which only sees input
+/// in the session the app runs in - independent of whether the screen is locked.
+pub fn start() {
+    thread::spawn(|| loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let settings = SETTINGS.lock().unwrap().clone();
+        let idle = settings.enabled
+            && idle_time().is_some_and(|idle| idle >= Duration::from_secs(settings.idle_minutes as u64 * 60));
+        profiles::request(SOURCE, if idle { Some(settings.profile) } else { None });
+    });
+}
+
+fn idle_time() -> Option<Duration> {
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe {
+        if !GetLastInputInfo(&mut info).as_bool() {
+            return None;
+        }
+        // Both are 32-bit tick counts; wrapping_sub survives the 49.7 day rollover
+        Some(Duration::from_millis(GetTickCount().wrapping_sub(info.dwTime) as u64))
+    }
+}
+
+#[command]
+pub fn get_idle_lockdown() -> Result<IdleLockdownSettings, String> {
+    Ok(SETTINGS.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_idle_lockdown(enabled: bool, idle_minutes: u32, profile: Profile) -> Result<(), String> {
+    if idle_minutes == 0 {
+        return Err("Idle timeout must be at least one minute".to_string());
+    }
+    let settings = IdleLockdownSettings {
+        enabled,
+        idle_minutes,
+        profile,
+    };
+    audit::record("idle_lockdown_changed", json!(settings));
+    *SETTINGS.lock().unwrap() = settings;
+    if !enabled {
+        profiles::request(SOURCE, None);
+    }
+    Ok(())
+}
@@ -0,0 +1,365 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Mutex,
+};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::category::DeviceCategory;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::hello;
+use super::notifications::{self, Severity};
+use super::paging::{Page, PageRequest};
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const INVENTORY_FILE: &str = "inventory.json";
+
+// last_seen is refreshed on every enumeration; only persist it this often
+const LAST_SEEN_RESOLUTION: i64 = 60;
+
+/// Every device this machine has ever enumerated, keyed by instance ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryRecord {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    pub category: DeviceCategory,
+    /// Trust at the last sighting
+    pub trusted: bool,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Times it was plugged in, counted from the first time it was seen
+    #[serde(default)]
+    pub connection_count: u32,
+    /// Attached at the last enumeration
+    #[serde(default)]
+    pub connected: bool,
+    /// Blocked whenever it is plugged in; set ahead of time from the history
+    #[serde(default)]
+    pub preblocked: bool,
+}
+
+/// One device from an enumeration pass.
+pub struct Sighting<'a> {
+    pub instance_id: &'a str,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: Option<&'a str>,
+    pub product: Option<&'a str>,
+    pub serial: Option<&'a str>,
+    pub category: DeviceCategory,
+    pub trusted: bool,
+}
+
+/// Every field is optional; set fields must all match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct InventoryFilter {
+    /// Case-insensitive substring of manufacturer, product or serial
+    pub text: Option<String>,
+    pub category: Option<DeviceCategory>,
+    pub trusted: Option<bool>,
+    /// Seen at some point within `[seen_since, seen_until]`
+    pub seen_since: Option<DateTime<Utc>>,
+    pub seen_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum InventorySortField {
+    #[default]
+    LastSeen,
+    FirstSeen,
+    Product,
+    Manufacturer,
+    VendorId,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct InventorySort {
+    pub field: InventorySortField,
+    pub descending: bool,
+}
+
+impl Default for InventorySort {
+    /// Most recently seen first
+    fn default() -> Self {
+        InventorySort {
+            field: InventorySortField::LastSeen,
+            descending: true,
+        }
+    }
+}
+
+lazy_static! {
+    static ref INVENTORY: Mutex<Option<HashMap<String, InventoryRecord>>> = Mutex::new(None);
+}
+
+fn load() -> HashMap<String, InventoryRecord> {
+    let records: Vec<InventoryRecord> = usb_config::data_file(INVENTORY_FILE)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    records
+        .into_iter()
+        .map(|record| (record.instance_id.to_ascii_uppercase(), record))
+        .collect()
+}
+
+fn save(inventory: &HashMap<String, InventoryRecord>) {
+    let records: Vec<&InventoryRecord> = inventory.values().collect();
+    let result = usb_config::data_file(INVENTORY_FILE).and_then(|path| {
+        let data = serde_json::to_vec(&records).map_err(|e| e.to_string())?;
+        fs::write(path, data).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::error!("Failed to save device inventory: {}", e);
+    }
+}
+
+fn with_inventory<T>(f: impl FnOnce(&mut HashMap<String, InventoryRecord>) -> T) -> T {
+    let mut inventory = INVENTORY.lock().unwrap();
+    f(inventory.get_or_insert_with(load))
+}
+
+/// Fold an enumeration pass into the inventory. `sightings` must be every
+/// attached device: anything missing counts as unplugged.
+pub fn observe(sightings: &[Sighting]) {
+    let now = Utc::now();
+    with_inventory(|inventory| {
+        let mut changed = false;
+        let mut seen = HashSet::new();
+        for sighting in sightings {
+            let key = sighting.instance_id.to_ascii_uppercase();
+            seen.insert(key.clone());
+            match inventory.get_mut(&key) {
+                Some(record) => {
+                    let stale = now - record.last_seen > Duration::seconds(LAST_SEEN_RESOLUTION);
+                    if stale || record.trusted != sighting.trusted || record.category != sighting.category {
+                        changed = true;
+                    }
+                    if !record.connected {
+                        record.connected = true;
+                        record.connection_count += 1;
+                        changed = true;
+                    }
+                    record.last_seen = now;
+                    record.trusted = sighting.trusted;
+                    record.category = sighting.category;
+                    // Strings are only readable while the driver is loaded
+                    if let Some(product) = sighting.product {
+                        record.product = Some(product.to_string());
+                    }
+                    if let Some(manufacturer) = sighting.manufacturer {
+                        record.manufacturer = Some(manufacturer.to_string());
+                    }
+                }
+                None => {
+                    inventory.insert(
+                        key,
+                        InventoryRecord {
+                            instance_id: sighting.instance_id.to_string(),
+                            vendor_id: sighting.vendor_id,
+                            product_id: sighting.product_id,
+                            manufacturer: sighting.manufacturer.map(str::to_string),
+                            product: sighting.product.map(str::to_string),
+                            serial: sighting.serial.map(str::to_string),
+                            category: sighting.category,
+                            trusted: sighting.trusted,
+                            first_seen: now,
+                            last_seen: now,
+                            connection_count: 1,
+                            connected: true,
+                            preblocked: false,
+                        },
+                    );
+                    changed = true;
+                }
+            }
+        }
+        for (key, record) in inventory.iter_mut() {
+            if record.connected && !seen.contains(key) {
+                record.connected = false;
+                changed = true;
+            }
+        }
+        if changed {
+            save(inventory);
+        }
+    });
+}
+
+/// Every record, in no particular order.
+pub fn records() -> Vec<InventoryRecord> {
+    with_inventory(|inventory| inventory.values().cloned().collect())
+}
+
+fn matches(record: &InventoryRecord, filter: &InventoryFilter) -> bool {
+    if let Some(text) = filter.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let needle = text.to_lowercase();
+        let hit = [&record.manufacturer, &record.product, &record.serial]
+            .iter()
+            .any(|field| field.as_deref().is_some_and(|value| value.to_lowercase().contains(&needle)));
+        if !hit {
+            return false;
+        }
+    }
+    filter.category.is_none_or(|category| record.category == category)
+        && filter.trusted.is_none_or(|trusted| record.trusted == trusted)
+        && filter.seen_since.is_none_or(|since| record.last_seen >= since)
+        && filter.seen_until.is_none_or(|until| record.first_seen <= until)
+}
+
+fn compare(a: &InventoryRecord, b: &InventoryRecord, field: InventorySortField) -> Ordering {
+    // Missing strings sort last, case-insensitively otherwise
+    let text = |value: &Option<String>| value.as_deref().map(str::to_lowercase);
+    let by_text = |x: &Option<String>, y: &Option<String>| match (text(x), text(y)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    match field {
+        InventorySortField::LastSeen => a.last_seen.cmp(&b.last_seen),
+        InventorySortField::FirstSeen => a.first_seen.cmp(&b.first_seen),
+        InventorySortField::Product => by_text(&a.product, &b.product),
+        InventorySortField::Manufacturer => by_text(&a.manufacturer, &b.manufacturer),
+        InventorySortField::VendorId => (a.vendor_id, a.product_id).cmp(&(b.vendor_id, b.product_id)),
+    }
+}
+
+/// One page of the inventory records matching `filter`, sorted on the
+/// backend. By default the most recently seen come first. The inventory is
+/// kept in memory and saved as JSON like every other data file, not in
+/// SQLite: a machine sees a few thousand devices at most, a full scan of
+/// which takes well under a millisecond, and no database engine has to ship
+/// with the app or be migrated.
+#[command]
+pub fn query_inventory(
+    filter: InventoryFilter,
+    sort: Option<InventorySort>,
+    page: Option<PageRequest>,
+) -> Result<Page<InventoryRecord>, String> {
+    let sort = sort.unwrap_or_default();
+    let mut records: Vec<InventoryRecord> = with_inventory(|inventory| {
+        inventory
+            .values()
+            .filter(|record| matches(record, &filter))
+            .cloned()
+            .collect()
+    });
+    records.sort_by(|a, b| {
+        // Instance ID breaks ties so pages are stable between calls
+        let order = compare(a, b, sort.field).then_with(|| a.instance_id.cmp(&b.instance_id));
+        if sort.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+    Ok(page.unwrap_or_default().apply(records))
+}
+
+/// Every device ever seen, attached or not, most recently seen first.
+#[command]
+pub fn get_device_history() -> Result<Vec<InventoryRecord>, String> {
+    let mut records = records();
+    records.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.instance_id.cmp(&b.instance_id)));
+    Ok(records)
+}
+
+// A device without a serial gets a new instance ID on every port, so past
+// the instance ID it is matched by VID/PID and serial, or by VID/PID alone
+// when neither side has a serial
+fn same_device(record: &InventoryRecord, device: &UsbDeviceInfo, instance_id: &str) -> bool {
+    if record.instance_id.eq_ignore_ascii_case(instance_id) {
+        return true;
+    }
+    record.vendor_id == device.vendor_id()
+        && record.product_id == device.product_id()
+        && match (record.serial.as_deref(), device.serial_number()) {
+            (Some(recorded), Some(serial)) => recorded.eq_ignore_ascii_case(serial),
+            (None, None) => true,
+            _ => false,
+        }
+}
+
+/// Block a device that just arrived if it was pre-blocked from the history.
+/// Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) if device.state() == DeviceState::Connected => instance_id,
+        _ => return false,
+    };
+    let preblocked = with_inventory(|inventory| {
+        inventory
+            .values()
+            .any(|record| record.preblocked && same_device(record, device, instance_id))
+    });
+    if !preblocked {
+        return false;
+    }
+    match usb_control::block(instance_id, BlockReason::Manual) {
+        Ok(_) => {
+            audit::record("preblocked_device_blocked", json!({ "instance_id": instance_id }));
+            notifications::notify(
+                Severity::Warning,
+                "preblocked_device_blocked",
+                "Pre-blocked USB device blocked",
+                device.product().unwrap_or(instance_id),
+            );
+            true
+        }
+        Err(e) => {
+            log::error!("Failed to block pre-blocked device {}: {}", instance_id, e);
+            false
+        }
+    }
+}
+
+/// Pre-block a device from the history, or stop doing so. It is recognised
+/// on any port by VID/PID and serial; one without a serial pre-blocks every
+/// serial-less unit of its model. A matching device that is attached is
+/// blocked right away; lifting the rule leaves it as it is.
+#[command]
+pub fn set_device_preblocked(instance_id: String, preblocked: bool) -> Result<InventoryRecord, String> {
+    if !preblocked {
+        hello::require_consent("stop blocking a device on arrival")?;
+    }
+    let record = with_inventory(|inventory| {
+        let record = inventory
+            .get_mut(&instance_id.to_ascii_uppercase())
+            .ok_or_else(|| format!("{} has never been seen", instance_id))?;
+        record.preblocked = preblocked;
+        let record = record.clone();
+        save(inventory);
+        Ok::<_, String>(record)
+    })?;
+    audit::record(
+        "device_preblock_changed",
+        json!({ "instance_id": record.instance_id, "preblocked": preblocked }),
+    );
+    if preblocked {
+        for device in commands::get_usb_devices()? {
+            let attached = match device.instance_id() {
+                Some(id) if device.state() == DeviceState::Connected && same_device(&record, &device, id) => id,
+                _ => continue,
+            };
+            if !usb_control::is_blocked_by_us(attached) {
+                usb_control::block(attached, BlockReason::Manual)?;
+            }
+        }
+    }
+    Ok(record)
+}
@@ -0,0 +1,255 @@
+use std::{fs, sync::Mutex};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::category;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const LOCKDOWN_FILE: &str = "keyboard-lockdown.json";
+pub const EVENT_KEYBOARD_LOCKDOWN: &str = "usb://keyboard-lockdown";
+
+/// A keyboard allowed to type while lockdown is on: attached when lockdown
+/// was turned on, or approved since. It is recognised on any port by VID/PID
+/// and serial; one without a serial admits every serial-less unit of its
+/// model, since its instance ID changes with the port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownKeyboard {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(default)]
+    pub serial: Option<String>,
+    pub product: Option<String>,
+    pub known_since: DateTime<Utc>,
+}
+
+/// A keyboard disabled on arrival, waiting for the user's approval.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingKeyboard {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: Option<String>,
+    pub product: Option<String>,
+    pub detected_at: DateTime<Utc>,
+    /// Set when disabling it failed; it is still listed so the user knows
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyboardLockdown {
+    pub enabled: bool,
+    pub known: Vec<KnownKeyboard>,
+    #[serde(skip_deserializing)]
+    pub pending: Vec<PendingKeyboard>,
+}
+
+lazy_static! {
+    static ref LOCKDOWN: Mutex<KeyboardLockdown> = Mutex::new(KeyboardLockdown::default());
+}
+
+fn save(lockdown: &KeyboardLockdown) -> Result<(), String> {
+    let path = usb_config::data_file(LOCKDOWN_FILE)?;
+    let data = serde_json::to_vec_pretty(lockdown).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn same_keyboard(known: &KnownKeyboard, keyboard: &KnownKeyboard) -> bool {
+    if known.instance_id.eq_ignore_ascii_case(&keyboard.instance_id) {
+        return true;
+    }
+    known.vendor_id == keyboard.vendor_id
+        && known.product_id == keyboard.product_id
+        && match (known.serial.as_deref(), keyboard.serial.as_deref()) {
+            (Some(known), Some(serial)) => known.eq_ignore_ascii_case(serial),
+            (None, None) => true,
+            _ => false,
+        }
+}
+
+fn is_known(lockdown: &KeyboardLockdown, keyboard: &KnownKeyboard) -> bool {
+    lockdown.known.iter().any(|known| same_keyboard(known, keyboard))
+}
+
+fn known(device: &UsbDeviceInfo, instance_id: &str) -> KnownKeyboard {
+    KnownKeyboard {
+        instance_id: instance_id.to_string(),
+        vendor_id: device.vendor_id(),
+        product_id: device.product_id(),
+        serial: device.serial_number().map(str::to_string),
+        product: device.product().map(str::to_string),
+        known_since: Utc::now(),
+    }
+}
+
+/// Load the saved lockdown setting and known keyboards. Called from setup.
+/// A file that cannot be read or parsed turns lockdown on with no keyboard
+/// known, rather than silently off: keyboards already attached keep typing
+/// and new ones wait for approval.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(LOCKDOWN_FILE)?;
+    let loaded = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(KeyboardLockdown::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let mut lockdown = LOCKDOWN.lock().unwrap();
+    match loaded {
+        Ok(loaded) => {
+            *lockdown = loaded;
+            Ok(())
+        }
+        Err(e) => {
+            *lockdown = KeyboardLockdown {
+                enabled: true,
+                ..KeyboardLockdown::default()
+            };
+            audit::record("keyboard_lockdown_load_failed", json!({ "error": e }));
+            Err(e)
+        }
+    }
+}
+
+/// Block a keyboard that just arrived and is not known, composite devices
+/// with a keyboard function included: a BadUSB stick starts typing as soon
+/// as it enumerates. Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) if device.state() == DeviceState::Connected => instance_id,
+        _ => return false,
+    };
+    if !category::has_keyboard(device.interfaces()) {
+        return false;
+    }
+    {
+        let lockdown = LOCKDOWN.lock().unwrap();
+        if !lockdown.enabled || is_known(&lockdown, &known(device, instance_id)) {
+            return false;
+        }
+    }
+
+    // Not under the lock: the block can take as long as the device timeout
+    let result = usb_control::block(instance_id, BlockReason::KeyboardLockdown);
+    let pending = PendingKeyboard {
+        instance_id: instance_id.to_string(),
+        vendor_id: device.vendor_id(),
+        product_id: device.product_id(),
+        serial: device.serial_number().map(str::to_string),
+        product: device.product().map(str::to_string),
+        detected_at: Utc::now(),
+        error: result.as_ref().err().map(ToString::to_string),
+    };
+    audit::record("keyboard_lockdown_blocked", json!(pending));
+    notifications::notify(
+        Severity::Warning,
+        "keyboard_lockdown_blocked",
+        "New keyboard blocked",
+        &format!(
+            "{} was blocked until you approve it from a keyboard or mouse you already use.",
+            pending.product.as_deref().unwrap_or(instance_id)
+        ),
+    );
+    let mut lockdown = LOCKDOWN.lock().unwrap();
+    lockdown.pending.retain(|entry| !entry.instance_id.eq_ignore_ascii_case(instance_id));
+    lockdown.pending.push(pending);
+    events::emit(EVENT_KEYBOARD_LOCKDOWN, lockdown.clone());
+    result.is_ok()
+}
+
+#[command]
+pub fn get_keyboard_lockdown() -> Result<KeyboardLockdown, String> {
+    Ok(LOCKDOWN.lock().unwrap().clone())
+}
+
+/// Turn keyboard lockdown on or off. Turning it on makes every keyboard
+/// attached right now known; turning it off needs consent and leaves
+/// keyboards already blocked as they are.
+#[command]
+pub fn set_keyboard_lockdown(enabled: bool) -> Result<KeyboardLockdown, String> {
+    if !enabled && LOCKDOWN.lock().unwrap().enabled {
+        hello::require_consent("turn off keyboard lockdown")?;
+    }
+    let attached = if enabled { commands::get_usb_devices()? } else { Vec::new() };
+    let mut lockdown = LOCKDOWN.lock().unwrap();
+    lockdown.enabled = enabled;
+    for device in &attached {
+        match device.instance_id() {
+            Some(instance_id)
+                if device.state() == DeviceState::Connected && category::has_keyboard(device.interfaces()) =>
+            {
+                let keyboard = known(device, instance_id);
+                if !is_known(&lockdown, &keyboard) {
+                    lockdown.known.push(keyboard);
+                }
+            }
+            _ => {}
+        }
+    }
+    save(&lockdown)?;
+    audit::record(
+        "keyboard_lockdown_changed",
+        json!({ "enabled": enabled, "known": lockdown.known.len() }),
+    );
+    events::emit(EVENT_KEYBOARD_LOCKDOWN, lockdown.clone());
+    Ok(lockdown.clone())
+}
+
+/// Let a blocked keyboard type. The keyboard itself is disabled, so the
+/// approval can only come from an input device that already works, and
+/// the admin PIN and Windows Hello confirm it was the user.
+#[command]
+pub fn approve_keyboard(instance_id: String, admin_token: Option<String>) -> Result<KeyboardLockdown, String> {
+    let pending = LOCKDOWN
+        .lock()
+        .unwrap()
+        .pending
+        .iter()
+        .find(|entry| entry.instance_id.eq_ignore_ascii_case(&instance_id))
+        .cloned()
+        .ok_or_else(|| format!("{} is not waiting for approval", instance_id))?;
+    admin_pin::require_admin(admin_token.as_deref(), "approve a new keyboard")?;
+    hello::require_consent("approve a new keyboard")?;
+    usb_control::unblock(&pending.instance_id)?;
+
+    let mut lockdown = LOCKDOWN.lock().unwrap();
+    lockdown.pending.retain(|entry| !entry.instance_id.eq_ignore_ascii_case(&instance_id));
+    let keyboard = KnownKeyboard {
+        instance_id: pending.instance_id.clone(),
+        vendor_id: pending.vendor_id,
+        product_id: pending.product_id,
+        serial: pending.serial.clone(),
+        product: pending.product.clone(),
+        known_since: Utc::now(),
+    };
+    if !is_known(&lockdown, &keyboard) {
+        lockdown.known.push(keyboard);
+    }
+    save(&lockdown)?;
+    audit::record("keyboard_approved", json!({ "instance_id": pending.instance_id }));
+    events::emit(EVENT_KEYBOARD_LOCKDOWN, lockdown.clone());
+    Ok(lockdown.clone())
+}
+
+/// Forget a known keyboard, so it is blocked the next time it arrives.
+#[command]
+pub fn forget_keyboard(instance_id: String) -> Result<KeyboardLockdown, String> {
+    let mut lockdown = LOCKDOWN.lock().unwrap();
+    if !lockdown.known.iter().any(|known| known.instance_id.eq_ignore_ascii_case(&instance_id)) {
+        return Err(format!("{} is not a known keyboard", instance_id));
+    }
+    lockdown.known.retain(|known| !known.instance_id.eq_ignore_ascii_case(&instance_id));
+    save(&lockdown)?;
+    audit::record("keyboard_forgotten", json!({ "instance_id": instance_id }));
+    events::emit(EVENT_KEYBOARD_LOCKDOWN, lockdown.clone());
+    Ok(lockdown.clone())
+}
@@ -0,0 +1,410 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::c_void,
+    fs,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::{
+    core::w,
+    Win32::{
+        Foundation::{HANDLE, HWND, LPARAM, LRESULT, WPARAM},
+        System::LibraryLoader::GetModuleHandleW,
+        UI::{
+            Input::{
+                GetRawInputData, GetRawInputDeviceInfoW, RegisterRawInputDevices, HRAWINPUT, RAWINPUT,
+                RAWINPUTDEVICE, RAWINPUTHEADER, RIDEV_INPUTSINK, RIDI_DEVICENAME, RID_INPUT,
+            },
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, HWND_MESSAGE, MSG,
+                WINDOW_EX_STYLE, WINDOW_STYLE, WNDCLASSW,
+            },
+        },
+    },
+};
+
+use super::audit;
+use super::correlation::parse_vid_pid;
+use super::events;
+use super::hello;
+use super::hid_quarantine;
+use super::notifications::{self, Severity};
+use super::usb_config;
+
+pub const EVENT_KEYSTROKE_ANOMALY: &str = "usb://keystroke-anomaly";
+
+const BASELINE_FILE: &str = "keystroke-baseline.json";
+
+const WM_INPUT: u32 = 0x00FF;
+const RIM_TYPE_KEYBOARD: u32 = 1;
+const RI_KEY_BREAK: u16 = 1;
+const USAGE_PAGE_GENERIC_DESKTOP: u16 = 0x01;
+const USAGE_KEYBOARD: u16 = 0x06;
+
+// Longer gaps are pauses, not cadence
+const MAX_INTERVAL_MS: f64 = 2000.0;
+// Intervals learned before the baseline is trusted
+const LEARN_SAMPLES: u64 = 500;
+const WINDOW: usize = 20;
+// A window this many times faster than the baseline is not a person typing
+const SPEEDUP_FACTOR: f64 = 4.0;
+// ...nor one this many times more regular
+const UNIFORMITY_FACTOR: f64 = 10.0;
+const SAVE_EVERY: u64 = 100;
+const ALERT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Coarse inter-key timing of one keyboard: the mean and spread of the gaps
+/// between key presses while typing. Key codes are never recorded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CadenceBaseline {
+    samples: u64,
+    mean_ms: f64,
+    // Welford's running sum of squared differences
+    m2: f64,
+    #[serde(skip)]
+    window: VecDeque<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CadenceAnomaly {
+    pub window_mean_ms: f64,
+    pub window_stddev_ms: f64,
+    pub baseline_mean_ms: f64,
+    pub baseline_stddev_ms: f64,
+}
+
+fn mean_and_stddev(values: &VecDeque<f64>) -> (f64, f64) {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+impl CadenceBaseline {
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn learned(&self) -> bool {
+        self.samples >= LEARN_SAMPLES
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        self.mean_ms
+    }
+
+    pub fn stddev_ms(&self) -> f64 {
+        if self.samples < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.samples - 1) as f64).sqrt()
+        }
+    }
+
+    fn learn(&mut self, interval_ms: f64) {
+        self.samples += 1;
+        let delta = interval_ms - self.mean_ms;
+        self.mean_ms += delta / self.samples as f64;
+        self.m2 += delta * (interval_ms - self.mean_ms);
+    }
+
+    /// Feed the gap before one key press. Once learned, returns an anomaly
+    /// when the last `WINDOW` gaps are far faster or far more regular than
+    /// the baseline. Anomalous input is not learned from.
+    pub fn record(&mut self, interval_ms: f64) -> Option<CadenceAnomaly> {
+        if !(0.0..=MAX_INTERVAL_MS).contains(&interval_ms) {
+            self.window.clear();
+            return None;
+        }
+        if !self.learned() {
+            self.learn(interval_ms);
+            return None;
+        }
+
+        self.window.push_back(interval_ms);
+        if self.window.len() < WINDOW {
+            return None;
+        }
+        let (window_mean, window_stddev) = mean_and_stddev(&self.window);
+        let too_fast = window_mean < self.mean_ms / SPEEDUP_FACTOR;
+        let too_regular = window_mean < self.mean_ms && window_stddev < self.stddev_ms() / UNIFORMITY_FACTOR;
+        if too_fast || too_regular {
+            // One alert per burst
+            self.window.clear();
+            return Some(CadenceAnomaly {
+                window_mean_ms: window_mean,
+                window_stddev_ms: window_stddev,
+                baseline_mean_ms: self.mean_ms,
+                baseline_stddev_ms: self.stddev_ms(),
+            });
+        }
+        if let Some(oldest) = self.window.pop_front() {
+            self.learn(oldest);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystrokePolicy {
+    pub enabled: bool,
+    /// The primary trusted keyboard. Input is attributed by VID/PID, so two
+    /// keyboards of the same model share a baseline.
+    pub keyboard_instance_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeystrokeStatus {
+    pub policy: KeystrokePolicy,
+    pub samples: u64,
+    pub learned: bool,
+    pub mean_interval_ms: f64,
+    pub stddev_ms: f64,
+}
+
+#[derive(Default)]
+struct Tracker {
+    // Raw input device handle -> VID/PID, resolved once per handle
+    devices: HashMap<isize, Option<(u16, u16)>>,
+    // Make codes held down per device, so auto-repeat is not counted as typing
+    pressed: HashSet<(isize, u16)>,
+    last_press: Option<Instant>,
+    last_alert: Option<Instant>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<KeystrokePolicy> = Mutex::new(KeystrokePolicy {
+        enabled: false,
+        keyboard_instance_id: None,
+    });
+    static ref BASELINE: Mutex<CadenceBaseline> = Mutex::new(load());
+    static ref TRACKER: Mutex<Tracker> = Mutex::new(Tracker::default());
+}
+
+fn load() -> CadenceBaseline {
+    usb_config::data_file(BASELINE_FILE)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save(baseline: &CadenceBaseline) {
+    let result = usb_config::data_file(BASELINE_FILE).and_then(|path| {
+        let data = serde_json::to_vec(baseline).map_err(|e| e.to_string())?;
+        fs::write(path, data).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::error!("Failed to save keystroke baseline: {}", e);
+    }
+}
+
+/// Listen for keyboard raw input from every device, in the background. Raw
+/// input is the only user-mode source that says which keyboard a key came from.
+pub fn start() {
+    thread::spawn(|| {
+        if let Err(e) = run_message_loop() {
+            log::warn!("Keystroke baseline unavailable: {}", e);
+        }
+    });
+}
+
+fn run_message_loop() -> Result<(), String> {
+    unsafe {
+        let instance = GetModuleHandleW(None).map_err(|e| e.to_string())?;
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: w!("UsbShieldRawInput"),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err("Failed to register the raw input window class".to_string());
+        }
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            w!("UsbShieldRawInput"),
+            w!(""),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            None,
+            instance,
+            None,
+        );
+        if window.0 == 0 {
+            return Err("Failed to create the raw input window".to_string());
+        }
+        // INPUTSINK: keep receiving input while another app has focus
+        let device = RAWINPUTDEVICE {
+            usUsagePage: USAGE_PAGE_GENERIC_DESKTOP,
+            usUsage: USAGE_KEYBOARD,
+            dwFlags: RIDEV_INPUTSINK,
+            hwndTarget: window,
+        };
+        if !RegisterRawInputDevices(&[device], std::mem::size_of::<RAWINPUTDEVICE>() as u32).as_bool() {
+            return Err("Failed to register for keyboard raw input".to_string());
+        }
+
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, HWND(0), 0, 0).as_bool() {
+            DispatchMessageW(&message);
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message == WM_INPUT {
+        on_raw_input(HRAWINPUT(lparam.0));
+    }
+    DefWindowProcW(window, message, wparam, lparam)
+}
+
+unsafe fn device_vid_pid(device: HANDLE) -> Option<(u16, u16)> {
+    let mut len = 0u32;
+    GetRawInputDeviceInfoW(device, RIDI_DEVICENAME, None, &mut len);
+    let mut name = vec![0u16; len as usize];
+    if GetRawInputDeviceInfoW(device, RIDI_DEVICENAME, Some(name.as_mut_ptr() as *mut c_void), &mut len) == u32::MAX {
+        return None;
+    }
+    // E.g. \\?\HID#VID_046D&PID_C31C&MI_00#7&1a2b3c&0&0000#{884b96c3-...}
+    parse_vid_pid(&String::from_utf16_lossy(&name))
+}
+
+unsafe fn on_raw_input(input: HRAWINPUT) {
+    let mut raw = RAWINPUT::default();
+    let mut len = std::mem::size_of::<RAWINPUT>() as u32;
+    let read = GetRawInputData(
+        input,
+        RID_INPUT,
+        Some(&mut raw as *mut RAWINPUT as *mut c_void),
+        &mut len,
+        std::mem::size_of::<RAWINPUTHEADER>() as u32,
+    );
+    if read == u32::MAX || raw.header.dwType != RIM_TYPE_KEYBOARD {
+        return;
+    }
+    let key = raw.data.keyboard;
+
+    let now = Instant::now();
+    let source = {
+        let mut tracker = TRACKER.lock().unwrap();
+        let handle = raw.header.hDevice;
+        let source = *tracker.devices.entry(handle.0).or_insert_with(|| device_vid_pid(handle));
+        if key.Flags & RI_KEY_BREAK != 0 {
+            tracker.pressed.remove(&(handle.0, key.MakeCode));
+            return;
+        }
+        if !tracker.pressed.insert((handle.0, key.MakeCode)) {
+            return;
+        }
+        match source {
+            Some(source) => source,
+            None => return,
+        }
+    };
+    // Every keyboard, whether or not a baseline is being kept
+    hid_quarantine::on_key_press(source, now);
+
+    let policy = POLICY.lock().unwrap().clone();
+    match policy.keyboard_instance_id.as_deref().and_then(parse_vid_pid) {
+        Some(keyboard) if policy.enabled && keyboard == source => {}
+        _ => return,
+    }
+    let interval = {
+        let mut tracker = TRACKER.lock().unwrap();
+        let interval = tracker.last_press.map(|last| now.duration_since(last).as_secs_f64() * 1000.0);
+        tracker.last_press = Some(now);
+        match interval {
+            Some(interval) => interval,
+            None => return,
+        }
+    };
+
+    let anomaly = {
+        let mut baseline = BASELINE.lock().unwrap();
+        let before = baseline.samples();
+        let anomaly = baseline.record(interval);
+        if baseline.samples() != before && baseline.samples().is_multiple_of(SAVE_EVERY) {
+            save(&baseline);
+        }
+        anomaly
+    };
+    if let Some(anomaly) = anomaly {
+        let mut tracker = TRACKER.lock().unwrap();
+        if tracker.last_alert.is_none_or(|last| now.duration_since(last) >= ALERT_INTERVAL) {
+            tracker.last_alert = Some(now);
+            drop(tracker);
+            let payload = json!({ "instance_id": policy.keyboard_instance_id, "anomaly": anomaly });
+            audit::record("keystroke_anomaly", payload.clone());
+            events::emit(EVENT_KEYSTROKE_ANOMALY, payload);
+            notifications::notify(
+                Severity::Critical,
+                "keystroke_anomaly",
+                "Unusual typing detected",
+                "Keystrokes are arriving faster or more evenly than a person types.",
+            );
+        }
+    }
+}
+
+#[command]
+pub fn get_keystroke_baseline() -> Result<KeystrokeStatus, String> {
+    let baseline = BASELINE.lock().unwrap();
+    Ok(KeystrokeStatus {
+        policy: POLICY.lock().unwrap().clone(),
+        samples: baseline.samples(),
+        learned: baseline.learned(),
+        mean_interval_ms: baseline.mean_ms(),
+        stddev_ms: baseline.stddev_ms(),
+    })
+}
+
+/// Choosing a different keyboard starts a new baseline.
+#[command]
+pub fn set_keystroke_baseline(policy: KeystrokePolicy) -> Result<(), String> {
+    if policy.enabled && policy.keyboard_instance_id.as_deref().and_then(parse_vid_pid).is_none() {
+        return Err("Choose the keyboard to learn".to_string());
+    }
+    let previous = POLICY.lock().unwrap().clone();
+    let same_keyboard = match (&previous.keyboard_instance_id, &policy.keyboard_instance_id) {
+        (Some(before), Some(after)) => before.eq_ignore_ascii_case(after),
+        (None, None) => true,
+        _ => false,
+    };
+    // A fresh baseline learns whatever is typed next, scripted or not
+    if previous.enabled && (!policy.enabled || !same_keyboard) {
+        hello::require_consent("turn off or retrain keystroke anomaly detection")?;
+    }
+    if !same_keyboard {
+        reset();
+    }
+    audit::record("keystroke_policy_changed", json!({ "previous": previous, "current": policy }));
+    *POLICY.lock().unwrap() = policy;
+    Ok(())
+}
+
+fn reset() {
+    let mut baseline = BASELINE.lock().unwrap();
+    *baseline = CadenceBaseline::default();
+    save(&baseline);
+    TRACKER.lock().unwrap().last_press = None;
+}
+
+#[command]
+pub fn reset_keystroke_baseline() -> Result<(), String> {
+    if BASELINE.lock().unwrap().learned() {
+        hello::require_consent("retrain keystroke anomaly detection")?;
+    }
+    reset();
+    audit::record("keystroke_baseline_reset", json!({}));
+    Ok(())
+}
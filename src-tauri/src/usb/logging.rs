@@ -0,0 +1,178 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::command;
+
+use super::audit;
+use super::usb_config;
+
+const LOG_FILE: &str = "usb-shield.log";
+// Rotated to usb-shield.log.1, .2, ... once it grows past this
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+const KEEP_ROTATED: usize = 3;
+// What the in-app viewer can page through without reading the files
+const RECENT_CAPACITY: usize = 2000;
+
+/// Ordered from most to least severe, like `log::Level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => LogLevel::Error,
+            Level::Warn => LogLevel::Warn,
+            Level::Info => LogLevel::Info,
+            Level::Debug => LogLevel::Debug,
+            Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// One line of the log file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub level: LogLevel,
+    /// Module path for `log` macros, `audit` for audited operations
+    pub target: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub fields: Value,
+}
+
+lazy_static! {
+    static ref LEVEL: Mutex<LogLevel> = Mutex::new(LogLevel::Info);
+    static ref RECENT: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+    // Serialises appends and rotation
+    static ref FILE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        LogLevel::from(metadata.level()) <= *LEVEL.lock().unwrap()
+    }
+
+    fn log(&self, record: &Record) {
+        write(record.level().into(), record.target(), &record.args().to_string(), Value::Null);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Route the `log` macros to the log file. Called from setup once the app
+/// data directory is known.
+pub fn init() -> Result<(), String> {
+    log::set_logger(&LOGGER).map_err(|e| format!("Failed to install the logger: {}", e))?;
+    log::set_max_level((*LEVEL.lock().unwrap()).into());
+    Ok(())
+}
+
+fn rotate() -> Result<(), String> {
+    let path = usb_config::data_file(LOG_FILE)?;
+    let size = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+    if size < MAX_FILE_BYTES {
+        return Ok(());
+    }
+    let rotated = |n: usize| usb_config::data_file(&format!("{}.{}", LOG_FILE, n));
+    let _ = fs::remove_file(rotated(KEEP_ROTATED)?);
+    for n in (1..KEEP_ROTATED).rev() {
+        let _ = fs::rename(rotated(n)?, rotated(n + 1)?);
+    }
+    fs::rename(&path, rotated(1)?).map_err(|e| format!("Failed to rotate {}: {}", path.display(), e))
+}
+
+fn append(entry: &LogEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let _guard = FILE_LOCK.lock().unwrap();
+    rotate()?;
+    let path = usb_config::data_file(LOG_FILE)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Log one entry with structured `fields`, if `level` is enabled. Errors and
+/// warnings still go to stderr as well, for runs from a console.
+pub fn write(level: LogLevel, target: &str, message: &str, fields: Value) {
+    if level > *LEVEL.lock().unwrap() {
+        return;
+    }
+    let entry = LogEntry {
+        timestamp: Utc::now(),
+        level,
+        target: target.to_string(),
+        message: message.to_string(),
+        fields,
+    };
+    if level <= LogLevel::Warn {
+        eprintln!("{}", entry.message);
+    }
+    if let Err(e) = append(&entry) {
+        eprintln!("Failed to write log entry: {}", e);
+    }
+    let mut recent = RECENT.lock().unwrap();
+    if recent.len() == RECENT_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(entry);
+}
+
+/// The last `n` entries of this session at `level` or more severe, oldest
+/// first. Older entries are in the log files.
+#[command]
+pub fn get_recent_logs(n: usize, level: Option<LogLevel>) -> Result<Vec<LogEntry>, String> {
+    let level = level.unwrap_or(LogLevel::Trace);
+    let recent = RECENT.lock().unwrap();
+    let mut entries: Vec<LogEntry> = recent.iter().rev().filter(|e| e.level <= level).take(n).cloned().collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+#[command]
+pub fn get_log_level() -> Result<LogLevel, String> {
+    Ok(*LEVEL.lock().unwrap())
+}
+
+/// Change how much is logged from now on. The audit log is not affected.
+#[command]
+pub fn set_log_level(level: LogLevel) -> Result<(), String> {
+    let previous = std::mem::replace(&mut *LEVEL.lock().unwrap(), level);
+    log::set_max_level(level.into());
+    audit::record("log_level_changed", json!({ "previous": previous, "current": level }));
+    Ok(())
+}
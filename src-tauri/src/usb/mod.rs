@@ -1,3 +1,86 @@
-mod usb_config;
-mod usb_control;
-pub mod commands;
\ No newline at end of file
+pub mod admin_pin;
+pub mod audit;
+pub mod backend;
+pub mod background;
+pub mod category;
+pub mod class_names;
+pub mod class_policy;
+mod correlation;
+pub mod deep_link;
+pub mod device_interfaces;
+pub mod device_labels;
+pub mod device_power;
+pub mod docks;
+mod dpapi;
+pub mod dry_run;
+pub mod drivers;
+pub mod eject;
+pub mod usb_config;
+pub mod usb_control;
+pub mod usb_names;
+pub mod commands;
+pub mod config_export;
+pub mod email_alerts;
+pub mod emergency;
+pub mod error;
+pub mod etw;
+pub mod event_log;
+pub mod events;
+pub mod exfiltration;
+pub mod forensics;
+pub mod gpo_policy;
+pub mod guest;
+pub mod hello;
+pub mod hid_quarantine;
+pub mod hotplug;
+mod helper_client;
+pub mod hub_policy;
+pub mod idle;
+pub mod inventory;
+pub mod keyboard_lockdown;
+pub mod keystrokes;
+pub mod logging;
+pub mod network;
+pub mod network_adapters;
+pub mod notifications;
+pub mod operations;
+pub mod paging;
+pub mod pause;
+pub mod policy_diff;
+pub mod port_locks;
+pub mod port_power;
+pub mod power;
+pub mod privilege;
+pub mod profiles;
+pub mod protection_schedule;
+pub mod quarantine;
+pub mod quota;
+pub mod reblock;
+pub mod remote;
+pub mod rollback;
+pub mod scheduler;
+pub mod security_key;
+pub mod self_test;
+pub mod siem;
+mod serial_ports;
+mod service_control;
+mod signing;
+pub mod simulation;
+pub mod smartcard;
+pub mod status;
+pub mod storage_readonly;
+pub mod suggestions;
+pub mod support_bundle;
+pub mod tamper;
+pub mod threats;
+pub mod transfers;
+pub mod tray;
+pub mod type_c;
+pub mod uninstall;
+pub mod trust_rules;
+pub mod trust_share;
+pub mod verification;
+pub mod volumes;
+pub mod vpn;
+pub mod webhooks;
+pub mod wireless;
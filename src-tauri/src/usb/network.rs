@@ -0,0 +1,195 @@
+use std::{
+    net::{Ipv4Addr, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::Win32::{
+    NetworkManagement::{
+        IpHelper::{GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_MULTICAST, IP_ADAPTER_ADDRESSES_LH},
+        Ndis::IfOperStatusUp,
+    },
+    Networking::WinSock::{AF_INET, AF_UNSPEC, SOCKADDR_IN},
+};
+
+use super::audit;
+use super::profiles::{self, Profile};
+
+const SOURCE: &str = "network";
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkLocation {
+    Corporate,
+    Other,
+    /// Detection is disabled or nothing is configured
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkPolicy {
+    pub enabled: bool,
+    /// IPv4 CIDRs such as `10.20.0.0/16`; any up adapter inside one counts as corporate
+    pub corporate_subnets: Vec<String>,
+    /// `host:port` endpoints only reachable from inside, e.g. `dc01.corp.local:389`
+    pub corporate_hosts: Vec<String>,
+    pub corporate_profile: Profile,
+    pub other_profile: Profile,
+}
+
+/// A network adapter that is up, with its IPv4 addresses.
+#[derive(Debug, Clone, Serialize)]
+pub struct Adapter {
+    pub name: String,
+    pub description: String,
+    pub if_type: u32,
+    pub ipv4: Vec<Ipv4Addr>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<NetworkPolicy> = Mutex::new(NetworkPolicy {
+        enabled: false,
+        corporate_subnets: Vec::new(),
+        corporate_hosts: Vec::new(),
+        corporate_profile: Profile::Standard,
+        other_profile: Profile::Strict,
+    });
+    static ref LOCATION: Mutex<NetworkLocation> = Mutex::new(NetworkLocation::Unknown);
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        evaluate();
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+pub fn location() -> NetworkLocation {
+    *LOCATION.lock().unwrap()
+}
+
+fn evaluate() {
+    let policy = POLICY.lock().unwrap().clone();
+    let location = detect(&policy);
+    *LOCATION.lock().unwrap() = location;
+
+    let profile = match location {
+        NetworkLocation::Corporate => Some(policy.corporate_profile),
+        NetworkLocation::Other => Some(policy.other_profile),
+        NetworkLocation::Unknown => None,
+    };
+    profiles::request(SOURCE, profile);
+}
+
+fn detect(policy: &NetworkPolicy) -> NetworkLocation {
+    if !policy.enabled || (policy.corporate_subnets.is_empty() && policy.corporate_hosts.is_empty()) {
+        return NetworkLocation::Unknown;
+    }
+
+    let subnets: Vec<(Ipv4Addr, u32)> = policy.corporate_subnets.iter().filter_map(|cidr| parse_cidr(cidr)).collect();
+    let in_subnet = up_adapters()
+        .iter()
+        .flat_map(|adapter| adapter.ipv4.iter())
+        .any(|ip| subnets.iter().any(|(net, prefix)| in_cidr(*ip, *net, *prefix)));
+    if in_subnet || policy.corporate_hosts.iter().any(|host| reachable(host)) {
+        NetworkLocation::Corporate
+    } else {
+        NetworkLocation::Other
+    }
+}
+
+fn reachable(endpoint: &str) -> bool {
+    endpoint
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.any(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok()))
+        .unwrap_or(false)
+}
+
+pub fn parse_cidr(cidr: &str) -> Option<(Ipv4Addr, u32)> {
+    let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr, "32"));
+    let prefix: u32 = prefix.trim().parse().ok().filter(|p| *p <= 32)?;
+    Some((addr.trim().parse().ok()?, prefix))
+}
+
+pub fn in_cidr(ip: Ipv4Addr, network: Ipv4Addr, prefix: u32) -> bool {
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    (u32::from(ip) & mask) == (u32::from(network) & mask)
+}
+
+/// Every adapter whose operational status is up.
+pub fn up_adapters() -> Vec<Adapter> {
+    let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST;
+    let mut size: u32 = 16 * 1024;
+    let mut buffer: Vec<u8>;
+    unsafe {
+        loop {
+            buffer = vec![0u8; size as usize];
+            let status = GetAdaptersAddresses(
+                AF_UNSPEC.0 as u32,
+                flags,
+                None,
+                Some(buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH),
+                &mut size,
+            );
+            match status {
+                0 => break,
+                // ERROR_BUFFER_OVERFLOW: `size` now holds what's needed
+                111 => continue,
+                _ => return Vec::new(),
+            }
+        }
+
+        let mut adapters = Vec::new();
+        let mut current = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+        while !current.is_null() {
+            let adapter = &*current;
+            if adapter.OperStatus == IfOperStatusUp {
+                let mut ipv4 = Vec::new();
+                let mut unicast = adapter.FirstUnicastAddress;
+                while !unicast.is_null() {
+                    let sockaddr = (*unicast).Address.lpSockaddr;
+                    if !sockaddr.is_null() && (*sockaddr).sa_family == AF_INET {
+                        let sin = &*(sockaddr as *const SOCKADDR_IN);
+                        ipv4.push(Ipv4Addr::from(u32::from_be(sin.sin_addr.S_un.S_addr)));
+                    }
+                    unicast = (*unicast).Next;
+                }
+                adapters.push(Adapter {
+                    name: adapter.FriendlyName.to_string().unwrap_or_default(),
+                    description: adapter.Description.to_string().unwrap_or_default(),
+                    if_type: adapter.IfType,
+                    ipv4,
+                });
+            }
+            current = adapter.Next;
+        }
+        adapters
+    }
+}
+
+#[command]
+pub fn get_network_policy() -> Result<NetworkPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_network_policy(policy: NetworkPolicy) -> Result<(), String> {
+    if let Some(bad) = policy.corporate_subnets.iter().find(|cidr| parse_cidr(cidr).is_none()) {
+        return Err(format!("Invalid subnet: {}", bad));
+    }
+    audit::record("network_policy_changed", json!(policy));
+    *POLICY.lock().unwrap() = policy;
+    // Re-evaluate off the IPC thread; host probes can take seconds
+    thread::spawn(evaluate);
+    Ok(())
+}
+
+#[command]
+pub fn get_network_location() -> Result<NetworkLocation, String> {
+    Ok(location())
+}
@@ -0,0 +1,181 @@
+use std::{fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::category::{self, DeviceCategory, InterfaceClass, CLASS_CDC};
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::usb_control::BlockReason;
+
+const CDC_MBIM: u8 = 0x0E;
+const NETWORK_ADAPTERS_FILE: &str = "network-adapters.json";
+// Vendor-class NICs (ASIX, Realtek) declare class FF and are only known as
+// network adapters by the Net-class driver they load
+const NET_CLASS_GUID: &str = "{4d36e972-e325-11ce-bfc1-08002be10318}";
+
+/// Whether wired USB network adapters are blocked on sight. Kept apart from
+/// the wireless policy: a LAN Turtle looks like any USB NIC, and a trusted
+/// VID/PID proves nothing when it can be spoofed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkAdapterPolicy {
+    pub block: bool,
+    /// Container IDs of docks whose built-in Ethernet stays allowed
+    pub allowed_docks: Vec<String>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<NetworkAdapterPolicy> = Mutex::new(NetworkAdapterPolicy::default());
+}
+
+fn read_policy() -> Result<NetworkAdapterPolicy, String> {
+    let path = usb_config::data_file(NETWORK_ADAPTERS_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(NetworkAdapterPolicy::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_policy(policy: &NetworkAdapterPolicy) -> Result<(), String> {
+    let path = usb_config::data_file(NETWORK_ADAPTERS_FILE)?;
+    let data = serde_json::to_vec_pretty(policy).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Restore the saved policy at startup.
+pub fn load() -> Result<(), String> {
+    *POLICY.lock().unwrap() = read_policy()?;
+    Ok(())
+}
+
+/// CDC-ECM, CDC-EEM, CDC-NCM or RNDIS. MBIM is left to the modem policy.
+pub fn is_network_adapter(interfaces: &[InterfaceClass]) -> bool {
+    interfaces
+        .iter()
+        .any(|i| category::is_network_interface(i) && !(i.class_code == CLASS_CDC && i.sub_class_code == CDC_MBIM))
+}
+
+// A dock's own NIC carries the dock's container ID; an adapter plugged into
+// one of its ports has a container of its own
+fn allowed(policy: &NetworkAdapterPolicy, device: &UsbDeviceInfo) -> bool {
+    device.container_id().is_some_and(|container| {
+        policy.allowed_docks.iter().any(|dock| dock.eq_ignore_ascii_case(container))
+    })
+}
+
+// Wi-Fi adapters and modems load Net-class drivers too; the wireless
+// policy decides about those
+fn is_network_device(device: &UsbDeviceInfo) -> bool {
+    if is_network_adapter(device.interfaces()) {
+        return true;
+    }
+    let wireless = matches!(device.category(), DeviceCategory::WifiAdapter | DeviceCategory::CellularModem)
+        || device.interfaces().iter().any(|i| i.class_code == CLASS_CDC && i.sub_class_code == CDC_MBIM);
+    !wireless && device.setup_classes().any(|class| class.eq_ignore_ascii_case(NET_CLASS_GUID))
+}
+
+fn should_block(policy: &NetworkAdapterPolicy, device: &UsbDeviceInfo) -> bool {
+    policy.block && device.state() == DeviceState::Connected && is_network_device(device) && !allowed(policy, device)
+}
+
+fn block(device: &UsbDeviceInfo) -> bool {
+    let result = commands::block_device_for(
+        device.vendor_id(),
+        device.product_id(),
+        None,
+        device.instance_id().map(str::to_string),
+        BlockReason::NetworkAdapterPolicy,
+    );
+    audit::record(
+        "network_adapter_blocked",
+        json!({
+            "instance_id": device.instance_id(),
+            "vendor_id": device.vendor_id(),
+            "product_id": device.product_id(),
+            "error": result.as_ref().err(),
+        }),
+    );
+    notifications::notify(
+        Severity::Warning,
+        "network_adapter_blocked",
+        "USB network adapter blocked",
+        &format!(
+            "{} could have redirected this computer's network traffic.",
+            device.product().unwrap_or("A USB network adapter")
+        ),
+    );
+    result.is_ok()
+}
+
+/// Block a network adapter that just arrived, trusted or not, unless it is
+/// part of an allowed dock. Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    if !should_block(&POLICY.lock().unwrap(), device) {
+        return false;
+    }
+    block(device)
+}
+
+#[command]
+pub fn get_network_adapter_policy() -> Result<NetworkAdapterPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+/// Turn blocking of USB network adapters on or off. Turning it on also
+/// blocks the adapters attached now; turning it off needs consent and
+/// leaves them blocked.
+#[command]
+pub fn set_block_usb_network_adapters(enabled: bool) -> Result<NetworkAdapterPolicy, String> {
+    let previous = POLICY.lock().unwrap().block;
+    if previous && !enabled {
+        hello::require_consent("allow USB network adapters")?;
+    }
+    {
+        let mut policy = POLICY.lock().unwrap();
+        let mut updated = policy.clone();
+        updated.block = enabled;
+        save_policy(&updated)?;
+        *policy = updated;
+    }
+    audit::record("network_adapter_policy_changed", json!({ "block": enabled }));
+
+    if enabled && !previous {
+        let policy = POLICY.lock().unwrap().clone();
+        for device in commands::get_usb_devices()? {
+            if should_block(&policy, &device) {
+                block(&device);
+            }
+        }
+    }
+    get_network_adapter_policy()
+}
+
+/// Allow the built-in network adapter of a dock, by the dock's container
+/// ID, or stop allowing it. Allowing needs consent.
+#[command]
+pub fn set_network_adapter_dock_allowed(container_id: String, allowed: bool) -> Result<NetworkAdapterPolicy, String> {
+    if allowed {
+        hello::require_consent("allow a dock's network adapter")?;
+    }
+    let policy = {
+        let mut policy = POLICY.lock().unwrap();
+        let mut updated = policy.clone();
+        updated.allowed_docks.retain(|dock| !dock.eq_ignore_ascii_case(&container_id));
+        if allowed {
+            updated.allowed_docks.push(container_id.clone());
+        }
+        save_policy(&updated)?;
+        *policy = updated.clone();
+        updated
+    };
+    audit::record(
+        "network_adapter_dock_changed",
+        json!({ "container_id": container_id, "allowed": allowed }),
+    );
+    Ok(policy)
+}
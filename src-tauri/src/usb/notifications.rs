@@ -0,0 +1,304 @@
+use std::{collections::BTreeMap, fs, sync::Mutex};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::email_alerts;
+use super::events;
+use super::hello;
+use super::profiles::{self, Profile};
+use super::siem;
+use super::tray;
+use super::usb_config;
+use super::webhooks;
+
+pub const EVENT_NOTIFICATION: &str = "notification://toast";
+
+const NOTIFICATIONS_FILE: &str = "notifications.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Channel {
+    Toast,
+    Email,
+    Webhook,
+    Syslog,
+}
+
+/// Which channels fire under one profile: each channel with the lowest
+/// severity it is sent at. Channels not listed stay silent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileRoutes {
+    pub profile: Profile,
+    pub channels: BTreeMap<Channel, Severity>,
+}
+
+/// Device events that raise a native toast, each of which can be turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeviceEvent {
+    Connected,
+    Blocked,
+    Quarantined,
+    ThreatDetected,
+}
+
+impl DeviceEvent {
+    fn of(kind: &str) -> Option<Self> {
+        match kind {
+            "device_connected" | "wireless_device_connected" => Some(DeviceEvent::Connected),
+            "device_autoblocked"
+            | "hub_blocked"
+            | "keyboard_lockdown_blocked"
+            | "network_adapter_blocked"
+            | "class_policy_blocked"
+            | "port_lock_enforced"
+            | "preblocked_device_blocked"
+            | "wireless_device_blocked" => Some(DeviceEvent::Blocked),
+            "device_quarantined" | "hid_quarantined" => Some(DeviceEvent::Quarantined),
+            "threat_device_detected" => Some(DeviceEvent::ThreatDetected),
+            _ => None,
+        }
+    }
+}
+
+/// Which device events show a toast. Other channels are routed as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    pub connected: bool,
+    pub blocked: bool,
+    pub quarantined: bool,
+    pub threat_detected: bool,
+}
+
+impl NotificationPreferences {
+    fn allows(&self, event: DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::Connected => self.connected,
+            DeviceEvent::Blocked => self.blocked,
+            DeviceEvent::Quarantined => self.quarantined,
+            DeviceEvent::ThreatDetected => self.threat_detected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub severity: Severity,
+    /// Short machine-readable kind, e.g. `"exfiltration_alert"`
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub channel: Channel,
+    pub error: Option<String>,
+}
+
+// Saved together, so a silenced route or toast stays silenced after a restart
+#[derive(Serialize, Deserialize)]
+struct StoredSettings {
+    routes: Vec<ProfileRoutes>,
+    preferences: NotificationPreferences,
+}
+
+// Lock ROUTES before PREFERENCES when both are needed
+lazy_static! {
+    static ref ROUTES: Mutex<Vec<ProfileRoutes>> = Mutex::new(
+        [Profile::Standard, Profile::Strict, Profile::Lockdown]
+            .into_iter()
+            .map(|profile| ProfileRoutes {
+                profile,
+                channels: BTreeMap::from([(Channel::Toast, Severity::Info)]),
+            })
+            .collect()
+    );
+    static ref PREFERENCES: Mutex<NotificationPreferences> = Mutex::new(NotificationPreferences {
+        connected: true,
+        blocked: true,
+        quarantined: true,
+        threat_detected: true,
+    });
+}
+
+fn save(routes: &[ProfileRoutes], preferences: NotificationPreferences) -> Result<(), String> {
+    let stored = StoredSettings {
+        routes: routes.to_vec(),
+        preferences,
+    };
+    let path = usb_config::data_file(NOTIFICATIONS_FILE)?;
+    let data = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Replace the routes and toast preferences with the saved ones. Called
+/// from setup; without a file the defaults stay.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(NOTIFICATIONS_FILE)?;
+    let stored: StoredSettings = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let mut routes = ROUTES.lock().unwrap();
+    *routes = stored.routes;
+    *PREFERENCES.lock().unwrap() = stored.preferences;
+    Ok(())
+}
+
+/// Channels a notification of `severity` goes to under `profile`.
+pub fn channels_for(profile: Profile, severity: Severity) -> Vec<Channel> {
+    ROUTES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|routes| routes.profile == profile)
+        .map(|routes| {
+            routes
+                .channels
+                .iter()
+                .filter(|(_, minimum)| severity >= **minimum)
+                .map(|(channel, _)| *channel)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn deliver(channel: Channel, notification: &Notification) -> Result<(), String> {
+    match channel {
+        // The main window renders these; Windows does while it is hidden,
+        // and always for device events, which are worth the interruption
+        Channel::Toast => {
+            events::emit(EVENT_NOTIFICATION, notification.clone());
+            if DeviceEvent::of(&notification.kind).is_some() || !tray::dashboard_visible() {
+                tray::balloon(&notification.title, &notification.body);
+            }
+            Ok(())
+        }
+        Channel::Email => email_alerts::queue(&notification.title, &notification.body),
+        Channel::Syslog => siem::send_notification(&notification.kind, json!(notification)),
+        Channel::Webhook => webhooks::send_notification(&notification.title, &json!(notification)),
+    }
+}
+
+/// Send a notification to every channel routed for its severity under the
+/// active profile. Delivery failures are audited, never returned: alerting
+/// must not get in the way of enforcement.
+pub fn notify(severity: Severity, kind: &str, title: &str, body: &str) -> Vec<Delivery> {
+    let notification = Notification {
+        severity,
+        kind: kind.to_string(),
+        title: title.to_string(),
+        body: body.to_string(),
+        at: Utc::now(),
+    };
+    let toast = DeviceEvent::of(kind).is_none_or(|event| PREFERENCES.lock().unwrap().allows(event));
+    let deliveries: Vec<Delivery> = channels_for(profiles::active(), severity)
+        .into_iter()
+        .filter(|channel| *channel != Channel::Toast || toast)
+        .map(|channel| Delivery {
+            channel,
+            error: deliver(channel, &notification).err(),
+        })
+        .collect();
+    let failed: Vec<&Delivery> = deliveries.iter().filter(|d| d.error.is_some()).collect();
+    if !failed.is_empty() {
+        audit::record("notification_failed", json!({ "kind": kind, "deliveries": failed }));
+    }
+    deliveries
+}
+
+#[command]
+pub fn get_notification_settings() -> Result<Vec<ProfileRoutes>, String> {
+    Ok(ROUTES.lock().unwrap().clone())
+}
+
+/// Route `channel` under `profile` from `min_severity` up, or silence it with `None`.
+#[command]
+pub fn set_notification_route(profile: Profile, channel: Channel, min_severity: Option<Severity>) -> Result<(), String> {
+    let previous = {
+        let routes = ROUTES.lock().unwrap();
+        routes
+            .iter()
+            .find(|routes| routes.profile == profile)
+            .and_then(|routes| routes.channels.get(&channel).copied())
+    };
+    let quieter = match (previous, min_severity) {
+        (Some(_), None) => true,
+        (Some(before), Some(after)) => after > before,
+        (None, _) => false,
+    };
+    if quieter {
+        hello::require_consent("silence security notifications")?;
+    }
+
+    {
+        let mut routes = ROUTES.lock().unwrap();
+        let mut updated = routes.clone();
+        let index = match updated.iter().position(|routes| routes.profile == profile) {
+            Some(index) => index,
+            None => {
+                updated.push(ProfileRoutes {
+                    profile,
+                    channels: BTreeMap::new(),
+                });
+                updated.len() - 1
+            }
+        };
+        match min_severity {
+            Some(severity) => updated[index].channels.insert(channel, severity),
+            None => updated[index].channels.remove(&channel),
+        };
+        save(&updated, *PREFERENCES.lock().unwrap())?;
+        *routes = updated;
+    }
+    audit::record(
+        "notification_route_changed",
+        json!({ "profile": profile, "channel": channel, "previous": previous, "current": min_severity }),
+    );
+    Ok(())
+}
+
+#[command]
+pub fn get_notification_preferences() -> Result<NotificationPreferences, String> {
+    Ok(*PREFERENCES.lock().unwrap())
+}
+
+/// Turn toasts for each kind of device event on or off. Turning one off
+/// needs consent; email, webhook and syslog routes are not affected.
+#[command]
+pub fn set_notification_preferences(preferences: NotificationPreferences) -> Result<(), String> {
+    let previous = *PREFERENCES.lock().unwrap();
+    let silenced = [
+        DeviceEvent::Connected,
+        DeviceEvent::Blocked,
+        DeviceEvent::Quarantined,
+        DeviceEvent::ThreatDetected,
+    ]
+    .into_iter()
+    .any(|event| previous.allows(event) && !preferences.allows(event));
+    if silenced {
+        hello::require_consent("silence device notifications")?;
+    }
+    {
+        let routes = ROUTES.lock().unwrap();
+        save(&routes, preferences)?;
+        *PREFERENCES.lock().unwrap() = preferences;
+    }
+    audit::record(
+        "notification_preferences_changed",
+        json!({ "previous": previous, "current": preferences }),
+    );
+    Ok(())
+}
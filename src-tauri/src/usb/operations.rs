@@ -0,0 +1,84 @@
+use std::{
+    cell::Cell,
+    fmt::Display,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use serde::Serialize;
+
+use super::events;
+
+pub const EVENT_OPERATION_PROGRESS: &str = "operation://progress";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A stage reached by a long-running command, so the UI can show a spinner
+/// with what is happening instead of a frozen window.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationProgress {
+    /// Distinguishes two runs of the same command
+    pub id: u64,
+    /// The command, e.g. `block_all_usb_ports`
+    pub operation: String,
+    pub stage: String,
+    /// Last event of the run
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+thread_local! {
+    // The operation running on this pool thread, if any
+    static CURRENT: Cell<Option<(u64, &'static str)>> = const { Cell::new(None) };
+}
+
+// Clears the thread's operation even if the work panics, since pool threads are reused
+struct Running;
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(None));
+    }
+}
+
+fn emit(id: u64, operation: &str, stage: &str, done: bool, error: Option<String>) {
+    events::emit(
+        EVENT_OPERATION_PROGRESS,
+        OperationProgress {
+            id,
+            operation: operation.to_string(),
+            stage: stage.to_string(),
+            done,
+            error,
+        },
+    );
+}
+
+/// Report that the operation on this thread reached `stage`. A no-op when
+/// the work was not started through `run`, e.g. a profile change blocking
+/// the ports on its own.
+pub fn stage(stage: &str) {
+    if let Some((id, operation)) = CURRENT.with(Cell::get) {
+        emit(id, operation, stage, false, None);
+    }
+}
+
+/// Run `work` on the blocking task pool, keeping the IPC thread free, and
+/// report its stages and outcome as `operation://progress` events.
+pub async fn run<T, E>(operation: &'static str, work: impl FnOnce() -> Result<T, E> + Send + 'static) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: Display + From<String> + Send + 'static,
+{
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    tauri::async_runtime::spawn_blocking(move || {
+        CURRENT.with(|current| current.set(Some((id, operation))));
+        let running = Running;
+        emit(id, operation, "started", false, None);
+        let result = work();
+        drop(running);
+        let error = result.as_ref().err().map(ToString::to_string);
+        emit(id, operation, if error.is_some() { "failed" } else { "finished" }, true, error);
+        result
+    })
+    .await
+    .unwrap_or_else(|e| Err(E::from(format!("{} did not complete: {}", operation, e))))
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Offset pagination for list queries. Omitted fields give the first page
+/// of `DEFAULT_PAGE_SIZE` items.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Matching items before paging
+    pub total: usize,
+    pub offset: usize,
+    /// Where the next page starts; `None` on the last page
+    pub next_offset: Option<usize>,
+}
+
+impl PageRequest {
+    /// Cut one page out of the full, already sorted result.
+    pub fn apply<T>(&self, items: Vec<T>) -> Page<T> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let total = items.len();
+        let items: Vec<T> = items.into_iter().skip(self.offset).take(limit).collect();
+        let end = self.offset + items.len();
+        Page {
+            items,
+            total,
+            offset: self.offset,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+}
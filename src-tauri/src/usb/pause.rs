@@ -0,0 +1,134 @@
+use std::{sync::Mutex, time::Duration};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::email_alerts;
+use super::error::UsbShieldError;
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::reblock::{self, ReblockTarget};
+use super::security_key;
+use super::usb_control::{self, BlockReason};
+
+pub const EVENT_PROTECTION_PAUSED: &str = "usb://protection-paused";
+
+/// Autoblock held off for a while. Arrivals during the pause are let in and
+/// blocked again when it ends, unless they were trusted in the meantime.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectionPause {
+    pub paused_at: DateTime<Utc>,
+    pub resumes_at: DateTime<Utc>,
+    /// Untrusted devices that arrived while paused
+    pub admitted: Vec<String>,
+    /// Pending re-block that ends the pause
+    pub reblock_id: u64,
+}
+
+lazy_static! {
+    static ref PAUSE: Mutex<Option<ProtectionPause>> = Mutex::new(None);
+}
+
+/// Whether `instance_id` arrived during the running pause and was let in.
+pub fn admitted(instance_id: &str) -> bool {
+    PAUSE.lock().unwrap().as_ref().is_some_and(|pause| {
+        pause.admitted.iter().any(|admitted| admitted.eq_ignore_ascii_case(instance_id))
+    })
+}
+
+/// Let an untrusted arrival through while protection is paused, remembering
+/// it for the resume. Returns whether the device was let in.
+pub fn admit(device: &UsbDeviceInfo) -> bool {
+    let mut guard = PAUSE.lock().unwrap();
+    let pause = match guard.as_mut() {
+        Some(pause) => pause,
+        None => return false,
+    };
+    if let Some(instance_id) = device.instance_id() {
+        pause.admitted.push(instance_id.to_string());
+    }
+    true
+}
+
+/// End the pause: block what came in during it and is still untrusted.
+/// Run by the re-block ticker when time runs out.
+pub fn end() -> Result<Vec<String>, String> {
+    let pause = PAUSE
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "Protection is not paused".to_string())?;
+
+    let mut errors = Vec::new();
+    let attached = commands::get_usb_devices()?;
+    for device in attached
+        .iter()
+        .filter(|device| device.state() == DeviceState::Connected && !device.trusted())
+    {
+        let instance_id = match device.instance_id() {
+            Some(instance_id) if pause.admitted.iter().any(|id| id.eq_ignore_ascii_case(instance_id)) => instance_id,
+            _ => continue,
+        };
+        if let Err(e) = usb_control::block(instance_id, BlockReason::TimedReblock) {
+            errors.push(format!("{}: {}", instance_id, e));
+        }
+    }
+    audit::record("protection_resumed", json!({ "pause": pause, "errors": errors }));
+    events::emit(EVENT_PROTECTION_PAUSED, None::<ProtectionPause>);
+    notifications::notify(
+        Severity::Info,
+        "protection_resumed",
+        "USB protection resumed",
+        "Untrusted devices plugged in during the pause are blocked again.",
+    );
+    Ok(errors)
+}
+
+#[command]
+pub fn get_protection_pause() -> Result<Option<ProtectionPause>, String> {
+    Ok(PAUSE.lock().unwrap().clone())
+}
+
+/// Stop autoblocking for `duration_secs`, e.g. to plug in a projector
+/// without trusting it. Guarded like turning autoblock off.
+#[command]
+pub fn pause_protection(duration_secs: u64, admin_token: Option<String>) -> Result<ProtectionPause, UsbShieldError> {
+    if duration_secs == 0 {
+        return Err("Duration must be greater than zero".to_string().into());
+    }
+    admin_pin::require_admin(admin_token.as_deref(), "pause protection")?;
+    security_key::require_presence("pause protection")?;
+    hello::require_consent("pause protection")?;
+
+    let mut guard = PAUSE.lock().unwrap();
+    let paused_at = Utc::now();
+    // Pausing again only moves the deadline
+    let admitted = guard.take().map(|pause| pause.admitted).unwrap_or_default();
+    let pause = ProtectionPause {
+        paused_at,
+        resumes_at: paused_at + chrono::Duration::seconds(duration_secs as i64),
+        admitted,
+        reblock_id: reblock::schedule(ReblockTarget::Pause, Duration::from_secs(duration_secs)),
+    };
+    *guard = Some(pause.clone());
+    drop(guard);
+    audit::record("protection_paused", json!({ "duration_secs": duration_secs, "resumes_at": pause.resumes_at }));
+    events::emit(EVENT_PROTECTION_PAUSED, Some(pause.clone()));
+    email_alerts::protection_disabled(&format!("protection was paused until {}", pause.resumes_at));
+    Ok(pause)
+}
+
+/// Resume protection before the pause is up.
+#[command]
+pub fn resume_protection() -> Result<Vec<String>, String> {
+    if let Some(pause) = PAUSE.lock().unwrap().as_ref() {
+        reblock::cancel(pause.reblock_id);
+    }
+    end()
+}
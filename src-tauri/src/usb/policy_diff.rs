@@ -0,0 +1,192 @@
+use std::fs;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::command;
+
+use super::config_export;
+use super::support_bundle;
+
+// Fields that identify a rule in a list, so an edited rule shows up as
+// modified rather than as one removed and one added
+const RULE_KEYS: &[&str] = &[
+    "vendor_id",
+    "product_id",
+    "class_code",
+    "container_id",
+    "location_path",
+    "mount_point",
+    "name",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyChange {
+    /// Top-level policy section, e.g. `"hub_policy"`
+    pub section: String,
+    /// Where in the section, e.g. `"hub_policy.allowed[vendor_id=1507]"`
+    pub path: String,
+    pub kind: ChangeKind,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// What applying `target` on top of `base` would change.
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyChangeset {
+    /// File path, or `"live"` for the policy in force
+    pub base: String,
+    pub target: String,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub changes: Vec<PolicyChange>,
+}
+
+fn read_policy(path: &str) -> Result<Value, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let policy: Value = serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    if policy.is_object() {
+        Ok(policy)
+    } else {
+        Err(format!("{} is not a policy file", path))
+    }
+}
+
+fn rule_key(rule: &Value) -> Option<String> {
+    let rule = rule.as_object()?;
+    let parts: Vec<String> = RULE_KEYS
+        .iter()
+        .filter_map(|key| rule.get(*key).filter(|value| !value.is_null()).map(|value| format!("{}={}", key, value)))
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(","))
+    }
+}
+
+struct Differ {
+    changes: Vec<PolicyChange>,
+}
+
+impl Differ {
+    fn push(&mut self, path: String, kind: ChangeKind, before: Option<&Value>, after: Option<&Value>) {
+        let section = path.split(['.', '[']).next().unwrap_or_default().to_string();
+        self.changes.push(PolicyChange {
+            section,
+            path,
+            kind,
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+
+    fn value(&mut self, path: String, before: &Value, after: &Value) {
+        match (before, after) {
+            (Value::Object(before), Value::Object(after)) => self.object(&path, before, after),
+            (Value::Array(before), Value::Array(after)) => self.rules(&path, before, after),
+            _ if before != after => self.push(path, ChangeKind::Modified, Some(before), Some(after)),
+            _ => {}
+        }
+    }
+
+    fn object(&mut self, path: &str, before: &Map<String, Value>, after: &Map<String, Value>) {
+        let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+        for (key, old) in before {
+            match after.get(key) {
+                Some(new) => self.value(join(key), old, new),
+                None => self.push(join(key), ChangeKind::Removed, Some(old), None),
+            }
+        }
+        for (key, new) in after.iter().filter(|(key, _)| !before.contains_key(*key)) {
+            self.push(join(key), ChangeKind::Added, None, Some(new));
+        }
+    }
+
+    /// Lists are compared as sets of rules: matched by their identifying
+    /// fields when every rule has them, else by value, so order never counts.
+    fn rules(&mut self, path: &str, before: &[Value], after: &[Value]) {
+        let keyed = |rules: &[Value]| -> Option<Vec<(String, Value)>> {
+            let keyed: Vec<(String, Value)> = rules
+                .iter()
+                .map(|rule| rule_key(rule).map(|key| (key, rule.clone())))
+                .collect::<Option<_>>()?;
+            let unique = keyed.iter().enumerate().all(|(i, (key, _))| keyed[..i].iter().all(|(k, _)| k != key));
+            unique.then_some(keyed)
+        };
+        match (keyed(before), keyed(after)) {
+            (Some(before), Some(after)) => {
+                for (key, old) in &before {
+                    let rule_path = format!("{}[{}]", path, key);
+                    match after.iter().find(|(k, _)| k == key) {
+                        Some((_, new)) => self.value(rule_path, old, new),
+                        None => self.push(rule_path, ChangeKind::Removed, Some(old), None),
+                    }
+                }
+                for (key, new) in after.iter().filter(|(key, _)| !before.iter().any(|(k, _)| k == key)) {
+                    self.push(format!("{}[{}]", path, key), ChangeKind::Added, None, Some(new));
+                }
+            }
+            _ => {
+                for old in before.iter().filter(|rule| !after.contains(rule)) {
+                    self.push(format!("{}[{}]", path, old), ChangeKind::Removed, Some(old), None);
+                }
+                for new in after.iter().filter(|rule| !before.contains(rule)) {
+                    self.push(format!("{}[{}]", path, new), ChangeKind::Added, None, Some(new));
+                }
+            }
+        }
+    }
+}
+
+/// Structured changes from `before` to `after`.
+pub fn diff(before: &Value, after: &Value) -> Vec<PolicyChange> {
+    let mut differ = Differ { changes: Vec::new() };
+    differ.value(String::new(), before, after);
+    differ.changes
+}
+
+/// Compare two exported policy files (`policy.json` from a support bundle,
+/// or two `export_config` files), or the live policy against one when `base`
+/// is unset, so a push can be reviewed before it is applied. The live side
+/// takes the shape of the target. Nothing is changed.
+#[command]
+pub fn diff_policies(base: Option<String>, target: String) -> Result<PolicyChangeset, String> {
+    let after = read_policy(&target)?;
+    let (before, after) = match config_export::settings_of(&after) {
+        Some(after) => {
+            let before = match &base {
+                Some(path) => config_export::settings_of(&read_policy(path)?)
+                    .ok_or_else(|| format!("{} is not a configuration export like {}", path, target))?,
+                None => config_export::live_settings()?,
+            };
+            (before, after)
+        }
+        None => {
+            let before = match &base {
+                Some(path) => read_policy(path)?,
+                None => support_bundle::policy(),
+            };
+            if let Some(path) = base.as_ref().filter(|_| config_export::settings_of(&before).is_some()) {
+                return Err(format!("{} is a configuration export; {} is not", path, target));
+            }
+            (before, after)
+        }
+    };
+    let changes = diff(&before, &after);
+    let count = |kind: ChangeKind| changes.iter().filter(|change| change.kind == kind).count();
+    Ok(PolicyChangeset {
+        base: base.unwrap_or_else(|| "live".to_string()),
+        target,
+        added: count(ChangeKind::Added),
+        removed: count(ChangeKind::Removed),
+        modified: count(ChangeKind::Modified),
+        changes,
+    })
+}
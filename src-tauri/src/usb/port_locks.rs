@@ -0,0 +1,242 @@
+use std::{fs, sync::Mutex};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::commands::{DeviceState, UsbDeviceInfo};
+use super::correlation::{self, DevNode};
+use super::dry_run;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const PORT_LOCKS_FILE: &str = "port-locks.json";
+
+/// A physical port kept locked whatever is plugged into it, including hubs
+/// and everything behind them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortLock {
+    pub location_path: String,
+    /// The hub the port belongs to; `None` for root ports
+    pub hub_instance_id: Option<String>,
+    pub port: Option<u8>,
+    /// The hub switched the port off. Otherwise whatever arrives on it is
+    /// disabled instead.
+    pub power_cut: bool,
+    pub since: DateTime<Utc>,
+}
+
+lazy_static! {
+    static ref LOCKS: Mutex<Vec<PortLock>> = Mutex::new(Vec::new());
+}
+
+// The locked port itself, or a port on a hub plugged into it
+fn covers(lock: &str, location_path: &str) -> bool {
+    let lock = lock.to_ascii_uppercase();
+    let location_path = location_path.to_ascii_uppercase();
+    location_path == lock || location_path.starts_with(&format!("{}#", lock))
+}
+
+fn locking(location_path: &str) -> Option<PortLock> {
+    LOCKS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|lock| covers(&lock.location_path, location_path))
+        .cloned()
+}
+
+fn read_locks() -> Result<Vec<PortLock>, String> {
+    let path = usb_config::data_file(PORT_LOCKS_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_locks(locks: &[PortLock]) -> Result<(), String> {
+    let path = usb_config::data_file(PORT_LOCKS_FILE)?;
+    let data = serde_json::to_vec_pretty(locks).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Disable every devnode at or behind `location_path`, deepest first so
+/// nothing re-enumerates on a hub that is about to go.
+fn block_at(location_path: &str, devnodes: &[DevNode]) -> Vec<String> {
+    let mut nodes: Vec<&DevNode> = devnodes
+        .iter()
+        .filter(|node| !node.disabled)
+        .filter(|node| node.location_path.as_deref().is_some_and(|path| covers(location_path, path)))
+        .collect();
+    nodes.sort_by_key(|node| std::cmp::Reverse(node.port_chain.len()));
+    nodes
+        .into_iter()
+        .filter_map(|node| {
+            usb_control::block(&node.instance_id, BlockReason::PortLock)
+                .err()
+                .map(|e| format!("{}: {}", node.instance_id, e))
+        })
+        .collect()
+}
+
+/// Load the saved locks and re-apply them to what is attached. Called from
+/// setup once the backend is up.
+pub fn load() -> Result<(), String> {
+    let locks = read_locks()?;
+    let devnodes = backend::controller().devnodes()?;
+    for lock in &locks {
+        for error in block_at(&lock.location_path, &devnodes) {
+            log::error!("Port lock {}: {}", lock.location_path, error);
+        }
+    }
+    *LOCKS.lock().unwrap() = locks;
+    Ok(())
+}
+
+/// Block a device that just arrived on a locked port, trusted or not.
+/// Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    if device.state() != DeviceState::Connected {
+        return false;
+    }
+    let (instance_id, location_path) = match (device.instance_id(), device.location_path()) {
+        (Some(instance_id), Some(location_path)) => (instance_id, location_path),
+        _ => return false,
+    };
+    let lock = match locking(location_path) {
+        Some(lock) => lock,
+        None => return false,
+    };
+    match usb_control::block(instance_id, BlockReason::PortLock) {
+        Ok(_) => {
+            audit::record(
+                "port_lock_enforced",
+                json!({ "instance_id": instance_id, "location_path": lock.location_path }),
+            );
+            notifications::notify(
+                Severity::Warning,
+                "port_lock_enforced",
+                "USB device blocked on a locked port",
+                device.product().unwrap_or(instance_id),
+            );
+            true
+        }
+        Err(e) => {
+            log::error!("Failed to block {} on locked port {}: {}", instance_id, lock.location_path, e);
+            false
+        }
+    }
+}
+
+#[command]
+pub fn get_port_locks() -> Result<Vec<PortLock>, String> {
+    Ok(LOCKS.lock().unwrap().clone())
+}
+
+/// Lock a physical port by its location path. Whatever is attached is
+/// disabled, and the hub switches the port off when it can; anything
+/// plugged in later is blocked on arrival. The lock survives restarts.
+#[command]
+pub fn block_port(location_path: String) -> Result<PortLock, String> {
+    dry_run::refuse("block_port")?;
+    let ports = correlation::parse_port_chain(&location_path)
+        .ok_or_else(|| format!("Not a USB location path: {}", location_path))?;
+    if LOCKS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|lock| lock.location_path.eq_ignore_ascii_case(&location_path))
+    {
+        return Err(format!("Port already locked: {}", location_path));
+    }
+    let controller = backend::controller();
+    let devnodes = controller.devnodes()?;
+    let hub = location_path.rsplit_once('#').and_then(|(parent, _)| {
+        devnodes
+            .iter()
+            .find(|node| node.location_path.as_deref().is_some_and(|path| path.eq_ignore_ascii_case(parent)))
+    });
+    let port = ports.last().copied();
+
+    // Devnodes first: they cannot be reached once the port is dark
+    let errors = block_at(&location_path, &devnodes);
+    let power_cut = match (hub, port) {
+        (Some(hub), Some(port)) => controller.set_port_power(&hub.instance_id, port, false).is_ok(),
+        _ => false,
+    };
+    let lock = PortLock {
+        location_path,
+        hub_instance_id: hub.map(|hub| hub.instance_id.clone()),
+        port,
+        power_cut,
+        since: Utc::now(),
+    };
+
+    let mut locks = LOCKS.lock().unwrap();
+    locks.push(lock.clone());
+    if let Err(e) = save_locks(&locks) {
+        locks.pop();
+        return Err(e);
+    }
+    drop(locks);
+    audit::record("port_locked", json!({ "lock": lock, "errors": errors }));
+    Ok(lock)
+}
+
+/// Lift a port lock, restoring power to the port and re-enabling what the
+/// lock blocked there.
+#[command]
+pub fn unblock_port(location_path: String, admin_token: Option<String>) -> Result<(), String> {
+    dry_run::refuse("unblock_port")?;
+    admin_pin::require_admin(admin_token.as_deref(), "unlock a USB port")?;
+    hello::require_consent("unlock a USB port")?;
+    let lock = {
+        let mut locks = LOCKS.lock().unwrap();
+        let index = locks
+            .iter()
+            .position(|lock| lock.location_path.eq_ignore_ascii_case(&location_path))
+            .ok_or_else(|| format!("Port is not locked: {}", location_path))?;
+        let lock = locks.remove(index);
+        if let Err(e) = save_locks(&locks) {
+            locks.insert(index, lock);
+            return Err(e);
+        }
+        lock
+    };
+
+    let controller = backend::controller();
+    let mut errors = Vec::new();
+    if let (true, Some(hub), Some(port)) = (lock.power_cut, &lock.hub_instance_id, lock.port) {
+        if let Err(e) = controller.set_port_power(hub, port, true) {
+            errors.push(format!("{}: {}", hub, e));
+        }
+    }
+    let devnodes = controller.devnodes()?;
+    for record in usb_control::block_records() {
+        if record.reason != BlockReason::PortLock {
+            continue;
+        }
+        let location_path = devnodes
+            .iter()
+            .find(|node| node.instance_id.eq_ignore_ascii_case(&record.instance_id))
+            .and_then(|node| node.location_path.as_deref());
+        // Devices behind another lock further up the chain stay blocked
+        match location_path {
+            Some(path) if covers(&lock.location_path, path) && locking(path).is_none() => {
+                if let Err(e) = usb_control::unblock(&record.instance_id) {
+                    errors.push(format!("{}: {}", record.instance_id, e));
+                }
+            }
+            _ => {}
+        }
+    }
+    audit::record("port_unlocked", json!({ "lock": lock, "errors": errors }));
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::{sync::Mutex, thread, time::Duration};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rusb::{DeviceHandle, UsbContext};
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::dry_run;
+use super::hello;
+use super::operations;
+
+// Hub class requests (USB 2.0 §11.24)
+const REQUEST_TYPE_HUB_IN: u8 = 0xA0;
+const REQUEST_TYPE_PORT_OUT: u8 = 0x23;
+const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_CLEAR_FEATURE: u8 = 0x01;
+const REQUEST_SET_FEATURE: u8 = 0x03;
+const FEATURE_PORT_POWER: u16 = 8;
+const DESCRIPTOR_HUB: u16 = 0x29;
+const DESCRIPTOR_SUPERSPEED_HUB: u16 = 0x2A;
+// wHubCharacteristics bits 1:0
+const POWER_SWITCHING_MASK: u16 = 0b11;
+const POWER_SWITCHING_PER_PORT: u16 = 0b01;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+// Long enough for the device's own capacitors to drain
+const POWER_OFF_TIME: Duration = Duration::from_secs(2);
+
+/// A hub port this app switched off. The hub keeps it off until it is
+/// switched back on, reset or replugged.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoweredOffPort {
+    pub hub_instance_id: String,
+    pub port: u8,
+    /// The device that was attached when power was cut
+    pub instance_id: String,
+    pub since: DateTime<Utc>,
+}
+
+lazy_static! {
+    static ref POWERED_OFF: Mutex<Vec<PoweredOffPort>> = Mutex::new(Vec::new());
+}
+
+/// Switch one downstream port of an open hub with SET/CLEAR_FEATURE(PORT_POWER).
+/// Refused unless the hub descriptor reports per-port power switching; on a
+/// ganged hub the request would cut every port.
+pub fn switch_port_power<T: UsbContext>(handle: &DeviceHandle<T>, port: u8, on: bool) -> Result<(), String> {
+    let mut descriptor = [0u8; 16];
+    let len = [DESCRIPTOR_HUB, DESCRIPTOR_SUPERSPEED_HUB]
+        .iter()
+        .find_map(|kind| {
+            handle
+                .read_control(
+                    REQUEST_TYPE_HUB_IN,
+                    REQUEST_GET_DESCRIPTOR,
+                    kind << 8,
+                    0,
+                    &mut descriptor,
+                    CONTROL_TIMEOUT,
+                )
+                .ok()
+        })
+        .ok_or_else(|| "Failed to read the hub descriptor".to_string())?;
+    if len < 5 {
+        return Err("Hub descriptor is truncated".to_string());
+    }
+
+    let ports = descriptor[2];
+    let characteristics = u16::from_le_bytes([descriptor[3], descriptor[4]]);
+    if characteristics & POWER_SWITCHING_MASK != POWER_SWITCHING_PER_PORT {
+        return Err("This hub does not switch power per port".to_string());
+    }
+    if port == 0 || port > ports {
+        return Err(format!("The hub has no port {}", port));
+    }
+
+    let request = if on { REQUEST_SET_FEATURE } else { REQUEST_CLEAR_FEATURE };
+    handle
+        .write_control(
+            REQUEST_TYPE_PORT_OUT,
+            request,
+            FEATURE_PORT_POWER,
+            port as u16,
+            &[],
+            CONTROL_TIMEOUT,
+        )
+        .map(|_| ())
+        .map_err(|e| format!("Failed to switch port {} power: {}", port, e))
+}
+
+/// The hub and port a device hangs off.
+fn hub_port(instance_id: &str) -> Result<(String, u8), String> {
+    let node = backend::controller()
+        .devnodes()?
+        .into_iter()
+        .find(|node| node.instance_id.eq_ignore_ascii_case(instance_id))
+        .ok_or_else(|| format!("Device not found: {}", instance_id))?;
+    match (node.parent_instance_id, node.port_chain.last()) {
+        (Some(hub), Some(port)) => Ok((hub, *port)),
+        _ => Err(format!("{} is not attached to a hub port", instance_id)),
+    }
+}
+
+/// Turn the device's port off and on again, which recovers most hung
+/// devices without unplugging them.
+#[command]
+pub async fn power_cycle_port(instance_id: String) -> Result<(), String> {
+    dry_run::refuse("power_cycle_port")?;
+    operations::run("power_cycle_port", move || cycle_port(instance_id)).await
+}
+
+fn cycle_port(instance_id: String) -> Result<(), String> {
+    let (hub, port) = hub_port(&instance_id)?;
+    let controller = backend::controller();
+    operations::stage("Cutting port power");
+    controller.set_port_power(&hub, port, false)?;
+    thread::sleep(POWER_OFF_TIME);
+    operations::stage("Restoring port power");
+    let result = controller.set_port_power(&hub, port, true);
+    audit::record(
+        "port_power_cycled",
+        json!({ "instance_id": instance_id, "hub_instance_id": hub, "port": port, "error": result.as_ref().err() }),
+    );
+    result
+}
+
+/// Cut power to the device's port. Stronger than disabling the devnode:
+/// the device gets no power at all, so it cannot re-enumerate or charge.
+#[command]
+pub fn cut_port_power(instance_id: String) -> Result<PoweredOffPort, String> {
+    dry_run::refuse("cut_port_power")?;
+    let (hub, port) = hub_port(&instance_id)?;
+    backend::controller().set_port_power(&hub, port, false)?;
+    let record = PoweredOffPort {
+        hub_instance_id: hub,
+        port,
+        instance_id,
+        since: Utc::now(),
+    };
+    audit::record("port_power_cut", json!(record));
+    let mut powered_off = POWERED_OFF.lock().unwrap();
+    powered_off.retain(|p| !(p.hub_instance_id.eq_ignore_ascii_case(&record.hub_instance_id) && p.port == port));
+    powered_off.push(record.clone());
+    Ok(record)
+}
+
+#[command]
+pub fn restore_port_power(hub_instance_id: String, port: u8) -> Result<(), String> {
+    dry_run::refuse("restore_port_power")?;
+    hello::require_consent("restore power to a USB port")?;
+    backend::controller().set_port_power(&hub_instance_id, port, true)?;
+    POWERED_OFF
+        .lock()
+        .unwrap()
+        .retain(|p| !(p.hub_instance_id.eq_ignore_ascii_case(&hub_instance_id) && p.port == port));
+    audit::record("port_power_restored", json!({ "hub_instance_id": hub_instance_id, "port": port }));
+    Ok(())
+}
+
+#[command]
+pub fn get_powered_off_ports() -> Result<Vec<PoweredOffPort>, String> {
+    Ok(POWERED_OFF.lock().unwrap().clone())
+}
@@ -0,0 +1,137 @@
+use std::{ffi::c_void, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::Win32::{
+    Foundation::HANDLE,
+    System::{
+        Power::{
+            GetSystemPowerStatus, PowerSettingRegisterNotification, DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+            HPOWERNOTIFY, POWERBROADCAST_SETTING, SYSTEM_POWER_STATUS,
+        },
+        SystemServices::GUID_ACDC_POWER_SOURCE,
+    },
+    UI::WindowsAndMessaging::{DEVICE_NOTIFY_CALLBACK, PBT_POWERSETTINGCHANGE},
+};
+
+use super::audit;
+use super::events;
+use super::profiles::{self, Profile};
+
+const SOURCE: &str = "power";
+pub const EVENT_POWER_CHANGED: &str = "power://source-changed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// Short-term source such as a UPS
+    ShortTerm,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerPolicy {
+    pub enabled: bool,
+    pub battery_profile: Profile,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<PowerPolicy> = Mutex::new(PowerPolicy {
+        enabled: false,
+        battery_profile: Profile::Strict,
+    });
+    static ref SOURCE_STATE: Mutex<PowerSource> = Mutex::new(PowerSource::Unknown);
+    static ref NOTIFY_HANDLE: Mutex<Option<isize>> = Mutex::new(None);
+}
+
+/// Read the current source and subscribe to GUID_ACDC_POWER_SOURCE changes.
+/// The callback form of the notification needs no window.
+pub fn start() {
+    update(current_source());
+
+    // Lives for the rest of the process, as the registration does
+    let params: &'static mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS = Box::leak(Box::new(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+        Callback: Some(on_power_setting),
+        Context: std::ptr::null_mut(),
+    }));
+    let mut handle = HPOWERNOTIFY::default();
+    let status = unsafe {
+        PowerSettingRegisterNotification(
+            &GUID_ACDC_POWER_SOURCE,
+            DEVICE_NOTIFY_CALLBACK,
+            HANDLE(params as *mut _ as isize),
+            &mut handle as *mut HPOWERNOTIFY as *mut *mut c_void,
+        )
+    };
+    if status.is_ok() {
+        *NOTIFY_HANDLE.lock().unwrap() = Some(handle.0);
+    } else {
+        log::error!("Failed to register for power source notifications: {:?}", status);
+    }
+}
+
+unsafe extern "system" fn on_power_setting(_context: *const c_void, kind: u32, setting: *const c_void) -> u32 {
+    if kind == PBT_POWERSETTINGCHANGE && !setting.is_null() {
+        let setting = &*(setting as *const POWERBROADCAST_SETTING);
+        if setting.PowerSetting == GUID_ACDC_POWER_SOURCE && setting.DataLength >= 4 {
+            let value = u32::from_le_bytes(std::slice::from_raw_parts(setting.Data.as_ptr(), 4).try_into().unwrap());
+            update(match value {
+                0 => PowerSource::Ac,
+                1 => PowerSource::Battery,
+                2 => PowerSource::ShortTerm,
+                _ => PowerSource::Unknown,
+            });
+        }
+    }
+    0
+}
+
+fn current_source() -> PowerSource {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    if unsafe { !GetSystemPowerStatus(&mut status).as_bool() } {
+        return PowerSource::Unknown;
+    }
+    match status.ACLineStatus {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+fn update(source: PowerSource) {
+    let previous = std::mem::replace(&mut *SOURCE_STATE.lock().unwrap(), source);
+    if previous != source {
+        events::emit(EVENT_POWER_CHANGED, source);
+    }
+    apply();
+}
+
+fn apply() {
+    let policy = POLICY.lock().unwrap().clone();
+    let source = *SOURCE_STATE.lock().unwrap();
+    let on_battery = matches!(source, PowerSource::Battery | PowerSource::ShortTerm);
+    profiles::request(
+        SOURCE,
+        if policy.enabled && on_battery { Some(policy.battery_profile) } else { None },
+    );
+}
+
+#[command]
+pub fn get_power_source() -> Result<PowerSource, String> {
+    Ok(*SOURCE_STATE.lock().unwrap())
+}
+
+#[command]
+pub fn get_power_policy() -> Result<PowerPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_power_policy(policy: PowerPolicy) -> Result<(), String> {
+    audit::record("power_policy_changed", json!(policy));
+    *POLICY.lock().unwrap() = policy;
+    apply();
+    Ok(())
+}
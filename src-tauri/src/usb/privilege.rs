@@ -0,0 +1,168 @@
+use std::{ffi::c_void, os::windows::ffi::OsStrExt, path::Path};
+use serde::Serialize;
+use serde_json::json;
+use tauri::{command, AppHandle};
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{CloseHandle, ERROR_CANCELLED, HANDLE},
+        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        System::Threading::{
+            GetCurrentProcess, GetCurrentProcessId, OpenProcess, OpenProcessToken, WaitForSingleObject,
+            PROCESS_SYNCHRONIZE,
+        },
+        UI::{
+            Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW},
+            WindowsAndMessaging::SW_SHOWNORMAL,
+        },
+    },
+};
+
+use super::audit;
+use super::error::UsbShieldError;
+use super::helper_client;
+use super::self_test;
+use super::simulation;
+
+/// Passed to the elevated copy so it waits for this one to exit before the
+/// single-instance check would hand it straight back.
+const WAIT_FOR_PID_ARG: &str = "--wait-for-pid";
+const PREDECESSOR_TIMEOUT_MS: u32 = 10_000;
+
+/// What this process may do, so the UI can offer to elevate before a
+/// block fails instead of after.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivilegeStatus {
+    /// The process token is elevated (UAC "Run as administrator")
+    pub elevated: bool,
+    /// The helper service is listening and enforces on our behalf
+    pub helper_running: bool,
+    /// Devices can be disabled and enabled through SetupAPI. Through the
+    /// helper an unelevated process can only disable them, and lift no
+    /// policy
+    pub device_control: bool,
+    /// The machine-wide USBSTOR start value can be written
+    pub storage_policy: bool,
+    /// Per-user policy under HKCU; never needs elevation
+    pub user_policy: bool,
+    /// `relaunch_elevated` would help: not elevated and nothing else covers it
+    pub should_elevate: bool,
+}
+
+pub fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut returned = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut c_void),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        )
+        .as_bool();
+        CloseHandle(token);
+        ok && elevation.TokenIsElevated != 0
+    }
+}
+
+pub fn status() -> PrivilegeStatus {
+    let elevated = is_elevated();
+    // The simulated backend enforces in memory and needs neither
+    if simulation::is_active() {
+        return PrivilegeStatus {
+            elevated,
+            helper_running: false,
+            device_control: true,
+            storage_policy: true,
+            user_policy: true,
+            should_elevate: false,
+        };
+    }
+    let helper_running = helper_client::is_running();
+    let device_control = elevated || helper_running;
+    let storage_policy = helper_running || self_test::usbstor_writable().is_ok();
+    PrivilegeStatus {
+        elevated,
+        helper_running,
+        device_control,
+        storage_policy,
+        user_policy: true,
+        should_elevate: !elevated && (!device_control || !storage_policy),
+    }
+}
+
+#[command]
+pub fn get_privilege_status() -> Result<PrivilegeStatus, String> {
+    Ok(status())
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
+
+fn wide_path(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+/// Start this executable again through a UAC prompt and exit once it is
+/// running. Declining the prompt leaves this instance as it was.
+#[command]
+pub fn relaunch_elevated(app: AppHandle) -> Result<(), UsbShieldError> {
+    if is_elevated() {
+        return Err(UsbShieldError::Other {
+            message: "Already running as administrator".to_string(),
+        });
+    }
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let file = wide_path(&exe);
+    let parameters = wide(&format!("{} {}", WAIT_FOR_PID_ARG, unsafe { GetCurrentProcessId() }));
+
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(file.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        nShow: SW_SHOWNORMAL.0 as i32,
+        ..Default::default()
+    };
+    if let Err(e) = unsafe { ShellExecuteExW(&mut info) }.ok() {
+        if e.code() == ERROR_CANCELLED.to_hresult() {
+            return Err(UsbShieldError::ConsentDenied {
+                action: "relaunch as administrator".to_string(),
+                message: "The administrator prompt was declined".to_string(),
+            });
+        }
+        return Err(UsbShieldError::Other {
+            message: format!("Failed to relaunch elevated: {}", e),
+        });
+    }
+    if !info.hProcess.is_invalid() {
+        unsafe { CloseHandle(info.hProcess) };
+    }
+    audit::record("relaunched_elevated", json!({ "exe": exe }));
+    app.exit(0);
+    Ok(())
+}
+
+/// In an elevated relaunch, wait for the instance that started us to exit.
+/// Called first thing in `run`, before the single-instance plugin looks.
+pub fn wait_for_predecessor() {
+    let mut args = std::env::args().skip_while(|arg| arg != WAIT_FOR_PID_ARG).skip(1);
+    let pid = match args.next().and_then(|pid| pid.parse::<u32>().ok()) {
+        Some(pid) => pid,
+        None => return,
+    };
+    unsafe {
+        // Already gone is the common case and fine
+        if let Ok(process) = OpenProcess(PROCESS_SYNCHRONIZE, false, pid) {
+            WaitForSingleObject(process, PREDECESSOR_TIMEOUT_MS);
+            CloseHandle(process);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::commands::{apply_port_block, block_untrusted_for, lift_port_block};
+use super::events;
+use super::hello;
+use super::usb_control::BlockReason;
+
+pub const EVENT_PROFILE_CHANGED: &str = "profile://changed";
+
+/// Protection profiles, ordered from most relaxed to strictest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Profile {
+    /// The user's own settings apply unchanged.
+    Standard,
+    /// Autoblock is forced on and untrusted devices are blocked on entry.
+    Strict,
+    /// Additionally, the port-level storage block is applied.
+    Lockdown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileStatus {
+    pub active: Profile,
+    /// Which condition asked for which profile, e.g. `{"idle": "Strict"}`
+    pub requests: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ProfileChanged {
+    previous: Profile,
+    current: Profile,
+    requests: HashMap<String, Profile>,
+}
+
+struct ProfileState {
+    requests: HashMap<String, Profile>,
+    active: Profile,
+    // Whether entering Lockdown applied the port block, so leaving lifts it
+    ports_blocked_by_profile: bool,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<ProfileState> = Mutex::new(ProfileState {
+        requests: HashMap::new(),
+        active: Profile::Standard,
+        ports_blocked_by_profile: false,
+    });
+}
+
+pub fn active() -> Profile {
+    STATE.lock().unwrap().active
+}
+
+/// Each condition (idle timer, network location, manual choice, ...) files a
+/// request under its own `source` key, or withdraws it with `None`. The
+/// active profile is the strictest outstanding request.
+pub fn request(source: &str, profile: Option<Profile>) {
+    let (previous, current, requests) = {
+        let mut state = STATE.lock().unwrap();
+        match profile {
+            Some(profile) => state.requests.insert(source.to_string(), profile),
+            None => state.requests.remove(source),
+        };
+        let previous = state.active;
+        state.active = state.requests.values().copied().max().unwrap_or(Profile::Standard);
+        (previous, state.active, state.requests.clone())
+    };
+
+    if previous != current {
+        apply_transition(previous, current);
+        audit::record(
+            "profile_changed",
+            json!({ "previous": previous, "current": current, "requests": requests }),
+        );
+        events::emit(EVENT_PROFILE_CHANGED, ProfileChanged { previous, current, requests });
+    }
+}
+
+fn apply_transition(previous: Profile, current: Profile) {
+    if current >= Profile::Strict && previous < Profile::Strict {
+        if let Err(e) = block_untrusted_for(BlockReason::Profile) {
+            log::error!("Failed to block untrusted devices on profile change: {}", e);
+        }
+    }
+
+    if current == Profile::Lockdown && previous != Profile::Lockdown {
+        match apply_port_block() {
+            Ok(()) => STATE.lock().unwrap().ports_blocked_by_profile = true,
+            Err(e) => log::error!("Failed to apply lockdown port block: {}", e),
+        }
+    } else if previous == Profile::Lockdown && current != Profile::Lockdown {
+        let ports_blocked = std::mem::replace(&mut STATE.lock().unwrap().ports_blocked_by_profile, false);
+        if ports_blocked {
+            if let Err(e) = lift_port_block(None) {
+                log::error!("Failed to lift lockdown port block: {}", e);
+            }
+        }
+    }
+}
+
+#[command]
+pub fn get_active_profile() -> Result<ProfileStatus, String> {
+    let state = STATE.lock().unwrap();
+    Ok(ProfileStatus {
+        active: state.active,
+        requests: state.requests.clone(),
+    })
+}
+
+/// Manually pin a minimum profile (`None` clears the manual request).
+/// Automatic conditions can still escalate above it.
+#[command]
+pub fn set_active_profile(profile: Option<Profile>) -> Result<(), String> {
+    hello::require_consent("change the protection profile")?;
+    request("manual", profile);
+    Ok(())
+}
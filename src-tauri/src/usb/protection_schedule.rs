@@ -0,0 +1,131 @@
+use std::{fs, sync::Mutex, thread, time::Duration};
+use chrono::{Local, NaiveDateTime, NaiveTime, Weekday};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::events;
+use super::hello;
+use super::profiles::{self, Profile};
+use super::scheduler;
+use super::usb_config;
+
+const SOURCE: &str = "schedule";
+pub const EVENT_SCHEDULE_CHANGED: &str = "schedule://changed";
+const TICK: Duration = Duration::from_secs(30);
+const SCHEDULE_FILE: &str = "schedule.json";
+
+/// A weekly window during which a protection profile is requested, e.g.
+/// Lockdown from 17:00 to 09:00 on weekdays. `end` before `start` wraps
+/// past midnight; equal times cover the whole day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub name: String,
+    /// Days the window starts on; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub profile: Profile,
+}
+
+/// Which rule is in force, sent whenever that changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduleState {
+    pub rule: Option<String>,
+    pub profile: Option<Profile>,
+}
+
+lazy_static! {
+    static ref RULES: Mutex<Vec<ScheduleRule>> = Mutex::new(Vec::new());
+    static ref CURRENT: Mutex<ScheduleState> = Mutex::new(ScheduleState { rule: None, profile: None });
+}
+
+fn read_rules() -> Result<Vec<ScheduleRule>, String> {
+    let path = usb_config::data_file(SCHEDULE_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_rules(rules: &[ScheduleRule]) -> Result<(), String> {
+    let path = usb_config::data_file(SCHEDULE_FILE)?;
+    let data = serde_json::to_vec_pretty(rules).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Restore the saved schedule at startup, before `start`.
+pub fn load() -> Result<(), String> {
+    *RULES.lock().unwrap() = read_rules()?;
+    Ok(())
+}
+
+/// Evaluate the schedule now, so a window already open at startup is in
+/// force straight away, then again at every tick. Rules only ever file a
+/// profile request, so whatever else asks for a stricter profile still wins.
+pub fn start() {
+    thread::spawn(|| loop {
+        evaluate_at(Local::now().naive_local());
+        thread::sleep(TICK);
+    });
+}
+
+/// Request the profile of the strictest rule covering `at`, or withdraw the
+/// request outside every rule.
+pub fn evaluate_at(at: NaiveDateTime) -> ScheduleState {
+    let state = {
+        let rules = RULES.lock().unwrap();
+        let rule = rules
+            .iter()
+            .filter(|rule| scheduler::window_covers(&rule.days, rule.start, rule.end, at))
+            .max_by_key(|rule| rule.profile);
+        ScheduleState {
+            rule: rule.map(|rule| rule.name.clone()),
+            profile: rule.map(|rule| rule.profile),
+        }
+    };
+    let changed = {
+        let mut current = CURRENT.lock().unwrap();
+        std::mem::replace(&mut *current, state.clone()) != state
+    };
+    if changed {
+        profiles::request(SOURCE, state.profile);
+        audit::record("schedule_window_changed", json!(state));
+        events::emit(EVENT_SCHEDULE_CHANGED, state.clone());
+    }
+    state
+}
+
+#[command]
+pub fn get_schedule() -> Result<Vec<ScheduleRule>, String> {
+    Ok(RULES.lock().unwrap().clone())
+}
+
+/// Replace the schedule and apply it at once. Dropping or changing a rule
+/// can end a protection window early, so it needs consent.
+#[command]
+pub fn set_schedule(rules: Vec<ScheduleRule>) -> Result<ScheduleState, String> {
+    for rule in &rules {
+        if rule.name.trim().is_empty() {
+            return Err("Every schedule rule needs a name".to_string());
+        }
+    }
+    let previous = RULES.lock().unwrap().clone();
+    if previous.iter().any(|rule| !rules.contains(rule)) {
+        hello::require_consent("change the protection schedule")?;
+    }
+    save_rules(&rules)?;
+    audit::record("schedule_changed", json!({ "previous": previous, "current": rules }));
+    *RULES.lock().unwrap() = rules;
+    Ok(evaluate_at(Local::now().naive_local()))
+}
+
+/// The rule in force right now, if any.
+#[command]
+pub fn get_schedule_state() -> Result<ScheduleState, String> {
+    Ok(CURRENT.lock().unwrap().clone())
+}
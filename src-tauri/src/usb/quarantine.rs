@@ -0,0 +1,273 @@
+use std::{fs, sync::Mutex};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::{self, UsbDeviceInfo};
+use super::dry_run;
+use super::event_log::{self, LogEvent};
+use super::events;
+use super::hello;
+use super::inventory;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+use super::webhooks::{self, WebhookEvent};
+
+pub const EVENT_DEVICE_QUARANTINED: &str = "usb://device-quarantined";
+pub const EVENT_QUARANTINE_RESOLVED: &str = "usb://quarantine-resolved";
+
+const QUARANTINE_FILE: &str = "quarantine.json";
+
+/// What the user decided for a quarantined device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuarantineDecision {
+    /// Trust its VID/PID and let it in
+    Trust,
+    /// Keep it disabled, now and whenever it is plugged in again
+    Block,
+    /// Let it in until it is unplugged; it is quarantined again next time
+    AllowOnce,
+}
+
+/// An untrusted arrival held disabled until the user decides, with the
+/// descriptor data they need to recognise it.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedDevice {
+    pub device: UsbDeviceInfo,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantineResolution {
+    pub instance_id: String,
+    pub decision: QuarantineDecision,
+}
+
+// What survives a restart: the listing is rebuilt from the devnodes
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedQuarantine {
+    enabled: bool,
+    held: Vec<HeldDevice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HeldDevice {
+    instance_id: String,
+    quarantined_at: DateTime<Utc>,
+}
+
+lazy_static! {
+    static ref ENABLED: Mutex<bool> = Mutex::new(false);
+    static ref QUARANTINE: Mutex<Vec<QuarantinedDevice>> = Mutex::new(Vec::new());
+}
+
+fn read_saved() -> Result<SavedQuarantine, String> {
+    let path = usb_config::data_file(QUARANTINE_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SavedQuarantine::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save() -> Result<(), String> {
+    let saved = SavedQuarantine {
+        enabled: enabled(),
+        held: QUARANTINE
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|held| {
+                Some(HeldDevice {
+                    instance_id: held.device.instance_id()?.to_string(),
+                    quarantined_at: held.quarantined_at,
+                })
+            })
+            .collect(),
+    };
+    let path = usb_config::data_file(QUARANTINE_FILE)?;
+    let data = serde_json::to_vec_pretty(&saved).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Restore the mode and the devices awaiting a decision at startup. Those
+/// still attached are held disabled again; one that was unplugged meanwhile
+/// is quarantined anew when it returns, if the mode is still on.
+pub fn load() -> Result<(), String> {
+    let saved = read_saved()?;
+    *ENABLED.lock().unwrap() = saved.enabled;
+    let held: Vec<HeldDevice> = saved
+        .held
+        .into_iter()
+        .filter(|held| match usb_control::block(&held.instance_id, BlockReason::Quarantine) {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("Failed to quarantine {} again: {}", held.instance_id, e);
+                false
+            }
+        })
+        .collect();
+    // Listed as they are now, disabled under the quarantine reason
+    let devices = commands::get_usb_devices()?;
+    *QUARANTINE.lock().unwrap() = held
+        .into_iter()
+        .filter_map(|held| {
+            let device = devices
+                .iter()
+                .find(|d| d.instance_id().is_some_and(|id| id.eq_ignore_ascii_case(&held.instance_id)))?;
+            Some(QuarantinedDevice {
+                device: device.clone(),
+                quarantined_at: held.quarantined_at,
+            })
+        })
+        .collect();
+    save()
+}
+
+/// Whether autoblock quarantines untrusted arrivals instead of blocking them.
+pub fn enabled() -> bool {
+    *ENABLED.lock().unwrap()
+}
+
+/// Disable an untrusted arrival and hold it for a decision. Returns whether
+/// it was disabled.
+pub fn hold(device: &UsbDeviceInfo) -> bool {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) => instance_id,
+        None => return false,
+    };
+    if let Err(e) = usb_control::block(instance_id, BlockReason::Quarantine) {
+        log::error!("Failed to quarantine {}: {}", instance_id, e);
+        return false;
+    }
+    // Listed as it is now: disabled and quarantined
+    let device = commands::get_usb_devices()
+        .ok()
+        .and_then(|devices| {
+            devices
+                .into_iter()
+                .find(|d| d.instance_id().is_some_and(|id| id.eq_ignore_ascii_case(instance_id)))
+        })
+        .unwrap_or_else(|| device.clone());
+    let entry = QuarantinedDevice {
+        device,
+        quarantined_at: Utc::now(),
+    };
+    {
+        let mut quarantine = QUARANTINE.lock().unwrap();
+        quarantine.retain(|held| held.device.instance_id() != Some(instance_id));
+        quarantine.push(entry.clone());
+    }
+    if let Err(e) = save() {
+        log::error!("Failed to save the quarantine: {}", e);
+    }
+    let details = json!({
+        "instance_id": instance_id,
+        "vendor_id": entry.device.vendor_id(),
+        "product_id": entry.device.product_id(),
+    });
+    let message = format!("USB device {} was quarantined until the user decides.", instance_id);
+    event_log::write(LogEvent::DeviceQuarantined, &message, &details);
+    webhooks::dispatch(WebhookEvent::DeviceQuarantined, &message, &details);
+    audit::record("device_quarantined", details);
+    notifications::notify(
+        Severity::Warning,
+        "device_quarantined",
+        "New USB device quarantined",
+        &format!(
+            "{} is held until you trust, block or allow it once.",
+            entry.device.product().unwrap_or(instance_id)
+        ),
+    );
+    events::emit(EVENT_DEVICE_QUARANTINED, entry);
+    true
+}
+
+#[command]
+pub fn get_quarantine_mode() -> Result<bool, String> {
+    Ok(enabled())
+}
+
+/// Quarantine untrusted arrivals instead of blocking them outright. Either
+/// way they are disabled, so neither direction needs consent.
+#[command]
+pub fn set_quarantine_mode(enabled: bool) -> Result<(), String> {
+    let previous = std::mem::replace(&mut *ENABLED.lock().unwrap(), enabled);
+    if let Err(e) = save() {
+        *ENABLED.lock().unwrap() = previous;
+        return Err(e);
+    }
+    audit::record("quarantine_mode_changed", json!({ "enabled": enabled }));
+    Ok(())
+}
+
+#[command]
+pub fn get_quarantined_devices() -> Result<Vec<QuarantinedDevice>, String> {
+    Ok(QUARANTINE.lock().unwrap().clone())
+}
+
+/// Decide what happens to a quarantined device. Letting it in, once or for
+/// good, needs the admin PIN and consent like any unblock.
+#[command]
+pub fn resolve_quarantine(
+    id: String,
+    decision: QuarantineDecision,
+    admin_token: Option<String>,
+) -> Result<QuarantineResolution, String> {
+    dry_run::refuse("resolve_quarantine")?;
+    let held = QUARANTINE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|held| held.device.instance_id().is_some_and(|held_id| held_id.eq_ignore_ascii_case(&id)))
+        .cloned()
+        .ok_or_else(|| format!("{} is not quarantined", id))?;
+    let device = &held.device;
+
+    match decision {
+        QuarantineDecision::Trust => {
+            admin_pin::require_admin(admin_token.as_deref(), "trust a quarantined device")?;
+            hello::require_consent("trust a quarantined device")?;
+            commands::app_state().add_trusted_device(device.vendor_id(), device.product_id())?;
+            usb_control::unblock(&id)?;
+        }
+        QuarantineDecision::AllowOnce => {
+            admin_pin::require_admin(admin_token.as_deref(), "allow a quarantined device")?;
+            hello::require_consent("allow a quarantined device")?;
+            usb_control::unblock(&id)?;
+        }
+        QuarantineDecision::Block => {
+            // Pre-blocked devices are blocked on arrival, before autoblock
+            inventory::set_device_preblocked(id.clone(), true)?;
+            // Still disabled; only the recorded reason changes
+            usb_control::block(&id, BlockReason::Manual)?;
+        }
+    }
+
+    QUARANTINE
+        .lock()
+        .unwrap()
+        .retain(|held| !held.device.instance_id().is_some_and(|held_id| held_id.eq_ignore_ascii_case(&id)));
+    if let Err(e) = save() {
+        log::error!("Failed to save the quarantine: {}", e);
+    }
+    let resolution = QuarantineResolution {
+        instance_id: id,
+        decision,
+    };
+    audit::record(
+        "quarantine_resolved",
+        json!({
+            "resolution": resolution,
+            "vendor_id": device.vendor_id(),
+            "product_id": device.product_id(),
+        }),
+    );
+    events::emit(EVENT_QUARANTINE_RESOLVED, resolution.clone());
+    Ok(resolution)
+}
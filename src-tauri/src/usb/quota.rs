@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Mutex,
+};
+use chrono::{Local, NaiveDate};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::correlation::{parse_serial, parse_vid_pid};
+use super::events;
+use super::exfiltration;
+use super::hello;
+use super::storage_readonly;
+use super::transfers::TransferStats;
+use super::usb_config;
+
+pub const EVENT_WRITE_QUOTA_EXCEEDED: &str = "usb://write-quota-exceeded";
+
+// Today's usage survives a restart, so quitting the app does not hand out a fresh quota
+const USAGE_FILE: &str = "write-quota.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceQuota {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Limits one unit only; `None` covers every unit of the VID/PID
+    #[serde(default)]
+    pub serial: Option<String>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteQuotaPolicy {
+    pub enabled: bool,
+    /// Combined daily limit across all removable media
+    #[serde(default)]
+    pub global_bytes: Option<u64>,
+    #[serde(default)]
+    pub per_device: Vec<DeviceQuota>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceQuotaUsage {
+    pub device_instance_id: String,
+    pub bytes_written: u64,
+    pub quota: Option<u64>,
+    pub exceeded: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteQuotaUsage {
+    pub day: NaiveDate,
+    pub devices: Vec<DeviceQuotaUsage>,
+    pub bytes_written: u64,
+    pub global_quota: Option<u64>,
+    pub global_exceeded: bool,
+    /// Mount points currently read-only because of a quota
+    pub write_blocked: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaExceeded {
+    /// `None` when the global quota ran out
+    pub device_instance_id: Option<String>,
+    pub bytes_written: u64,
+    pub quota: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DailyUsage {
+    day: Option<NaiveDate>,
+    /// Device instance ID (upper case) -> bytes written today
+    written: HashMap<String, u64>,
+    /// Quotas already reported today; `None` is the global quota
+    notified: HashSet<Option<String>>,
+    // Transfer session key -> session bytes_written at the last sample
+    #[serde(skip)]
+    last: HashMap<String, u64>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<WriteQuotaPolicy> = Mutex::new(WriteQuotaPolicy {
+        enabled: false,
+        global_bytes: None,
+        per_device: Vec::new(),
+    });
+    static ref USAGE: Mutex<DailyUsage> = Mutex::new(load());
+    // Mount points this module made read-only
+    static ref BLOCKED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+fn load() -> DailyUsage {
+    usb_config::data_file(USAGE_FILE)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save(usage: &DailyUsage) {
+    let result = usb_config::data_file(USAGE_FILE).and_then(|path| {
+        let data = serde_json::to_vec(usage).map_err(|e| e.to_string())?;
+        fs::write(path, data).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        log::error!("Failed to save write quota usage: {}", e);
+    }
+}
+
+fn device_quota(policy: &WriteQuotaPolicy, instance_id: &str) -> Option<u64> {
+    let (vendor_id, product_id) = parse_vid_pid(instance_id)?;
+    let serial = parse_serial(instance_id);
+    policy
+        .per_device
+        .iter()
+        .filter(|q| q.vendor_id == vendor_id && q.product_id == product_id)
+        .filter(|q| match (&q.serial, &serial) {
+            (None, _) => true,
+            (Some(bound), Some(actual)) => bound.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+        })
+        .map(|q| q.bytes)
+        .min()
+}
+
+/// Whether a quota currently holds `mount_point` read-only.
+pub fn blocks(mount_point: &str) -> bool {
+    BLOCKED.lock().unwrap().contains(&mount_point.to_ascii_uppercase())
+}
+
+/// Called with every transfer sample. Adds the bytes written since the last
+/// sample to today's usage, then write-protects the volumes of every device
+/// over its quota and releases the rest.
+pub fn check(stats: &[TransferStats]) {
+    let policy = POLICY.lock().unwrap().clone();
+    let today = Local::now().date_naive();
+    let mut exceeded = Vec::new();
+
+    let over: HashSet<String> = {
+        let mut usage = USAGE.lock().unwrap();
+        let mut changed = false;
+        if usage.day != Some(today) {
+            usage.day = Some(today);
+            usage.written.clear();
+            usage.notified.clear();
+            changed = true;
+        }
+
+        for stat in stats {
+            let key = format!("{}|{}", stat.device_instance_id.to_ascii_uppercase(), stat.volume_serial);
+            let previous = usage.last.insert(key, stat.totals.bytes_written).unwrap_or(0);
+            let delta = stat.totals.bytes_written.saturating_sub(previous);
+            if delta > 0 {
+                *usage.written.entry(stat.device_instance_id.to_ascii_uppercase()).or_default() += delta;
+                changed = true;
+            }
+        }
+
+        let mut over = HashSet::new();
+        if policy.enabled {
+            let total: u64 = usage.written.values().sum();
+            let global_over = policy.global_bytes.is_some_and(|quota| total >= quota);
+            if global_over && usage.notified.insert(None) {
+                exceeded.push(QuotaExceeded {
+                    device_instance_id: None,
+                    bytes_written: total,
+                    quota: policy.global_bytes.unwrap_or_default(),
+                });
+                changed = true;
+            }
+
+            let written: Vec<(String, u64)> = usage.written.iter().map(|(k, v)| (k.clone(), *v)).collect();
+            for (device, bytes) in written {
+                let device_over = match device_quota(&policy, &device) {
+                    Some(quota) if bytes >= quota => {
+                        if usage.notified.insert(Some(device.clone())) {
+                            exceeded.push(QuotaExceeded {
+                                device_instance_id: Some(device.clone()),
+                                bytes_written: bytes,
+                                quota,
+                            });
+                            changed = true;
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if global_over || device_over {
+                    over.insert(device);
+                }
+            }
+            if global_over {
+                over.extend(stats.iter().map(|s| s.device_instance_id.to_ascii_uppercase()));
+            }
+        }
+
+        if changed {
+            save(&usage);
+        }
+        over
+    };
+
+    for event in exceeded {
+        audit::record("write_quota_exceeded", json!(event));
+        events::emit(EVENT_WRITE_QUOTA_EXCEEDED, event);
+    }
+    enforce(stats, &over);
+}
+
+fn enforce(stats: &[TransferStats], over: &HashSet<String>) {
+    let controller = backend::controller();
+    let mounted: Vec<&TransferStats> = stats.iter().filter(|s| s.mounted).collect();
+    let wanted: HashSet<String> = mounted
+        .iter()
+        .filter(|s| over.contains(&s.device_instance_id.to_ascii_uppercase()))
+        .map(|s| s.mount_point.to_ascii_uppercase())
+        .collect();
+
+    let mut blocked = BLOCKED.lock().unwrap();
+    // Read-only does not survive a remount; forget letters that went away
+    blocked.retain(|mount| mounted.iter().any(|s| s.mount_point.eq_ignore_ascii_case(mount)));
+
+    for mount in wanted.difference(&blocked.clone()) {
+        match controller.set_volume_read_only(mount, true) {
+            Ok(()) => {
+                blocked.insert(mount.clone());
+                audit::record("write_quota_blocked", json!({ "mount_point": mount }));
+            }
+            Err(e) => audit::record("write_quota_blocked", json!({ "mount_point": mount, "error": e })),
+        }
+    }
+    for mount in blocked.clone().difference(&wanted) {
+        // An exfiltration block or read-only mode on the same volume outlives the quota
+        if !exfiltration::write_blocked(mount) && !storage_readonly::holds(mount) {
+            if let Err(e) = controller.set_volume_read_only(mount, false) {
+                audit::record("write_quota_released", json!({ "mount_point": mount, "error": e }));
+                continue;
+            }
+        }
+        blocked.remove(mount);
+        audit::record("write_quota_released", json!({ "mount_point": mount }));
+    }
+}
+
+#[command]
+pub fn get_write_quota_policy() -> Result<WriteQuotaPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+/// Whether `current` allows more writes than `previous` anywhere.
+fn relaxes(previous: &WriteQuotaPolicy, current: &WriteQuotaPolicy) -> bool {
+    if !previous.enabled {
+        return false;
+    }
+    if !current.enabled {
+        return true;
+    }
+    let global_relaxed = match (previous.global_bytes, current.global_bytes) {
+        (Some(_), None) => true,
+        (Some(before), Some(after)) => after > before,
+        _ => false,
+    };
+    global_relaxed
+        || previous.per_device.iter().any(|before| {
+            !current.per_device.iter().any(|after| {
+                after.vendor_id == before.vendor_id
+                    && after.product_id == before.product_id
+                    && after.serial == before.serial
+                    && after.bytes <= before.bytes
+            })
+        })
+}
+
+#[command]
+pub fn set_write_quota_policy(policy: WriteQuotaPolicy) -> Result<(), String> {
+    if policy.global_bytes == Some(0) || policy.per_device.iter().any(|q| q.bytes == 0) {
+        return Err("A write quota must be greater than zero; block the device instead".to_string());
+    }
+    let previous = POLICY.lock().unwrap().clone();
+    if relaxes(&previous, &policy) {
+        hello::require_consent("raise a write quota")?;
+    }
+    audit::record("write_quota_policy_changed", json!({ "previous": previous, "current": policy }));
+    *POLICY.lock().unwrap() = policy;
+    Ok(())
+}
+
+#[command]
+pub fn get_write_quota_usage() -> Result<WriteQuotaUsage, String> {
+    let policy = POLICY.lock().unwrap().clone();
+    let usage = USAGE.lock().unwrap();
+    let total: u64 = usage.written.values().sum();
+    let global_exceeded = policy.enabled && policy.global_bytes.is_some_and(|quota| total >= quota);
+    let mut devices: Vec<DeviceQuotaUsage> = usage
+        .written
+        .iter()
+        .map(|(device, bytes)| {
+            let quota = device_quota(&policy, device);
+            DeviceQuotaUsage {
+                device_instance_id: device.clone(),
+                bytes_written: *bytes,
+                quota,
+                exceeded: policy.enabled && quota.is_some_and(|quota| *bytes >= quota),
+            }
+        })
+        .collect();
+    devices.sort_by(|a, b| a.device_instance_id.cmp(&b.device_instance_id));
+
+    Ok(WriteQuotaUsage {
+        day: usage.day.unwrap_or_else(|| Local::now().date_naive()),
+        devices,
+        bytes_written: total,
+        global_quota: policy.global_bytes,
+        global_exceeded,
+        write_blocked: BLOCKED.lock().unwrap().iter().cloned().collect(),
+    })
+}
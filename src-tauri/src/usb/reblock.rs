@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::{apply_port_block, enable_device, expire_temporary_trust};
+use super::dry_run;
+use super::guest;
+use super::pause;
+use super::hello;
+use super::events;
+use super::usb_control::{self, BlockReason};
+
+pub const EVENT_COUNTDOWN: &str = "usb://reblock-countdown";
+pub const EVENT_EXPIRED: &str = "usb://reblock-expired";
+pub const EVENT_EXTENDED: &str = "usb://reblock-extended";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReblockTarget {
+    Device { instance_id: String },
+    Ports,
+    /// A temporary trust grant
+    Trust { vendor_id: u16, product_id: u16 },
+    /// The end of guest mode
+    Guest,
+    /// The end of a protection pause
+    Pause,
+}
+
+#[derive(Debug, Clone)]
+struct PendingReblock {
+    target: ReblockTarget,
+    deadline: Instant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReblockStatus {
+    pub id: u64,
+    pub target: ReblockTarget,
+    pub remaining_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReblockExpired {
+    id: u64,
+    target: ReblockTarget,
+    error: Option<String>,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<HashMap<u64, PendingReblock>> = Mutex::new(HashMap::new());
+    static ref TICKER_STARTED: Mutex<bool> = Mutex::new(false);
+}
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Re-block `target` once `after` has elapsed. A scheduled re-block for the
+/// same target is replaced rather than duplicated. Returns the schedule id.
+pub fn schedule(target: ReblockTarget, after: Duration) -> u64 {
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|_, p| p.target != target);
+
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    pending.insert(
+        id,
+        PendingReblock {
+            target: target.clone(),
+            deadline: Instant::now() + after,
+        },
+    );
+    drop(pending);
+
+    audit::record("reblock_scheduled", json!({ "id": id, "target": target, "after_secs": after.as_secs() }));
+    ensure_ticker();
+    id
+}
+
+/// Push the deadline of a pending re-block further out.
+pub fn extend(id: u64, extra: Duration) -> Result<ReblockStatus, String> {
+    let mut pending = PENDING.lock().unwrap();
+    let entry = pending.get_mut(&id).ok_or_else(|| format!("No pending re-block with id {}", id))?;
+    entry.deadline += extra;
+    Ok(status(id, entry))
+}
+
+/// Drop a pending re-block without acting on it.
+pub fn cancel(id: u64) -> Option<ReblockTarget> {
+    PENDING.lock().unwrap().remove(&id).map(|p| p.target)
+}
+
+/// Re-block right away instead of waiting for the deadline.
+pub fn fire_now(id: u64) -> Result<(), String> {
+    let target = cancel(id).ok_or_else(|| format!("No pending re-block with id {}", id))?;
+    reblock(id, target)
+}
+
+pub fn pending() -> Vec<ReblockStatus> {
+    PENDING.lock().unwrap().iter().map(|(id, p)| status(*id, p)).collect()
+}
+
+fn status(id: u64, pending: &PendingReblock) -> ReblockStatus {
+    ReblockStatus {
+        id,
+        target: pending.target.clone(),
+        remaining_secs: pending.deadline.saturating_duration_since(Instant::now()).as_secs(),
+    }
+}
+
+// One ticker thread serves every schedule: emits a countdown each second and
+// re-blocks whatever expired.
+fn ensure_ticker() {
+    let mut started = TICKER_STARTED.lock().unwrap();
+    if *started {
+        return;
+    }
+    *started = true;
+
+    thread::spawn(|| loop {
+        thread::sleep(Duration::from_secs(1));
+
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        let mut countdown = Vec::new();
+        {
+            let mut pending = PENDING.lock().unwrap();
+            pending.retain(|id, p| {
+                if p.deadline <= now {
+                    expired.push((*id, p.target.clone()));
+                    false
+                } else {
+                    countdown.push(status(*id, p));
+                    true
+                }
+            });
+        }
+
+        for entry in countdown {
+            events::emit(EVENT_COUNTDOWN, entry);
+        }
+        for (id, target) in expired {
+            let _ = reblock(id, target);
+        }
+    });
+}
+
+fn reblock(id: u64, target: ReblockTarget) -> Result<(), String> {
+    let result = match &target {
+        ReblockTarget::Device { instance_id } => usb_control::block(instance_id, BlockReason::TimedReblock)
+            .map(|_| ())
+            .map_err(String::from),
+        ReblockTarget::Ports => apply_port_block().map_err(String::from),
+        ReblockTarget::Trust { vendor_id, product_id } => expire_temporary_trust(*vendor_id, *product_id, id),
+        ReblockTarget::Guest => guest::end().map(|_| ()),
+        ReblockTarget::Pause => pause::end().map(|_| ()),
+    };
+
+    audit::record(
+        "reblock_applied",
+        json!({ "id": id, "target": target, "error": result.as_ref().err() }),
+    );
+    events::emit(
+        EVENT_EXPIRED,
+        ReblockExpired {
+            id,
+            target,
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+#[command]
+pub fn get_pending_reblocks() -> Result<Vec<ReblockStatus>, String> {
+    Ok(pending())
+}
+
+/// Drop a pending re-block, leaving its target as it is now. A temporary
+/// trust grant, a guest session or a pause cannot be cancelled, since that
+/// would keep it open for good: revoke it, end it, or trust the model
+/// permanently instead.
+#[command]
+pub fn cancel_reblock(id: u64, admin_token: Option<String>) -> Result<(), String> {
+    admin_pin::require_admin(admin_token.as_deref(), "cancel a re-block")?;
+    let mut pending = PENDING.lock().unwrap();
+    let target = pending
+        .get(&id)
+        .map(|p| p.target.clone())
+        .ok_or_else(|| format!("No pending re-block with id {}", id))?;
+    match target {
+        ReblockTarget::Trust { .. } => {
+            return Err("A temporary trust grant cannot be cancelled; revoke it or trust the device".to_string())
+        }
+        ReblockTarget::Guest => return Err("A guest session cannot be cancelled; end it instead".to_string()),
+        ReblockTarget::Pause => {
+            return Err("A protection pause cannot be cancelled; resume protection instead".to_string())
+        }
+        ReblockTarget::Device { .. } | ReblockTarget::Ports => {}
+    }
+    pending.remove(&id);
+    drop(pending);
+    audit::record("reblock_cancelled", json!({ "id": id, "target": target }));
+    Ok(())
+}
+
+/// Enable a device for `duration_secs`, then block it again. The returned
+/// schedule ids drive `extend_timed_unblock` / `revoke_timed_unblock`.
+#[command]
+pub fn unblock_device_for(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    instance_id: Option<String>,
+    duration_secs: u64,
+    admin_token: Option<String>,
+) -> Result<Vec<ReblockStatus>, String> {
+    if duration_secs == 0 {
+        return Err("Duration must be greater than zero".to_string());
+    }
+    dry_run::refuse("unblock_device_for")?;
+
+    admin_pin::require_admin(admin_token.as_deref(), "temporarily unblock a device")?;
+    hello::require_consent("temporarily unblock a device")?;
+    let changes = enable_device(vendor_id, product_id, serial, instance_id, None)?;
+    let pending = changes
+        .into_iter()
+        .filter_map(|change| {
+            let id = schedule(
+                ReblockTarget::Device { instance_id: change.instance_id },
+                Duration::from_secs(duration_secs),
+            );
+            let entry = PENDING.lock().unwrap().get(&id).cloned();
+            entry.map(|p| status(id, &p))
+        })
+        .collect();
+    Ok(pending)
+}
+
+#[command]
+pub fn extend_timed_unblock(id: u64, extra_secs: u64, admin_token: Option<String>) -> Result<ReblockStatus, String> {
+    admin_pin::require_admin(admin_token.as_deref(), "extend a timed unblock")?;
+    let status = extend(id, Duration::from_secs(extra_secs))?;
+    audit::record("reblock_extended", json!({ "id": id, "extra_secs": extra_secs }));
+    events::emit(EVENT_EXTENDED, status.clone());
+    Ok(status)
+}
+
+/// End a timed unblock early: the device is blocked again immediately.
+/// Trust grants, guest sessions and pauses have their own commands to end
+/// them.
+#[command]
+pub fn revoke_timed_unblock(id: u64) -> Result<(), String> {
+    let target = PENDING
+        .lock()
+        .unwrap()
+        .get(&id)
+        .map(|p| p.target.clone())
+        .ok_or_else(|| format!("No pending re-block with id {}", id))?;
+    match target {
+        ReblockTarget::Device { .. } | ReblockTarget::Ports => fire_now(id),
+        _ => Err(format!("Re-block {} is not a timed unblock", id)),
+    }
+}
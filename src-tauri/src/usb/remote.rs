@@ -0,0 +1,189 @@
+use std::{sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+use super::audit;
+use super::backend;
+use super::correlation::DevNode;
+use super::hello;
+use super::usb_control::{self, BlockReason};
+
+// Arrivals are caught on the next pass; USB/IP attach takes seconds anyway
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Virtual host controllers of USB/IP clients, matched as substrings of the
+// controller's instance ID: usbip-win (`USBIPWIN\...`, `ROOT\USBIP\0000`)
+// and usbip-win2 (`ROOT\USBIP_WIN2\UDE`).
+const USBIP_CONTROLLERS: &[&str] = &["USBIPWIN", "\\USBIP\\", "USBIP_WIN2"];
+
+const SERVICES_KEY: &str = r"SYSTEM\CurrentControlSet\Services";
+
+/// A commercial USB sharing client, recognised by the virtual bus driver it
+/// installs. Devices it redirects hang off that driver's host controller.
+struct RedirectionClient {
+    name: &'static str,
+    /// Substrings of the virtual controller's instance ID
+    controllers: &'static [&'static str],
+    /// Driver service names under `Services`
+    services: &'static [&'static str],
+}
+
+const REDIRECTION_CLIENTS: &[RedirectionClient] = &[
+    RedirectionClient {
+        name: "VirtualHere",
+        controllers: &["VHUSB"],
+        services: &["vhusb3hc", "vhusb2hc"],
+    },
+    RedirectionClient {
+        name: "USB Network Gate",
+        controllers: &["EUSBHUB", "ELTIMA"],
+        services: &["eusbhub", "eusbstub"],
+    },
+];
+
+/// How a device reaches this machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Attachment {
+    /// Plugged into a local controller
+    Physical,
+    /// Exported by another machine over USB/IP
+    UsbIp,
+    /// Forwarded by a third-party sharing client (see `redirection_client`)
+    Redirected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteUsbPolicy {
+    /// Disable every USB/IP-attached device, trusted or not.
+    pub block_usbip: bool,
+    /// Disable devices arriving through a known redirection client.
+    #[serde(default)]
+    pub block_redirected: bool,
+}
+
+/// What `get_redirection_clients` reports for one known client.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedirectionClientStatus {
+    pub name: String,
+    /// Its bus driver is registered as a service
+    pub installed: bool,
+    /// Instance IDs of devices currently attached through it
+    pub devices: Vec<String>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<RemoteUsbPolicy> = Mutex::new(RemoteUsbPolicy {
+        block_usbip: false,
+        block_redirected: false,
+    });
+}
+
+pub fn attachment(node: &DevNode) -> Attachment {
+    let controller = match node.controller_instance_id.as_deref() {
+        Some(id) => id.to_ascii_uppercase(),
+        None => return Attachment::Physical,
+    };
+    if USBIP_CONTROLLERS.iter().any(|pattern| controller.contains(pattern)) {
+        Attachment::UsbIp
+    } else if redirection_client(node).is_some() {
+        Attachment::Redirected
+    } else {
+        Attachment::Physical
+    }
+}
+
+/// Name of the sharing client a device arrives through, if any.
+pub fn redirection_client(node: &DevNode) -> Option<&'static str> {
+    let controller = node.controller_instance_id.as_deref()?.to_ascii_uppercase();
+    REDIRECTION_CLIENTS
+        .iter()
+        .find(|client| client.controllers.iter().any(|pattern| controller.contains(pattern)))
+        .map(|client| client.name)
+}
+
+fn service_installed(name: &str) -> bool {
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey(format!("{}\\{}", SERVICES_KEY, name))
+        .is_ok()
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        enforce();
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+/// Disable network-attached devices the policy forbids. Trust is not
+/// consulted: a trusted VID/PID says nothing about the remote machine.
+fn enforce() {
+    let policy = POLICY.lock().unwrap().clone();
+    if !policy.block_usbip && !policy.block_redirected {
+        return;
+    }
+    let devnodes = match backend::controller().devnodes() {
+        Ok(devnodes) => devnodes,
+        Err(_) => return,
+    };
+    for node in devnodes.iter().filter(|node| !node.disabled) {
+        let attachment = attachment(node);
+        let forbidden = match attachment {
+            Attachment::UsbIp => policy.block_usbip,
+            Attachment::Redirected => policy.block_redirected,
+            Attachment::Physical => false,
+        };
+        if !forbidden {
+            continue;
+        }
+        let result = usb_control::block(&node.instance_id, BlockReason::RemoteUsbPolicy);
+        audit::record(
+            "remote_usb_blocked",
+            json!({
+                "instance_id": node.instance_id,
+                "controller": node.controller_instance_id,
+                "attachment": attachment,
+                "client": redirection_client(node),
+                "error": result.err().map(String::from),
+            }),
+        );
+    }
+}
+
+#[command]
+pub fn get_remote_usb_policy() -> Result<RemoteUsbPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_remote_usb_policy(policy: RemoteUsbPolicy) -> Result<(), String> {
+    let previous = POLICY.lock().unwrap().clone();
+    if (previous.block_usbip && !policy.block_usbip) || (previous.block_redirected && !policy.block_redirected) {
+        hello::require_consent("allow network-attached USB devices")?;
+    }
+    audit::record("remote_usb_policy_changed", json!({ "previous": previous, "current": policy }));
+    *POLICY.lock().unwrap() = policy;
+    enforce();
+    Ok(())
+}
+
+/// Diagnostics: which known redirection clients are installed, and what
+/// is attached through them right now.
+#[command]
+pub fn get_redirection_clients() -> Result<Vec<RedirectionClientStatus>, String> {
+    let devnodes = backend::controller().devnodes()?;
+    Ok(REDIRECTION_CLIENTS
+        .iter()
+        .map(|client| RedirectionClientStatus {
+            name: client.name.to_string(),
+            installed: client.services.iter().any(|service| service_installed(service)),
+            devices: devnodes
+                .iter()
+                .filter(|node| redirection_client(node) == Some(client.name))
+                .map(|node| node.instance_id.clone())
+                .collect(),
+        })
+        .collect())
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use uport_shield_helper::{
+    protocol::RemovableStoragePolicy,
+    registry::{self, Root},
+};
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::dry_run;
+use super::commands::{restart_storage, REMOVABLE_STORAGE_POLICY_KEY};
+use super::error::UsbShieldError;
+use super::hello;
+use super::operations;
+use super::tamper;
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const SNAPSHOT_FILE: &str = "rollback-snapshot.json";
+
+/// A devnode as it was when the snapshot was taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub instance_id: String,
+    pub disabled: bool,
+    /// Why we had blocked it, if we had
+    pub reason: Option<BlockReason>,
+}
+
+/// Storage policy and device states from just before a policy change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// The change it was taken for, e.g. `block_all_usb_ports`
+    pub taken_before: String,
+    /// The USBSTOR `Start` value, `None` if it could not be read
+    pub usbstor_start: Option<u32>,
+    /// The per-user `RemovableStorageDevices\Deny_All` value, `None` when unset
+    pub deny_all: Option<u32>,
+    /// Machine-wide per-class policies; classes not listed had none
+    pub removable_storage: Vec<RemovableStoragePolicy>,
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub snapshot: SystemSnapshot,
+    pub devices_enabled: Vec<String>,
+    pub devices_disabled: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+fn deny_all() -> Result<Option<u32>, String> {
+    match registry::open(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY)? {
+        Some(key) => Ok(key.dword("Deny_All")?),
+        None => Ok(None),
+    }
+}
+
+fn save(snapshot: &SystemSnapshot) -> Result<(), String> {
+    let path = usb_config::data_file(SNAPSHOT_FILE)?;
+    let data = serde_json::to_vec_pretty(snapshot).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn load() -> Result<Option<SystemSnapshot>, String> {
+    let path = usb_config::data_file(SNAPSHOT_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Record the current storage policy and device states on disk before
+/// `change` alters them. A snapshot not yet restored is kept and returned
+/// instead: a second change would otherwise overwrite the state from before
+/// the first with the state in between.
+pub fn take(change: &str) -> Result<SystemSnapshot, String> {
+    if let Some(snapshot) = load()? {
+        return Ok(snapshot);
+    }
+    let controller = backend::controller();
+    let snapshot = SystemSnapshot {
+        taken_at: Utc::now(),
+        taken_before: change.to_string(),
+        usbstor_start: controller.usbstor_start(),
+        deny_all: deny_all()?,
+        removable_storage: controller.removable_storage_policies()?,
+        devices: controller
+            .devnodes()?
+            .into_iter()
+            .map(|node| DeviceSnapshot {
+                reason: usb_control::block_reason(&node.instance_id),
+                instance_id: node.instance_id,
+                disabled: node.disabled,
+            })
+            .collect(),
+    };
+    save(&snapshot)?;
+    Ok(snapshot)
+}
+
+/// `take` for callers about to enforce: a snapshot that cannot be written
+/// is logged rather than allowed to hold up the block.
+pub fn take_before(change: &str) {
+    if let Err(e) = take(change) {
+        log::error!("No rollback snapshot before {}: {}", change, e);
+    }
+}
+
+#[command]
+pub fn get_rollback_snapshot() -> Result<Option<SystemSnapshot>, String> {
+    load()
+}
+
+fn restore(snapshot: &SystemSnapshot, report: &mut RestoreReport) -> Result<(), UsbShieldError> {
+    let controller = backend::controller();
+
+    operations::stage("Restoring the USB storage driver");
+    if let Some(start) = snapshot.usbstor_start.filter(|start| *start == 3 || *start == 4) {
+        // Guarded again only if it was a block we were guarding
+        let guarded = if start == 4 { Some(4) } else { None };
+        tamper::write_usbstor_start(guarded, || {
+            controller
+                .apply_policy(start)
+                .map_err(|e| UsbShieldError::policy("restore the USB storage driver", e))
+        })?;
+    }
+
+    operations::stage("Restoring the removable storage policies");
+    let result = match snapshot.deny_all {
+        Some(value) => registry::create(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY)
+            .and_then(|key| key.set_dword("Deny_All", value)),
+        None => registry::open(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY)
+            .and_then(|key| key.map_or(Ok(()), |key| key.delete_value("Deny_All"))),
+    };
+    result.map_err(|e| UsbShieldError::policy("restore the removable storage policy", e.into()))?;
+
+    // Classes that have a policy now but had none are reset to deny nothing
+    let mut policies = snapshot.removable_storage.clone();
+    for current in controller.removable_storage_policies()? {
+        if !policies.iter().any(|policy| policy.class_guid.eq_ignore_ascii_case(&current.class_guid)) {
+            policies.push(RemovableStoragePolicy {
+                class_guid: current.class_guid,
+                deny_read: false,
+                deny_write: false,
+                deny_execute: false,
+            });
+        }
+    }
+    for policy in &policies {
+        tamper::write_removable_storage(policy, || {
+            controller
+                .set_removable_storage_policy(policy)
+                .map_err(|e| UsbShieldError::policy("restore the removable storage policy", e))
+        })?;
+    }
+
+    operations::stage("Restoring device states");
+    // Devices unplugged since then are left for hotplug to judge
+    for node in controller.devnodes()? {
+        let before = match snapshot
+            .devices
+            .iter()
+            .find(|device| device.instance_id.eq_ignore_ascii_case(&node.instance_id))
+        {
+            Some(before) => before,
+            None => continue,
+        };
+        let (result, changed) = match (before.disabled, node.disabled) {
+            (false, true) => (usb_control::unblock(&node.instance_id), &mut report.devices_enabled),
+            (true, false) => (
+                usb_control::block(&node.instance_id, before.reason.unwrap_or(BlockReason::Manual)),
+                &mut report.devices_disabled,
+            ),
+            _ => continue,
+        };
+        match result {
+            Ok(_) => changed.push(node.instance_id),
+            Err(e) => report.errors.push(format!("{}: {}", node.instance_id, e)),
+        }
+    }
+
+    restart_storage()?;
+    Ok(())
+}
+
+/// Put the storage policy and device states back the way the last snapshot
+/// found them, then discard it. The snapshot survives restarts, so a bad
+/// policy can be undone after one. Relaxes protection, so needs the admin
+/// session and consent.
+#[command]
+pub async fn restore_previous_state(admin_token: Option<String>) -> Result<RestoreReport, UsbShieldError> {
+    operations::run("restore_previous_state", move || {
+        dry_run::refuse("restore_previous_state")?;
+        let snapshot = load()?.ok_or_else(|| "There is no snapshot to restore".to_string())?;
+        admin_pin::require_admin(admin_token.as_deref(), "restore the previous USB policy")?;
+        hello::require_consent("restore the previous USB policy")?;
+
+        let mut report = RestoreReport {
+            snapshot,
+            devices_enabled: Vec::new(),
+            devices_disabled: Vec::new(),
+            errors: Vec::new(),
+        };
+        let snapshot = report.snapshot.clone();
+        let result = restore(&snapshot, &mut report);
+        if let Err(e) = &result {
+            report.errors.push(e.to_string());
+        }
+        audit::record(
+            "previous_state_restored",
+            json!({
+                "taken_at": snapshot.taken_at,
+                "taken_before": snapshot.taken_before,
+                "devices_enabled": report.devices_enabled,
+                "devices_disabled": report.devices_disabled,
+                "errors": report.errors,
+            }),
+        );
+        result?;
+
+        let path = usb_config::data_file(SNAPSHOT_FILE)?;
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        Ok(report)
+    })
+    .await
+}
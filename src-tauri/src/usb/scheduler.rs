@@ -0,0 +1,171 @@
+use std::{collections::HashMap, sync::Mutex};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::UsbDeviceInfo;
+use super::events;
+use super::hello;
+use super::usb_control;
+
+pub const EVENT_AUTOBLOCK_PROMPT: &str = "usb://autoblock-prompt";
+// Unanswered prompts lapse and the device stays blocked
+const PROMPT_TIMEOUT_SECS: i64 = 120;
+
+/// How autoblock treats an untrusted arrival. Ordered from least to most
+/// protective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AutoblockSensitivity {
+    /// Hold the device disabled and ask the user whether to let it in
+    Prompt,
+    /// Block with no prompt
+    Block,
+}
+
+/// A stretch of the week with its own autoblock sensitivity. `end` before
+/// `start` wraps past midnight; equal times cover the whole day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SensitivityBand {
+    pub name: String,
+    /// Days the band starts on; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub sensitivity: AutoblockSensitivity,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoblockPrompt {
+    pub device: UsbDeviceInfo,
+    pub band: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+lazy_static! {
+    static ref BANDS: Mutex<Vec<SensitivityBand>> = Mutex::new(Vec::new());
+    // Keyed by instance ID
+    static ref PROMPTS: Mutex<HashMap<String, AutoblockPrompt>> = Mutex::new(HashMap::new());
+}
+
+/// Whether a weekly window from `start` to `end` on `days` (empty: every
+/// day) covers `at`. `end` before `start` wraps past midnight; equal times
+/// cover the whole day.
+pub fn window_covers(days: &[Weekday], start: NaiveTime, end: NaiveTime, at: NaiveDateTime) -> bool {
+    let time = at.time();
+    let on_day = |day: Weekday| days.is_empty() || days.contains(&day);
+    if start == end {
+        on_day(at.weekday())
+    } else if start < end {
+        on_day(at.weekday()) && time >= start && time < end
+    } else {
+        // Past midnight the window belongs to the day it started on
+        (on_day(at.weekday()) && time >= start) || (on_day(at.weekday().pred()) && time < end)
+    }
+}
+
+fn band_covers(band: &SensitivityBand, at: NaiveDateTime) -> bool {
+    window_covers(&band.days, band.start, band.end, at)
+}
+
+/// The band in force at `at`, first match wins. Outside every band
+/// autoblock blocks without asking.
+pub fn band_at(bands: &[SensitivityBand], at: NaiveDateTime) -> Option<&SensitivityBand> {
+    bands.iter().find(|band| band_covers(band, at))
+}
+
+/// Sensitivity for an arrival now, with the name of the band that set it.
+pub fn current_sensitivity() -> (AutoblockSensitivity, Option<String>) {
+    let bands = BANDS.lock().unwrap();
+    match band_at(&bands, Local::now().naive_local()) {
+        Some(band) => (band.sensitivity, Some(band.name.clone())),
+        None => (AutoblockSensitivity::Block, None),
+    }
+}
+
+/// Ask the user about a device autoblock has just held. It stays disabled
+/// until the prompt is answered.
+pub fn prompt(device: &UsbDeviceInfo, band: Option<String>) {
+    let instance_id = match device.instance_id() {
+        Some(instance_id) => instance_id.to_string(),
+        None => return,
+    };
+    let prompt = AutoblockPrompt {
+        device: device.clone(),
+        band: band.unwrap_or_default(),
+        expires_at: Utc::now() + chrono::Duration::seconds(PROMPT_TIMEOUT_SECS),
+    };
+    PROMPTS.lock().unwrap().insert(instance_id, prompt.clone());
+    events::emit(EVENT_AUTOBLOCK_PROMPT, prompt);
+}
+
+fn prune(prompts: &mut HashMap<String, AutoblockPrompt>) {
+    let now = Utc::now();
+    prompts.retain(|_, prompt| prompt.expires_at > now);
+}
+
+#[command]
+pub fn get_autoblock_schedule() -> Result<Vec<SensitivityBand>, String> {
+    Ok(BANDS.lock().unwrap().clone())
+}
+
+/// Replace the sensitivity bands. Adding or widening a Prompt band lets
+/// devices in on a click, so it needs consent.
+#[command]
+pub fn set_autoblock_schedule(bands: Vec<SensitivityBand>) -> Result<(), String> {
+    for band in &bands {
+        if band.name.trim().is_empty() {
+            return Err("Every sensitivity band needs a name".to_string());
+        }
+    }
+    let previous = BANDS.lock().unwrap().clone();
+    let relaxed = bands
+        .iter()
+        .any(|band| band.sensitivity == AutoblockSensitivity::Prompt && !previous.contains(band));
+    if relaxed {
+        hello::require_consent("let autoblock ask before blocking")?;
+    }
+    audit::record("autoblock_schedule_changed", json!({ "previous": previous, "current": bands }));
+    *BANDS.lock().unwrap() = bands;
+    Ok(())
+}
+
+#[command]
+pub fn get_autoblock_prompts() -> Result<Vec<AutoblockPrompt>, String> {
+    let mut prompts = PROMPTS.lock().unwrap();
+    prune(&mut prompts);
+    Ok(prompts.values().cloned().collect())
+}
+
+/// Answer a pending prompt: `allow` enables the held device, which needs the
+/// admin PIN and consent like any unblock; otherwise it stays blocked.
+/// Lapsed prompts can no longer be answered.
+#[command]
+pub fn answer_autoblock_prompt(instance_id: String, allow: bool, admin_token: Option<String>) -> Result<(), String> {
+    if allow {
+        admin_pin::require_admin(admin_token.as_deref(), "allow a held device")?;
+        hello::require_consent("allow a held device")?;
+    }
+    let prompt = {
+        let mut prompts = PROMPTS.lock().unwrap();
+        prune(&mut prompts);
+        let key = prompts
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(&instance_id))
+            .cloned()
+            .ok_or_else(|| format!("No pending prompt for {}", instance_id))?;
+        prompts.remove(&key).unwrap()
+    };
+    if allow {
+        usb_control::unblock(&instance_id)?;
+    }
+    audit::record(
+        "autoblock_prompt_answered",
+        json!({ "instance_id": instance_id, "band": prompt.band, "allowed": allow }),
+    );
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+
+/// A FIDO key (or any token) whose physical presence unlocks sensitive commands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisteredKey {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Pin to one physical unit; without it any key of the model qualifies
+    pub serial: Option<String>,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityKeyGate {
+    pub enabled: bool,
+    pub keys: Vec<RegisteredKey>,
+}
+
+lazy_static! {
+    static ref GATE: Mutex<SecurityKeyGate> = Mutex::new(SecurityKeyGate {
+        enabled: false,
+        keys: Vec::new(),
+    });
+}
+
+/// Fail unless the gate is off or one of the registered keys is plugged in.
+/// `action` names the command for the audit trail.
+pub fn require_presence(action: &str) -> Result<(), String> {
+    let gate = GATE.lock().unwrap().clone();
+    if !gate.enabled {
+        return Ok(());
+    }
+
+    let present = backend::controller().devnodes()?.iter().find_map(|node| {
+        gate.keys.iter().find(|key| {
+            key.vendor_id == node.vendor_id
+                && key.product_id == node.product_id
+                && key.serial.as_deref().is_none_or(|serial| {
+                    node.serial.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(serial))
+                })
+        })
+    });
+
+    match present {
+        Some(key) => {
+            audit::record("security_key_verified", json!({ "action": action, "key": key.label }));
+            Ok(())
+        }
+        None => {
+            audit::record("security_key_missing", json!({ "action": action }));
+            Err(format!("A registered security key must be plugged in to {}", action))
+        }
+    }
+}
+
+#[command]
+pub fn get_security_key_gate() -> Result<SecurityKeyGate, String> {
+    Ok(GATE.lock().unwrap().clone())
+}
+
+/// Registering keys is always allowed when the gate is off; once on, every
+/// change to the gate itself requires a registered key to be present.
+#[command]
+pub fn register_security_key(key: RegisteredKey) -> Result<(), String> {
+    require_presence("register a security key")?;
+    let mut gate = GATE.lock().unwrap();
+    gate.keys.retain(|k| !(k.vendor_id == key.vendor_id && k.product_id == key.product_id && k.serial == key.serial));
+    audit::record("security_key_registered", json!(key));
+    gate.keys.push(key);
+    Ok(())
+}
+
+#[command]
+pub fn remove_security_key(vendor_id: u16, product_id: u16, serial: Option<String>) -> Result<(), String> {
+    require_presence("remove a security key")?;
+    let mut gate = GATE.lock().unwrap();
+    gate.keys.retain(|k| !(k.vendor_id == vendor_id && k.product_id == product_id && k.serial == serial));
+    if gate.keys.is_empty() {
+        gate.enabled = false;
+    }
+    audit::record(
+        "security_key_removed",
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "serial": serial }),
+    );
+    Ok(())
+}
+
+/// Turning the gate off needs the admin PIN as well as a key.
+#[command]
+pub fn set_security_key_gate(enabled: bool, admin_token: Option<String>) -> Result<(), String> {
+    if !enabled {
+        admin_pin::require_admin(admin_token.as_deref(), "turn off the security key gate")?;
+    }
+    require_presence("change the security key gate")?;
+    let mut gate = GATE.lock().unwrap();
+    if enabled && gate.keys.is_empty() {
+        return Err("Register at least one security key first".to_string());
+    }
+    gate.enabled = enabled;
+    audit::record("security_key_gate_changed", json!({ "enabled": enabled }));
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::{fs, sync::Mutex};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+use winreg::{
+    enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_SET_VALUE},
+    RegKey,
+};
+
+use super::audit;
+use super::backend;
+use super::events;
+use super::helper_client;
+use super::notifications::{self, Severity};
+use super::operations;
+use super::simulation;
+use super::usb_config;
+
+pub const EVENT_STARTUP_HEALTH: &str = "health://startup";
+
+const USBSTOR_KEY: &str = r"SYSTEM\CurrentControlSet\Services\USBSTOR";
+const PROBE_FILE: &str = ".self-test";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+/// Result of the launch self-test. `degraded` lists, in plain words, what
+/// will not work, so the UI can say so before the first block fails.
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupHealth {
+    pub checked_at: DateTime<Utc>,
+    /// Devices and ports can be blocked: elevated, or the helper is running
+    pub can_enforce: bool,
+    pub checks: Vec<HealthCheck>,
+    pub degraded: Vec<String>,
+}
+
+lazy_static! {
+    static ref LAST: Mutex<Option<StartupHealth>> = Mutex::new(None);
+}
+
+fn check(name: &str, result: Result<(), String>) -> HealthCheck {
+    HealthCheck {
+        name: name.to_string(),
+        ok: result.is_ok(),
+        detail: result.err(),
+    }
+}
+
+// Writing the key we manage is the test for elevation that matters here
+pub(crate) fn usbstor_writable() -> Result<(), String> {
+    let root = RegKey::predef(HKEY_LOCAL_MACHINE);
+    root.open_subkey_with_flags(USBSTOR_KEY, KEY_READ)
+        .map_err(|e| format!("USBSTOR service key is missing: {}", e))?;
+    root.open_subkey_with_flags(USBSTOR_KEY, KEY_SET_VALUE)
+        .map(|_| ())
+        .map_err(|e| format!("USBSTOR service key is read-only (not elevated): {}", e))
+}
+
+fn data_dir_writable() -> Result<(), String> {
+    let path = usb_config::data_file(PROBE_FILE)?;
+    fs::write(&path, b"ok").map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+/// Check everything enforcement relies on. Takes well under a second; no
+/// device or policy is touched.
+pub fn run() -> StartupHealth {
+    let controller = backend::controller();
+    let enumeration = check("device_enumeration", controller.list_devices().map(|_| ()));
+    let setupapi = check("setupapi", controller.devnodes().map(|_| ()));
+    let persisted = check("persisted_state", data_dir_writable());
+
+    let mut degraded = Vec::new();
+    if !enumeration.ok {
+        degraded.push("USB devices cannot be listed; nothing can be detected or blocked".to_string());
+    }
+    if !setupapi.ok {
+        degraded.push("Device Manager entries cannot be read; individual devices cannot be blocked".to_string());
+    }
+    if !persisted.ok {
+        degraded.push("Settings cannot be saved; trust and lock changes are lost on restart".to_string());
+    }
+    let devices_reachable = enumeration.ok && setupapi.ok;
+    let mut checks = vec![enumeration, setupapi, persisted];
+
+    // The simulated backend enforces in memory and needs neither
+    let can_enforce = if simulation::is_active() {
+        true
+    } else {
+        let registry = check("registry", usbstor_writable());
+        let helper = check(
+            "helper",
+            if helper_client::is_running() {
+                Ok(())
+            } else {
+                Err("The helper service is not running".to_string())
+            },
+        );
+        let can_enforce = registry.ok || helper.ok;
+        if !can_enforce {
+            degraded.push(
+                "Not elevated and the helper service is not running: blocking devices and ports will fail".to_string(),
+            );
+        }
+        checks.push(registry);
+        checks.push(helper);
+        can_enforce
+    };
+
+    StartupHealth {
+        checked_at: Utc::now(),
+        can_enforce: can_enforce && devices_reachable,
+        checks,
+        degraded,
+    }
+}
+
+/// Run the self-test once at launch, after the backend is up, and report it.
+pub fn run_at_startup() {
+    let health = run();
+    audit::record("startup_self_test", json!(health));
+    if !health.degraded.is_empty() {
+        notifications::notify(
+            Severity::Critical,
+            "startup_degraded",
+            "USB-Shield is running in degraded mode",
+            &health.degraded.join("\n"),
+        );
+    }
+    *LAST.lock().unwrap() = Some(health.clone());
+    events::emit(EVENT_STARTUP_HEALTH, health);
+}
+
+/// Whether the last self-test found anything degraded.
+pub fn degraded() -> bool {
+    LAST.lock().unwrap().as_ref().is_some_and(|health| !health.degraded.is_empty())
+}
+
+#[command]
+pub fn get_startup_health() -> Result<Option<StartupHealth>, String> {
+    Ok(LAST.lock().unwrap().clone())
+}
+
+/// Re-run the self-test, e.g. after starting the helper service.
+#[command]
+pub async fn run_self_test() -> Result<StartupHealth, String> {
+    operations::run("run_self_test", || {
+        let health = run();
+        *LAST.lock().unwrap() = Some(health.clone());
+        events::emit(EVENT_STARTUP_HEALTH, health.clone());
+        Ok(health)
+    })
+    .await
+}
@@ -0,0 +1,84 @@
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::DeviceAndDriverInstallation::{
+            CM_Get_Child, CM_Get_Sibling, CM_Open_DevNode_Key, RegDisposition_OpenExisting, CM_REGISTRY_HARDWARE,
+            CR_SUCCESS,
+        },
+        System::Registry::{RegCloseKey, RegQueryValueExW, HKEY, KEY_READ, REG_SZ, REG_VALUE_TYPE},
+    },
+};
+
+use super::correlation::{from_wide, wide};
+
+// FTDI and similar vendor drivers put the COM port two levels down:
+// USB device -> FTDIBUS\... -> (port). CDC-ACM composites put it on the MI_xx child.
+const MAX_DEPTH: u32 = 3;
+
+/// Resolve the COM port (e.g. `COM7`) assigned to a device or any function
+/// below it, by reading `PortName` from the devnode hardware keys.
+pub fn com_port(dev_inst: u32) -> Option<String> {
+    find_port_name(dev_inst, 0)
+}
+
+fn find_port_name(dev_inst: u32, depth: u32) -> Option<String> {
+    if let Some(port) = read_port_name(dev_inst) {
+        return Some(port);
+    }
+    if depth >= MAX_DEPTH {
+        return None;
+    }
+
+    unsafe {
+        let mut child = 0u32;
+        if CM_Get_Child(&mut child, dev_inst, 0) != CR_SUCCESS {
+            return None;
+        }
+        loop {
+            if let Some(port) = find_port_name(child, depth + 1) {
+                return Some(port);
+            }
+            let mut sibling = 0u32;
+            if CM_Get_Sibling(&mut sibling, child, 0) != CR_SUCCESS {
+                return None;
+            }
+            child = sibling;
+        }
+    }
+}
+
+fn read_port_name(dev_inst: u32) -> Option<String> {
+    unsafe {
+        let mut key = HKEY::default();
+        if CM_Open_DevNode_Key(dev_inst, KEY_READ.0, 0, RegDisposition_OpenExisting, &mut key, CM_REGISTRY_HARDWARE)
+            != CR_SUCCESS
+        {
+            return None;
+        }
+
+        let name = wide("PortName");
+        let mut value_type = REG_VALUE_TYPE::default();
+        let mut buffer = [0u16; 64];
+        let mut size = (buffer.len() * 2) as u32;
+        let status = RegQueryValueExW(
+            key,
+            PCWSTR(name.as_ptr()),
+            None,
+            Some(&mut value_type),
+            Some(buffer.as_mut_ptr() as *mut u8),
+            Some(&mut size),
+        );
+        RegCloseKey(key);
+
+        if status.is_err() || value_type != REG_SZ {
+            return None;
+        }
+        let port = from_wide(&buffer);
+        // Parallel ports (LPTn) also use PortName; only report serial ports
+        if port.to_ascii_uppercase().starts_with("COM") {
+            Some(port)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use serde_json::json;
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::{
+        Foundation::{
+            BOOL, ERROR_DEPENDENT_SERVICES_RUNNING, ERROR_INVALID_SERVICE_CONTROL, ERROR_SERVICE_ALREADY_RUNNING,
+            ERROR_SERVICE_DISABLED, ERROR_SERVICE_NOT_ACTIVE, WIN32_ERROR,
+        },
+        Security::SC_HANDLE,
+        System::{
+            GroupPolicy::{RefreshPolicyEx, RP_FORCE},
+            Services::{
+                CloseServiceHandle, ControlService, OpenSCManagerW, OpenServiceW, QueryServiceStatus,
+                StartServiceW, SC_MANAGER_CONNECT, SERVICE_CONTROL_STOP, SERVICE_QUERY_STATUS,
+                SERVICE_RUNNING, SERVICE_START, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP,
+                SERVICE_STOPPED,
+            },
+        },
+    },
+};
+
+use super::audit;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Closes the handle on every return path
+struct Handle(SC_HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseServiceHandle(self.0);
+        }
+    }
+}
+
+fn is(error: &windows::core::Error, code: WIN32_ERROR) -> bool {
+    error.code() == code.to_hresult()
+}
+
+fn open(service: &str, access: u32) -> Result<Handle, String> {
+    unsafe {
+        let manager = OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT)
+            .map(Handle)
+            .map_err(|e| format!("Cannot connect to the Service Control Manager: {}", e))?;
+        OpenServiceW(manager.0, &HSTRING::from(service), access)
+            .map(Handle)
+            .map_err(|e| format!("Cannot open the {} service: {}", service, e))
+    }
+}
+
+fn current_state(service: &str, handle: &Handle) -> Result<SERVICE_STATUS_CURRENT_STATE, String> {
+    let mut status = SERVICE_STATUS::default();
+    unsafe { QueryServiceStatus(handle.0, &mut status) }
+        .ok()
+        .map_err(|e| format!("Cannot query the {} service: {}", service, e))?;
+    Ok(status.dwCurrentState)
+}
+
+fn wait_for(
+    service: &str,
+    handle: &Handle,
+    state: SERVICE_STATUS_CURRENT_STATE,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    while current_state(service, handle)? != state {
+        if Instant::now() >= deadline {
+            return Err(format!("The {} service did not settle within {}s", service, timeout.as_secs()));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+fn record(action: &str, service: &str, started: Instant, result: &Result<(), String>) {
+    audit::record(
+        "service_control",
+        json!({
+            "action": action,
+            "service": service,
+            "duration_ms": started.elapsed().as_millis() as u64,
+            "error": result.as_ref().err(),
+        }),
+    );
+}
+
+/// Stop `service` and wait until it has. A service that is not running is
+/// already where we want it.
+pub fn stop(service: &str, timeout: Duration) -> Result<(), String> {
+    let started = Instant::now();
+    let result = open(service, SERVICE_STOP | SERVICE_QUERY_STATUS).and_then(|handle| {
+        let mut status = SERVICE_STATUS::default();
+        match unsafe { ControlService(handle.0, SERVICE_CONTROL_STOP, &mut status) }.ok() {
+            Ok(()) => wait_for(service, &handle, SERVICE_STOPPED, timeout),
+            Err(e) if is(&e, ERROR_SERVICE_NOT_ACTIVE) => Ok(()),
+            // A storage driver refuses to stop while a volume it serves is mounted
+            Err(e) if is(&e, ERROR_INVALID_SERVICE_CONTROL) || is(&e, ERROR_DEPENDENT_SERVICES_RUNNING) => {
+                Err(format!("The {} service is in use and cannot be stopped: {}", service, e))
+            }
+            Err(e) => Err(format!("Failed to stop the {} service: {}", service, e)),
+        }
+    });
+    record("stop", service, started, &result);
+    result
+}
+
+/// Start `service` and wait until it runs. A disabled service stays
+/// stopped, which is what a block asks for.
+pub fn start(service: &str, timeout: Duration) -> Result<(), String> {
+    let started = Instant::now();
+    let result = open(service, SERVICE_START | SERVICE_QUERY_STATUS).and_then(|handle| {
+        match unsafe { StartServiceW(handle.0, None) }.ok() {
+            Ok(()) => wait_for(service, &handle, SERVICE_RUNNING, timeout),
+            Err(e) if is(&e, ERROR_SERVICE_ALREADY_RUNNING) || is(&e, ERROR_SERVICE_DISABLED) => Ok(()),
+            Err(e) => Err(format!("Failed to start the {} service: {}", service, e)),
+        }
+    });
+    record("start", service, started, &result);
+    result
+}
+
+/// Ask Group Policy to re-apply machine and user policy now, like
+/// `gpupdate /force`. The refresh itself runs in the background.
+pub fn refresh_policy() -> Result<(), String> {
+    let started = Instant::now();
+    let result = [true, false].into_iter().try_for_each(|machine| {
+        unsafe { RefreshPolicyEx(BOOL::from(machine), RP_FORCE) }.ok().map_err(|e| {
+            format!(
+                "Failed to refresh {} Group Policy: {}",
+                if machine { "machine" } else { "user" },
+                e
+            )
+        })
+    });
+    record("refresh_policy", "gpsvc", started, &result);
+    result
+}
@@ -0,0 +1,400 @@
+use std::{
+    fs,
+    io::Write,
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use chrono::Utc;
+use lazy_static::lazy_static;
+use native_tls::{TlsConnector, TlsStream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::command;
+
+use super::audit::{self, AuditEntry};
+use super::hello;
+use super::operations;
+use super::usb_config;
+
+const SIEM_FILE: &str = "siem.json";
+const APP_NAME: &str = "USB-Shield";
+// RFC 5424 facility 13, "log audit"
+const SYSLOG_FACILITY: u8 = 13;
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Entries waiting while the collector is slow or down; newer ones are
+// dropped beyond this rather than holding memory without bound
+const QUEUE_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiemTransport {
+    Udp,
+    Tcp,
+    /// Syslog over TLS (RFC 5425)
+    Tls,
+    /// One POST per event
+    Http,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiemFormat {
+    /// ArcSight Common Event Format
+    Cef,
+    Json,
+}
+
+/// Where audit events are forwarded to, if anywhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiemSettings {
+    pub enabled: bool,
+    pub transport: SiemTransport,
+    /// `host:port` for syslog, a URL for HTTP
+    pub endpoint: String,
+    pub format: SiemFormat,
+}
+
+impl Default for SiemSettings {
+    fn default() -> Self {
+        SiemSettings {
+            enabled: false,
+            transport: SiemTransport::Udp,
+            endpoint: String::new(),
+            format: SiemFormat::Cef,
+        }
+    }
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Connection {
+    fn send(&mut self, frame: &[u8]) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.write_all(frame),
+            Connection::Tls(stream) => stream.write_all(frame),
+        }
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: Mutex<SiemSettings> = Mutex::new(SiemSettings::default());
+    // Each entry goes with the settings in force when it was recorded
+    static ref QUEUE: Mutex<Option<SyncSender<(SiemSettings, AuditEntry)>>> = Mutex::new(None);
+}
+
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "-".to_string())
+}
+
+// Syslog severity and CEF severity (0-10) of an audited action
+fn severity(action: &str) -> (&'static str, u8, u8) {
+    if action.contains("tamper") || action.contains("threat") {
+        ("critical", 2, 10)
+    } else if ["blocked", "quarantined", "disabled", "failed"].iter().any(|word| action.contains(word)) {
+        ("warning", 4, 6)
+    } else {
+        ("info", 6, 3)
+    }
+}
+
+fn cef_header(value: &str) -> String {
+    value.replace('\\', r"\\").replace('|', r"\|")
+}
+
+fn cef_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('=', r"\=")
+        .replace('\r', r"\r")
+        .replace('\n', r"\n")
+}
+
+fn cef(entry: &AuditEntry) -> String {
+    let (_, _, cef_severity) = severity(&entry.action);
+    let mut extension = format!(
+        "rt={} act={} dvchost={}",
+        entry.timestamp.timestamp_millis(),
+        cef_value(&entry.action),
+        cef_value(&hostname())
+    );
+    if let Value::Object(fields) = &entry.details {
+        for (key, value) in fields {
+            let key: String = key.chars().filter(char::is_ascii_alphanumeric).collect();
+            let value = match value {
+                Value::Null => continue,
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            extension.push_str(&format!(" {}={}", key, cef_value(&value)));
+        }
+    }
+    format!(
+        "CEF:0|{app}|{app}|{}|{}|{}|{}|{}",
+        env!("CARGO_PKG_VERSION"),
+        cef_header(&entry.action),
+        cef_header(&entry.action.replace('_', " ")),
+        cef_severity,
+        extension,
+        app = APP_NAME,
+    )
+}
+
+fn body(format: SiemFormat, entry: &AuditEntry) -> String {
+    match format {
+        SiemFormat::Cef => cef(entry),
+        SiemFormat::Json => json!({
+            "timestamp": entry.timestamp,
+            "host": hostname(),
+            "source": APP_NAME,
+            "severity": severity(&entry.action).0,
+            "action": entry.action,
+            "details": entry.details,
+        })
+        .to_string(),
+    }
+}
+
+// RFC 5424, with the action as MSGID
+fn syslog_line(format: SiemFormat, entry: &AuditEntry) -> String {
+    let (_, syslog_severity, _) = severity(&entry.action);
+    let msg_id: String = entry.action.chars().filter(|c| c.is_ascii_graphic()).take(32).collect();
+    format!(
+        "<{}>1 {} {} {} - {} - {}",
+        SYSLOG_FACILITY * 8 + syslog_severity,
+        entry.timestamp.to_rfc3339(),
+        hostname(),
+        APP_NAME,
+        if msg_id.is_empty() { "-".to_string() } else { msg_id },
+        body(format, entry)
+    )
+}
+
+fn host_of(endpoint: &str) -> Result<&str, String> {
+    match endpoint.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+            Ok(host.trim_matches(&['[', ']'][..]))
+        }
+        _ => Err(format!("'{}' is not a host:port endpoint", endpoint)),
+    }
+}
+
+fn validate(settings: &SiemSettings) -> Result<(), String> {
+    if settings.transport == SiemTransport::Http {
+        if settings.endpoint.starts_with("http://") || settings.endpoint.starts_with("https://") {
+            Ok(())
+        } else {
+            Err(format!("'{}' is not an http:// or https:// URL", settings.endpoint))
+        }
+    } else {
+        host_of(&settings.endpoint).map(|_| ())
+    }
+}
+
+fn connect(settings: &SiemSettings) -> Result<Connection, String> {
+    let address = settings
+        .endpoint
+        .to_socket_addrs()
+        .map_err(|e| format!("Cannot resolve {}: {}", settings.endpoint, e))?
+        .next()
+        .ok_or_else(|| format!("{} did not resolve to an address", settings.endpoint))?;
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT)
+        .map_err(|e| format!("Cannot connect to {}: {}", settings.endpoint, e))?;
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+    if settings.transport != SiemTransport::Tls {
+        return Ok(Connection::Tcp(stream));
+    }
+    let connector = TlsConnector::new().map_err(|e| e.to_string())?;
+    connector
+        .connect(host_of(&settings.endpoint)?, stream)
+        .map(|stream| Connection::Tls(Box::new(stream)))
+        .map_err(|e| format!("TLS handshake with {} failed: {}", settings.endpoint, e))
+}
+
+fn post(settings: &SiemSettings, entry: &AuditEntry) -> Result<(), String> {
+    let connector = TlsConnector::new().map_err(|e| e.to_string())?;
+    let agent = ureq::AgentBuilder::new()
+        .tls_connector(Arc::new(connector))
+        .timeout(TIMEOUT)
+        .build();
+    let content_type = match settings.format {
+        SiemFormat::Cef => "text/plain",
+        SiemFormat::Json => "application/json",
+    };
+    agent
+        .post(&settings.endpoint)
+        .set("Content-Type", content_type)
+        .send_string(&body(settings.format, entry))
+        .map(|_| ())
+        .map_err(|e| format!("POST to {} failed: {}", settings.endpoint, e))
+}
+
+// Stream connections are kept open between events, with the settings they
+// were opened for
+fn send(
+    settings: &SiemSettings,
+    open: &mut Option<(SiemSettings, Connection)>,
+    entry: &AuditEntry,
+) -> Result<(), String> {
+    match settings.transport {
+        SiemTransport::Http => post(settings, entry),
+        SiemTransport::Udp => {
+            let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+            socket
+                .send_to(syslog_line(settings.format, entry).as_bytes(), settings.endpoint.as_str())
+                .map(|_| ())
+                .map_err(|e| format!("Cannot send to {}: {}", settings.endpoint, e))
+        }
+        SiemTransport::Tcp | SiemTransport::Tls => {
+            // Octet counting, so a message may contain newlines
+            let line = syslog_line(settings.format, entry);
+            let frame = format!("{} {}", line.len(), line);
+            if open.as_ref().is_none_or(|(opened_for, _)| opened_for != settings) {
+                *open = Some((settings.clone(), connect(settings)?));
+            }
+            let (_, connection) = open.as_mut().unwrap();
+            if connection.send(frame.as_bytes()).is_ok() {
+                return Ok(());
+            }
+            // The collector may have closed an idle connection; retry once
+            let mut connection = connect(settings)?;
+            let result = connection
+                .send(frame.as_bytes())
+                .map_err(|e| format!("Cannot send to {}: {}", settings.endpoint, e));
+            *open = result.is_ok().then(|| (settings.clone(), connection));
+            result
+        }
+    }
+}
+
+fn run(queue: Receiver<(SiemSettings, AuditEntry)>) {
+    let mut open = None;
+    for (settings, entry) in queue {
+        if let Err(e) = send(&settings, &mut open, &entry) {
+            open = None;
+            log::warn!("SIEM forwarding: {}", e);
+        }
+    }
+}
+
+fn ensure_worker() {
+    let mut queue = QUEUE.lock().unwrap();
+    if queue.is_none() {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        thread::spawn(move || run(receiver));
+        *queue = Some(sender);
+    }
+}
+
+/// Queue an audit entry for the collector, if forwarding is on. Never
+/// waits on the network.
+pub fn forward(entry: &AuditEntry) {
+    let settings = SETTINGS.lock().unwrap().clone();
+    if !settings.enabled {
+        return;
+    }
+    if let Some(queue) = QUEUE.lock().unwrap().as_ref() {
+        if let Err(TrySendError::Full(_)) = queue.try_send((settings, entry.clone())) {
+            log::warn!("SIEM forwarding is behind; dropped audit entry '{}'", entry.action);
+        }
+    }
+}
+
+/// Queue a notification routed to the Syslog channel. It goes to the
+/// configured collector even while audit forwarding is off, so routing
+/// only alerts there is possible; fails if no collector is set up.
+pub fn send_notification(kind: &str, details: Value) -> Result<(), String> {
+    let settings = SETTINGS.lock().unwrap().clone();
+    validate(&settings).map_err(|e| format!("No SIEM collector is configured: {}", e))?;
+    ensure_worker();
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        action: kind.to_string(),
+        details,
+    };
+    match QUEUE.lock().unwrap().as_ref() {
+        Some(queue) => queue.try_send((settings, entry)).map_err(|e| match e {
+            TrySendError::Full(_) => "SIEM forwarding is behind; notification dropped".to_string(),
+            TrySendError::Disconnected(_) => "SIEM forwarding has stopped".to_string(),
+        }),
+        None => Err("SIEM forwarding has not started".to_string()),
+    }
+}
+
+fn save(settings: &SiemSettings) -> Result<(), String> {
+    let path = usb_config::data_file(SIEM_FILE)?;
+    let data = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Load the saved forwarding settings and start forwarding if they say so.
+/// Called from setup.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(SIEM_FILE)?;
+    let settings = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => SiemSettings::default(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    *SETTINGS.lock().unwrap() = settings;
+    ensure_worker();
+    Ok(())
+}
+
+#[command]
+pub fn get_siem_forwarding() -> Result<SiemSettings, String> {
+    Ok(SETTINGS.lock().unwrap().clone())
+}
+
+/// Change where audit events are forwarded. Changing or turning off
+/// forwarding that is on needs consent: it is how a SOC would notice
+/// protection being relaxed.
+#[command]
+pub fn configure_siem_forwarding(settings: SiemSettings) -> Result<SiemSettings, String> {
+    if settings.enabled {
+        validate(&settings)?;
+    }
+    let previous = SETTINGS.lock().unwrap().clone();
+    if previous.enabled && previous != settings {
+        hello::require_consent("change SIEM forwarding")?;
+    }
+    save(&settings)?;
+    ensure_worker();
+    // Recorded under the old settings, so the collector sees itself dropped
+    audit::record(
+        "siem_forwarding_changed",
+        json!({
+            "enabled": settings.enabled,
+            "transport": settings.transport,
+            "endpoint": settings.endpoint,
+            "format": settings.format,
+        }),
+    );
+    *SETTINGS.lock().unwrap() = settings.clone();
+    Ok(settings)
+}
+
+/// Send one test event to the configured endpoint, whether or not
+/// forwarding is on, and report whether it got there. UDP can only report
+/// that it was sent.
+#[command]
+pub async fn test_siem_connection() -> Result<(), String> {
+    operations::run("test_siem_connection", || {
+        let settings = SETTINGS.lock().unwrap().clone();
+        validate(&settings)?;
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            action: "siem_test".to_string(),
+            details: json!({ "message": "Test event from USB-Shield" }),
+        };
+        send(&settings, &mut None, &entry)
+    })
+    .await
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use hmac::{Hmac, Mac};
+use once_cell::sync::OnceCell;
+use rand::RngCore;
+use sha2::Sha256;
+
+use super::usb_config;
+
+const KEY_FILE: &str = "signing.key";
+
+static KEY: OnceCell<Vec<u8>> = OnceCell::new();
+
+/// Machine-local HMAC key, generated on first use and kept in the app data dir.
+fn key() -> Result<&'static [u8], String> {
+    KEY.get_or_try_init(|| {
+        let path = usb_config::data_file(KEY_FILE)?;
+        if let Ok(existing) = fs::read(&path) {
+            if existing.len() == 32 {
+                return Ok(existing);
+            }
+        }
+        let mut key = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        fs::write(&path, &key).map_err(|e| format!("Failed to write signing key: {}", e))?;
+        Ok(key)
+    })
+    .map(|key| key.as_slice())
+}
+
+/// HMAC-SHA256 over `data`, hex encoded.
+pub fn sign(data: &[u8]) -> Result<String, String> {
+    sign_with(key()?, data)
+}
+
+/// Like `sign`, with a caller-supplied key instead of the machine key, for
+/// data another machine has to verify.
+pub fn sign_with(key: &[u8], data: &[u8]) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(data);
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn verify_with(key: &[u8], data: &[u8], signature: &str) -> Result<bool, String> {
+    let expected = hex::decode(signature).map_err(|_| "Signature is not valid hex".to_string())?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(data);
+    Ok(mac.verify_slice(&expected).is_ok())
+}
@@ -0,0 +1,816 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+use uport_shield_helper::protocol::{Failure, RemovableStoragePolicy, WakeState};
+
+use super::backend::{RawDevice, UsbController};
+use super::category::{InterfaceClass, CLASS_HID, CLASS_HUB, CLASS_MASS_STORAGE, CLASS_SMART_CARD};
+use super::correlation::{parse_interface_number, parse_serial, parse_vid_pid, DevNode, InterfaceNode};
+use super::device_power::SelectiveSuspend;
+use super::drivers::{DriverInfo, FunctionDriver};
+use super::events;
+use super::hotplug;
+use super::type_c::{PartnerKind, PowerContract, TypeCPort};
+use super::volumes::{IoCounters, Volume};
+
+// `USB_SHIELD_SIMULATION=1` runs the built-in demo scenario,
+// `USB_SHIELD_SIMULATION=path\to\script.json` a scripted one, `0` forces hardware.
+// Only debug builds and builds with the `simulation` feature honour it.
+const ENV_VAR: &str = "USB_SHIELD_SIMULATION";
+// Same event the frontend already refreshes on
+const EVENT_DEVICE_CHANGED: &str = "usb-device-changed";
+
+lazy_static! {
+    static ref STATE: Mutex<SimState> = Mutex::new(SimState::default());
+}
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// A scripted device. Its instance ID carries the VID/PID (and serial) the
+/// same way a real one does, so correlation treats it like hardware.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimDevice {
+    pub instance_id: String,
+    #[serde(default)]
+    pub device_class: u8,
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    #[serde(default)]
+    pub product: Option<String>,
+    #[serde(default = "default_bus")]
+    pub bus_number: u8,
+    #[serde(default)]
+    pub ports: Vec<u8>,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceClass>,
+    #[serde(default)]
+    pub parent_instance_id: Option<String>,
+    #[serde(default)]
+    pub container_id: Option<String>,
+    /// E.g. `ROOT\USBIP_WIN2\UDE` to emulate a USB/IP attach
+    #[serde(default)]
+    pub controller_instance_id: Option<String>,
+    #[serde(default)]
+    pub com_port: Option<String>,
+    /// Mounted while the device is enabled
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// `None` leaves the driver default in place
+    #[serde(default)]
+    pub selective_suspend: Option<bool>,
+    /// `None` for devices that cannot wake the machine at all
+    #[serde(default)]
+    pub wake_armed: Option<bool>,
+    /// Hubs only: the hub switches power per port
+    #[serde(default)]
+    pub port_power_switching: bool,
+    /// Composite devices only: interface numbers whose function is disabled
+    #[serde(default)]
+    pub disabled_interfaces: Vec<u8>,
+    #[serde(default)]
+    pub driver: Option<DriverInfo>,
+    #[serde(default)]
+    pub function_drivers: Vec<FunctionDriver>,
+}
+
+impl SimDevice {
+    // Each interface of a multi-interface device gets its own child devnode,
+    // as usbccgp creates them
+    fn is_composite(&self) -> bool {
+        self.interfaces.len() > 1 && (self.device_class == 0 || self.device_class == 0xEF)
+    }
+
+    /// `USB\VID_x&PID_y\serial` -> `USB\VID_x&PID_y&MI_nn\serial&00nn`
+    fn interface_instance_id(&self, number: u8) -> String {
+        let (ids, rest) = self.instance_id.rsplit_once('\\').unwrap_or((&self.instance_id, ""));
+        format!("{}&MI_{:02X}\\{}&{:04X}", ids, number, rest, number)
+    }
+
+    // A disabled mass storage function takes the device's volumes with it
+    fn storage_disabled(&self) -> bool {
+        self.disabled_interfaces
+            .iter()
+            .any(|&n| self.interfaces.get(n as usize).is_some_and(|i| i.class_code == CLASS_MASS_STORAGE))
+    }
+}
+
+fn default_bus() -> u8 {
+    1
+}
+
+// Shaped like a real one, with the bus standing in for the controller
+fn location_path(bus: u8, ports: &[u8]) -> Option<String> {
+    if ports.is_empty() {
+        return None;
+    }
+    let ports: Vec<String> = ports.iter().map(|port| format!("USB({})", port)).collect();
+    Some(format!("PCIROOT(0)#PCI({:02X}00)#USBROOT(0)#{}", bus, ports.join("#")))
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Make the next `count` state changes on a device fail. With `hang_ms` the
+/// call stalls instead, which exercises the watchdog when it exceeds the
+/// operation timeout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureSpec {
+    pub instance_id: String,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    #[serde(default)]
+    pub hang_ms: Option<u64>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum SimAction {
+    Attach { device: Box<SimDevice> },
+    Detach { instance_id: String },
+    InjectFailure { failure: FailureSpec },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TimelineStep {
+    /// Seconds after startup
+    after_secs: u64,
+    #[serde(flatten)]
+    action: SimAction,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Script {
+    #[serde(default)]
+    devices: Vec<SimDevice>,
+    #[serde(default)]
+    timeline: Vec<TimelineStep>,
+    #[serde(default)]
+    type_c_ports: Vec<TypeCPort>,
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    devices: Vec<SimDevice>,
+    failures: HashMap<String, FailureSpec>,
+    usbstor_start: u32,
+    // Per mount point, as the volume manager would count them
+    io: HashMap<String, IoCounters>,
+    read_only: HashSet<String>,
+    storage_write_protect: bool,
+    // Class GUID (lower case) -> machine-wide removable storage policy
+    removable_storage: HashMap<String, RemovableStoragePolicy>,
+    type_c_ports: Vec<TypeCPort>,
+    // Hub instance ID (upper case) and port -> devices that lost power there
+    unpowered: HashMap<(String, u8), Vec<SimDevice>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationStatus {
+    pub active: bool,
+    pub usbstor_start: u32,
+    pub devices: Vec<SimDevice>,
+    pub pending_failures: Vec<FailureSpec>,
+    /// Write-protected mount points
+    pub read_only: Vec<String>,
+    pub storage_write_protect: bool,
+}
+
+/// `None` when the hardware backend should be used, otherwise the script
+/// path (`Some(None)` for the built-in scenario). Release builds without the
+/// `simulation` feature ignore the variable: anyone who can set it must not
+/// be able to swap enforcement for a fake backend.
+pub fn requested() -> Option<Option<String>> {
+    if !cfg!(any(feature = "simulation", debug_assertions)) {
+        return None;
+    }
+    match std::env::var(ENV_VAR) {
+        Ok(value) if value == "0" => None,
+        Ok(value) if value.is_empty() || value == "1" => Some(None),
+        Ok(path) => Some(Some(path)),
+        Err(_) if cfg!(feature = "simulation") => Some(None),
+        Err(_) => None,
+    }
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+pub struct SimulatedController;
+
+/// Replace the simulated machine with a scenario (the script file format)
+/// and start playing its timeline. Pending failures are discarded.
+pub fn load_script(script_json: &str) -> Result<(), String> {
+    let script: Script = serde_json::from_str(script_json).map_err(|e| format!("Invalid simulation script: {}", e))?;
+    start(script);
+    Ok(())
+}
+
+/// Load the script file, or the built-in demo scenario for `None`.
+pub fn load_script_file(path: Option<&str>) -> Result<(), String> {
+    match path {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read simulation script {}: {}", path, e))?;
+            load_script(&text).map_err(|e| format!("{} ({})", e, path))
+        }
+        None => {
+            start(demo_script());
+            Ok(())
+        }
+    }
+}
+
+fn start(script: Script) {
+    {
+        let mut state = STATE.lock().unwrap();
+        state.devices = script.devices;
+        state.type_c_ports = script.type_c_ports;
+        state.failures.clear();
+        state.io.clear();
+        state.read_only.clear();
+        state.unpowered.clear();
+        state.usbstor_start = 3;
+        state.storage_write_protect = false;
+        state.removable_storage.clear();
+    }
+    ACTIVE.store(true, Ordering::SeqCst);
+
+    let mut timeline = script.timeline;
+    if !timeline.is_empty() {
+        timeline.sort_by_key(|step| step.after_secs);
+        thread::spawn(move || play(timeline));
+    }
+}
+
+fn play(timeline: Vec<TimelineStep>) {
+    let started = Instant::now();
+    for step in timeline {
+        let at = started + Duration::from_secs(step.after_secs);
+        if let Some(wait) = at.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        let result = match step.action {
+            SimAction::Attach { device } => attach(*device),
+            SimAction::Detach { instance_id } => detach(&instance_id).map_err(String::from),
+            SimAction::InjectFailure { failure } => {
+                inject_failure(failure);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            log::error!("Simulation step at {}s failed: {}", step.after_secs, e);
+        }
+    }
+}
+
+fn attach(device: SimDevice) -> Result<(), String> {
+    if parse_vid_pid(&device.instance_id).is_none() {
+        return Err(format!("Instance ID {} carries no VID/PID", device.instance_id));
+    }
+    let mut state = STATE.lock().unwrap();
+    state.devices.retain(|d| !d.instance_id.eq_ignore_ascii_case(&device.instance_id));
+    state.devices.push(device);
+    drop(state);
+    events::emit(EVENT_DEVICE_CHANGED, ());
+    hotplug::notify();
+    Ok(())
+}
+
+fn detach(instance_id: &str) -> Result<(), Failure> {
+    let mut state = STATE.lock().unwrap();
+    let before = state.devices.len();
+    state.devices.retain(|d| !d.instance_id.eq_ignore_ascii_case(instance_id));
+    if state.devices.len() == before {
+        return Err(Failure::device_not_found(instance_id));
+    }
+    drop(state);
+    events::emit(EVENT_DEVICE_CHANGED, ());
+    hotplug::notify();
+    Ok(())
+}
+
+fn inject_failure(failure: FailureSpec) {
+    STATE
+        .lock()
+        .unwrap()
+        .failures
+        .insert(failure.instance_id.to_ascii_uppercase(), failure);
+}
+
+impl UsbController for SimulatedController {
+    fn list_devices(&self) -> Result<Vec<RawDevice>, String> {
+        // Like libusb, disabled devices drop out of the listing
+        Ok(STATE
+            .lock()
+            .unwrap()
+            .devices
+            .iter()
+            .filter(|d| d.enabled)
+            .filter_map(|d| {
+                let (vendor_id, product_id) = parse_vid_pid(&d.instance_id)?;
+                Some(RawDevice {
+                    vendor_id,
+                    product_id,
+                    device_class: d.device_class,
+                    manufacturer: d.manufacturer.clone(),
+                    product: d.product.clone(),
+                    serial_number: parse_serial(&d.instance_id),
+                    bus_number: d.bus_number,
+                    ports: d.ports.clone(),
+                    interfaces: d.interfaces.clone(),
+                })
+            })
+            .collect())
+    }
+
+    fn devnodes(&self) -> Result<Vec<DevNode>, String> {
+        Ok(STATE
+            .lock()
+            .unwrap()
+            .devices
+            .iter()
+            .filter_map(|d| {
+                let (vendor_id, product_id) = parse_vid_pid(&d.instance_id)?;
+                Some(DevNode {
+                    instance_id: d.instance_id.clone(),
+                    parent_instance_id: d.parent_instance_id.clone(),
+                    vendor_id,
+                    product_id,
+                    description: d.product.clone(),
+                    serial: parse_serial(&d.instance_id),
+                    port_chain: d.ports.clone(),
+                    location_path: location_path(d.bus_number, &d.ports),
+                    container_id: d.container_id.clone(),
+                    controller_instance_id: d.controller_instance_id.clone(),
+                    dev_inst: 0,
+                    started: d.enabled,
+                    disabled: !d.enabled,
+                    driver: d.driver.clone(),
+                    function_drivers: d.function_drivers.clone(),
+                })
+            })
+            .collect())
+    }
+
+    fn interface_nodes(&self, instance_id: &str) -> Result<Vec<InterfaceNode>, String> {
+        let state = STATE.lock().unwrap();
+        let device = state
+            .devices
+            .iter()
+            .find(|d| d.instance_id.eq_ignore_ascii_case(instance_id))
+            .ok_or_else(|| format!("Device not found: {}", instance_id))?;
+        if !device.enabled || !device.is_composite() {
+            return Ok(Vec::new());
+        }
+        Ok(device
+            .interfaces
+            .iter()
+            .enumerate()
+            .map(|(number, class)| InterfaceNode {
+                instance_id: device.interface_instance_id(number as u8),
+                interface_number: number as u8,
+                class: Some(*class),
+                description: None,
+                disabled: device.disabled_interfaces.contains(&(number as u8)),
+            })
+            .collect())
+    }
+
+    fn com_port(&self, node: &DevNode) -> Option<String> {
+        STATE
+            .lock()
+            .unwrap()
+            .devices
+            .iter()
+            .find(|d| d.instance_id.eq_ignore_ascii_case(&node.instance_id))
+            .and_then(|d| d.com_port.clone())
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>, String> {
+        Ok(STATE
+            .lock()
+            .unwrap()
+            .devices
+            .iter()
+            .filter(|d| d.enabled && !d.storage_disabled())
+            .flat_map(|d| {
+                d.volumes.iter().map(move |volume| Volume {
+                    device_instance_id: d.instance_id.clone(),
+                    ..volume.clone()
+                })
+            })
+            .collect())
+    }
+
+    fn io_counters(&self, mount_point: &str) -> Result<IoCounters, String> {
+        let state = STATE.lock().unwrap();
+        let mounted = state
+            .devices
+            .iter()
+            .filter(|d| d.enabled && !d.storage_disabled())
+            .any(|d| d.volumes.iter().any(|v| v.mount_point.eq_ignore_ascii_case(mount_point)));
+        if !mounted {
+            return Err(format!("Failed to open {}", mount_point));
+        }
+        Ok(state.io.get(&mount_point.to_ascii_uppercase()).copied().unwrap_or_default())
+    }
+
+    fn set_device_state(&self, instance_id: &str, enable: bool) -> Result<(), Failure> {
+        let key = instance_id.to_ascii_uppercase();
+        let failure = {
+            let mut state = STATE.lock().unwrap();
+            let failure = state.failures.get_mut(&key).map(|f| {
+                f.count = f.count.saturating_sub(1);
+                f.clone()
+            });
+            if failure.as_ref().is_some_and(|f| f.count == 0) {
+                state.failures.remove(&key);
+            }
+            failure
+        };
+
+        match failure {
+            Some(FailureSpec { hang_ms: Some(ms), .. }) => thread::sleep(Duration::from_millis(ms)),
+            Some(FailureSpec { message, .. }) => {
+                return Err(Failure::from(message.unwrap_or_else(|| {
+                    format!("Simulated failure changing state of {} (CR_REMOVE_VETOED)", instance_id)
+                })))
+            }
+            None => {}
+        }
+
+        let mut state = STATE.lock().unwrap();
+        if let Some(number) = parse_interface_number(instance_id) {
+            let device = state
+                .devices
+                .iter_mut()
+                .filter(|d| d.enabled && d.is_composite())
+                .find(|d| d.interface_instance_id(number).eq_ignore_ascii_case(instance_id))
+                .ok_or_else(|| Failure::device_not_found(instance_id))?;
+            device.disabled_interfaces.retain(|&n| n != number);
+            if !enable {
+                device.disabled_interfaces.push(number);
+            }
+        } else {
+            let device = state
+                .devices
+                .iter_mut()
+                .find(|d| d.instance_id.eq_ignore_ascii_case(instance_id))
+                .ok_or_else(|| Failure::device_not_found(instance_id))?;
+            device.enabled = enable;
+        }
+        drop(state);
+        events::emit(EVENT_DEVICE_CHANGED, ());
+        Ok(())
+    }
+
+    fn set_volume_read_only(&self, mount_point: &str, read_only: bool) -> Result<(), String> {
+        let mut state = STATE.lock().unwrap();
+        let key = mount_point.to_ascii_uppercase();
+        if read_only {
+            state.read_only.insert(key);
+        } else {
+            state.read_only.remove(&key);
+        }
+        Ok(())
+    }
+
+    // Simulated volumes have no open files, so `force` changes nothing
+    fn eject_device(&self, instance_id: &str, _mount_points: &[String], _force: bool) -> Result<(), Failure> {
+        detach(instance_id)
+    }
+
+    fn set_storage_write_protect(&self, enabled: bool) -> Result<(), Failure> {
+        STATE.lock().unwrap().storage_write_protect = enabled;
+        Ok(())
+    }
+
+    fn storage_write_protect(&self) -> Result<bool, String> {
+        Ok(STATE.lock().unwrap().storage_write_protect)
+    }
+
+    fn removable_storage_policies(&self) -> Result<Vec<RemovableStoragePolicy>, String> {
+        Ok(STATE.lock().unwrap().removable_storage.values().cloned().collect())
+    }
+
+    fn set_removable_storage_policy(&self, policy: &RemovableStoragePolicy) -> Result<(), Failure> {
+        let mut state = STATE.lock().unwrap();
+        let key = policy.class_guid.to_ascii_lowercase();
+        if policy.deny_read || policy.deny_write || policy.deny_execute {
+            state.removable_storage.insert(key, policy.clone());
+        } else {
+            state.removable_storage.remove(&key);
+        }
+        Ok(())
+    }
+
+    fn selective_suspend(&self, instance_id: &str) -> Result<SelectiveSuspend, String> {
+        let state = STATE.lock().unwrap();
+        let device = state
+            .devices
+            .iter()
+            .find(|d| d.instance_id.eq_ignore_ascii_case(instance_id))
+            .ok_or_else(|| format!("Device not found: {}", instance_id))?;
+        Ok(SelectiveSuspend {
+            instance_id: device.instance_id.clone(),
+            enabled: device.selective_suspend,
+            enhanced_power_management: device.selective_suspend,
+        })
+    }
+
+    fn set_selective_suspend(&self, instance_id: &str, enabled: bool) -> Result<(), String> {
+        let mut state = STATE.lock().unwrap();
+        let device = state
+            .devices
+            .iter_mut()
+            .find(|d| d.instance_id.eq_ignore_ascii_case(instance_id) && d.enabled)
+            .ok_or_else(|| format!("Device not present: {}", instance_id))?;
+        device.selective_suspend = Some(enabled);
+        Ok(())
+    }
+
+    fn wake_devices(&self) -> Result<Vec<WakeState>, String> {
+        Ok(STATE
+            .lock()
+            .unwrap()
+            .devices
+            .iter()
+            .filter(|d| d.enabled)
+            .filter_map(|d| {
+                Some(WakeState {
+                    name: d.product.clone()?,
+                    armed: d.wake_armed?,
+                })
+            })
+            .collect())
+    }
+
+    fn set_wake_enabled(&self, name: &str, enabled: bool) -> Result<(), String> {
+        let mut state = STATE.lock().unwrap();
+        let device = state
+            .devices
+            .iter_mut()
+            .find(|d| d.enabled && d.wake_armed.is_some() && d.product.as_deref() == Some(name))
+            .ok_or_else(|| format!("{} is not a wake-programmable device", name))?;
+        device.wake_armed = Some(enabled);
+        Ok(())
+    }
+
+    fn type_c_ports(&self) -> Result<Vec<TypeCPort>, String> {
+        Ok(STATE.lock().unwrap().type_c_ports.clone())
+    }
+
+    fn set_port_power(&self, hub_instance_id: &str, port: u8, on: bool) -> Result<(), String> {
+        let mut state = STATE.lock().unwrap();
+        let hub = state
+            .devices
+            .iter()
+            .find(|d| d.instance_id.eq_ignore_ascii_case(hub_instance_id) && d.enabled)
+            .ok_or_else(|| format!("Hub not found: {}", hub_instance_id))?;
+        if !hub.port_power_switching {
+            return Err("This hub does not switch power per port".to_string());
+        }
+        let key = (hub.instance_id.to_ascii_uppercase(), port);
+        if on {
+            let devices = state.unpowered.remove(&key).unwrap_or_default();
+            state.devices.extend(devices);
+        } else {
+            // Everything downstream of the port goes, hubs included
+            let mut chain = hub.ports.clone();
+            chain.push(port);
+            let bus = hub.bus_number;
+            let (lost, kept) = std::mem::take(&mut state.devices)
+                .into_iter()
+                .partition(|d: &SimDevice| d.bus_number == bus && d.ports.starts_with(&chain));
+            state.devices = kept;
+            state.unpowered.entry(key).or_default().extend(lost);
+        }
+        drop(state);
+        events::emit(EVENT_DEVICE_CHANGED, ());
+        hotplug::notify();
+        Ok(())
+    }
+
+    fn apply_policy(&self, usbstor_start: u32) -> Result<(), Failure> {
+        if usbstor_start != 3 && usbstor_start != 4 {
+            return Err(format!("Refusing USBSTOR Start={}", usbstor_start).into());
+        }
+        STATE.lock().unwrap().usbstor_start = usbstor_start;
+        Ok(())
+    }
+
+    fn usbstor_start(&self) -> Option<u32> {
+        Some(STATE.lock().unwrap().usbstor_start)
+    }
+
+    fn restart_storage_service(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn ensure_active() -> Result<(), String> {
+    if is_active() {
+        Ok(())
+    } else {
+        Err(format!("Simulation backend is not active; set {}=1 to enable it", ENV_VAR))
+    }
+}
+
+#[command]
+pub fn get_simulation_state() -> Result<SimulationStatus, String> {
+    let state = STATE.lock().unwrap();
+    Ok(SimulationStatus {
+        active: is_active(),
+        usbstor_start: state.usbstor_start,
+        devices: state.devices.clone(),
+        pending_failures: state.failures.values().cloned().collect(),
+        read_only: state.read_only.iter().cloned().collect(),
+        storage_write_protect: state.storage_write_protect,
+    })
+}
+
+/// Hot-plug a scripted device.
+#[command]
+pub fn simulate_attach(device: SimDevice) -> Result<(), String> {
+    ensure_active()?;
+    attach(device)
+}
+
+#[command]
+pub fn simulate_detach(instance_id: String) -> Result<(), String> {
+    ensure_active()?;
+    Ok(detach(&instance_id)?)
+}
+
+#[command]
+pub fn simulate_failure(failure: FailureSpec) -> Result<(), String> {
+    ensure_active()?;
+    inject_failure(failure);
+    Ok(())
+}
+
+/// Count a read and/or write on a mounted simulated volume.
+#[command]
+pub fn simulate_transfer(mount_point: String, bytes_read: u64, bytes_written: u64) -> Result<(), String> {
+    ensure_active()?;
+    let mut state = STATE.lock().unwrap();
+    let key = mount_point.to_ascii_uppercase();
+    if bytes_written > 0 && state.read_only.contains(&key) {
+        return Err(format!("{} is write-protected", mount_point));
+    }
+    let counters = state.io.entry(key).or_default();
+    counters.bytes_read += bytes_read;
+    counters.bytes_written += bytes_written;
+    counters.reads += (bytes_read > 0) as u64;
+    counters.writes += (bytes_written > 0) as u64;
+    Ok(())
+}
+
+fn interface(class_code: u8, sub_class_code: u8, protocol_code: u8) -> InterfaceClass {
+    InterfaceClass {
+        class_code,
+        sub_class_code,
+        protocol_code,
+    }
+}
+
+fn demo_device(instance_id: &str, product: &str, ports: &[u8], interfaces: Vec<InterfaceClass>) -> SimDevice {
+    SimDevice {
+        instance_id: instance_id.to_string(),
+        device_class: 0,
+        manufacturer: Some("Simulated".to_string()),
+        product: Some(product.to_string()),
+        bus_number: 1,
+        ports: ports.to_vec(),
+        interfaces,
+        parent_instance_id: None,
+        container_id: None,
+        controller_instance_id: None,
+        com_port: None,
+        volumes: Vec::new(),
+        enabled: true,
+        selective_suspend: None,
+        wake_armed: None,
+        port_power_switching: false,
+        disabled_interfaces: Vec::new(),
+        driver: None,
+        function_drivers: Vec::new(),
+    }
+}
+
+/// A small desk setup, then an unknown flash drive that is plugged in,
+/// refuses its first disable attempt, and is pulled again.
+fn demo_script() -> Script {
+    let hub = SimDevice {
+        device_class: CLASS_HUB,
+        port_power_switching: true,
+        ..demo_device("USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1", "USB2.0 Hub", &[1], vec![interface(CLASS_HUB, 0, 0)])
+    };
+    let under_hub = |device: SimDevice| SimDevice {
+        parent_instance_id: Some(hub.instance_id.clone()),
+        ..device
+    };
+    let stranger = "USB\\VID_090C&PID_1000\\0376622070004893";
+
+    Script {
+        devices: vec![
+            under_hub(demo_device(
+                "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&1",
+                "USB Keyboard",
+                &[1, 1],
+                vec![interface(CLASS_HID, 1, 1)],
+            )),
+            under_hub(demo_device(
+                "USB\\VID_046D&PID_C077\\6&2C0E4F1&0&2",
+                "USB Optical Mouse",
+                &[1, 2],
+                vec![interface(CLASS_HID, 1, 2)],
+            )),
+            demo_device(
+                "USB\\VID_0781&PID_5581\\4C530001230918115462",
+                "Ultra",
+                &[2],
+                vec![interface(CLASS_MASS_STORAGE, 6, 0x50)],
+            ),
+            demo_device(
+                "USB\\VID_1050&PID_0407\\5&3F2E1D0C&0&3",
+                "YubiKey OTP+FIDO+CCID",
+                &[3],
+                vec![interface(CLASS_HID, 1, 1), interface(CLASS_HID, 0, 0), interface(CLASS_SMART_CARD, 0, 0)],
+            ),
+            hub.clone(),
+        ],
+        timeline: vec![
+            TimelineStep {
+                after_secs: 10,
+                action: SimAction::Attach {
+                    device: Box::new(demo_device(
+                        stranger,
+                        "USB Flash Disk",
+                        &[4],
+                        vec![interface(CLASS_MASS_STORAGE, 6, 0x50)],
+                    )),
+                },
+            },
+            TimelineStep {
+                after_secs: 10,
+                action: SimAction::InjectFailure {
+                    failure: FailureSpec {
+                        instance_id: stranger.to_string(),
+                        count: 1,
+                        hang_ms: None,
+                        message: None,
+                    },
+                },
+            },
+            TimelineStep {
+                after_secs: 60,
+                action: SimAction::Detach {
+                    instance_id: stranger.to_string(),
+                },
+            },
+        ],
+        // A laptop on a PD charger, with its second port empty
+        type_c_ports: vec![
+            TypeCPort {
+                manager_instance_id: "ACPI\\USBC000\\0".to_string(),
+                manager_name: Some("UCM-UCSI ACPI Device".to_string()),
+                connector: Some(1),
+                partner: PartnerKind::PowerOnly,
+                charging: Some(true),
+                contract: Some(PowerContract {
+                    voltage_mv: 20_000,
+                    current_ma: 3_250,
+                    power_delivery: true,
+                }),
+            },
+            TypeCPort {
+                manager_instance_id: "ACPI\\USBC000\\0".to_string(),
+                manager_name: Some("UCM-UCSI ACPI Device".to_string()),
+                connector: Some(2),
+                partner: PartnerKind::Nothing,
+                charging: Some(false),
+                contract: None,
+            },
+        ],
+    }
+}
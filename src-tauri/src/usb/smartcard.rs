@@ -0,0 +1,173 @@
+use std::{sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::Security::Credentials::{
+        SCardEstablishContext, SCardGetStatusChangeW, SCardListReadersW, SCardReleaseContext, SCARD_READERSTATEW,
+        SCARD_SCOPE_USER, SCARD_STATE, SCARD_STATE_CHANGED, SCARD_STATE_PRESENT, SCARD_STATE_UNAWARE,
+    },
+};
+
+use super::audit;
+use super::correlation::wide;
+use super::events;
+use super::profiles::{self, Profile};
+
+const SOURCE: &str = "smartcard";
+pub const EVENT_CARD_CHANGED: &str = "smartcard://changed";
+// Pseudo-reader that signals reader arrival/removal
+const PNP_NOTIFICATION: &str = "\\\\?PnP?\\Notification";
+const WAIT_TIMEOUT_MS: u32 = 5_000;
+const SCARD_E_TIMEOUT: i32 = 0x8010000A_u32 as i32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartCardPolicy {
+    pub enabled: bool,
+    /// Reader to watch; `None` = any card in any reader counts as present
+    pub reader: Option<String>,
+    /// Profile applied while no card is inserted
+    pub removed_profile: Profile,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<SmartCardPolicy> = Mutex::new(SmartCardPolicy {
+        enabled: false,
+        reader: None,
+        removed_profile: Profile::Strict,
+    });
+    static ref CARD_PRESENT: Mutex<Option<bool>> = Mutex::new(None);
+    // Set when a card goes from present to absent, cleared when one is
+    // inserted. The first poll is only a baseline: no card at startup is
+    // not a removal.
+    static ref REMOVED: Mutex<bool> = Mutex::new(false);
+}
+
+/// Watch card insert/remove through the smart card resource manager.
+/// SCardGetStatusChange blocks until a reader changes state, so the watcher
+/// runs on its own thread and re-establishes its context on failure.
+pub fn start() {
+    thread::spawn(|| loop {
+        if let Err(e) = watch() {
+            log::warn!("Smart card watcher: {}", e);
+        }
+        thread::sleep(Duration::from_secs(10));
+    });
+}
+
+fn watch() -> Result<(), String> {
+    let mut context = 0usize;
+    let status = unsafe { SCardEstablishContext(SCARD_SCOPE_USER, None, None, &mut context) };
+    if status != 0 {
+        return Err(format!("SCardEstablishContext failed (Error {:#x})", status));
+    }
+
+    let result = (|| -> Result<(), String> {
+        loop {
+            // Reader names are rebuilt every round: readers come and go, which
+            // the PnP pseudo-reader reports as a change
+            let mut names = match POLICY.lock().unwrap().reader.clone() {
+                Some(reader) => vec![reader],
+                None => list_readers(context),
+            };
+            names.push(PNP_NOTIFICATION.to_string());
+            let wide_names: Vec<Vec<u16>> = names.iter().map(|n| wide(n)).collect();
+            let mut states: Vec<SCARD_READERSTATEW> = wide_names.iter().map(|n| reader_state(n)).collect();
+
+            // First call with UNAWARE returns the current state immediately
+            let status = unsafe { SCardGetStatusChangeW(context, 0, states.as_mut_ptr(), states.len() as u32) };
+            if status != 0 && status != SCARD_E_TIMEOUT {
+                return Err(format!("SCardGetStatusChange failed (Error {:#x})", status));
+            }
+            update(card_present(&states));
+
+            // Then block until something changes
+            for state in states.iter_mut() {
+                state.dwCurrentState = SCARD_STATE(state.dwEventState.0 & !SCARD_STATE_CHANGED.0);
+            }
+            let status =
+                unsafe { SCardGetStatusChangeW(context, WAIT_TIMEOUT_MS, states.as_mut_ptr(), states.len() as u32) };
+            if status != 0 && status != SCARD_E_TIMEOUT {
+                return Err(format!("SCardGetStatusChange failed (Error {:#x})", status));
+            }
+        }
+    })();
+
+    unsafe {
+        SCardReleaseContext(context);
+    }
+    result
+}
+
+fn reader_state(name: &[u16]) -> SCARD_READERSTATEW {
+    SCARD_READERSTATEW {
+        szReader: PCWSTR(name.as_ptr()),
+        dwCurrentState: SCARD_STATE_UNAWARE,
+        ..Default::default()
+    }
+}
+
+// The last entry is always the PnP pseudo-reader and never holds a card
+fn card_present(states: &[SCARD_READERSTATEW]) -> bool {
+    states[..states.len() - 1]
+        .iter()
+        .any(|s| (s.dwEventState.0 & SCARD_STATE_PRESENT.0) != 0)
+}
+
+fn list_readers(context: usize) -> Vec<String> {
+    let mut length = 0u32;
+    unsafe {
+        if SCardListReadersW(context, PCWSTR::null(), PWSTR::null(), &mut length) != 0 || length == 0 {
+            return Vec::new();
+        }
+        let mut buffer = vec![0u16; length as usize];
+        if SCardListReadersW(context, PCWSTR::null(), PWSTR(buffer.as_mut_ptr()), &mut length) != 0 {
+            return Vec::new();
+        }
+        // Multi-string: NUL separated, double NUL terminated
+        buffer
+            .split(|&c| c == 0)
+            .filter(|s| !s.is_empty())
+            .map(String::from_utf16_lossy)
+            .collect()
+    }
+}
+
+fn update(present: bool) {
+    let previous = CARD_PRESENT.lock().unwrap().replace(present);
+    if present {
+        *REMOVED.lock().unwrap() = false;
+    } else if previous == Some(true) {
+        *REMOVED.lock().unwrap() = true;
+    }
+    if previous != Some(present) {
+        audit::record("smartcard_changed", json!({ "present": present }));
+        events::emit(EVENT_CARD_CHANGED, present);
+    }
+    apply();
+}
+
+fn apply() {
+    let policy = POLICY.lock().unwrap().clone();
+    // Only a card we saw being removed triggers the policy, not "never inserted"
+    let removed = *REMOVED.lock().unwrap();
+    profiles::request(
+        SOURCE,
+        if policy.enabled && removed { Some(policy.removed_profile) } else { None },
+    );
+}
+
+#[command]
+pub fn get_smartcard_policy() -> Result<SmartCardPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_smartcard_policy(policy: SmartCardPolicy) -> Result<(), String> {
+    audit::record("smartcard_policy_changed", json!(policy));
+    *POLICY.lock().unwrap() = policy;
+    apply();
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use tauri::{command, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use super::backend;
+use super::class_policy;
+use super::commands::{app_state, get_usb_devices, DeviceState};
+use super::events::{self, LastEvent};
+use super::gpo_policy;
+use super::guest;
+use super::operations;
+use super::pause;
+use super::profiles::{self, Profile};
+use super::self_test;
+use super::usb_control::{self, BlockReason};
+
+pub const WIDGET_LABEL: &str = "status-widget";
+
+/// What the status widget shows: one line of protection state and the
+/// most recent event.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusSummary {
+    pub profile: Profile,
+    pub autoblock: bool,
+    pub connected: usize,
+    pub blocked: usize,
+    /// Held disabled waiting for a quarantine decision
+    pub quarantined: usize,
+    /// Blocked devices by the decision that blocked them
+    pub blocked_by_reason: BTreeMap<BlockReason, usize>,
+    /// Connected and not trusted
+    pub untrusted: usize,
+    pub last_event: Option<LastEvent>,
+    /// The startup self-test found enforcement degraded
+    pub degraded: bool,
+}
+
+#[command]
+pub fn get_status_summary() -> Result<StatusSummary, String> {
+    let devices = get_usb_devices()?;
+    let connected = devices.iter().filter(|d| d.state() == DeviceState::Connected);
+    Ok(StatusSummary {
+        profile: profiles::active(),
+        autoblock: app_state().autoblock_enabled(),
+        connected: connected.clone().count(),
+        blocked: devices.iter().filter(|d| d.state() == DeviceState::Blocked).count(),
+        quarantined: devices.iter().filter(|d| d.state() == DeviceState::Quarantined).count(),
+        blocked_by_reason: devices
+            .iter()
+            .filter(|d| d.state() == DeviceState::Blocked)
+            .filter_map(|d| d.block_reason())
+            .fold(BTreeMap::new(), |mut counts, reason| {
+                *counts.entry(reason).or_insert(0) += 1;
+                counts
+            }),
+        untrusted: connected.filter(|d| !d.trusted()).count(),
+        last_event: events::last(),
+        degraded: self_test::degraded(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionLevel {
+    /// Every mechanism in use is in force and nothing untrusted is running
+    FullyProtected,
+    PartiallyProtected,
+    /// No mechanism is keeping anything out
+    Unprotected,
+}
+
+/// One of the ways devices are kept out, as read back from the machine
+/// rather than from our own settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectionMechanism {
+    pub name: String,
+    pub active: bool,
+    pub detail: Option<String>,
+}
+
+/// The shield indicator: what is actually enforced right now, and why it
+/// falls short of full protection if it does.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveProtection {
+    pub level: ProtectionLevel,
+    pub mechanisms: Vec<ProtectionMechanism>,
+    /// Empty when fully protected
+    pub reasons: Vec<String>,
+}
+
+fn mechanism(name: &str, active: bool, detail: Option<String>) -> ProtectionMechanism {
+    ProtectionMechanism {
+        name: name.to_string(),
+        active,
+        detail,
+    }
+}
+
+/// Read back every enforcement mechanism (autoblock, the USBSTOR driver,
+/// machine-wide storage policies and the devnodes we disabled) and sum them
+/// up in one level.
+#[command]
+pub async fn get_effective_protection_status() -> Result<EffectiveProtection, String> {
+    operations::run("get_effective_protection_status", effective_protection).await
+}
+
+fn effective_protection() -> Result<EffectiveProtection, String> {
+    let devices = get_usb_devices()?;
+    let mut reasons = Vec::new();
+
+    let pause = pause::get_protection_pause()?;
+    let autoblock = app_state().autoblock_enabled() && pause.is_none();
+    if !app_state().autoblock_enabled() {
+        reasons.push("Autoblock is off".to_string());
+    }
+    if let Some(pause) = &pause {
+        reasons.push(format!("Protection is paused until {}", pause.resumes_at.to_rfc3339()));
+    }
+    if let Some(session) = guest::get_guest_mode()? {
+        reasons.push(format!("Guest mode admits devices until {}", session.expires_at.to_rfc3339()));
+    }
+
+    let usbstor_start = backend::controller().usbstor_start();
+    let storage_driver = usbstor_start == Some(4);
+    if usbstor_start.is_none() {
+        reasons.push("The USB storage driver setting could not be read".to_string());
+    }
+
+    let denying: Vec<String> = gpo_policy::get_gpo_storage_policies()?
+        .into_iter()
+        .filter(|policy| policy.deny_read || policy.deny_write || policy.deny_execute)
+        .map(|policy| policy.class_guid)
+        .collect();
+
+    // A devnode we hold blocked that is running again was re-enabled behind our back
+    let ours: Vec<_> = devices
+        .iter()
+        .filter(|d| d.instance_id().is_some_and(usb_control::is_blocked_by_us))
+        .collect();
+    let bypassed = ours.iter().filter(|d| d.state() == DeviceState::Connected).count();
+    if bypassed > 0 {
+        reasons.push(format!("{} blocked device(s) are running again", bypassed));
+    }
+    // Only the ones autoblock should have stopped: a class Allow policy,
+    // guest mode and the pause let devices in on purpose
+    let untrusted = devices
+        .iter()
+        .filter(|d| d.state() == DeviceState::Connected && !d.trusted() && !class_policy::allowed(d))
+        .filter(|d| {
+            d.instance_id().is_none_or(|id| {
+                !usb_control::is_blocked_by_us(id) && !guest::admitted(id) && !pause::admitted(id)
+            })
+        })
+        .count();
+    if untrusted > 0 {
+        reasons.push(format!("{} untrusted device(s) are connected", untrusted));
+    }
+    if self_test::degraded() {
+        reasons.push("The startup self-test found enforcement degraded".to_string());
+    }
+
+    let mechanisms = vec![
+        mechanism("autoblock", autoblock, pause.map(|_| "paused".to_string())),
+        mechanism(
+            "storage_driver",
+            storage_driver,
+            usbstor_start.map(|start| format!("USBSTOR Start={}", start)),
+        ),
+        mechanism(
+            "storage_policy",
+            !denying.is_empty(),
+            (!denying.is_empty()).then(|| denying.join(", ")),
+        ),
+        mechanism(
+            "device_blocks",
+            ours.len() > bypassed,
+            Some(format!("{} of {} in force", ours.len() - bypassed, ours.len())),
+        ),
+    ];
+    let level = if reasons.is_empty() {
+        ProtectionLevel::FullyProtected
+    } else if mechanisms.iter().any(|m| m.active) {
+        ProtectionLevel::PartiallyProtected
+    } else {
+        ProtectionLevel::Unprotected
+    };
+    Ok(EffectiveProtection {
+        level,
+        mechanisms,
+        reasons,
+    })
+}
+
+/// Open the widget, or close it if it is already open. Returns whether it
+/// is open afterwards. Async because building a window from a synchronous
+/// command, which runs on the main thread, deadlocks on Windows.
+#[command]
+pub async fn toggle_status_widget(app: tauri::AppHandle) -> Result<bool, String> {
+    toggle_widget(&app)
+}
+
+/// `toggle_status_widget` for callers already off the main thread.
+pub fn toggle_widget(app: &tauri::AppHandle) -> Result<bool, String> {
+    if let Some(window) = app.get_webview_window(WIDGET_LABEL) {
+        window.close().map_err(|e| e.to_string())?;
+        return Ok(false);
+    }
+    // The frontend renders the widget instead of the main view for this label
+    WebviewWindowBuilder::new(app, WIDGET_LABEL, WebviewUrl::App("index.html".into()))
+        .title("USB-Shield status")
+        .inner_size(260.0, 96.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .build()
+        .map_err(|e| format!("Failed to open the status widget: {}", e))?;
+    Ok(true)
+}
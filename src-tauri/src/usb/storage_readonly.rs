@@ -0,0 +1,144 @@
+use std::{collections::HashSet, sync::Mutex};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::backend::{self, UsbController};
+use super::dry_run::{self, Planned, PlannedStep, PolicyPlan};
+use super::exfiltration;
+use super::forensics;
+use super::hello;
+use super::quota;
+
+const WRITE_PROTECT_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Control\StorageDevicePolicies";
+
+struct ReadOnlyState {
+    enabled: bool,
+    // Mount points this mode write-protected, upper case
+    protected: HashSet<String>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<ReadOnlyState> = Mutex::new(ReadOnlyState {
+        enabled: false,
+        protected: HashSet::new(),
+    });
+}
+
+/// Whether removable storage is being kept read-only instead of blocked.
+pub fn enabled() -> bool {
+    STATE.lock().unwrap().enabled
+}
+
+/// Whether read-only mode holds `mount_point` write-protected.
+pub fn holds(mount_point: &str) -> bool {
+    let state = STATE.lock().unwrap();
+    state.enabled && state.protected.contains(&mount_point.to_ascii_uppercase())
+}
+
+// The mounted volumes the machine-wide value came too late for; the ones
+// that could not be protected go to `errors`
+fn protect_mounted(controller: &dyn UsbController, errors: &mut Vec<String>) -> Result<HashSet<String>, String> {
+    let mut protected = HashSet::new();
+    for volume in controller.volumes()? {
+        match controller.set_volume_read_only(&volume.mount_point, true) {
+            Ok(()) => {
+                protected.insert(volume.mount_point.to_ascii_uppercase());
+            }
+            Err(e) => errors.push(format!("{}: {}", volume.mount_point, e)),
+        }
+    }
+    Ok(protected)
+}
+
+/// Take the mode back from the machine-wide value, which stays in the
+/// registry across a restart, and write-protect the volumes already mounted
+/// so switching it off releases them. Called from setup once the backend is
+/// up.
+pub fn load() -> Result<(), String> {
+    let controller = backend::controller();
+    if !controller.storage_write_protect()? {
+        return Ok(());
+    }
+    // Shown as on even if the volumes below cannot be listed: the registry
+    // value alone keeps new volumes read-only
+    STATE.lock().unwrap().enabled = true;
+    let mut errors = Vec::new();
+    let protected = protect_mounted(controller, &mut errors)?;
+    STATE.lock().unwrap().protected = protected;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+#[command]
+pub fn get_storage_readonly() -> Result<bool, String> {
+    Ok(enabled())
+}
+
+/// Let removable storage mount but never be written to, as an alternative to
+/// blocking it. The machine-wide policy covers volumes mounted from now on;
+/// the ones already mounted are write-protected one by one.
+#[command]
+pub fn set_storage_readonly(enabled: bool, admin_token: Option<String>) -> Result<Planned<()>, String> {
+    if dry_run::active() {
+        return plan(enabled).map(Planned::Simulated);
+    }
+    if !enabled {
+        admin_pin::require_admin(admin_token.as_deref(), "allow writing to removable storage")?;
+        hello::require_consent("allow writing to removable storage")?;
+    }
+    let controller = backend::controller();
+    // A forensic session needs the machine-wide value until it ends
+    controller.set_storage_write_protect(enabled || forensics::active())?;
+
+    let mut errors = Vec::new();
+    let protected = if enabled { protect_mounted(controller, &mut errors)? } else { HashSet::new() };
+    // Swapped in before the old set is released, and never held while the
+    // other write blocks are consulted: they check `holds` under their own locks
+    let released = {
+        let mut state = STATE.lock().unwrap();
+        state.enabled = enabled;
+        std::mem::replace(&mut state.protected, protected.clone())
+    };
+    if !enabled {
+        for mount in released {
+            // Other write blocks on the same volume stay until released there
+            if exfiltration::write_blocked(&mount) || quota::blocks(&mount) || forensics::holds(&mount) {
+                continue;
+            }
+            if let Err(e) = controller.set_volume_read_only(&mount, false) {
+                errors.push(format!("{}: {}", mount, e));
+            }
+        }
+    }
+    audit::record(
+        "storage_readonly_changed",
+        json!({ "enabled": enabled, "protected": protected, "errors": errors }),
+    );
+    Ok(Planned::Applied(()))
+}
+
+// The machine-wide value, then the volumes it would protect or release
+fn plan(enabled: bool) -> Result<PolicyPlan, String> {
+    let mut plan = PolicyPlan::new("set_storage_readonly");
+    plan.steps.push(PlannedStep::SetRegistryValue {
+        key: WRITE_PROTECT_KEY.to_string(),
+        name: "WriteProtect".to_string(),
+        value: enabled as u32,
+    });
+    let mounts: Vec<String> = if enabled {
+        backend::controller().volumes()?.into_iter().map(|volume| volume.mount_point).collect()
+    } else {
+        STATE.lock().unwrap().protected.iter().cloned().collect()
+    };
+    plan.steps.extend(mounts.into_iter().map(|mount_point| PlannedStep::SetVolumeReadOnly {
+        mount_point,
+        read_only: enabled,
+    }));
+    Ok(plan)
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit::{self, AuditEntry};
+use super::category::DeviceCategory;
+use super::commands;
+use super::correlation::parse_vid_pid;
+use super::error::UsbShieldError;
+use super::hello;
+use super::inventory::{self, InventoryRecord};
+
+// A trust candidate has connected this often over at least this long
+const MIN_CONNECTIONS: usize = 20;
+const MIN_HISTORY_DAYS: i64 = 30;
+// Trust for a model not seen in this long is suggested for revocation
+const STALE_TRUST_DAYS: i64 = 180;
+
+// Audit actions that count against a device
+const FLAG_ACTIONS: &[&str] = &[
+    "exfiltration_alert",
+    "keystroke_anomaly",
+    "class_policy_blocked",
+    "wireless_device_blocked",
+    "wireless_device_connected",
+    "write_quota_exceeded",
+    "threat_device_blocked",
+    "preblocked_device_blocked",
+    "network_adapter_blocked",
+    "keyboard_lockdown_blocked",
+    "hid_quarantined",
+    "device_quarantined",
+    "remote_usb_blocked",
+];
+
+// Never suggested for trust: the risk is in what they do, not how often they come back
+const UNSUGGESTED_CATEGORIES: &[DeviceCategory] = &[
+    DeviceCategory::Storage,
+    DeviceCategory::CellularModem,
+    DeviceCategory::WifiAdapter,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SuggestionKind {
+    Trust,
+    Revoke,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicySuggestion {
+    pub kind: SuggestionKind,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    #[serde(default)]
+    pub product: Option<String>,
+    /// Human-readable reason, e.g. "Connected 240 times over 6 months, never flagged"
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub connections: usize,
+    #[serde(default)]
+    pub flags: usize,
+    #[serde(default)]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+fn entry_instance_id(entry: &AuditEntry) -> Option<&str> {
+    entry.details["instance_id"]
+        .as_str()
+        .or_else(|| entry.details["device_instance_id"].as_str())
+}
+
+fn span(days: i64) -> String {
+    if days >= 60 {
+        format!("{} months", days / 30)
+    } else {
+        format!("{} days", days)
+    }
+}
+
+/// Derive suggestions from the audit log, the inventory and the trusted
+/// list. Pure, so it can be checked against any history.
+pub fn suggest(
+    entries: &[AuditEntry],
+    records: &[InventoryRecord],
+    trusted: &[(u16, u16)],
+    now: DateTime<Utc>,
+) -> Vec<PolicySuggestion> {
+    // Per instance ID: connection timestamps, and the number of flags
+    let mut connections: HashMap<String, Vec<DateTime<Utc>>> = HashMap::new();
+    let mut flags: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let instance_id = match entry_instance_id(entry) {
+            Some(instance_id) => instance_id.to_ascii_uppercase(),
+            None => continue,
+        };
+        if entry.action == "device_connected" {
+            connections.entry(instance_id).or_default().push(entry.timestamp);
+        } else if FLAG_ACTIONS.contains(&entry.action.as_str()) {
+            *flags.entry(instance_id).or_default() += 1;
+        }
+    }
+    let trusted: HashSet<(u16, u16)> = trusted.iter().copied().collect();
+    let mut suggestions = Vec::new();
+
+    for record in records {
+        let key = record.instance_id.to_ascii_uppercase();
+        // Trust is per model; one suggestion covers every unit
+        let suggested = suggestions
+            .iter()
+            .any(|s: &PolicySuggestion| s.vendor_id == record.vendor_id && s.product_id == record.product_id);
+        if suggested
+            || trusted.contains(&(record.vendor_id, record.product_id))
+            || UNSUGGESTED_CATEGORIES.contains(&record.category)
+            || flags.contains_key(&key)
+        {
+            continue;
+        }
+        let seen = match connections.get(&key) {
+            Some(seen) if seen.len() >= MIN_CONNECTIONS => seen,
+            _ => continue,
+        };
+        let first = seen.iter().min().copied().unwrap_or(record.first_seen).min(record.first_seen);
+        let days = (now - first).num_days();
+        if days < MIN_HISTORY_DAYS {
+            continue;
+        }
+        suggestions.push(PolicySuggestion {
+            kind: SuggestionKind::Trust,
+            vendor_id: record.vendor_id,
+            product_id: record.product_id,
+            product: record.product.clone(),
+            reason: format!("Connected {} times over {}, never flagged", seen.len(), span(days)),
+            connections: seen.len(),
+            flags: 0,
+            last_seen: Some(record.last_seen),
+        });
+    }
+
+    let mut trusted: Vec<(u16, u16)> = trusted.into_iter().collect();
+    trusted.sort();
+    for (vendor_id, product_id) in trusted {
+        let units: Vec<&InventoryRecord> = records
+            .iter()
+            .filter(|record| record.vendor_id == vendor_id && record.product_id == product_id)
+            .collect();
+        let flagged: usize = flags
+            .iter()
+            .filter(|(instance_id, _)| parse_vid_pid(instance_id) == Some((vendor_id, product_id)))
+            .map(|(_, count)| count)
+            .sum();
+        let last_seen = units.iter().map(|record| record.last_seen).max();
+        let connected: usize = units
+            .iter()
+            .filter_map(|record| connections.get(&record.instance_id.to_ascii_uppercase()))
+            .map(Vec::len)
+            .sum();
+
+        let reason = if flagged > 0 {
+            format!("Trusted but flagged {} time(s)", flagged)
+        } else {
+            match last_seen {
+                None => "Trusted but never seen on this machine".to_string(),
+                Some(last) if (now - last).num_days() >= STALE_TRUST_DAYS => {
+                    format!("Trusted but not seen for {}", span((now - last).num_days()))
+                }
+                Some(_) => continue,
+            }
+        };
+        suggestions.push(PolicySuggestion {
+            kind: SuggestionKind::Revoke,
+            vendor_id,
+            product_id,
+            product: units.iter().find_map(|record| record.product.clone()),
+            reason,
+            connections: connected,
+            flags: flagged,
+            last_seen,
+        });
+    }
+    suggestions
+}
+
+#[command]
+pub fn get_policy_suggestions() -> Result<Vec<PolicySuggestion>, String> {
+    Ok(suggest(
+        &audit::read_all()?,
+        &inventory::records(),
+        &commands::app_state().trusted_devices(),
+        Utc::now(),
+    ))
+}
+
+/// Apply a suggestion from `get_policy_suggestions` in one step.
+#[command]
+pub fn accept_policy_suggestion(
+    suggestion: PolicySuggestion,
+    admin_token: Option<String>,
+) -> Result<(), UsbShieldError> {
+    match suggestion.kind {
+        SuggestionKind::Trust => {
+            hello::require_consent("trust a suggested device")?;
+            commands::app_state().add_trusted_device(suggestion.vendor_id, suggestion.product_id)?;
+        }
+        SuggestionKind::Revoke => {
+            commands::app_state().remove_trusted_device(
+                suggestion.vendor_id,
+                suggestion.product_id,
+                admin_token.as_deref(),
+            )?
+        }
+    }
+    audit::record("policy_suggestion_accepted", json!(suggestion));
+    Ok(())
+}
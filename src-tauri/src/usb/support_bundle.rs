@@ -0,0 +1,263 @@
+use std::{fs::File, io::Write, path::PathBuf};
+use chrono::Utc;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tauri::command;
+use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use super::audit;
+use super::class_policy;
+use super::commands;
+use super::device_power;
+use super::docks;
+use super::exfiltration;
+use super::hello;
+use super::helper_client;
+use super::hub_policy;
+use super::idle;
+use super::network;
+use super::network_adapters;
+use super::port_locks;
+use super::power;
+use super::profiles;
+use super::quota;
+use super::remote;
+use super::scheduler;
+use super::security_key;
+use super::simulation;
+use super::smartcard;
+use super::status;
+use super::storage_readonly;
+use super::transfers;
+use super::trust_rules;
+use super::type_c;
+use super::usb_config;
+use super::vpn;
+use super::wireless;
+
+// Audit entries included, newest last
+const RECENT_LOG_ENTRIES: usize = 500;
+
+const CURRENT_VERSION_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+// Setup class of host controllers and hubs
+const USB_CLASS_KEY: &str = r"SYSTEM\CurrentControlSet\Control\Class\{36fc9e60-c465-11cf-8056-444553540000}";
+
+// Keys whose values identify a unit, a machine or a person
+const IDENTIFYING_KEYS: &[&str] = &[
+    "serial",
+    "serial_number",
+    "volume_serial",
+    "instance_id",
+    "device_instance_id",
+    "parent_instance_id",
+    "hub_instance_id",
+    "container_id",
+    "label",
+    "labels",
+    "computer",
+    "user",
+    "exported_by",
+    // Who shared a trust binding, and the deep links that carried them
+    "from",
+    "url",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SupportBundle {
+    pub path: String,
+    pub anonymized: bool,
+    /// Files inside the archive
+    pub files: Vec<String>,
+}
+
+fn section<T: Serialize>(result: Result<T, String>) -> Value {
+    match result {
+        Ok(value) => json!(value),
+        Err(e) => json!({ "error": e }),
+    }
+}
+
+fn diagnostics() -> Value {
+    json!({
+        "status": section(status::get_status_summary()),
+        "devices": section(commands::get_usb_devices()),
+        "type_c_ports": section(type_c::get_type_c_ports()),
+        "wake_devices": section(device_power::get_wake_devices()),
+        "usb_suspend_setting": section(device_power::get_usb_suspend_setting()),
+        "transfers": section(transfers::get_transfer_stats()),
+        "write_quota_usage": section(quota::get_write_quota_usage()),
+        "write_blocked_volumes": section(exfiltration::get_write_blocked_volumes()),
+        "docks": section(docks::get_docks()),
+        "redirection_clients": section(remote::get_redirection_clients()),
+        "network_location": section(network::get_network_location()),
+        "vpn": section(vpn::get_vpn_status()),
+        "power_source": section(power::get_power_source()),
+        "simulation": section(simulation::get_simulation_state()),
+        "helper_running": helper_client::is_running(),
+    })
+}
+
+/// Every policy setting in force, as written to `policy.json`. Also the
+/// live side of a policy diff.
+pub fn policy() -> Value {
+    json!({
+        "profile": section(profiles::get_active_profile()),
+        "autoblock": commands::app_state().autoblock_enabled(),
+        "autoblock_schedule": section(scheduler::get_autoblock_schedule()),
+        "class_policies": section(class_policy::get_class_policies()),
+        "hub_policy": section(hub_policy::get_hub_policy()),
+        "wireless": section(wireless::get_wireless_policy()),
+        "network_adapters": section(network_adapters::get_network_adapter_policy()),
+        "port_locks": section(port_locks::get_port_locks()),
+        "storage_readonly": section(storage_readonly::get_storage_readonly()),
+        "device_operation_timeout": section(commands::get_device_operation_timeout()),
+        "trusted_devices": commands::app_state().trusted_devices(),
+        "trust_bindings": section(trust_rules::get_trust_bindings()),
+        "volume_label_rules": section(trust_rules::get_volume_label_rules()),
+        "idle_lockdown": section(idle::get_idle_lockdown()),
+        "network": section(network::get_network_policy()),
+        "vpn_storage": section(vpn::get_vpn_storage_rule()),
+        "power": section(power::get_power_policy()),
+        "smartcard": section(smartcard::get_smartcard_policy()),
+        "security_key_gate": section(security_key::get_security_key_gate()),
+        "windows_hello": section(hello::get_windows_hello_requirement()),
+        "remote_usb": section(remote::get_remote_usb_policy()),
+        "exfiltration": section(exfiltration::get_exfiltration_policy()),
+        "write_quota": section(quota::get_write_quota_policy()),
+    })
+}
+
+fn registry_string(key: &RegKey, name: &str) -> Option<String> {
+    key.get_value::<String, _>(name).ok()
+}
+
+fn environment() -> Value {
+    let root = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let os = root.open_subkey(CURRENT_VERSION_KEY).ok().map(|key| {
+        json!({
+            "product": registry_string(&key, "ProductName"),
+            "display_version": registry_string(&key, "DisplayVersion"),
+            "build": registry_string(&key, "CurrentBuild"),
+            "revision": key.get_value::<u32, _>("UBR").ok(),
+        })
+    });
+    let drivers: Vec<Value> = root
+        .open_subkey(USB_CLASS_KEY)
+        .map(|class| {
+            class
+                .enum_keys()
+                .flatten()
+                // Skip "Properties" and other non-instance subkeys
+                .filter(|name| name.chars().all(|c| c.is_ascii_digit()))
+                .filter_map(|name| class.open_subkey(&name).ok())
+                .map(|key| {
+                    json!({
+                        "description": registry_string(&key, "DriverDesc"),
+                        "provider": registry_string(&key, "ProviderName"),
+                        "version": registry_string(&key, "DriverVersion"),
+                        "date": registry_string(&key, "DriverDate"),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    json!({
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": os,
+        "architecture": std::env::consts::ARCH,
+        "usb_drivers": drivers,
+        "computer": std::env::var("COMPUTERNAME").ok(),
+        "user": std::env::var("USERNAME").ok(),
+        "generated_at": Utc::now(),
+    })
+}
+
+fn pseudonym(value: &str, salt: &[u8]) -> String {
+    // Stable within a bundle, so the same device still lines up across files.
+    // Serials and VID/PID-based IDs are guessable, so without the salt a
+    // dictionary would reverse them.
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(value.to_ascii_uppercase().as_bytes());
+    format!("anon-{}", &hex::encode(hasher.finalize())[..12])
+}
+
+/// Replace identifying strings with pseudonyms, recursively.
+fn anonymize(value: &mut Value, salt: &[u8]) {
+    match value {
+        Value::Object(map) => anonymize_object(map, salt),
+        Value::Array(items) => items.iter_mut().for_each(|item| anonymize(item, salt)),
+        _ => {}
+    }
+}
+
+fn anonymize_object(map: &mut Map<String, Value>, salt: &[u8]) {
+    for (key, value) in map.iter_mut() {
+        if IDENTIFYING_KEYS.contains(&key.as_str()) {
+            scrub(value, salt);
+        } else {
+            anonymize(value, salt);
+        }
+    }
+}
+
+fn scrub(value: &mut Value, salt: &[u8]) {
+    match value {
+        Value::String(text) => *text = pseudonym(text, salt),
+        Value::Array(items) => items.iter_mut().for_each(|item| scrub(item, salt)),
+        Value::Object(map) => anonymize_object(map, salt),
+        _ => {}
+    }
+}
+
+/// Zip diagnostics, the recent audit log, the current policy and
+/// environment info into one file for a bug report. With `anonymize`,
+/// serials, instance IDs, labels and machine/user names are replaced by
+/// pseudonyms that are stable across the files of one bundle but not
+/// between bundles.
+#[command]
+pub fn generate_support_bundle(anonymize: bool, path: Option<String>) -> Result<SupportBundle, String> {
+    let entries = audit::read_all()?;
+    let recent = &entries[entries.len().saturating_sub(RECENT_LOG_ENTRIES)..];
+
+    let mut files = vec![
+        ("diagnostics.json", diagnostics()),
+        ("audit-log.json", json!(recent)),
+        ("policy.json", policy()),
+        ("environment.json", environment()),
+    ];
+    if anonymize {
+        // Never written out, so the pseudonyms cannot be recomputed
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        for (_, value) in files.iter_mut() {
+            self::anonymize(value, &salt);
+        }
+    }
+
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => usb_config::data_file(&format!("support-bundle-{}.zip", Utc::now().format("%Y%m%dT%H%M%SZ")))?,
+    };
+    let write_error = |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+    let file = File::create(&path).map_err(|e| write_error(&e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, value) in &files {
+        let body = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+        zip.start_file(*name, options).map_err(|e| write_error(&e))?;
+        zip.write_all(&body).map_err(|e| write_error(&e))?;
+    }
+    zip.finish().map_err(|e| write_error(&e))?;
+
+    let bundle = SupportBundle {
+        path: path.display().to_string(),
+        anonymized: anonymize,
+        files: files.iter().map(|(name, _)| name.to_string()).collect(),
+    };
+    audit::record("support_bundle_generated", json!(bundle));
+    Ok(bundle)
+}
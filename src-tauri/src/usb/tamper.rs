@@ -0,0 +1,267 @@
+use std::{collections::BTreeMap, fs, sync::Mutex, thread, time::Duration};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+use uport_shield_helper::protocol::RemovableStoragePolicy;
+
+use super::audit;
+use super::backend;
+use super::event_log::{self, LogEvent};
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::webhooks::{self, WebhookEvent};
+
+pub const EVENT_TAMPER_DETECTED: &str = "usb://tamper-detected";
+const TICK: Duration = Duration::from_secs(5);
+const TAMPER_FILE: &str = "tamper-guard.json";
+
+/// What the watchdog does when an enforced setting was changed behind our back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TamperResponse {
+    /// Write our value back and report it
+    Restore,
+    /// Only report it
+    Alert,
+}
+
+/// One enforced setting found changed by another program or by hand.
+#[derive(Debug, Clone, Serialize)]
+pub struct TamperEvent {
+    /// `USBSTOR\Start`, or `RemovableStorageDevices\{class GUID}`
+    pub setting: String,
+    pub expected: String,
+    pub found: String,
+    pub detected_at: DateTime<Utc>,
+    pub restored: bool,
+    /// Set when writing our value back failed
+    pub error: Option<String>,
+}
+
+// The values we wrote and keep in force; a setting we never wrote is not ours to guard
+#[derive(Default, Serialize, Deserialize)]
+struct Enforced {
+    #[serde(default)]
+    usbstor_start: Option<u32>,
+    // Class GUID (lower case) -> the policy written for it
+    #[serde(default)]
+    removable_storage: BTreeMap<String, RemovableStoragePolicy>,
+}
+
+// Saved, so settings changed while the app was closed are still caught
+// when it starts again
+#[derive(Serialize, Deserialize)]
+struct StoredGuard {
+    #[serde(flatten)]
+    enforced: Enforced,
+    response: TamperResponse,
+}
+
+lazy_static! {
+    static ref ENFORCED: Mutex<Enforced> = Mutex::new(Enforced::default());
+    static ref RESPONSE: Mutex<TamperResponse> = Mutex::new(TamperResponse::Restore);
+    static ref LAST_TAMPER: Mutex<Option<TamperEvent>> = Mutex::new(None);
+}
+
+// Called with ENFORCED held. A failed save is logged rather than undoing a
+// write that already took effect.
+fn save(enforced: &Enforced, response: TamperResponse) {
+    let result = usb_config::data_file(TAMPER_FILE).and_then(|path| {
+        let stored = json!({
+            "usbstor_start": enforced.usbstor_start,
+            "removable_storage": enforced.removable_storage,
+            "response": response,
+        });
+        let data = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+        fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    });
+    if let Err(e) = result {
+        log::error!("Tamper watchdog: {}", e);
+    }
+}
+
+/// Restore what was guarded before the last exit, and the response mode.
+/// Called from setup, before `start`.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(TAMPER_FILE)?;
+    let stored: StoredGuard = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    *ENFORCED.lock().unwrap() = stored.enforced;
+    *RESPONSE.lock().unwrap() = stored.response;
+    Ok(())
+}
+
+/// Make our own write of the USBSTOR `Start` value and guard it from then
+/// on, or stop guarding it with `None`. The watchdog waits for the write,
+/// so it never mistakes a change in progress for tampering.
+pub fn write_usbstor_start<E>(start: Option<u32>, write: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+    let mut enforced = ENFORCED.lock().unwrap();
+    write()?;
+    if enforced.usbstor_start != start {
+        enforced.usbstor_start = start;
+        save(&enforced, *RESPONSE.lock().unwrap());
+    }
+    Ok(())
+}
+
+/// `write_usbstor_start` for the removable storage policy of one class. A
+/// policy denying nothing is no longer guarded.
+pub fn write_removable_storage<E>(
+    policy: &RemovableStoragePolicy,
+    write: impl FnOnce() -> Result<(), E>,
+) -> Result<(), E> {
+    let mut enforced = ENFORCED.lock().unwrap();
+    write()?;
+    let key = policy.class_guid.to_ascii_lowercase();
+    let changed = if policy.deny_read || policy.deny_write || policy.deny_execute {
+        enforced.removable_storage.insert(key, policy.clone()).as_ref() != Some(policy)
+    } else {
+        enforced.removable_storage.remove(&key).is_some()
+    };
+    if changed {
+        save(&enforced, *RESPONSE.lock().unwrap());
+    }
+    Ok(())
+}
+
+/// Poll the enforced settings. Registry change notifications would not
+/// cover the simulated backend, and a few seconds of lag is no worse than
+/// the next device arrival, which is when the values take effect.
+pub fn start() {
+    thread::spawn(|| loop {
+        thread::sleep(TICK);
+        check();
+    });
+}
+
+fn describe(policy: Option<&RemovableStoragePolicy>) -> String {
+    let denied: Vec<&str> = policy
+        .map(|p| {
+            [("read", p.deny_read), ("write", p.deny_write), ("execute", p.deny_execute)]
+                .into_iter()
+                .filter(|(_, deny)| *deny)
+                .map(|(name, _)| name)
+                .collect()
+        })
+        .unwrap_or_default();
+    if denied.is_empty() {
+        "nothing denied".to_string()
+    } else {
+        format!("deny {}", denied.join(", "))
+    }
+}
+
+/// Compare every enforced setting with what the machine has now, and
+/// restore or report each one that differs.
+pub fn check() -> Vec<TamperEvent> {
+    let controller = backend::controller();
+    let restore = *RESPONSE.lock().unwrap() == TamperResponse::Restore;
+    let enforced = ENFORCED.lock().unwrap();
+    let mut found = Vec::new();
+    let mut report = |setting: String, expected: String, actual: String, write: &dyn Fn() -> Result<(), String>| {
+        let error = if restore { write().err() } else { None };
+        found.push(TamperEvent {
+            setting,
+            expected,
+            found: actual,
+            detected_at: Utc::now(),
+            restored: restore && error.is_none(),
+            error,
+        });
+    };
+
+    if let Some(start) = enforced.usbstor_start {
+        // Unreadable is not evidence of a change
+        if let Some(actual) = controller.usbstor_start().filter(|&actual| actual != start) {
+            report(
+                r"USBSTOR\Start".to_string(),
+                start.to_string(),
+                actual.to_string(),
+                &|| controller.apply_policy(start).map_err(String::from),
+            );
+        }
+    }
+    if !enforced.removable_storage.is_empty() {
+        match controller.removable_storage_policies() {
+            Ok(current) => {
+                for (class_guid, policy) in &enforced.removable_storage {
+                    let actual = current.iter().find(|p| p.class_guid.eq_ignore_ascii_case(class_guid));
+                    let unchanged = actual.is_some_and(|p| {
+                        (p.deny_read, p.deny_write, p.deny_execute)
+                            == (policy.deny_read, policy.deny_write, policy.deny_execute)
+                    });
+                    if !unchanged {
+                        report(
+                            format!(r"RemovableStorageDevices\{}", policy.class_guid),
+                            describe(Some(policy)),
+                            describe(actual),
+                            &|| controller.set_removable_storage_policy(policy).map_err(String::from),
+                        );
+                    }
+                }
+            }
+            Err(e) => log::warn!("Tamper watchdog: {}", e),
+        }
+    }
+    drop(enforced);
+
+    for event in &found {
+        let message = format!(
+            "USB protection was tampered with: {} was changed from {} to {}{}.",
+            event.setting,
+            event.expected,
+            event.found,
+            if event.restored { " and has been restored" } else { "" }
+        );
+        event_log::write(LogEvent::TamperDetected, &message, &json!(event));
+        webhooks::dispatch(WebhookEvent::TamperDetected, &message, &json!(event));
+        audit::record("tamper_detected", json!(event));
+        events::emit(EVENT_TAMPER_DETECTED, event.clone());
+        notifications::notify(
+            Severity::Critical,
+            "tamper_detected",
+            "USB protection was tampered with",
+            &if event.restored {
+                format!("{} was changed to {} and has been restored.", event.setting, event.found)
+            } else {
+                format!("{} was changed to {}.", event.setting, event.found)
+            },
+        );
+    }
+    if let Some(last) = found.last() {
+        *LAST_TAMPER.lock().unwrap() = Some(last.clone());
+    }
+    found
+}
+
+#[command]
+pub fn get_tamper_response() -> Result<TamperResponse, String> {
+    Ok(*RESPONSE.lock().unwrap())
+}
+
+/// Choose between restoring tampered settings and only reporting them.
+/// Giving up the restore needs consent.
+#[command]
+pub fn set_tamper_response(response: TamperResponse) -> Result<(), String> {
+    if response == TamperResponse::Alert && *RESPONSE.lock().unwrap() == TamperResponse::Restore {
+        hello::require_consent("stop restoring tampered settings")?;
+    }
+    let enforced = ENFORCED.lock().unwrap();
+    *RESPONSE.lock().unwrap() = response;
+    save(&enforced, response);
+    drop(enforced);
+    audit::record("tamper_response_changed", json!({ "response": response }));
+    Ok(())
+}
+
+/// The most recent tampering the watchdog found, if any.
+#[command]
+pub fn get_last_tamper() -> Result<Option<TamperEvent>, String> {
+    Ok(LAST_TAMPER.lock().unwrap().clone())
+}
@@ -0,0 +1,173 @@
+use std::{fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::admin_pin;
+use super::audit;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::error::UsbShieldError;
+use super::notifications::{self, Severity};
+use super::usb_config;
+use super::usb_control::{self, BlockReason};
+
+const BUILT_IN: &str = include_str!("threat_signatures.json");
+const UPDATE_FILE: &str = "threat-signatures.json";
+
+/// Known attack hardware. A signature matches on whichever of its fields
+/// are set: VID, VID and PID, or a case-insensitive part of the vendor or
+/// product string, for devices that only reveal themselves there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreatSignature {
+    pub name: String,
+    #[serde(default)]
+    pub vendor_id: Option<u16>,
+    #[serde(default)]
+    pub product_id: Option<u16>,
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    #[serde(default)]
+    pub product: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreatDatabase {
+    pub version: u32,
+    pub signatures: Vec<ThreatSignature>,
+}
+
+lazy_static! {
+    // Shipped in the binary, so a parse failure is a packaging mistake
+    static ref BUILT_IN_DATABASE: ThreatDatabase =
+        serde_json::from_str(BUILT_IN).expect("built-in threat signatures");
+    /// Signatures from the last update file; the built-in ones always apply
+    static ref UPDATE: Mutex<Option<ThreatDatabase>> = Mutex::new(None);
+}
+
+fn contains(field: Option<&str>, needle: &Option<String>) -> bool {
+    match needle {
+        Some(needle) => field.is_some_and(|field| field.to_lowercase().contains(&needle.to_lowercase())),
+        None => true,
+    }
+}
+
+impl ThreatSignature {
+    fn specific(&self) -> bool {
+        self.vendor_id.is_some() || self.manufacturer.is_some() || self.product.is_some()
+    }
+
+    fn matches(&self, vendor_id: u16, product_id: u16, manufacturer: Option<&str>, product: Option<&str>) -> bool {
+        self.specific()
+            && self.vendor_id.is_none_or(|vid| vid == vendor_id)
+            && self.product_id.is_none_or(|pid| pid == product_id)
+            && contains(manufacturer, &self.manufacturer)
+            && contains(product, &self.product)
+    }
+}
+
+/// Name of the first signature the device matches.
+pub fn lookup(vendor_id: u16, product_id: u16, manufacturer: Option<&str>, product: Option<&str>) -> Option<String> {
+    let update = UPDATE.lock().unwrap();
+    BUILT_IN_DATABASE
+        .signatures
+        .iter()
+        .chain(update.iter().flat_map(|db| db.signatures.iter()))
+        .find(|signature| signature.matches(vendor_id, product_id, manufacturer, product))
+        .map(|signature| signature.name.clone())
+}
+
+fn read_update() -> Result<Option<ThreatDatabase>, String> {
+    let path = usb_config::data_file(UPDATE_FILE)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Block a known attack device, trusted or not and whatever the autoblock
+/// mode. Returns whether it was blocked.
+pub fn enforce(device: &UsbDeviceInfo) -> bool {
+    let (instance_id, name) = match (device.instance_id(), device.threat_match()) {
+        (Some(instance_id), Some(name)) if device.state() == DeviceState::Connected => (instance_id, name),
+        _ => return false,
+    };
+    let result = usb_control::block(instance_id, BlockReason::KnownThreat);
+    audit::record(
+        "threat_device_blocked",
+        json!({
+            "instance_id": instance_id,
+            "vendor_id": device.vendor_id(),
+            "product_id": device.product_id(),
+            "signature": name,
+            "error": result.as_ref().err().map(ToString::to_string),
+        }),
+    );
+    notifications::notify(
+        Severity::Critical,
+        "threat_device_detected",
+        "Known attack device plugged in",
+        &match &result {
+            Ok(_) => format!("{} was detected and blocked.", name),
+            Err(e) => format!("{} was detected but could not be blocked: {}", name, e),
+        },
+    );
+    result.is_ok()
+}
+
+/// Load the last signature update and block any matching device already
+/// attached. Called from setup once the backend is up.
+pub fn load() -> Result<(), String> {
+    *UPDATE.lock().unwrap() = read_update()?;
+    for device in commands::get_usb_devices()? {
+        enforce(&device);
+    }
+    Ok(())
+}
+
+/// The built-in signatures followed by those from the last update.
+#[command]
+pub fn get_threat_signatures() -> Result<ThreatDatabase, String> {
+    let mut database = BUILT_IN_DATABASE.clone();
+    if let Some(update) = UPDATE.lock().unwrap().as_ref() {
+        database.version = database.version.max(update.version);
+        database.signatures.extend(update.signatures.iter().cloned());
+    }
+    Ok(database)
+}
+
+/// Install a signature update from `path`. Updates add to the built-in
+/// signatures and replace earlier updates; an older version is refused.
+#[command]
+pub fn update_threat_database(path: String, admin_token: Option<String>) -> Result<ThreatDatabase, UsbShieldError> {
+    admin_pin::require_admin(admin_token.as_deref(), "update the threat database")?;
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let update: ThreatDatabase =
+        serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+    if let Some(signature) = update.signatures.iter().find(|signature| !signature.specific()) {
+        return Err(format!("Signature {} would match every device", signature.name).into());
+    }
+    let installed = UPDATE.lock().unwrap().as_ref().map_or(BUILT_IN_DATABASE.version, |db| db.version);
+    if update.version < installed {
+        return Err(format!(
+            "{} is version {}; version {} is already installed",
+            path, update.version, installed
+        )
+        .into());
+    }
+
+    let target = usb_config::data_file(UPDATE_FILE)?;
+    fs::write(&target, &data).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    audit::record(
+        "threat_database_updated",
+        json!({ "path": path, "version": update.version, "signatures": update.signatures.len() }),
+    );
+    *UPDATE.lock().unwrap() = Some(update);
+    load()?;
+    Ok(get_threat_signatures()?)
+}
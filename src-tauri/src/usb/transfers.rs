@@ -0,0 +1,143 @@
+use std::{collections::HashMap, sync::Mutex, thread, time::Duration};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tauri::command;
+
+use super::backend;
+use super::events;
+use super::exfiltration;
+use super::quota;
+use super::volumes::{IoCounters, Volume};
+
+pub const EVENT_TRANSFER_STATS: &str = "usb://transfer-stats";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bytes moved to and from one removable volume since this app session
+/// first saw it mounted.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferStats {
+    pub device_instance_id: String,
+    pub mount_point: String,
+    pub volume_serial: String,
+    pub label: Option<String>,
+    pub session_started: DateTime<Utc>,
+    pub last_updated: DateTime<Utc>,
+    /// Still mounted at the last sample
+    pub mounted: bool,
+    pub totals: IoCounters,
+}
+
+struct Session {
+    stats: TransferStats,
+    // Counter value at session start, or 0 after the volume was remounted
+    baseline: IoCounters,
+    last: IoCounters,
+    // Totals of earlier mounts within this session
+    carried: IoCounters,
+}
+
+lazy_static! {
+    // Keyed by device instance ID and volume serial, so a remount under a
+    // different drive letter continues the same session.
+    static ref SESSIONS: Mutex<HashMap<String, Session>> = Mutex::new(HashMap::new());
+}
+
+fn session_key(volume: &Volume) -> String {
+    format!("{}|{}", volume.device_instance_id.to_ascii_uppercase(), volume.volume_serial)
+}
+
+fn add(a: IoCounters, b: IoCounters) -> IoCounters {
+    IoCounters {
+        bytes_read: a.bytes_read + b.bytes_read,
+        bytes_written: a.bytes_written + b.bytes_written,
+        reads: a.reads + b.reads,
+        writes: a.writes + b.writes,
+    }
+}
+
+fn since(current: IoCounters, baseline: IoCounters) -> IoCounters {
+    IoCounters {
+        bytes_read: current.bytes_read.saturating_sub(baseline.bytes_read),
+        bytes_written: current.bytes_written.saturating_sub(baseline.bytes_written),
+        reads: current.reads.saturating_sub(baseline.reads),
+        writes: current.writes.saturating_sub(baseline.writes),
+    }
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        sample();
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+/// Read the counters of every mounted USB volume once and fold them into
+/// the session totals. Returns the updated stats.
+pub fn sample() -> Vec<TransferStats> {
+    let controller = backend::controller();
+    let volumes = controller.volumes().unwrap_or_default();
+    let now = Utc::now();
+
+    let mut sessions = SESSIONS.lock().unwrap();
+    for session in sessions.values_mut() {
+        session.stats.mounted = false;
+    }
+
+    for volume in &volumes {
+        let counters = match controller.io_counters(&volume.mount_point) {
+            Ok(counters) => counters,
+            Err(_) => continue,
+        };
+        let session = sessions.entry(session_key(volume)).or_insert_with(|| Session {
+            stats: TransferStats {
+                device_instance_id: volume.device_instance_id.clone(),
+                mount_point: volume.mount_point.clone(),
+                volume_serial: volume.volume_serial.clone(),
+                label: volume.label.clone(),
+                session_started: now,
+                last_updated: now,
+                mounted: true,
+                totals: IoCounters::default(),
+            },
+            // Traffic from before we started watching is not ours to count
+            baseline: counters,
+            last: counters,
+            carried: IoCounters::default(),
+        });
+
+        // The volume manager restarts its counters on remount
+        if counters.bytes_read < session.last.bytes_read || counters.bytes_written < session.last.bytes_written {
+            session.carried = add(session.carried, since(session.last, session.baseline));
+            session.baseline = IoCounters::default();
+        }
+        session.last = counters;
+        session.stats.mount_point = volume.mount_point.clone();
+        session.stats.mounted = true;
+        session.stats.last_updated = now;
+        session.stats.totals = add(session.carried, since(counters, session.baseline));
+    }
+
+    let stats: Vec<TransferStats> = sessions.values().map(|s| s.stats.clone()).collect();
+    drop(sessions);
+    exfiltration::check(&stats);
+    quota::check(&stats);
+    events::emit(EVENT_TRANSFER_STATS, stats.clone());
+    stats
+}
+
+/// Session totals across all volumes of one device.
+pub fn device_totals(instance_id: &str) -> Option<IoCounters> {
+    let sessions = SESSIONS.lock().unwrap();
+    sessions
+        .values()
+        .filter(|s| s.stats.device_instance_id.eq_ignore_ascii_case(instance_id))
+        .map(|s| s.stats.totals)
+        .reduce(add)
+}
+
+#[command]
+pub fn get_transfer_stats() -> Result<Vec<TransferStats>, String> {
+    Ok(SESSIONS.lock().unwrap().values().map(|s| s.stats.clone()).collect())
+}
@@ -0,0 +1,104 @@
+use std::thread;
+use tauri::{
+    async_runtime::block_on,
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
+    AppHandle, Manager,
+};
+use tauri_plugin_notification::NotificationExt;
+
+use super::commands;
+use super::events;
+use super::pause;
+
+const MAIN_WINDOW: &str = "main";
+const PAUSE_FROM_TRAY_SECS: u64 = 10 * 60;
+
+/// Put the tray icon up. Closing the main window only hides it; the tray
+/// keeps the app, and so autoblock, running until Quit.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let block = MenuItem::with_id(app, "block_untrusted", "Block all untrusted", true, None::<&str>)?;
+    let pause = MenuItem::with_id(app, "pause", "Pause protection for 10 min", true, None::<&str>)?;
+    let resume = MenuItem::with_id(app, "resume", "Resume protection", true, None::<&str>)?;
+    let open = MenuItem::with_id(app, "open", "Open dashboard", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[&block, &pause, &resume, &PredefinedMenuItem::separator(app)?, &open, &quit],
+    )?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("USB-Shield")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                open_dashboard(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let hidden = window.clone();
+        window.on_window_event(move |event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                api.prevent_close();
+                let _ = hidden.hide();
+            }
+        });
+    }
+    Ok(())
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let action: fn() -> Result<(), String> = match event.id.as_ref() {
+        "block_untrusted" => || block_on(commands::block_all_untrusted()).map(|_| ()).map_err(String::from),
+        // No admin session from the tray; with a PIN set this fails and says so
+        "pause" => || pause::pause_protection(PAUSE_FROM_TRAY_SECS, None).map(|_| ()).map_err(String::from),
+        "resume" => || pause::resume_protection().map(|_| ()),
+        "open" => return open_dashboard(app),
+        "quit" => return app.exit(0),
+        _ => return,
+    };
+    // Off the event loop: consent prompts block until answered, and menu
+    // clicks have nowhere to return an error to
+    thread::spawn(move || {
+        if let Err(e) = action() {
+            balloon("USB-Shield", &e);
+        }
+    });
+}
+
+fn open_dashboard(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Whether the dashboard is on screen to render toasts itself.
+pub fn dashboard_visible() -> bool {
+    events::app_handle()
+        .and_then(|app| app.get_webview_window(MAIN_WINDOW))
+        .and_then(|window| window.is_visible().ok())
+        .unwrap_or(false)
+}
+
+/// Show a native notification from the tray. A no-op before setup has run.
+pub fn balloon(title: &str, body: &str) {
+    if let Some(app) = events::app_handle() {
+        if let Err(e) = app.notification().builder().title(title).body(body).show() {
+            log::error!("Failed to show notification: {}", e);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::category::{InterfaceClass, CLASS_HID};
+use super::commands::app_state;
+use super::hello;
+use super::usb_config;
+use super::volumes::Volume;
+
+const TRUST_RULES_FILE: &str = "trust-rules.json";
+
+/// Narrows a VID/PID trust entry to one physical stick and its current
+/// formatting. Reformatting changes the volume serial, so a reformatted
+/// stick stops matching until it is approved again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustBinding {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Device serial the trust is pinned to
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// Accepted volume serials (`"1A2B-3C4D"`); empty accepts any
+    #[serde(default)]
+    pub volume_serials: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BindingVerdict {
+    /// No binding for this VID/PID; plain VID/PID trust applies
+    Unbound,
+    Matched,
+    /// Bound, but this unit or its volumes differ: needs re-approval
+    Mismatched,
+}
+
+// Saved next to trusted-devices.json: without them every pinned model
+// would fall back to plain VID/PID trust after a restart
+#[derive(Default, Serialize, Deserialize)]
+struct StoredRules {
+    #[serde(default)]
+    bindings: Vec<TrustBinding>,
+    #[serde(default)]
+    label_rules: Vec<String>,
+}
+
+// Lock BINDINGS before LABEL_RULES when both are needed
+lazy_static! {
+    static ref BINDINGS: Mutex<Vec<TrustBinding>> = Mutex::new(Vec::new());
+    // Volume label patterns such as `CORP-*`, set by the provisioning tool
+    static ref LABEL_RULES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+fn save(bindings: &[TrustBinding], label_rules: &[String]) -> Result<(), String> {
+    let stored = StoredRules {
+        bindings: bindings.to_vec(),
+        label_rules: label_rules.to_vec(),
+    };
+    let path = usb_config::data_file(TRUST_RULES_FILE)?;
+    let data = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Replace the bindings and label rules with the saved ones. Part of
+/// `load_trusted_devices`; a file that fails to parse leaves them alone.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(TRUST_RULES_FILE)?;
+    let stored: StoredRules = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoredRules::default(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let mut bindings = BINDINGS.lock().unwrap();
+    *bindings = stored.bindings;
+    *LABEL_RULES.lock().unwrap() = stored.label_rules;
+    Ok(())
+}
+
+/// Check a device against the bindings for its VID/PID. Volume serials are
+/// only compared once a volume is mounted; a blocked stick never mounts, so
+/// requiring one up front would lock it out for good.
+pub fn verdict(vendor_id: u16, product_id: u16, serial: Option<&str>, volumes: &[Volume]) -> BindingVerdict {
+    let bindings = BINDINGS.lock().unwrap();
+    let mut candidates = bindings
+        .iter()
+        .filter(|b| b.vendor_id == vendor_id && b.product_id == product_id)
+        .peekable();
+    if candidates.peek().is_none() {
+        return BindingVerdict::Unbound;
+    }
+
+    let matched = candidates.any(|binding| {
+        let serial_ok = match (&binding.serial, serial) {
+            (None, _) => true,
+            (Some(bound), Some(actual)) => bound.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+        };
+        let volumes_ok = binding.volume_serials.is_empty()
+            || volumes.iter().all(|volume| {
+                binding
+                    .volume_serials
+                    .iter()
+                    .any(|bound| bound.eq_ignore_ascii_case(&volume.volume_serial))
+            });
+        serial_ok && volumes_ok
+    });
+    if matched {
+        BindingVerdict::Matched
+    } else {
+        BindingVerdict::Mismatched
+    }
+}
+
+/// The binding for one unit, if it has one.
+pub fn binding_for(vendor_id: u16, product_id: u16, serial: Option<&str>) -> Option<TrustBinding> {
+    BINDINGS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|b| b.vendor_id == vendor_id && b.product_id == product_id && b.serial.as_deref() == serial)
+        .cloned()
+}
+
+/// Replace any binding for the same unit and trust its VID/PID. Callers
+/// handle consent and auditing. The binding is only kept if it could be
+/// saved.
+pub fn install_binding(binding: TrustBinding) -> Result<(), String> {
+    {
+        let mut bindings = BINDINGS.lock().unwrap();
+        let mut updated = bindings.clone();
+        updated.retain(|b| {
+            !(b.vendor_id == binding.vendor_id && b.product_id == binding.product_id && b.serial == binding.serial)
+        });
+        updated.push(binding.clone());
+        save(&updated, &LABEL_RULES.lock().unwrap())?;
+        *bindings = updated;
+    }
+    app_state()
+        .add_trusted_device(binding.vendor_id, binding.product_id)
+        .map_err(String::from)
+}
+
+#[command]
+pub fn get_trust_bindings() -> Result<Vec<TrustBinding>, String> {
+    Ok(BINDINGS.lock().unwrap().clone())
+}
+
+/// Trust a present device and pin the trust to its serial and the volume
+/// serials it currently carries. Calling it again for a reformatted stick is
+/// the re-approval, and asks for confirmation.
+#[command]
+pub fn bind_trusted_device(instance_id: String) -> Result<TrustBinding, String> {
+    let controller = backend::controller();
+    let node = controller
+        .devnodes()?
+        .into_iter()
+        .find(|node| node.instance_id.eq_ignore_ascii_case(&instance_id))
+        .ok_or_else(|| format!("Device not found: {}", instance_id))?;
+    let volume_serials: Vec<String> = controller
+        .volumes()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|volume| volume.device_instance_id.eq_ignore_ascii_case(&instance_id))
+        .map(|volume| volume.volume_serial)
+        .collect();
+
+    let binding = TrustBinding {
+        vendor_id: node.vendor_id,
+        product_id: node.product_id,
+        serial: node.serial.clone(),
+        volume_serials,
+    };
+
+    let previous = binding_for(binding.vendor_id, binding.product_id, binding.serial.as_deref());
+    if previous.as_ref().is_some_and(|p| *p != binding) {
+        hello::require_consent("re-approve a trusted device")?;
+    }
+
+    install_binding(binding.clone())?;
+    audit::record(
+        "trust_binding_set",
+        json!({ "instance_id": instance_id, "binding": binding, "previous": previous }),
+    );
+    Ok(binding)
+}
+
+#[command]
+pub fn remove_trust_binding(vendor_id: u16, product_id: u16, serial: Option<String>) -> Result<(), String> {
+    let mut bindings = BINDINGS.lock().unwrap();
+    let mut updated = bindings.clone();
+    updated.retain(|b| !(b.vendor_id == vendor_id && b.product_id == product_id && b.serial == serial));
+    if updated.len() == bindings.len() {
+        return Err("No such trust binding".to_string());
+    }
+    save(&updated, &LABEL_RULES.lock().unwrap())?;
+    *bindings = updated;
+    drop(bindings);
+    audit::record(
+        "trust_binding_removed",
+        json!({ "vendor_id": vendor_id, "product_id": product_id, "serial": serial }),
+    );
+    Ok(())
+}
+
+/// Whether a device is trusted through the label convention: every mounted
+/// volume carries a label matching a rule. Devices that also expose HID are
+/// never trusted this way, since anyone can label a BadUSB stick `CORP-`.
+/// The label is only readable once mounted, so this cannot vouch for a stick
+/// that was blocked on arrival.
+pub fn label_allowed(volumes: &[Volume], interfaces: &[InterfaceClass]) -> bool {
+    if volumes.is_empty() || interfaces.iter().any(|i| i.class_code == CLASS_HID) {
+        return false;
+    }
+    let rules = LABEL_RULES.lock().unwrap();
+    volumes.iter().all(|volume| {
+        volume
+            .label
+            .as_deref()
+            .is_some_and(|label| rules.iter().any(|pattern| glob_match(pattern, label)))
+    })
+}
+
+/// Case-insensitive match supporting `*` (any run) and `?` (one character).
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_uppercase().chars().collect();
+    let text: Vec<char> = text.to_uppercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, tried)) = backtrack {
+            p = star + 1;
+            t = tried + 1;
+            backtrack = Some((star, tried + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[command]
+pub fn get_volume_label_rules() -> Result<Vec<String>, String> {
+    Ok(LABEL_RULES.lock().unwrap().clone())
+}
+
+/// Replace the label allow-list. Adding a pattern widens trust and asks
+/// for confirmation; removing patterns does not.
+#[command]
+pub fn set_volume_label_rules(rules: Vec<String>) -> Result<(), String> {
+    let rules: Vec<String> = rules
+        .into_iter()
+        .map(|rule| rule.trim().to_string())
+        .filter(|rule| !rule.is_empty())
+        .collect();
+    if rules.iter().any(|rule| rule.chars().all(|c| c == '*' || c == '?')) {
+        return Err("A label pattern must contain at least one literal character".to_string());
+    }
+
+    let previous = LABEL_RULES.lock().unwrap().clone();
+    if rules.iter().any(|rule| !previous.contains(rule)) {
+        hello::require_consent("allow volumes by label")?;
+    }
+    let bindings = BINDINGS.lock().unwrap();
+    let mut label_rules = LABEL_RULES.lock().unwrap();
+    save(&bindings, &rules)?;
+    *label_rules = rules.clone();
+    drop(label_rules);
+    drop(bindings);
+    audit::record("volume_label_rules_changed", json!({ "previous": previous, "current": rules }));
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::backend;
+use super::hello;
+use super::signing;
+use super::trust_rules::{self, TrustBinding};
+
+// 2: the key is derived with Argon2id and a salt carried in the token
+const TOKEN_VERSION: u8 = 2;
+// Prefix of the QR payload, so a scanner app shows what it is
+const QR_PREFIX: &str = "USB-SHIELD-TRUST:";
+const TOKEN_LIFETIME_HOURS: i64 = 24;
+
+// No 0/O, 1/I/L or U, which get misread when typed from a screen
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTVWXYZ23456789";
+const CODE_LEN: usize = 10;
+const SALT_LEN: usize = 16;
+
+/// What a share token vouches for. Short field names keep the QR small.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SharedTrust {
+    v: u8,
+    /// For `code_key`, so the importer derives the same key
+    #[serde(default)]
+    salt: String,
+    binding: TrustBinding,
+    #[serde(default)]
+    product: Option<String>,
+    /// Machine the token was exported on
+    #[serde(default)]
+    from: Option<String>,
+    exp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrustShare {
+    pub token: String,
+    /// `token` with a recognisable prefix, for rendering as a QR code
+    pub qr_payload: String,
+    /// Typed in on the importing machine. Shown separately from the token:
+    /// anyone holding both can trust the device.
+    pub code: String,
+    pub expires: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedTrust {
+    pub binding: TrustBinding,
+    pub product: Option<String>,
+    pub from: Option<String>,
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    let code: String = (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &code[..CODE_LEN / 2], &code[CODE_LEN / 2..])
+}
+
+/// The HMAC key both machines derive from the share code and the token's
+/// salt. The machine key cannot be used: the importer does not have it. The
+/// code is short enough to guess offline from a captured token, so every
+/// guess costs a full Argon2id run.
+fn code_key(code: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let mut key = vec![0u8; 32];
+    Argon2::default()
+        .hash_password_into(normalized.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive the share key: {}", e))?;
+    Ok(key)
+}
+
+/// Export the trust binding of a present, bound device as a token another
+/// install can import with `import_trusted_device`. The token is signed with
+/// a one-off code and expires after a day.
+#[command]
+pub fn export_trusted_device(instance_id: String) -> Result<TrustShare, String> {
+    let node = backend::controller()
+        .devnodes()?
+        .into_iter()
+        .find(|node| node.instance_id.eq_ignore_ascii_case(&instance_id))
+        .ok_or_else(|| format!("Device not found: {}", instance_id))?;
+    let binding = trust_rules::binding_for(node.vendor_id, node.product_id, node.serial.as_deref())
+        .ok_or_else(|| "Trust the device on this machine before sharing it".to_string())?;
+    if binding.serial.is_none() {
+        // Without a serial the token would vouch for every unit of the model
+        return Err("The device has no serial number and cannot be shared".to_string());
+    }
+
+    let salt: [u8; SALT_LEN] = rand::thread_rng().gen();
+    let shared = SharedTrust {
+        v: TOKEN_VERSION,
+        salt: URL_SAFE_NO_PAD.encode(salt),
+        binding,
+        product: node.description.clone(),
+        from: std::env::var("COMPUTERNAME").ok(),
+        exp: Utc::now() + Duration::hours(TOKEN_LIFETIME_HOURS),
+    };
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&shared).map_err(|e| e.to_string())?);
+    let code = generate_code();
+    let signature = signing::sign_with(&code_key(&code, &salt)?, payload.as_bytes())?;
+    let token = format!("{}.{}", payload, signature);
+
+    audit::record(
+        "trusted_device_exported",
+        json!({ "instance_id": instance_id, "binding": shared.binding, "expires": shared.exp }),
+    );
+    Ok(TrustShare {
+        qr_payload: format!("{}{}", QR_PREFIX, token),
+        token,
+        code,
+        expires: shared.exp,
+    })
+}
+
+/// Verify a token from `export_trusted_device` against its share code and
+/// trust the device it describes. Accepts the QR payload as well.
+#[command]
+pub fn import_trusted_device(token: String, code: String) -> Result<ImportedTrust, String> {
+    let token = token.trim();
+    let token = token.strip_prefix(QR_PREFIX).unwrap_or(token);
+    let (payload, signature) = token
+        .split_once('.')
+        .ok_or_else(|| "Not a USB-Shield trust token".to_string())?;
+    // Read before it is verified only for the version and salt; a tampered
+    // salt derives another key and fails the check below
+    let shared: SharedTrust = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .ok_or_else(|| "The token is damaged".to_string())?;
+    if shared.v != TOKEN_VERSION {
+        return Err(format!("Unsupported token version {}", shared.v));
+    }
+    let salt = URL_SAFE_NO_PAD
+        .decode(&shared.salt)
+        .map_err(|_| "The token is damaged".to_string())?;
+    if !signing::verify_with(&code_key(&code, &salt)?, payload.as_bytes(), signature)? {
+        return Err("The code does not match this token".to_string());
+    }
+    if shared.exp < Utc::now() {
+        return Err("The token has expired; export it again".to_string());
+    }
+    if shared.binding.serial.is_none() {
+        return Err("The token does not name a single device".to_string());
+    }
+
+    hello::require_consent("trust a device shared from another machine")?;
+    trust_rules::install_binding(shared.binding.clone())?;
+    audit::record(
+        "trusted_device_imported",
+        json!({ "binding": shared.binding, "product": shared.product, "from": shared.from }),
+    );
+    Ok(ImportedTrust {
+        binding: shared.binding,
+        product: shared.product,
+        from: shared.from,
+    })
+}
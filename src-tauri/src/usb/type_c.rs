@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use tauri::command;
+use windows::{
+    core::GUID,
+    Win32::{
+        Devices::DeviceAndDriverInstallation::{
+            SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW, DIGCF_PRESENT, SPDRP_DEVICEDESC,
+            SPDRP_FRIENDLYNAME, SP_DEVINFO_DATA,
+        },
+        Foundation::HWND,
+    },
+};
+
+use super::backend;
+use super::correlation::{devnode_status, instance_id, registry_strings};
+
+// Setup class "UCM" (USB Connector Managers), where UcmUcsiCx and
+// UcmTcpciCx clients register
+const GUID_DEVCLASS_UCM: GUID = GUID::from_u128(0xe6f1aa1c_7f3b_4473_b2e8_c97d8ac71d53);
+
+/// What is plugged into a Type-C connector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartnerKind {
+    Nothing,
+    /// Supplies power but offers no data lanes: a plain charger
+    PowerOnly,
+    /// Enumerates over USB, with or without charging
+    Data,
+    /// Audio or debug accessory mode
+    Accessory,
+    /// The platform does not say
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerContract {
+    pub voltage_mv: u32,
+    pub current_ma: u32,
+    /// Negotiated over USB PD, not just advertised through Type-C current
+    pub power_delivery: bool,
+}
+
+/// One Type-C connector behind a connector manager.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeCPort {
+    /// The connector manager devnode, e.g. `ACPI\USBC000\0`
+    pub manager_instance_id: String,
+    #[serde(default)]
+    pub manager_name: Option<String>,
+    /// Connector number, when the platform reports connectors individually
+    #[serde(default)]
+    pub connector: Option<u8>,
+    pub partner: PartnerKind,
+    /// This machine is the sink of the current contract
+    #[serde(default)]
+    pub charging: Option<bool>,
+    #[serde(default)]
+    pub contract: Option<PowerContract>,
+}
+
+/// Connector managers present on this machine. UCM keeps partner and
+/// contract state in the kernel and publishes none of it to user mode, so
+/// each manager is reported with those fields unknown; the simulated
+/// backend fills them in.
+pub fn enumerate_type_c_ports() -> Result<Vec<TypeCPort>, String> {
+    let mut ports = Vec::new();
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(Some(&GUID_DEVCLASS_UCM), None, HWND(0), DIGCF_PRESENT)
+            .map_err(|e| format!("Failed to enumerate connector managers: {}", e))?;
+        let mut device_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+
+        for index in 0.. {
+            if !SetupDiEnumDeviceInfo(device_info_set, index, &mut device_info_data).as_bool() {
+                break;
+            }
+            let manager_instance_id = match instance_id(device_info_set, &device_info_data) {
+                Some(id) => id,
+                None => continue,
+            };
+            // A stopped manager reports nothing at all
+            if !devnode_status(device_info_data.DevInst).0 {
+                continue;
+            }
+            ports.push(TypeCPort {
+                manager_instance_id,
+                manager_name: registry_strings(device_info_set, &device_info_data, SPDRP_FRIENDLYNAME)
+                    .into_iter()
+                    .chain(registry_strings(device_info_set, &device_info_data, SPDRP_DEVICEDESC))
+                    .next(),
+                connector: None,
+                partner: PartnerKind::Unknown,
+                charging: None,
+                contract: None,
+            });
+        }
+
+        SetupDiDestroyDeviceInfoList(device_info_set);
+    }
+    Ok(ports)
+}
+
+#[command]
+pub fn get_type_c_ports() -> Result<Vec<TypeCPort>, String> {
+    backend::controller().type_c_ports()
+}
@@ -0,0 +1,319 @@
+use std::{collections::HashSet, fs, path::PathBuf, thread, time::Duration};
+use serde::Serialize;
+use serde_json::json;
+use tauri::{command, AppHandle};
+use uport_shield_helper::{
+    auth,
+    protocol::{GuardPolicy, RemovableStoragePolicy},
+    registry::{self, Root},
+    service,
+};
+use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+use super::admin_pin;
+use super::audit;
+use super::backend;
+use super::commands::{restart_storage, REMOVABLE_STORAGE_POLICY_KEY};
+use super::error::UsbShieldError;
+use super::event_log;
+use super::gpo_policy::StorageClass;
+use super::hello;
+use super::helper_client;
+use super::operations;
+use super::privilege;
+use super::reblock;
+use super::security_key;
+use super::simulation;
+use super::storage_readonly;
+use super::tamper;
+use super::usb_config;
+use super::usb_control;
+
+/// Passed by the uninstaller: clean up, then exit without starting the UI.
+pub const CLEANUP_ARG: &str = "--cleanup";
+// `identifier` in tauri.conf.json, which names the app data directory
+const APP_IDENTIFIER: &str = "com.uport-shield.app";
+const PROFILE_LIST_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\ProfileList";
+// Long enough for the IPC reply carrying the report to reach the window
+const EXIT_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, Serialize)]
+pub struct CleanupReport {
+    pub devices_enabled: Vec<String>,
+    /// Devices we disabled that are not plugged in, so cannot be enabled now
+    pub devices_absent: Vec<String>,
+    /// Policies removed or reset, e.g. `HKLM\...\USBSTOR\Start`
+    pub policies_cleared: Vec<String>,
+    /// The helper service was stopped and deleted, with its ProgramData
+    /// directory
+    pub helper_removed: bool,
+    pub data_removed: bool,
+    pub errors: Vec<String>,
+}
+
+// What the audit log says we left in place, for a process that did not
+// make those changes itself
+#[derive(Default)]
+struct Leftovers {
+    // Upper-case instance IDs whose last recorded change was a disable
+    disabled: HashSet<String>,
+    storage_classes: Vec<StorageClass>,
+    readonly: bool,
+}
+
+fn leftovers() -> Result<Leftovers, String> {
+    let mut leftovers = Leftovers::default();
+    for entry in audit::read_all()? {
+        let details = &entry.details;
+        match entry.action.as_str() {
+            "device_disabled" | "device_enabled" => {
+                if let Some(instance_id) = details["instance_id"].as_str() {
+                    let key = instance_id.to_ascii_uppercase();
+                    if entry.action == "device_disabled" {
+                        leftovers.disabled.insert(key);
+                    } else {
+                        leftovers.disabled.remove(&key);
+                    }
+                }
+            }
+            "gpo_storage_policy_changed" => {
+                if let Ok(class) = serde_json::from_value::<StorageClass>(details["class"].clone()) {
+                    if !leftovers.storage_classes.contains(&class) {
+                        leftovers.storage_classes.push(class);
+                    }
+                }
+            }
+            "storage_readonly_changed" => leftovers.readonly = details["enabled"].as_bool().unwrap_or(false),
+            _ => {}
+        }
+    }
+    Ok(leftovers)
+}
+
+fn enable_devices(leftovers: &Leftovers, report: &mut CleanupReport) {
+    for pending in reblock::pending() {
+        reblock::cancel(pending.id);
+    }
+
+    let mut ours = leftovers.disabled.clone();
+    ours.extend(usb_control::block_records().into_iter().map(|record| record.instance_id.to_ascii_uppercase()));
+    // Stop the service's guard first, or it would block them again
+    if helper_client::is_running() {
+        match helper_client::sync_guard(GuardPolicy::default()) {
+            Ok(status) => ours.extend(status.blocked.into_iter().map(|block| block.instance_id.to_ascii_uppercase())),
+            Err(e) => report.errors.push(format!("Helper service guard: {}", e)),
+        }
+    }
+
+    let present = match backend::controller().devnodes() {
+        Ok(devnodes) => devnodes,
+        Err(e) => {
+            report.errors.push(e);
+            return;
+        }
+    };
+    let mut ours: Vec<String> = ours.into_iter().collect();
+    ours.sort();
+    for instance_id in ours {
+        let node = present.iter().find(|node| node.instance_id.eq_ignore_ascii_case(&instance_id));
+        if node.is_some_and(|node| !node.disabled) {
+            continue;
+        }
+        match usb_control::unblock(&instance_id) {
+            Ok(_) => report.devices_enabled.push(instance_id),
+            Err(UsbShieldError::DeviceNotFound { .. }) => report.devices_absent.push(instance_id),
+            Err(e) => report.errors.push(format!("{}: {}", instance_id, e)),
+        }
+    }
+}
+
+fn clear_policies(leftovers: &Leftovers, report: &mut CleanupReport) {
+    let controller = backend::controller();
+    let mut outcome = |policy: String, result: Result<(), String>| match result {
+        Ok(()) => report.policies_cleared.push(policy),
+        Err(e) => report.errors.push(format!("{}: {}", policy, e)),
+    };
+
+    if controller.usbstor_start() == Some(4) {
+        outcome(
+            r"HKLM\SYSTEM\CurrentControlSet\Services\USBSTOR\Start".to_string(),
+            tamper::write_usbstor_start(None, || controller.apply_policy(3)).map_err(String::from),
+        );
+    }
+    outcome(
+        format!(r"HKCU\{}", REMOVABLE_STORAGE_POLICY_KEY),
+        registry::delete_tree(Root::CurrentUser, REMOVABLE_STORAGE_POLICY_KEY).map_err(String::from),
+    );
+    for class in &leftovers.storage_classes {
+        for guid in class.class_guids() {
+            let policy = RemovableStoragePolicy {
+                class_guid: guid.to_string(),
+                deny_read: false,
+                deny_write: false,
+                deny_execute: false,
+            };
+            outcome(
+                format!(r"HKLM\SOFTWARE\Policies\Microsoft\Windows\RemovableStorageDevices\{}", guid),
+                tamper::write_removable_storage(&policy, || controller.set_removable_storage_policy(&policy))
+                    .map_err(String::from),
+            );
+        }
+    }
+    if leftovers.readonly || storage_readonly::enabled() {
+        outcome(
+            r"HKLM\SYSTEM\CurrentControlSet\Control\StorageDevicePolicies\WriteProtect".to_string(),
+            controller.set_storage_write_protect(false).map_err(String::from),
+        );
+    }
+    outcome(format!("{} event source", event_log::SOURCE), event_log::remove_source());
+
+    if let Err(e) = restart_storage() {
+        report.errors.push(e);
+    }
+}
+
+// After the devices, whose re-enabling may still go through the helper
+fn remove_helper(report: &mut CleanupReport) {
+    // Simulated machines have no service to remove
+    if simulation::is_active() {
+        return;
+    }
+    if service::is_installed() {
+        if let Err(e) = service::uninstall() {
+            report.errors.push(format!("Helper service: {}", e));
+            return;
+        }
+    }
+    if let Some(dir) = auth::secret_path().parent() {
+        match fs::remove_dir_all(dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                report.errors.push(format!("Failed to remove {}: {}", dir.display(), e));
+                return;
+            }
+        }
+    }
+    report.helper_removed = true;
+}
+
+/// Undo everything USB-Shield enforces on this machine: re-enable the
+/// devices it disabled, remove its storage policies, event source and
+/// helper service, and delete its data directory. The audit log is read first, so this also
+/// works in a fresh process, as the uninstaller runs it.
+pub fn cleanup() -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let leftovers = leftovers().unwrap_or_else(|e| {
+        report.errors.push(format!("Audit log: {}", e));
+        Leftovers::default()
+    });
+
+    enable_devices(&leftovers, &mut report);
+    clear_policies(&leftovers, &mut report);
+    remove_helper(&mut report);
+
+    // The log is about to go, but a running app still forwards this to SIEM
+    audit::record(
+        "enforcements_cleaned_up",
+        json!({
+            "devices_enabled": report.devices_enabled,
+            "devices_absent": report.devices_absent,
+            "policies_cleared": report.policies_cleared,
+            "helper_removed": report.helper_removed,
+            "errors": report.errors,
+            "user": std::env::var("USERNAME").ok(),
+            "computer": std::env::var("COMPUTERNAME").ok(),
+        }),
+    );
+    match usb_config::data_dir().and_then(|dir| {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))
+    }) {
+        Ok(()) => report.data_removed = true,
+        Err(e) => report.errors.push(e),
+    }
+    report
+}
+
+// Every user profile with an app data directory. The uninstaller runs
+// elevated, possibly as another administrator than whoever used the app,
+// so its own APPDATA says nothing.
+fn profile_data_dirs() -> Vec<PathBuf> {
+    let profiles = match RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(PROFILE_LIST_KEY) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            eprintln!("Cannot list user profiles: {}", e);
+            return Vec::new();
+        }
+    };
+    let system_drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    profiles
+        .enum_keys()
+        .flatten()
+        .filter_map(|sid| profiles.open_subkey(sid).ok())
+        .filter_map(|profile| profile.get_value::<String, _>("ProfileImagePath").ok())
+        .map(|path| {
+            PathBuf::from(path.replace("%SystemDrive%", &system_drive))
+                .join("AppData")
+                .join("Roaming")
+                .join(APP_IDENTIFIER)
+        })
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Entry point for `uport-shield.exe --cleanup`. Only an elevated process,
+/// as the uninstaller is, may run it. Cleans up once per user profile that
+/// used the app, reading that user's audit log. Returns the exit code.
+pub fn run_from_uninstaller() -> i32 {
+    if !privilege::is_elevated() {
+        eprintln!("{} must be run elevated", CLEANUP_ARG);
+        return 1;
+    }
+    if let Err(e) = backend::init() {
+        eprintln!("{}", e);
+        return 1;
+    }
+    let mut data_dirs = profile_data_dirs();
+    if data_dirs.is_empty() {
+        // Nobody's audit log to go by, but the machine-wide policies still go
+        data_dirs.push(std::env::temp_dir().join(APP_IDENTIFIER));
+    }
+    let mut failed = false;
+    for data_dir in data_dirs {
+        if let Err(e) = usb_config::init(data_dir) {
+            eprintln!("{}", e);
+            failed = true;
+            continue;
+        }
+        let report = cleanup();
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        failed |= !report.errors.is_empty();
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+/// `cleanup` from the UI, e.g. before removing the app by hand. Needs the
+/// same proof as `unblock_everything`; the app exits shortly after the
+/// report is returned, since nothing it would go on doing is wanted any more.
+#[command]
+pub async fn cleanup_all_enforcements(
+    app: AppHandle,
+    admin_token: Option<String>,
+) -> Result<CleanupReport, UsbShieldError> {
+    operations::run("cleanup_all_enforcements", move || {
+        admin_pin::require_admin(admin_token.as_deref(), "remove all USB-Shield enforcement")?;
+        security_key::require_presence("remove all USB-Shield enforcement")?;
+        hello::require_consent("remove all USB-Shield enforcement")?;
+        let report = cleanup();
+        thread::spawn(move || {
+            thread::sleep(EXIT_DELAY);
+            app.exit(0);
+        });
+        Ok(report)
+    })
+    .await
+}
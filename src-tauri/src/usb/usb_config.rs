@@ -0,0 +1,30 @@
+use std::{fs, path::PathBuf, sync::RwLock};
+
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Set the app data directory (created if missing). Called from setup, and
+/// by the uninstaller once for each user profile it cleans up.
+pub fn init(data_dir: PathBuf) -> Result<(), String> {
+    fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+    *DATA_DIR.write().unwrap() = Some(data_dir);
+    Ok(())
+}
+
+/// The app data directory itself.
+pub fn data_dir() -> Result<PathBuf, String> {
+    DATA_DIR
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "App data directory not initialised".to_string())
+}
+
+/// Path of a file inside the app data directory.
+pub fn data_file(name: &str) -> Result<PathBuf, String> {
+    DATA_DIR
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|dir| dir.join(name))
+        .ok_or_else(|| "App data directory not initialised".to_string())
+}
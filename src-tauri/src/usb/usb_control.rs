@@ -0,0 +1,332 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::audit;
+use super::backend;
+use super::correlation::parse_vid_pid;
+use super::error::UsbShieldError;
+use super::etw::{self, TraceEvent};
+use super::event_log::{self, LogEvent};
+use super::webhooks::{self, WebhookEvent};
+
+pub const DEFAULT_OPERATION_TIMEOUT_MS: u64 = 15_000;
+
+lazy_static! {
+    // Devnodes this app has disabled, keyed by instance ID; rebuilt from the
+    // audit log at startup
+    static ref BLOCK_RECORDS: Mutex<HashMap<String, BlockRecord>> = Mutex::new(HashMap::new());
+    static ref OPERATION_TIMEOUT_MS: Mutex<u64> = Mutex::new(DEFAULT_OPERATION_TIMEOUT_MS);
+    // Instance IDs whose class installer call never returned
+    static ref HUNG_OPERATIONS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+pub fn operation_timeout() -> Duration {
+    Duration::from_millis(*OPERATION_TIMEOUT_MS.lock().unwrap())
+}
+
+pub fn set_operation_timeout(timeout_ms: u64) {
+    *OPERATION_TIMEOUT_MS.lock().unwrap() = timeout_ms;
+}
+
+/// Why a devnode was blocked: which decision path disabled it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum BlockReason {
+    /// The user, from the UI, a batch or a class-wide action
+    Manual,
+    /// An untrusted arrival while autoblock was on
+    Autoblock,
+    ClassPolicy,
+    HubPolicy,
+    WirelessPolicy,
+    /// A Strict or stricter profile blocking untrusted devices
+    Profile,
+    /// A timed unblock ran out
+    TimedReblock,
+    RemoteUsbPolicy,
+    /// Storage blocked while the VPN is down
+    VpnPolicy,
+    DeepLink,
+    /// Plugged into a locked port
+    PortLock,
+    /// Matched a known attack device signature
+    KnownThreat,
+    /// Typed faster than a person can right after it was plugged in
+    HidQuarantine,
+    /// An untrusted arrival waiting for the user's decision
+    Quarantine,
+    /// A keyboard not seen before, while keyboard lockdown is on
+    KeyboardLockdown,
+    /// A USB Ethernet adapter while those are blocked
+    NetworkAdapterPolicy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockRecord {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub reason: BlockReason,
+    pub blocked_at: DateTime<Utc>,
+}
+
+pub fn block_records() -> Vec<BlockRecord> {
+    BLOCK_RECORDS.lock().unwrap().values().cloned().collect()
+}
+
+pub fn is_blocked_by_us(instance_id: &str) -> bool {
+    BLOCK_RECORDS.lock().unwrap().contains_key(&instance_id.to_ascii_uppercase())
+}
+
+/// Why we blocked `instance_id`, if we did.
+pub fn block_reason(instance_id: &str) -> Option<BlockReason> {
+    BLOCK_RECORDS
+        .lock()
+        .unwrap()
+        .get(&instance_id.to_ascii_uppercase())
+        .map(|record| record.reason)
+}
+
+/// Rebuild the block records from the audit log, whose `device_disabled` and
+/// `device_enabled` entries list every change we made, so a restart still
+/// knows what it blocked and why. Devices enabled since by other means are
+/// left out.
+pub fn load() -> Result<(), String> {
+    let mut restored = HashMap::new();
+    for entry in audit::read_all()? {
+        let details = &entry.details;
+        let enable = match entry.action.as_str() {
+            "device_enabled" => true,
+            "device_disabled" => false,
+            _ => continue,
+        };
+        let instance_id = match details["instance_id"].as_str() {
+            Some(instance_id) if details["error"].is_null() => instance_id,
+            _ => continue,
+        };
+        let key = instance_id.to_ascii_uppercase();
+        if enable {
+            restored.remove(&key);
+            continue;
+        }
+        let reason = serde_json::from_value::<BlockReason>(details["reason"].clone());
+        if let (Ok(reason), Some((vendor_id, product_id))) = (reason, parse_vid_pid(instance_id)) {
+            restored.insert(
+                key,
+                BlockRecord {
+                    instance_id: instance_id.to_string(),
+                    vendor_id,
+                    product_id,
+                    reason,
+                    blocked_at: entry.timestamp,
+                },
+            );
+        }
+    }
+    let devnodes = backend::controller().devnodes()?;
+    restored.retain(|key, _| {
+        !devnodes
+            .iter()
+            .any(|node| node.instance_id.eq_ignore_ascii_case(key) && !node.disabled)
+    });
+    *BLOCK_RECORDS.lock().unwrap() = restored;
+    Ok(())
+}
+
+fn record_state(instance_id: &str, reason: Option<BlockReason>) {
+    let key = instance_id.to_ascii_uppercase();
+    let mut records = BLOCK_RECORDS.lock().unwrap();
+    match reason {
+        None => {
+            records.remove(&key);
+        }
+        Some(reason) => {
+            if let Some((vendor_id, product_id)) = parse_vid_pid(instance_id) {
+                records.insert(
+                    key,
+                    BlockRecord {
+                        instance_id: instance_id.to_string(),
+                        vendor_id,
+                        product_id,
+                        reason,
+                        blocked_at: Utc::now(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Outcome of a successful state change.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateChange {
+    pub instance_id: String,
+    pub enabled: bool,
+    /// 1 when the first attempt succeeded
+    pub attempts: u32,
+}
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF_MS: u64 = 100;
+
+/// Disable exactly one devnode, addressed by its device instance ID (e.g.
+/// `USB\VID_0781&PID_5581\4C530001230918115462`), recording `reason` with it.
+pub fn block(instance_id: &str, reason: BlockReason) -> Result<StateChange, UsbShieldError> {
+    set_device_state(instance_id, Some(reason))
+}
+
+/// Enable exactly one devnode and forget why it was blocked.
+pub fn unblock(instance_id: &str) -> Result<StateChange, UsbShieldError> {
+    set_device_state(instance_id, None)
+}
+
+/// Enable (`None`) or disable (`Some(reason)`) one devnode.
+///
+/// Disabling a device while it is still enumerating often fails transiently,
+/// so failed attempts are retried with exponential backoff (100, 200, 400 ms).
+/// Timeouts are not retried: the hung call is still holding the devnode.
+fn set_device_state(instance_id: &str, reason: Option<BlockReason>) -> Result<StateChange, UsbShieldError> {
+    let enable = reason.is_none();
+    let mut trace = etw::activity(
+        TraceEvent::SetDeviceState,
+        format!("{} -> {}", instance_id, if enable { "enable" } else { "disable" }),
+    );
+
+    let mut backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        match run_supervised(instance_id, enable) {
+            Ok(()) => break Ok(()),
+            Err(e) if matches!(e, UsbShieldError::DeviceTimeout { .. }) || attempts >= MAX_ATTEMPTS => {
+                break Err(with_attempts(e, attempts))
+            }
+            Err(_) => {
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+        }
+    };
+
+    if result.is_ok() {
+        record_state(instance_id, reason);
+    }
+    let vid_pid = parse_vid_pid(instance_id);
+    let details = json!({
+        "instance_id": instance_id,
+        "vendor_id": vid_pid.map(|(vendor_id, _)| vendor_id),
+        "product_id": vid_pid.map(|(_, product_id)| product_id),
+        "reason": reason,
+        "attempts": attempts,
+        "error": result.as_ref().err().map(UsbShieldError::to_string),
+    });
+    let (event, message) = match (&result, enable) {
+        (Ok(()), true) => (LogEvent::DeviceUnblocked, format!("USB device {} was unblocked.", instance_id)),
+        (Ok(()), false) => (LogEvent::DeviceBlocked, format!("USB device {} was blocked.", instance_id)),
+        (Err(e), _) => (
+            LogEvent::DeviceStateFailed,
+            format!(
+                "Failed to {} USB device {}: {}",
+                if enable { "unblock" } else { "block" },
+                instance_id,
+                e
+            ),
+        ),
+    };
+    event_log::write(event, &message, &details);
+    if event == LogEvent::DeviceBlocked {
+        webhooks::dispatch(WebhookEvent::DeviceBlocked, &message, &details);
+    }
+    audit::record(if enable { "device_enabled" } else { "device_disabled" }, details);
+    trace.track(result).map(|()| StateChange {
+        instance_id: instance_id.to_string(),
+        enabled: enable,
+        attempts,
+    })
+}
+
+// The shown message says how often it was tried; the kind stays
+fn with_attempts(error: UsbShieldError, attempts: u32) -> UsbShieldError {
+    let suffix = format!(" (after {} attempt(s))", attempts);
+    match error {
+        UsbShieldError::DeviceTimeout { instance_id, message } => UsbShieldError::DeviceTimeout {
+            instance_id,
+            message: message + &suffix,
+        },
+        UsbShieldError::SetupApiError {
+            instance_id,
+            code,
+            message,
+        } => UsbShieldError::SetupApiError {
+            instance_id,
+            code,
+            message: message + &suffix,
+        },
+        UsbShieldError::Other { message } => UsbShieldError::Other {
+            message: message + &suffix,
+        },
+        other => other,
+    }
+}
+
+/// Run one attempt on a worker thread supervised by a watchdog. If it does
+/// not finish within the operation timeout a `DeviceTimeout` is returned and
+/// the devnode is refused further operations until the stuck call returns.
+fn run_supervised(instance_id: &str, enable: bool) -> Result<(), UsbShieldError> {
+    let key = instance_id.to_ascii_uppercase();
+    if HUNG_OPERATIONS.lock().unwrap().contains(&key) {
+        return Err(UsbShieldError::DeviceTimeout {
+            instance_id: instance_id.to_string(),
+            message: format!("Timeout: a previous operation on {} has not completed", instance_id),
+        });
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let finished = Arc::new(AtomicBool::new(false));
+    let worker_finished = finished.clone();
+    let worker_id = instance_id.to_string();
+    thread::spawn(move || {
+        let result = backend::controller()
+            .set_device_state(&worker_id, enable)
+            .map_err(|e| UsbShieldError::device(&worker_id, e));
+        // The watchdog may have given up already; clear the hung marker either way.
+        // Both sides touch `finished` under the HUNG_OPERATIONS lock.
+        let mut hung = HUNG_OPERATIONS.lock().unwrap();
+        worker_finished.store(true, Ordering::SeqCst);
+        hung.remove(&worker_id.to_ascii_uppercase());
+        drop(hung);
+        let _ = sender.send(result);
+    });
+
+    let timeout = operation_timeout();
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            let mut hung = HUNG_OPERATIONS.lock().unwrap();
+            if !finished.load(Ordering::SeqCst) {
+                hung.insert(key);
+            }
+            Err(UsbShieldError::DeviceTimeout {
+                instance_id: instance_id.to_string(),
+                message: format!(
+                    "Timeout: device state change for {} did not complete within {} ms",
+                    instance_id,
+                    timeout.as_millis()
+                ),
+            })
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(UsbShieldError::from("Device state worker exited unexpectedly".to_string()))
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fs, sync::Mutex};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::json;
+use tauri::command;
+use usb_ids::{Device, FromId, Vendor, Vendors};
+
+use super::audit;
+use super::usb_config;
+
+const UPDATE_FILE: &str = "usb.ids";
+
+/// Vendor and product names parsed from a `usb.ids` file.
+#[derive(Debug, Clone, Default)]
+pub struct UsbIds {
+    version: Option<String>,
+    vendors: HashMap<u16, (String, HashMap<u16, String>)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsbIdsStatus {
+    /// Names come from an imported file rather than the copy built in
+    pub updated: bool,
+    /// The `# Version:` line of the imported file
+    pub version: Option<String>,
+    pub vendors: usize,
+}
+
+lazy_static! {
+    // The last imported file; loaded on first use
+    static ref UPDATE: Mutex<Option<Option<UsbIds>>> = Mutex::new(None);
+}
+
+// `0781  SanDisk Corp.`, with one more tab for each level down
+fn parse_entry(line: &str) -> Option<(u16, String)> {
+    let (id, name) = line.split_once("  ")?;
+    if id.len() != 4 {
+        return None;
+    }
+    Some((u16::from_str_radix(id, 16).ok()?, name.trim().to_string()))
+}
+
+/// Parse the vendor section of `usb.ids`. Interfaces and the class, HID and
+/// language tables after it are skipped.
+pub fn parse(text: &str) -> UsbIds {
+    let mut ids = UsbIds::default();
+    let mut vendor: Option<u16> = None;
+    for line in text.lines() {
+        if let Some(version) = line.strip_prefix("# Version:") {
+            ids.version = Some(version.trim().to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(device) = line.strip_prefix('\t') {
+            if device.starts_with('\t') {
+                continue;
+            }
+            if let (Some(vendor), Some((product_id, name))) = (vendor, parse_entry(device)) {
+                if let Some((_, products)) = ids.vendors.get_mut(&vendor) {
+                    products.insert(product_id, name);
+                }
+            }
+            continue;
+        }
+        // Any other top-level line, e.g. a class (`C 08  Mass Storage`),
+        // ends the current vendor
+        vendor = match parse_entry(line) {
+            Some((vendor_id, name)) => {
+                ids.vendors.insert(vendor_id, (name, HashMap::new()));
+                Some(vendor_id)
+            }
+            None => None,
+        };
+    }
+    ids
+}
+
+fn read_update() -> Option<UsbIds> {
+    let path = usb_config::data_file(UPDATE_FILE).ok()?;
+    let text = fs::read_to_string(path).ok()?;
+    Some(parse(&text))
+}
+
+/// Vendor and product names for a VID/PID: from the imported `usb.ids`
+/// when there is one and it knows the device, from the built-in copy
+/// otherwise.
+pub fn names(vendor_id: u16, product_id: u16) -> (Option<String>, Option<String>) {
+    let mut guard = UPDATE.lock().unwrap();
+    if let Some(ids) = guard.get_or_insert_with(read_update) {
+        if let Some((vendor, products)) = ids.vendors.get(&vendor_id) {
+            return (Some(vendor.clone()), products.get(&product_id).cloned());
+        }
+    }
+    drop(guard);
+    (
+        Vendor::from_id(vendor_id).map(|vendor| vendor.name().to_string()),
+        Device::from_vid_pid(vendor_id, product_id).map(|device| device.name().to_string()),
+    )
+}
+
+#[command]
+pub fn get_usb_ids_status() -> Result<UsbIdsStatus, String> {
+    let mut guard = UPDATE.lock().unwrap();
+    Ok(match guard.get_or_insert_with(read_update) {
+        Some(ids) => UsbIdsStatus {
+            updated: true,
+            version: ids.version.clone(),
+            vendors: ids.vendors.len(),
+        },
+        None => UsbIdsStatus {
+            updated: false,
+            version: None,
+            vendors: Vendors::iter().count(),
+        },
+    })
+}
+
+/// Import a newer `usb.ids` (from http://www.linux-usb.org/usb.ids) to
+/// resolve names the built-in copy does not know yet.
+#[command]
+pub fn update_usb_ids(path: String) -> Result<UsbIdsStatus, String> {
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let ids = parse(&text);
+    if ids.vendors.is_empty() {
+        return Err(format!("{} lists no vendors; is it a usb.ids file?", path));
+    }
+    let target = usb_config::data_file(UPDATE_FILE)?;
+    fs::write(&target, &text).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    audit::record(
+        "usb_ids_updated",
+        json!({ "path": path, "version": ids.version, "vendors": ids.vendors.len() }),
+    );
+    *UPDATE.lock().unwrap() = Some(Some(ids));
+    get_usb_ids_status()
+}
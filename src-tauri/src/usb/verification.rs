@@ -0,0 +1,203 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::events;
+use super::hello;
+use super::profiles::{self, Profile};
+use super::signing;
+use super::usb_control::{self, BlockReason};
+
+pub const EVENT_VERIFICATION: &str = "verification://completed";
+const TICK: Duration = Duration::from_secs(30);
+const MIN_INTERVAL_MINUTES: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationSchedule {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+}
+
+/// One health check of the enforcement pipeline itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+/// A device whose state did not match what policy requires.
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub instance_id: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub expected: DeviceState,
+    pub actual: DeviceState,
+    /// Set when re-applying the policy failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationReport {
+    pub timestamp: DateTime<Utc>,
+    /// Every check passed and no drift was found. Drift that was corrected
+    /// still fails the run: the control was not in force until now.
+    pub passed: bool,
+    pub profile: Profile,
+    pub checks: Vec<VerificationCheck>,
+    pub drift: Vec<Drift>,
+    /// HMAC over the report with this field empty
+    pub signature: String,
+}
+
+lazy_static! {
+    static ref SCHEDULE: Mutex<VerificationSchedule> = Mutex::new(VerificationSchedule {
+        enabled: false,
+        interval_minutes: 60,
+    });
+    static ref LAST_REPORT: Mutex<Option<VerificationReport>> = Mutex::new(None);
+}
+
+pub fn start() {
+    thread::spawn(|| {
+        let mut last_run = Instant::now();
+        loop {
+            thread::sleep(TICK);
+            let schedule = SCHEDULE.lock().unwrap().clone();
+            let interval = Duration::from_secs(schedule.interval_minutes as u64 * 60);
+            if schedule.enabled && last_run.elapsed() >= interval {
+                last_run = Instant::now();
+                verify();
+            }
+        }
+    });
+}
+
+fn check<T>(name: &str, result: Result<T, String>) -> VerificationCheck {
+    VerificationCheck {
+        name: name.to_string(),
+        passed: result.is_ok(),
+        detail: result.err(),
+    }
+}
+
+/// What policy says this device's state should be, if it says anything.
+fn expected_state(device: &UsbDeviceInfo, profile: Profile) -> Option<DeviceState> {
+    let instance_id = device.instance_id()?;
+    if usb_control::is_blocked_by_us(instance_id) {
+        return Some(DeviceState::Blocked);
+    }
+    if profile >= Profile::Strict && !device.trusted() {
+        return Some(DeviceState::Blocked);
+    }
+    None
+}
+
+/// Re-disable every device that should be blocked but is running.
+fn reapply(devices: &[UsbDeviceInfo], profile: Profile) -> Vec<Drift> {
+    devices
+        .iter()
+        .filter(|device| device.state() == DeviceState::Connected)
+        .filter_map(|device| {
+            let expected = expected_state(device, profile)?;
+            let instance_id = device.instance_id()?.to_string();
+            // A devnode we blocked keeps its original reason
+            let reason = usb_control::block_reason(&instance_id).unwrap_or(BlockReason::Profile);
+            let error = usb_control::block(&instance_id, reason).err().map(String::from);
+            Some(Drift {
+                instance_id,
+                vendor_id: device.vendor_id(),
+                product_id: device.product_id(),
+                expected,
+                actual: device.state(),
+                error,
+            })
+        })
+        .collect()
+}
+
+/// Re-block whatever in `devices` policy says should not be running, under
+/// the active profile.
+pub fn reconcile(devices: &[UsbDeviceInfo]) -> Vec<Drift> {
+    reapply(devices, profiles::active())
+}
+
+/// Run the checks, detect and correct drift, and record the signed outcome
+/// in the audit log.
+pub fn verify() -> VerificationReport {
+    let profile = profiles::active();
+    let devices = commands::get_usb_devices();
+    let mut checks = vec![
+        check("enumeration", devices.as_ref().map(|_| ()).map_err(Clone::clone)),
+        check("audit_log", audit::read_all().map(|_| ())),
+    ];
+    let drift = devices.as_deref().map(|devices| reapply(devices, profile)).unwrap_or_default();
+    checks.push(VerificationCheck {
+        name: "drift".to_string(),
+        passed: drift.is_empty(),
+        detail: (!drift.is_empty()).then(|| format!("{} device(s) re-blocked", drift.len())),
+    });
+
+    let mut report = VerificationReport {
+        timestamp: Utc::now(),
+        passed: checks.iter().all(|check| check.passed),
+        profile,
+        checks,
+        drift,
+        signature: String::new(),
+    };
+    report.signature = serde_json::to_vec(&report)
+        .map_err(|e| e.to_string())
+        .and_then(|data| signing::sign(&data))
+        .unwrap_or_else(|e| {
+            log::error!("Failed to sign verification report: {}", e);
+            String::new()
+        });
+
+    audit::record(
+        if report.passed { "verification_passed" } else { "verification_failed" },
+        json!(report),
+    );
+    events::emit(EVENT_VERIFICATION, report.clone());
+    *LAST_REPORT.lock().unwrap() = Some(report.clone());
+    report
+}
+
+#[command]
+pub fn get_verification_schedule() -> Result<VerificationSchedule, String> {
+    Ok(SCHEDULE.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_verification_schedule(schedule: VerificationSchedule) -> Result<(), String> {
+    if schedule.interval_minutes < MIN_INTERVAL_MINUTES {
+        return Err(format!("The interval must be at least {} minutes", MIN_INTERVAL_MINUTES));
+    }
+    let previous = SCHEDULE.lock().unwrap().clone();
+    if previous.enabled && (!schedule.enabled || schedule.interval_minutes > previous.interval_minutes) {
+        hello::require_consent("reduce scheduled policy verification")?;
+    }
+    audit::record("verification_schedule_changed", json!(schedule));
+    *SCHEDULE.lock().unwrap() = schedule;
+    Ok(())
+}
+
+#[command]
+pub fn run_verification_now() -> Result<VerificationReport, String> {
+    Ok(verify())
+}
+
+#[command]
+pub fn get_last_verification() -> Result<Option<VerificationReport>, String> {
+    Ok(LAST_REPORT.lock().unwrap().clone())
+}
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, ffi::c_void};
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::DeviceAndDriverInstallation::{
+            SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW,
+            SetupDiGetDeviceInterfaceDetailW, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT, SP_DEVICE_INTERFACE_DATA,
+            SP_DEVICE_INTERFACE_DETAIL_DATA_W, SP_DEVINFO_DATA,
+        },
+        Foundation::{CloseHandle, HANDLE, HWND},
+        Storage::FileSystem::{
+            CreateFileW, GetLogicalDrives, GetVolumeInformationW, FILE_FLAGS_AND_ATTRIBUTES, FILE_SHARE_READ,
+            FILE_SHARE_WRITE, OPEN_EXISTING,
+        },
+        System::{
+            Ioctl::{
+                DISK_PERFORMANCE, GUID_DEVINTERFACE_DISK, IOCTL_DISK_PERFORMANCE, IOCTL_STORAGE_GET_DEVICE_NUMBER,
+                STORAGE_DEVICE_NUMBER,
+            },
+            IO::DeviceIoControl,
+        },
+    },
+};
+
+use super::correlation::{self, from_wide, wide};
+
+/// A mounted volume on a USB-attached disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Volume {
+    /// USB devnode the disk belongs to
+    #[serde(default)]
+    pub device_instance_id: String,
+    /// `"E:"`
+    pub mount_point: String,
+    /// Filesystem serial as `dir` prints it, e.g. `"1A2B-3C4D"`. Changes on reformat.
+    pub volume_serial: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub file_system: Option<String>,
+}
+
+/// Cumulative I/O on a volume since it was mounted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IoCounters {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Every drive letter backed by a USB disk. Drives that are not on USB
+/// (internal disks, network shares, optical drives) are left out.
+pub fn enumerate_volumes() -> Result<Vec<Volume>, String> {
+    let usb_disks = usb_disks()?;
+    let mask = unsafe { GetLogicalDrives() };
+    let mut volumes = Vec::new();
+
+    for index in 0..26u8 {
+        if mask & (1 << index) == 0 {
+            continue;
+        }
+        let letter = (b'A' + index) as char;
+        let device_instance_id = match device_number(&format!("\\\\.\\{}:", letter)).and_then(|n| usb_disks.get(&n)) {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+
+        let root = wide(&format!("{}:\\", letter));
+        let mut label = [0u16; 261];
+        let mut file_system = [0u16; 261];
+        let mut serial = 0u32;
+        let ok = unsafe {
+            GetVolumeInformationW(
+                PCWSTR(root.as_ptr()),
+                Some(&mut label),
+                Some(&mut serial),
+                None,
+                None,
+                Some(&mut file_system),
+            )
+        };
+        // Unformatted or still mounting
+        if !ok.as_bool() {
+            continue;
+        }
+
+        volumes.push(Volume {
+            device_instance_id,
+            mount_point: format!("{}:", letter),
+            volume_serial: format_volume_serial(serial),
+            label: Some(from_wide(&label)).filter(|s| !s.is_empty()),
+            file_system: Some(from_wide(&file_system)).filter(|s| !s.is_empty()),
+        });
+    }
+
+    Ok(volumes)
+}
+
+pub fn format_volume_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+/// Disk device number -> instance ID of the USB device the disk sits on.
+fn usb_disks() -> Result<HashMap<u32, String>, String> {
+    let mut disks = HashMap::new();
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            Some(&GUID_DEVINTERFACE_DISK),
+            None,
+            HWND(0),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )
+        .map_err(|e| format!("Failed to enumerate disks: {}", e))?;
+
+        let mut interface = SP_DEVICE_INTERFACE_DATA {
+            cbSize: std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32,
+            ..Default::default()
+        };
+        for index in 0.. {
+            if !SetupDiEnumDeviceInterfaces(device_info_set, None, &GUID_DEVINTERFACE_DISK, index, &mut interface)
+                .as_bool()
+            {
+                break;
+            }
+
+            let mut required = 0u32;
+            SetupDiGetDeviceInterfaceDetailW(device_info_set, &interface, None, 0, Some(&mut required), None);
+            if required == 0 {
+                continue;
+            }
+            // u32 elements keep the detail struct aligned
+            let mut buffer = vec![0u32; (required as usize).div_ceil(4)];
+            let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+            (*detail).cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+            let mut device_info_data = SP_DEVINFO_DATA {
+                cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+                ..Default::default()
+            };
+            if !SetupDiGetDeviceInterfaceDetailW(
+                device_info_set,
+                &interface,
+                Some(detail),
+                required,
+                None,
+                Some(&mut device_info_data),
+            )
+            .as_bool()
+            {
+                continue;
+            }
+
+            let path = PCWSTR((*detail).DevicePath.as_ptr()).to_string().unwrap_or_default();
+            let usb_device = match correlation::usb_device_ancestor(device_info_data.DevInst) {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(number) = device_number(&path) {
+                disks.insert(number, usb_device);
+            }
+        }
+
+        SetupDiDestroyDeviceInfoList(device_info_set);
+    }
+    Ok(disks)
+}
+
+/// Read the volume's cumulative I/O counters (IOCTL_DISK_PERFORMANCE).
+pub fn io_counters(mount_point: &str) -> Result<IoCounters, String> {
+    let path = wide(&format!("\\\\.\\{}", mount_point));
+    unsafe {
+        let handle: HANDLE = CreateFileW(
+            PCWSTR(path.as_ptr()),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            None,
+        )
+        .map_err(|e| format!("Failed to open {}: {}", mount_point, e))?;
+
+        let mut performance = DISK_PERFORMANCE::default();
+        let mut returned = 0u32;
+        let ok = DeviceIoControl(
+            handle,
+            IOCTL_DISK_PERFORMANCE,
+            None,
+            0,
+            Some(&mut performance as *mut _ as *mut c_void),
+            std::mem::size_of::<DISK_PERFORMANCE>() as u32,
+            Some(&mut returned),
+            None,
+        );
+        CloseHandle(handle);
+        if !ok.as_bool() {
+            return Err(format!("Failed to read I/O counters of {}", mount_point));
+        }
+        Ok(IoCounters {
+            bytes_read: performance.BytesRead.max(0) as u64,
+            bytes_written: performance.BytesWritten.max(0) as u64,
+            reads: performance.ReadCount as u64,
+            writes: performance.WriteCount as u64,
+        })
+    }
+}
+
+/// IOCTL_STORAGE_GET_DEVICE_NUMBER on a disk or volume path. Opening with
+/// no access rights is enough for the query and needs no elevation.
+fn device_number(path: &str) -> Option<u32> {
+    let path = wide(path);
+    unsafe {
+        let handle: HANDLE = CreateFileW(
+            PCWSTR(path.as_ptr()),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            None,
+        )
+        .ok()?;
+
+        let mut number = STORAGE_DEVICE_NUMBER::default();
+        let mut returned = 0u32;
+        let ok = DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_GET_DEVICE_NUMBER,
+            None,
+            0,
+            Some(&mut number as *mut _ as *mut c_void),
+            std::mem::size_of::<STORAGE_DEVICE_NUMBER>() as u32,
+            Some(&mut returned),
+            None,
+        );
+        CloseHandle(handle);
+        if ok.as_bool() {
+            Some(number.DeviceNumber)
+        } else {
+            None
+        }
+    }
+}
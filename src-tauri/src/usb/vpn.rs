@@ -0,0 +1,158 @@
+use std::{collections::HashSet, sync::Mutex, thread, time::Duration};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::category::DeviceCategory;
+use super::commands::{block_class_for, enable_devices, DeviceIdentity};
+use super::events;
+use super::network::{self, Adapter};
+use super::usb_control::BlockReason;
+
+pub const EVENT_VPN_CHANGED: &str = "network://vpn-changed";
+const POLL_INTERVAL: Duration = Duration::from_secs(20);
+
+// IANA ifType values used by VPN clients
+const IF_TYPE_PPP: u32 = 23;
+const IF_TYPE_PROP_VIRTUAL: u32 = 53;
+const IF_TYPE_TUNNEL: u32 = 131;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VpnStorageRule {
+    /// Permit storage devices only while the VPN is connected
+    pub enabled: bool,
+    /// Substrings of the adapter name/description identifying the corporate
+    /// VPN (e.g. "AnyConnect", "WireGuard"). Empty: any PPP/tunnel adapter.
+    pub adapter_patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VpnStatus {
+    pub connected: bool,
+    pub adapter: Option<String>,
+    pub storage_allowed: bool,
+}
+
+lazy_static! {
+    static ref RULE: Mutex<VpnStorageRule> = Mutex::new(VpnStorageRule {
+        enabled: false,
+        adapter_patterns: Vec::new(),
+    });
+    static ref CONNECTED: Mutex<Option<bool>> = Mutex::new(None);
+    // Storage devnodes this rule disabled, re-enabled when the VPN returns
+    static ref BLOCKED_BY_RULE: Mutex<HashSet<(u16, u16, String)>> = Mutex::new(HashSet::new());
+}
+
+pub fn start() {
+    thread::spawn(|| loop {
+        evaluate();
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+/// Whether storage may be enabled right now under the VPN rule.
+pub fn storage_allowed() -> bool {
+    !RULE.lock().unwrap().enabled || CONNECTED.lock().unwrap().unwrap_or(false)
+}
+
+fn vpn_adapter(rule: &VpnStorageRule, adapters: &[Adapter]) -> Option<String> {
+    adapters
+        .iter()
+        .find(|adapter| {
+            if rule.adapter_patterns.is_empty() {
+                matches!(adapter.if_type, IF_TYPE_PPP | IF_TYPE_PROP_VIRTUAL | IF_TYPE_TUNNEL)
+                    && !adapter.ipv4.is_empty()
+            } else {
+                rule.adapter_patterns.iter().any(|pattern| {
+                    let pattern = pattern.to_lowercase();
+                    adapter.name.to_lowercase().contains(&pattern)
+                        || adapter.description.to_lowercase().contains(&pattern)
+                })
+            }
+        })
+        .map(|adapter| adapter.name.clone())
+}
+
+fn evaluate() {
+    let rule = RULE.lock().unwrap().clone();
+    let connected = vpn_adapter(&rule, &network::up_adapters()).is_some();
+
+    let previous = CONNECTED.lock().unwrap().replace(connected);
+    if previous == Some(connected) {
+        return;
+    }
+
+    events::emit(EVENT_VPN_CHANGED, status_for(&rule, connected));
+    if rule.enabled {
+        enforce(connected);
+    }
+}
+
+fn enforce(connected: bool) {
+    if connected {
+        let identities: Vec<DeviceIdentity> = BLOCKED_BY_RULE
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(vendor_id, product_id, instance_id)| DeviceIdentity {
+                vendor_id,
+                product_id,
+                serial: None,
+                instance_id: Some(instance_id),
+            })
+            .collect();
+        if !identities.is_empty() {
+            let _ = enable_devices(identities);
+        }
+        audit::record("vpn_storage_allowed", json!({}));
+    } else {
+        match block_class_for(DeviceCategory::Storage, BlockReason::VpnPolicy) {
+            Ok(results) => {
+                let mut blocked = BLOCKED_BY_RULE.lock().unwrap();
+                for item in results {
+                    for change in item.changes {
+                        blocked.insert((item.identity.vendor_id, item.identity.product_id, change.instance_id));
+                    }
+                }
+            }
+            Err(e) => log::error!("Failed to block storage after VPN disconnect: {}", e),
+        }
+        audit::record("vpn_storage_blocked", json!({}));
+    }
+}
+
+fn status_for(rule: &VpnStorageRule, connected: bool) -> VpnStatus {
+    VpnStatus {
+        connected,
+        adapter: vpn_adapter(rule, &network::up_adapters()),
+        storage_allowed: !rule.enabled || connected,
+    }
+}
+
+#[command]
+pub fn get_vpn_status() -> Result<VpnStatus, String> {
+    let rule = RULE.lock().unwrap().clone();
+    let connected = vpn_adapter(&rule, &network::up_adapters()).is_some();
+    Ok(status_for(&rule, connected))
+}
+
+#[command]
+pub fn get_vpn_storage_rule() -> Result<VpnStorageRule, String> {
+    Ok(RULE.lock().unwrap().clone())
+}
+
+#[command]
+pub fn set_vpn_storage_rule(rule: VpnStorageRule) -> Result<(), String> {
+    let enabled = rule.enabled;
+    audit::record("vpn_storage_rule_changed", json!(rule));
+    *RULE.lock().unwrap() = rule;
+    // Force the next evaluation to (re)apply the rule for the current state
+    *CONNECTED.lock().unwrap() = None;
+    if !enabled {
+        enforce(true);
+    }
+    thread::spawn(evaluate);
+    Ok(())
+}
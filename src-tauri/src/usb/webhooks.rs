@@ -0,0 +1,334 @@
+use std::{
+    fs,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use chrono::Utc;
+use lazy_static::lazy_static;
+use native_tls::TlsConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::command;
+
+use super::audit;
+use super::dpapi;
+use super::hello;
+use super::operations;
+use super::signing;
+use super::usb_config;
+
+const WEBHOOKS_FILE: &str = "webhooks.json";
+pub const SIGNATURE_HEADER: &str = "X-USB-Shield-Signature";
+const EVENT_HEADER: &str = "X-USB-Shield-Event";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Security events posted to webhooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    DeviceBlocked,
+    DeviceQuarantined,
+    TamperDetected,
+    /// Any other notification routed to the Webhook channel
+    Notification,
+    /// From `send_test_webhook`
+    Test,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::DeviceBlocked => "device_blocked",
+            WebhookEvent::DeviceQuarantined => "device_quarantined",
+            WebhookEvent::TamperDetected => "tamper_detected",
+            WebhookEvent::Notification => "notification",
+            WebhookEvent::Test => "test",
+        }
+    }
+}
+
+/// One receiver. The payload is signed with its secret, so the receiver can
+/// tell it came from this machine. The secret is accepted but never
+/// returned; it is kept encrypted with DPAPI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// `None` keeps the saved secret for this URL
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub has_secret: bool,
+}
+
+// A receiver with its secret in the clear, which stays in memory only
+#[derive(Clone)]
+struct Target {
+    url: String,
+    secret: String,
+}
+
+impl Target {
+    fn redacted(&self) -> Webhook {
+        Webhook {
+            url: self.url.clone(),
+            secret: None,
+            has_secret: true,
+        }
+    }
+}
+
+// What is written to disk. Files from before secrets were encrypted carry
+// `secret` in the clear; it is encrypted on the next save.
+#[derive(Serialize, Deserialize)]
+struct StoredWebhook {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    protected_secret: Option<String>,
+    #[serde(default, skip_serializing)]
+    secret: Option<String>,
+}
+
+/// Outcome of posting one payload to one webhook, retries included.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub url: String,
+    pub ok: bool,
+    /// HTTP status of the last attempt, if the receiver answered
+    pub status: Option<u16>,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+// The webhooks to post to, the event and its payload
+type Job = (Vec<Target>, WebhookEvent, String);
+
+lazy_static! {
+    static ref WEBHOOKS: Mutex<Vec<Target>> = Mutex::new(Vec::new());
+    static ref QUEUE: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+}
+
+fn payload(event: WebhookEvent, message: &str, details: &Value) -> String {
+    json!({
+        "event": event.name(),
+        "timestamp": Utc::now(),
+        "host": std::env::var("COMPUTERNAME").ok(),
+        "message": message,
+        "details": details,
+    })
+    .to_string()
+}
+
+/// Post `payload` to `webhook`, retrying network errors, 429 and 5xx with
+/// exponential backoff (1, 2, 4 s). Other answers are final.
+fn deliver(webhook: &Target, event: &str, payload: &str) -> WebhookDelivery {
+    let mut delivery = WebhookDelivery {
+        url: webhook.url.clone(),
+        ok: false,
+        status: None,
+        attempts: 0,
+        error: None,
+    };
+    let signature = match signing::sign_with(webhook.secret.as_bytes(), payload.as_bytes()) {
+        Ok(signature) => format!("sha256={}", signature),
+        Err(e) => {
+            delivery.error = Some(e);
+            return delivery;
+        }
+    };
+    let agent = match TlsConnector::new() {
+        Ok(connector) => ureq::AgentBuilder::new()
+            .tls_connector(Arc::new(connector))
+            .timeout(TIMEOUT)
+            .build(),
+        Err(e) => {
+            delivery.error = Some(e.to_string());
+            return delivery;
+        }
+    };
+
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        delivery.attempts += 1;
+        let retry = match agent
+            .post(&webhook.url)
+            .set("Content-Type", "application/json")
+            .set(EVENT_HEADER, event)
+            .set(SIGNATURE_HEADER, &signature)
+            .send_string(payload)
+        {
+            Ok(response) => {
+                delivery.ok = true;
+                delivery.status = Some(response.status());
+                delivery.error = None;
+                return delivery;
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                delivery.status = Some(status);
+                delivery.error = Some(format!("{} answered HTTP {}", webhook.url, status));
+                status == 429 || status >= 500
+            }
+            Err(e) => {
+                delivery.status = None;
+                delivery.error = Some(format!("POST to {} failed: {}", webhook.url, e));
+                true
+            }
+        };
+        if !retry || delivery.attempts >= MAX_ATTEMPTS {
+            return delivery;
+        }
+        thread::sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+fn deliver_all(webhooks: &[Target], event: WebhookEvent, payload: &str) -> Vec<WebhookDelivery> {
+    webhooks.iter().map(|webhook| deliver(webhook, event.name(), payload)).collect()
+}
+
+fn run(queue: Receiver<Job>) {
+    for (webhooks, event, payload) in queue {
+        for delivery in deliver_all(&webhooks, event, &payload) {
+            if !delivery.ok {
+                log::warn!("Webhook: {}", delivery.error.unwrap_or_default());
+            }
+        }
+    }
+}
+
+/// Post a security event to every webhook, in the background: delivery
+/// and its retries never hold up enforcement.
+pub fn dispatch(event: WebhookEvent, message: &str, details: &Value) {
+    let webhooks = WEBHOOKS.lock().unwrap().clone();
+    if webhooks.is_empty() {
+        return;
+    }
+    let mut queue = QUEUE.lock().unwrap();
+    let sender = queue.get_or_insert_with(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run(receiver));
+        sender
+    });
+    let _ = sender.send((webhooks, event, payload(event, message, details)));
+}
+
+/// `dispatch` for a notification routed to the Webhook channel, which
+/// fails when there is no webhook to route it to.
+pub fn send_notification(message: &str, details: &Value) -> Result<(), String> {
+    if WEBHOOKS.lock().unwrap().is_empty() {
+        return Err("No webhooks are configured".to_string());
+    }
+    dispatch(WebhookEvent::Notification, message, details);
+    Ok(())
+}
+
+fn save(webhooks: &[Target]) -> Result<(), String> {
+    let stored = webhooks
+        .iter()
+        .map(|webhook| {
+            Ok(StoredWebhook {
+                url: webhook.url.clone(),
+                protected_secret: Some(
+                    dpapi::protect(&webhook.secret)
+                        .map_err(|e| format!("Failed to encrypt the secret for {}: {}", webhook.url, e))?,
+                ),
+                secret: None,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let path = usb_config::data_file(WEBHOOKS_FILE)?;
+    let data = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Load the saved webhooks and decrypt their secrets. Called from setup.
+pub fn load() -> Result<(), String> {
+    let path = usb_config::data_file(WEBHOOKS_FILE)?;
+    let stored: Vec<StoredWebhook> = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let plaintext = stored.iter().any(|webhook| webhook.secret.is_some());
+    let webhooks = stored
+        .into_iter()
+        .map(|webhook| {
+            let secret = match (webhook.protected_secret, webhook.secret) {
+                (Some(protected), _) => dpapi::unprotect(&protected)
+                    .map_err(|e| format!("Failed to decrypt the secret for {}: {}", webhook.url, e))?,
+                (None, Some(secret)) => secret,
+                (None, None) => return Err(format!("The webhook for {} has no secret", webhook.url)),
+            };
+            Ok(Target { url: webhook.url, secret })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if plaintext {
+        save(&webhooks)?;
+    }
+    *WEBHOOKS.lock().unwrap() = webhooks;
+    Ok(())
+}
+
+#[command]
+pub fn get_webhooks() -> Result<Vec<Webhook>, String> {
+    Ok(WEBHOOKS.lock().unwrap().iter().map(Target::redacted).collect())
+}
+
+/// Replace the configured webhooks. A webhook sent without a secret keeps
+/// the one saved for its URL. Removing one needs consent: whoever receives
+/// it would stop hearing about blocks and tampering.
+#[command]
+pub fn set_webhooks(webhooks: Vec<Webhook>) -> Result<Vec<Webhook>, String> {
+    let previous = WEBHOOKS.lock().unwrap().clone();
+    let mut targets = Vec::new();
+    for webhook in webhooks {
+        if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+            return Err(format!("'{}' is not an http:// or https:// URL", webhook.url));
+        }
+        let secret = match webhook.secret {
+            Some(secret) => secret,
+            None => previous
+                .iter()
+                .find(|existing| existing.url == webhook.url)
+                .map(|existing| existing.secret.clone())
+                .unwrap_or_default(),
+        };
+        if secret.is_empty() {
+            return Err(format!("The webhook for {} needs a secret to sign with", webhook.url));
+        }
+        targets.push(Target { url: webhook.url, secret });
+    }
+    let removed = previous
+        .iter()
+        .any(|existing| !targets.iter().any(|target| target.url == existing.url));
+    if removed {
+        hello::require_consent("remove a webhook")?;
+    }
+    save(&targets)?;
+    audit::record(
+        "webhooks_changed",
+        json!({ "urls": targets.iter().map(|target| target.url.as_str()).collect::<Vec<_>>() }),
+    );
+    let redacted = targets.iter().map(Target::redacted).collect();
+    *WEBHOOKS.lock().unwrap() = targets;
+    Ok(redacted)
+}
+
+/// Post a test event to every webhook now, with retries, and report how
+/// each delivery went.
+#[command]
+pub async fn send_test_webhook() -> Result<Vec<WebhookDelivery>, String> {
+    operations::run("send_test_webhook", || {
+        let webhooks = WEBHOOKS.lock().unwrap().clone();
+        if webhooks.is_empty() {
+            return Err("No webhooks are configured".to_string());
+        }
+        let payload = payload(WebhookEvent::Test, "Test event from USB-Shield", &Value::Null);
+        Ok(deliver_all(&webhooks, WebhookEvent::Test, &payload))
+    })
+    .await
+}
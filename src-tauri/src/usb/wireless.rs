@@ -0,0 +1,148 @@
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use super::audit;
+use super::category::DeviceCategory;
+use super::commands::{self, DeviceState, UsbDeviceInfo};
+use super::events;
+use super::hello;
+use super::notifications::{self, Severity};
+use super::usb_control::BlockReason;
+
+pub const EVENT_WIRELESS_DEVICE: &str = "usb://wireless-device";
+
+/// What happens when an untrusted modem or Wi-Fi adapter is attached.
+/// Ordered from least to most protective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WirelessAction {
+    Allow,
+    /// Leave it working but raise an event and audit it
+    Alert,
+    Block,
+}
+
+/// Rules for devices that open a network path of their own, kept apart from
+/// wired USB NICs which usually join the managed LAN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WirelessPolicy {
+    pub cellular_modem: WirelessAction,
+    pub wifi_adapter: WirelessAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WirelessDeviceEvent {
+    pub device: UsbDeviceInfo,
+    pub action: WirelessAction,
+    /// Set when `action` is Block and blocking failed
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref POLICY: Mutex<WirelessPolicy> = Mutex::new(WirelessPolicy {
+        cellular_modem: WirelessAction::Alert,
+        wifi_adapter: WirelessAction::Alert,
+    });
+}
+
+fn action_for(policy: &WirelessPolicy, category: DeviceCategory) -> Option<WirelessAction> {
+    match category {
+        DeviceCategory::CellularModem => Some(policy.cellular_modem),
+        DeviceCategory::WifiAdapter => Some(policy.wifi_adapter),
+        _ => None,
+    }
+}
+
+/// Apply the policy to a device that was just attached. Trusted devices and
+/// other categories are left alone.
+pub fn on_connected(device: &UsbDeviceInfo) {
+    if device.trusted() || device.state() != DeviceState::Connected {
+        return;
+    }
+    let action = match action_for(&POLICY.lock().unwrap(), device.category()) {
+        Some(WirelessAction::Allow) | None => return,
+        Some(action) => action,
+    };
+    enforce(device, action);
+}
+
+fn enforce(device: &UsbDeviceInfo, action: WirelessAction) {
+    let error = if action == WirelessAction::Block {
+        commands::block_device_for(
+            device.vendor_id(),
+            device.product_id(),
+            None,
+            device.instance_id().map(str::to_string),
+            BlockReason::WirelessPolicy,
+        )
+        .err()
+        .map(|e| e.to_string())
+    } else {
+        None
+    };
+    if let Some(e) = &error {
+        log::error!("Failed to block wireless device: {}", e);
+    }
+
+    let event = if action == WirelessAction::Block { "wireless_device_blocked" } else { "wireless_device_connected" };
+    audit::record(
+        event,
+        json!({
+            "instance_id": device.instance_id(),
+            "vendor_id": device.vendor_id(),
+            "product_id": device.product_id(),
+            "category": device.category(),
+            "error": error,
+        }),
+    );
+    let kind = match device.category() {
+        DeviceCategory::CellularModem => "Cellular modem",
+        _ => "Wi-Fi adapter",
+    };
+    notifications::notify(
+        Severity::Warning,
+        event,
+        &format!("{} {}", kind, if action == WirelessAction::Block { "blocked" } else { "connected" }),
+        "It can reach networks outside the corporate proxy.",
+    );
+    events::emit(
+        EVENT_WIRELESS_DEVICE,
+        WirelessDeviceEvent {
+            device: device.clone(),
+            action,
+            error,
+        },
+    );
+}
+
+#[command]
+pub fn get_wireless_policy() -> Result<WirelessPolicy, String> {
+    Ok(POLICY.lock().unwrap().clone())
+}
+
+/// Replace the policy. Tightening a category to Block also blocks the
+/// untrusted devices of it that are attached now.
+#[command]
+pub fn set_wireless_policy(policy: WirelessPolicy) -> Result<(), String> {
+    let previous = POLICY.lock().unwrap().clone();
+    if policy.cellular_modem < previous.cellular_modem || policy.wifi_adapter < previous.wifi_adapter {
+        hello::require_consent("relax the modem and Wi-Fi adapter policy")?;
+    }
+    *POLICY.lock().unwrap() = policy.clone();
+    audit::record("wireless_policy_changed", json!(policy));
+
+    let newly_blocked = |category: DeviceCategory| {
+        action_for(&policy, category) == Some(WirelessAction::Block)
+            && action_for(&previous, category) != Some(WirelessAction::Block)
+    };
+    if newly_blocked(DeviceCategory::CellularModem) || newly_blocked(DeviceCategory::WifiAdapter) {
+        for device in commands::get_usb_devices()? {
+            if newly_blocked(device.category()) && !device.trusted() && device.state() == DeviceState::Connected {
+                enforce(&device, WirelessAction::Block);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use common::{device, devices, enabled, machine, reload, DESK, FLASH_DRIVE, KEYBOARD};
+use std::{thread, time::Duration};
+
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use tauri::async_runtime::block_on;
+use uport_shield_lib::usb::{
+    category::DeviceCategory,
+    commands, guest, hotplug, pause,
+    quarantine::{self, QuarantineDecision},
+    reblock::{self, ReblockTarget},
+    scheduler::{self, AutoblockSensitivity, SensitivityBand},
+    simulation, status,
+};
+
+const BASH_BUNNY: &str = "USB\\VID_F000&PID_FFF0\\CH000001";
+
+#[test]
+fn autoblock_mode_round_trips() {
+    let _machine = machine(DESK);
+
+    assert!(commands::app_state().autoblock_enabled());
+    commands::app_state().set_autoblock_mode(false, None).unwrap();
+    assert!(!commands::app_state().autoblock_enabled());
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+    assert!(commands::app_state().autoblock_enabled());
+}
+
+#[test]
+fn block_all_untrusted_spares_trusted_devices() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x046D, 0xC31C).unwrap();
+    commands::app_state().add_trusted_device(0x05E3, 0x0610).unwrap();
+
+    block_on(commands::block_all_untrusted()).unwrap();
+    assert!(enabled(KEYBOARD));
+    assert!(!enabled(FLASH_DRIVE));
+
+    let trusted: Vec<_> = devices().into_iter().filter(|d| d["trusted"] == true).collect();
+    assert_eq!(trusted.len(), 2);
+
+    block_on(commands::unblock_all_trusted(None)).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+    commands::app_state().remove_trusted_device(0x046D, 0xC31C, None).unwrap();
+    commands::app_state().remove_trusted_device(0x05E3, 0x0610, None).unwrap();
+}
+
+#[test]
+fn trusted_devices_round_trip() {
+    let _machine = machine(DESK);
+
+    commands::app_state().add_trusted_device(0x0781, 0x5581).unwrap();
+    assert!(commands::app_state().trusted_devices().contains(&(0x0781, 0x5581)));
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+    assert!(!commands::app_state().trusted_devices().contains(&(0x0781, 0x5581)));
+}
+
+#[test]
+fn autoblock_disables_untrusted_arrivals() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(!enabled(KEYBOARD));
+    // Only arrivals are acted on
+    assert!(enabled(FLASH_DRIVE));
+
+    commands::app_state().add_trusted_device(0x046D, 0xC31C).unwrap();
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(enabled(KEYBOARD));
+
+    commands::app_state().remove_trusted_device(0x046D, 0xC31C, None).unwrap();
+    commands::enable_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()), None).unwrap();
+}
+
+fn band(name: &str, days: Vec<Weekday>, start: u32, end: u32, sensitivity: AutoblockSensitivity) -> SensitivityBand {
+    SensitivityBand {
+        name: name.to_string(),
+        days,
+        start: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+        end: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+        sensitivity,
+    }
+}
+
+#[test]
+fn sensitivity_bands_prompt_by_day_and_block_at_night() {
+    let workdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+    let bands = vec![
+        band("work hours", workdays, 9, 18, AutoblockSensitivity::Prompt),
+        band("night", Vec::new(), 22, 6, AutoblockSensitivity::Block),
+    ];
+    // 2026-10-14 is a Wednesday
+    let at = |hour: u32| NaiveDate::from_ymd_opt(2026, 10, 14).unwrap().and_hms_opt(hour, 30, 0).unwrap();
+    assert_eq!(scheduler::band_at(&bands, at(10)).unwrap().name, "work hours");
+    assert_eq!(scheduler::band_at(&bands, at(23)).unwrap().name, "night");
+    assert_eq!(scheduler::band_at(&bands, at(2)).unwrap().name, "night");
+    assert!(scheduler::band_at(&bands, at(20)).is_none());
+
+    // An all-day Prompt band holds the arrival and waits for an answer
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    scheduler::set_autoblock_schedule(vec![band("always ask", Vec::new(), 0, 0, AutoblockSensitivity::Prompt)]).unwrap();
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(!enabled(KEYBOARD));
+    assert_eq!(scheduler::get_autoblock_prompts().unwrap().len(), 1);
+
+    scheduler::answer_autoblock_prompt(KEYBOARD.to_string(), true, None).unwrap();
+    assert!(enabled(KEYBOARD));
+    assert!(scheduler::get_autoblock_prompts().unwrap().is_empty());
+    scheduler::set_autoblock_schedule(Vec::new()).unwrap();
+}
+
+#[test]
+fn blocked_devices_say_why() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+
+    assert_eq!(device(KEYBOARD)["block_reason"], "Autoblock");
+    assert_eq!(device(FLASH_DRIVE)["block_reason"], "Manual");
+    let summary = status::get_status_summary().unwrap();
+    assert_eq!(summary.blocked_by_reason.values().sum::<usize>(), 2);
+
+    commands::enable_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()), None).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+    assert!(device(FLASH_DRIVE)["block_reason"].is_null());
+}
+
+#[test]
+fn temporary_trust_lapses_and_reblocks() {
+    let _machine = machine(DESK);
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+
+    let grant = commands::add_trusted_device_temporary(0x0781, 0x5581, 1, None).unwrap();
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["trusted"], true);
+    // Never persisted with the permanent whitelist
+    assert!(!commands::app_state().trusted_devices().contains(&(0x0781, 0x5581)));
+    assert!(grant.remaining_secs <= 1);
+    // Cancelling would keep the trust for good
+    assert!(reblock::cancel_reblock(grant.id, None).is_err());
+    assert!(reblock::revoke_timed_unblock(grant.id).is_err());
+
+    thread::sleep(Duration::from_millis(2_500));
+    assert!(!enabled(FLASH_DRIVE));
+    let stick = device(FLASH_DRIVE);
+    assert_eq!(stick["trusted"], false);
+    assert_eq!(stick["block_reason"], "TimedReblock");
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn guest_mode_lets_one_stick_in_and_reverts() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    guest::start_guest_mode(vec![DeviceCategory::Storage], 1, 1, None).unwrap();
+    simulation::simulate_detach(FLASH_DRIVE.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["trusted"], true);
+    let session = guest::get_guest_mode().unwrap().unwrap();
+    assert_eq!(session.devices.len(), 1);
+    // The session cannot be made endless by dropping its timer
+    let timer = reblock::pending().into_iter().find(|p| p.target == ReblockTarget::Guest).unwrap();
+    assert!(reblock::cancel_reblock(timer.id, None).is_err());
+
+    thread::sleep(Duration::from_millis(2_500));
+    assert!(guest::get_guest_mode().unwrap().is_none());
+    assert!(!enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["trusted"], false);
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn paused_protection_blocks_arrivals_again_on_resume() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    pause::pause_protection(600, None).unwrap();
+    simulation::simulate_detach(FLASH_DRIVE.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(pause::get_protection_pause().unwrap().unwrap().admitted, vec![FLASH_DRIVE.to_string()]);
+
+    assert!(pause::resume_protection().unwrap().is_empty());
+    assert!(pause::get_protection_pause().unwrap().is_none());
+    assert!(!enabled(FLASH_DRIVE));
+    assert!(enabled(KEYBOARD));
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn known_attack_devices_are_blocked_even_with_autoblock_off() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    commands::app_state().set_autoblock_mode(false, None).unwrap();
+
+    let with_bunny = DESK.replacen(
+        "\"devices\": [",
+        r#""devices": [
+        {
+            "instance_id": "USB\\VID_F000&PID_FFF0\\CH000001",
+            "product": "Keyboard",
+            "ports": [2],
+            "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }]
+        },"#,
+        1,
+    );
+    reload(&with_bunny);
+    hotplug::rescan();
+    assert!(!enabled(BASH_BUNNY));
+    let bunny = device(BASH_BUNNY);
+    assert_eq!(bunny["threat_match"], "Bash Bunny");
+    assert_eq!(bunny["block_reason"], "KnownThreat");
+    assert!(enabled(FLASH_DRIVE));
+
+    commands::enable_device(0xF000, 0xFFF0, None, Some(BASH_BUNNY.to_string()), None).unwrap();
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+}
+
+fn replug_flash_drive() {
+    simulation::simulate_detach(FLASH_DRIVE.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+}
+
+#[test]
+fn quarantined_arrivals_wait_for_a_decision() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    quarantine::set_quarantine_mode(true).unwrap();
+
+    replug_flash_drive();
+    assert!(!enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["state"], "Quarantined");
+    let held = quarantine::get_quarantined_devices().unwrap();
+    assert_eq!(held.len(), 1);
+    assert_eq!(held[0].device.instance_id(), Some(FLASH_DRIVE));
+
+    // Still waiting after a restart
+    quarantine::load().unwrap();
+    assert!(quarantine::get_quarantine_mode().unwrap());
+    let restored = quarantine::get_quarantined_devices().unwrap();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].quarantined_at, held[0].quarantined_at);
+    assert_eq!(device(FLASH_DRIVE)["block_reason"], "Quarantine");
+
+    // Allowed once: in now, asked about again next time
+    quarantine::resolve_quarantine(FLASH_DRIVE.to_string(), QuarantineDecision::AllowOnce, None).unwrap();
+    assert!(enabled(FLASH_DRIVE));
+    assert!(quarantine::get_quarantined_devices().unwrap().is_empty());
+    replug_flash_drive();
+    assert_eq!(device(FLASH_DRIVE)["state"], "Quarantined");
+
+    // Blocked for good: never asked about again
+    quarantine::resolve_quarantine(FLASH_DRIVE.to_string(), QuarantineDecision::Block, None).unwrap();
+    replug_flash_drive();
+    assert!(!enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["block_reason"], "Manual");
+    assert!(quarantine::get_quarantined_devices().unwrap().is_empty());
+
+    quarantine::set_quarantine_mode(false).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
@@ -0,0 +1,88 @@
+//! Shared setup for the integration tests. Every test binary runs against the
+//! simulated backend, so the suite needs neither hardware nor admin rights.
+
+#![allow(dead_code)]
+
+use std::sync::{Mutex, MutexGuard, Once};
+
+use serde_json::Value;
+use uport_shield_lib::usb::{backend, commands, simulation, usb_config};
+
+static INIT: Once = Once::new();
+// Enforcement state is process-global; tests in one binary take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Hub, keyboard and flash drive; the keyboard and drive sit behind the hub.
+pub const DESK: &str = r#"{
+    "devices": [
+        {
+            "instance_id": "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1",
+            "device_class": 9,
+            "product": "USB2.0 Hub",
+            "ports": [1],
+            "interfaces": [{ "class_code": 9, "sub_class_code": 0, "protocol_code": 0 }]
+        },
+        {
+            "instance_id": "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&1",
+            "product": "USB Keyboard",
+            "ports": [1, 1],
+            "parent_instance_id": "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1",
+            "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }]
+        },
+        {
+            "instance_id": "USB\\VID_0781&PID_5581\\4C530001230918115462",
+            "product": "Ultra",
+            "ports": [1, 2],
+            "parent_instance_id": "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1",
+            "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }]
+        }
+    ]
+}"#;
+
+pub const KEYBOARD: &str = "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&1";
+pub const FLASH_DRIVE: &str = "USB\\VID_0781&PID_5581\\4C530001230918115462";
+
+/// Install the simulated backend (once per binary), load `script` and hold
+/// the serial lock for the duration of the test.
+pub fn machine(script: &str) -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    INIT.call_once(|| {
+        let data_dir = std::env::temp_dir().join(format!("usb-shield-tests-{}", std::process::id()));
+        usb_config::init(data_dir).expect("data dir");
+        backend::install(Box::new(simulation::SimulatedController)).expect("backend");
+    });
+    simulation::load_script(script).expect("script");
+    guard
+}
+
+/// `get_usb_devices` as JSON, which is what the frontend sees.
+pub fn devices() -> Vec<Value> {
+    let devices = commands::get_usb_devices().expect("enumeration");
+    serde_json::to_value(devices)
+        .expect("serialize")
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn device(instance_id: &str) -> Value {
+    devices()
+        .into_iter()
+        .find(|d| d["instance_id"].as_str().is_some_and(|id| id.eq_ignore_ascii_case(instance_id)))
+        .unwrap_or_else(|| panic!("{} not listed", instance_id))
+}
+
+/// Whether the simulated devnode is currently enabled.
+pub fn enabled(instance_id: &str) -> bool {
+    simulation::get_simulation_state()
+        .expect("state")
+        .devices
+        .iter()
+        .find(|d| d.instance_id.eq_ignore_ascii_case(instance_id))
+        .is_some_and(|d| d.enabled)
+}
+
+/// Swap the scenario mid-test, while already holding the lock from `machine`.
+pub fn reload(script: &str) {
+    simulation::load_script(script).expect("script");
+}
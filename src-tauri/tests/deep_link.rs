@@ -0,0 +1,26 @@
+#![cfg(feature = "test-harness")]
+
+use uport_shield_lib::usb::deep_link::{parse, DeepLinkAction};
+
+#[test]
+fn parses_supported_links() {
+    assert_eq!(parse("usb-shield://status").unwrap(), DeepLinkAction::Status);
+    assert_eq!(
+        parse("usb-shield://approve?serial=4C530001230918115462").unwrap(),
+        DeepLinkAction::Approve {
+            serial: "4C530001230918115462".to_string()
+        }
+    );
+    assert_eq!(
+        parse("USB-SHIELD://Block?serial=ABC").unwrap(),
+        DeepLinkAction::Block { serial: "ABC".to_string() }
+    );
+}
+
+#[test]
+fn rejects_malformed_links() {
+    assert!(parse("usb-shield://approve").is_err());
+    assert!(parse("usb-shield://approve?serial=").is_err());
+    assert!(parse("usb-shield://format?drive=C").is_err());
+    assert!(parse("https://approve?serial=ABC").is_err());
+}
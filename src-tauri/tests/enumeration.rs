@@ -0,0 +1,273 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use tauri::async_runtime::block_on;
+
+use common::{device, devices, machine, reload, DESK, FLASH_DRIVE, KEYBOARD};
+use uport_shield_lib::usb::{
+    backend,
+    category::InterfaceClass,
+    class_names::{self, Language},
+    class_policy::{self, ClassAction},
+    commands,
+    hotplug::{self, HotplugKind},
+    privilege, self_test, simulation,
+    status::{self, ProtectionLevel},
+    type_c::{self, PartnerKind},
+    usb_names,
+};
+
+#[test]
+fn lists_every_scripted_device_with_its_category() {
+    let _machine = machine(DESK);
+
+    let listed = devices();
+    assert_eq!(listed.len(), 3);
+    assert_eq!(device(KEYBOARD)["category"], "Keyboard");
+    assert_eq!(device(FLASH_DRIVE)["category"], "Storage");
+    assert_eq!(device("USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1")["category"], "Hub");
+}
+
+#[test]
+fn correlates_port_chain_serial_and_parent() {
+    let _machine = machine(DESK);
+
+    let drive = device(FLASH_DRIVE);
+    assert_eq!(drive["port_chain"], "1-1.2");
+    assert_eq!(drive["port_number"], 2);
+    assert_eq!(drive["serial_number"], "4C530001230918115462");
+    assert_eq!(drive["parent_instance_id"], "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1");
+    assert_eq!(drive["state"], "Connected");
+
+    // Generated location keys are not serials
+    assert!(device(KEYBOARD)["serial_number"].is_null());
+}
+
+#[test]
+fn blocked_device_stays_listed_after_it_drops_off_the_bus() {
+    let _machine = machine(DESK);
+
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["state"], "Blocked");
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["state"], "Connected");
+}
+
+#[test]
+fn hotplugged_devices_appear_and_disappear() {
+    let _machine = machine(DESK);
+
+    let stranger: simulation::SimDevice = serde_json::from_str(
+        r#"{
+            "instance_id": "USB\\VID_090C&PID_1000\\0376622070004893",
+            "ports": [2],
+            "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }]
+        }"#,
+    )
+    .unwrap();
+    simulation::simulate_attach(stranger).unwrap();
+    assert_eq!(devices().len(), 4);
+    assert_eq!(device("USB\\VID_090C&PID_1000\\0376622070004893")["port_chain"], "1-2");
+
+    simulation::simulate_detach("USB\\VID_090C&PID_1000\\0376622070004893".to_string()).unwrap();
+    assert_eq!(devices().len(), 3);
+}
+
+#[test]
+fn lists_the_driver_each_device_loads() {
+    let _machine = machine(
+        r#"{
+            "devices": [
+                {
+                    "instance_id": "USB\\VID_1A86&PID_7523\\5&3A1F0C2&0&3",
+                    "ports": [3],
+                    "interfaces": [{ "class_code": 255, "sub_class_code": 1, "protocol_code": 2 }],
+                    "driver": {
+                        "service": "CH341SER_A64",
+                        "provider": "wch.cn",
+                        "version": "3.5.2019.1",
+                        "date": "2019-01-30",
+                        "inf_name": "oem42.inf",
+                        "signature": "unsigned"
+                    }
+                },
+                {
+                    "instance_id": "USB\\VID_0BDA&PID_8153\\000001",
+                    "ports": [4],
+                    "interfaces": [{ "class_code": 255, "sub_class_code": 255, "protocol_code": 0 }],
+                    "driver": { "service": "usbccgp", "inf_name": "usb.inf", "signature": "signed" },
+                    "function_drivers": [
+                        {
+                            "instance_id": "USB\\VID_0BDA&PID_8153&MI_00\\6&1F2E3D&0&0000",
+                            "driver": { "service": "rtux64w10", "inf_name": "oem7.inf", "signature": "unsigned" }
+                        }
+                    ]
+                }
+            ]
+        }"#,
+    );
+
+    let driver = &device("USB\\VID_1A86&PID_7523\\5&3A1F0C2&0&3")["driver"];
+    assert_eq!(driver["service"], "CH341SER_A64");
+    assert_eq!(driver["date"], "2019-01-30");
+    assert_eq!(driver["signature"], "unsigned");
+    assert!(driver["signer"].is_null());
+
+    // A signed composite driver does not hide an unsigned function driver
+    let adapter = device("USB\\VID_0BDA&PID_8153\\000001");
+    assert_eq!(adapter["driver"]["signature"], "signed");
+    assert_eq!(adapter["function_drivers"][0]["driver"]["service"], "rtux64w10");
+    assert_eq!(adapter["driver_signature"], "unsigned");
+}
+
+#[test]
+fn reports_scripted_type_c_ports() {
+    let _machine = machine(
+        r#"{
+            "devices": [],
+            "type_c_ports": [
+                {
+                    "manager_instance_id": "ACPI\\USBC000\\0",
+                    "connector": 1,
+                    "partner": "PowerOnly",
+                    "charging": true,
+                    "contract": { "voltage_mv": 5000, "current_ma": 3000, "power_delivery": false }
+                }
+            ]
+        }"#,
+    );
+
+    let ports = type_c::get_type_c_ports().unwrap();
+    assert_eq!(ports.len(), 1);
+    assert_eq!(ports[0].partner, PartnerKind::PowerOnly);
+    assert_eq!(ports[0].contract.unwrap().current_ma, 3000);
+}
+
+#[test]
+fn status_summary_counts_devices() {
+    let _machine = machine(DESK);
+
+    let summary = status::get_status_summary().unwrap();
+    assert_eq!(summary.connected, 3);
+    assert_eq!(summary.blocked, 0);
+    assert_eq!(summary.untrusted, 3);
+}
+
+#[test]
+fn effective_protection_reads_back_every_mechanism() {
+    let _machine = machine(DESK);
+    let state = commands::app_state();
+    state.set_autoblock_mode(false, None).unwrap();
+
+    let status = block_on(status::get_effective_protection_status()).unwrap();
+    assert_eq!(status.level, ProtectionLevel::Unprotected);
+    assert!(status.reasons.iter().any(|r| r == "Autoblock is off"));
+
+    state.set_autoblock_mode(true, None).unwrap();
+    state.add_trusted_device(0x05E3, 0x0610).unwrap();
+    block_on(commands::block_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()))).unwrap();
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    assert_eq!(block_on(status::get_effective_protection_status()).unwrap().level, ProtectionLevel::FullyProtected);
+
+    // Re-enabled behind our back
+    backend::controller().set_device_state(KEYBOARD, true).unwrap();
+    let status = block_on(status::get_effective_protection_status()).unwrap();
+    assert_eq!(status.level, ProtectionLevel::PartiallyProtected);
+    assert_eq!(status.reasons, ["1 blocked device(s) are running again"]);
+
+    // A keyboard a class Allow policy lets in is not a gap
+    commands::enable_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()), None).unwrap();
+    class_policy::set_class_policy(0x03, Some(ClassAction::Allow), None).unwrap();
+    assert_eq!(block_on(status::get_effective_protection_status()).unwrap().level, ProtectionLevel::FullyProtected);
+    class_policy::set_class_policy(0x03, None, None).unwrap();
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+    state.remove_trusted_device(0x05E3, 0x0610, None).unwrap();
+}
+
+#[test]
+fn names_class_triples_in_the_display_language() {
+    let interface = |class_code, sub_class_code, protocol_code| InterfaceClass {
+        class_code,
+        sub_class_code,
+        protocol_code,
+    };
+    let composite = [interface(0x03, 0x01, 0x01), interface(0x08, 0x06, 0x50), interface(0x03, 0x01, 0x01)];
+
+    assert_eq!(
+        class_names::device_class_names(Language::English, 0x00, &composite),
+        ["HID – Keyboard", "Mass Storage – SCSI transparent"]
+    );
+    assert_eq!(
+        class_names::device_class_names(Language::German, 0x00, &composite),
+        ["HID – Tastatur", "Massenspeicher – SCSI transparent"]
+    );
+    assert_eq!(class_names::device_class_names(Language::English, 0x42, &[]), ["Class 0x42"]);
+}
+
+#[test]
+fn rescan_reports_arrivals_and_removals() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    let changes = hotplug::rescan();
+    assert_eq!(changes.len(), 1);
+    assert!(matches!(changes[0].kind, HotplugKind::Removed));
+    assert_eq!(changes[0].device.instance_id(), Some(KEYBOARD));
+
+    // Blocking is not a removal: the device stays listed as Blocked
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    assert!(hotplug::rescan().is_empty());
+
+    reload(DESK);
+    let changes = hotplug::rescan();
+    assert!(changes
+        .iter()
+        .any(|c| matches!(c.kind, HotplugKind::Connected) && c.device.instance_id() == Some(KEYBOARD)));
+}
+
+#[test]
+fn self_test_passes_on_the_simulated_backend() {
+    let _machine = machine(DESK);
+
+    let health = block_on(self_test::run_self_test()).unwrap();
+    assert!(health.can_enforce);
+    assert!(health.degraded.is_empty());
+    assert!(health.checks.iter().all(|check| check.ok));
+    assert!(!status::get_status_summary().unwrap().degraded);
+}
+
+#[test]
+fn simulated_backend_never_asks_to_elevate() {
+    let _machine = machine(DESK);
+
+    let privileges = privilege::get_privilege_status().unwrap();
+    assert!(privileges.device_control && privileges.storage_policy && privileges.user_policy);
+    assert!(!privileges.should_elevate);
+}
+
+#[test]
+fn names_unreadable_devices_from_the_usb_id_database() {
+    let _machine = machine(DESK);
+    assert_eq!(device(FLASH_DRIVE)["vendor_name"], "SanDisk Corp.");
+
+    let path = std::env::temp_dir().join(format!("usb-shield-usb-ids-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "# Version: 2030.01.01\n0781  SanDisk Corporation\n\t5581  Ultra Flair\n\t\t00  interface\nC 08  Mass Storage\n",
+    )
+    .unwrap();
+    let status = usb_names::update_usb_ids(path.display().to_string()).unwrap();
+    assert!(status.updated);
+    assert_eq!(status.version.as_deref(), Some("2030.01.01"));
+    assert_eq!(status.vendors, 1);
+
+    let drive = device(FLASH_DRIVE);
+    assert_eq!(drive["vendor_name"], "SanDisk Corporation");
+    assert_eq!(drive["product_name"], "Ultra Flair");
+    // Vendors the imported file lacks still resolve from the built-in copy
+    assert_eq!(device(KEYBOARD)["vendor_name"], "Logitech, Inc.");
+}
@@ -0,0 +1,213 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{device, devices, enabled, machine, reload, DESK, FLASH_DRIVE, KEYBOARD};
+use uport_shield_lib::usb::{
+    audit::AuditEntry,
+    category::DeviceCategory,
+    commands, hotplug,
+    inventory::{self, InventoryFilter, InventoryRecord, InventorySort, InventorySortField},
+    paging::PageRequest,
+    simulation,
+    suggestions::{self, SuggestionKind},
+};
+
+#[test]
+fn filters_the_inventory() {
+    let _machine = machine(DESK);
+    devices();
+
+    let by_text = inventory::query_inventory(
+        InventoryFilter {
+            text: Some("ultra".to_string()),
+            ..Default::default()
+        },
+        None,
+        None,
+    )
+    .unwrap()
+    .items;
+    assert_eq!(by_text.len(), 1);
+    assert_eq!(by_text[0].instance_id, FLASH_DRIVE);
+
+    // Serials are searchable too
+    let by_serial = inventory::query_inventory(
+        InventoryFilter {
+            text: Some("0918115462".to_string()),
+            ..Default::default()
+        },
+        None,
+        None,
+    )
+    .unwrap()
+    .items;
+    assert_eq!(by_serial.len(), 1);
+
+    let keyboards = inventory::query_inventory(
+        InventoryFilter {
+            category: Some(DeviceCategory::Keyboard),
+            trusted: Some(false),
+            ..Default::default()
+        },
+        None,
+        None,
+    )
+    .unwrap()
+    .items;
+    assert_eq!(keyboards.len(), 1);
+    assert_eq!(keyboards[0].instance_id, KEYBOARD);
+
+    let before_anything = inventory::query_inventory(
+        InventoryFilter {
+            seen_until: Some(Utc::now() - Duration::days(1)),
+            ..Default::default()
+        },
+        None,
+        None,
+    )
+    .unwrap()
+    .items;
+    assert!(before_anything.is_empty());
+}
+
+#[test]
+fn pages_through_a_sorted_inventory() {
+    let _machine = machine(DESK);
+    devices();
+    let sort = Some(InventorySort {
+        field: InventorySortField::Product,
+        descending: false,
+    });
+
+    let first = inventory::query_inventory(
+        InventoryFilter::default(),
+        sort,
+        Some(PageRequest { offset: 0, limit: Some(2) }),
+    )
+    .unwrap();
+    assert_eq!(first.total, 3);
+    assert_eq!(first.next_offset, Some(2));
+    let names: Vec<_> = first.items.iter().map(|r| r.product.clone().unwrap()).collect();
+    assert_eq!(names, ["Ultra", "USB Keyboard"]);
+
+    let rest = inventory::query_inventory(
+        InventoryFilter::default(),
+        sort,
+        Some(PageRequest { offset: 2, limit: Some(2) }),
+    )
+    .unwrap();
+    assert_eq!(rest.items.len(), 1);
+    assert_eq!(rest.items[0].product.as_deref(), Some("USB2.0 Hub"));
+    assert_eq!(rest.next_offset, None);
+}
+
+#[test]
+fn suggests_trust_for_long_lived_devices_and_revokes_stale_trust() {
+    let now = Utc::now();
+    let record = |instance_id: &str, category, days_ago: i64| InventoryRecord {
+        instance_id: instance_id.to_string(),
+        vendor_id: u16::from_str_radix(&instance_id[8..12], 16).unwrap(),
+        product_id: u16::from_str_radix(&instance_id[17..21], 16).unwrap(),
+        manufacturer: None,
+        product: Some("Test".to_string()),
+        serial: None,
+        category,
+        trusted: false,
+        first_seen: now - Duration::days(180),
+        last_seen: now - Duration::days(days_ago),
+        connection_count: 1,
+        connected: days_ago == 0,
+        preblocked: false,
+    };
+    let entry = |action: &str, instance_id: &str, days_ago: i64| AuditEntry {
+        timestamp: now - Duration::days(days_ago),
+        action: action.to_string(),
+        details: serde_json::json!({ "instance_id": instance_id }),
+    };
+    let records = vec![
+        record(KEYBOARD, DeviceCategory::Keyboard, 0),
+        record("USB\\VID_045E&PID_0040\\1", DeviceCategory::Mouse, 0),
+        record("USB\\VID_1234&PID_5678\\1", DeviceCategory::Other, 400),
+    ];
+    let mut entries: Vec<AuditEntry> = (0..40).map(|day| entry("device_connected", KEYBOARD, day * 4)).collect();
+    entries.extend((0..40).map(|day| entry("device_connected", "USB\\VID_045E&PID_0040\\1", day)));
+    entries.push(entry("keystroke_anomaly", "USB\\VID_045E&PID_0040\\1", 2));
+
+    let suggestions = suggestions::suggest(&entries, &records, &[(0x1234, 0x5678)], now);
+    assert_eq!(suggestions.len(), 2);
+    // The mouse was flagged, so only the keyboard is a trust candidate
+    assert_eq!(suggestions[0].kind, SuggestionKind::Trust);
+    assert_eq!((suggestions[0].vendor_id, suggestions[0].product_id), (0x046D, 0xC31C));
+    assert_eq!(suggestions[0].connections, 40);
+    assert_eq!(suggestions[1].kind, SuggestionKind::Revoke);
+    assert!(suggestions[1].reason.contains("not seen"));
+
+    // Quarantined once for keystroke injection: no longer a candidate
+    entries.push(entry("hid_quarantined", KEYBOARD, 1));
+    let suggestions = suggestions::suggest(&entries, &records, &[(0x1234, 0x5678)], now);
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].kind, SuggestionKind::Revoke);
+}
+
+fn history(instance_id: &str) -> InventoryRecord {
+    inventory::get_device_history()
+        .unwrap()
+        .into_iter()
+        .find(|record| record.instance_id.eq_ignore_ascii_case(instance_id))
+        .unwrap_or_else(|| panic!("{} not in the history", instance_id))
+}
+
+#[test]
+fn unplugged_devices_stay_in_the_history_and_can_be_preblocked() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    simulation::simulate_detach(FLASH_DRIVE.to_string()).unwrap();
+    hotplug::rescan();
+    let offline = history(FLASH_DRIVE);
+    assert!(!offline.connected);
+    assert!(offline.connection_count >= 1);
+
+    inventory::set_device_preblocked(FLASH_DRIVE.to_string(), true).unwrap();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(!enabled(FLASH_DRIVE));
+    assert_eq!(device(FLASH_DRIVE)["block_reason"], "Manual");
+    let back = history(FLASH_DRIVE);
+    assert!(back.connected && back.preblocked);
+    assert_eq!(back.connection_count, offline.connection_count + 1);
+
+    inventory::set_device_preblocked(FLASH_DRIVE.to_string(), false).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn preblocked_devices_without_a_serial_are_recognised_on_another_port() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    inventory::set_device_preblocked(KEYBOARD.to_string(), true).unwrap();
+    assert!(!enabled(KEYBOARD));
+
+    // Moved to another port: a new instance ID for the same keyboard
+    const MOVED: &str = "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&3";
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    simulation::simulate_attach(
+        serde_json::from_str(
+            r#"{ "instance_id": "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&3", "ports": [1, 3],
+                 "parent_instance_id": "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1",
+                 "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }] }"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    hotplug::rescan();
+    assert!(!enabled(MOVED));
+    assert_eq!(device(MOVED)["block_reason"], "Manual");
+
+    inventory::set_device_preblocked(KEYBOARD.to_string(), false).unwrap();
+    commands::enable_device(0x046D, 0xC31C, None, Some(MOVED.to_string()), None).unwrap();
+    reload(DESK);
+    commands::enable_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()), None).unwrap();
+}
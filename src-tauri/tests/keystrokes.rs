@@ -0,0 +1,138 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use common::{device, enabled, machine, DESK, KEYBOARD};
+use std::time::{Duration, Instant};
+
+use uport_shield_lib::usb::{
+    commands, hid_quarantine, hotplug, keyboard_lockdown, keystrokes::CadenceBaseline, simulation, usb_config,
+};
+
+// A person: gaps between 100 and 300 ms, never twice the same
+fn human(i: usize) -> f64 {
+    100.0 + ((i * 37) % 200) as f64
+}
+
+#[test]
+fn scripted_burst_deviates_from_the_baseline() {
+    let mut baseline = CadenceBaseline::default();
+    for i in 0..500 {
+        assert!(baseline.record(human(i)).is_none());
+    }
+    assert!(baseline.learned());
+
+    // More of the same typing passes
+    for i in 500..600 {
+        assert!(baseline.record(human(i)).is_none());
+    }
+
+    // An injector replaying keys every 8 ms
+    let alerts = (0..20).filter_map(|_| baseline.record(8.0)).count();
+    assert_eq!(alerts, 1);
+}
+
+#[test]
+fn new_keyboard_typing_like_a_script_is_quarantined_until_approved() {
+    let _machine = machine(DESK);
+    let keyboard = commands::get_usb_devices()
+        .unwrap()
+        .into_iter()
+        .find(|device| device.instance_id() == Some(KEYBOARD))
+        .unwrap();
+    hid_quarantine::on_connected(&keyboard);
+
+    // Quick but human typing first, then a payload at 8 ms a key
+    let start = Instant::now();
+    for i in 0..10 {
+        hid_quarantine::on_key_press((0x046D, 0xC31C), start + Duration::from_millis(i * 60));
+    }
+    assert!(enabled(KEYBOARD));
+    for i in 0..20 {
+        hid_quarantine::on_key_press((0x046D, 0xC31C), start + Duration::from_millis(600 + i * 8));
+    }
+    assert!(!enabled(KEYBOARD));
+    let quarantine = hid_quarantine::get_hid_quarantine().unwrap();
+    assert_eq!(quarantine.len(), 1);
+    assert_eq!(quarantine[0].instance_id, KEYBOARD);
+
+    hid_quarantine::approve_hid_device(KEYBOARD.to_string(), None).unwrap();
+    assert!(enabled(KEYBOARD));
+    assert!(hid_quarantine::get_hid_quarantine().unwrap().is_empty());
+}
+
+#[test]
+fn keyboard_lockdown_blocks_new_keyboards_until_approved() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    let lockdown = keyboard_lockdown::set_keyboard_lockdown(true).unwrap();
+    assert!(lockdown.known.iter().any(|known| known.instance_id == KEYBOARD));
+
+    let ducky = "USB\\VID_03EB&PID_2401\\5&3B1E52C&0&3";
+    simulation::simulate_attach(
+        serde_json::from_str(
+            r#"{
+                "instance_id": "USB\\VID_03EB&PID_2401\\5&3B1E52C&0&3",
+                "product": "Keyboard",
+                "ports": [3],
+                "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }]
+            }"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    hotplug::rescan();
+    assert!(!enabled(ducky));
+    assert!(enabled(KEYBOARD));
+    assert_eq!(device(ducky)["block_reason"], "KeyboardLockdown");
+    assert_eq!(keyboard_lockdown::get_keyboard_lockdown().unwrap().pending.len(), 1);
+
+    let lockdown = keyboard_lockdown::approve_keyboard(ducky.to_string(), None).unwrap();
+    assert!(enabled(ducky));
+    assert!(lockdown.pending.is_empty());
+    assert!(lockdown.known.iter().any(|known| known.instance_id == ducky));
+
+    keyboard_lockdown::forget_keyboard(ducky.to_string()).unwrap();
+    keyboard_lockdown::set_keyboard_lockdown(false).unwrap();
+}
+
+#[test]
+fn known_keyboard_without_a_serial_keeps_typing_on_another_port() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    keyboard_lockdown::set_keyboard_lockdown(true).unwrap();
+
+    let moved = "USB\\VID_046D&PID_C31C\\5&3B1E52C&0&4";
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    simulation::simulate_attach(
+        serde_json::from_str(
+            r#"{
+                "instance_id": "USB\\VID_046D&PID_C31C\\5&3B1E52C&0&4",
+                "product": "USB Keyboard",
+                "ports": [4],
+                "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }]
+            }"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    hotplug::rescan();
+    assert!(enabled(moved));
+    assert!(keyboard_lockdown::get_keyboard_lockdown().unwrap().pending.is_empty());
+
+    keyboard_lockdown::set_keyboard_lockdown(false).unwrap();
+}
+
+#[test]
+fn unreadable_lockdown_file_turns_lockdown_on() {
+    let _machine = machine(DESK);
+    let path = usb_config::data_file("keyboard-lockdown.json").unwrap();
+    std::fs::write(&path, b"{ not json").unwrap();
+
+    assert!(keyboard_lockdown::load().is_err());
+    let lockdown = keyboard_lockdown::get_keyboard_lockdown().unwrap();
+    assert!(lockdown.enabled);
+    assert!(lockdown.known.is_empty());
+
+    keyboard_lockdown::set_keyboard_lockdown(false).unwrap();
+}
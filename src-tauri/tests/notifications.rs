@@ -0,0 +1,269 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, UdpSocket},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use common::{machine, DESK, FLASH_DRIVE};
+use serde_json::Value;
+use tauri::async_runtime::block_on;
+use uport_shield_lib::usb::{
+    commands,
+    email_alerts::{self, EmailAlertSettings, SmtpSecurity},
+    notifications::{self, Channel, NotificationPreferences, Severity},
+    profiles::Profile,
+    siem::{self, SiemFormat, SiemSettings, SiemTransport},
+    usb_config,
+    webhooks::{self, Webhook},
+};
+
+/// Answers every POST with 200 and hands over its signature header and body.
+fn webhook_receiver() -> (String, mpsc::Receiver<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let (mut signature, mut length) = (String::new(), 0);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                if let Some((name, value)) = line.trim_end().split_once(": ") {
+                    match name.to_ascii_lowercase().as_str() {
+                        "x-usb-shield-signature" => signature = value.to_string(),
+                        "content-length" => length = value.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0u8; length];
+            let _ = reader.read_exact(&mut body);
+            let _ = reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = sender.send((signature, String::from_utf8_lossy(&body).to_string()));
+        }
+    });
+    (url, receiver)
+}
+
+/// Accepts one SMTP session and hands over the message it was given.
+fn smtp_receiver() -> (u16, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let _ = writer.write_all(b"220 localhost ESMTP\r\n");
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                b"250 localhost\r\n"
+            } else if command.starts_with("DATA") {
+                let _ = writer.write_all(b"354 End with <CRLF>.<CRLF>\r\n");
+                let mut message = String::new();
+                line.clear();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != ".\r\n" {
+                    message.push_str(&line);
+                    line.clear();
+                }
+                let _ = sender.send(message);
+                b"250 Queued\r\n"
+            } else if command.starts_with("QUIT") {
+                let _ = writer.write_all(b"221 Bye\r\n");
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            let _ = writer.write_all(reply);
+            line.clear();
+        }
+    });
+    (port, receiver)
+}
+
+#[test]
+fn routes_channels_per_profile_and_severity() {
+    let _machine = machine(DESK);
+    assert_eq!(notifications::channels_for(Profile::Strict, Severity::Info), vec![Channel::Toast]);
+
+    notifications::set_notification_route(Profile::Strict, Channel::Email, Some(Severity::Critical)).unwrap();
+    assert_eq!(notifications::channels_for(Profile::Strict, Severity::Warning), vec![Channel::Toast]);
+    assert_eq!(
+        notifications::channels_for(Profile::Strict, Severity::Critical),
+        vec![Channel::Toast, Channel::Email]
+    );
+    // Other profiles keep their own routes
+    assert_eq!(notifications::channels_for(Profile::Standard, Severity::Critical), vec![Channel::Toast]);
+
+    let deliveries = notifications::notify(Severity::Critical, "test", "Test", "Routed under Standard");
+    assert_eq!(deliveries.len(), 1);
+    assert!(deliveries[0].error.is_none());
+
+    notifications::set_notification_route(Profile::Strict, Channel::Email, None).unwrap();
+}
+
+#[test]
+fn syslog_routes_reach_the_collector_without_audit_forwarding() {
+    let _machine = machine(DESK);
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    siem::configure_siem_forwarding(SiemSettings {
+        enabled: false,
+        transport: SiemTransport::Udp,
+        endpoint: collector.local_addr().unwrap().to_string(),
+        format: SiemFormat::Json,
+    })
+    .unwrap();
+    notifications::set_notification_route(Profile::Standard, Channel::Syslog, Some(Severity::Warning)).unwrap();
+
+    let deliveries = notifications::notify(Severity::Warning, "harness_syslog", "Routed", "To syslog");
+    let syslog = deliveries.iter().find(|d| d.channel == Channel::Syslog).expect("routed");
+    assert!(syslog.error.is_none(), "{:?}", syslog.error);
+    let mut buffer = [0u8; 8192];
+    let (len, _) = collector.recv_from(&mut buffer).expect("syslog message");
+    let message = String::from_utf8_lossy(&buffer[..len]).to_string();
+    assert!(message.contains("harness_syslog") && message.contains("To syslog"), "{}", message);
+
+    notifications::set_notification_route(Profile::Standard, Channel::Syslog, None).unwrap();
+    siem::configure_siem_forwarding(SiemSettings::default()).unwrap();
+    let deliveries = notifications::notify(Severity::Warning, "harness_syslog", "Routed", "Nowhere");
+    assert!(deliveries.iter().all(|d| d.channel != Channel::Syslog));
+}
+
+#[test]
+fn device_event_toasts_can_be_turned_off_per_kind() {
+    let _machine = machine(DESK);
+    let all = notifications::get_notification_preferences().unwrap();
+    notifications::set_notification_preferences(NotificationPreferences { connected: false, ..all }).unwrap();
+
+    let connected = notifications::notify(Severity::Info, "device_connected", "USB device connected", "Ultra");
+    assert!(connected.is_empty());
+    let blocked = notifications::notify(Severity::Warning, "device_autoblocked", "USB device blocked", "Ultra");
+    assert_eq!(blocked.len(), 1);
+    assert_eq!(blocked[0].channel, Channel::Toast);
+
+    notifications::set_notification_preferences(all).unwrap();
+}
+
+#[test]
+fn silenced_toasts_stay_silenced_after_a_restart() {
+    let _machine = machine(DESK);
+    let all = notifications::get_notification_preferences().unwrap();
+    notifications::set_notification_preferences(NotificationPreferences { blocked: false, ..all }).unwrap();
+
+    let path = usb_config::data_file("notifications.json").unwrap();
+    let stored: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(stored["preferences"]["blocked"], false);
+    notifications::load().unwrap();
+    assert!(!notifications::get_notification_preferences().unwrap().blocked);
+    let blocked = notifications::notify(Severity::Warning, "port_lock_enforced", "Blocked", "Ultra");
+    assert!(blocked.is_empty());
+
+    notifications::set_notification_preferences(all).unwrap();
+}
+
+#[test]
+fn blocks_are_posted_to_webhooks_with_a_signature() {
+    let _machine = machine(DESK);
+    let (url, received) = webhook_receiver();
+    let webhook = |secret: Option<&str>| Webhook {
+        url: url.clone(),
+        secret: secret.map(String::from),
+        has_secret: false,
+    };
+    assert!(webhooks::set_webhooks(vec![webhook(None)]).is_err());
+    webhooks::set_webhooks(vec![webhook(Some("s3cret"))]).unwrap();
+    // Listed without the secret, kept when it is not sent again, and
+    // encrypted on disk
+    let listed = webhooks::get_webhooks().unwrap();
+    assert!(listed[0].has_secret);
+    assert!(!serde_json::to_string(&listed).unwrap().contains("s3cret"));
+    webhooks::set_webhooks(vec![webhook(None)]).unwrap();
+    let stored = std::fs::read_to_string(usb_config::data_file("webhooks.json").unwrap()).unwrap();
+    assert!(!stored.contains("s3cret"));
+
+    let deliveries = block_on(webhooks::send_test_webhook()).unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert!(deliveries[0].ok, "{:?}", deliveries[0].error);
+    assert_eq!((deliveries[0].status, deliveries[0].attempts), (Some(200), 1));
+    let (signature, body) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    let expected = format!(
+        "sha256={}",
+        hex::encode({
+            use hmac::Mac;
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(b"s3cret").unwrap();
+            mac.update(body.as_bytes());
+            mac.finalize().into_bytes()
+        })
+    );
+    assert_eq!(signature, expected);
+
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    let (_, body) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    let payload: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["event"], "device_blocked");
+    assert_eq!(payload["details"]["instance_id"], FLASH_DRIVE);
+
+    notifications::set_notification_route(Profile::Standard, Channel::Webhook, Some(Severity::Warning)).unwrap();
+    let deliveries = notifications::notify(Severity::Warning, "harness_webhook", "Routed", "To the webhook");
+    assert!(deliveries.iter().any(|d| d.channel == Channel::Webhook && d.error.is_none()));
+    let (_, body) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    let payload: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["event"], "notification");
+    assert_eq!(payload["details"]["kind"], "harness_webhook");
+    notifications::set_notification_route(Profile::Standard, Channel::Webhook, None).unwrap();
+
+    webhooks::set_webhooks(Vec::new()).unwrap();
+    block_on(commands::unblock_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None, None)).unwrap();
+}
+
+#[test]
+fn test_emails_reach_the_admin_and_the_password_stays_encrypted() {
+    let _machine = machine(DESK);
+    let (port, received) = smtp_receiver();
+    let settings = EmailAlertSettings {
+        enabled: true,
+        host: "127.0.0.1".to_string(),
+        port,
+        security: SmtpSecurity::None,
+        username: None,
+        password: Some("hunter2".to_string()),
+        has_password: false,
+        from: "shield@example.com".to_string(),
+        to: "admin@example.com".to_string(),
+    };
+    assert!(email_alerts::configure_email_alerts(EmailAlertSettings {
+        to: "not an address".to_string(),
+        ..settings.clone()
+    })
+    .is_err());
+    email_alerts::configure_email_alerts(settings).unwrap();
+
+    let saved = email_alerts::get_email_alerts().unwrap();
+    assert!(saved.has_password);
+    assert!(!serde_json::to_string(&saved).unwrap().contains("hunter2"));
+    let stored = std::fs::read_to_string(usb_config::data_file("email-alerts.json").unwrap()).unwrap();
+    assert!(!stored.contains("hunter2"));
+
+    block_on(email_alerts::send_test_email()).unwrap();
+    let message = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(message.contains("Subject: [USB-Shield] Test alert"), "{}", message);
+    assert!(message.contains("admin@example.com"));
+
+    email_alerts::configure_email_alerts(EmailAlertSettings {
+        password: Some(String::new()),
+        ..EmailAlertSettings::default()
+    })
+    .unwrap();
+}
@@ -0,0 +1,387 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use std::{fs, io::Read, net::UdpSocket, time::Duration as StdDuration};
+
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tauri::async_runtime::block_on;
+use uport_shield_lib::usb::{
+    audit::{self, ExportFormat},
+    backend,
+    class_policy::{self, ClassAction},
+    commands, config_export,
+    logging::{self, LogLevel},
+    paging::PageRequest,
+    policy_diff::{self, ChangeKind},
+    profiles::{self, Profile},
+    rollback,
+    siem::{self, SiemFormat, SiemSettings, SiemTransport},
+    support_bundle,
+    trust_rules::{self, TrustBinding},
+    uninstall, usb_config,
+    usb_control::{self, BlockReason},
+};
+
+use common::{enabled, machine, reload, DESK, FLASH_DRIVE};
+
+#[test]
+fn audit_entries_survive_a_reread() {
+    let _machine = machine(DESK);
+
+    audit::record("harness_marker", json!({ "run": "audit_entries_survive_a_reread" }));
+    let entries = audit::read_all().unwrap();
+    let entry = entries
+        .iter()
+        .rev()
+        .find(|e| e.action == "harness_marker")
+        .expect("entry written");
+    assert_eq!(entry.details["run"], "audit_entries_survive_a_reread");
+}
+
+#[test]
+fn audited_operations_are_logged_at_the_chosen_level() {
+    let _machine = machine(DESK);
+
+    audit::record("harness_log_marker", json!({ "level": "info" }));
+    let recent = logging::get_recent_logs(50, Some(LogLevel::Info)).unwrap();
+    let entry = recent.iter().rev().find(|e| e.message == "harness_log_marker").expect("entry logged");
+    assert_eq!(entry.target, "audit");
+    assert_eq!(entry.fields["level"], "info");
+    assert!(logging::get_recent_logs(50, Some(LogLevel::Error))
+        .unwrap()
+        .iter()
+        .all(|e| e.level == LogLevel::Error));
+
+    logging::set_log_level(LogLevel::Warn).unwrap();
+    audit::record("harness_log_marker", json!({ "level": "warn" }));
+    let recent = logging::get_recent_logs(50, None).unwrap();
+    assert!(!recent.iter().any(|e| e.fields["level"] == "warn"));
+    logging::set_log_level(LogLevel::Info).unwrap();
+}
+
+#[test]
+fn audit_events_are_forwarded_to_syslog_as_cef() {
+    let _machine = machine(DESK);
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(StdDuration::from_secs(5))).unwrap();
+    let received = |wanted: &str| {
+        let mut buffer = [0u8; 8192];
+        loop {
+            let (len, _) = collector.recv_from(&mut buffer).expect("syslog message");
+            let message = String::from_utf8_lossy(&buffer[..len]).to_string();
+            if message.contains(wanted) {
+                return message;
+            }
+        }
+    };
+
+    assert!(siem::configure_siem_forwarding(SiemSettings {
+        enabled: true,
+        transport: SiemTransport::Udp,
+        endpoint: "not an endpoint".to_string(),
+        format: SiemFormat::Cef,
+    })
+    .is_err());
+    siem::configure_siem_forwarding(SiemSettings {
+        enabled: true,
+        transport: SiemTransport::Udp,
+        endpoint: collector.local_addr().unwrap().to_string(),
+        format: SiemFormat::Cef,
+    })
+    .unwrap();
+
+    block_on(siem::test_siem_connection()).unwrap();
+    assert!(received("siem_test").contains("CEF:0|USB-Shield|USB-Shield|"));
+
+    audit::record("harness_forwarded", json!({ "instance_id": FLASH_DRIVE, "note": "a=b" }));
+    let message = received("harness_forwarded");
+    assert!(message.starts_with("<110>1 "), "{}", message);
+    assert!(message.contains("act=harness_forwarded"));
+    assert!(message.contains(r"note=a\=b"));
+
+    siem::configure_siem_forwarding(SiemSettings::default()).unwrap();
+}
+
+#[test]
+fn profile_changes_are_audited() {
+    let _machine = machine(r#"{ "devices": [] }"#);
+
+    profiles::request("test", Some(Profile::Strict));
+    profiles::request("test", None);
+
+    let changes: Vec<_> = audit::read_all()
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.action == "profile_changed")
+        .collect();
+    assert!(changes.len() >= 2);
+    assert_eq!(changes[changes.len() - 1].details["current"], "Standard");
+    assert_eq!(changes[changes.len() - 2].details["current"], "Strict");
+}
+
+#[test]
+fn signed_export_matches_its_detached_signature() {
+    let _machine = machine(DESK);
+    audit::record("harness_marker", json!({ "run": "signed_export" }));
+
+    let now = Utc::now();
+    let export = audit::export_audit_range(
+        now - Duration::minutes(5),
+        now + Duration::minutes(5),
+        ExportFormat::Json,
+        None,
+    )
+    .unwrap();
+    assert!(export.entries >= 1);
+
+    let body: Value = serde_json::from_str(&fs::read_to_string(&export.path).unwrap()).unwrap();
+    assert!(body["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|e| e["details"]["run"] == "signed_export"));
+    assert_eq!(fs::read_to_string(&export.signature_path).unwrap().trim(), export.signature);
+}
+
+#[test]
+fn audit_query_is_paged_newest_first() {
+    let _machine = machine(DESK);
+
+    for run in 0..3 {
+        audit::record("harness_paging", json!({ "run": run }));
+    }
+    let filter = audit::AuditFilter {
+        action: Some("harness_paging".to_string()),
+        ..Default::default()
+    };
+    let page = audit::query_audit_log(filter.clone(), None, Some(PageRequest { offset: 0, limit: Some(2) })).unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(page.items[0].details["run"], 2);
+    assert_eq!(page.next_offset, Some(2));
+
+    let oldest = audit::query_audit_log(filter, Some(true), None).unwrap();
+    assert_eq!(oldest.items[0].details["run"], 0);
+}
+
+#[test]
+fn anonymized_support_bundle_hides_serials() {
+    let _machine = machine(DESK);
+
+    let bundle = support_bundle::generate_support_bundle(true, None).unwrap();
+    assert_eq!(bundle.files, ["diagnostics.json", "audit-log.json", "policy.json", "environment.json"]);
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&bundle.path).unwrap()).unwrap();
+    let mut diagnostics = String::new();
+    archive.by_name("diagnostics.json").unwrap().read_to_string(&mut diagnostics).unwrap();
+    let body: Value = serde_json::from_str(&diagnostics).unwrap();
+    assert!(body["devices"].as_array().unwrap().len() >= 3);
+    assert!(!diagnostics.contains("4C530001230918115462"));
+    assert!(diagnostics.contains("anon-"));
+
+    // Salted per bundle: the same serial gets another pseudonym next time
+    let pseudonyms = |path: &str| {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        let mut diagnostics = String::new();
+        archive.by_name("diagnostics.json").unwrap().read_to_string(&mut diagnostics).unwrap();
+        let body: Value = serde_json::from_str(&diagnostics).unwrap();
+        body["devices"][0]["instance_id"].as_str().unwrap().to_string()
+    };
+    let again = support_bundle::generate_support_bundle(true, Some(format!("{}.again.zip", bundle.path))).unwrap();
+    assert_ne!(pseudonyms(&bundle.path), pseudonyms(&again.path));
+}
+
+#[test]
+fn policy_diff_lists_what_a_push_would_change() {
+    let _machine = machine(DESK);
+
+    let mut pushed = support_bundle::policy();
+    pushed["autoblock"] = json!(!pushed["autoblock"].as_bool().unwrap());
+    pushed["trusted_devices"].as_array_mut().unwrap().push(json!([4660, 1]));
+    pushed["hub_policy"]["allowed"] = json!([{ "vendor_id": 1507, "product_id": 1552, "container_id": null }]);
+    pushed.as_object_mut().unwrap().remove("power");
+    let path = usb_config::data_file("pushed-policy.json").unwrap();
+    fs::write(&path, serde_json::to_vec(&pushed).unwrap()).unwrap();
+    let path = path.to_string_lossy().to_string();
+
+    let changeset = policy_diff::diff_policies(None, path.clone()).unwrap();
+    assert_eq!((changeset.added, changeset.removed, changeset.modified), (2, 1, 1));
+    let change = |section: &str| changeset.changes.iter().find(|c| c.section == section).unwrap();
+    assert_eq!(change("autoblock").kind, ChangeKind::Modified);
+    assert_eq!(change("trusted_devices").kind, ChangeKind::Added);
+    assert_eq!(change("hub_policy").path, "hub_policy.allowed[vendor_id=1507,product_id=1552]");
+    assert_eq!(change("power").kind, ChangeKind::Removed);
+
+    // Same file on both sides: nothing to apply
+    assert!(policy_diff::diff_policies(Some(path.clone()), path).unwrap().changes.is_empty());
+}
+
+#[test]
+fn trusted_devices_are_saved_and_reloaded() {
+    let _machine = machine(DESK);
+
+    commands::app_state().add_trusted_device(0x1234, 0x0001).unwrap();
+    let path = usb_config::data_file("trusted-devices.json").unwrap();
+    let saved: Vec<(u16, u16)> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(saved.contains(&(0x1234, 0x0001)));
+
+    // Edited on disk, e.g. deployed by an admin
+    fs::write(&path, "[[4660, 2]]").unwrap();
+    commands::app_state().load_trusted_devices().unwrap();
+    assert_eq!(commands::app_state().trusted_devices(), [(0x1234, 0x0002)]);
+
+    commands::app_state().remove_trusted_device(0x1234, 0x0002, None).unwrap();
+    commands::app_state().load_trusted_devices().unwrap();
+    assert!(commands::app_state().trusted_devices().is_empty());
+}
+
+#[test]
+fn trust_bindings_and_label_rules_are_saved_with_the_trust_list() {
+    let _machine = machine(DESK);
+
+    let binding = trust_rules::bind_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+    trust_rules::set_volume_label_rules(vec!["CORP-*".to_string()]).unwrap();
+    let path = usb_config::data_file("trust-rules.json").unwrap();
+    let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["bindings"][0]["serial"], "4C530001230918115462");
+    assert_eq!(saved["label_rules"], json!(["CORP-*"]));
+
+    // Read back as setup does after a restart
+    fs::write(&path, serde_json::to_vec(&saved).unwrap()).unwrap();
+    commands::app_state().load_trusted_devices().unwrap();
+    assert_eq!(trust_rules::get_trust_bindings().unwrap(), [binding]);
+    assert_eq!(trust_rules::get_volume_label_rules().unwrap(), ["CORP-*"]);
+
+    trust_rules::set_volume_label_rules(Vec::new()).unwrap();
+    trust_rules::remove_trust_binding(0x0781, 0x5581, Some("4C530001230918115462".to_string())).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+    commands::app_state().load_trusted_devices().unwrap();
+    assert!(trust_rules::get_trust_bindings().unwrap().is_empty());
+}
+
+#[test]
+fn restoring_the_snapshot_undoes_a_port_block() {
+    let _machine = machine(DESK);
+    let usbstor_start = backend::controller().usbstor_start();
+
+    block_on(commands::block_all_usb_ports()).unwrap();
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    assert_eq!(backend::controller().usbstor_start(), Some(4));
+
+    // Read back from disk, as after a restart
+    let snapshot = rollback::get_rollback_snapshot().unwrap().expect("snapshot");
+    assert_eq!(snapshot.taken_before, "block_all_usb_ports");
+    assert_eq!(snapshot.usbstor_start, usbstor_start);
+    // A second block keeps the state from before the first
+    block_on(commands::block_all_usb_ports()).unwrap();
+    assert_eq!(rollback::get_rollback_snapshot().unwrap().unwrap().taken_at, snapshot.taken_at);
+
+    let report = block_on(rollback::restore_previous_state(None)).unwrap();
+    assert_eq!(report.devices_enabled, [FLASH_DRIVE]);
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(backend::controller().usbstor_start(), usbstor_start);
+    assert!(rollback::get_rollback_snapshot().unwrap().is_none());
+}
+
+#[test]
+fn cleanup_lifts_our_blocks_and_removes_the_data_directory() {
+    let _machine = machine(DESK);
+    block_on(commands::block_all_usb_ports()).unwrap();
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+
+    let report = uninstall::cleanup();
+    assert_eq!(report.devices_enabled, [FLASH_DRIVE]);
+    assert!(report.policies_cleared.iter().any(|policy| policy.ends_with(r"USBSTOR\Start")));
+    assert!(report.data_removed);
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(backend::controller().usbstor_start(), Some(3));
+    let data_dir = usb_config::data_dir().unwrap();
+    assert!(!data_dir.exists());
+
+    // Other tests in this binary still need it
+    usb_config::init(data_dir).unwrap();
+}
+
+#[test]
+fn blocks_and_trust_changes_are_audited_and_exported() {
+    let _machine = machine(DESK);
+
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    commands::app_state().add_trusted_device(0x0781, 0x5581).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+
+    let latest = |action: &str| {
+        let filter = audit::AuditFilter {
+            action: Some(action.to_string()),
+            ..Default::default()
+        };
+        audit::get_audit_log(filter, None).unwrap().items.into_iter().next()
+    };
+    assert_eq!(latest("device_disabled").unwrap().details["instance_id"], FLASH_DRIVE);
+    assert_eq!(latest("device_enabled").unwrap().details["vendor_id"], 0x0781);
+    assert_eq!(latest("trusted_device_added").unwrap().details["product_id"], 0x5581);
+    assert!(latest("trusted_device_removed").is_some());
+
+    let path = usb_config::data_file("audit-export-test.csv").unwrap();
+    let export = audit::export_audit_log(path.display().to_string(), ExportFormat::Csv).unwrap();
+    assert!(export.entries >= 4);
+    assert!(fs::read_to_string(&path).unwrap().contains("trusted_device_added"));
+}
+
+#[test]
+fn block_records_are_rebuilt_from_the_audit_log() {
+    let _machine = machine(DESK);
+
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    usb_control::load().unwrap();
+    assert_eq!(usb_control::block_reason(FLASH_DRIVE), Some(BlockReason::Manual));
+
+    // Enabled behind our back, e.g. from Device Manager, while the app was closed
+    reload(DESK);
+    usb_control::load().unwrap();
+    assert!(!usb_control::is_blocked_by_us(FLASH_DRIVE));
+}
+
+#[test]
+fn exported_config_restores_trust_and_class_policies() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x1234, 0x0003).unwrap();
+    class_policy::set_class_policy(0x03, Some(ClassAction::Allow), None).unwrap();
+    let path = usb_config::data_file("exported-config.json").unwrap().to_string_lossy().to_string();
+    let binding = TrustBinding {
+        vendor_id: 0x1234,
+        product_id: 0x0003,
+        serial: Some("0001".to_string()),
+        volume_serials: Vec::new(),
+    };
+    trust_rules::install_binding(binding.clone()).unwrap();
+    let exported = config_export::export_config(path.clone()).unwrap();
+    assert_eq!(exported.version, config_export::CONFIG_VERSION);
+    assert_eq!(exported.trust_bindings, std::slice::from_ref(&binding));
+    // Diffed against the live settings in its own shape, a fresh export matches
+    assert!(policy_diff::diff_policies(None, path.clone()).unwrap().changes.is_empty());
+
+    trust_rules::remove_trust_binding(0x1234, 0x0003, binding.serial.clone()).unwrap();
+    commands::app_state().remove_trusted_device(0x1234, 0x0003, None).unwrap();
+    class_policy::set_class_policy(0x03, None, None).unwrap();
+    class_policy::set_class_policy(0xE0, Some(ClassAction::Block), None).unwrap();
+
+    let import = block_on(config_export::import_config(path.clone(), None)).unwrap();
+    assert!(import.errors.is_empty(), "{:?}", import.errors);
+    assert_eq!(import.changes.len(), 4);
+    assert!(commands::app_state().trusted_devices().contains(&(0x1234, 0x0003)));
+    assert_eq!(trust_rules::get_trust_bindings().unwrap(), std::slice::from_ref(&binding));
+    let policies = class_policy::get_class_policies().unwrap();
+    assert_eq!(policies.len(), 1);
+    assert_eq!((policies[0].class_code, policies[0].action), (0x03, ClassAction::Allow));
+
+    // Importing the same file again changes nothing
+    assert!(block_on(config_export::import_config(path, None)).unwrap().changes.is_empty());
+
+    class_policy::set_class_policy(0x03, None, None).unwrap();
+    trust_rules::remove_trust_binding(0x1234, 0x0003, binding.serial).unwrap();
+    commands::app_state().remove_trusted_device(0x1234, 0x0003, None).unwrap();
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use common::{devices, machine, reload, DESK, FLASH_DRIVE, KEYBOARD};
+use uport_shield_lib::usb::{device_power, port_power};
+
+// The desk keyboard, armed to wake the machine
+const WAKE_DESK: &str = r#"{
+    "devices": [
+        {
+            "instance_id": "USB\\VID_046D&PID_C31C\\6&2C0E4F1&0&1",
+            "product": "USB Keyboard",
+            "ports": [1],
+            "interfaces": [{ "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }],
+            "wake_armed": true
+        },
+        {
+            "instance_id": "USB\\VID_0781&PID_5581\\4C530001230918115462",
+            "product": "Ultra",
+            "ports": [2],
+            "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }]
+        }
+    ]
+}"#;
+
+#[test]
+fn selective_suspend_round_trips() {
+    let _machine = machine(DESK);
+
+    let before = device_power::get_selective_suspend(KEYBOARD.to_string()).unwrap();
+    assert_eq!(before.enabled, None);
+
+    let after = device_power::set_selective_suspend(KEYBOARD.to_string(), false).unwrap();
+    assert_eq!(after.enabled, Some(false));
+    assert!(device_power::set_selective_suspend("USB\\VID_FFFF&PID_0001\\1".to_string(), true).is_err());
+}
+
+#[test]
+fn wake_can_be_disarmed() {
+    let _machine = machine(WAKE_DESK);
+
+    let devices = device_power::get_wake_devices().unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].instance_id, KEYBOARD);
+    assert!(devices[0].armed);
+
+    device_power::set_wake_enabled(KEYBOARD.to_string(), false).unwrap();
+    assert!(!device_power::get_wake_devices().unwrap()[0].armed);
+    // The flash drive cannot wake anything
+    assert!(device_power::set_wake_enabled(FLASH_DRIVE.to_string(), true).is_err());
+}
+
+#[test]
+fn cut_port_power_drops_the_device_until_restored() {
+    // Only a hub that switches ports individually may cut one
+    let _machine = machine(DESK);
+    assert!(port_power::cut_port_power(FLASH_DRIVE.to_string()).is_err());
+
+    reload(&DESK.replace(r#""device_class": 9,"#, r#""device_class": 9, "port_power_switching": true,"#));
+    let cut = port_power::cut_port_power(FLASH_DRIVE.to_string()).unwrap();
+    assert_eq!(cut.port, 2);
+    assert_eq!(devices().len(), 2);
+    assert_eq!(port_power::get_powered_off_ports().unwrap().len(), 1);
+
+    port_power::restore_port_power(cut.hub_instance_id, cut.port).unwrap();
+    assert_eq!(devices().len(), 3);
+    assert!(port_power::get_powered_off_ports().unwrap().is_empty());
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "test-harness")]
+
+use uport_shield_helper::registry::{self, RegistryError, Root};
+use uport_shield_lib::usb::error::UsbShieldError;
+use winreg::{enums::HKEY_CURRENT_USER, RegKey};
+
+// A scratch key of our own under HKCU, so the suite needs no elevation
+fn scratch(name: &str) -> String {
+    format!(r"Software\USB-Shield-Tests\{}-{}", std::process::id(), name)
+}
+
+#[test]
+fn values_round_trip_through_nested_keys_with_unicode_names() {
+    let path = format!(r"{}\Políticas\Ünïcode", scratch("values"));
+    let key = registry::create(Root::CurrentUser, &path).unwrap();
+    key.set_dword("Deny_All", 1).unwrap();
+    key.set_string("Beschreibung", "Wechseldatenträger").unwrap();
+
+    // Read back with a different API: what we wrote is what Windows stored
+    let reread = RegKey::predef(HKEY_CURRENT_USER).open_subkey(&path).unwrap();
+    assert_eq!(reread.get_value::<u32, _>("Deny_All").unwrap(), 1);
+    assert_eq!(reread.get_value::<String, _>("Beschreibung").unwrap(), "Wechseldatenträger");
+    assert_eq!(key.dword("Deny_All").unwrap(), Some(1));
+    assert_eq!(key.dword("Beschreibung").unwrap(), None);
+    assert_eq!(key.dword("Missing").unwrap(), None);
+
+    key.delete_value("Deny_All").unwrap();
+    key.delete_value("Deny_All").unwrap();
+    assert_eq!(key.dword("Deny_All").unwrap(), None);
+    drop(key);
+
+    registry::delete_tree(Root::CurrentUser, &scratch("values")).unwrap();
+    assert!(registry::open(Root::CurrentUser, &path).unwrap().is_none());
+}
+
+#[test]
+fn deleting_a_tree_takes_subkeys_along_and_tolerates_missing_keys() {
+    let root = scratch("tree");
+    registry::create(Root::CurrentUser, &format!(r"{}\{{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}}", root))
+        .unwrap()
+        .set_dword("Deny_Write", 1)
+        .unwrap();
+    registry::create(Root::CurrentUser, &root).unwrap().set_dword("Deny_All", 1).unwrap();
+
+    registry::delete_tree(Root::CurrentUser, &root).unwrap();
+    assert!(registry::open(Root::CurrentUser, &root).unwrap().is_none());
+    registry::delete_tree(Root::CurrentUser, &root).unwrap();
+}
+
+#[test]
+fn access_denied_reads_as_an_elevation_problem() {
+    let error = RegistryError {
+        path: r"HKLM\SYSTEM\CurrentControlSet\Services\USBSTOR\Start".to_string(),
+        code: 5,
+    };
+    assert!(error.is_access_denied());
+    assert_eq!(
+        UsbShieldError::policy("block USB storage ports", error.into()),
+        UsbShieldError::ElevationRequired {
+            action: "block USB storage ports".to_string()
+        }
+    );
+}
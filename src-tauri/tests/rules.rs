@@ -0,0 +1,529 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use std::{thread, time::Duration};
+
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use tauri::async_runtime::block_on;
+use uport_shield_helper::protocol::Failure;
+
+use common::{device, enabled, machine, reload, DESK, FLASH_DRIVE, KEYBOARD};
+use uport_shield_lib::usb::{
+    backend,
+    category::DeviceCategory,
+    class_policy::{self, ClassAction},
+    commands,
+    device_interfaces,
+    dry_run::{self, PlannedStep},
+    error::UsbShieldError,
+    gpo_policy::{self, StorageClass},
+    hotplug,
+    hub_policy::{self, HubPolicy, HubRule},
+    network_adapters,
+    port_locks,
+    profiles::{self, Profile},
+    protection_schedule::{self, ScheduleRule},
+    reblock,
+    remote::{self, RemoteUsbPolicy},
+    simulation::{self, FailureSpec},
+    verification,
+    wireless::{self, WirelessAction, WirelessPolicy},
+};
+
+const HUB: &str = "USB\\VID_05E3&PID_0610\\5&1A2B3C4D&0&1";
+
+#[test]
+fn strict_profile_blocks_untrusted_devices_only() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x046D, 0xC31C).unwrap();
+    commands::app_state().add_trusted_device(0x05E3, 0x0610).unwrap();
+
+    profiles::request("test", Some(Profile::Strict));
+    assert_eq!(profiles::active(), Profile::Strict);
+    assert!(enabled(KEYBOARD));
+    assert!(enabled(HUB));
+    assert!(!enabled(FLASH_DRIVE));
+
+    profiles::request("test", None);
+    assert_eq!(profiles::active(), Profile::Standard);
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+    commands::app_state().remove_trusted_device(0x046D, 0xC31C, None).unwrap();
+    commands::app_state().remove_trusted_device(0x05E3, 0x0610, None).unwrap();
+}
+
+#[test]
+fn strictest_outstanding_request_wins() {
+    let _machine = machine(r#"{ "devices": [] }"#);
+
+    profiles::request("test-a", Some(Profile::Strict));
+    profiles::request("test-b", Some(Profile::Standard));
+    assert_eq!(profiles::active(), Profile::Strict);
+
+    profiles::request("test-a", None);
+    assert_eq!(profiles::active(), Profile::Standard);
+    profiles::request("test-b", None);
+}
+
+#[test]
+fn schedule_requests_its_profile_inside_each_window() {
+    let _machine = machine(r#"{ "devices": [] }"#);
+    let after_hours = ScheduleRule {
+        name: "after hours".to_string(),
+        days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        start: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        end: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        profile: Profile::Strict,
+    };
+    protection_schedule::set_schedule(vec![after_hours]).unwrap();
+    let at = |day: u32, hour: u32| NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+
+    // Tuesday evening, and on into Wednesday morning
+    let state = protection_schedule::evaluate_at(at(13, 20));
+    assert_eq!(state.rule.as_deref(), Some("after hours"));
+    assert_eq!(profiles::active(), Profile::Strict);
+    assert_eq!(protection_schedule::evaluate_at(at(14, 8)).profile, Some(Profile::Strict));
+
+    assert_eq!(protection_schedule::evaluate_at(at(14, 10)).rule, None);
+    assert_eq!(profiles::active(), Profile::Standard);
+    // Not on a Saturday evening
+    assert_eq!(protection_schedule::evaluate_at(at(17, 20)).rule, None);
+
+    // The schedule outlives a restart
+    protection_schedule::load().unwrap();
+    assert_eq!(protection_schedule::get_schedule().unwrap()[0].name, "after hours");
+
+    protection_schedule::set_schedule(Vec::new()).unwrap();
+    assert_eq!(profiles::active(), Profile::Standard);
+}
+
+#[test]
+fn class_block_leaves_other_classes_alone() {
+    let _machine = machine(DESK);
+
+    let results = block_on(commands::block_all_of_class(DeviceCategory::Storage)).unwrap().applied().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].error.is_none());
+    assert!(!enabled(FLASH_DRIVE));
+    assert!(enabled(KEYBOARD));
+
+    block_on(commands::unblock_all_of_class(DeviceCategory::Storage, None)).unwrap();
+    assert!(enabled(FLASH_DRIVE));
+}
+
+#[test]
+fn dry_run_reports_the_plan_and_changes_nothing() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x046D, 0xC31C).unwrap();
+    commands::app_state().add_trusted_device(0x05E3, 0x0610).unwrap();
+    let usbstor_start = backend::controller().usbstor_start();
+    dry_run::set_dry_run_mode(true, None).unwrap();
+
+    let plan = block_on(commands::block_all_untrusted()).unwrap().simulated().unwrap();
+    assert_eq!(plan.command, "block_all_untrusted");
+    assert!(
+        matches!(
+            plan.steps.as_slice(),
+            [PlannedStep::DisableDevice { instance_id, .. }] if instance_id == FLASH_DRIVE
+        ),
+        "{:?}",
+        plan.steps
+    );
+
+    let plan = block_on(commands::block_device(0x1234, 0x5678, None, None)).unwrap().simulated().unwrap();
+    assert!(plan.steps.is_empty());
+    assert_eq!(plan.unmatched, ["Device not found: VID_1234&PID_5678"]);
+
+    let plan = block_on(commands::block_all_usb_ports()).unwrap().simulated().unwrap();
+    assert_eq!(
+        plan.steps[0],
+        PlannedStep::SetRegistryValue {
+            key: r"HKLM\SYSTEM\CurrentControlSet\Services\USBSTOR".to_string(),
+            name: "Start".to_string(),
+            value: 4,
+        }
+    );
+    assert_eq!(plan.steps.last(), Some(&PlannedStep::RestartStorageService));
+
+    let plan = block_on(gpo_policy::set_gpo_storage_policy(StorageClass::Wpd, true, true, false))
+        .unwrap()
+        .simulated()
+        .unwrap();
+    assert!(plan
+        .steps
+        .iter()
+        .any(|step| matches!(step, PlannedStep::SetRegistryValue { name, value: 1, .. } if name == "Deny_Read")));
+    assert!(gpo_policy::get_gpo_storage_policies().unwrap().is_empty());
+
+    let refused = port_locks::block_port("PCIROOT(0)#PCI(1400)#USBROOT(0)#USB(1)".to_string()).unwrap_err();
+    assert!(refused.contains("cannot be simulated"), "{}", refused);
+    let refused = reblock::unblock_device_for(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), 60, None);
+    assert!(refused.unwrap_err().contains("cannot be simulated"));
+
+    dry_run::set_dry_run_mode(false, None).unwrap();
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(backend::controller().usbstor_start(), usbstor_start);
+    commands::app_state().remove_trusted_device(0x046D, 0xC31C, None).unwrap();
+    commands::app_state().remove_trusted_device(0x05E3, 0x0610, None).unwrap();
+}
+
+#[test]
+fn composite_functions_are_blocked_one_at_a_time() {
+    const PHONE: &str = "USB\\VID_22B8&PID_2E82\\ZY22C4B7QX";
+    // A phone with MTP, ADB and mass storage functions
+    let script = r#"{
+        "devices": [
+            {
+                "instance_id": "USB\\VID_22B8&PID_2E82\\ZY22C4B7QX",
+                "product": "moto g",
+                "ports": [3],
+                "interfaces": [
+                    { "class_code": 6, "sub_class_code": 1, "protocol_code": 1 },
+                    { "class_code": 255, "sub_class_code": 66, "protocol_code": 1 },
+                    { "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }
+                ],
+                "volumes": [{ "mount_point": "F:", "volume_serial": "5E6F-7A8B" }]
+            }
+        ]
+    }"#;
+    let _machine = machine(script);
+    hotplug::rescan();
+
+    let interfaces = device_interfaces::get_device_interfaces(PHONE.to_string()).unwrap();
+    assert_eq!(interfaces.len(), 3);
+    assert_eq!(interfaces[2].category, Some(DeviceCategory::Storage));
+    assert!(device_interfaces::get_device_interfaces(KEYBOARD.to_string()).is_err());
+
+    let storage = device_interfaces::block_device_interface(PHONE.to_string(), 2).unwrap();
+    assert!(storage.blocked);
+    assert!(enabled(PHONE));
+    assert_eq!(device(PHONE)["state"], "Connected");
+    assert!(backend::controller().volumes().unwrap().is_empty());
+    assert!(!device_interfaces::get_device_interfaces(PHONE.to_string()).unwrap()[0].blocked);
+    assert!(matches!(
+        device_interfaces::block_device_interface(PHONE.to_string(), 7),
+        Err(UsbShieldError::DeviceNotFound { .. })
+    ));
+
+    // Still off when it is plugged in again
+    commands::app_state().set_autoblock_mode(false, None).unwrap();
+    simulation::simulate_detach(PHONE.to_string()).unwrap();
+    hotplug::rescan();
+    reload(script);
+    hotplug::rescan();
+    assert!(enabled(PHONE));
+    assert!(device_interfaces::get_device_interfaces(PHONE.to_string()).unwrap()[2].blocked);
+    assert_eq!(device_interfaces::get_interface_rules().unwrap().len(), 1);
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+
+    device_interfaces::unblock_device_interface(PHONE.to_string(), 2, None).unwrap();
+    assert_eq!(backend::controller().volumes().unwrap().len(), 1);
+    assert!(device_interfaces::get_interface_rules().unwrap().is_empty());
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let _machine = machine(DESK);
+    simulation::simulate_failure(FailureSpec {
+        instance_id: FLASH_DRIVE.to_string(),
+        count: 2,
+        hang_ms: None,
+        message: None,
+    })
+    .unwrap();
+
+    let changes = block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string())))
+        .unwrap()
+        .applied()
+        .unwrap();
+    assert_eq!(changes[0].attempts, 3);
+    assert!(!enabled(FLASH_DRIVE));
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn hung_state_change_times_out_without_retry() {
+    let _machine = machine(DESK);
+    commands::set_device_operation_timeout(1_000).unwrap();
+    simulation::simulate_failure(FailureSpec {
+        instance_id: FLASH_DRIVE.to_string(),
+        count: 1,
+        hang_ms: Some(1_500),
+        message: None,
+    })
+    .unwrap();
+
+    let error = block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap_err();
+    assert!(matches!(error, UsbShieldError::DeviceTimeout { .. }), "{:?}", error);
+    assert!(error.to_string().contains("after 1 attempt"), "{}", error);
+
+    // Let the stalled call return before the next test touches the devnode
+    thread::sleep(Duration::from_millis(1_000));
+    commands::set_device_operation_timeout(15_000).unwrap();
+}
+
+#[test]
+fn usbip_policy_blocks_network_attached_devices() {
+    let _machine = machine(
+        r#"{
+        "devices": [
+            {
+                "instance_id": "USB\\VID_0781&PID_5581\\4C530001230918115462",
+                "ports": [1],
+                "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }]
+            },
+            {
+                "instance_id": "USB\\VID_090C&PID_1000\\0376622070004893",
+                "ports": [1],
+                "controller_instance_id": "ROOT\\USBIP_WIN2\\UDE",
+                "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }]
+            }
+        ]
+    }"#,
+    );
+    assert_eq!(common::device("USB\\VID_090C&PID_1000\\0376622070004893")["attachment"], "UsbIp");
+    assert_eq!(common::device(FLASH_DRIVE)["attachment"], "Physical");
+
+    remote::set_remote_usb_policy(RemoteUsbPolicy { block_usbip: true, block_redirected: false }).unwrap();
+    assert!(!enabled("USB\\VID_090C&PID_1000\\0376622070004893"));
+    assert!(enabled(FLASH_DRIVE));
+
+    remote::set_remote_usb_policy(RemoteUsbPolicy { block_usbip: false, block_redirected: false }).unwrap();
+}
+
+#[test]
+fn modems_and_wifi_adapters_follow_their_own_policy() {
+    let _machine = machine(DESK);
+    // Otherwise autoblock disables both before the wireless policy runs
+    commands::app_state().set_autoblock_mode(false, None).unwrap();
+    hotplug::rescan();
+    wireless::set_wireless_policy(WirelessPolicy {
+        cellular_modem: WirelessAction::Block,
+        wifi_adapter: WirelessAction::Alert,
+    })
+    .unwrap();
+
+    const MODEM: &str = "USB\\VID_2C7C&PID_0125\\8C2A4F10";
+    const WIFI: &str = "USB\\VID_148F&PID_5370\\1.0";
+    for script in [
+        r#"{ "instance_id": "USB\\VID_2C7C&PID_0125\\8C2A4F10", "ports": [2],
+             "interfaces": [{ "class_code": 2, "sub_class_code": 14, "protocol_code": 0 }] }"#,
+        r#"{ "instance_id": "USB\\VID_148F&PID_5370\\1.0", "ports": [3],
+             "interfaces": [{ "class_code": 255, "sub_class_code": 255, "protocol_code": 255 }] }"#,
+    ] {
+        simulation::simulate_attach(serde_json::from_str(script).unwrap()).unwrap();
+    }
+    hotplug::rescan();
+
+    assert_eq!(common::device(MODEM)["category"], "CellularModem");
+    assert_eq!(common::device(WIFI)["category"], "WifiAdapter");
+    assert!(!enabled(MODEM));
+    assert!(enabled(WIFI));
+
+    wireless::set_wireless_policy(WirelessPolicy {
+        cellular_modem: WirelessAction::Alert,
+        wifi_adapter: WirelessAction::Alert,
+    })
+    .unwrap();
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+}
+
+#[test]
+fn usb_network_adapters_are_blocked_unless_part_of_an_allowed_dock() {
+    let _machine = machine(DESK);
+    commands::app_state().set_autoblock_mode(false, None).unwrap();
+    hotplug::rescan();
+    network_adapters::set_block_usb_network_adapters(true).unwrap();
+    network_adapters::set_network_adapter_dock_allowed("{7d3c1f2a-0000-4e5b-9a61-2f1d0c3b4a55}".to_string(), true)
+        .unwrap();
+
+    // The policy outlives a restart
+    network_adapters::load().unwrap();
+    assert!(network_adapters::get_network_adapter_policy().unwrap().block);
+
+    // An RNDIS implant, a vendor-class NIC known only by its Net-class
+    // driver, and the Ethernet of an approved dock
+    const TURTLE: &str = "USB\\VID_0B95&PID_772B\\000ECC8B2A4F";
+    const VENDOR_NIC: &str = "USB\\VID_0B95&PID_1790\\00249B1A2C3D";
+    const DOCK_NIC: &str = "USB\\VID_17EF&PID_A387\\301000001";
+    for script in [
+        r#"{ "instance_id": "USB\\VID_0B95&PID_772B\\000ECC8B2A4F", "ports": [2],
+             "interfaces": [{ "class_code": 224, "sub_class_code": 1, "protocol_code": 3 }] }"#,
+        r#"{ "instance_id": "USB\\VID_0B95&PID_1790\\00249B1A2C3D", "ports": [4],
+             "interfaces": [{ "class_code": 255, "sub_class_code": 255, "protocol_code": 0 }],
+             "driver": { "service": "AX88179", "signature": "signed",
+                         "class_guid": "{4D36E972-E325-11CE-BFC1-08002BE10318}" } }"#,
+        r#"{ "instance_id": "USB\\VID_17EF&PID_A387\\301000001", "ports": [3],
+             "container_id": "{7D3C1F2A-0000-4E5B-9A61-2F1D0C3B4A55}",
+             "interfaces": [{ "class_code": 2, "sub_class_code": 13, "protocol_code": 0 }] }"#,
+    ] {
+        simulation::simulate_attach(serde_json::from_str(script).unwrap()).unwrap();
+    }
+    hotplug::rescan();
+
+    assert!(!enabled(TURTLE));
+    assert_eq!(common::device(TURTLE)["block_reason"], "NetworkAdapterPolicy");
+    assert!(!enabled(VENDOR_NIC));
+    assert!(enabled(DOCK_NIC));
+
+    network_adapters::set_network_adapter_dock_allowed("{7d3c1f2a-0000-4e5b-9a61-2f1d0c3b4a55}".to_string(), false)
+        .unwrap();
+    network_adapters::set_block_usb_network_adapters(false).unwrap();
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+}
+
+#[test]
+fn verification_reblocks_devices_enabled_behind_our_back() {
+    let _machine = machine(DESK);
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+    assert!(verification::verify().passed);
+
+    // Re-enabled outside the app, e.g. from Device Manager
+    backend::controller().set_device_state(FLASH_DRIVE, true).unwrap();
+    let report = verification::verify();
+    assert!(!report.passed);
+    assert_eq!(report.drift.len(), 1);
+    assert_eq!(report.drift[0].instance_id, FLASH_DRIVE);
+    assert!(!report.signature.is_empty());
+    assert!(!enabled(FLASH_DRIVE));
+
+    assert!(verification::verify().passed);
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn restarting_the_usb_service_reconciles_what_comes_back() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+    block_on(commands::block_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()))).unwrap();
+
+    // Windows re-enables it while re-detecting
+    backend::controller().set_device_state(FLASH_DRIVE, true).unwrap();
+    block_on(commands::restart_usb_service()).unwrap();
+    assert!(!enabled(FLASH_DRIVE));
+
+    let state = hotplug::redetect("service_restart").unwrap();
+    assert!(state.drift.is_empty());
+    assert!(state.missing.is_empty());
+    assert!(!state.timed_out);
+    assert!(state.devices.iter().any(|d| d.instance_id() == Some(KEYBOARD)));
+
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn class_policies_block_storage_and_let_keyboards_through() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    class_policy::set_class_policy(0x08, Some(ClassAction::Block), None).unwrap();
+    assert!(!enabled(FLASH_DRIVE));
+    assert!(enabled(KEYBOARD));
+
+    // An allowed HID keyboard is not autoblocked when it arrives
+    class_policy::set_class_policy(0x03, Some(ClassAction::Allow), None).unwrap();
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(enabled(KEYBOARD));
+    assert_eq!(class_policy::get_class_policies().unwrap().len(), 2);
+
+    class_policy::set_class_policy(0x08, None, None).unwrap();
+    class_policy::set_class_policy(0x03, None, None).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn unapproved_hubs_are_blocked_with_everything_behind_them() {
+    let _machine = machine(DESK);
+    hotplug::rescan();
+
+    let approved = HubRule {
+        vendor_id: 0x05E3,
+        product_id: Some(0x0610),
+        container_id: None,
+    };
+    let blocked = hub_policy::set_hub_policy(
+        HubPolicy {
+            enabled: true,
+            allowed: vec![approved],
+        },
+        None,
+    )
+    .unwrap();
+    assert!(blocked.is_empty());
+    assert!(enabled(HUB) && enabled(KEYBOARD) && enabled(FLASH_DRIVE));
+
+    let blocked = hub_policy::set_hub_policy(
+        HubPolicy {
+            enabled: true,
+            allowed: Vec::new(),
+        },
+        None,
+    )
+    .unwrap();
+    assert_eq!(blocked.len(), 1);
+    assert_eq!(blocked[0].downstream.len(), 2);
+    assert!(!enabled(HUB) && !enabled(KEYBOARD) && !enabled(FLASH_DRIVE));
+
+    hub_policy::set_hub_policy(
+        HubPolicy {
+            enabled: false,
+            allowed: Vec::new(),
+        },
+        None,
+    )
+    .unwrap();
+    commands::enable_device(0x05E3, 0x0610, None, Some(HUB.to_string()), None).unwrap();
+    commands::enable_device(0x046D, 0xC31C, None, Some(KEYBOARD.to_string()), None).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn locked_ports_block_whatever_is_plugged_in() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x046D, 0xC31C).unwrap();
+    hotplug::rescan();
+
+    let location = device(KEYBOARD)["location_path"].as_str().unwrap().to_string();
+    let lock = port_locks::block_port(location.clone()).unwrap();
+    // The desk hub cannot switch its ports, so the keyboard is disabled instead
+    assert!(!lock.power_cut);
+    assert!(!enabled(KEYBOARD));
+    assert!(enabled(FLASH_DRIVE));
+    assert_eq!(device(KEYBOARD)["block_reason"], "PortLock");
+
+    // Trusted or not, a fresh arrival on the port is blocked
+    simulation::simulate_detach(KEYBOARD.to_string()).unwrap();
+    hotplug::rescan();
+    reload(DESK);
+    hotplug::rescan();
+    assert!(!enabled(KEYBOARD));
+
+    port_locks::unblock_port(location, None).unwrap();
+    assert!(enabled(KEYBOARD));
+    assert!(port_locks::get_port_locks().unwrap().is_empty());
+    commands::app_state().remove_trusted_device(0x046D, 0xC31C, None).unwrap();
+}
+
+#[test]
+fn failures_carry_a_code_the_frontend_can_branch_on() {
+    let _machine = machine(DESK);
+
+    let error = block_on(commands::block_device(0x1234, 0x5678, None, None)).unwrap_err();
+    assert_eq!(
+        error,
+        UsbShieldError::DeviceNotFound {
+            device: "VID_1234&PID_5678".to_string()
+        }
+    );
+    let wire = serde_json::to_value(&error).unwrap();
+    assert_eq!(wire["kind"], "device_not_found");
+
+    // What the helper reports over the pipe keeps its Win32 code
+    let error = UsbShieldError::device(FLASH_DRIVE, Failure::win32("Failed to call class installer", 13));
+    assert!(matches!(error, UsbShieldError::SetupApiError { code: 13, .. }), "{:?}", error);
+    assert_eq!(error.to_string(), format!("{}: Failed to call class installer (error 13)", FLASH_DRIVE));
+}
@@ -0,0 +1,306 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use common::{device, machine, reload, FLASH_DRIVE};
+use tauri::async_runtime::block_on;
+use uport_shield_helper::protocol::RemovableStoragePolicy;
+use uport_shield_lib::usb::{
+    backend, eject,
+    exfiltration::{self, ExfiltrationPolicy},
+    forensics,
+    gpo_policy::{self, StorageClass},
+    quota::{self, DeviceQuota, WriteQuotaPolicy},
+    simulation, storage_readonly,
+    tamper::{self, TamperResponse},
+    transfers,
+};
+
+const STICK: &str = r#"{
+    "devices": [
+        {
+            "instance_id": "USB\\VID_0781&PID_5581\\4C530001230918115462",
+            "ports": [2],
+            "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }],
+            "volumes": [{ "mount_point": "E:", "volume_serial": "1A2B-3C4D" }]
+        }
+    ]
+}"#;
+
+#[test]
+fn counts_session_traffic_across_remounts() {
+    let _machine = machine(STICK);
+
+    // Traffic before the first sample predates the session
+    simulation::simulate_transfer("E:".to_string(), 1_000, 0).unwrap();
+    transfers::sample();
+
+    simulation::simulate_transfer("E:".to_string(), 0, 4_096).unwrap();
+    let stats = transfers::sample();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].totals.bytes_written, 4_096);
+    assert_eq!(stats[0].totals.bytes_read, 0);
+
+    // Remount resets the volume counters; the session keeps counting
+    reload(STICK);
+    simulation::simulate_transfer("E:".to_string(), 0, 1_024).unwrap();
+    transfers::sample();
+    assert_eq!(device(FLASH_DRIVE)["transfer"]["bytes_written"], 5_120);
+}
+
+#[test]
+fn write_burst_blocks_the_volume() {
+    // A different volume serial, so the session from the other test stays out of it
+    let _machine = machine(&STICK.replace("1A2B-3C4D", "5E6F-7081"));
+    exfiltration::set_exfiltration_policy(ExfiltrationPolicy {
+        enabled: true,
+        threshold_bytes: 1_000_000,
+        window_secs: 300,
+        write_block: true,
+    })
+    .unwrap();
+
+    transfers::sample();
+    simulation::simulate_transfer("E:".to_string(), 0, 600_000).unwrap();
+    transfers::sample();
+    assert!(exfiltration::get_write_blocked_volumes().unwrap().is_empty());
+
+    simulation::simulate_transfer("E:".to_string(), 0, 600_000).unwrap();
+    transfers::sample();
+    assert_eq!(exfiltration::get_write_blocked_volumes().unwrap(), vec!["E:".to_string()]);
+    assert!(simulation::simulate_transfer("E:".to_string(), 0, 1).is_err());
+    // Reads still go through
+    simulation::simulate_transfer("E:".to_string(), 512, 0).unwrap();
+
+    exfiltration::release_write_block("E:".to_string(), None).unwrap();
+    simulation::simulate_transfer("E:".to_string(), 0, 1).unwrap();
+    exfiltration::set_exfiltration_policy(ExfiltrationPolicy {
+        enabled: false,
+        threshold_bytes: 500 * 1024 * 1024,
+        window_secs: 300,
+        write_block: false,
+    })
+    .unwrap();
+}
+
+#[test]
+fn daily_quota_makes_the_device_read_only() {
+    // Another unit of the same model, which the quota is pinned to
+    let _machine = machine(
+        &STICK
+            .replace("4C530001230918115462", "4C530001230918115463")
+            .replace("1A2B-3C4D", "9A8B-7C6D"),
+    );
+    let policy = |bytes| WriteQuotaPolicy {
+        enabled: true,
+        global_bytes: None,
+        per_device: vec![DeviceQuota {
+            vendor_id: 0x0781,
+            product_id: 0x5581,
+            serial: Some("4C530001230918115463".to_string()),
+            bytes,
+        }],
+    };
+    quota::set_write_quota_policy(policy(10_000)).unwrap();
+
+    transfers::sample();
+    simulation::simulate_transfer("E:".to_string(), 0, 6_000).unwrap();
+    transfers::sample();
+    assert!(!quota::blocks("E:"));
+
+    simulation::simulate_transfer("E:".to_string(), 0, 6_000).unwrap();
+    transfers::sample();
+    assert!(quota::blocks("E:"));
+    assert!(simulation::simulate_transfer("E:".to_string(), 0, 1).is_err());
+    let usage = quota::get_write_quota_usage().unwrap();
+    let entry = usage
+        .devices
+        .iter()
+        .find(|d| d.device_instance_id.ends_with("4C530001230918115463"))
+        .unwrap();
+    assert_eq!(entry.bytes_written, 12_000);
+    assert!(entry.exceeded);
+
+    // Raising the quota lifts the block on the next sample
+    quota::set_write_quota_policy(policy(50_000)).unwrap();
+    transfers::sample();
+    assert!(!quota::blocks("E:"));
+    simulation::simulate_transfer("E:".to_string(), 0, 1).unwrap();
+
+    quota::set_write_quota_policy(WriteQuotaPolicy {
+        enabled: false,
+        global_bytes: None,
+        per_device: Vec::new(),
+    })
+    .unwrap();
+    transfers::sample();
+}
+
+#[test]
+fn forensic_mode_write_blocks_and_hashes_new_storage() {
+    let _machine = machine(r#"{ "devices": [] }"#);
+    let evidence_dir = std::env::temp_dir().join(format!("usb-shield-evidence-{}", std::process::id()));
+    std::fs::create_dir_all(&evidence_dir).unwrap();
+    std::fs::write(evidence_dir.join("notes.txt"), b"abc").unwrap();
+    let mount_point = evidence_dir.display().to_string();
+
+    forensics::start_forensic_mode(Some("responder".to_string()), Some("IR-42".to_string())).unwrap();
+    // Before anything is plugged in
+    assert!(simulation::get_simulation_state().unwrap().storage_write_protect);
+    let stick: simulation::SimDevice = serde_json::from_value(serde_json::json!({
+        "instance_id": FLASH_DRIVE,
+        "ports": [2],
+        "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }],
+        "volumes": [{ "mount_point": mount_point, "volume_serial": "1A2B-3C4D" }]
+    }))
+    .unwrap();
+    simulation::simulate_attach(stick).unwrap();
+
+    let evidence = forensics::scan();
+    assert_eq!(evidence.len(), 1);
+    assert_eq!(evidence[0].serial.as_deref(), Some("4C530001230918115462"));
+    assert!(evidence[0].write_blocked);
+    assert!(simulation::simulate_transfer(mount_point.clone(), 0, 1).is_err());
+    // Already recorded
+    assert!(forensics::scan().is_empty());
+
+    let reads = block_on(forensics::hash_evidence(mount_point.clone())).unwrap();
+    assert_eq!(reads.files.len(), 1);
+    assert!(reads.errors.is_empty());
+    // SHA-256 of "abc"
+    assert_eq!(reads.files[0].sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert!(block_on(forensics::hash_evidence(std::env::temp_dir().display().to_string())).is_err());
+
+    let case_dir = std::env::temp_dir().join(format!("usb-shield-case-{}", std::process::id()));
+    let acquired = block_on(forensics::acquire_evidence(
+        mount_point.clone(),
+        case_dir.display().to_string(),
+    ))
+    .unwrap();
+    assert_eq!(acquired.files.len(), 1);
+    assert_eq!(std::fs::read(case_dir.join("notes.txt")).unwrap(), b"abc");
+    assert!(block_on(forensics::acquire_evidence(mount_point.clone(), mount_point.clone())).is_err());
+
+    let export = forensics::stop_forensic_mode(None).unwrap();
+    assert_eq!(export.report.session.operator, "responder");
+    assert_eq!(export.report.files.len(), 2);
+    assert!(!simulation::get_simulation_state().unwrap().storage_write_protect);
+    assert!(!export.report.signature.is_empty());
+    assert!(std::path::Path::new(&export.path).exists());
+    simulation::simulate_transfer(mount_point, 0, 1).unwrap();
+}
+
+#[test]
+fn read_only_mode_keeps_storage_mounted_but_unwritable() {
+    let _machine = machine(&STICK.replace("1A2B-3C4D", "9A8B-7C6D"));
+
+    storage_readonly::set_storage_readonly(true, None).unwrap();
+    assert!(simulation::get_simulation_state().unwrap().storage_write_protect);
+    assert!(simulation::simulate_transfer("E:".to_string(), 0, 1).is_err());
+    simulation::simulate_transfer("E:".to_string(), 512, 0).unwrap();
+    let stick = device(FLASH_DRIVE);
+    assert_eq!(stick["read_only"], true);
+    assert_eq!(stick["state"], "Connected");
+
+    storage_readonly::set_storage_readonly(false, None).unwrap();
+    assert!(!simulation::get_simulation_state().unwrap().storage_write_protect);
+    simulation::simulate_transfer("E:".to_string(), 0, 1).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["read_only"], false);
+}
+
+#[test]
+fn read_only_mode_is_restored_from_the_registry_after_a_restart() {
+    let _machine = machine(STICK);
+
+    // Left set by the previous run; the new process starts with it off
+    backend::controller().set_storage_write_protect(true).unwrap();
+    assert!(!storage_readonly::get_storage_readonly().unwrap());
+    storage_readonly::load().unwrap();
+    assert!(storage_readonly::get_storage_readonly().unwrap());
+    assert!(storage_readonly::holds("E:"));
+    assert!(simulation::simulate_transfer("E:".to_string(), 0, 1).is_err());
+
+    storage_readonly::set_storage_readonly(false, None).unwrap();
+    assert!(!simulation::get_simulation_state().unwrap().storage_write_protect);
+    assert_eq!(device(FLASH_DRIVE)["read_only"], false);
+}
+
+#[test]
+fn gpo_storage_policy_is_written_per_class_and_reported() {
+    let _machine = machine(STICK);
+    assert!(gpo_policy::get_gpo_storage_policies().unwrap().is_empty());
+
+    block_on(gpo_policy::set_gpo_storage_policy(StorageClass::RemovableDisks, false, true, true)).unwrap();
+    let policies = block_on(gpo_policy::set_gpo_storage_policy(StorageClass::Wpd, true, true, false))
+        .unwrap()
+        .applied()
+        .unwrap();
+    // Portable devices span two classes
+    assert_eq!(policies.len(), 3);
+    let disks = policies
+        .iter()
+        .find(|p| p.class == Some(StorageClass::RemovableDisks))
+        .unwrap();
+    assert!(!disks.deny_read && disks.deny_write && disks.deny_execute);
+    assert!(block_on(gpo_policy::set_gpo_storage_policy(StorageClass::Wpd, false, false, true)).is_err());
+
+    // Nothing denied removes the class from the report
+    let policies = block_on(gpo_policy::set_gpo_storage_policy(StorageClass::Wpd, false, false, false))
+        .unwrap()
+        .applied()
+        .unwrap();
+    assert_eq!(policies.len(), 1);
+}
+
+#[test]
+fn tampered_storage_policy_is_restored_or_reported() {
+    let _machine = machine(STICK);
+    block_on(gpo_policy::set_gpo_storage_policy(StorageClass::RemovableDisks, false, true, false)).unwrap();
+    assert!(tamper::check().is_empty());
+    let cleared = RemovableStoragePolicy {
+        class_guid: "{53f5630d-b6bf-11d0-94f2-00a0c91efb8b}".to_string(),
+        deny_read: false,
+        deny_write: false,
+        deny_execute: false,
+    };
+
+    // Another tool deletes the policy key
+    backend::controller().set_removable_storage_policy(&cleared).unwrap();
+    let found = tamper::check();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].expected, "deny write");
+    assert_eq!(found[0].found, "nothing denied");
+    assert!(found[0].restored);
+    assert!(gpo_policy::get_gpo_storage_policies().unwrap()[0].deny_write);
+
+    tamper::set_tamper_response(TamperResponse::Alert).unwrap();
+    // Both the guarded policy and the response mode survive a restart
+    tamper::load().unwrap();
+    assert_eq!(tamper::get_tamper_response().unwrap(), TamperResponse::Alert);
+    backend::controller().set_removable_storage_policy(&cleared).unwrap();
+    assert!(!tamper::check()[0].restored);
+    assert!(gpo_policy::get_gpo_storage_policies().unwrap().is_empty());
+    assert!(tamper::get_last_tamper().unwrap().unwrap().setting.contains("53f5630d"));
+
+    tamper::set_tamper_response(TamperResponse::Restore).unwrap();
+    block_on(gpo_policy::set_gpo_storage_policy(StorageClass::RemovableDisks, false, false, false)).unwrap();
+    assert!(tamper::check().is_empty());
+}
+
+#[test]
+fn ejecting_removes_the_device_and_its_volume() {
+    let _machine = machine(STICK);
+    assert_eq!(backend::controller().volumes().unwrap().len(), 1);
+
+    block_on(eject::eject_device(FLASH_DRIVE.to_string(), None)).unwrap();
+    assert!(backend::controller().volumes().unwrap().is_empty());
+    assert!(simulation::get_simulation_state()
+        .unwrap()
+        .devices
+        .iter()
+        .all(|d| !d.instance_id.eq_ignore_ascii_case(FLASH_DRIVE)));
+
+    // Gone, so a second eject has nothing to act on
+    let error = block_on(eject::eject_device(FLASH_DRIVE.to_string(), Some(true))).unwrap_err();
+    assert!(error.to_string().contains("not found"), "{}", error);
+}
@@ -0,0 +1,176 @@
+#![cfg(feature = "test-harness")]
+
+mod common;
+
+use common::{device, enabled, machine, DESK, FLASH_DRIVE};
+use tauri::async_runtime::block_on;
+use uport_shield_lib::usb::{
+    admin_pin, category::DeviceCategory, commands, device_labels, hotplug, trust_rules, trust_share,
+};
+
+fn stick(volume_serial: &str) -> String {
+    format!(
+        r#"{{
+        "devices": [
+            {{
+                "instance_id": "USB\\VID_0781&PID_5581\\4C530001230918115462",
+                "ports": [2],
+                "interfaces": [{{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }}],
+                "volumes": [{{ "mount_point": "E:", "volume_serial": "{}", "label": "BACKUP" }}]
+            }}
+        ]
+    }}"#,
+        volume_serial
+    )
+}
+
+#[test]
+fn reformatted_stick_needs_reapproval() {
+    let _machine = machine(&stick("1A2B-3C4D"));
+
+    let binding = trust_rules::bind_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+    assert_eq!(binding.serial.as_deref(), Some("4C530001230918115462"));
+    assert_eq!(binding.volume_serials, vec!["1A2B-3C4D".to_string()]);
+    assert_eq!(device(FLASH_DRIVE)["trusted"], true);
+    assert_eq!(device(FLASH_DRIVE)["volumes"][0]["device_instance_id"], FLASH_DRIVE);
+
+    // Same stick, new filesystem
+    common::reload(&stick("5E6F-7081"));
+    assert_eq!(device(FLASH_DRIVE)["trusted"], false);
+    assert_eq!(device(FLASH_DRIVE)["approval_required"], true);
+
+    trust_rules::bind_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["trusted"], true);
+    assert_eq!(device(FLASH_DRIVE)["approval_required"], false);
+
+    trust_rules::remove_trust_binding(0x0781, 0x5581, Some("4C530001230918115462".to_string())).unwrap();
+    uport_shield_lib::usb::commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+}
+
+#[test]
+fn a_volume_that_mounts_mismatched_blocks_the_stick() {
+    let _machine = machine(&stick("1A2B-3C4D"));
+    trust_rules::bind_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+    hotplug::rescan();
+
+    // The stick stays put; only its volume changes underneath it
+    common::reload(&stick("5E6F-7081"));
+    hotplug::rescan();
+    assert!(!enabled(FLASH_DRIVE));
+
+    trust_rules::remove_trust_binding(0x0781, 0x5581, Some("4C530001230918115462".to_string())).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+    commands::enable_device(0x0781, 0x5581, None, Some(FLASH_DRIVE.to_string()), None).unwrap();
+}
+
+#[test]
+fn provisioned_labels_are_trusted_without_enrollment() {
+    let _machine = machine(
+        r#"{
+        "devices": [
+            {
+                "instance_id": "USB\\VID_0951&PID_1666\\E0D55EA574C8F4A1",
+                "ports": [1],
+                "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }],
+                "volumes": [{ "mount_point": "F:", "volume_serial": "0001-0002", "label": "corp-0042" }]
+            },
+            {
+                "instance_id": "USB\\VID_0951&PID_1666\\E0D55EA574C8F4A2",
+                "ports": [2],
+                "interfaces": [{ "class_code": 8, "sub_class_code": 6, "protocol_code": 80 }],
+                "volumes": [{ "mount_point": "G:", "volume_serial": "0003-0004", "label": "HOLIDAY" }]
+            },
+            {
+                "instance_id": "USB\\VID_1234&PID_5678\\BADUSB0001",
+                "ports": [3],
+                "interfaces": [
+                    { "class_code": 8, "sub_class_code": 6, "protocol_code": 80 },
+                    { "class_code": 3, "sub_class_code": 1, "protocol_code": 1 }
+                ],
+                "volumes": [{ "mount_point": "H:", "volume_serial": "0005-0006", "label": "CORP-0001" }]
+            }
+        ]
+    }"#,
+    );
+    trust_rules::set_volume_label_rules(vec!["CORP-*".to_string()]).unwrap();
+
+    assert_eq!(device("USB\\VID_0951&PID_1666\\E0D55EA574C8F4A1")["trusted"], true);
+    assert_eq!(device("USB\\VID_0951&PID_1666\\E0D55EA574C8F4A2")["trusted"], false);
+    // Storage plus a keyboard interface never qualifies
+    assert_eq!(device("USB\\VID_1234&PID_5678\\BADUSB0001")["trusted"], false);
+
+    assert!(trust_rules::set_volume_label_rules(vec!["*".to_string()]).is_err());
+    trust_rules::set_volume_label_rules(Vec::new()).unwrap();
+}
+
+#[test]
+fn shared_trust_imports_with_its_code_only() {
+    let _machine = machine(&stick("1A2B-3C4D"));
+
+    assert!(trust_share::export_trusted_device(FLASH_DRIVE.to_string()).is_err());
+    trust_rules::bind_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+    let share = trust_share::export_trusted_device(FLASH_DRIVE.to_string()).unwrap();
+
+    // The second machine: same stick, no binding yet
+    trust_rules::remove_trust_binding(0x0781, 0x5581, Some("4C530001230918115462".to_string())).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["trusted"], false);
+    assert!(trust_share::import_trusted_device(share.token.clone(), "AAAAA-AAAAA".to_string()).is_err());
+
+    let imported = trust_share::import_trusted_device(share.qr_payload, share.code.to_lowercase()).unwrap();
+    assert_eq!(imported.binding.volume_serials, ["1A2B-3C4D"]);
+    assert_eq!(device(FLASH_DRIVE)["trusted"], true);
+
+    trust_rules::remove_trust_binding(0x0781, 0x5581, Some("4C530001230918115462".to_string())).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, None).unwrap();
+}
+
+#[test]
+fn admin_pin_guards_trust_removal_and_autoblock() {
+    let _machine = machine(DESK);
+    commands::app_state().add_trusted_device(0x0781, 0x5581).unwrap();
+
+    admin_pin::set_admin_pin(Some("4821".to_string()), None).unwrap();
+    assert!(admin_pin::get_admin_pin_status().unwrap().configured);
+    assert!(commands::app_state().remove_trusted_device(0x0781, 0x5581, None).is_err());
+    assert!(commands::app_state().set_autoblock_mode(false, None).is_err());
+    assert!(block_on(commands::unblock_usb_port(None, Some("forged".to_string()))).is_err());
+    assert!(block_on(commands::unblock_all_trusted(None)).is_err());
+    assert!(block_on(commands::unblock_all_of_class(DeviceCategory::Storage, None)).is_err());
+    // Turning protection on never needs the PIN
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+
+    assert!(admin_pin::verify_admin_pin("0000".to_string()).is_err());
+    let session = admin_pin::verify_admin_pin("4821".to_string()).unwrap();
+    commands::app_state().set_autoblock_mode(false, Some(session.token.as_str())).unwrap();
+    commands::app_state().set_autoblock_mode(true, None).unwrap();
+    commands::app_state().remove_trusted_device(0x0781, 0x5581, Some(session.token.as_str())).unwrap();
+    assert!(!commands::app_state().trusted_devices().contains(&(0x0781, 0x5581)));
+
+    admin_pin::set_admin_pin(None, Some(session.token)).unwrap();
+    assert!(!admin_pin::get_admin_pin_status().unwrap().configured);
+}
+
+#[test]
+fn labels_follow_the_unit_and_fall_back_to_the_model() {
+    let _machine = machine(DESK);
+
+    device_labels::set_device_label(0x0781, 0x5581, None, Some("SanDisk Ultra".to_string())).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["label"], "SanDisk Ultra");
+
+    let serial = Some("4C530001230918115462".to_string());
+    device_labels::set_device_label(0x0781, 0x5581, serial.clone(), Some("Anna's backup drive".to_string()))
+        .unwrap();
+    device_labels::set_device_note(0x0781, 0x5581, serial.clone(), Some("encrypted".to_string())).unwrap();
+    let drive = device(FLASH_DRIVE);
+    assert_eq!(drive["label"], "Anna's backup drive");
+    assert_eq!(drive["note"], "encrypted");
+
+    // Clearing both drops the unit's entry; the model's name shows again
+    device_labels::set_device_label(0x0781, 0x5581, serial.clone(), None).unwrap();
+    device_labels::set_device_note(0x0781, 0x5581, serial, Some("  ".to_string())).unwrap();
+    assert_eq!(device(FLASH_DRIVE)["label"], "SanDisk Ultra");
+    device_labels::set_device_label(0x0781, 0x5581, None, None).unwrap();
+    assert!(device(FLASH_DRIVE)["label"].is_null());
+    assert!(device_labels::get_device_labels().unwrap().is_empty());
+}
@@ -193,6 +193,14 @@ export const App = () => {
             >
               Unblock Ports
             </button>
+            <button
+              onClick={() =>
+                invoke("toggle_status_widget").catch((err) => setError(String(err)))
+              }
+              className="px-6 py-3 rounded-lg bg-gray-700 hover:bg-gray-600 transition-all duration-300"
+            >
+              Status Widget
+            </button>
           </div>
           <label className="flex items-center cursor-pointer">
             <div className="relative">
@@ -59,6 +59,12 @@ export const DeviceCard = ({
             <span className="font-mono truncate">{device.serial_number}</span>
           </div>
         )}
+        {device.class_names.length > 0 && (
+          <div className="flex">
+            <span className="w-24 text-gray-500">Class:</span>
+            <span className="truncate">{device.class_names.join(", ")}</span>
+          </div>
+        )}
       </div>
 
       <button
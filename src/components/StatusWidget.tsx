@@ -0,0 +1,65 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { StatusSummary } from "../types";
+
+const profileColor = {
+  Standard: "bg-green-500",
+  Strict: "bg-yellow-500",
+  Lockdown: "bg-red-500",
+};
+
+export const StatusWidget = () => {
+  const [summary, setSummary] = useState<StatusSummary | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const refresh = async (): Promise<void> => {
+    try {
+      setSummary(await invoke<StatusSummary>("get_status_summary"));
+      setError(null);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  useEffect(() => {
+    refresh();
+    const unlistenPromise = listen("status://changed", refresh);
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  return (
+    <div
+      data-tauri-drag-region
+      className="h-screen p-3 bg-gray-900 text-gray-100 text-xs select-none"
+    >
+      {error && <p className="text-red-400 truncate">{error}</p>}
+      {summary && (
+        <>
+          <div data-tauri-drag-region className="flex items-center gap-2">
+            <span
+              className={`w-2 h-2 rounded-full ${profileColor[summary.profile]}`}
+            />
+            <span className="font-medium">{summary.profile}</span>
+            <span className="text-gray-400">
+              autoblock {summary.autoblock ? "on" : "off"}
+            </span>
+          </div>
+          <p className="mt-1 text-gray-300">
+            {summary.connected} connected · {summary.untrusted} untrusted ·{" "}
+            {summary.blocked} blocked
+          </p>
+          <p className="mt-1 text-gray-500 truncate">
+            {summary.last_event
+              ? `${summary.last_event.name} at ${new Date(
+                  summary.last_event.at
+                ).toLocaleTimeString()}`
+              : "No events yet"}
+          </p>
+        </>
+      )}
+    </div>
+  );
+};
@@ -1,9 +1,14 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
+import { getCurrentWindow } from "@tauri-apps/api/window";
 import { App } from "./App";
+import { StatusWidget } from "./components/StatusWidget";
+
+// The status widget window loads the same bundle; its label picks the view
+const isWidget = getCurrentWindow().label === "status-widget";
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
-    <App />
+    {isWidget ? <StatusWidget /> : <App />}
   </React.StrictMode>
 );
@@ -1,12 +1,750 @@
+export type DeviceCategory =
+  | "Storage"
+  | "Keyboard"
+  | "Mouse"
+  | "Audio"
+  | "Camera"
+  | "Network"
+  | "CellularModem"
+  | "WifiAdapter"
+  | "Hub"
+  | "SecurityKey"
+  | "Other";
+
+export type DeviceState = "Connected" | "Blocked" | "Quarantined" | "Disconnected" | "Unknown";
+
+export type BlockReason =
+  | "Manual"
+  | "Autoblock"
+  | "ClassPolicy"
+  | "HubPolicy"
+  | "WirelessPolicy"
+  | "Profile"
+  | "TimedReblock"
+  | "RemoteUsbPolicy"
+  | "VpnPolicy"
+  | "DeepLink"
+  | "PortLock"
+  | "KnownThreat"
+  | "HidQuarantine"
+  | "Quarantine"
+  | "KeyboardLockdown"
+  | "NetworkAdapterPolicy";
+
+export type DriverSignature = "signed" | "unsigned" | "unknown";
+
+export interface DriverInfo {
+  service: string | null;
+  provider: string | null;
+  version: string | null;
+  date: string | null;
+  inf_name: string | null;
+  signature: DriverSignature;
+  signer: string | null;
+  class_guid: string | null;
+}
+
+/** The driver of an &MI_xx interface or a function below it */
+export interface FunctionDriver {
+  instance_id: string;
+  driver: DriverInfo;
+}
+
+export interface Volume {
+  device_instance_id: string;
+  mount_point: string;
+  volume_serial: string;
+  label: string | null;
+  file_system: string | null;
+}
+
+export interface IoCounters {
+  bytes_read: number;
+  bytes_written: number;
+  reads: number;
+  writes: number;
+}
+
+export type Attachment = "Physical" | "UsbIp" | "Redirected";
+
+export interface InterfaceClass {
+  class_code: number;
+  sub_class_code: number;
+  protocol_code: number;
+}
+
+export interface DeviceInterface {
+  instance_id: string;
+  interface_number: number;
+  class: InterfaceClass | null;
+  category: DeviceCategory | null;
+  description: string | null;
+  blocked: boolean;
+}
+
+/** An interface kept blocked on one device whenever it is plugged in */
+export interface InterfaceRule {
+  instance_id: string;
+  interface_number: number;
+}
+
 export interface UsbDeviceInfo {
   vendor_id: number;
   product_id: number;
   manufacturer: string | null;
   product: string | null;
+  vendor_name: string | null;
+  product_name: string | null;
   serial_number: string | null;
   port_number: number | null;
-  connected: boolean;
+  port_chain: string | null;
+  location_path: string | null;
+  instance_id: string | null;
+  parent_instance_id: string | null;
+  container_id: string | null;
+  com_port: string | null;
+  category: DeviceCategory;
+  device_class: number;
+  interfaces: InterfaceClass[];
+  class_names: string[];
+  state: DeviceState;
+  block_reason: BlockReason | null;
+  attachment: Attachment;
+  redirection_client: string | null;
+  driver: DriverInfo | null;
+  function_drivers: FunctionDriver[];
+  /** Worst signature among driver and function_drivers */
+  driver_signature: DriverSignature | null;
+  volumes: Volume[];
+  transfer: IoCounters | null;
+  read_only: boolean;
   trusted: boolean;
+  approval_required: boolean;
+  threat_match: string | null;
+  label: string | null;
+  note: string | null;
+}
+
+export interface UsbIdsStatus {
+  updated: boolean;
+  version: string | null;
+  vendors: number;
+}
+
+export interface DeviceLabel {
+  vendor_id: number;
+  product_id: number;
+  serial: string | null;
+  label: string | null;
+  note: string | null;
 }
 
 export type TrustedDevice = [number, number]; // [vendor_id, product_id]
+
+export interface InventoryRecord {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  manufacturer: string | null;
+  product: string | null;
+  serial: string | null;
+  category: DeviceCategory;
+  trusted: boolean;
+  first_seen: string;
+  last_seen: string;
+  connection_count: number;
+  connected: boolean;
+  preblocked: boolean;
+}
+
+export type Profile = "Standard" | "Strict" | "Lockdown";
+
+export interface LastEvent {
+  name: string;
+  at: string;
+}
+
+export interface StatusSummary {
+  profile: Profile;
+  autoblock: boolean;
+  connected: number;
+  blocked: number;
+  quarantined: number;
+  blocked_by_reason: Partial<Record<BlockReason, number>>;
+  untrusted: number;
+  last_event: LastEvent | null;
+  degraded: boolean;
+}
+
+export type ProtectionLevel = "fully_protected" | "partially_protected" | "unprotected";
+
+export interface ProtectionMechanism {
+  name: string;
+  active: boolean;
+  detail: string | null;
+}
+
+export interface EffectiveProtection {
+  level: ProtectionLevel;
+  mechanisms: ProtectionMechanism[];
+  reasons: string[];
+}
+
+export type TamperResponse = "Restore" | "Alert";
+
+export interface TamperEvent {
+  setting: string;
+  expected: string;
+  found: string;
+  detected_at: string;
+  restored: boolean;
+  error: string | null;
+}
+
+export interface SupportBundle {
+  path: string;
+  anonymized: boolean;
+  files: string[];
+}
+
+export interface TrustShare {
+  token: string;
+  qr_payload: string;
+  code: string;
+  expires: string;
+}
+
+export type WirelessAction = "Allow" | "Alert" | "Block";
+
+export interface WirelessPolicy {
+  cellular_modem: WirelessAction;
+  wifi_adapter: WirelessAction;
+}
+
+export interface NetworkAdapterPolicy {
+  block: boolean;
+  allowed_docks: string[];
+}
+
+export interface VerificationSchedule {
+  enabled: boolean;
+  interval_minutes: number;
+}
+
+export interface VerificationReport {
+  timestamp: string;
+  passed: boolean;
+  profile: Profile;
+  checks: { name: string; passed: boolean; detail: string | null }[];
+  drift: {
+    instance_id: string;
+    vendor_id: number;
+    product_id: number;
+    expected: DeviceState;
+    actual: DeviceState;
+    error: string | null;
+  }[];
+  signature: string;
+}
+
+export interface ForensicSession {
+  id: string;
+  operator: string;
+  case_reference: string | null;
+  computer: string | null;
+  started: string;
+}
+
+export interface EvidenceItem {
+  device_instance_id: string;
+  vendor_id: number | null;
+  product_id: number | null;
+  serial: string | null;
+  description: string | null;
+  mount_point: string;
+  volume_serial: string;
+  label: string | null;
+  file_system: string | null;
+  attached_at: string;
+  write_blocked: boolean;
+  error: string | null;
+}
+
+export interface FileHash {
+  path: string;
+  size: number;
+  sha256: string;
+  hashed_at: string;
+  copied_to: string | null;
+}
+
+export interface FileError {
+  path: string;
+  error: string;
+  failed_at: string;
+}
+
+export interface EvidenceReads {
+  files: FileHash[];
+  errors: FileError[];
+}
+
+export interface ForensicStatus {
+  session: ForensicSession | null;
+  evidence: EvidenceItem[];
+  files_hashed: number;
+  files_failed: number;
+}
+
+export type ClassAction = "Allow" | "Block";
+
+export interface ClassPolicy {
+  class_code: number;
+  action: ClassAction;
+}
+
+export type AutoblockSensitivity = "Prompt" | "Block";
+
+export type Weekday = "Mon" | "Tue" | "Wed" | "Thu" | "Fri" | "Sat" | "Sun";
+
+export interface SensitivityBand {
+  name: string;
+  days?: Weekday[];
+  start: string; // "HH:MM:SS"
+  end: string;
+  sensitivity: AutoblockSensitivity;
+}
+
+export interface ScheduleRule {
+  name: string;
+  days?: Weekday[];
+  start: string; // "HH:MM:SS"
+  end: string;
+  profile: Profile;
+}
+
+export interface ScheduleState {
+  rule: string | null;
+  profile: Profile | null;
+}
+
+export interface OperationProgress {
+  id: number;
+  operation: string;
+  stage: string;
+  done: boolean;
+  error: string | null;
+}
+
+export interface AutoblockPrompt {
+  device: UsbDeviceInfo;
+  band: string;
+  expires_at: string;
+}
+
+export interface HubRule {
+  vendor_id: number;
+  product_id?: number | null;
+  container_id?: string | null;
+}
+
+export interface HubPolicy {
+  enabled: boolean;
+  allowed: HubRule[];
+}
+
+export interface BlockedHub {
+  instance_id: string;
+  downstream: string[];
+  errors: string[];
+}
+
+export type ChangeKind = "Added" | "Removed" | "Modified";
+
+export interface PolicyChange {
+  section: string;
+  path: string;
+  kind: ChangeKind;
+  before: unknown | null;
+  after: unknown | null;
+}
+
+export interface PolicyChangeset {
+  base: string;
+  target: string;
+  added: number;
+  removed: number;
+  modified: number;
+  changes: PolicyChange[];
+}
+
+export interface HealthCheck {
+  name: string;
+  ok: boolean;
+  detail: string | null;
+}
+
+export interface StartupHealth {
+  checked_at: string;
+  can_enforce: boolean;
+  checks: HealthCheck[];
+  degraded: string[];
+}
+
+export interface PrivilegeStatus {
+  elevated: boolean;
+  helper_running: boolean;
+  device_control: boolean;
+  storage_policy: boolean;
+  user_policy: boolean;
+  should_elevate: boolean;
+}
+
+export type StorageClass = "removable_disks" | "cd_dvd" | "floppy" | "tape" | "wpd";
+
+export interface GpoStoragePolicy {
+  class: StorageClass | null;
+  class_guid: string;
+  deny_read: boolean;
+  deny_write: boolean;
+  deny_execute: boolean;
+}
+
+export interface GuardPolicy {
+  autoblock: boolean;
+  trusted: [number, number][];
+  bindings: GuardBinding[];
+  allowed_classes: number[];
+}
+
+export interface GuardBinding {
+  vendor_id: number;
+  product_id: number;
+  serial: string | null;
+}
+
+export interface GuardBlock {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  blocked_at: number;
+}
+
+export interface GuardStatus {
+  policy: GuardPolicy;
+  active: boolean;
+  blocked: GuardBlock[];
+}
+
+export interface BackgroundProtection {
+  helper_running: boolean;
+  guard: GuardStatus | null;
+}
+
+export interface AdminSession {
+  token: string;
+  expires_at: string;
+}
+
+export interface AdminPinStatus {
+  configured: boolean;
+  set_at: string | null;
+  locked: boolean;
+}
+
+export interface GuestDevice {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  product: string | null;
+  admitted_at: string;
+}
+
+export interface GuestSession {
+  categories: DeviceCategory[];
+  max_devices: number;
+  started_at: string;
+  expires_at: string;
+  previous_profile: Profile | null;
+  devices: GuestDevice[];
+  reblock_id: number;
+}
+
+export interface ConfigFile {
+  version: number;
+  exported_at: string;
+  exported_from: string | null;
+  autoblock: boolean;
+  trusted_devices: [number, number][];
+  class_policies: ClassPolicy[];
+  port_locks: string[];
+  trust_bindings: TrustBinding[];
+}
+
+export interface TrustBinding {
+  vendor_id: number;
+  product_id: number;
+  serial: string | null;
+  volume_serials: string[];
+}
+
+export interface ConfigImport {
+  version: number;
+  exported_from: string | null;
+  changes: PolicyChange[];
+  errors: string[];
+}
+
+/** Typed command failure; branch on `kind`, fall back to the message. */
+export type UsbShieldError =
+  | { kind: "registry_access_denied"; key: string }
+  | { kind: "elevation_required"; action: string }
+  | { kind: "device_not_found"; device: string }
+  | { kind: "setup_api_error"; instance_id: string; code: number; message: string }
+  | { kind: "device_timeout"; instance_id: string; message: string }
+  | { kind: "admin_pin_required"; action: string }
+  | { kind: "consent_denied"; action: string; message: string }
+  | { kind: "other"; message: string };
+
+export interface ProtectionPause {
+  paused_at: string;
+  resumes_at: string;
+  admitted: string[];
+  reblock_id: number;
+}
+
+export interface PortLock {
+  location_path: string;
+  hub_instance_id: string | null;
+  port: number | null;
+  power_cut: boolean;
+  since: string;
+}
+
+export type QuarantineDecision = "Trust" | "Block" | "AllowOnce";
+
+export interface QuarantinedDevice {
+  device: UsbDeviceInfo;
+  quarantined_at: string;
+}
+
+export interface QuarantineResolution {
+  instance_id: string;
+  decision: QuarantineDecision;
+}
+
+export interface QuarantinedHid {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  product: string | null;
+  detected_at: string;
+  mean_interval_ms: number;
+  error: string | null;
+}
+
+export interface KnownKeyboard {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  serial: string | null;
+  product: string | null;
+  known_since: string;
+}
+
+export interface PendingKeyboard {
+  instance_id: string;
+  vendor_id: number;
+  product_id: number;
+  serial: string | null;
+  product: string | null;
+  detected_at: string;
+  error: string | null;
+}
+
+export interface KeyboardLockdown {
+  enabled: boolean;
+  known: KnownKeyboard[];
+  pending: PendingKeyboard[];
+}
+
+export interface ThreatSignature {
+  name: string;
+  vendor_id: number | null;
+  product_id: number | null;
+  manufacturer: string | null;
+  product: string | null;
+  notes: string | null;
+}
+
+export interface ThreatDatabase {
+  version: number;
+  signatures: ThreatSignature[];
+}
+
+export type Severity = "Info" | "Warning" | "Critical";
+
+export type NotificationChannel = "Toast" | "Email" | "Webhook" | "Syslog";
+
+export interface ProfileRoutes {
+  profile: Profile;
+  channels: Partial<Record<NotificationChannel, Severity>>;
+}
+
+export interface NotificationPreferences {
+  connected: boolean;
+  blocked: boolean;
+  quarantined: boolean;
+  threat_detected: boolean;
+}
+
+export interface Notification {
+  severity: Severity;
+  kind: string;
+  title: string;
+  body: string;
+  at: string;
+}
+
+export interface PolicySuggestion {
+  kind: "Trust" | "Revoke";
+  vendor_id: number;
+  product_id: number;
+  product: string | null;
+  reason: string;
+  connections: number;
+  flags: number;
+  last_seen: string | null;
+}
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+export interface LogEntry {
+  timestamp: string;
+  level: LogLevel;
+  target: string;
+  message: string;
+  fields?: unknown;
+}
+
+export type SiemTransport = "udp" | "tcp" | "tls" | "http";
+
+export type SiemFormat = "cef" | "json";
+
+export interface SiemSettings {
+  enabled: boolean;
+  transport: SiemTransport;
+  /** `host:port` for syslog, a URL for HTTP */
+  endpoint: string;
+  format: SiemFormat;
+}
+
+/**
+ * Payloads are signed with `secret`: `X-USB-Shield-Signature: sha256=<hex HMAC>`.
+ * The secret is never returned; omit it to keep the saved one.
+ */
+export interface Webhook {
+  url: string;
+  secret?: string | null;
+  has_secret: boolean;
+}
+
+export interface WebhookDelivery {
+  url: string;
+  ok: boolean;
+  status: number | null;
+  attempts: number;
+  error: string | null;
+}
+
+export type SmtpSecurity = "none" | "start_tls" | "tls";
+
+export interface EmailAlertSettings {
+  enabled: boolean;
+  host: string;
+  port: number;
+  security: SmtpSecurity;
+  username: string | null;
+  /** Write-only: omit to keep the saved password, `""` to remove it */
+  password?: string | null;
+  /** Read-only */
+  has_password?: boolean;
+  from: string;
+  /** The admin address alerts go to */
+  to: string;
+}
+
+export type PlannedStep =
+  | {
+      kind: "disable_device";
+      instance_id: string;
+      vendor_id: number;
+      product_id: number;
+      description: string | null;
+      reason: BlockReason;
+    }
+  | {
+      kind: "enable_device";
+      instance_id: string;
+      vendor_id: number;
+      product_id: number;
+      description: string | null;
+    }
+  | { kind: "schedule_reblock"; target: string; after_secs: number }
+  | { kind: "set_registry_value"; key: string; name: string; value: number }
+  | { kind: "delete_registry_value"; key: string; name: string }
+  | { kind: "delete_registry_key"; key: string }
+  | { kind: "set_volume_read_only"; mount_point: string; read_only: boolean }
+  | { kind: "restart_storage_service" };
+
+/** What a policy command would have done, from dry-run mode */
+export interface PolicyPlan {
+  command: string;
+  steps: PlannedStep[];
+  /** Requested devices that match nothing attached */
+  unmatched: string[];
+}
+
+/** Result of block/unblock commands: applied, or only planned in dry-run mode */
+export type Planned<T> = { applied: T } | { simulated: PolicyPlan };
+
+export interface DeviceSnapshot {
+  instance_id: string;
+  disabled: boolean;
+  reason: BlockReason | null;
+}
+
+/** Storage policy and device states from just before a policy change */
+export interface SystemSnapshot {
+  taken_at: string;
+  /** e.g. "block_all_usb_ports" */
+  taken_before: string;
+  usbstor_start: number | null;
+  deny_all: number | null;
+  removable_storage: {
+    class_guid: string;
+    deny_read: boolean;
+    deny_write: boolean;
+    deny_execute: boolean;
+  }[];
+  devices: DeviceSnapshot[];
+}
+
+export interface RestoreReport {
+  snapshot: SystemSnapshot;
+  devices_enabled: string[];
+  devices_disabled: string[];
+  errors: string[];
+}
+
+/** From `cleanup_all_enforcements`, after which the app exits */
+export interface CleanupReport {
+  devices_enabled: string[];
+  /** Disabled by us but not plugged in, so left as they are */
+  devices_absent: string[];
+  policies_cleared: string[];
+  /** The helper service and its ProgramData directory are gone */
+  helper_removed: boolean;
+  data_removed: boolean;
+  errors: string[];
+}